@@ -1,88 +1,428 @@
 extern crate alloc;
 use crate::http::alloc::string::ToString;
+use crate::tls::TlsTransport;
+use crate::transport::TcpTransport;
+use alloc::boxed::Box;
 use alloc::format;
 use alloc::string::String;
+use alloc::vec;
 use alloc::vec::Vec;
-use noli::net::lookup_host;
-use noli::net::SocketAddr;
-use noli::net::TcpStream;
+use core::cell::Cell;
+use core::cell::RefCell;
+use saba_core::cache::format_http_date;
+use saba_core::config::BrowserConfig;
+use saba_core::connection_pool::ConnectionPool;
+use saba_core::constants::KEEP_ALIVE_IDLE_TIMEOUT;
+use saba_core::constants::MAX_REDIRECTS;
+use saba_core::deadline::Deadline;
+use saba_core::deadline::Timeouts;
 use saba_core::error::Error;
+use saba_core::http::host_header_value;
+use saba_core::http::is_idempotent_method;
+use saba_core::http::is_redirect;
+use saba_core::http::keeps_connection_alive;
+use saba_core::http::redirect_authorization;
+use saba_core::http::redirect_method;
+use saba_core::http::Header;
+use saba_core::http::HttpRequest;
 use saba_core::http::HttpResponse;
+use saba_core::http::Proxy;
+use saba_core::transport::send_request;
+use saba_core::transport::HttpTransport;
+use saba_core::transport::TransportStream;
+use saba_core::url::Url;
 
-pub struct HttpClient {}
+pub struct HttpClient {
+    /// 使い終わった接続を再利用するためのプール
+    pool: RefCell<ConnectionPool<Box<dyn TransportStream>>>,
+    /// TLSで暗号化した接続のプール。同じホストとポートでも、暗号化していない接続とは混ぜない
+    secure_pool: RefCell<ConnectionPool<Box<dyn TransportStream>>>,
+    /// サーバーに接続する方法。標準ではnoliのTCPを使う
+    transport: Box<dyn HttpTransport>,
+    /// 送ったリクエストの数。プールの接続のタイムアウトに使う
+    clock: Cell<u64>,
+    /// 接続とレスポンスの受信を待つ回数の上限
+    timeouts: Cell<Timeouts>,
+    /// レスポンスを待っている間に呼ばれ、trueを返すとリクエストを取り消す
+    cancel_check: Option<Box<dyn Fn() -> bool>>,
+    /// 設定されている場合は、全てのリクエストをこのプロキシを通して送る
+    proxy: RefCell<Option<Proxy>>,
+    /// User-Agentなどの、全てのリクエストに付けるヘッダ
+    config: RefCell<BrowserConfig>,
+    /// https://のURLを取得するときに使うTLSの実装。設定されていない場合、https://のURLは取得できない
+    tls: Option<Box<dyn TlsTransport>>,
+}
 
 impl HttpClient {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            pool: RefCell::new(ConnectionPool::new(KEEP_ALIVE_IDLE_TIMEOUT)),
+            secure_pool: RefCell::new(ConnectionPool::new(KEEP_ALIVE_IDLE_TIMEOUT)),
+            transport: Box::new(TcpTransport),
+            clock: Cell::new(0),
+            timeouts: Cell::new(Timeouts::default()),
+            cancel_check: None,
+            proxy: RefCell::new(None),
+            config: RefCell::new(BrowserConfig::new()),
+            tls: None,
+        }
     }
 
-    pub fn get(&self, host: String, port: u16, path: String) -> Result<HttpResponse, Error> {
-        let ips = match lookup_host(&host) {
-            Ok(ips) => ips,
-            Err(e) => {
-                return Err(Error::Network(format!(
-                    "Failed to find IP addresses: {:#?}",
-                    e
-                )))
+    pub fn set_timeouts(&self, timeouts: Timeouts) {
+        self.timeouts.set(timeouts);
+    }
+
+    pub fn set_proxy(&self, proxy: Option<Proxy>) {
+        *self.proxy.borrow_mut() = proxy;
+    }
+
+    pub fn set_config(&self, config: BrowserConfig) {
+        *self.config.borrow_mut() = config;
+    }
+
+    /// テストなどで、noliのTCPの代わりに使う接続の方法を設定する
+    pub fn set_transport(&mut self, transport: Box<dyn HttpTransport>) {
+        self.transport = transport;
+        // 以前の方法で作った接続は再利用しない
+        *self.pool.borrow_mut() = ConnectionPool::new(KEEP_ALIVE_IDLE_TIMEOUT);
+        *self.secure_pool.borrow_mut() = ConnectionPool::new(KEEP_ALIVE_IDLE_TIMEOUT);
+    }
+
+    pub fn set_tls_transport(&mut self, tls: Box<dyn TlsTransport>) {
+        self.tls = Some(tls);
+    }
+
+    /// 応答しないサーバーを待っている間にも、ユーザーが読み込みを取り消せるようにする
+    pub fn set_cancel_check(&mut self, cancel_check: Box<dyn Fn() -> bool>) {
+        self.cancel_check = Some(cancel_check);
+    }
+
+    fn check_cancelled(&self) -> Result<(), Error> {
+        match &self.cancel_check {
+            Some(cancel_check) if cancel_check() => Err(Error::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    /// URLのリソースを取得する。リダイレクトされた場合は、転送先をたどって最終的なレスポンスを返す
+    pub fn fetch(&self, url: String) -> Result<HttpResponse, Error> {
+        self.fetch_with_headers(url, Vec::new())
+    }
+
+    /// `headers`は最初のリクエストにだけ付ける。キャッシュの再検証などに使う
+    pub fn fetch_with_headers(
+        &self,
+        url: String,
+        headers: Vec<Header>,
+    ) -> Result<HttpResponse, Error> {
+        self.fetch_internal("GET", url, headers, None)
+    }
+
+    /// `date`（1970年1月1日からの秒数）より後に変更されていた場合だけ、URLのリソースを取得する。
+    /// 変更されていなければ、ボディのない304 Not Modifiedのレスポンスを返すので、
+    /// 呼び出し側は前に取得したスタイルシートや画像をそのまま使える。
+    /// `date`には、前のレスポンスのLast-Modified（HttpCache::last_modified）を渡す
+    pub fn get_if_modified(&self, url: String, date: u64) -> Result<HttpResponse, Error> {
+        self.fetch_with_headers(
+            url,
+            vec![Header::new(
+                "If-Modified-Since".to_string(),
+                format_http_date(date),
+            )],
+        )
+    }
+
+    /// フォームの内容をPOSTし、リダイレクトをたどって最終的なレスポンスを返す
+    pub fn fetch_post(
+        &self,
+        url: String,
+        content_type: String,
+        body: String,
+    ) -> Result<HttpResponse, Error> {
+        self.fetch_internal("POST", url, Vec::new(), Some((content_type, body)))
+    }
+
+    /// https://fetch.spec.whatwg.org/#http-redirect-fetch
+    fn fetch_internal(
+        &self,
+        method: &str,
+        url: String,
+        headers: Vec<Header>,
+        body: Option<(String, String)>,
+    ) -> Result<HttpResponse, Error> {
+        let mut method = method;
+        let mut url = url;
+        let mut headers = headers;
+        let mut body = body;
+        let mut authorization = Url::new(url.clone()).parse()?.authorization();
+        // 同じリクエストを繰り返すリダイレクトのループを検出するため、たどったURLを覚えておく
+        let mut visited: Vec<(String, String)> = Vec::new();
+
+        for _ in 0..=MAX_REDIRECTS {
+            let parsed_url = Url::new(url.clone()).parse()?;
+            visited.push((method.to_string(), parsed_url.to_string()));
+
+            let request = match &body {
+                Some((content_type, body)) if method == "POST" => {
+                    let mut request = HttpRequest::post(&parsed_url.path());
+                    if let Some(authorization) = &authorization {
+                        request = request.header("Authorization", authorization);
+                    }
+                    request.body(content_type, body.clone())
+                }
+                _ => Self::get_request(&parsed_url.path(), authorization.clone(), headers),
+            };
+            // https://のURLは、TLSで暗号化した接続で送る
+            let response = self.send_to(
+                parsed_url.is_secure(),
+                parsed_url.host(),
+                parsed_url.port(),
+                request,
+            )?;
+
+            if !is_redirect(response.status_code()) {
+                return Ok(response);
+            }
+            // 転送先がわからない場合は、リダイレクトのレスポンスをそのまま返す
+            let location = match response.header_value("Location") {
+                Ok(location) => location,
+                Err(_) => return Ok(response),
+            };
+
+            method = redirect_method(response.status_code(), method);
+            if method != "POST" {
+                body = None;
             }
+            headers = Vec::new();
+            url = parsed_url.join(&location);
+
+            // メソッドが変わっても、同じオリジンへのリダイレクトでは認証情報を送り続ける
+            let next = Url::new(url.clone()).parse()?;
+            authorization = redirect_authorization(&parsed_url, &next, authorization);
+            let next = next.to_string();
+            if visited.contains(&(method.to_string(), next.clone())) {
+                return Err(Error::Network(format!("redirect loop detected: {}", next)));
+            }
+        }
+
+        Err(Error::Network(format!(
+            "too many redirects (more than {})",
+            MAX_REDIRECTS
+        )))
+    }
+
+    pub fn get(&self, host: String, port: u16, path: String) -> Result<HttpResponse, Error> {
+        self.get_with_authorization(host, port, path, None)
+    }
+
+    /// `authorization`が指定された場合、Authorizationヘッダを付けてリクエストを送る
+    pub fn get_with_authorization(
+        &self,
+        host: String,
+        port: u16,
+        path: String,
+        authorization: Option<String>,
+    ) -> Result<HttpResponse, Error> {
+        self.get_with_headers(host, port, path, authorization, Vec::new())
+    }
+
+    /// If-None-Matchのような追加のヘッダを付けてGETリクエストを送る
+    pub fn get_with_headers(
+        &self,
+        host: String,
+        port: u16,
+        path: String,
+        authorization: Option<String>,
+        headers: Vec<Header>,
+    ) -> Result<HttpResponse, Error> {
+        self.send(host, port, Self::get_request(&path, authorization, headers))
+    }
+
+    fn get_request(path: &str, authorization: Option<String>, headers: Vec<Header>) -> HttpRequest {
+        let mut request = HttpRequest::get(path);
+        if let Some(authorization) = authorization {
+            request = request.header("Authorization", &authorization);
+        }
+        for header in headers {
+            request = request.header(&header.name(), &header.value());
+        }
+        request
+    }
+
+    /// リクエストボディを持つPOSTリクエストを送る。フォームの送信などに使う
+    /// https://datatracker.ietf.org/doc/html/rfc9110#section-9.3.3
+    pub fn post(
+        &self,
+        host: String,
+        port: u16,
+        path: String,
+        content_type: String,
+        body: String,
+    ) -> Result<HttpResponse, Error> {
+        let request = HttpRequest::post(&path).body(&content_type, body);
+
+        self.send(host, port, request)
+    }
+
+    /// ボディを受信せずに、ステータスとヘッダだけを取得する。大きなリソースをダウンロードする前に、
+    /// Content-TypeやContent-Lengthを確かめるために使う
+    /// https://datatracker.ietf.org/doc/html/rfc9110#section-9.3.2
+    pub fn head(&self, host: String, port: u16, path: String) -> Result<HttpResponse, Error> {
+        self.send(host, port, HttpRequest::head(&path))
+    }
+
+    /// サーバーが対応しているメソッドを問い合わせる。結果はAllowヘッダに入る。
+    /// `path`に"*"を渡すと、サーバー全体について問い合わせる
+    /// https://datatracker.ietf.org/doc/html/rfc9110#section-9.3.7
+    pub fn options(&self, host: String, port: u16, path: String) -> Result<HttpResponse, Error> {
+        self.send(host, port, HttpRequest::options(&path))
+    }
+
+    /// クライアントが全てのリクエストに付けるヘッダ。User-Agentなどの設定のヘッダはその後に付ける。
+    /// リクエストに同じ名前のヘッダがある場合はそちらを使う
+    fn default_headers(&self, host: &str, port: u16) -> Vec<Header> {
+        let mut headers = vec![
+            Header::new("Host".to_string(), host_header_value(host, port)),
+            Header::new("Connection".to_string(), "keep-alive".to_string()),
+        ];
+        headers.extend(self.config.borrow().default_headers());
+        headers
+    }
+
+    /// リクエストを送り、レスポンスを受け取る。RefererやAccept-Languageのような任意のヘッダは、
+    /// HttpRequestに設定して渡す。
+    /// 同じホストとポートへの接続が残っていれば、それを再利用してリクエストを送る。
+    /// サーバーが既に接続を閉じていて、レスポンスを1バイトも受け取れなかった場合は、
+    /// 冪等なメソッドのリクエストだけを新しい接続で送り直す
+    pub fn send(
+        &self,
+        host: String,
+        port: u16,
+        request: HttpRequest,
+    ) -> Result<HttpResponse, Error> {
+        self.send_to(false, host, port, request)
+    }
+
+    /// `secure`がtrueの場合は、TLSで暗号化した接続でリクエストを送る
+    fn send_to(
+        &self,
+        secure: bool,
+        host: String,
+        port: u16,
+        request: HttpRequest,
+    ) -> Result<HttpResponse, Error> {
+        let request = request.with_default_headers(self.default_headers(&host, port));
+
+        // wasabi OSには時計がないので、リクエストの回数を時刻の代わりに使う
+        let now = self.clock.get() + 1;
+        self.clock.set(now);
+
+        if secure {
+            return self.send_secure(host, port, request, now);
+        }
+
+        // プロキシを使う場合は、プロキシに接続し、リクエストの対象をabsolute-formで送る。
+        // Hostヘッダは転送先のサーバーのまま
+        let (request, host, port) = match self.proxy.borrow().clone() {
+            Some(proxy) => (
+                request.absolute_form(&host, port),
+                proxy.host(),
+                proxy.port(),
+            ),
+            None => (request, host, port),
         };
 
-        if ips.len() < 1 {
-            return Err(Error::Network("Failed to find IP addresses".to_string()));
+        let pooled = self.pool.borrow_mut().take(&host, port, now);
+        if let Some(stream) = pooled {
+            match self.send_on(&self.pool, stream, &host, port, &request, now, false) {
+                // サーバーが既に閉じていた接続でレスポンスを受け取れなかった場合だけ、新しい接続で送り直す。
+                // POSTのように送り直すと処理が重複しうるリクエストは、エラーを返す
+                Err(Error::NoResponse(_)) if is_idempotent_method(&request.method()) => {}
+                result => return result,
+            }
         }
 
-        let socket_addr: SocketAddr = (ips[0], port).into();
+        let stream = self.connect(&host, port)?;
+        self.send_on(&self.pool, stream, &host, port, &request, now, true)
+    }
 
-        let mut stream = match TcpStream::connect(socket_addr) {
-            Ok(stream) => stream,
-            Err(_) => {
-                return Err(Error::Network(
-                    "Failed to connect to TCP stream".to_string(),
-                ))
+    /// TLSのハンドシェイクを済ませた接続でリクエストを送る。接続はsecure_poolで再利用する
+    fn send_secure(
+        &self,
+        host: String,
+        port: u16,
+        request: HttpRequest,
+        now: u64,
+    ) -> Result<HttpResponse, Error> {
+        let tls = match &self.tls {
+            Some(tls) => tls,
+            None => {
+                return Err(Error::Network(format!(
+                    "HTTPS is not supported: no TLS transport is configured for {}",
+                    host
+                )))
             }
         };
+        // プロキシを通すにはCONNECTでトンネルを作る必要があるが、まだ対応していない
+        if self.proxy.borrow().is_some() {
+            return Err(Error::Network(
+                "HTTPS through a proxy is not supported".to_string(),
+            ));
+        }
 
-        let mut request = String::from("GET /");
-        request.push_str(&path);
-        request.push_str(" HTTP/1.1\n");
-
-        // ヘッダの追加
-        request.push_str("Host: ");
-        request.push_str(&host);
-        request.push('\n');
-        request.push_str("Accept: text/html\n");
-        request.push_str("Connection: close\n");
-        request.push('\n');
-
-        let _bytes_written = match stream.write(request.as_bytes()) {
-            Ok(bytes) => bytes,
-            Err(_) => {
-                return Err(Error::Network(
-                    "Failed to send a request to TCP stream".to_string(),
-                ))
+        let pooled = self.secure_pool.borrow_mut().take(&host, port, now);
+        if let Some(stream) = pooled {
+            match self.send_on(&self.secure_pool, stream, &host, port, &request, now, false) {
+                Err(Error::NoResponse(_)) if is_idempotent_method(&request.method()) => {}
+                result => return result,
             }
-        };
+        }
+
+        let stream = tls.connect(&host, self.connect(&host, port)?)?;
+        self.send_on(&self.secure_pool, stream, &host, port, &request, now, true)
+    }
 
-        let mut received = Vec::new();
+    /// 接続できるまで、タイムアウトの回数だけ接続を試みる
+    fn connect(&self, host: &str, port: u16) -> Result<Box<dyn TransportStream>, Error> {
+        let mut deadline = Deadline::new(self.timeouts.get().connect);
         loop {
-            let mut buf = [0u8; 4096];
-            let bytes_read = match stream.read(&mut buf) {
-                Ok(bytes) => bytes,
-                Err(_) => {
-                    return Err(Error::Network(
-                        "Failed to receive a request from TCP stream".to_string(),
-                    ))
-                }
-            };
-            if bytes_read == 0 {
-                break;
+            match self.transport.connect(host, port) {
+                Ok(stream) => return Ok(stream),
+                // 名前解決に失敗したホストには、何度試みても接続できない
+                Err(e @ Error::Dns(_)) => return Err(e),
+                Err(_) => {}
             }
-            received.extend_from_slice(&buf[..bytes_read]);
+            self.check_cancelled()?;
+            deadline.tick(&format!("connecting to {}:{}", host, port))?;
         }
+    }
 
-        match core::str::from_utf8(&received) {
-            Ok(response) => HttpResponse::new(response.to_string()),
-            Err(e) => Err(Error::Network(format!("Invalid received response: {}", e))),
+    /// `fresh`は、新しく作った接続かどうか。使い終わった接続は`pool`に戻す
+    #[allow(clippy::too_many_arguments)]
+    fn send_on(
+        &self,
+        pool: &RefCell<ConnectionPool<Box<dyn TransportStream>>>,
+        mut stream: Box<dyn TransportStream>,
+        host: &str,
+        port: u16,
+        request: &HttpRequest,
+        now: u64,
+        fresh: bool,
+    ) -> Result<HttpResponse, Error> {
+        let (response, closed) = send_request(
+            &mut stream,
+            request,
+            fresh,
+            self.timeouts.get().read,
+            &format!("{}:{}", host, port),
+            &|| self.check_cancelled(),
+        )?;
+
+        // レスポンスの終わりがわかり、サーバーが接続を維持する場合は、次のリクエストのために残しておく
+        if !closed && keeps_connection_alive(&response) {
+            pool.borrow_mut().put(host.to_string(), port, stream, now);
         }
+
+        Ok(response)
     }
 }
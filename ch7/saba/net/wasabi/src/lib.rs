@@ -1,3 +1,5 @@
 #![no_std]
 
 pub mod http;
+pub mod tls;
+pub mod transport;
@@ -0,0 +1,17 @@
+extern crate alloc;
+use alloc::boxed::Box;
+use saba_core::error::Error;
+use saba_core::transport::TransportStream;
+
+/// TCPの接続の上でTLSのハンドシェイクを行い、暗号化した接続を作る。
+/// HttpClientはhttps://のURLを取得するときにこれを使う。
+/// `host`はSNIと、サーバー証明書のホスト名の確認に使う。
+/// 証明書を検証できなかった場合はError::Certificateを返し、ツールバーとエラーページで理由を知らせる
+/// https://datatracker.ietf.org/doc/html/rfc8446
+pub trait TlsTransport {
+    fn connect(
+        &self,
+        host: &str,
+        stream: Box<dyn TransportStream>,
+    ) -> Result<Box<dyn TransportStream>, Error>;
+}
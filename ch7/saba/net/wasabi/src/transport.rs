@@ -0,0 +1,55 @@
+extern crate alloc;
+use crate::transport::alloc::string::ToString;
+use alloc::boxed::Box;
+use alloc::format;
+use noli::net::lookup_host;
+use noli::net::SocketAddr;
+use noli::net::TcpStream;
+use saba_core::error::Error;
+use saba_core::transport::HttpTransport;
+use saba_core::transport::TransportStream;
+
+/// noliのTCPで接続するHttpTransport。HttpClientが標準で使う
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpTransport;
+
+impl HttpTransport for TcpTransport {
+    /// 1回だけ接続を試みる。接続できるまで試みるのはHttpClientが行う
+    fn connect(&self, host: &str, port: u16) -> Result<Box<dyn TransportStream>, Error> {
+        let ips = match lookup_host(host) {
+            Ok(ips) => ips,
+            Err(e) => return Err(Error::Dns(format!("{}: {:#?}", host, e))),
+        };
+
+        if ips.len() < 1 {
+            return Err(Error::Dns(host.to_string()));
+        }
+
+        let socket_addr: SocketAddr = (ips[0], port).into();
+        match TcpStream::connect(socket_addr) {
+            Ok(stream) => Ok(Box::new(TcpConnection(stream))),
+            Err(_) => Err(Error::Network(format!(
+                "Failed to connect to {}:{}",
+                host, port
+            ))),
+        }
+    }
+}
+
+/// noliのTcpStreamは相手が閉じたことを知らせないので、peer_closedは既定のfalseのままにする。
+/// 閉じられた接続は、読み込みのタイムアウトで見つける
+struct TcpConnection(TcpStream);
+
+impl TransportStream for TcpConnection {
+    fn write_bytes(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.0
+            .write(buf)
+            .map_err(|_| Error::Network("Failed to send a request to TCP stream".to_string()))
+    }
+
+    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.0
+            .read(buf)
+            .map_err(|_| Error::Network("Failed to receive a request from TCP stream".to_string()))
+    }
+}
@@ -0,0 +1,98 @@
+//! JavaScriptの実行のベンチマーク。
+//! `cargo bench --bench js`で実行する（nightlyの`test`クレートを使用する）。
+//! 本書のJavaScriptにはループがないので、ループを展開したように同じ文を繰り返すスクリプトを使う
+
+#![feature(test)]
+
+extern crate test;
+
+use saba_core::renderer::dom::node::Node;
+use saba_core::renderer::dom::node::NodeKind;
+use saba_core::renderer::html::parser::HtmlParser;
+use saba_core::renderer::html::token::HtmlTokenizer;
+use saba_core::renderer::js::ast::JsParser;
+use saba_core::renderer::js::ast::Program;
+use saba_core::renderer::js::runtime::JsRuntime;
+use saba_core::renderer::js::token::JsLexer;
+use std::cell::RefCell;
+use std::rc::Rc;
+use test::black_box;
+use test::Bencher;
+
+/// 関数の呼び出しと変数の更新を、10,000回繰り返すスクリプト
+fn unrolled_calls() -> String {
+    let mut js = String::from("var count = 0; var step = 1; function add(a, b) { return a + b; } ");
+    for _ in 0..10_000 {
+        js.push_str("count = add(count, step); ");
+    }
+    js
+}
+
+/// 変数の参照と足し算だけを、10,000回繰り返すスクリプト
+fn unrolled_additions() -> String {
+    let mut js = String::from("var total = 0; var a = 1; var b = 2; ");
+    for _ in 0..10_000 {
+        js.push_str("total = total + a + b; ");
+    }
+    js
+}
+
+/// 要素を探してテキストを書き換えることを、1,000回繰り返すスクリプト。
+/// 書き換えるたびにDOMツリーが変更される
+fn unrolled_text_updates() -> String {
+    let mut js = String::from("var target = 0; ");
+    for i in 0..1_000 {
+        js.push_str(&format!(
+            "target = document.getElementById(\"target\"); target.textContent = \"{}\"; ",
+            i
+        ));
+    }
+    js
+}
+
+/// 1,000個の段落の後ろに、書き換える要素がある文書
+fn large_document() -> Rc<RefCell<Node>> {
+    let mut html = String::from("<html><head></head><body>");
+    for i in 0..1_000 {
+        html.push_str(&format!("<p id=\"p{}\">paragraph</p>", i));
+    }
+    html.push_str("<p id=\"target\">0</p></body></html>");
+    let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+    let document = window.borrow().document();
+    document
+}
+
+fn parse(js: &str) -> Program {
+    JsParser::new(JsLexer::new(js.to_string())).parse_ast()
+}
+
+fn execute(program: &Program) -> JsRuntime {
+    let dom = Rc::new(RefCell::new(Node::new(NodeKind::Document)));
+    let mut runtime = JsRuntime::new(dom);
+    runtime.execute(program);
+    runtime
+}
+
+#[bench]
+fn execute_unrolled_calls(b: &mut Bencher) {
+    let program = parse(&unrolled_calls());
+    b.iter(|| black_box(execute(&program)));
+}
+
+#[bench]
+fn execute_unrolled_text_updates(b: &mut Bencher) {
+    let program = parse(&unrolled_text_updates());
+    // テキストを書き換えるだけなので、文書は繰り返しの間で使い回す
+    let document = large_document();
+    b.iter(|| {
+        let mut runtime = JsRuntime::new(document.clone());
+        runtime.execute(&program);
+        black_box(runtime)
+    });
+}
+
+#[bench]
+fn execute_unrolled_additions(b: &mut Bencher) {
+    let program = parse(&unrolled_additions());
+    b.iter(|| black_box(execute(&program)));
+}
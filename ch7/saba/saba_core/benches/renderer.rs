@@ -0,0 +1,144 @@
+//! パーサー、スタイル計算、レイアウトのベンチマーク。
+//! `cargo bench`で実行する（nightlyの`test`クレートを使用する）
+#![feature(test)]
+
+extern crate test;
+
+use saba_core::renderer::css::cssom::CssParser;
+use saba_core::renderer::css::cssom::StyleSheet;
+use saba_core::renderer::css::token::CssTokenizer;
+use saba_core::renderer::dom::api::get_style_content;
+use saba_core::renderer::html::parser::HtmlParser;
+use saba_core::renderer::html::token::HtmlTokenizer;
+use saba_core::renderer::layout::layout_view::LayoutView;
+use test::black_box;
+use test::Bencher;
+
+const STYLE: &str = "<style>p { color: red; } .item { background-color: blue; } #last { display: none; } a { color: green; }</style>";
+
+/// 10,000個の要素を持つ文書
+fn many_elements() -> String {
+    let mut html = String::from("<html><head>");
+    html.push_str(STYLE);
+    html.push_str("</head><body>");
+    for i in 0..10_000 {
+        html.push_str(&format!("<p class=\"item\" id=\"p{}\">item {}</p>", i, i));
+    }
+    html.push_str("<p id=\"last\">last</p></body></html>");
+    html
+}
+
+/// 要素が深く入れ子になった文書
+fn deep_nesting() -> String {
+    let mut html = String::from("<html><head>");
+    html.push_str(STYLE);
+    html.push_str("</head><body>");
+    for _ in 0..200 {
+        html.push_str("<p><a>");
+    }
+    html.push_str("deep");
+    for _ in 0..200 {
+        html.push_str("</a></p>");
+    }
+    html.push_str("</body></html>");
+    html
+}
+
+/// 巨大なテキストを持つ文書
+fn huge_text() -> String {
+    let mut html = String::from("<html><head></head><body><p>");
+    for _ in 0..20_000 {
+        html.push_str("lorem ipsum ");
+    }
+    html.push_str("</p></body></html>");
+    html
+}
+
+fn parse_css(html: &str) -> StyleSheet {
+    let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+    let dom = window.borrow().document();
+    let style = get_style_content(dom);
+    CssParser::new(CssTokenizer::new(style)).parse_stylesheet()
+}
+
+#[bench]
+fn tokenize_many_elements(b: &mut Bencher) {
+    let html = many_elements();
+    b.iter(|| black_box(HtmlTokenizer::new(html.clone()).count()));
+}
+
+#[bench]
+fn tokenize_huge_text(b: &mut Bencher) {
+    let html = huge_text();
+    b.iter(|| black_box(HtmlTokenizer::new(html.clone()).count()));
+}
+
+#[bench]
+fn tokenize_chunks_many_elements(b: &mut Bencher) {
+    let html = many_elements();
+    b.iter(|| black_box(tokenize_in_chunks(&html)));
+}
+
+#[bench]
+fn tokenize_chunks_huge_text(b: &mut Bencher) {
+    let html = huge_text();
+    b.iter(|| black_box(tokenize_in_chunks(&html)));
+}
+
+/// 受信したレスポンスのように、1KBずつ渡しながらトークンにする。返すのはトークンの数
+fn tokenize_in_chunks(html: &str) -> usize {
+    let mut tokenizer = HtmlTokenizer::streaming();
+    let mut count = 0;
+    for chunk in html.as_bytes().chunks(1024) {
+        tokenizer.push_input(core::str::from_utf8(chunk).expect("html should be ascii"));
+        count += tokenizer.by_ref().count();
+    }
+    tokenizer.close_input();
+    count + tokenizer.count()
+}
+
+#[bench]
+fn construct_tree_many_elements(b: &mut Bencher) {
+    let html = many_elements();
+    b.iter(|| black_box(HtmlParser::new(HtmlTokenizer::new(html.clone())).construct_tree()));
+}
+
+#[bench]
+fn construct_tree_huge_text(b: &mut Bencher) {
+    let html = huge_text();
+    b.iter(|| black_box(HtmlParser::new(HtmlTokenizer::new(html.clone())).construct_tree()));
+}
+
+#[bench]
+fn construct_tree_deep_nesting(b: &mut Bencher) {
+    let html = deep_nesting();
+    b.iter(|| black_box(HtmlParser::new(HtmlTokenizer::new(html.clone())).construct_tree()));
+}
+
+#[bench]
+fn style_and_layout_many_elements(b: &mut Bencher) {
+    let html = many_elements();
+    let window = HtmlParser::new(HtmlTokenizer::new(html.clone())).construct_tree();
+    let dom = window.borrow().document();
+    let cssom = parse_css(&html);
+    b.iter(|| black_box(LayoutView::new(dom.clone(), &cssom)));
+}
+
+#[bench]
+fn style_and_layout_deep_nesting(b: &mut Bencher) {
+    let html = deep_nesting();
+    let window = HtmlParser::new(HtmlTokenizer::new(html.clone())).construct_tree();
+    let dom = window.borrow().document();
+    let cssom = parse_css(&html);
+    b.iter(|| black_box(LayoutView::new(dom.clone(), &cssom)));
+}
+
+#[bench]
+fn paint_huge_text(b: &mut Bencher) {
+    let html = huge_text();
+    let window = HtmlParser::new(HtmlTokenizer::new(html.clone())).construct_tree();
+    let dom = window.borrow().document();
+    let cssom = parse_css(&html);
+    let layout_view = LayoutView::new(dom, &cssom);
+    b.iter(|| black_box(layout_view.paint()));
+}
@@ -1,12 +1,175 @@
+use crate::cache::HttpCache;
+use crate::config::BrowserConfig;
+use crate::config::HEADERS_SETTINGS_KEY;
+use crate::console::ConsoleEntry;
+use crate::console::ConsoleEntryKind;
+use crate::constants::DEFAULT_SEARCH_ENGINE;
+use crate::constants::MAX_REDIRECTS;
+use crate::constants::MAX_RESOURCE_HINTS_PER_ITERATION;
+use crate::cookie::CookieJar;
+use crate::display_item::DisplayItem;
+use crate::download::should_download;
+use crate::download::Download;
+use crate::download::DownloadManager;
+use crate::error::Error;
+use crate::http::Header;
+use crate::http::HttpResponse;
+use crate::http::Proxy;
+use crate::internal_page::apply_headers_query;
+use crate::internal_page::is_internal_url;
+use crate::internal_page::view_source_target;
+use crate::internal_page::ABOUT_CONSOLE;
+use crate::internal_page::ABOUT_HEADERS;
+use crate::internal_page::ABOUT_RETRY;
+use crate::navigation::NavigationOutcome;
+use crate::navigation::NavigationRequest;
+use crate::navigation::NavigationSource;
+use crate::page_observer::PageObserver;
+use crate::permission::origin_of;
+use crate::permission::Permission;
+use crate::permission::PermissionManager;
+use crate::permission::PermissionState;
+use crate::permission::PERMISSIONS_SETTINGS_KEY;
+use crate::platform::Clock;
+use crate::platform::LogLevel;
+use crate::platform::Logger;
+use crate::platform::NoClock;
+use crate::platform::NoPanicGuard;
+use crate::platform::NoScriptWatchdog;
+use crate::platform::NoSettingsStorage;
+use crate::platform::NullLogger;
+use crate::platform::PanicGuard;
+use crate::platform::ScriptWatchdog;
+use crate::platform::SettingsStorage;
+use crate::renderer::css::cssom::CascadeOrigin;
+use crate::renderer::css::cssom::CssParser;
+use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::css::token::CssTokenizer;
+use crate::renderer::dom::node::Node;
+use crate::renderer::image::create_decoder;
+use crate::renderer::image::decode_in_tasks;
+use crate::renderer::image::Image;
+use crate::renderer::js::ast::JsParser;
+use crate::renderer::js::runtime::JsRuntime;
+use crate::renderer::js::token::JsLexer;
 use crate::renderer::page::Page;
+use crate::resource_loader::ResourceLoader;
+use crate::task::run_tasks;
+use crate::task::Task;
+use crate::task::TaskPriority;
+use crate::task::TaskQueue;
+use crate::url::split_fragment;
+use crate::url::Url;
+use crate::url::UrlParseError;
+use crate::utils::convert_dom_to_string;
+use crate::utils::convert_layout_to_string;
+use crate::utils::form_urlencode;
+use alloc::format;
 use alloc::rc::Rc;
+use alloc::rc::Weak;
+use alloc::string::String;
+use alloc::string::ToString;
 use alloc::vec::Vec;
+use core::cell::Cell;
 use core::cell::RefCell;
 
+/// SettingsStorageに、ユーザーのスタイルシートを保存するときのキー
+pub const USER_STYLESHEET_SETTINGS_KEY: &str = "user-stylesheet";
+
+/// 読み込みが終わったページの状態。DOMツリー、レイアウトツリーの文字列表現、描画する内容を持つ
+#[derive(Debug, Clone)]
+pub struct RenderedPage {
+    url: String,
+    title: String,
+    document: Option<Rc<RefCell<Node>>>,
+    dom_dump: String,
+    layout_dump: String,
+    display_items: Vec<DisplayItem>,
+}
+
+impl RenderedPage {
+    fn new(url: &str, page: &Page) -> Self {
+        let document = page.document();
+        Self {
+            url: url.to_string(),
+            title: page.title(),
+            dom_dump: convert_dom_to_string(&document),
+            layout_dump: convert_layout_to_string(&page.layout_root()),
+            document,
+            display_items: page.display_items(),
+        }
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    pub fn title(&self) -> String {
+        self.title.clone()
+    }
+
+    pub fn document(&self) -> Option<Rc<RefCell<Node>>> {
+        self.document.clone()
+    }
+
+    pub fn dom_dump(&self) -> String {
+        self.dom_dump.clone()
+    }
+
+    pub fn layout_dump(&self) -> String {
+        self.layout_dump.clone()
+    }
+
+    pub fn display_items(&self) -> Vec<DisplayItem> {
+        self.display_items.clone()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Browser {
+    /// 入力を処理しているウィンドウのページ。loadなどは、このページに対して行う
     active_page_index: usize,
+    /// ウィンドウごとのページ。HTTPキャッシュ、Cookie、権限などは、全てのページで共有する
     pages: Vec<Rc<RefCell<Page>>>,
+    /// 新しく作るページに渡す、自分自身への参照
+    this: Weak<RefCell<Browser>>,
+    search_engine: String,
+    /// ページを読み込んでいる間もBrowserを共有して借用できるように、RefCellに入れる
+    http_cache: RefCell<HttpCache>,
+    /// ページの状態の変化を受け取る、埋め込み側のオブザーバー
+    observers: Vec<Rc<dyn PageObserver>>,
+    /// 埋め込み側が渡す、OSに依存する機能
+    clock: Rc<dyn Clock>,
+    logger: Rc<dyn Logger>,
+    panic_guard: Rc<dyn PanicGuard>,
+    script_watchdog: Rc<dyn ScriptWatchdog>,
+    /// リクエストを中継するプロキシ。ネットワークの処理を行うhandle_urlが参照する
+    proxy: Option<Proxy>,
+    /// User-Agentなどの、全てのリクエストに付けるヘッダ。proxyと同じくhandle_urlが参照する。
+    /// ページを読み込んでいる間にabout:headersで変更できるように、RefCellに入れる
+    config: RefCell<BrowserConfig>,
+    /// devtoolsのコンソールの記録。ページのconsole.log()と、コンソールに入力した式と結果
+    console: RefCell<Vec<ConsoleEntry>>,
+    /// about:consoleを表示している間、式を評価するのに使う直前のページのJsRuntime
+    inspected_runtime: RefCell<Option<JsRuntime>>,
+    showing_console: Cell<bool>,
+    /// イベントループで実行するタスク。タスクの中から追加できるように、Rcで共有する
+    tasks: Rc<RefCell<TaskQueue>>,
+    /// ページの<link>が指す、タスクが全て終わった後で取得してHTTPキャッシュに入れるリソース
+    resource_loader: RefCell<ResourceLoader>,
+    /// ページとして表示せずにダウンロードしたファイル
+    downloads: RefCell<DownloadManager>,
+    /// オリジンごとの権限と、決めた権限を保存する場所
+    permissions: RefCell<PermissionManager>,
+    settings_storage: Rc<dyn SettingsStorage>,
+    /// Set-Cookieで受け取ったCookie。Cookieの権限を許可したオリジンのものだけを持つ
+    cookies: RefCell<CookieJar>,
+    /// 表示中のページが確認を求めたので、ユーザーの答えを待っている移動
+    pending_navigation: RefCell<Option<NavigationRequest>>,
+    /// 1CSSピクセルを何ピクセルで描くか。全てのページのビューポートに使う
+    device_scale_factor: f64,
+    /// ブラウザを使う人が設定したスタイルシート。全てのページに、作者のスタイルより弱く適用する
+    user_style: StyleSheet,
 }
 
 impl Browser {
@@ -16,9 +179,32 @@ impl Browser {
         let browser = Rc::new(RefCell::new(Self {
             active_page_index: 0,
             pages: Vec::new(),
+            this: Weak::new(),
+            search_engine: DEFAULT_SEARCH_ENGINE.to_string(),
+            http_cache: RefCell::new(HttpCache::new()),
+            observers: Vec::new(),
+            clock: Rc::new(NoClock),
+            logger: Rc::new(NullLogger),
+            panic_guard: Rc::new(NoPanicGuard),
+            script_watchdog: Rc::new(NoScriptWatchdog),
+            proxy: None,
+            config: RefCell::new(BrowserConfig::new()),
+            console: RefCell::new(Vec::new()),
+            inspected_runtime: RefCell::new(None),
+            showing_console: Cell::new(false),
+            tasks: Rc::new(RefCell::new(TaskQueue::new())),
+            resource_loader: RefCell::new(ResourceLoader::new()),
+            downloads: RefCell::new(DownloadManager::new()),
+            permissions: RefCell::new(PermissionManager::new()),
+            settings_storage: Rc::new(NoSettingsStorage),
+            cookies: RefCell::new(CookieJar::new()),
+            pending_navigation: RefCell::new(None),
+            device_scale_factor: 1.0,
+            user_style: StyleSheet::new(),
         }));
 
         page.set_browser(Rc::downgrade(&browser));
+        browser.borrow_mut().this = Rc::downgrade(&browser);
         browser.borrow_mut().pages.push(Rc::new(RefCell::new(page)));
 
         browser
@@ -27,4 +213,2209 @@ impl Browser {
     pub fn current_page(&self) -> Rc<RefCell<Page>> {
         self.pages[self.active_page_index].clone()
     }
+
+    /// 新しいウィンドウのためのページを作り、その番号を返す。入力を処理するページは変えない
+    pub fn open_page(&mut self) -> usize {
+        let mut page = Page::new();
+        page.set_browser(self.this.clone());
+        page.set_device_scale_factor(self.device_scale_factor);
+        self.pages.push(Rc::new(RefCell::new(page)));
+        self.pages.len() - 1
+    }
+
+    pub fn device_scale_factor(&self) -> f64 {
+        self.device_scale_factor
+    }
+
+    /// 画面の倍率を変え、開いている全てのページをレイアウトし直す。0以下の値は無視する
+    pub fn set_device_scale_factor(&mut self, device_scale_factor: f64) {
+        if device_scale_factor <= 0.0 {
+            return;
+        }
+        self.device_scale_factor = device_scale_factor;
+        for page in &self.pages {
+            page.borrow_mut()
+                .set_device_scale_factor(device_scale_factor);
+        }
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn active_page_index(&self) -> usize {
+        self.active_page_index
+    }
+
+    /// 入力を処理するページを切り替える。ウィンドウにフォーカスが移ったときに、UIが呼ぶ
+    pub fn set_active_page(&mut self, index: usize) -> Result<(), Error> {
+        if index >= self.pages.len() {
+            return Err(Error::Other(format!("no such page: {}", index)));
+        }
+        self.active_page_index = index;
+        Ok(())
+    }
+
+    /// ページのタイトルや読み込みの状態が変わったときに、通知を受け取るオブザーバーを登録する
+    pub fn add_observer(&mut self, observer: Rc<dyn PageObserver>) {
+        self.observers.push(observer);
+    }
+
+    pub fn observers(&self) -> Vec<Rc<dyn PageObserver>> {
+        self.observers.clone()
+    }
+
+    /// 時計を持つ環境では、HTTPキャッシュの鮮度をその時刻で判断する
+    pub fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn set_logger(&mut self, logger: Rc<dyn Logger>) {
+        self.logger = logger;
+    }
+
+    /// パニックを捕まえられる環境では、ページの読み込み中のパニックをエラーとして扱う
+    pub fn set_panic_guard(&mut self, panic_guard: Rc<dyn PanicGuard>) {
+        self.panic_guard = panic_guard;
+    }
+
+    /// ユーザーに尋ねられる環境では、止まらないスクリプトを中断するかをScriptWatchdogで決める
+    pub fn set_script_watchdog(&mut self, script_watchdog: Rc<dyn ScriptWatchdog>) {
+        self.script_watchdog = script_watchdog;
+    }
+
+    pub fn script_watchdog(&self) -> Rc<dyn ScriptWatchdog> {
+        self.script_watchdog.clone()
+    }
+
+    /// 設定を保存できる環境では、保存しておいた権限とリクエストのヘッダとユーザーのスタイルシートを読み込み、
+    /// これから決めた権限とabout:headersで変更したヘッダと設定したスタイルシートを保存する
+    pub fn set_settings_storage(&mut self, storage: Rc<dyn SettingsStorage>) {
+        if let Some(saved) = storage.load(PERMISSIONS_SETTINGS_KEY) {
+            if let Err(e) = self.permissions.borrow_mut().load(&saved) {
+                self.log(
+                    LogLevel::Warning,
+                    &format!("failed to load the saved permissions: {:?}", e),
+                );
+            }
+        }
+        if let Some(saved) = storage.load(HEADERS_SETTINGS_KEY) {
+            if let Err(e) = apply_headers_query(&mut self.config.borrow_mut(), &saved) {
+                self.log(
+                    LogLevel::Warning,
+                    &format!("failed to load the saved request headers: {:?}", e),
+                );
+            }
+        }
+        if let Some(saved) = storage.load(USER_STYLESHEET_SETTINGS_KEY) {
+            self.user_style = parse_user_stylesheet(&saved);
+        }
+        self.settings_storage = storage;
+    }
+
+    /// 表示中のページ全体をSVGの文書にし、`name`をキーにしてSettingsStorageに保存する。保存した文書を返す
+    pub fn export_svg(&self, name: &str) -> String {
+        let svg = self.current_page().borrow().to_svg();
+        self.settings_storage.save(name, &svg);
+        self.log(
+            LogLevel::Info,
+            &format!("exported the page to {} ({} bytes)", name, svg.len()),
+        );
+        svg
+    }
+
+    /// `url`のオリジンに`permission`を許可しているかどうか。内部ページには全ての権限を許可する
+    pub fn permission_state(&self, url: &str, permission: Permission) -> PermissionState {
+        match origin_of(url) {
+            Some(origin) => self.permissions.borrow().state(&origin, permission),
+            None => PermissionState::Allow,
+        }
+    }
+
+    /// まだ決めていないオリジンの権限の既定の値を設定する。
+    /// Askにすると、オリジンごとに初めて必要になったときにオブザーバーを通してユーザーに尋ねる
+    pub fn set_default_permission(&self, permission: Permission, state: PermissionState) {
+        self.permissions
+            .borrow_mut()
+            .set_default_state(permission, state);
+    }
+
+    /// `url`のオリジンについて、ユーザーが決めた権限を記録して保存する。
+    /// Cookieをブロックした場合は、そのオリジンから受け取ったCookieも削除する
+    pub fn set_permission(
+        &self,
+        url: &str,
+        permission: Permission,
+        allowed: bool,
+    ) -> Result<(), Error> {
+        let origin = origin_of(url)
+            .ok_or_else(|| Error::UnexpectedInput(format!("{} does not have an origin", url)))?;
+        self.permissions
+            .borrow_mut()
+            .set(&origin, permission, allowed);
+        if permission == Permission::Cookies && !allowed {
+            self.cookies.borrow_mut().remove(&origin);
+        }
+        self.settings_storage.save(
+            PERMISSIONS_SETTINGS_KEY,
+            &self.permissions.borrow().serialize(),
+        );
+        Ok(())
+    }
+
+    /// `url`のオリジンに`permission`を使わせてよいかどうか。
+    /// ユーザーに尋ねる必要がある場合は、オブザーバーに知らせ、答えが出るまではブロックする
+    pub fn is_permitted(&self, url: &str, permission: Permission) -> bool {
+        let origin = match origin_of(url) {
+            Some(origin) => origin,
+            None => return true,
+        };
+        let state = self.permissions.borrow().state(&origin, permission);
+        match state {
+            PermissionState::Allow => true,
+            PermissionState::Block => false,
+            PermissionState::Ask => {
+                let first_request = self
+                    .permissions
+                    .borrow_mut()
+                    .take_request(&origin, permission);
+                if first_request {
+                    for observer in &self.observers {
+                        observer.on_permission_request(&origin, permission);
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    /// `url`のオリジンへのリクエストに付けるCookieヘッダの値
+    pub fn cookie_header(&self, url: &str) -> Option<String> {
+        self.cookies.borrow().header_value(&origin_of(url)?)
+    }
+
+    /// イベントループで実行するタスクを追加する
+    pub fn post_task(&self, priority: TaskPriority, task: Task) {
+        self.tasks.borrow_mut().push(priority, task);
+    }
+
+    pub fn task_queue(&self) -> Rc<RefCell<TaskQueue>> {
+        self.tasks.clone()
+    }
+
+    /// UIがイベントループの繰り返しごとに呼ぶ。溜まっているタスクを、
+    /// 入力への反応を遅らせない範囲で実行し、実行した数を返す
+    pub fn run_tasks(&self) -> usize {
+        run_tasks(&self.tasks, self.clock.as_ref())
+    }
+
+    /// ページの<link rel="preload">と<link rel="prefetch">が指すリソースを取得して、HTTPキャッシュに入れる。
+    /// 先読みは急がないので、タスクキューが空の場合だけ、1回の繰り返しで
+    /// MAX_RESOURCE_HINTS_PER_ITERATION個まで取得する。取得したリソースの数を返す
+    pub fn load_resource_hints(
+        &self,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+    ) -> usize {
+        if !self.tasks.borrow().is_empty() {
+            return 0;
+        }
+        let mut loaded = 0;
+        while loaded < MAX_RESOURCE_HINTS_PER_ITERATION {
+            let hint = match self.resource_loader.borrow_mut().pop() {
+                Some(hint) => hint,
+                None => break,
+            };
+            let reason = match self.fetch(hint.url(), handle_url) {
+                Ok(response) => response.error_status(),
+                Err(e) => Some(format!("{:?}", e)),
+            };
+            // 先読みに失敗したリソースは、ページが使うときに改めて取得するので、ここでは記録だけする
+            if let Some(reason) = reason {
+                self.current_page()
+                    .borrow_mut()
+                    .report_subresource_error(&hint.url(), &reason);
+            }
+            loaded += 1;
+        }
+        loaded
+    }
+
+    /// まだ取得していない先読みのリソースの数
+    pub fn pending_resource_hints(&self) -> usize {
+        self.resource_loader.borrow().len()
+    }
+
+    pub fn downloads(&self) -> Vec<Download> {
+        self.downloads.borrow().downloads()
+    }
+
+    pub fn clear_downloads(&self) {
+        self.downloads.borrow_mut().clear();
+    }
+
+    pub fn log(&self, level: LogLevel, message: &str) {
+        self.logger.log(level, message);
+    }
+
+    pub fn record_console(&self, entry: ConsoleEntry) {
+        self.console.borrow_mut().push(entry);
+    }
+
+    pub fn console_entries(&self) -> Vec<ConsoleEntry> {
+        self.console.borrow().clone()
+    }
+
+    /// devtoolsのコンソールに入力された式を、表示中のページのスクリプトと同じ環境で評価する。
+    /// about:consoleを表示している場合は、その前に表示していたページの環境で評価し、
+    /// about:consoleを描画し直す。入力の後に記録した行を返す
+    pub fn evaluate_console_input(&self, input: &str) -> Vec<ConsoleEntry> {
+        self.record_console(ConsoleEntry::new(ConsoleEntryKind::Input, input));
+        let first_new_entry = self.console.borrow().len();
+
+        let program = JsParser::new(JsLexer::new(input.to_string())).parse_ast();
+        let evaluation = if self.showing_console.get() {
+            self.inspected_runtime
+                .borrow_mut()
+                .as_mut()
+                .map(|runtime| runtime.evaluate(&program))
+        } else {
+            self.current_page().borrow_mut().evaluate_script(&program)
+        };
+
+        match evaluation {
+            Some(evaluation) => {
+                for message in evaluation.console_messages {
+                    self.record_console(ConsoleEntry::new(ConsoleEntryKind::Log, &message));
+                }
+                for error in &evaluation.errors {
+                    self.record_console(ConsoleEntry::new(ConsoleEntryKind::Error, error));
+                }
+                let result = match evaluation.value {
+                    Some(value) => value.inspect(),
+                    None => "undefined".to_string(),
+                };
+                if evaluation.errors.is_empty() {
+                    self.record_console(ConsoleEntry::new(ConsoleEntryKind::Result, &result));
+                }
+            }
+            None => self.record_console(ConsoleEntry::new(
+                ConsoleEntryKind::Error,
+                "no page to evaluate the input in",
+            )),
+        }
+
+        if self.showing_console.get() {
+            // about:consoleは内部ページなので、読み込みに失敗しない
+            let _ = self
+                .current_page()
+                .borrow_mut()
+                .load_internal_page(ABOUT_CONSOLE);
+        }
+
+        self.console.borrow()[first_new_entry..].to_vec()
+    }
+
+    /// Noneを設定すると、プロキシを使わずにサーバーへ直接接続する
+    pub fn set_proxy(&mut self, proxy: Option<Proxy>) {
+        self.proxy = proxy;
+    }
+
+    pub fn proxy(&self) -> Option<Proxy> {
+        self.proxy.clone()
+    }
+
+    pub fn config(&self) -> BrowserConfig {
+        self.config.borrow().clone()
+    }
+
+    pub fn set_config(&mut self, config: BrowserConfig) {
+        *self.config.borrow_mut() = config;
+    }
+
+    /// about:headersのクエリで設定を変更して保存する。リンクやスクリプトからの移動では呼ばないので、
+    /// ページがリクエストのヘッダを変えることはできない
+    pub fn change_headers(&self, query: &str) -> Result<(), Error> {
+        apply_headers_query(&mut self.config.borrow_mut(), query)?;
+        self.settings_storage
+            .save(HEADERS_SETTINGS_KEY, &self.config.borrow().serialize());
+        Ok(())
+    }
+
+    /// ユーザーのスタイルシートを設定して保存する。これから読み込むページから適用する
+    pub fn set_user_stylesheet(&mut self, css: &str) {
+        self.user_style = parse_user_stylesheet(css);
+        self.settings_storage
+            .save(USER_STYLESHEET_SETTINGS_KEY, css);
+    }
+
+    pub fn user_stylesheet(&self) -> StyleSheet {
+        self.user_style.clone()
+    }
+
+    pub fn search_engine(&self) -> String {
+        self.search_engine.clone()
+    }
+
+    /// 検索エンジンのURLのテンプレートを設定する。"%s"が検索語に置き換えられる
+    pub fn set_search_engine(&mut self, template: String) {
+        self.search_engine = template;
+    }
+
+    /// 検索語を、設定されている検索エンジンのURLに変換する
+    pub fn search_url(&self, query: &str) -> String {
+        self.search_engine
+            .replace("%s", &form_urlencode(query.trim()))
+    }
+
+    /// HTTPキャッシュを使ってURLのレスポンスを取得する。
+    /// `handle_url`には、条件付きリクエストのためのヘッダと、Cookieを許可している場合はCookieヘッダが渡される。
+    /// リダイレクトの途中のレスポンスが持つSet-Cookieは、まだ保存しない
+    pub fn fetch(
+        &self,
+        url: String,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+    ) -> Result<HttpResponse, Error> {
+        // オブザーバーがユーザーに尋ねている間にBrowserを使えるように、キャッシュを借用する前に確かめる
+        let use_cookies = self.is_permitted(&url, Permission::Cookies);
+        let mut http_cache = self.http_cache.borrow_mut();
+        if let Some(now) = self.clock.now() {
+            http_cache.set_now(now);
+        }
+
+        self.log(LogLevel::Info, &format!("fetching {}", url));
+        http_cache
+            .fetch(&url, |mut headers| {
+                if use_cookies {
+                    if let Some(cookie) = self.cookie_header(&url) {
+                        headers.push(Header::new("Cookie".to_string(), cookie));
+                    }
+                }
+                let response = handle_url(url.clone(), headers)?;
+                if let (true, Some(origin)) = (use_cookies, origin_of(&url)) {
+                    let mut cookies = self.cookies.borrow_mut();
+                    for set_cookie in response.header_values("Set-Cookie") {
+                        cookies.store(&origin, &set_cookie);
+                    }
+                }
+                Ok(response)
+            })
+            .map_err(|e| {
+                self.log(
+                    LogLevel::Error,
+                    &format!("failed to fetch {}: {:?}", url, e),
+                );
+                e
+            })
+    }
+
+    /// スタイルシートを取得して解釈し、ページに渡す。解釈したスタイルシートはHTTPキャッシュの
+    /// レスポンスと一緒に保存し、同じレスポンスを使う場合はトークン化と解釈をせずにそれを使う
+    fn load_stylesheet(
+        &self,
+        page: &Rc<RefCell<Page>>,
+        href: String,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+    ) {
+        let response = match self.fetch(href.clone(), handle_url) {
+            Ok(response) => match response.error_status() {
+                Some(reason) => Err(reason),
+                None => Ok(response),
+            },
+            Err(e) => Err(format!("{:?}", e)),
+        };
+        let response = match response {
+            Ok(response) => response,
+            Err(reason) => {
+                page.borrow_mut().receive_stylesheet_error(&href, &reason);
+                return;
+            }
+        };
+
+        let cached = self.http_cache.borrow().stylesheet(&href);
+        let sheet = match cached {
+            Some(sheet) => {
+                self.log(
+                    LogLevel::Info,
+                    &format!("using the parsed stylesheet of {}", href),
+                );
+                sheet
+            }
+            None => {
+                let sheet = CssParser::new(CssTokenizer::new(response.body())).parse_stylesheet();
+                // HTTPキャッシュに保存されなかったレスポンスのスタイルシートは保存しない
+                self.http_cache
+                    .borrow_mut()
+                    .set_stylesheet(&href, sheet.clone());
+                sheet
+            }
+        };
+        page.borrow_mut().receive_stylesheet(&href, sheet);
+    }
+
+    /// 画像を取得してデコードを始める。デコードできた行は、タスクを実行するたびにページに渡して描画する。
+    /// 取得やデコードに失敗した画像は、ページが使うリソースを読み込めなかったものとして数える
+    fn load_image(
+        &self,
+        page: &Rc<RefCell<Page>>,
+        src: String,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+    ) {
+        let decoder = match self.fetch(src.clone(), handle_url) {
+            Ok(response) => match response.error_status() {
+                Some(reason) => Err(reason),
+                None => create_decoder(response.body_bytes()).map_err(|e| format!("{:?}", e)),
+            },
+            Err(e) => Err(format!("{:?}", e)),
+        };
+        let decoder = match decoder {
+            Ok(decoder) => decoder,
+            Err(reason) => {
+                page.borrow_mut().receive_image_error(&src, &reason);
+                return;
+            }
+        };
+
+        // 画像の大きさはヘッダからわかるので、デコードを始める前にレイアウトしておく
+        page.borrow_mut()
+            .receive_image(&src, decoder.borrow().image());
+
+        let progress_page = page.clone();
+        let progress_src = src.clone();
+        let error_page = page.clone();
+        decode_in_tasks(
+            self.tasks.clone(),
+            decoder,
+            Rc::new(move |image: &Image| {
+                progress_page
+                    .borrow_mut()
+                    .receive_image(&progress_src, image)
+            }),
+            Rc::new(move |e| {
+                error_page
+                    .borrow_mut()
+                    .receive_image_error(&src, &format!("{:?}", e))
+            }),
+        );
+    }
+
+    /// ページのアイコンを取得し、取得できた場合はオブザーバーに知らせる。
+    /// 取得に失敗したアイコンは、ページが使うリソースを読み込めなかったものとして数える
+    fn load_icon(
+        &self,
+        page: &Rc<RefCell<Page>>,
+        href: String,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+    ) {
+        let reason = match self.fetch(href.clone(), handle_url) {
+            Ok(response) => response.error_status(),
+            Err(e) => Some(format!("{:?}", e)),
+        };
+        match reason {
+            Some(reason) => page.borrow_mut().report_subresource_error(&href, &reason),
+            None => {
+                for observer in &self.observers {
+                    observer.on_favicon(&href);
+                }
+            }
+        }
+    }
+
+    /// `destination`を現在のページに読み込む。UIを持たないので、読み込んだ後の描画は呼び出し側が行う。
+    /// "#"から始まるURLはページの中をスクロールし、about:とview-source:のURLはそれぞれ内部ページと
+    /// ソースの表示として扱う。ページを取得できなかった場合はエラーを返す
+    pub fn load(
+        &self,
+        destination: &str,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+    ) -> Result<(), Error> {
+        let page = self.current_page();
+
+        if let ("", Some(fragment)) = split_fragment(destination) {
+            page.borrow_mut().scroll_to_fragment(fragment);
+            return Ok(());
+        }
+
+        // エラーページの"Retry"のリンクは、表示中のページのURLを読み込み直す
+        if destination == ABOUT_RETRY {
+            let url = page.borrow().url();
+            return match url {
+                Some(url) if url != ABOUT_RETRY => self.load(&url, handle_url),
+                _ => Ok(()),
+            };
+        }
+
+        // about:consoleに移る前に、式を評価するためのページの環境を残しておく
+        if destination == ABOUT_CONSOLE && !self.showing_console.get() {
+            *self.inspected_runtime.borrow_mut() = page.borrow_mut().take_runtime();
+        }
+        self.showing_console.set(destination == ABOUT_CONSOLE);
+
+        // about:headersのクエリは、アドレスバーに入力された場合にだけcommit_navigationで適用する。
+        // クエリを除いたURLで表示するので、読み込み直しても同じ変更を繰り返さない
+        if headers_query(destination).is_some() {
+            return page.borrow_mut().load_internal_page(ABOUT_HEADERS);
+        }
+
+        if is_internal_url(destination) {
+            return page.borrow_mut().load_internal_page(destination);
+        }
+
+        if let Some(target) = view_source_target(destination) {
+            let response = self.fetch(target.to_string(), handle_url)?;
+            page.borrow_mut().receive_source(response);
+            page.borrow_mut().set_url(destination);
+            return Ok(());
+        }
+
+        // フラグメントはサーバーに送らず、ページを読み込んだ後のスクロールに使う
+        let (url, fragment) = split_fragment(destination);
+        let response = self.fetch(url.to_string(), handle_url)?;
+
+        // 添付ファイルと表示できない種類のレスポンスは、HTMLとして解釈せずにダウンロードする。
+        // 表示中のページはそのまま残す
+        if should_download(&response) {
+            let download = Download::from_response(url, &response);
+            self.log(
+                LogLevel::Info,
+                &format!("downloaded {} ({} bytes)", download.name(), download.size()),
+            );
+            for observer in &self.observers {
+                observer.on_download(&download);
+            }
+            self.downloads.borrow_mut().add(download);
+            return Ok(());
+        }
+
+        // HTMLの解釈からスクリプトの実行までのパニックは、このページの読み込みの失敗として扱う
+        let mut response = Some(response);
+        // スクリプトを実行する前に、権限を確かめるためのURLを設定しておく
+        page.borrow_mut().set_url(destination);
+        let result = self.panic_guard.run(&mut || {
+            if let Some(response) = response.take() {
+                self.render(&page, response, fragment, handle_url);
+            }
+        });
+        if let Err(reason) = result {
+            self.log(
+                LogLevel::Error,
+                &format!("the page crashed while loading {}: {}", destination, reason),
+            );
+            return Err(Error::Crashed(reason));
+        }
+
+        Ok(())
+    }
+
+    /// 取得したレスポンスを解釈し、スタイルの計算、レイアウト、スクリプトの実行をして描画する
+    fn render(
+        &self,
+        page: &Rc<RefCell<Page>>,
+        response: HttpResponse,
+        fragment: Option<&str>,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+    ) {
+        page.borrow_mut().receive_response(response);
+
+        // レイアウトが変わるので、子フレームと画像より先にスタイルシートを読み込む
+        let pending = page.borrow().pending_stylesheets();
+        for href in pending {
+            self.load_stylesheet(page, href, handle_url);
+        }
+
+        // ページの中の<iframe>が指すドキュメントを、子フレームとして読み込む。読み込んだ子フレームの中の
+        // <iframe>も続けて読み込む。読み込めなかった子フレームは、もう一度取得しない
+        let mut requested: Vec<Vec<usize>> = Vec::new();
+        loop {
+            let pending: Vec<(Vec<usize>, String)> = page
+                .borrow()
+                .pending_child_frames()
+                .into_iter()
+                .filter(|(path, _)| !requested.contains(path))
+                .collect();
+            if pending.is_empty() {
+                break;
+            }
+            for (path, src) in pending {
+                requested.push(path.clone());
+                match self.fetch(src.clone(), handle_url) {
+                    // エラーのレスポンスも子フレームに表示するが、読み込めなかったものとして数える
+                    Ok(response) => {
+                        if let Some(reason) = response.error_status() {
+                            page.borrow_mut().report_subresource_error(&src, &reason);
+                        }
+                        page.borrow_mut()
+                            .receive_child_frame_response(&path, response)
+                    }
+                    // 子フレームの読み込みに失敗しても、親のページの表示は続ける
+                    Err(e) => page
+                        .borrow_mut()
+                        .report_subresource_error(&src, &format!("{:?}", e)),
+                }
+            }
+        }
+
+        // ページの中の<img>が指す画像を取得し、タスクに分けて少しずつデコードする
+        let pending = page.borrow_mut().take_pending_images();
+        for src in pending {
+            self.load_image(page, src, handle_url);
+        }
+
+        let icon = page.borrow().icon_url();
+        if let Some(href) = icon {
+            self.load_icon(page, href, handle_url);
+        }
+
+        // 前のページのpreloadは取り消し、このページが指すリソースを後で取得する
+        let mut resource_loader = self.resource_loader.borrow_mut();
+        resource_loader.cancel_preloads();
+        for hint in page.borrow().resource_hints() {
+            resource_loader.push(hint);
+        }
+
+        if let Some(fragment) = fragment {
+            page.borrow_mut().scroll_to_fragment(fragment);
+        }
+    }
+
+    /// `destination`を読み込み、その結果を返す。ブラウザを外部のツールやテストから使うための入り口
+    pub fn navigate(
+        &self,
+        destination: &str,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+    ) -> Result<RenderedPage, Error> {
+        self.load(destination, handle_url)?;
+        Ok(RenderedPage::new(
+            destination,
+            &self.current_page().borrow(),
+        ))
+    }
+
+    /// 現在のページを`request`の移動先に移す。UIのどこから始まった移動も、ここを通す。
+    /// ページのbeforeunloadのハンドラが確認を求めた場合は、移動を保留してオブザーバーに知らせ、
+    /// Blockedを返す。ページの中のフラグメントへの移動は、ページを離れないので確認しない
+    pub fn request_navigation(
+        &self,
+        request: NavigationRequest,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+    ) -> Result<NavigationOutcome, Error> {
+        let page = self.current_page();
+        let leaves_page = !request.destination().starts_with('#');
+        if leaves_page && page.borrow_mut().before_unload() {
+            let destination = request.destination();
+            *self.pending_navigation.borrow_mut() = Some(request);
+            for observer in &self.observers {
+                observer.on_before_unload(&destination);
+            }
+            return Ok(NavigationOutcome::Blocked);
+        }
+
+        self.commit_navigation(request, handle_url)
+    }
+
+    /// 保留している移動を、ユーザーの答えに従って続けるか取り消す
+    pub fn confirm_navigation(
+        &self,
+        proceed: bool,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+    ) -> Result<NavigationOutcome, Error> {
+        let request = self.pending_navigation.borrow_mut().take();
+        match request {
+            Some(request) if proceed => self.commit_navigation(request, handle_url),
+            _ => Ok(NavigationOutcome::Cancelled),
+        }
+    }
+
+    pub fn pending_navigation(&self) -> Option<NavigationRequest> {
+        self.pending_navigation.borrow().clone()
+    }
+
+    /// 現在のページの履歴で、1つ前のページに戻る。戻る先がない場合はCancelledを返す
+    pub fn go_back(
+        &self,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+    ) -> Result<NavigationOutcome, Error> {
+        let back_url = self.current_page().borrow().session_history().back_url();
+        match back_url {
+            Some(url) => self.request_navigation(
+                NavigationRequest::new(&url, NavigationSource::Back),
+                handle_url,
+            ),
+            None => Ok(NavigationOutcome::Cancelled),
+        }
+    }
+
+    /// 現在のページの履歴で、1つ後のページに進む。進む先がない場合はCancelledを返す
+    pub fn go_forward(
+        &self,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+    ) -> Result<NavigationOutcome, Error> {
+        let forward_url = self.current_page().borrow().session_history().forward_url();
+        match forward_url {
+            Some(url) => self.request_navigation(
+                NavigationRequest::new(&url, NavigationSource::Forward),
+                handle_url,
+            ),
+            None => Ok(NavigationOutcome::Cancelled),
+        }
+    }
+
+    /// 移動先を読み込み、履歴を更新する。読み込んだページのスクリプトがlocationで移動を要求した場合は、
+    /// リダイレクトと同じ回数まで続けてたどる。ユーザーが操作する前の読み込み中の移動なので、
+    /// 移動元のエントリを移動先で置き換え、戻るでリダイレクトするページに戻らないようにする
+    fn commit_navigation(
+        &self,
+        request: NavigationRequest,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+    ) -> Result<NavigationOutcome, Error> {
+        let page = self.current_page();
+        if request.source() == NavigationSource::AddressBar {
+            if let Some(query) = headers_query(&request.destination()) {
+                self.change_headers(query)?;
+            }
+        }
+
+        let mut request = request;
+        let mut during_load = false;
+        for _ in 0..=MAX_REDIRECTS {
+            let destination = request.destination();
+            let result = self.load(&destination, handle_url);
+
+            // 読み込めなかったページもUIがエラーページを表示するので、履歴に残す。
+            // ダウンロードとフラグメントへの移動は、表示するページが変わらないので残さない
+            let shown = result.is_err() || page.borrow().url().as_deref() == Some(&destination);
+            if shown {
+                let mut page = page.borrow_mut();
+                let history = page.session_history_mut();
+                match request.source() {
+                    _ if during_load => history.replace(&destination),
+                    NavigationSource::Back => history.go(-1),
+                    NavigationSource::Forward => history.go(1),
+                    NavigationSource::Reload => {}
+                    _ => history.push(&destination),
+                }
+                let title = page.title();
+                page.session_history_mut().set_title(&title);
+            }
+            result?;
+
+            let next = page.borrow_mut().take_script_navigation();
+            match next {
+                Some(next) => {
+                    request = NavigationRequest::new(&next, NavigationSource::Script);
+                    during_load = true;
+                }
+                None => return Ok(NavigationOutcome::Committed),
+            }
+        }
+
+        Err(Error::Other(format!(
+            "too many navigations by scripts (more than {})",
+            MAX_REDIRECTS
+        )))
+    }
+
+    /// アドレスバーに入力された文字列を、移動先のURLに変換する。スキームを持たない入力のうち、
+    /// "example.com/a"のように空白を含まずホスト名にドットがあるものはhttp://のURLとして扱い、
+    /// それ以外は検索語として扱う
+    pub fn resolve_input(&self, input: &str) -> String {
+        // "#"から始まる入力は、現在のページの中のフラグメントへの移動として扱う
+        if is_internal_url(input) || view_source_target(input).is_some() || input.starts_with('#') {
+            return input.to_string();
+        }
+
+        match Url::new(input.to_string()).parse() {
+            Err(UrlParseError::MissingScheme) if looks_like_host(input) => {
+                format!("http://{}", input)
+            }
+            Err(UrlParseError::MissingScheme) => self.search_url(input),
+            _ => input.to_string(),
+        }
+    }
+}
+
+/// "about:headers?"に続くクエリ
+fn headers_query(destination: &str) -> Option<&str> {
+    destination
+        .strip_prefix(ABOUT_HEADERS)
+        .and_then(|rest| rest.strip_prefix('?'))
+}
+
+/// スキームを省略したURLに見える入力かどうか。空白を含まず、ホスト名の部分がドットで区切られている
+fn looks_like_host(input: &str) -> bool {
+    if input.chars().any(|c| c.is_whitespace()) {
+        return false;
+    }
+    let host = input.split(['/', '?', '#']).next().unwrap_or("");
+    let host = host.rsplit_once(':').map_or(host, |(host, _)| host);
+    host.contains('.') && !host.starts_with('.') && !host.ends_with('.')
+}
+
+/// ユーザーのスタイルシートを、ユーザーのオリジンのStyleSheetとして解釈する
+fn parse_user_stylesheet(css: &str) -> StyleSheet {
+    let mut style = CssParser::new(CssTokenizer::new(css.to_string())).parse_stylesheet();
+    style.set_origin(CascadeOrigin::User);
+    style
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::constants::IMAGE_DECODE_ROWS_PER_TASK;
+    use crate::constants::MAX_FRAME_DEPTH;
+    use crate::constants::SCRIPT_WATCHDOG_INTERVAL;
+    use crate::internal_page::ABOUT_VERSION;
+    use crate::security::SecurityState;
+
+    #[test]
+    fn test_resolve_url_input() {
+        let browser = Browser::new();
+        assert_eq!(
+            "http://example.com/index.html".to_string(),
+            browser
+                .borrow()
+                .resolve_input("http://example.com/index.html")
+        );
+        assert_eq!(
+            "about:blank".to_string(),
+            browser.borrow().resolve_input("about:blank")
+        );
+        assert_eq!("#top".to_string(), browser.borrow().resolve_input("#top"));
+        // スキームを省略したホスト名は、http://のURLとして扱う
+        assert_eq!(
+            "http://example.com".to_string(),
+            browser.borrow().resolve_input("example.com")
+        );
+        assert_eq!(
+            "http://www.example.com:8000/a.html?q=1".to_string(),
+            browser
+                .borrow()
+                .resolve_input("www.example.com:8000/a.html?q=1")
+        );
+    }
+
+    #[test]
+    fn test_resolve_search_input() {
+        let browser = Browser::new();
+        assert_eq!(
+            "http://www.google.com/search?q=hello+world%21".to_string(),
+            browser.borrow().resolve_input("hello world!")
+        );
+        // ドットがあっても、空白を含む入力や文の終わりのドットは検索語として扱う
+        assert_eq!(
+            "http://www.google.com/search?q=see+example.com".to_string(),
+            browser.borrow().resolve_input("see example.com")
+        );
+        assert_eq!(
+            "http://www.google.com/search?q=hello.".to_string(),
+            browser.borrow().resolve_input("hello.")
+        );
+    }
+
+    #[test]
+    fn test_custom_search_engine() {
+        let browser = Browser::new();
+        browser
+            .borrow_mut()
+            .set_search_engine("http://search.example/?query=%s&lang=ja".to_string());
+        assert_eq!(
+            "http://search.example/?query=%E3%81%82&lang=ja".to_string(),
+            browser.borrow().resolve_input("あ")
+        );
+    }
+
+    #[test]
+    fn test_navigate() {
+        let handle_url = |url: String, _headers: Vec<Header>| {
+            assert_eq!("http://example.com/index.html", url);
+            HttpResponse::new(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><head><script>document.title = \"test\";</script></head><body><p>hello</p></body></html>"
+                    .to_string(),
+            )
+        };
+
+        let browser = Browser::new();
+        let page = browser
+            .borrow()
+            .navigate("http://example.com/index.html#top", &handle_url)
+            .expect("failed to navigate");
+
+        assert_eq!("http://example.com/index.html#top", page.url());
+        assert_eq!("test", page.title());
+        assert!(page.document().is_some());
+        assert!(page.dom_dump().contains(
+            "      Element(Element { kind: P, attributes: [] })\n        Text(\"hello\")\n"
+        ));
+        assert_eq!(
+            "\nBlock Element(Element { kind: Body, attributes: [] }) (0, 0) 590x20\n  Block Element(Element { kind: P, attributes: [] }) (0, 0) 590x20\n    Text Text(\"hello\") (0, 0) 40x20\n",
+            page.layout_dump()
+        );
+        // <body>と<p>の四角と、テキスト
+        assert_eq!(3, page.display_items().len());
+
+        // ページを取得できない場合は、エラーを返す
+        let result = browser
+            .borrow()
+            .navigate("http://example.com/missing.html", &|_, _| {
+                Err(Error::Network("failed to connect".to_string()))
+            });
+        assert!(result.is_err());
+
+        // about:domは、直前に表示していたページのDOMツリーを表示する
+        let page = browser
+            .borrow()
+            .navigate("about:dom", &handle_url)
+            .expect("failed to navigate");
+        assert!(page
+            .dom_dump()
+            .contains("Text(\"#text \\\"hello\\\" (line 1, column 70)\")"));
+    }
+
+    #[test]
+    fn test_retry() {
+        let online = Cell::new(false);
+        let handle_url = |_url: String, _headers: Vec<Header>| {
+            if !online.get() {
+                return Err(Error::Dns("example.com".to_string()));
+            }
+            HttpResponse::new(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><body><p>back online</p></body></html>"
+                    .to_string(),
+            )
+        };
+
+        // UIと同じように、読み込めなかったURLのエラーページを表示する
+        let browser = Browser::new();
+        let url = "http://example.com/index.html";
+        let error = browser
+            .borrow()
+            .load(url, &handle_url)
+            .expect_err("the first load should fail");
+        let page = browser.borrow().current_page();
+        page.borrow_mut().load_error_page(url, &error);
+        assert!(page
+            .borrow()
+            .document()
+            .map(|document| convert_dom_to_string(&Some(document)))
+            .unwrap_or_default()
+            .contains("ERR_NAME_NOT_RESOLVED"));
+
+        // "Retry"のリンクを開くと、読み込めなかったURLを取得し直す
+        online.set(true);
+        let retried = browser
+            .borrow()
+            .navigate(ABOUT_RETRY, &handle_url)
+            .expect("failed to retry");
+        assert!(retried.dom_dump().contains("Text(\"back online\")"));
+        assert_eq!(Some(url.to_string()), page.borrow().url());
+    }
+
+    #[test]
+    fn test_headers_page() {
+        let handle_url =
+            |_url: String, _headers: Vec<Header>| Err(Error::Network("unreachable".to_string()));
+        let storage = Rc::new(MemoryStorage::default());
+        let browser = Browser::new();
+        browser.borrow_mut().set_settings_storage(storage.clone());
+
+        // リンクやスクリプトからの移動では、クエリを無視して現在の値を表示する
+        let query = "about:headers?user-agent=test-agent&accept=text%2Fplain";
+        for source in [NavigationSource::Link, NavigationSource::Script] {
+            browser
+                .borrow()
+                .request_navigation(NavigationRequest::new(query, source), &handle_url)
+                .expect("failed to open about:headers");
+            assert_eq!(BrowserConfig::new(), browser.borrow().config());
+        }
+
+        browser
+            .borrow()
+            .request_navigation(
+                NavigationRequest::new(query, NavigationSource::AddressBar),
+                &handle_url,
+            )
+            .expect("failed to open about:headers");
+        let page = browser.borrow().current_page();
+        let dom = RenderedPage::new(ABOUT_HEADERS, &page.borrow()).dom_dump();
+        assert!(dom.contains("Text(\"User-Agent: test-agent\")"));
+        assert!(dom.contains("Text(\"Accept: text/plain\")"));
+
+        // 変更した値は、handle_urlが参照する設定に反映される
+        let config = browser.borrow().config();
+        assert_eq!("test-agent", config.user_agent());
+        assert_eq!("text/plain", config.accept());
+        assert_eq!(Some("about:headers".to_string()), page.borrow().url());
+
+        // 変更した値は保存し、次に起動したブラウザが読み込む
+        let restarted = Browser::new();
+        restarted.borrow_mut().set_settings_storage(storage);
+        assert_eq!(config, restarted.borrow().config());
+
+        assert!(browser
+            .borrow()
+            .request_navigation(
+                NavigationRequest::new("about:headers?unknown=1", NavigationSource::AddressBar),
+                &handle_url,
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_security_state() {
+        let handle_url = |url: String, _headers: Vec<Header>| {
+            if url.starts_with("https://expired.example") {
+                return Err(Error::Certificate("certificate has expired".to_string()));
+            }
+            HttpResponse::new(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><body><p>hi</p></body></html>"
+                    .to_string(),
+            )
+        };
+
+        let browser = Browser::new();
+        let page = browser.borrow().current_page();
+        let states = [
+            ("about:blank", SecurityState::Internal),
+            ("http://example.com/", SecurityState::Insecure),
+            ("https://example.com/", SecurityState::Unverified),
+        ];
+        for (url, state) in states {
+            browser
+                .borrow()
+                .load(url, &handle_url)
+                .expect("failed to load");
+            assert_eq!(state, page.borrow().security_state());
+        }
+
+        // 証明書を検証できなかった場合は、エラーページに理由を持つ警告を表示する
+        let url = "https://expired.example/";
+        let error = browser
+            .borrow()
+            .load(url, &handle_url)
+            .expect_err("the load should fail");
+        page.borrow_mut().load_error_page(url, &error);
+        assert_eq!(
+            SecurityState::CertificateError("certificate has expired".to_string()),
+            page.borrow().security_state()
+        );
+    }
+
+    #[derive(Default)]
+    struct DownloadObserver {
+        names: RefCell<Vec<String>>,
+    }
+
+    impl PageObserver for DownloadObserver {
+        fn on_download(&self, download: &Download) {
+            self.names.borrow_mut().push(download.name());
+        }
+    }
+
+    #[test]
+    fn test_download() {
+        let handle_url = |url: String, _headers: Vec<Header>| {
+            let response: &[u8] = match url.as_str() {
+                "http://example.com/index.html" => {
+                    b"HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><body><p>hello</p></body></html>"
+                }
+                "http://example.com/logo.png" => {
+                    b"HTTP/1.1 200 OK\nContent-Type: image/png\n\n\x89PNG\xff"
+                }
+                _ => {
+                    b"HTTP/1.1 200 OK\nContent-Type: text/csv\nContent-Disposition: attachment; filename=\"data.csv\"\n\na,b\n"
+                }
+            };
+            HttpResponse::from_bytes(response)
+        };
+
+        let browser = Browser::new();
+        let observer = Rc::new(DownloadObserver::default());
+        browser.borrow_mut().add_observer(observer.clone());
+        browser
+            .borrow()
+            .load("http://example.com/index.html", &handle_url)
+            .expect("failed to load");
+        let items = browser.borrow().current_page().borrow().display_items();
+
+        // 画像と添付ファイルはダウンロードし、表示中のページはそのまま残す
+        browser
+            .borrow()
+            .load("http://example.com/logo.png", &handle_url)
+            .expect("failed to download");
+        browser
+            .borrow()
+            .load("http://example.com/export?format=csv", &handle_url)
+            .expect("failed to download");
+        assert_eq!(
+            items,
+            browser.borrow().current_page().borrow().display_items()
+        );
+
+        let downloads = browser.borrow().downloads();
+        assert_eq!(2, downloads.len());
+        assert_eq!("logo.png", downloads[0].name());
+        assert_eq!("image/png", downloads[0].mime_type());
+        assert_eq!(
+            alloc::vec![0x89, b'P', b'N', b'G', 0xff],
+            downloads[0].bytes()
+        );
+        assert_eq!("data.csv", downloads[1].name());
+        assert_eq!(4, downloads[1].size());
+        assert_eq!(
+            alloc::vec!["logo.png".to_string(), "data.csv".to_string()],
+            *observer.names.borrow()
+        );
+    }
+
+    #[derive(Default)]
+    struct IconObserver {
+        icons: RefCell<Vec<String>>,
+    }
+
+    impl PageObserver for IconObserver {
+        fn on_favicon(&self, url: &str) {
+            self.icons.borrow_mut().push(url.to_string());
+        }
+    }
+
+    #[test]
+    fn test_favicon() {
+        let handle_url = |url: String, _headers: Vec<Header>| {
+            let response: &[u8] = match url.as_str() {
+                "http://example.com/dir/index.html" => {
+                    b"HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><head><link rel=stylesheet href=a.css><link rel=\"shortcut icon\" href=icon.png><link rel=icon href=/second.png></head><body></body></html>"
+                }
+                "http://example.com/missing.html" => {
+                    b"HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><head><link rel=icon href=/none.png></head><body></body></html>"
+                }
+                "http://example.com/dir/icon.png" => {
+                    b"HTTP/1.1 200 OK\nContent-Type: image/png\n\n\x89PNG"
+                }
+                _ => b"HTTP/1.1 404 Not Found\nContent-Length: 0\n\n",
+            };
+            HttpResponse::from_bytes(response)
+        };
+
+        let browser = Browser::new();
+        let observer = Rc::new(IconObserver::default());
+        browser.borrow_mut().add_observer(observer.clone());
+        browser
+            .borrow()
+            .load("http://example.com/dir/index.html", &handle_url)
+            .expect("failed to load");
+        // 最初のアイコンだけを、ページのURLで解決して取得する
+        assert_eq!(
+            alloc::vec!["http://example.com/dir/icon.png".to_string()],
+            *observer.icons.borrow()
+        );
+
+        // 取得できなかったアイコンは知らせず、読み込めなかったリソースとして数える
+        browser
+            .borrow()
+            .load("http://example.com/missing.html", &handle_url)
+            .expect("failed to load");
+        assert_eq!(1, observer.icons.borrow().len());
+        assert_eq!(
+            1,
+            browser
+                .borrow()
+                .current_page()
+                .borrow()
+                .subresource_errors()
+        );
+    }
+
+    #[derive(Default)]
+    struct RecordingLogger {
+        messages: RefCell<Vec<String>>,
+    }
+
+    impl Logger for RecordingLogger {
+        fn log(&self, level: LogLevel, message: &str) {
+            self.messages
+                .borrow_mut()
+                .push(format!("{}: {}", level.name(), message));
+        }
+    }
+
+    struct FixedClock(u64);
+
+    /// テストはstdの環境で動くので、ui/cliと同じくcatch_unwindでパニックを捕まえる
+    struct CatchUnwindGuard;
+
+    impl PanicGuard for CatchUnwindGuard {
+        fn run(&self, f: &mut dyn FnMut()) -> Result<(), String> {
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).map_err(|payload| {
+                match payload.downcast_ref::<&str>() {
+                    Some(message) => message.to_string(),
+                    None => "unknown panic".to_string(),
+                }
+            })
+        }
+    }
+
+    /// 尋ねられたら、すぐにスクリプトを中断する
+    struct StoppingWatchdog;
+
+    impl ScriptWatchdog for StoppingWatchdog {
+        fn should_interrupt(&self, _ticks: u64) -> bool {
+            true
+        }
+    }
+
+    impl Clock for FixedClock {
+        fn now(&self) -> Option<u64> {
+            Some(self.0)
+        }
+    }
+
+    #[test]
+    fn test_console() {
+        let handle_url = |_url: String, _headers: Vec<Header>| {
+            HttpResponse::new(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><head><script>var a=1; console.log(\"loaded\");</script></head><body><p>hello</p></body></html>"
+                    .to_string(),
+            )
+        };
+
+        let browser = Browser::new();
+        browser
+            .borrow()
+            .load("http://example.com/", &handle_url)
+            .expect("failed to load");
+
+        // ページのスクリプトで定義された変数を使って評価する
+        let entries = browser.borrow().evaluate_console_input("a + 1");
+        assert_eq!(
+            alloc::vec![ConsoleEntry::new(ConsoleEntryKind::Result, "2")],
+            entries
+        );
+
+        // about:consoleを表示している間も、直前のページの環境で評価する
+        browser
+            .borrow()
+            .load(ABOUT_CONSOLE, &handle_url)
+            .expect("failed to load");
+        let entries = browser.borrow().evaluate_console_input("foo()");
+        assert_eq!(
+            alloc::vec![ConsoleEntry::new(
+                ConsoleEntryKind::Error,
+                "ReferenceError: foo is not defined"
+            )],
+            entries
+        );
+
+        let lines: Vec<String> = browser
+            .borrow()
+            .console_entries()
+            .iter()
+            .map(|e| e.line())
+            .collect();
+        assert_eq!(
+            alloc::vec![
+                "loaded".to_string(),
+                "$ a + 1".to_string(),
+                "= 2".to_string(),
+                "$ foo()".to_string(),
+                "! ReferenceError: foo is not defined".to_string(),
+            ],
+            lines
+        );
+        let page = RenderedPage::new(ABOUT_CONSOLE, &browser.borrow().current_page().borrow());
+        assert!(page
+            .dom_dump()
+            .contains("Text(\"! ReferenceError: foo is not defined\")"));
+    }
+
+    #[test]
+    fn test_panic_guard() {
+        // スタイルシートの取得は読み込みの途中で行われるので、そこでパニックさせる
+        let handle_url = |url: String, _headers: Vec<Header>| {
+            if url.ends_with("/style.css") {
+                panic!("boom");
+            }
+            HttpResponse::new(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><head><link rel=\"stylesheet\" href=\"style.css\"></head><body></body></html>"
+                    .to_string(),
+            )
+        };
+
+        let browser = Browser::new();
+        let logger = Rc::new(RecordingLogger::default());
+        browser.borrow_mut().set_logger(logger.clone());
+        browser
+            .borrow_mut()
+            .set_panic_guard(Rc::new(CatchUnwindGuard));
+
+        // パニックはBrowserの外に伝わらず、読み込みのエラーになる
+        assert_eq!(
+            Err(Error::Crashed("boom".to_string())),
+            browser.borrow().load("http://example.com/", &handle_url)
+        );
+        assert_eq!(
+            Some(&"error: the page crashed while loading http://example.com/: boom".to_string()),
+            logger.messages.borrow().last()
+        );
+
+        // 内部ページはネットワークのページと違い、ガードを通さずに読み込める
+        assert!(browser.borrow().load("about:blank", &handle_url).is_ok());
+    }
+
+    #[test]
+    fn test_malformed_page_without_panic_guard() {
+        // 以前はパニックしていた、解釈できないCSSとスクリプトを含むページ
+        let handle_url = |_url: String, _headers: Vec<Header>| {
+            HttpResponse::new(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><head><style>p { color: red; } % { color: blue; } @media</style><script>var a = 1 % 2;</script></head><body><p>hello</p></body></html>"
+                    .to_string(),
+            )
+        };
+
+        // 既定のNoPanicGuardでも、ページの内容が原因でブラウザが止まらない
+        let browser = Browser::new();
+        browser
+            .borrow()
+            .load("http://example.com/", &handle_url)
+            .expect("failed to load");
+
+        assert_eq!(
+            alloc::vec!["! SyntaxError: Invalid or unexpected token '%'".to_string()],
+            browser
+                .borrow()
+                .console_entries()
+                .iter()
+                .map(|e| e.line())
+                .collect::<Vec<String>>()
+        );
+        let page = RenderedPage::new(
+            "http://example.com/",
+            &browser.borrow().current_page().borrow(),
+        );
+        assert!(page.dom_dump().contains("Text(\"hello\")"));
+    }
+
+    #[test]
+    fn test_script_watchdog() {
+        let handle_url = |_url: String, _headers: Vec<Header>| {
+            let mut script = "var n = 0; ".to_string();
+            for _ in 0..SCRIPT_WATCHDOG_INTERVAL {
+                script.push_str("n = n + 1; ");
+            }
+            script.push_str("console.log(\"done\");");
+            HttpResponse::new(format!(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><head><script>{}</script></head><body></body></html>",
+                script
+            ))
+        };
+
+        let browser = Browser::new();
+        browser
+            .borrow_mut()
+            .set_script_watchdog(Rc::new(StoppingWatchdog));
+        browser
+            .borrow()
+            .load("http://example.com/", &handle_url)
+            .expect("failed to load");
+
+        // 中断したスクリプトは最後まで実行されず、中断したことがコンソールに残る
+        let entries = browser.borrow().console_entries();
+        assert_eq!(1, entries.len());
+        assert!(entries[0].line().contains("page was unresponsive"));
+    }
+
+    #[test]
+    fn test_user_stylesheet() {
+        let handle_url = |_url: String, _headers: Vec<Header>| {
+            HttpResponse::new(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><head><style>p { color: red; }</style></head><body><p>author</p><h1>heading</h1></body></html>"
+                    .to_string(),
+            )
+        };
+
+        let storage = Rc::new(MemoryStorage::default());
+        let browser = Browser::new();
+        browser.borrow_mut().set_settings_storage(storage.clone());
+        browser
+            .borrow_mut()
+            .set_user_stylesheet("p { color: green; } h1 { color: green; }");
+        let load = |browser: &Rc<RefCell<Browser>>, url: &str| {
+            browser
+                .borrow()
+                .load(url, &handle_url)
+                .expect("failed to load");
+            let page = browser.borrow().current_page();
+            let colors: Vec<(String, u32)> = page
+                .borrow()
+                .display_items()
+                .into_iter()
+                .filter_map(|item| match item {
+                    DisplayItem::Text { text, style, .. } => Some((text, style.color().code_u32())),
+                    _ => None,
+                })
+                .collect();
+            colors
+        };
+
+        // 作者のスタイルはユーザーのスタイルより優先する
+        assert_eq!(
+            alloc::vec![
+                ("author".to_string(), 0xff0000),
+                ("heading".to_string(), 0x008000),
+            ],
+            load(&browser, "http://example.com/")
+        );
+
+        // 内部ページには作者のスタイルを使わず、ユーザーのスタイルは内部ページのスタイルより優先する
+        let colors = load(&browser, ABOUT_VERSION);
+        assert!(!colors.is_empty());
+        assert!(colors.iter().all(|(_, color)| *color == 0x008000));
+
+        // 設定したスタイルシートは保存し、次に起動したブラウザが読み込む
+        let restarted = Browser::new();
+        restarted.borrow_mut().set_settings_storage(storage);
+        assert_eq!(
+            load(&browser, "http://example.com/"),
+            load(&restarted, "http://example.com/")
+        );
+    }
+
+    #[test]
+    fn test_nested_iframe() {
+        let requested = RefCell::new(Vec::new());
+        let handle_url = |url: String, _headers: Vec<Header>| {
+            requested.borrow_mut().push(url.clone());
+            let body = match url.as_str() {
+                "http://example.com/" => "<iframe src=/outer.html></iframe>",
+                "http://example.com/outer.html" => "<iframe src=/inner.html></iframe>",
+                "http://example.com/loop.html" => "<iframe src=/loop.html></iframe>",
+                _ => "<p>inner</p>",
+            };
+            HttpResponse::new(format!(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><head></head><body>{}</body></html>",
+                body
+            ))
+        };
+
+        let browser = Browser::new();
+        browser
+            .borrow()
+            .load("http://example.com/", &handle_url)
+            .expect("failed to load");
+        assert_eq!(
+            alloc::vec![
+                "http://example.com/".to_string(),
+                "http://example.com/outer.html".to_string(),
+                "http://example.com/inner.html".to_string(),
+            ],
+            *requested.borrow()
+        );
+        let page = browser.borrow().current_page();
+        assert!(page
+            .borrow()
+            .display_items()
+            .iter()
+            .any(|item| matches!(item, DisplayItem::Text { text, .. } if text == "inner")));
+
+        // 自分自身を指す<iframe>は、深さの上限まで読み込んで止める
+        requested.borrow_mut().clear();
+        browser
+            .borrow()
+            .load("http://example.com/loop.html", &handle_url)
+            .expect("failed to load");
+        assert_eq!(MAX_FRAME_DEPTH + 1, requested.borrow().len());
+    }
+
+    #[test]
+    fn test_platform() {
+        let sent = core::cell::Cell::new(0);
+        let handle_url = |url: String, _headers: Vec<Header>| {
+            if url != "http://example.com/" {
+                return Err(Error::Network("failed to connect".to_string()));
+            }
+            sent.set(sent.get() + 1);
+            // Sun, 06 Nov 1994 08:49:37 GMTは784111777秒
+            HttpResponse::new(
+                "HTTP/1.1 200 OK\nDate: Sun, 06 Nov 1994 08:49:37 GMT\nCache-Control: max-age=60\n\nhello"
+                    .to_string(),
+            )
+        };
+
+        let browser = Browser::new();
+        let logger = Rc::new(RecordingLogger::default());
+        browser.borrow_mut().set_logger(logger.clone());
+
+        browser
+            .borrow()
+            .fetch("http://example.com/".to_string(), &handle_url)
+            .expect("failed to fetch");
+        assert!(browser
+            .borrow()
+            .fetch("http://example.com/missing".to_string(), &handle_url)
+            .is_err());
+        assert_eq!(
+            alloc::vec![
+                "info: fetching http://example.com/".to_string(),
+                "info: fetching http://example.com/missing".to_string(),
+                "error: failed to fetch http://example.com/missing: Network(\"failed to connect\")"
+                    .to_string(),
+            ],
+            *logger.messages.borrow()
+        );
+
+        // 時計がない間は、レスポンスのDateを現在時刻とみなすので、キャッシュが新鮮なまま
+        browser
+            .borrow()
+            .fetch("http://example.com/".to_string(), &handle_url)
+            .expect("failed to fetch");
+        assert_eq!(1, sent.get());
+
+        // 時計の時刻ではmax-ageを過ぎているので、取得し直す
+        browser
+            .borrow_mut()
+            .set_clock(Rc::new(FixedClock(784111777 + 120)));
+        browser
+            .borrow()
+            .fetch("http://example.com/".to_string(), &handle_url)
+            .expect("failed to fetch");
+        assert_eq!(2, sent.get());
+    }
+
+    #[test]
+    fn test_resource_hints() {
+        let sent = RefCell::new(Vec::new());
+        let handle_url = |url: String, _headers: Vec<Header>| {
+            sent.borrow_mut().push(url.clone());
+            let body = match url.as_str() {
+                "http://example.com/dir/index.html" => {
+                    "<html><head><link rel=prefetch href=next.html><link rel=preload as=font href=/a.woff><link rel=preload as=style href=/a.css><link rel=stylesheet href=/b.css></head><body></body></html>"
+                }
+                "http://example.com/other.html" => {
+                    "<html><head><link rel=preload as=image href=/c.png></head><body></body></html>"
+                }
+                _ => "",
+            };
+            HttpResponse::new(format!(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\nCache-Control: max-age=60\n\n{}",
+                body
+            ))
+        };
+
+        let browser = Browser::new();
+        browser
+            .borrow()
+            .load("http://example.com/dir/index.html", &handle_url)
+            .expect("failed to load");
+        assert_eq!(2, browser.borrow().pending_resource_hints());
+
+        // タスクが残っている間は、先読みしない
+        browser
+            .borrow()
+            .post_task(TaskPriority::Timer, alloc::boxed::Box::new(|| {}));
+        assert_eq!(0, browser.borrow().load_resource_hints(&handle_url));
+        browser.borrow().run_tasks();
+
+        // preloadをprefetchより先に取得する
+        assert_eq!(1, browser.borrow().load_resource_hints(&handle_url));
+        assert_eq!(
+            Some(&"http://example.com/a.css".to_string()),
+            sent.borrow().last()
+        );
+
+        // ページを離れると、そのページのpreloadは取り消すが、prefetchは残す。
+        // rel=stylesheetは先読みではなく、ページを読み込むときに取得する
+        browser
+            .borrow()
+            .load("http://example.com/other.html", &handle_url)
+            .expect("failed to load");
+        while browser.borrow().load_resource_hints(&handle_url) > 0 {}
+        assert_eq!(
+            alloc::vec![
+                "http://example.com/dir/index.html".to_string(),
+                "http://example.com/b.css".to_string(),
+                "http://example.com/a.css".to_string(),
+                "http://example.com/other.html".to_string(),
+                "http://example.com/c.png".to_string(),
+                "http://example.com/dir/next.html".to_string(),
+            ],
+            *sent.borrow()
+        );
+
+        // 先読みしたページは、HTTPキャッシュから読み込む
+        browser
+            .borrow()
+            .load("http://example.com/dir/next.html", &handle_url)
+            .expect("failed to load");
+        assert_eq!(6, sent.borrow().len());
+    }
+
+    #[test]
+    fn test_subresource_errors() {
+        let handle_url = |url: String, _headers: Vec<Header>| {
+            match url.as_str() {
+            "http://example.com/" => HttpResponse::new(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><head><link rel=preload as=style href=/a.css></head><body><iframe src=http://example.com/missing></iframe><iframe src=http://down.example/></iframe></body></html>"
+                    .to_string(),
+            ),
+            "http://example.com/missing" | "http://example.com/a.css" => {
+                HttpResponse::new("HTTP/1.1 404 Not Found\n\n".to_string())
+            }
+            "http://example.com/ok" => HttpResponse::new("HTTP/1.1 200 OK\n\nok".to_string()),
+            _ => Err(Error::Network("failed to connect".to_string())),
+        }
+        };
+
+        let browser = Browser::new();
+        let observer = Rc::new(PermissionObserver::default());
+        browser.borrow_mut().add_observer(observer.clone());
+        browser
+            .borrow()
+            .load("http://example.com/", &handle_url)
+            .expect("failed to load");
+        while browser.borrow().load_resource_hints(&handle_url) > 0 {}
+
+        // 読み込めなかった子フレームと先読みを、コンソールのエラーとしてまとめる
+        let page = browser.borrow().current_page();
+        assert_eq!(3, page.borrow().subresource_errors());
+        assert_eq!(
+            alloc::vec![
+                "! failed to load http://example.com/missing: 404 Not Found".to_string(),
+                "! failed to load http://down.example/: Network(\"failed to connect\")".to_string(),
+                "! failed to load http://example.com/a.css: 404 Not Found".to_string(),
+            ],
+            browser
+                .borrow()
+                .console_entries()
+                .iter()
+                .map(|e| e.line())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(3, observer.messages.borrow().len());
+
+        // 別のページに移動すると、数え直す
+        browser
+            .borrow()
+            .load("http://example.com/ok", &handle_url)
+            .expect("failed to load");
+        assert_eq!(0, page.borrow().subresource_errors());
+    }
+
+    #[test]
+    fn test_image() {
+        let height = IMAGE_DECODE_ROWS_PER_TASK + 1;
+        let handle_url = move |url: String, _headers: Vec<Header>| {
+            match url.as_str() {
+            "http://example.com/" => HttpResponse::new(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><body><img src=a.ppm alt=A><img src=b.png alt=B><img src=/missing.ppm alt=C><img src=a.ppm width=4></body></html>"
+                    .to_string(),
+            ),
+            "http://example.com/a.ppm" => {
+                let mut raw = format!(
+                    "HTTP/1.1 200 OK\nContent-Type: image/x-portable-pixmap\n\nP6\n2 {}\n255\n",
+                    height
+                )
+                .into_bytes();
+                for _ in 0..2 * height {
+                    raw.extend_from_slice(&[0xff, 0x00, 0x00]);
+                }
+                HttpResponse::from_bytes(&raw)
+            }
+            "http://example.com/b.png" => HttpResponse::from_bytes(
+                b"HTTP/1.1 200 OK\nContent-Type: image/png\n\n\x89PNG\r\n\x1a\n\x00",
+            ),
+            _ => HttpResponse::new("HTTP/1.1 404 Not Found\n\n".to_string()),
+        }
+        };
+
+        let browser = Browser::new();
+        browser
+            .borrow()
+            .load("http://example.com/", &handle_url)
+            .expect("failed to load");
+        let page = browser.borrow().current_page();
+        let images = || {
+            page.borrow()
+                .display_items()
+                .into_iter()
+                .filter_map(|item| match item {
+                    DisplayItem::Image {
+                        image,
+                        layout_point,
+                    } => Some((image, layout_point)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // デコードを始める前から、画像の大きさで場所を空けておく
+        let drawn = images();
+        assert_eq!(2, drawn.len());
+        assert_eq!((2, height), (drawn[0].0.width(), drawn[0].0.height()));
+        assert_eq!(0, drawn[0].0.decoded_rows());
+        // width属性だけを指定した画像は、縦横比を保って拡大する
+        assert_eq!((4, height * 2), (drawn[1].0.width(), drawn[1].0.height()));
+
+        while browser.borrow().run_tasks() > 0 {}
+        let drawn = images();
+        assert!(drawn[0].0.is_complete());
+        assert_eq!(Some(0xff0000), drawn[0].0.pixel(1, height - 1));
+        assert!(drawn[1].0.is_complete());
+
+        // デコードできない画像と取得できない画像は、alt属性の文字列を表示して、読み込めなかったものとして数える
+        assert_eq!(2, page.borrow().subresource_errors());
+        let texts: Vec<String> = page
+            .borrow()
+            .display_items()
+            .into_iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text { text, .. } => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(alloc::vec!["B".to_string(), "C".to_string()], texts);
+        assert_eq!(
+            alloc::vec![
+                "! failed to load http://example.com/b.png: UnexpectedInput(\"PNG images are not supported\")"
+                    .to_string(),
+                "! failed to load http://example.com/missing.ppm: 404 Not Found".to_string(),
+            ],
+            browser
+                .borrow()
+                .console_entries()
+                .iter()
+                .map(|e| e.line())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_stylesheet() {
+        let version = Cell::new(1);
+        let handle_url = |url: String, headers: Vec<Header>| {
+            let etag = format!("\"v{}\"", version.get());
+            match url.as_str() {
+            "http://example.com/a" | "http://example.com/b" => HttpResponse::new(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><head><link rel=stylesheet href=/site.css><link rel=stylesheet href=/missing.css><style>.inline { color: red; }</style></head><body><p class=linked>linked</p><p class=inline>inline</p></body></html>"
+                    .to_string(),
+            ),
+            "http://example.com/site.css" => {
+                if headers.iter().any(|h| h.name() == "If-None-Match" && h.value() == etag) {
+                    return HttpResponse::new("HTTP/1.1 304 Not Modified\n\n".to_string());
+                }
+                let color = if version.get() == 1 { "blue" } else { "green" };
+                HttpResponse::new(format!(
+                    "HTTP/1.1 200 OK\nContent-Type: text/css\nETag: {}\nCache-Control: no-cache\n\n.linked {{ color: {}; }} .inline {{ color: blue; }}",
+                    etag, color
+                ))
+            }
+            _ => HttpResponse::new("HTTP/1.1 404 Not Found\n\n".to_string()),
+        }
+        };
+
+        let browser = Browser::new();
+        let logger = Rc::new(RecordingLogger::default());
+        browser.borrow_mut().set_logger(logger.clone());
+        let load = |url: &str| {
+            browser
+                .borrow()
+                .load(url, &handle_url)
+                .expect("failed to load");
+            let page = browser.borrow().current_page();
+            let colors: Vec<(String, u32)> = page
+                .borrow()
+                .display_items()
+                .into_iter()
+                .filter_map(|item| match item {
+                    DisplayItem::Text { text, style, .. } => Some((text, style.color().code_u32())),
+                    _ => None,
+                })
+                .collect();
+            colors
+        };
+        let reused = || {
+            logger
+                .messages
+                .borrow()
+                .iter()
+                .filter(|m| m.contains("using the parsed stylesheet"))
+                .count()
+        };
+
+        // <style>のルールは、<link>のスタイルシートのルールより優先する
+        let expected = |linked: u32| {
+            alloc::vec![
+                ("linked".to_string(), linked),
+                ("inline".to_string(), 0xff0000),
+            ]
+        };
+        assert_eq!(expected(0x0000ff), load("http://example.com/a"));
+        assert_eq!(0, reused());
+        assert_eq!(
+            1,
+            browser
+                .borrow()
+                .current_page()
+                .borrow()
+                .subresource_errors()
+        );
+
+        // 同じサイトの別のページでは、再検証して変わっていなければ解釈し直さない
+        assert_eq!(expected(0x0000ff), load("http://example.com/b"));
+        assert_eq!(1, reused());
+
+        // スタイルシートが変わると、ETagが変わるので解釈し直す
+        version.set(2);
+        assert_eq!(expected(0x008000), load("http://example.com/a"));
+        assert_eq!(1, reused());
+        assert!(browser
+            .borrow()
+            .http_cache
+            .borrow()
+            .stylesheet("http://example.com/site.css")
+            .is_some());
+    }
+
+    #[derive(Default)]
+    struct PermissionObserver {
+        requests: RefCell<Vec<String>>,
+        messages: RefCell<Vec<String>>,
+    }
+
+    impl PageObserver for PermissionObserver {
+        fn on_permission_request(&self, origin: &str, permission: Permission) {
+            self.requests
+                .borrow_mut()
+                .push(format!("{} {}", origin, permission.name()));
+        }
+
+        fn on_console_message(&self, message: &str) {
+            self.messages.borrow_mut().push(message.to_string());
+        }
+    }
+
+    /// 保存した値をメモリに持つSettingsStorage
+    #[derive(Default)]
+    struct MemoryStorage {
+        values: RefCell<Vec<(String, String)>>,
+    }
+
+    impl SettingsStorage for MemoryStorage {
+        fn load(&self, key: &str) -> Option<String> {
+            self.values
+                .borrow()
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.clone())
+        }
+
+        fn save(&self, key: &str, value: &str) {
+            let mut values = self.values.borrow_mut();
+            values.retain(|(k, _)| k != key);
+            values.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_javascript_permission() {
+        let handle_url = |_url: String, _headers: Vec<Header>| {
+            HttpResponse::new(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><head><script>console.log(\"hi\");</script></head><body></body></html>"
+                    .to_string(),
+            )
+        };
+
+        let browser = Browser::new();
+        let observer = Rc::new(PermissionObserver::default());
+        browser.borrow_mut().add_observer(observer.clone());
+        let storage = Rc::new(MemoryStorage::default());
+        browser.borrow_mut().set_settings_storage(storage.clone());
+        browser
+            .borrow()
+            .set_default_permission(Permission::JavaScript, PermissionState::Ask);
+
+        // 答えが出るまでは、スクリプトを実行しない
+        browser
+            .borrow()
+            .load("http://example.com/", &handle_url)
+            .expect("failed to load");
+        assert!(observer.messages.borrow().is_empty());
+        assert_eq!(
+            alloc::vec!["http://example.com javascript".to_string()],
+            *observer.requests.borrow()
+        );
+
+        browser
+            .borrow()
+            .set_permission("http://example.com/", Permission::JavaScript, true)
+            .expect("failed to set the permission");
+        browser
+            .borrow()
+            .load("http://example.com/", &handle_url)
+            .expect("failed to load");
+        assert_eq!(alloc::vec!["hi".to_string()], *observer.messages.borrow());
+        assert!(browser
+            .borrow()
+            .set_permission("about:blank", Permission::JavaScript, true)
+            .is_err());
+
+        // 保存した権限は、新しいBrowserでも使える
+        let restarted = Browser::new();
+        restarted.borrow_mut().set_settings_storage(storage);
+        restarted
+            .borrow()
+            .set_default_permission(Permission::JavaScript, PermissionState::Block);
+        assert_eq!(
+            PermissionState::Allow,
+            restarted
+                .borrow()
+                .permission_state("http://example.com/other", Permission::JavaScript)
+        );
+        assert_eq!(
+            PermissionState::Block,
+            restarted
+                .borrow()
+                .permission_state("http://example.com:8080/", Permission::JavaScript)
+        );
+    }
+
+    #[test]
+    fn test_cookie_permission() {
+        let sent_cookies = RefCell::new(Vec::new());
+        let handle_url = |_url: String, headers: Vec<Header>| {
+            let cookie = headers
+                .iter()
+                .find(|h| h.name() == "Cookie")
+                .map(|h| h.value());
+            sent_cookies.borrow_mut().push(cookie);
+            HttpResponse::new(
+                "HTTP/1.1 200 OK\nSet-Cookie: session=1; Path=/\nCache-Control: no-store\n\nhello"
+                    .to_string(),
+            )
+        };
+
+        let browser = Browser::new();
+        for _ in 0..2 {
+            browser
+                .borrow()
+                .fetch("http://example.com/".to_string(), &handle_url)
+                .expect("failed to fetch");
+        }
+        assert_eq!(
+            alloc::vec![None, Some("session=1".to_string())],
+            *sent_cookies.borrow()
+        );
+
+        // ブロックすると、受け取ったCookieを削除し、送りも保存もしない
+        browser
+            .borrow()
+            .set_permission("http://example.com/", Permission::Cookies, false)
+            .expect("failed to set the permission");
+        browser
+            .borrow()
+            .fetch("http://example.com/".to_string(), &handle_url)
+            .expect("failed to fetch");
+        assert_eq!(None, sent_cookies.borrow()[2]);
+        assert_eq!(None, browser.borrow().cookie_header("http://example.com/"));
+    }
+
+    #[test]
+    fn test_open_page() {
+        let requests = RefCell::new(Vec::new());
+        let handle_url = |url: String, headers: Vec<Header>| {
+            let cookie = headers
+                .iter()
+                .find(|h| h.name() == "Cookie")
+                .map(|h| h.value());
+            requests.borrow_mut().push((url.clone(), cookie));
+            HttpResponse::new(format!(
+                "HTTP/1.1 200 OK\nSet-Cookie: session=1\nCache-Control: max-age=60\n\n<html><body>{}</body></html>",
+                url
+            ))
+        };
+
+        let browser = Browser::new();
+        browser
+            .borrow()
+            .load("http://example.com/a", &handle_url)
+            .expect("failed to load");
+
+        // 新しいページを作っても、入力を処理するページは変わらない
+        let index = browser.borrow_mut().open_page();
+        assert_eq!(1, index);
+        assert_eq!(2, browser.borrow().page_count());
+        assert_eq!(0, browser.borrow().active_page_index());
+        assert!(browser.borrow_mut().set_active_page(2).is_err());
+
+        browser
+            .borrow_mut()
+            .set_active_page(index)
+            .expect("failed to switch pages");
+        browser
+            .borrow()
+            .load("http://example.com/b", &handle_url)
+            .expect("failed to load");
+        // 同じURLはキャッシュを共有するので、ネットワークを使わない
+        browser
+            .borrow()
+            .load("http://example.com/a", &handle_url)
+            .expect("failed to load");
+        assert_eq!(
+            alloc::vec![
+                ("http://example.com/a".to_string(), None),
+                (
+                    "http://example.com/b".to_string(),
+                    Some("session=1".to_string())
+                ),
+            ],
+            *requests.borrow()
+        );
+
+        // それぞれのページは、自分が読み込んだ内容を持ち続ける
+        browser
+            .borrow_mut()
+            .set_active_page(0)
+            .expect("failed to switch pages");
+        assert_eq!(
+            Some("http://example.com/a".to_string()),
+            browser.borrow().current_page().borrow().url()
+        );
+        assert_eq!(
+            Some("http://example.com/a".to_string()),
+            browser.borrow().pages[index].borrow().url()
+        );
+    }
+
+    #[test]
+    fn test_export_svg() {
+        let storage = Rc::new(MemoryStorage::default());
+        let browser = Browser::new();
+        browser.borrow_mut().set_settings_storage(storage.clone());
+        browser
+            .borrow()
+            .current_page()
+            .borrow_mut()
+            .receive_response(
+                HttpResponse::new(
+                    "HTTP/1.1 200 OK\n\n<html><body><p>hello</p></body></html>".to_string(),
+                )
+                .expect("failed to parse the response"),
+            );
+
+        let svg = browser.borrow().export_svg("page.svg");
+        assert!(svg.contains(">hello</text>"));
+        assert_eq!(Some(svg), storage.load("page.svg"));
+    }
+
+    #[test]
+    fn test_request_navigation() {
+        let handle_url = |url: String, _headers: Vec<Header>| {
+            let body = match url.as_str() {
+                "http://example.com/form.html" => "<html><head><script>function leave() { return \"unsaved\"; } window.onbeforeunload = leave;</script></head><body><p>form</p></body></html>",
+                "http://example.com/redirect.html" => "<html><head><script>location.href = \"/landing.html\";</script></head></html>",
+                _ => "<html><head><title>Next</title></head><body><p>next</p></body></html>",
+            };
+            HttpResponse::new(format!(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n{}",
+                body
+            ))
+        };
+        let browser = Browser::new();
+        let browser = browser.borrow();
+        let page = browser.current_page();
+        let navigate = |destination: &str| {
+            browser.request_navigation(
+                NavigationRequest::new(destination, NavigationSource::AddressBar),
+                &handle_url,
+            )
+        };
+
+        assert_eq!(
+            Ok(NavigationOutcome::Committed),
+            navigate("http://example.com/form.html")
+        );
+        // beforeunloadのハンドラが確認を求めるので、答えを待つ
+        assert_eq!(
+            Ok(NavigationOutcome::Blocked),
+            navigate("http://example.com/redirect.html")
+        );
+        assert_eq!(
+            Some("http://example.com/redirect.html".to_string()),
+            browser.pending_navigation().map(|r| r.destination())
+        );
+        assert_eq!(
+            Ok(NavigationOutcome::Cancelled),
+            browser.confirm_navigation(false, &handle_url)
+        );
+        assert_eq!(
+            Some("http://example.com/form.html".to_string()),
+            page.borrow().url()
+        );
+        // ページの中の移動は、確認しない
+        assert_eq!(Ok(NavigationOutcome::Committed), navigate("#top"));
+
+        assert_eq!(
+            Ok(NavigationOutcome::Blocked),
+            navigate("http://example.com/next.html")
+        );
+        assert_eq!(
+            Ok(NavigationOutcome::Committed),
+            browser.confirm_navigation(true, &handle_url)
+        );
+        assert_eq!(None, browser.pending_navigation());
+
+        // 履歴を戻ると進むも、ページを離れる前に確認する
+        assert_eq!(
+            Ok(NavigationOutcome::Committed),
+            browser.go_back(&handle_url)
+        );
+        assert_eq!(
+            Some("http://example.com/form.html".to_string()),
+            page.borrow().url()
+        );
+        assert_eq!(
+            Ok(NavigationOutcome::Blocked),
+            browser.go_forward(&handle_url)
+        );
+        assert_eq!(
+            Ok(NavigationOutcome::Committed),
+            browser.confirm_navigation(true, &handle_url)
+        );
+        assert_eq!(
+            Ok(NavigationOutcome::Cancelled),
+            browser.go_forward(&handle_url)
+        );
+
+        // 移動先のスクリプトがlocation.hrefに代入した場合は、続けて移動し、移動元を履歴に残さない
+        assert_eq!(
+            Ok(NavigationOutcome::Committed),
+            navigate("http://example.com/redirect.html")
+        );
+        assert_eq!(
+            Some("http://example.com/landing.html".to_string()),
+            page.borrow().url()
+        );
+        assert_eq!(
+            alloc::vec![
+                "http://example.com/form.html".to_string(),
+                "http://example.com/next.html".to_string(),
+                "http://example.com/landing.html".to_string(),
+            ],
+            page.borrow().session_history().urls()
+        );
+        // 履歴のエントリは、読み込んだページの<title>を持つ
+        assert_eq!(
+            alloc::vec!["".to_string(), "Next".to_string(), "Next".to_string(),],
+            page.borrow()
+                .session_history()
+                .entries()
+                .iter()
+                .map(|e| e.title())
+                .collect::<Vec<String>>()
+        );
+        assert_eq!("Next", page.borrow().title());
+        assert_eq!(
+            Some("http://example.com/next.html".to_string()),
+            page.borrow().session_history().back_url()
+        );
+    }
 }
@@ -0,0 +1,631 @@
+use crate::constants::MAX_HEURISTIC_FRESHNESS;
+use crate::constants::MAX_HTTP_CACHE_ENTRIES;
+use crate::error::Error;
+use crate::http::Header;
+use crate::http::HttpResponse;
+use crate::renderer::css::cssom::StyleSheet;
+use crate::url::Url;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// キャッシュに保存したレスポンスと、その鮮度を判断するための情報
+/// https://datatracker.ietf.org/doc/html/rfc9111#section-3
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    response: HttpResponse,
+    /// レスポンスが作られた時刻（Dateヘッダの値）
+    date: Option<u64>,
+    /// キャッシュに保存した時刻。304で再検証した場合は、その時刻
+    stored_at: u64,
+    /// レスポンスが新鮮な状態を保つ秒数
+    freshness_lifetime: u64,
+    /// no-cacheが指定された場合、使う前に必ず再検証する
+    no_cache: bool,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// レスポンスの本文を解釈したスタイルシート。本文が変わるとエントリごと置き換わるので、
+    /// 304で再検証した場合だけ使い続ける
+    stylesheet: Option<StyleSheet>,
+    /// 最後に使った順番。上限を超えたときに、最も小さいエントリを取り除く
+    last_used: u64,
+}
+
+impl CacheEntry {
+    fn new(response: HttpResponse, now: u64) -> Self {
+        let mut entry = Self {
+            response,
+            date: None,
+            stored_at: now,
+            freshness_lifetime: 0,
+            no_cache: false,
+            etag: None,
+            last_modified: None,
+            stylesheet: None,
+            last_used: 0,
+        };
+        let headers = entry.response.clone();
+        entry.update(&headers, now);
+        entry
+    }
+
+    /// レスポンス（または304レスポンス）のヘッダから、鮮度と検証子を更新する。
+    /// `now`はレスポンスを受け取った時刻
+    fn update(&mut self, response: &HttpResponse, now: u64) {
+        self.stored_at = now;
+        if let Some(date) = header(response, "Date").and_then(|d| parse_http_date(&d)) {
+            self.date = Some(date);
+        }
+        if let Some(etag) = header(response, "ETag") {
+            self.etag = Some(etag);
+        }
+        if let Some(last_modified) = header(response, "Last-Modified") {
+            self.last_modified = Some(last_modified);
+        }
+
+        let cache_control = CacheControl::parse(response);
+        if let Some(cache_control) = &cache_control {
+            self.no_cache = cache_control.no_cache;
+        }
+
+        // max-ageがExpiresヘッダより優先される
+        // https://datatracker.ietf.org/doc/html/rfc9111#section-4.2.1
+        let expires = header(response, "Expires").and_then(|e| parse_http_date(&e));
+        self.freshness_lifetime = match (cache_control.and_then(|c| c.max_age), expires) {
+            (Some(max_age), _) => max_age,
+            (None, Some(expires)) => match self.date {
+                Some(date) => expires.saturating_sub(date),
+                None => 0,
+            },
+            // 期限が指定されていない場合は、最後に変更されてから経った時間の10%の間は変わらないとみなす。
+            // 変わらないスタイルシートや画像を、ページを読み込み直すたびに再検証しないようにする
+            // https://datatracker.ietf.org/doc/html/rfc9111#section-4.2.2
+            (None, None) => {
+                let last_modified = self.last_modified.as_deref().and_then(parse_http_date);
+                match (last_modified, self.date) {
+                    (Some(last_modified), Some(date)) => {
+                        (date.saturating_sub(last_modified) / 10).min(MAX_HEURISTIC_FRESHNESS)
+                    }
+                    _ => 0,
+                }
+            }
+        };
+    }
+
+    /// https://datatracker.ietf.org/doc/html/rfc9111#section-4.2
+    fn is_fresh(&self, now: u64) -> bool {
+        if self.no_cache {
+            return false;
+        }
+        // Dateヘッダがない場合は、キャッシュに保存した時刻に作られたとみなす
+        let date = self.date.unwrap_or(self.stored_at);
+        // 受け取るまでに経っていた時間と、キャッシュに保存してから経った時間の和
+        // https://datatracker.ietf.org/doc/html/rfc9111#section-4.2.3
+        let age = self.stored_at.saturating_sub(date) + now.saturating_sub(self.stored_at);
+        age < self.freshness_lifetime
+    }
+
+    /// 条件付きリクエストに付けるヘッダ
+    /// https://datatracker.ietf.org/doc/html/rfc9110#section-13.1
+    fn validators(&self) -> Vec<Header> {
+        let mut headers = Vec::new();
+        if let Some(etag) = &self.etag {
+            headers.push(Header::new("If-None-Match".to_string(), etag.clone()));
+        }
+        if let Some(last_modified) = &self.last_modified {
+            headers.push(Header::new(
+                "If-Modified-Since".to_string(),
+                last_modified.clone(),
+            ));
+        }
+        headers
+    }
+}
+
+/// Cache-Controlヘッダのうち、キャッシュの動作に関係するディレクティブ
+/// https://datatracker.ietf.org/doc/html/rfc9111#section-5.2
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CacheControl {
+    max_age: Option<u64>,
+    no_cache: bool,
+    no_store: bool,
+}
+
+impl CacheControl {
+    fn parse(response: &HttpResponse) -> Option<Self> {
+        let value = header(response, "Cache-Control")?;
+
+        let mut cache_control = Self {
+            max_age: None,
+            no_cache: false,
+            no_store: false,
+        };
+        for directive in value.split(',') {
+            let directive = directive.trim();
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name.trim(), Some(argument.trim().trim_matches('"'))),
+                None => (directive, None),
+            };
+            match name.to_ascii_lowercase().as_str() {
+                "max-age" => cache_control.max_age = argument.and_then(|a| a.parse().ok()),
+                "no-cache" => cache_control.no_cache = true,
+                "no-store" => cache_control.no_store = true,
+                _ => {}
+            }
+        }
+
+        Some(cache_control)
+    }
+}
+
+fn header(response: &HttpResponse, name: &str) -> Option<String> {
+    response.header_value(name).ok()
+}
+
+/// 正規化したURLをキーにして、GETリクエストのレスポンスを保存するキャッシュ。
+/// wasabi OSには時計がないので、受け取ったレスポンスのDateヘッダの中で最も新しい時刻を
+/// 現在時刻とみなして鮮度を判断する。Dateヘッダのないレスポンスは、保存したときの現在時刻から経過時間を測る。
+/// 保存するレスポンスはMAX_HTTP_CACHE_ENTRIES個までで、超えた場合は最も長く使われていないものから取り除く
+#[derive(Debug, Clone, Default)]
+pub struct HttpCache {
+    entries: BTreeMap<String, CacheEntry>,
+    now: u64,
+    /// エントリを使うたびに増やす数。CacheEntry::last_usedに入れる
+    uses: u64,
+}
+
+impl HttpCache {
+    pub fn new() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            now: 0,
+            uses: 0,
+        }
+    }
+
+    /// URLを正規化してキャッシュのキーにする
+    fn key(url: &str) -> String {
+        match Url::new(url.to_string()).parse() {
+            Ok(url) => url.to_string(),
+            Err(_) => url.to_string(),
+        }
+    }
+
+    fn observe_date(&mut self, response: &HttpResponse) {
+        if let Some(date) = header(response, "Date").and_then(|d| parse_http_date(&d)) {
+            self.now = self.now.max(date);
+        }
+    }
+
+    /// 埋め込み側の時計で現在時刻を進める。時刻が戻ることはない
+    pub fn set_now(&mut self, now: u64) {
+        self.now = self.now.max(now);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 保存しているレスポンスのETag。保存していない場合と、ETagがない場合はNone
+    pub fn etag(&self, url: &str) -> Option<String> {
+        self.entries.get(&Self::key(url))?.etag.clone()
+    }
+
+    /// 保存しているレスポンスの本文を解釈したスタイルシート。同じ本文を解釈し直さないために使う
+    pub fn stylesheet(&self, url: &str) -> Option<StyleSheet> {
+        self.entries.get(&Self::key(url))?.stylesheet.clone()
+    }
+
+    /// 保存しているレスポンスの本文を解釈したスタイルシートを、レスポンスと一緒に保存する。
+    /// レスポンスを保存していない場合は何もしない
+    pub fn set_stylesheet(&mut self, url: &str, sheet: StyleSheet) {
+        if let Some(entry) = self.entries.get_mut(&Self::key(url)) {
+            entry.stylesheet = Some(sheet);
+        }
+    }
+
+    /// 保存しているレスポンスのLast-Modifiedの時刻。HttpClient::get_if_modifiedに渡して、
+    /// キャッシュを通さずに変更されたかどうかを確かめるときに使う
+    pub fn last_modified(&self, url: &str) -> Option<u64> {
+        let entry = self.entries.get(&Self::key(url))?;
+        entry.last_modified.as_deref().and_then(parse_http_date)
+    }
+
+    /// キャッシュを使ってURLのレスポンスを取得する。
+    /// 新鮮なエントリがある場合は`send`を呼ばずにそれを返す。古いエントリがある場合は
+    /// If-None-MatchとIf-Modified-Sinceを付けて`send`を呼び、304が返ってきたら保存した本文を使う
+    pub fn fetch<F>(&mut self, url: &str, send: F) -> Result<HttpResponse, Error>
+    where
+        F: FnOnce(Vec<Header>) -> Result<HttpResponse, Error>,
+    {
+        let key = Self::key(url);
+        self.uses += 1;
+        let uses = self.uses;
+
+        let validators = match self.entries.get_mut(&key) {
+            Some(entry) if entry.is_fresh(self.now) => {
+                entry.last_used = uses;
+                return Ok(entry.response.clone());
+            }
+            Some(entry) => entry.validators(),
+            None => Vec::new(),
+        };
+
+        let response = send(validators)?;
+        self.observe_date(&response);
+
+        // https://datatracker.ietf.org/doc/html/rfc9111#section-4.3.4
+        if response.status_code() == 304 {
+            if let Some(entry) = self.entries.get_mut(&key) {
+                entry.update(&response, self.now);
+                entry.last_used = uses;
+                return Ok(entry.response.clone());
+            }
+            return Ok(response);
+        }
+
+        self.store(key, response.clone());
+        Ok(response)
+    }
+
+    /// https://datatracker.ietf.org/doc/html/rfc9111#section-3
+    fn store(&mut self, key: String, response: HttpResponse) {
+        if response.status_code() != 200 {
+            return;
+        }
+        if let Some(cache_control) = CacheControl::parse(&response) {
+            if cache_control.no_store {
+                self.entries.remove(&key);
+                return;
+            }
+        }
+
+        let mut entry = CacheEntry::new(response, self.now);
+        entry.last_used = self.uses;
+        self.entries.insert(key, entry);
+
+        while self.entries.len() > MAX_HTTP_CACHE_ENTRIES {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(key) => self.entries.remove(&key),
+                None => break,
+            };
+        }
+    }
+}
+
+/// "Sun, 06 Nov 1994 08:49:37 GMT"の形式（IMF-fixdate）の日時を、
+/// 1970年1月1日からの秒数に変換する
+/// https://datatracker.ietf.org/doc/html/rfc9110#section-5.6.7
+pub fn parse_http_date(date: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let (_, date) = date.trim().split_once(", ")?;
+    let parts: Vec<&str> = date.split(' ').collect();
+    if parts.len() != 5 || parts[4] != "GMT" {
+        return None;
+    }
+
+    let day: u64 = parts[0].parse().ok()?;
+    let month = MONTHS.iter().position(|m| *m == parts[1])? as u64 + 1;
+    let year: u64 = parts[2].parse().ok()?;
+    let time: Vec<u64> = parts[3]
+        .split(':')
+        .map(|t| t.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    if time.len() != 3 || year < 1970 {
+        return None;
+    }
+
+    Some(days_from_civil(year, month, day) * 86400 + time[0] * 3600 + time[1] * 60 + time[2])
+}
+
+/// 1970年1月1日からの秒数を、"Sun, 06 Nov 1994 08:49:37 GMT"の形式（IMF-fixdate）の日時にする。
+/// If-Modified-Sinceヘッダの値に使う
+/// https://datatracker.ietf.org/doc/html/rfc9110#section-5.6.7
+pub fn format_http_date(secs: u64) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    // 1970年1月1日は木曜日
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+
+    let days = secs / 86400;
+    let time = secs % 86400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// 1970年1月1日から数えた日数を、年、月、日にする
+/// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+/// 1970年1月1日から数えた日数
+/// http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::cssom::CssParser;
+    use crate::renderer::css::token::CssTokenizer;
+    use core::cell::Cell;
+
+    fn response(raw: &str) -> HttpResponse {
+        HttpResponse::new(raw.to_string()).expect("failed to parse http response")
+    }
+
+    #[test]
+    fn test_parse_http_date() {
+        assert_eq!(Some(0), parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"));
+        assert_eq!(
+            Some(784111777),
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT")
+        );
+        assert_eq!(None, parse_http_date("xx xx xx"));
+    }
+
+    #[test]
+    fn test_format_http_date() {
+        assert_eq!("Thu, 01 Jan 1970 00:00:00 GMT", format_http_date(0));
+        assert_eq!("Sun, 06 Nov 1994 08:49:37 GMT", format_http_date(784111777));
+        // うるう年の2月29日
+        let date = "Tue, 29 Feb 2000 23:59:59 GMT";
+        assert_eq!(
+            date,
+            format_http_date(parse_http_date(date).expect("date should be valid"))
+        );
+    }
+
+    #[test]
+    fn test_heuristic_freshness() {
+        let mut cache = HttpCache::new();
+        let sent = Cell::new(0);
+        // 最後の変更から10日経っているので、1日（上限）は新鮮とみなす
+        let send = |_: Vec<Header>| {
+            sent.set(sent.get() + 1);
+            Ok(response(
+                "HTTP/1.1 200 OK\nDate: Sun, 06 Nov 1994 08:49:37 GMT\nLast-Modified: Thu, 27 Oct 1994 08:49:37 GMT\n\nbody { color: red; }",
+            ))
+        };
+        cache.fetch("http://example.com/style.css", send).unwrap();
+        cache.fetch("http://example.com/style.css", send).unwrap();
+        assert_eq!(1, sent.get());
+        assert_eq!(
+            parse_http_date("Thu, 27 Oct 1994 08:49:37 GMT"),
+            cache.last_modified("http://example.com/style.css")
+        );
+
+        cache.set_now(784111777 + 86400);
+        cache.fetch("http://example.com/style.css", send).unwrap();
+        assert_eq!(2, sent.get());
+    }
+
+    #[test]
+    fn test_fresh_entry() {
+        let mut cache = HttpCache::new();
+        let sent = Cell::new(0);
+        let send = |_: Vec<Header>| {
+            sent.set(sent.get() + 1);
+            Ok(response(
+                "HTTP/1.1 200 OK\nDate: Sun, 06 Nov 1994 08:49:37 GMT\nCache-Control: max-age=60\n\nhello",
+            ))
+        };
+
+        assert_eq!(
+            "hello",
+            cache.fetch("http://example.com", send).unwrap().body()
+        );
+        // 正規化すると同じURLになるので、ネットワークを使わずにキャッシュから返す
+        assert_eq!(
+            "hello",
+            cache.fetch("HTTP://EXAMPLE.COM:80/", send).unwrap().body()
+        );
+        assert_eq!(1, sent.get());
+        assert_eq!(1, cache.len());
+    }
+
+    #[test]
+    fn test_entry_without_date() {
+        let mut cache = HttpCache::new();
+        let sent = Cell::new(0);
+        let send = |_: Vec<Header>| {
+            sent.set(sent.get() + 1);
+            Ok(response(
+                "HTTP/1.1 200 OK\nCache-Control: max-age=60\n\nhello",
+            ))
+        };
+
+        cache.set_now(1000);
+        cache.fetch("http://example.com", send).unwrap();
+        cache.set_now(1059);
+        cache.fetch("http://example.com", send).unwrap();
+        assert_eq!(1, sent.get());
+
+        // Dateヘッダがなくても、保存してからmax-ageの秒数が経つと古くなる
+        cache.set_now(1060);
+        cache.fetch("http://example.com", send).unwrap();
+        assert_eq!(2, sent.get());
+    }
+
+    #[test]
+    fn test_revalidation() {
+        let mut cache = HttpCache::new();
+        cache
+            .fetch("http://example.com/index.html", |_| {
+                Ok(response(
+                    "HTTP/1.1 200 OK\nDate: Sun, 06 Nov 1994 08:49:37 GMT\nETag: \"v1\"\nLast-Modified: Sat, 05 Nov 1994 00:00:00 GMT\nCache-Control: no-cache\n\nhello",
+                ))
+            })
+            .unwrap();
+
+        let revalidated = cache
+            .fetch("http://example.com/index.html", |headers| {
+                let names: Vec<(String, String)> =
+                    headers.iter().map(|h| (h.name(), h.value())).collect();
+                assert_eq!(
+                    alloc::vec![
+                        ("If-None-Match".to_string(), "\"v1\"".to_string()),
+                        (
+                            "If-Modified-Since".to_string(),
+                            "Sat, 05 Nov 1994 00:00:00 GMT".to_string()
+                        ),
+                    ],
+                    names
+                );
+                Ok(response("HTTP/1.1 304 Not Modified\n\n"))
+            })
+            .unwrap();
+        // 304の場合は、保存していた本文を使う
+        assert_eq!(200, revalidated.status_code());
+        assert_eq!("hello", revalidated.body());
+    }
+
+    #[test]
+    fn test_stale_entry() {
+        let mut cache = HttpCache::new();
+        cache
+            .fetch("http://example.com/a", |_| {
+                Ok(response(
+                    "HTTP/1.1 200 OK\nDate: Sun, 06 Nov 1994 08:49:37 GMT\nCache-Control: max-age=60\n\nold",
+                ))
+            })
+            .unwrap();
+        // 別のレスポンスのDateヘッダから、時間が進んだことがわかる
+        cache
+            .fetch("http://example.com/b", |_| {
+                Ok(response(
+                    "HTTP/1.1 200 OK\nDate: Sun, 06 Nov 1994 08:50:37 GMT\n\nb",
+                ))
+            })
+            .unwrap();
+
+        let res = cache
+            .fetch("http://example.com/a", |_| {
+                Ok(response("HTTP/1.1 200 OK\nContent-Length: 3\n\nnew"))
+            })
+            .unwrap();
+        assert_eq!("new", res.body());
+    }
+
+    #[test]
+    fn test_no_store() {
+        let mut cache = HttpCache::new();
+        cache
+            .fetch("http://example.com", |_| {
+                Ok(response(
+                    "HTTP/1.1 200 OK\nCache-Control: no-store, max-age=60\n\nsecret",
+                ))
+            })
+            .unwrap();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_max_entries() {
+        let mut cache = HttpCache::new();
+        let send = |_: Vec<Header>| {
+            Ok(response(
+                "HTTP/1.1 200 OK\nCache-Control: max-age=60\n\nhello",
+            ))
+        };
+        for i in 0..MAX_HTTP_CACHE_ENTRIES {
+            cache
+                .fetch(&format!("http://example.com/{}", i), send)
+                .unwrap();
+        }
+        // 最初のエントリを使うと、次に古い2番目のエントリが先に取り除かれる
+        cache.fetch("http://example.com/0", send).unwrap();
+        cache.fetch("http://example.com/new", send).unwrap();
+        assert_eq!(MAX_HTTP_CACHE_ENTRIES, cache.len());
+        assert!(cache.entries.contains_key("http://example.com/0"));
+        assert!(!cache.entries.contains_key("http://example.com/1"));
+    }
+
+    #[test]
+    fn test_stylesheet() {
+        let sheet =
+            |css: &str| CssParser::new(CssTokenizer::new(css.to_string())).parse_stylesheet();
+        let mut cache = HttpCache::new();
+        let css = |body: &'static str| {
+            move |_: Vec<Header>| {
+                Ok(response(&format!(
+                    "HTTP/1.1 200 OK\nETag: \"1\"\nCache-Control: no-cache\n\n{}",
+                    body
+                )))
+            }
+        };
+        // レスポンスを保存していないURLには、スタイルシートも保存しない
+        cache.set_stylesheet("http://example.com/a.css", sheet("p { color: red; }"));
+        assert_eq!(None, cache.stylesheet("http://example.com/a.css"));
+
+        cache
+            .fetch("http://example.com/a.css", css("p { color: red; }"))
+            .unwrap();
+        cache.set_stylesheet("http://example.com/a.css", sheet("p { color: red; }"));
+        // URLは正規化して比べる
+        assert_eq!(
+            Some(sheet("p { color: red; }")),
+            cache.stylesheet("http://example.com:80/a.css")
+        );
+
+        // 304で再検証したレスポンスは同じ本文なので、スタイルシートを使い続ける
+        cache
+            .fetch("http://example.com/a.css", |_| {
+                Ok(response("HTTP/1.1 304 Not Modified\n\n"))
+            })
+            .unwrap();
+        assert!(cache.stylesheet("http://example.com/a.css").is_some());
+
+        // 新しいレスポンスを保存すると、前の本文のスタイルシートは取り除かれる
+        cache
+            .fetch("http://example.com/a.css", css("p { color: blue; }"))
+            .unwrap();
+        assert_eq!(None, cache.stylesheet("http://example.com/a.css"));
+    }
+}
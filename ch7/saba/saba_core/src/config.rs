@@ -0,0 +1,146 @@
+use crate::constants::DEFAULT_ACCEPT;
+use crate::constants::DEFAULT_USER_AGENT;
+use crate::http::Header;
+use crate::utils::form_urlencode;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// SettingsStorageに、ユーザーがabout:headersで変更したヘッダを保存するときのキー
+pub const HEADERS_SETTINGS_KEY: &str = "headers";
+
+/// ブラウザが全てのリクエストに付けるヘッダの設定。Browserが持ち、
+/// ネットワークの処理を行うhandle_urlがHttpClientに渡す。
+/// 埋め込み側やテストが、サーバーに対するブラウザの名乗り方を決められるようにする。
+/// ユーザーはabout:headersのページで値を変更できる
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrowserConfig {
+    user_agent: String,
+    accept: String,
+    /// Noneの場合、Accept-Languageヘッダを付けない
+    accept_language: Option<String>,
+    /// User-AgentとAccept-Languageの後に付ける、任意のヘッダ
+    extra_headers: Vec<Header>,
+}
+
+impl BrowserConfig {
+    pub fn new() -> Self {
+        Self {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            accept: DEFAULT_ACCEPT.to_string(),
+            accept_language: None,
+            extra_headers: Vec::new(),
+        }
+    }
+
+    pub fn user_agent(&self) -> String {
+        self.user_agent.clone()
+    }
+
+    pub fn set_user_agent(&mut self, user_agent: String) {
+        self.user_agent = user_agent;
+    }
+
+    pub fn accept(&self) -> String {
+        self.accept.clone()
+    }
+
+    pub fn set_accept(&mut self, accept: String) {
+        self.accept = accept;
+    }
+
+    pub fn accept_language(&self) -> Option<String> {
+        self.accept_language.clone()
+    }
+
+    pub fn set_accept_language(&mut self, accept_language: Option<String>) {
+        self.accept_language = accept_language;
+    }
+
+    pub fn extra_headers(&self) -> Vec<Header> {
+        self.extra_headers.clone()
+    }
+
+    /// 同じ名前のヘッダが既にある場合は、値を置き換える
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        let header = Header::new(name.to_string(), value.to_string());
+        match self
+            .extra_headers
+            .iter_mut()
+            .find(|h| h.name().eq_ignore_ascii_case(name))
+        {
+            Some(existing) => *existing = header,
+            None => self.extra_headers.push(header),
+        }
+    }
+
+    /// ユーザーが変更できる値を、about:headersのURLと同じクエリの形式で書き出す。
+    /// apply_headers_queryで読み込むと、同じ値に戻る。埋め込み側が加えたヘッダは書き出さない
+    pub fn serialize(&self) -> String {
+        format!(
+            "user-agent={}&accept={}&accept-language={}",
+            form_urlencode(&self.user_agent),
+            form_urlencode(&self.accept),
+            form_urlencode(self.accept_language.as_deref().unwrap_or(""))
+        )
+    }
+
+    /// HttpRequest::with_default_headersに渡すヘッダ。リクエストに同じ名前のヘッダがある場合は、
+    /// そちらが優先される
+    pub fn default_headers(&self) -> Vec<Header> {
+        let mut headers = Vec::new();
+        headers.push(Header::new(
+            "User-Agent".to_string(),
+            self.user_agent.clone(),
+        ));
+        headers.push(Header::new("Accept".to_string(), self.accept.clone()));
+        if let Some(accept_language) = &self.accept_language {
+            headers.push(Header::new(
+                "Accept-Language".to_string(),
+                accept_language.clone(),
+            ));
+        }
+        headers.extend(self.extra_headers.iter().cloned());
+        headers
+    }
+}
+
+impl Default for BrowserConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::HttpRequest;
+
+    #[test]
+    fn test_default_headers() {
+        let mut config = BrowserConfig::new();
+        assert_eq!(
+            alloc::vec![
+                Header::new("User-Agent".to_string(), DEFAULT_USER_AGENT.to_string()),
+                Header::new("Accept".to_string(), DEFAULT_ACCEPT.to_string())
+            ],
+            config.default_headers()
+        );
+
+        config.set_user_agent("test-agent/1.0".to_string());
+        config.set_accept("text/plain".to_string());
+        config.set_accept_language(Some("ja, en;q=0.5".to_string()));
+        config.set_header("DNT", "1");
+        config.set_header("dnt", "0");
+
+        // リクエスト自身のヘッダは、設定のヘッダで上書きしない
+        let request = HttpRequest::get("/")
+            .header("User-Agent", "override")
+            .with_default_headers(config.default_headers());
+        assert_eq!(
+            "GET / HTTP/1.1\r\nAccept: text/plain\r\nAccept-Language: ja, en;q=0.5\r\ndnt: 0\r\nUser-Agent: override\r\n\r\n",
+            request.serialize()
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use crate::constants::MAX_IDLE_CONNECTIONS_PER_HOST;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[derive(Debug)]
+struct IdleConnection<C> {
+    host: String,
+    port: u16,
+    connection: C,
+    last_used: u64,
+}
+
+/// 使い終わった接続を(ホスト, ポート)ごとに残しておき、次のリクエストで再利用するためのプール。
+/// 時刻は呼び出し側が決める単位で渡し、`idle_timeout`より長く使われていない接続は捨てる
+/// https://datatracker.ietf.org/doc/html/rfc9112#section-9.3
+#[derive(Debug)]
+pub struct ConnectionPool<C> {
+    idle: Vec<IdleConnection<C>>,
+    idle_timeout: u64,
+}
+
+impl<C> ConnectionPool<C> {
+    pub fn new(idle_timeout: u64) -> Self {
+        Self {
+            idle: Vec::new(),
+            idle_timeout,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.idle.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.idle.is_empty()
+    }
+
+    /// 長い間使われていない接続を捨てる
+    pub fn evict_expired(&mut self, now: u64) {
+        let idle_timeout = self.idle_timeout;
+        self.idle
+            .retain(|c| now.saturating_sub(c.last_used) <= idle_timeout);
+    }
+
+    /// 同じホストとポートへの接続が残っていれば、最も最近使ったものを取り出す
+    pub fn take(&mut self, host: &str, port: u16, now: u64) -> Option<C> {
+        self.evict_expired(now);
+
+        let index = self
+            .idle
+            .iter()
+            .rposition(|c| c.host == host && c.port == port)?;
+        Some(self.idle.remove(index).connection)
+    }
+
+    /// 使い終わった接続をプールに戻す。同じホストとポートへの接続が多すぎる場合は、古いものから捨てる
+    pub fn put(&mut self, host: String, port: u16, connection: C, now: u64) {
+        self.evict_expired(now);
+
+        self.idle.push(IdleConnection {
+            host,
+            port,
+            connection,
+            last_used: now,
+        });
+
+        let last = &self.idle[self.idle.len() - 1];
+        let (host, port) = (last.host.clone(), last.port);
+        while self
+            .idle
+            .iter()
+            .filter(|c| c.host == host && c.port == port)
+            .count()
+            > MAX_IDLE_CONNECTIONS_PER_HOST
+        {
+            if let Some(index) = self
+                .idle
+                .iter()
+                .position(|c| c.host == host && c.port == port)
+            {
+                self.idle.remove(index);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_reuse_same_origin() {
+        let mut pool = ConnectionPool::new(10);
+        pool.put("example.com".to_string(), 80, 1, 0);
+        pool.put("example.com".to_string(), 8080, 2, 0);
+
+        assert_eq!(None, pool.take("other.com", 80, 1));
+        assert_eq!(Some(2), pool.take("example.com", 8080, 1));
+        assert_eq!(Some(1), pool.take("example.com", 80, 1));
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_idle_timeout() {
+        let mut pool = ConnectionPool::new(10);
+        pool.put("example.com".to_string(), 80, 1, 0);
+        pool.put("example.com".to_string(), 80, 2, 5);
+
+        // 1つ目の接続だけがタイムアウトする
+        assert_eq!(Some(2), pool.take("example.com", 80, 12));
+        assert_eq!(None, pool.take("example.com", 80, 12));
+    }
+
+    #[test]
+    fn test_max_idle_connections() {
+        let mut pool = ConnectionPool::new(10);
+        for i in 0..5 {
+            pool.put("example.com".to_string(), 80, i, 0);
+        }
+        assert_eq!(MAX_IDLE_CONNECTIONS_PER_HOST, pool.len());
+        // 古い接続から捨てられる
+        assert_eq!(Some(4), pool.take("example.com", 80, 0));
+        assert_eq!(Some(3), pool.take("example.com", 80, 0));
+    }
+}
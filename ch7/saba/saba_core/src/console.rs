@@ -0,0 +1,49 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleEntryKind {
+    /// コンソールに入力された式
+    Input,
+    /// 入力された式を評価した値
+    Result,
+    /// スクリプトのconsole.log()で出力された文字列
+    Log,
+    /// スクリプトの実行中に起きたエラー
+    Error,
+}
+
+/// devtoolsのコンソールに表示する1行
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsoleEntry {
+    kind: ConsoleEntryKind,
+    text: String,
+}
+
+impl ConsoleEntry {
+    pub fn new(kind: ConsoleEntryKind, text: &str) -> Self {
+        Self {
+            kind,
+            text: text.to_string(),
+        }
+    }
+
+    pub fn kind(&self) -> ConsoleEntryKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> String {
+        self.text.clone()
+    }
+
+    /// 種類がわかるように、先頭に記号を付けた文字列
+    pub fn line(&self) -> String {
+        match self.kind {
+            ConsoleEntryKind::Input => format!("$ {}", self.text),
+            ConsoleEntryKind::Result => format!("= {}", self.text),
+            ConsoleEntryKind::Log => self.text.clone(),
+            ConsoleEntryKind::Error => format!("! {}", self.text),
+        }
+    }
+}
@@ -3,11 +3,18 @@ pub static LIGHTGREY: u32 = 0xd3d3d3;
 pub static GREY: u32 = 0x808080;
 pub static DARKGREY: u32 = 0x5a5a5a;
 pub static BLACK: u32 = 0x000000;
+pub static RED: u32 = 0xff0000;
 
 pub static ADDRESSBAR_HEIGHT: i64 = 20;
+// アドレスバーの左端に表示する、接続の安全性の印の幅。クリックすると説明のポップアップを開く
+pub static SECURITY_INDICATOR_WIDTH: i64 = 16;
+// アドレスバーの右端に表示する、読み込めなかったリソースの数の印の幅。"!99"まで表示できる
+pub static SUBRESOURCE_BADGE_WIDTH: i64 = 28;
 
 pub static WINDOW_INIT_X_POS: i64 = 30;
 pub static WINDOW_INIT_Y_POS: i64 = 50;
+// リンクを新しいウィンドウで開くとき、前のウィンドウからずらす距離
+pub static NEW_WINDOW_OFFSET: i64 = 30;
 
 pub static WINDOW_WIDTH: i64 = 600;
 pub static WINDOW_HEIGHT: i64 = 400;
@@ -22,6 +29,97 @@ pub static CONTENT_AREA_WIDTH: i64 = WINDOW_WIDTH - WINDOW_PADDING * 2;
 pub static CONTENT_AREA_HEIGHT: i64 =
     WINDOW_HEIGHT - TITLE_BAR_HEIGHT - TOOLBAR_HEIGHT - WINDOW_PADDING * 2;
 
+// 見出しの目次を表示するサイドバーの幅。開いている間は、コンテンツエリアの残りにページを表示する
+pub static TOC_SIDEBAR_WIDTH: i64 = 150;
+
 pub static CHAR_WIDTH: i64 = 8;
 pub static CHAR_HEIGHT: i64 = 16;
 pub static CHAR_HEIGHT_WITH_PADDING: i64 = CHAR_HEIGHT + 4;
+
+// 大きさが指定されておらず、中身の大きさもわからない置換要素の大きさ
+// https://www.w3.org/TR/CSS22/visudet.html#inline-replaced-width
+pub static REPLACED_DEFAULT_WIDTH: i64 = 300;
+pub static REPLACED_DEFAULT_HEIGHT: i64 = 150;
+
+// 表示できない画像の代わりにalt属性の文字列を描くときの、枠の色と、枠と文字の間の余白
+pub static IMAGE_ALT_BORDER_COLOR: &str = "gray";
+pub static IMAGE_ALT_PADDING: i64 = 2;
+
+// <ul>と<ol>の項目を右にずらす幅と、項目の左に描くマーカーと項目の間の余白
+// https://html.spec.whatwg.org/multipage/rendering.html#lists
+pub static LIST_INDENT: i64 = 40;
+pub static LIST_MARKER_GAP: i64 = 8;
+
+// ビットマップフォントには太字がないので、太字は文字を右にずらして重ねて描く。太字の文字列は、ずらした分だけ広くなる
+pub static BOLD_STROKE_OFFSET: i64 = 1;
+
+// アドレスバーの入力がURLでないときに使う検索エンジン。"%s"が検索語に置き換えられる
+pub static DEFAULT_SEARCH_ENGINE: &str = "http://www.google.com/search?q=%s";
+
+// 深くネストした文書でもスタックを使い切らないように、辿るツリーの深さに上限を設ける
+pub static MAX_TREE_DEPTH: usize = 512;
+
+// リダイレクトをたどる回数の上限
+// https://fetch.spec.whatwg.org/#http-redirect-fetch
+pub static MAX_REDIRECTS: usize = 20;
+
+// 入れ子の<iframe>を読み込む深さの上限。自分自身を指す<iframe>があっても読み込みが終わるようにする
+pub static MAX_FRAME_DEPTH: usize = 8;
+
+// Last-Modifiedから推測したキャッシュの鮮度の上限（秒）。古くから変わっていないリソースでも、1日に1回は再検証する
+// https://datatracker.ietf.org/doc/html/rfc9111#section-4.2.2
+pub static MAX_HEURISTIC_FRESHNESS: u64 = 86400;
+// HTTPキャッシュに保存するレスポンスの数の上限。超えた場合は、最も長く使われていないものから取り除く。
+// 解釈したスタイルシートはレスポンスと一緒に保存するので、同じ上限で取り除かれる
+pub static MAX_HTTP_CACHE_ENTRIES: usize = 64;
+
+// 再利用のために残しておく接続を閉じるまでの時間。wasabi OSには時計がないので、
+// その後に送ったリクエストの数で数える
+pub static KEEP_ALIVE_IDLE_TIMEOUT: u64 = 16;
+// 同じホストとポートに対して残しておく接続の数の上限
+pub static MAX_IDLE_CONNECTIONS_PER_HOST: usize = 2;
+
+// MutationObserverのコールバックがDOMツリーを変更し続けても止まるように、レコードを届ける回数に上限を設ける
+pub static MAX_MUTATION_DELIVERY_ROUNDS: usize = 16;
+
+// 接続とレスポンスの受信のタイムアウト。wasabi OSには時計がないので、
+// 接続を試みた回数と、データが届かないままソケットを読んだ回数で数える
+pub static DEFAULT_CONNECT_TIMEOUT: u64 = 3;
+pub static DEFAULT_READ_TIMEOUT: u64 = 100_000;
+
+// クローラーが取得するページの数の上限
+pub static MAX_CRAWL_PAGES: usize = 100;
+
+// paint-debugのオーバーレイで、レイアウトの枠と座標を描く色
+pub static DEBUG_OVERLAY_BLOCK_COLOR: &str = "fuchsia";
+pub static DEBUG_OVERLAY_INLINE_COLOR: &str = "blue";
+pub static DEBUG_OVERLAY_TEXT_COLOR: &str = "orange";
+
+// キャレットブラウジングで、キャレットと選択範囲の背景を描く色
+pub static CARET_COLOR: &str = "black";
+pub static SELECTION_COLOR: &str = "lightgray";
+
+// User-Agentヘッダの初期値
+pub static DEFAULT_USER_AGENT: &str = concat!("saba/", env!("CARGO_PKG_VERSION"));
+// Acceptヘッダの初期値
+pub static DEFAULT_ACCEPT: &str = "text/html";
+
+// イベントループの1回の繰り返しで、タイマーと先読みのタスクに使うミリ秒
+pub static TASK_TIME_BUDGET_MS: u64 = 16;
+// ミリ秒を測れない環境で、1回の繰り返しで実行するタイマーと先読みのタスクの数
+pub static MAX_BACKGROUND_TASKS_PER_ITERATION: usize = 4;
+// イベントループの1回の繰り返しで、<link rel="preload">と<link rel="prefetch">のリソースを取得する数
+pub static MAX_RESOURCE_HINTS_PER_ITERATION: usize = 1;
+
+// スクリプトが止まらないときに、ScriptWatchdogに止めるかを尋ねる間隔。関数の呼び出しと文の実行を数える
+pub static SCRIPT_WATCHDOG_INTERVAL: u64 = 10_000;
+
+// 画像をデコードするタスク1つで、デコードする行の数
+pub static IMAGE_DECODE_ROWS_PER_TASK: usize = 32;
+
+// 文字の送り幅を足し合わせるときに、1ピクセルを分ける数
+pub static FONT_SUBPIXELS: i64 = 64;
+
+// text/plainのページで、タブを何文字分の空白として表示するか
+// https://drafts.csswg.org/css-text/#tab-size-property
+pub static TAB_WIDTH: usize = 8;
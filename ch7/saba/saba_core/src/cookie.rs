@@ -0,0 +1,124 @@
+//! Cookie。レスポンスのSet-Cookieヘッダで受け取った名前と値をオリジンごとに保存し、
+//! 同じオリジンへのリクエストのCookieヘッダで送る。
+//! Domain属性とPath属性はまだ扱わず、Cookieを受け取ったオリジンにだけ送る。
+//! 有効期限はMax-Ageが0以下の場合の削除だけを扱い、それ以外はブラウザを閉じるまで持っておく
+//! https://datatracker.ietf.org/doc/html/rfc6265
+
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cookie {
+    origin: String,
+    name: String,
+    value: String,
+}
+
+/// 受け取ったCookieを、受け取った順に持つ
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self {
+            cookies: Vec::new(),
+        }
+    }
+
+    /// `origin`から受け取ったSet-Cookieヘッダの値を保存する。同じ名前のCookieは値を置き換える
+    /// https://datatracker.ietf.org/doc/html/rfc6265#section-5.2
+    pub fn store(&mut self, origin: &str, set_cookie: &str) {
+        let mut parts = set_cookie.split(';');
+        let (name, value) = match parts.next().and_then(|pair| pair.split_once('=')) {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => return,
+        };
+        if name.is_empty() {
+            return;
+        }
+        let expired = parts.any(|attribute| match attribute.split_once('=') {
+            Some((key, max_age)) if key.trim().eq_ignore_ascii_case("max-age") => {
+                matches!(max_age.trim().parse::<i64>(), Ok(max_age) if max_age <= 0)
+            }
+            _ => false,
+        });
+
+        self.cookies
+            .retain(|cookie| !(cookie.origin == origin && cookie.name == name));
+        if !expired {
+            self.cookies.push(Cookie {
+                origin: origin.to_string(),
+                name: name.to_string(),
+                value: value.to_string(),
+            });
+        }
+    }
+
+    /// `origin`へのリクエストに付けるCookieヘッダの値。送るCookieがない場合はNoneを返す
+    pub fn header_value(&self, origin: &str) -> Option<String> {
+        let pairs: Vec<String> = self
+            .cookies
+            .iter()
+            .filter(|cookie| cookie.origin == origin)
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect();
+        if pairs.is_empty() {
+            None
+        } else {
+            Some(pairs.join("; "))
+        }
+    }
+
+    /// `origin`から受け取ったCookieを全て削除する
+    pub fn remove(&mut self, origin: &str) {
+        self.cookies.retain(|cookie| cookie.origin != origin);
+    }
+
+    pub fn len(&self) -> usize {
+        self.cookies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.cookies.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cookie_jar() {
+        let mut jar = CookieJar::new();
+        assert_eq!(None, jar.header_value("http://example.com"));
+
+        jar.store("http://example.com", "a=1; Path=/");
+        jar.store(
+            "http://example.com",
+            "b=2; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+        );
+        jar.store("http://other.example", "c=3");
+        jar.store("http://example.com", "invalid");
+        assert_eq!(
+            Some("a=1; b=2".to_string()),
+            jar.header_value("http://example.com")
+        );
+
+        // 同じ名前は置き換え、Max-Ageが0のCookieは削除する
+        jar.store("http://example.com", "a=4");
+        jar.store("http://example.com", "b=; Max-Age=0");
+        assert_eq!(
+            Some("a=4".to_string()),
+            jar.header_value("http://example.com")
+        );
+        assert_eq!(2, jar.len());
+    }
+}
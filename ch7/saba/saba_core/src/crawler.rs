@@ -0,0 +1,316 @@
+//! 同じオリジンのリンクをたどってページを取得し、リンク切れとページの問題を報告するクローラー。
+//! robots.txtは読まないので、自分で管理しているサイトの確認に使う
+
+use crate::browser::Browser;
+use crate::constants::MAX_CRAWL_PAGES;
+use crate::error::Error;
+use crate::http::Header;
+use crate::http::HttpResponse;
+use crate::renderer::dom::api::DomTreeIterator;
+use crate::renderer::dom::node::ElementKind;
+use crate::url::split_fragment;
+use crate::url::Url;
+use crate::url::UrlParseError;
+use alloc::collections::BTreeMap;
+use alloc::collections::BTreeSet;
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// 1つのページを取得した結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageReport {
+    url: String,
+    depth: usize,
+    /// ステータスコードと理由。取得できなかった場合はエラーの内容
+    status: Result<(u32, String), String>,
+    /// ページの中で見つけたリンクの数
+    link_count: usize,
+    /// このページからリンクされている、取得できなかったページ
+    broken_links: Vec<(String, String)>,
+    /// ページを読み込む途中で見つかった問題。解釈できないリンクなど
+    errors: Vec<String>,
+}
+
+impl PageReport {
+    fn new(url: String, depth: usize, status: Result<(u32, String), String>) -> Self {
+        Self {
+            url,
+            depth,
+            status,
+            link_count: 0,
+            broken_links: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn status(&self) -> Result<(u32, String), String> {
+        self.status.clone()
+    }
+
+    pub fn link_count(&self) -> usize {
+        self.link_count
+    }
+
+    pub fn broken_links(&self) -> Vec<(String, String)> {
+        self.broken_links.clone()
+    }
+
+    pub fn errors(&self) -> Vec<String> {
+        self.errors.clone()
+    }
+
+    /// ステータスコードが400以上か、取得できなかった場合は壊れている
+    pub fn is_broken(&self) -> bool {
+        match &self.status {
+            Ok((code, _)) => *code >= 400,
+            Err(_) => true,
+        }
+    }
+
+    fn status_text(&self) -> String {
+        match &self.status {
+            Ok((code, reason)) => format!("{} {}", code, reason),
+            Err(e) => e.clone(),
+        }
+    }
+}
+
+/// クロールの結果。取得した順にページの結果を持つ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrawlReport {
+    pages: Vec<PageReport>,
+}
+
+impl CrawlReport {
+    pub fn pages(&self) -> Vec<PageReport> {
+        self.pages.clone()
+    }
+
+    /// 取得できなかったページかページの問題が1つでもあるかどうか。
+    /// 最初のページはどこからもリンクされていないので、リンク切れとは別に確かめる
+    pub fn has_problems(&self) -> bool {
+        self.pages
+            .iter()
+            .any(|p| p.is_broken() || !p.errors.is_empty())
+    }
+
+    /// 端末に出力するための文字列
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for page in &self.pages {
+            text.push_str(&format!(
+                "{} (depth {}): {}, {} links\n",
+                page.url,
+                page.depth,
+                page.status_text(),
+                page.link_count
+            ));
+            for (url, reason) in &page.broken_links {
+                text.push_str(&format!("  broken link: {} ({})\n", url, reason));
+            }
+            for error in &page.errors {
+                text.push_str(&format!("  error: {}\n", error));
+            }
+        }
+        text
+    }
+}
+
+/// `start`から同じオリジンのリンクを幅優先でたどる。`max_depth`より深いページは取得しない。
+/// 深さが`max_depth`のページは、リンク切れかどうかを確かめるために取得するが、その中のリンクはたどらない
+pub fn crawl(
+    start: &str,
+    max_depth: usize,
+    handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+) -> Result<CrawlReport, Error> {
+    let start = Url::new(split_fragment(start).0.to_string()).parse()?;
+    let origin = (start.scheme(), start.host(), start.port());
+
+    let browser = Browser::new();
+    let mut pages: Vec<PageReport> = Vec::new();
+    // リンク先のURLと、そのリンクを持っているページのURL
+    let mut referrers: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut visited: BTreeSet<String> = BTreeSet::new();
+    let mut queue: VecDeque<(String, usize)> = VecDeque::new();
+
+    visited.insert(start.to_string());
+    queue.push_back((start.to_string(), 0));
+
+    while let Some((url, depth)) = queue.pop_front() {
+        if pages.len() >= MAX_CRAWL_PAGES {
+            break;
+        }
+
+        let response = match handle_url(url.clone(), Vec::new()) {
+            Ok(response) => response,
+            Err(e) => {
+                pages.push(PageReport::new(url, depth, Err(format!("{:?}", e))));
+                continue;
+            }
+        };
+        let mut report = PageReport::new(
+            url.clone(),
+            depth,
+            Ok((response.status_code(), response.reason())),
+        );
+        if report.is_broken() || depth >= max_depth {
+            pages.push(report);
+            continue;
+        }
+
+        // 取得したレスポンスをそのまま使ってページを読み込む。<iframe>などはhandle_urlで取得する
+        let result = browser.borrow().navigate(&url, &|requested, headers| {
+            if requested == url {
+                Ok(response.clone())
+            } else {
+                handle_url(requested, headers)
+            }
+        });
+        let page = match result {
+            Ok(page) => page,
+            Err(e) => {
+                report
+                    .errors
+                    .push(format!("failed to load the page: {:?}", e));
+                pages.push(report);
+                continue;
+            }
+        };
+
+        let base = Url::new(url.clone()).parse()?;
+        for node in DomTreeIterator::new(page.document()) {
+            let href = match node.borrow().get_element() {
+                Some(e) if e.kind() == ElementKind::A => match e.get_attribute("href") {
+                    Some(href) => href,
+                    None => continue,
+                },
+                _ => continue,
+            };
+            report.link_count += 1;
+
+            // ページの中へのリンクは、取得しなくてよい
+            let joined = base.join(split_fragment(&href).0);
+            let link = match Url::new(joined).parse() {
+                Ok(link) => link,
+                // mailto:やftp:のような、このブラウザが扱わないスキームのリンクは確かめない。
+                // "mailto:"は"://"を持たないので、スキームがないURLとして扱われる
+                Err(UrlParseError::UnsupportedScheme(_) | UrlParseError::MissingScheme) => continue,
+                Err(e) => {
+                    report
+                        .errors
+                        .push(format!("invalid link {:?}: {}", href, e));
+                    continue;
+                }
+            };
+            if (link.scheme(), link.host(), link.port()) != origin {
+                continue;
+            }
+
+            let link = link.to_string();
+            let referrer = referrers.entry(link.clone()).or_default();
+            if !referrer.contains(&url) {
+                referrer.push(url.clone());
+            }
+            if visited.insert(link.clone()) {
+                queue.push_back((link, depth + 1));
+            }
+        }
+
+        pages.push(report);
+    }
+
+    // 壊れたページを、そのページにリンクしているページに報告する
+    let broken: Vec<(String, String)> = pages
+        .iter()
+        .filter(|p| p.is_broken())
+        .map(|p| (p.url.clone(), p.status_text()))
+        .collect();
+    for (url, reason) in broken {
+        for referrer in referrers.get(&url).into_iter().flatten() {
+            if let Some(page) = pages.iter_mut().find(|p| &p.url == referrer) {
+                page.broken_links.push((url.clone(), reason.clone()));
+            }
+        }
+    }
+
+    Ok(CrawlReport { pages })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handle_url(url: String, _headers: Vec<Header>) -> Result<HttpResponse, Error> {
+        let html = |body: &str| {
+            HttpResponse::new(format!(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><body>{}</body></html>",
+                body
+            ))
+        };
+        match url.as_str() {
+            "http://example.com/" => html(
+                "<a href=\"a.html#top\">a</a><a href=\"/missing.html\">missing</a><a href=\"http://other.example/\">other</a><a href=\"mailto:me@example.com\">mail</a><a href=\"http://:80/\">bad</a>",
+            ),
+            "http://example.com/a.html" => html("<a href=\"b.html\">b</a><a href=\"/\">top</a>"),
+            "http://example.com/b.html" => html("<a href=\"c.html\">c</a>"),
+            "http://example.com/missing.html" => {
+                HttpResponse::new("HTTP/1.1 404 Not Found\nContent-Length: 0\n\n".to_string())
+            }
+            _ => Err(Error::Network(format!("failed to connect to {}", url))),
+        }
+    }
+
+    #[test]
+    fn test_crawl() {
+        let report = crawl("http://example.com/", 2, &handle_url).expect("failed to crawl");
+        let urls: Vec<(String, usize)> = report
+            .pages()
+            .iter()
+            .map(|p| (p.url(), p.depth()))
+            .collect();
+        // 他のオリジンのページと、深さが2を超えるc.htmlは取得しない
+        assert_eq!(
+            alloc::vec![
+                ("http://example.com/".to_string(), 0),
+                ("http://example.com/a.html".to_string(), 1),
+                ("http://example.com/missing.html".to_string(), 1),
+                ("http://example.com/b.html".to_string(), 2),
+            ],
+            urls
+        );
+
+        let top = &report.pages()[0];
+        assert_eq!(5, top.link_count());
+        assert_eq!(
+            alloc::vec![(
+                "http://example.com/missing.html".to_string(),
+                "404 Not Found".to_string()
+            )],
+            top.broken_links()
+        );
+        assert_eq!(
+            alloc::vec!["invalid link \"http://:80/\": URL has no host".to_string()],
+            top.errors()
+        );
+        assert!(report.has_problems());
+        assert!(report
+            .to_text()
+            .starts_with("http://example.com/ (depth 0): 200 OK, 5 links\n  broken link: http://example.com/missing.html (404 Not Found)\n"));
+
+        // 最初のページを取得できない場合も、問題として報告する
+        let report =
+            crawl("http://example.com/missing.html", 2, &handle_url).expect("failed to crawl");
+        assert!(report.has_problems());
+    }
+}
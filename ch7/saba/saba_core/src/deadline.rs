@@ -0,0 +1,85 @@
+use crate::constants::DEFAULT_CONNECT_TIMEOUT;
+use crate::constants::DEFAULT_READ_TIMEOUT;
+use crate::error::Error;
+use alloc::format;
+
+/// HttpClientが使うタイムアウト。wasabi OSには時計がないので、時間の代わりに回数で数える
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// 接続を試みる回数
+    pub connect: u64,
+    /// データが届かないままソケットを読む回数。データが届くたびに数え直す
+    pub read: u64,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: DEFAULT_CONNECT_TIMEOUT,
+            read: DEFAULT_READ_TIMEOUT,
+        }
+    }
+}
+
+/// 処理を待つ回数の上限。待つたびにtickを呼び、上限を超えたらError::Timeoutを返す
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deadline {
+    timeout: u64,
+    elapsed: u64,
+}
+
+impl Deadline {
+    pub fn new(timeout: u64) -> Self {
+        Self {
+            timeout,
+            elapsed: 0,
+        }
+    }
+
+    /// 1回待ったことを記録する。`what`はタイムアウトしたときのエラーメッセージに使う
+    pub fn tick(&mut self, what: &str) -> Result<(), Error> {
+        self.elapsed += 1;
+        if self.is_expired() {
+            return Err(Error::Timeout(format!(
+                "{} timed out after {} attempts",
+                what, self.timeout
+            )));
+        }
+        Ok(())
+    }
+
+    /// 処理が進んだので、待った回数を数え直す
+    pub fn reset(&mut self) {
+        self.elapsed = 0;
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.elapsed >= self.timeout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_deadline() {
+        let mut deadline = Deadline::new(3);
+        assert_eq!(Ok(()), deadline.tick("reading"));
+        assert_eq!(Ok(()), deadline.tick("reading"));
+        assert!(!deadline.is_expired());
+
+        // 処理が進んだら、また3回まで待てる
+        deadline.reset();
+        assert_eq!(Ok(()), deadline.tick("reading"));
+        assert_eq!(Ok(()), deadline.tick("reading"));
+        assert_eq!(
+            Err(Error::Timeout(
+                "reading timed out after 3 attempts".to_string()
+            )),
+            deadline.tick("reading")
+        );
+        assert!(deadline.is_expired());
+    }
+}
@@ -1,18 +1,25 @@
+use crate::renderer::image::Image;
 use crate::renderer::layout::computed_style::ComputedStyle;
 use crate::renderer::layout::layout_object::LayoutPoint;
 use crate::renderer::layout::layout_object::LayoutSize;
+use alloc::rc::Rc;
 use alloc::string::String;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum DisplayItem {
     Rect {
-        style: ComputedStyle,
+        style: Rc<ComputedStyle>,
         layout_point: LayoutPoint,
         layout_size: LayoutSize,
     },
     Text {
         text: String,
-        style: ComputedStyle,
+        style: Rc<ComputedStyle>,
+        layout_point: LayoutPoint,
+    },
+    /// 描画する大きさに拡大・縮小した画像。layout_pointを左上として、画像の画素をそのまま描く
+    Image {
+        image: Rc<Image>,
         layout_point: LayoutPoint,
     },
 }
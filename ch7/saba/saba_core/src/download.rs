@@ -0,0 +1,188 @@
+//! ダウンロード。Content-Disposition: attachmentを持つレスポンスと、ページとして表示できない種類の
+//! レスポンスは、HTMLとして解釈せずにDownloadManagerに保存する。
+//! wasabi OSにはファイルシステムがないので、ダウンロードしたバイト列はメモリに持っておく
+//! https://html.spec.whatwg.org/multipage/links.html#downloading-resources
+
+use crate::http::HttpResponse;
+use crate::mime::content_kind;
+use crate::mime::essence;
+use crate::mime::ContentKind;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// ダウンロードしたファイル
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Download {
+    name: String,
+    url: String,
+    mime_type: String,
+    bytes: Vec<u8>,
+}
+
+impl Download {
+    pub fn new(name: String, url: String, mime_type: String, bytes: Vec<u8>) -> Self {
+        Self {
+            name,
+            url,
+            mime_type,
+            bytes,
+        }
+    }
+
+    /// `url`から取得した`response`のボディを、ダウンロードしたファイルにする
+    pub fn from_response(url: &str, response: &HttpResponse) -> Self {
+        let mime_type = match response.header_value("Content-Type") {
+            Ok(content_type) if !essence(&content_type).is_empty() => essence(&content_type),
+            _ => "application/octet-stream".to_string(),
+        };
+        Self::new(
+            download_filename(url, response),
+            url.to_string(),
+            mime_type,
+            response.body_bytes(),
+        )
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    pub fn mime_type(&self) -> String {
+        self.mime_type.clone()
+    }
+
+    /// バイト数
+    pub fn size(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+}
+
+/// ダウンロードしたファイルを、ダウンロードした順に持つ
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DownloadManager {
+    downloads: Vec<Download>,
+}
+
+impl DownloadManager {
+    pub fn new() -> Self {
+        Self {
+            downloads: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, download: Download) {
+        self.downloads.push(download);
+    }
+
+    pub fn downloads(&self) -> Vec<Download> {
+        self.downloads.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.downloads.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.downloads.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.downloads.clear();
+    }
+}
+
+/// レスポンスをページとして表示せずに、ダウンロードするかどうか
+pub fn should_download(response: &HttpResponse) -> bool {
+    if let Ok(disposition) = response.header_value("Content-Disposition") {
+        let disposition_type = disposition.split(';').next().unwrap_or("").trim();
+        if disposition_type.eq_ignore_ascii_case("attachment") {
+            return true;
+        }
+    }
+    matches!(content_kind(response), ContentKind::Unsupported(_))
+}
+
+/// ダウンロードしたファイルの名前。Content-Dispositionのfilenameパラメータがあればそれを使い、
+/// なければURLのパスの最後の部分を使う
+/// https://datatracker.ietf.org/doc/html/rfc6266#section-4.3
+pub fn download_filename(url: &str, response: &HttpResponse) -> String {
+    if let Ok(disposition) = response.header_value("Content-Disposition") {
+        for param in disposition.split(';').skip(1) {
+            if let Some((name, value)) = param.split_once('=') {
+                if name.trim().eq_ignore_ascii_case("filename") {
+                    let value = value.trim().trim_matches('"');
+                    // "../"のようなパスの区切りは、ファイル名に含めない
+                    let value = value.rsplit(['/', '\\']).next().unwrap_or("");
+                    if !value.is_empty() {
+                        return value.to_string();
+                    }
+                }
+            }
+        }
+    }
+
+    let path = url.split(['?', '#']).next().unwrap_or("");
+    let path = path.split_once("://").map(|(_, rest)| rest).unwrap_or(path);
+    match path.split_once('/') {
+        Some((_, path)) => match path.rsplit('/').next() {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => "download".to_string(),
+        },
+        None => "download".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(headers: &str, body: &str) -> HttpResponse {
+        HttpResponse::new(alloc::format!("HTTP/1.1 200 OK\n{}\n{}", headers, body))
+            .expect("failed to parse http response")
+    }
+
+    #[test]
+    fn test_should_download() {
+        assert!(should_download(&response(
+            "Content-Type: text/html\nContent-Disposition: attachment; filename=\"page.html\"\n",
+            "<html></html>"
+        )));
+        assert!(should_download(&response(
+            "Content-Type: application/zip\n",
+            "PK"
+        )));
+        assert!(!should_download(&response(
+            "Content-Type: text/plain\nContent-Disposition: inline\n",
+            "hello"
+        )));
+        assert!(!should_download(&response("", "<html></html>")));
+    }
+
+    #[test]
+    fn test_download_filename() {
+        let attachment = response(
+            "Content-Disposition: attachment; filename=\"../report.pdf\"\n",
+            "",
+        );
+        assert_eq!(
+            "report.pdf",
+            download_filename("http://example.com/get?id=1", &attachment)
+        );
+        let plain = response("Content-Type: image/png\n", "");
+        assert_eq!(
+            "logo.png",
+            download_filename("http://example.com/images/logo.png?v=2", &plain)
+        );
+        assert_eq!("download", download_filename("http://example.com/", &plain));
+        assert_eq!("download", download_filename("http://example.com", &plain));
+    }
+}
@@ -0,0 +1,177 @@
+//! 文字コード。レスポンスのボディのバイト列を、Content-Typeのcharsetで指定された文字コードで文字列にする。
+//! サーバーが間違ったcharsetを送ってきた場合に備えて、ユーザーは文字コードを選び直して読み直せる
+//! https://encoding.spec.whatwg.org/
+
+mod jis0208;
+
+use crate::encoding::jis0208::JIS0208;
+use alloc::string::String;
+use alloc::string::ToString;
+
+/// 読めないバイト列の代わりに使う文字
+const REPLACEMENT_CHARACTER: char = '\u{FFFD}';
+
+/// windows-1252で、0x80から0x9FまでのバイトをUnicodeに直す表。
+/// 文字が割り当てられていないバイトは、ISO-8859-1と同じく同じ値の制御文字にする
+/// https://encoding.spec.whatwg.org/index-windows-1252.txt
+#[rustfmt::skip]
+const WINDOWS_1252_C1: [u16; 32] = [
+    0x20AC, 0x0081, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021,
+    0x02C6, 0x2030, 0x0160, 0x2039, 0x0152, 0x008D, 0x017D, 0x008F,
+    0x0090, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0x009D, 0x017E, 0x0178,
+];
+
+/// ページを読むときに使える文字コード
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    ShiftJis,
+    /// "iso-8859-1"や"latin1"と指定されたページ。仕様に従ってwindows-1252として読む
+    Latin1,
+}
+
+impl Encoding {
+    /// UIで選べる文字コードを、選ぶ順に並べたもの
+    pub const ALL: [Encoding; 3] = [Encoding::Utf8, Encoding::ShiftJis, Encoding::Latin1];
+
+    /// 仕様書での文字コードの名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::ShiftJis => "Shift_JIS",
+            Encoding::Latin1 => "windows-1252",
+        }
+    }
+
+    /// Content-Typeのcharsetや、ユーザーが入力した文字コードの名前を読む。大文字と小文字は区別しない
+    /// https://encoding.spec.whatwg.org/#concept-encoding-get
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.trim().to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" | "unicode-1-1-utf-8" => Some(Encoding::Utf8),
+            "shift_jis" | "shift-jis" | "sjis" | "csshiftjis" | "ms932" | "ms_kanji"
+            | "windows-31j" | "x-sjis" => Some(Encoding::ShiftJis),
+            "latin1" | "latin-1" | "iso-8859-1" | "iso8859-1" | "iso_8859-1" | "l1" | "cp1252"
+            | "windows-1252" | "us-ascii" | "ascii" => Some(Encoding::Latin1),
+            _ => None,
+        }
+    }
+
+    /// バイト列を文字列にする。読めないバイト列は置換文字にし、改行の"\r\n"は"\n"にそろえる
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        let text = match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes).to_string(),
+            Encoding::ShiftJis => decode_shift_jis(bytes),
+            Encoding::Latin1 => bytes.iter().map(|b| decode_windows_1252(*b)).collect(),
+        };
+        text.replace("\r\n", "\n")
+    }
+}
+
+fn decode_windows_1252(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => {
+            char::from_u32(WINDOWS_1252_C1[(byte - 0x80) as usize] as u32).unwrap_or('\0')
+        }
+        _ => byte as char,
+    }
+}
+
+/// https://encoding.spec.whatwg.org/#shift_jis-decoder
+fn decode_shift_jis(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let lead = bytes[i];
+        i += 1;
+        match lead {
+            0x00..=0x80 => text.push(lead as char),
+            // 半角カタカナ
+            0xA1..=0xDF => text.push(char::from_u32(0xFF61 + (lead - 0xA1) as u32).unwrap_or('\0')),
+            0x81..=0x9F | 0xE0..=0xFC => {
+                let trail = match bytes.get(i) {
+                    Some(trail) => *trail,
+                    None => {
+                        text.push(REPLACEMENT_CHARACTER);
+                        break;
+                    }
+                };
+                match jis0208_pointer(lead, trail).and_then(jis0208_char) {
+                    Some(c) => {
+                        text.push(c);
+                        i += 1;
+                    }
+                    // 2バイト目がASCIIの場合は、その文字は次の文字として読み直す
+                    None if trail < 0x80 => text.push(REPLACEMENT_CHARACTER),
+                    None => {
+                        text.push(REPLACEMENT_CHARACTER);
+                        i += 1;
+                    }
+                }
+            }
+            _ => text.push(REPLACEMENT_CHARACTER),
+        }
+    }
+    text
+}
+
+fn jis0208_pointer(lead: u8, trail: u8) -> Option<usize> {
+    let lead_offset = if lead < 0xA0 { 0x81 } else { 0xC1 };
+    let trail_offset = if trail < 0x7F { 0x40 } else { 0x41 };
+    match trail {
+        0x40..=0x7E | 0x80..=0xFC => {
+            Some((lead - lead_offset) as usize * 188 + (trail - trail_offset) as usize)
+        }
+        _ => None,
+    }
+}
+
+fn jis0208_char(pointer: usize) -> Option<char> {
+    // 利用者定義文字の領域は、私用領域の文字にする
+    if (8836..=10715).contains(&pointer) {
+        return char::from_u32(0xE000 + (pointer - 8836) as u32);
+    }
+    match JIS0208.get(pointer) {
+        Some(0) | None => None,
+        Some(code) => char::from_u32(*code as u32),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_label() {
+        assert_eq!(Some(Encoding::Utf8), Encoding::from_label(" UTF-8 "));
+        assert_eq!(Some(Encoding::ShiftJis), Encoding::from_label("Shift_JIS"));
+        assert_eq!(Some(Encoding::Latin1), Encoding::from_label("ISO-8859-1"));
+        assert_eq!(None, Encoding::from_label("euc-jp"));
+    }
+
+    #[test]
+    fn test_decode_shift_jis() {
+        // "日本語ｶﾅ\r\nabc"
+        let bytes = [
+            0x93, 0xFA, 0x96, 0x7B, 0x8C, 0xEA, 0xB6, 0xC5, 0x0D, 0x0A, 0x61, 0x62, 0x63,
+        ];
+        assert_eq!("日本語ｶﾅ\nabc", Encoding::ShiftJis.decode(&bytes));
+        // 2バイト目がASCIIで文字にならない場合は、そのASCIIの文字を残す
+        assert_eq!(
+            "\u{FFFD}\0a\u{FFFD}",
+            Encoding::ShiftJis.decode(&[0x81, 0x00, 0x61, 0x93])
+        );
+        // 同じバイト列をUTF-8として読むと、読めないバイトは置換文字になる
+        assert!(Encoding::Utf8
+            .decode(&bytes)
+            .contains(REPLACEMENT_CHARACTER));
+    }
+
+    #[test]
+    fn test_decode_latin1() {
+        assert_eq!(
+            "caf\u{e9} \u{20ac}5",
+            Encoding::Latin1.decode(&[0x63, 0x61, 0x66, 0xE9, 0x20, 0x80, 0x35])
+        );
+    }
+}
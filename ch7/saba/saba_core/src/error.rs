@@ -1,9 +1,39 @@
+use crate::http::HttpParseError;
+use crate::url::UrlParseError;
 use alloc::string::String;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Error {
     Network(String),
+    /// ホスト名からIPアドレスを見つけられなかった
+    Dns(String),
+    /// TLSのハンドシェイクで、サーバー証明書を検証できなかった
+    Certificate(String),
     UnexpectedInput(String),
+    InvalidUrl(UrlParseError),
+    /// サーバーから受け取ったレスポンスを解析できなかった
+    InvalidResponse(HttpParseError),
     InvalidUI(String),
+    /// 決められた時間内に処理が終わらなかった
+    Timeout(String),
+    /// レスポンスを1バイトも受け取る前に、サーバーが接続を閉じた。
+    /// 再利用した接続では、サーバーが既に閉じていた接続として扱う
+    NoResponse(String),
+    /// ユーザーが処理を取り消した
+    Cancelled,
+    /// ページの解釈や描画、スクリプトの実行の途中でパニックが起きた
+    Crashed(String),
     Other(String),
 }
+
+impl From<UrlParseError> for Error {
+    fn from(e: UrlParseError) -> Self {
+        Error::InvalidUrl(e)
+    }
+}
+
+impl From<HttpParseError> for Error {
+    fn from(e: HttpParseError) -> Self {
+        Error::InvalidResponse(e)
+    }
+}
@@ -0,0 +1,448 @@
+use crate::config::BrowserConfig;
+use crate::console::ConsoleEntry;
+use crate::error::Error;
+use crate::renderer::css::cssom::CascadeOrigin;
+use crate::renderer::css::cssom::CssParser;
+use crate::renderer::css::cssom::Selector;
+use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::css::token::CssTokenizer;
+use crate::renderer::dom::node::Node;
+use crate::renderer::dom::node::NodeKind;
+use crate::utils::form_urldecode;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+pub static ABOUT_BLANK: &str = "about:blank";
+pub static ABOUT_VERSION: &str = "about:version";
+// 直前に表示していたページのDOMツリーとCSSのルールを表示する、デバッグ用のページ
+pub static ABOUT_DOM: &str = "about:dom";
+pub static ABOUT_CSS: &str = "about:css";
+// スクリプトの出力と、コンソールに入力した式の評価結果を表示するページ
+pub static ABOUT_CONSOLE: &str = "about:console";
+// エラーページの"Retry"のリンクが指す、表示中のページを読み込み直す操作。ページとしては表示しない
+pub static ABOUT_RETRY: &str = "about:retry";
+// User-Agentと、全てのリクエストに付けるAccept、Accept-Languageヘッダを変更する設定ページ。
+// "about:headers?user-agent=..."のようにクエリを付けてアドレスバーに入力すると、値を変更してから表示する
+pub static ABOUT_HEADERS: &str = "about:headers";
+pub static VIEW_SOURCE_PREFIX: &str = "view-source:";
+
+// 内部ページ、エラーページ、ソースの表示のような、ブラウザが作ったページのスタイル。
+// 作者のスタイルの代わりに、ユーザーエージェントのスタイルとして適用する
+pub static INTERNAL_PAGE_STYLE: &str = "body { color: black; } a { color: blue; }";
+
+/// "about:"から始まるURLはネットワークを使わず、ブラウザ内部のページとして扱う
+pub fn is_internal_url(url: &str) -> bool {
+    url.starts_with("about:")
+}
+
+/// "view-source:http://host/page.html"のようなURLから、取得するリソースのURLを取り出す
+pub fn view_source_target(url: &str) -> Option<&str> {
+    match url.strip_prefix(VIEW_SOURCE_PREFIX) {
+        Some(target) if !target.is_empty() => Some(target),
+        _ => None,
+    }
+}
+
+/// ブラウザが作ったページに適用する、ユーザーエージェントのスタイルシート
+pub fn internal_page_style() -> StyleSheet {
+    let mut style =
+        CssParser::new(CssTokenizer::new(INTERNAL_PAGE_STYLE.to_string())).parse_stylesheet();
+    style.set_origin(CascadeOrigin::UserAgent);
+    style
+}
+
+/// 内部ページのURLに対応するHTMLを返す
+pub fn internal_page_html(url: &str) -> Result<String, Error> {
+    match url {
+        "about:blank" => Ok("<html><head></head><body></body></html>".to_string()),
+        "about:version" => Ok(format!(
+            "<html><head></head><body><h1>{}</h1><p>Version: {}</p><p>Target: {}</p></body></html>",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            if cfg!(target_os = "none") {
+                "wasabi"
+            } else {
+                "host"
+            },
+        )),
+        _ => Err(Error::UnexpectedInput(format!(
+            "internal page {} is not supported",
+            url
+        ))),
+    }
+}
+
+/// エラーの種類を表す、エラーページに表示するコード
+fn error_code(error: &Error) -> &'static str {
+    match error {
+        Error::Dns(_) => "ERR_NAME_NOT_RESOLVED",
+        Error::Network(_) => "ERR_CONNECTION_FAILED",
+        Error::Certificate(_) => "ERR_CERT_INVALID",
+        Error::Timeout(_) => "ERR_TIMED_OUT",
+        Error::NoResponse(_) => "ERR_EMPTY_RESPONSE",
+        Error::InvalidUrl(_) => "ERR_INVALID_URL",
+        Error::InvalidResponse(_) => "ERR_INVALID_RESPONSE",
+        Error::Cancelled => "ERR_ABORTED",
+        Error::Crashed(_) => "ERR_CRASHED",
+        Error::UnexpectedInput(_) | Error::InvalidUI(_) | Error::Other(_) => "ERR_FAILED",
+    }
+}
+
+/// ページを読み込めなかったときに表示するHTMLを返す。
+/// 理由の説明とエラーの種類のコードに加えて、読み込み直すための"Retry"のリンクを表示する
+pub fn error_page_html(url: &str, error: &Error) -> String {
+    let message = match error {
+        Error::Timeout(detail) => format!("The server took too long to respond: {}", detail),
+        Error::NoResponse(detail) => format!("The server did not send any data: {}", detail),
+        Error::Dns(host) => format!("The server's address could not be found: {}", host),
+        Error::Network(detail) => format!("Could not connect to the server: {}", detail),
+        Error::Certificate(reason) => {
+            format!("The server's certificate could not be verified: {}", reason)
+        }
+        Error::InvalidUrl(e) => format!("The address is not valid: {}", e),
+        Error::InvalidResponse(e) => format!("The server sent an invalid response: {}", e),
+        Error::Cancelled => "The page load was cancelled.".to_string(),
+        Error::Crashed(reason) => format!("The page crashed while loading: {}", reason),
+        Error::UnexpectedInput(detail) | Error::InvalidUI(detail) | Error::Other(detail) => {
+            detail.clone()
+        }
+    };
+
+    format!(
+        "<html><head></head><body><h1>This page cannot be displayed</h1><p>{}</p><p>{}</p><p>{}</p><p><a href=\"{}\">Retry</a></p></body></html>",
+        strip_markup(url),
+        strip_markup(&message),
+        error_code(error),
+        ABOUT_RETRY
+    )
+}
+
+/// 404や500のようなエラーのレスポンスにボディがないときに、代わりに表示するHTMLを返す
+pub fn status_page_html(status_code: u32, reason: &str) -> String {
+    format!(
+        "<html><head></head><body><h1>{} {}</h1><p>The server returned an error without a page to display.</p><p>HTTP ERROR {}</p><p><a href=\"{}\">Retry</a></p></body></html>",
+        status_code,
+        strip_markup(reason),
+        status_code,
+        ABOUT_RETRY
+    )
+}
+
+/// 表示できない種類のレスポンスを受け取ったときに、代わりに表示するHTMLを返す
+pub fn unsupported_content_page_html(mime_type: &str) -> String {
+    format!(
+        "<html><head></head><body><h1>This page cannot be displayed</h1><p>The content type {} is not supported.</p></body></html>",
+        strip_markup(mime_type)
+    )
+}
+
+/// about:domのHTML。DOMツリーのノードを、子ノードを入れ子の<ul>にした<li>として文書順に並べる。
+/// HTMLのソースから作られたノードには、ソースの中の行と列を添える
+pub fn dom_page_html(document: &Option<Rc<RefCell<Node>>>) -> String {
+    /// 辿る途中のノードと、子ノードを全て並べた後に閉じるリスト
+    enum Visit {
+        Node(Rc<RefCell<Node>>),
+        Close,
+    }
+
+    let mut list = String::new();
+    // 深くネストした文書でもスタックを使い切らないように、明示的なスタックで辿る
+    let mut stack: Vec<Visit> = Vec::new();
+    if let Some(document) = document {
+        stack.push(Visit::Node(document.clone()));
+    }
+    while let Some(visit) = stack.pop() {
+        let node = match visit {
+            Visit::Node(node) => node,
+            Visit::Close => {
+                list.push_str("</ul></li>");
+                continue;
+            }
+        };
+
+        let label = match node.borrow().kind() {
+            NodeKind::Document => "#document".to_string(),
+            NodeKind::Element(e) => {
+                let mut label = e.kind().to_string();
+                for attr in e.attributes() {
+                    label.push_str(&format!(" {}=\"{}\"", attr.name(), attr.value()));
+                }
+                label
+            }
+            NodeKind::Text(text) => format!("#text \"{}\"", text.trim()),
+            NodeKind::Comment(data) => format!("#comment \"{}\"", data.trim()),
+        };
+        let label = match node.borrow().source_position() {
+            Some(position) => format!("{} ({})", label, position),
+            None => label,
+        };
+        list.push_str(&format!("<li>{}", strip_markup(&label)));
+
+        let mut children = Vec::new();
+        let mut child = node.borrow().first_child();
+        while let Some(c) = child {
+            child = c.borrow().next_sibling();
+            children.push(c);
+        }
+        if children.is_empty() {
+            list.push_str("</li>");
+            continue;
+        }
+
+        // 子ノードを先に辿るため、逆順にスタックに積む
+        list.push_str("<ul>");
+        stack.push(Visit::Close);
+        for c in children.into_iter().rev() {
+            stack.push(Visit::Node(c));
+        }
+    }
+
+    tree_page_html("DOM tree", list)
+}
+
+/// about:cssのHTML。ルールをスタイルシートに書かれた順に<li>で並べ、宣言をその中の<ul>に並べる
+pub fn css_page_html(style: &Option<StyleSheet>) -> String {
+    let mut list = String::new();
+    for rule in style.iter().flat_map(|s| s.rules.iter()) {
+        list.push_str(&format!(
+            "<li>{}<ul>",
+            strip_markup(&selector_text(&rule.selector))
+        ));
+        for declaration in &rule.declarations {
+            list.push_str(&format!(
+                "<li>{}</li>",
+                strip_markup(&declaration.css_text())
+            ));
+        }
+        list.push_str("</ul></li>");
+    }
+
+    tree_page_html("CSS rules", list)
+}
+
+/// about:headersのページ。現在の値と、値を変更するためにアドレスバーに入力するURLを表示する
+pub fn headers_page_html(config: &BrowserConfig) -> String {
+    let accept_language = config
+        .accept_language()
+        .unwrap_or_else(|| "(not sent)".to_string());
+    format!(
+        "<html><head></head><body><h1>Request headers</h1><p>User-Agent: {}</p><p>Accept: {}</p><p>Accept-Language: {}</p><p>To change a header, type {}?user-agent=VALUE, {}?accept=VALUE or {}?accept-language=VALUE into the address bar. An empty accept-language stops sending the header, and {}?reset restores the defaults.</p></body></html>",
+        strip_markup(&config.user_agent()),
+        strip_markup(&config.accept()),
+        strip_markup(&accept_language),
+        ABOUT_HEADERS,
+        ABOUT_HEADERS,
+        ABOUT_HEADERS,
+        ABOUT_HEADERS
+    )
+}
+
+/// about:headersのURLのクエリで、`config`の値を変更する。
+/// "user-agent=saba%2F2.0&accept-language=ja"のように、"&"で区切って複数の値を変更できる。
+/// BrowserConfig::serializeで保存した設定も、これで読み込む
+pub fn apply_headers_query(config: &mut BrowserConfig, query: &str) -> Result<(), Error> {
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = form_urldecode(value).trim().to_string();
+        match name {
+            "user-agent" if !value.is_empty() => config.set_user_agent(value),
+            "accept" if !value.is_empty() => config.set_accept(value),
+            "accept-language" if value.is_empty() => config.set_accept_language(None),
+            "accept-language" => config.set_accept_language(Some(value)),
+            "reset" => *config = BrowserConfig::new(),
+            _ => {
+                return Err(Error::UnexpectedInput(format!(
+                    "{} cannot change {}",
+                    ABOUT_HEADERS,
+                    strip_markup(pair)
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// about:consoleのHTML。コンソールの記録を古い順に並べる
+pub fn console_page_html(entries: &[ConsoleEntry]) -> String {
+    debug_page_html("Console", entries.iter().map(|e| e.line()).collect())
+}
+
+fn debug_page_html(title: &str, lines: Vec<String>) -> String {
+    let mut body = format!("<h1>{}</h1>", title);
+    if lines.is_empty() {
+        body.push_str("<p>(empty)</p>");
+    }
+    for line in lines {
+        body.push_str(&format!("<p>{}</p>", strip_markup(&line)));
+    }
+
+    format!("<html><head></head><body>{}</body></html>", body)
+}
+
+/// `list`は<li>を並べたHTML。空の場合は"(empty)"と表示する
+fn tree_page_html(title: &str, list: String) -> String {
+    let body = if list.is_empty() {
+        "<p>(empty)</p>".to_string()
+    } else {
+        format!("<ul>{}</ul>", list)
+    };
+
+    format!(
+        "<html><head></head><body><h1>{}</h1>{}</body></html>",
+        title, body
+    )
+}
+
+fn selector_text(selector: &Selector) -> String {
+    match selector {
+        Selector::UnknownSelector => "(unknown selector)".to_string(),
+        selector => selector.selector_text(),
+    }
+}
+
+/// HTMLのタグとして解釈される文字を取り除く。
+/// "&"は文字参照として解釈されないように、"&amp;"にする
+fn strip_markup(text: &str) -> String {
+    text.chars()
+        .filter(|c| *c != '<' && *c != '>')
+        .collect::<String>()
+        .replace('&', "&amp;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::cssom::CssParser;
+    use crate::renderer::css::token::CssTokenizer;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+
+    #[test]
+    fn test_is_internal_url() {
+        assert!(is_internal_url("about:blank"));
+        assert!(is_internal_url("about:version"));
+        assert!(!is_internal_url("http://example.com"));
+    }
+
+    #[test]
+    fn test_view_source_target() {
+        assert_eq!(
+            Some("http://example.com/index.html"),
+            view_source_target("view-source:http://example.com/index.html")
+        );
+        assert_eq!(None, view_source_target("view-source:"));
+        assert_eq!(None, view_source_target("http://example.com"));
+    }
+
+    #[test]
+    fn test_about_blank() {
+        assert_eq!(
+            Ok("<html><head></head><body></body></html>".to_string()),
+            internal_page_html(ABOUT_BLANK)
+        );
+    }
+
+    #[test]
+    fn test_about_version() {
+        let html = internal_page_html(ABOUT_VERSION).expect("about:version should exist");
+        assert!(html.contains(env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn test_error_page_html() {
+        let html = error_page_html(
+            "http://example.com/<script>",
+            &Error::Timeout("reading a response timed out".to_string()),
+        );
+        assert!(html.contains("The server took too long to respond: reading a response timed out"));
+        assert!(html.contains("http://example.com/script"));
+        assert!(html.contains("<p>ERR_TIMED_OUT</p>"));
+        assert!(html.contains("<a href=\"about:retry\">Retry</a>"));
+
+        // 名前解決の失敗と接続の失敗を区別して表示する
+        let html = error_page_html(
+            "http://unknown.example/",
+            &Error::Dns("unknown.example".to_string()),
+        );
+        assert!(html.contains("The server's address could not be found: unknown.example"));
+        assert!(html.contains("<p>ERR_NAME_NOT_RESOLVED</p>"));
+        assert!(status_page_html(404, "Not Found").contains("<p>HTTP ERROR 404</p>"));
+
+        let html = error_page_html(
+            "https://example.com/",
+            &Error::Certificate("certificate has expired".to_string()),
+        );
+        assert!(html.contains("<p>ERR_CERT_INVALID</p>"));
+
+        // URLの"&"は、文字参照として解釈されずにそのまま表示される
+        let html = error_page_html(
+            "http://example.com/?a=1&copy=2",
+            &Error::Network("refused".to_string()),
+        );
+        assert!(html.contains("http://example.com/?a=1&amp;copy=2"));
+    }
+
+    #[test]
+    fn test_apply_headers_query() {
+        let mut config = BrowserConfig::new();
+        apply_headers_query(
+            &mut config,
+            "user-agent=saba%2F2.0+(test)&accept=text%2Fplain&accept-language=ja",
+        )
+        .expect("failed to apply the query");
+        assert_eq!("saba/2.0 (test)", config.user_agent());
+        assert_eq!("text/plain", config.accept());
+        assert_eq!(Some("ja".to_string()), config.accept_language());
+        assert!(headers_page_html(&config).contains("<p>User-Agent: saba/2.0 (test)</p>"));
+
+        apply_headers_query(&mut config, "accept-language=").expect("failed to apply the query");
+        assert_eq!(None, config.accept_language());
+        assert!(apply_headers_query(&mut config, "user-agent=").is_err());
+        assert!(apply_headers_query(&mut config, "cookie=a").is_err());
+
+        // 保存した設定は、同じクエリとして読み込める
+        let mut loaded = BrowserConfig::new();
+        apply_headers_query(&mut loaded, &config.serialize()).expect("failed to load the query");
+        assert_eq!(config, loaded);
+
+        apply_headers_query(&mut config, "reset").expect("failed to apply the query");
+        assert_eq!(BrowserConfig::new(), config);
+    }
+
+    #[test]
+    fn test_dom_page_html() {
+        let window = HtmlParser::new(HtmlTokenizer::new(
+            "<html><head></head><body><p id=\"a\">hi</p></body></html>".to_string(),
+        ))
+        .construct_tree();
+        let html = dom_page_html(&Some(window.borrow().document()));
+        assert_eq!(
+            "<html><head></head><body><h1>DOM tree</h1><ul><li>#document<ul><li>html (line 1, column 1)<ul><li>head (line 1, column 7)</li><li>body (line 1, column 20)<ul><li>p id=\"a\" (line 1, column 26)<ul><li>#text \"hi\" (line 1, column 36)</li></ul></li></ul></li></ul></li></ul></li></ul></body></html>",
+            html
+        );
+        assert!(dom_page_html(&None).contains("(empty)"));
+    }
+
+    #[test]
+    fn test_css_page_html() {
+        let style = CssParser::new(CssTokenizer::new(
+            "p { color: red; } .a:first-child { width: 10; } #b { background-color: #ff0000; }"
+                .to_string(),
+        ))
+        .parse_stylesheet();
+        let html = css_page_html(&Some(style));
+        assert_eq!(
+            "<html><head></head><body><h1>CSS rules</h1><ul><li>p<ul><li>color: red</li></ul></li><li>.a:first-child<ul><li>width: 10</li></ul></li><li>#b<ul><li>background-color: #ff0000</li></ul></li></ul></body></html>",
+            html
+        );
+    }
+
+    #[test]
+    fn test_unknown_internal_page() {
+        assert!(internal_page_html("about:foo").is_err());
+    }
+}
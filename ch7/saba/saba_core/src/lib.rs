@@ -3,10 +3,32 @@
 extern crate alloc;
 
 pub mod browser;
+pub mod cache;
+pub mod config;
+pub mod connection_pool;
+pub mod console;
 pub mod constants;
+pub mod cookie;
+pub mod crawler;
+pub mod deadline;
 pub mod display_item;
+pub mod download;
+pub mod encoding;
 pub mod error;
 pub mod http;
+pub mod internal_page;
+pub mod mime;
+pub mod navigation;
+pub mod page_observer;
+pub mod permission;
+pub mod platform;
+pub mod punycode;
 pub mod renderer;
+pub mod resource_loader;
+pub mod security;
+pub mod svg_export;
+pub mod task;
+pub mod text;
+pub mod transport;
 pub mod url;
 pub mod utils;
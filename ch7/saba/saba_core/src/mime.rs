@@ -0,0 +1,168 @@
+//! レスポンスのContent-Typeから、ページをどのように表示するかを決める。
+//! Content-Typeがない場合は、ボディの先頭を見て判断する
+//! https://mimesniff.spec.whatwg.org/
+
+use crate::http::HttpResponse;
+use alloc::string::String;
+use alloc::string::ToString;
+
+/// レスポンスの表示のしかた
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentKind {
+    /// HTMLとして解釈する
+    Html,
+    /// 書かれたとおりの文字として表示する
+    PlainText,
+    /// 表示できない種類。MIMEタイプのessenceを持つ
+    Unsupported(String),
+}
+
+/// "text/html; charset=utf-8"のようなMIMEタイプから、パラメータを除いた"text/html"を小文字で返す
+/// https://mimesniff.spec.whatwg.org/#mime-type-essence
+pub fn essence(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// "text/html; charset=Shift_JIS"のようなMIMEタイプから、charsetパラメータの値を引用符を除いて返す
+/// https://mimesniff.spec.whatwg.org/#parsing-a-mime-type
+pub fn charset(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|parameter| {
+        let (name, value) = parameter.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// レスポンスのContent-Typeとボディから、表示のしかたを決める
+pub fn content_kind(response: &HttpResponse) -> ContentKind {
+    match response.header_value("Content-Type") {
+        Ok(content_type) if !essence(&content_type).is_empty() => {
+            kind_of_essence(&essence(&content_type))
+        }
+        _ => sniff(&response.body()),
+    }
+}
+
+fn kind_of_essence(essence: &str) -> ContentKind {
+    match essence {
+        "text/html" | "application/xhtml+xml" => ContentKind::Html,
+        "application/json" | "application/javascript" | "application/xml" => ContentKind::PlainText,
+        _ if essence.starts_with("text/") => ContentKind::PlainText,
+        _ => ContentKind::Unsupported(essence.to_string()),
+    }
+}
+
+/// HTMLの始まりを表すタグ。大文字と小文字を区別せず、後ろに空白か">"が続くものを探す
+/// https://mimesniff.spec.whatwg.org/#rules-for-identifying-an-unknown-mime-type
+const HTML_PATTERNS: [&str; 17] = [
+    "<!doctype html",
+    "<html",
+    "<head",
+    "<script",
+    "<iframe",
+    "<h1",
+    "<div",
+    "<font",
+    "<table",
+    "<a",
+    "<style",
+    "<title",
+    "<b",
+    "<body",
+    "<br",
+    "<p",
+    "<!--",
+];
+
+/// Content-Typeがないレスポンスのボディから、表示のしかたを決める
+pub fn sniff(body: &str) -> ContentKind {
+    let start = body.trim_start_matches(['\t', '\n', '\x0c', '\r', ' ']);
+    for pattern in HTML_PATTERNS {
+        let head = match start.get(..pattern.len()) {
+            Some(head) => head,
+            None => continue,
+        };
+        if !head.eq_ignore_ascii_case(pattern) {
+            continue;
+        }
+        // "<!--"以外は、タグ名の後ろに空白か">"が続く
+        let terminated =
+            pattern == "<!--" || matches!(start[pattern.len()..].chars().next(), Some(' ' | '>'));
+        if terminated {
+            return ContentKind::Html;
+        }
+    }
+
+    // 制御文字や、UTF-8として読めなかった文字を含む場合はバイナリとみなす
+    // https://mimesniff.spec.whatwg.org/#binary-data-byte
+    let binary = body.chars().any(|c| {
+        c == '\u{FFFD}'
+            || (c.is_ascii_control() && !matches!(c, '\t' | '\n' | '\x0c' | '\r' | '\x1b'))
+    });
+    if binary {
+        ContentKind::Unsupported("application/octet-stream".to_string())
+    } else {
+        ContentKind::PlainText
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(headers: &str, body: &str) -> HttpResponse {
+        HttpResponse::new(alloc::format!("HTTP/1.1 200 OK\n{}\n{}", headers, body))
+            .expect("failed to parse http response")
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!("text/html", essence(" Text/HTML ; charset=utf-8"));
+        assert_eq!(
+            Some("Shift_JIS".to_string()),
+            charset("text/html; q=1; Charset=\"Shift_JIS\"")
+        );
+        assert_eq!(None, charset("text/html"));
+        assert_eq!(
+            ContentKind::Html,
+            content_kind(&response(
+                "Content-Type: text/html; charset=utf-8\n",
+                "hello"
+            ))
+        );
+        assert_eq!(
+            ContentKind::PlainText,
+            content_kind(&response("Content-Type: text/plain\n", "<html>"))
+        );
+        assert_eq!(
+            ContentKind::PlainText,
+            content_kind(&response("Content-Type: application/json\n", "{}"))
+        );
+        assert_eq!(
+            ContentKind::Unsupported("image/png".to_string()),
+            content_kind(&response("Content-Type: image/png\n", "\u{FFFD}PNG"))
+        );
+    }
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(ContentKind::Html, sniff("\n  <!DOCTYPE html><html></html>"));
+        assert_eq!(ContentKind::Html, sniff("<p>hello</p>"));
+        assert_eq!(ContentKind::Html, sniff("<!-- comment -->"));
+        // "<pre>"は"<p"の後ろに空白も">"もないので、HTMLの始まりとはみなさない
+        assert_eq!(ContentKind::PlainText, sniff("<pre>"));
+        assert_eq!(ContentKind::PlainText, sniff("hello, world\n"));
+        assert_eq!(
+            ContentKind::Unsupported("application/octet-stream".to_string()),
+            sniff("\u{0}\u{1}data")
+        );
+        // Content-Typeがない場合も、ボディを見て決める
+        assert_eq!(ContentKind::Html, content_kind(&response("", "<html>")));
+    }
+}
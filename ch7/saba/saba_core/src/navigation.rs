@@ -0,0 +1,252 @@
+//! ページの移動。アドレスバーへの入力、リンクのクリック、履歴の移動、スクリプトのlocationへの代入の
+//! どれから始まった移動も、Browser::request_navigationを通して行う。
+//! ページを離れる前にページのbeforeunloadのハンドラを呼び、ページが確認を求めた場合は、
+//! UIがユーザーに尋ねてBrowser::confirm_navigationで答えを渡すまで移動を保留する
+//! https://html.spec.whatwg.org/multipage/browsing-the-web.html#navigating-across-documents
+
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// 移動が始まった場所
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavigationSource {
+    /// アドレスバーに入力されたURLか検索語
+    AddressBar,
+    /// ページの中のリンク
+    Link,
+    /// 表示中のページの読み込み直し。履歴は変えない
+    Reload,
+    /// 履歴の1つ前のページ
+    Back,
+    /// 履歴の1つ後のページ
+    Forward,
+    /// スクリプトのlocation.hrefへの代入とlocation.assign()
+    Script,
+}
+
+/// 保留している移動や、これから行う移動
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NavigationRequest {
+    destination: String,
+    source: NavigationSource,
+}
+
+impl NavigationRequest {
+    pub fn new(destination: &str, source: NavigationSource) -> Self {
+        Self {
+            destination: destination.to_string(),
+            source,
+        }
+    }
+
+    pub fn destination(&self) -> String {
+        self.destination.clone()
+    }
+
+    pub fn source(&self) -> NavigationSource {
+        self.source
+    }
+}
+
+/// Browser::request_navigationの結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavigationOutcome {
+    /// 移動先のページを読み込んだ
+    Committed,
+    /// ページが離れることの確認を求めたので、ユーザーの答えを待っている
+    Blocked,
+    /// ユーザーがページに留まることを選んだか、履歴に移動先がなかった
+    Cancelled,
+}
+
+/// 履歴の1つのエントリ。タイトルは、ページを読み込んだ後に分かる
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    url: String,
+    title: String,
+}
+
+impl HistoryEntry {
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// ページのタイトル。まだ読み込んでいないか、タイトルがないページの場合は空文字列
+    pub fn title(&self) -> String {
+        self.title.clone()
+    }
+}
+
+/// ページごとの履歴。移動したURLを順に持ち、戻ると進むで表示する位置を動かす
+/// https://html.spec.whatwg.org/multipage/document-sequences.html#tn-session-history-entries
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionHistory {
+    entries: Vec<HistoryEntry>,
+    /// 表示しているエントリの位置。履歴が空の場合は0
+    index: usize,
+}
+
+impl SessionHistory {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            index: 0,
+        }
+    }
+
+    /// 新しいページに移動したことを記録する。表示中の位置より後ろのエントリは捨てる。
+    /// 表示中のページと同じURLは、読み込み直しとして扱い追加しない
+    pub fn push(&mut self, url: &str) {
+        if self.current().as_deref() == Some(url) {
+            return;
+        }
+        if !self.entries.is_empty() {
+            self.entries.truncate(self.index + 1);
+        }
+        self.entries.push(HistoryEntry {
+            url: url.to_string(),
+            title: String::new(),
+        });
+        self.index = self.entries.len() - 1;
+    }
+
+    /// 表示しているエントリを、新しいURLで置き換える。履歴が空の場合は追加する。
+    /// 読み込み中のページのスクリプトがlocationで移動した場合に使い、戻るで移動元に戻らないようにする
+    /// https://html.spec.whatwg.org/multipage/browsing-the-web.html#navigationhistorybehavior
+    pub fn replace(&mut self, url: &str) {
+        match self.entries.get_mut(self.index) {
+            Some(entry) => {
+                entry.url = url.to_string();
+                entry.title = String::new();
+            }
+            None => self.push(url),
+        }
+    }
+
+    /// 表示しているエントリに、読み込んだページのタイトルを記録する
+    pub fn set_title(&mut self, title: &str) {
+        if let Some(entry) = self.entries.get_mut(self.index) {
+            entry.title = title.to_string();
+        }
+    }
+
+    pub fn current(&self) -> Option<String> {
+        self.entries.get(self.index).map(|e| e.url())
+    }
+
+    pub fn back_url(&self) -> Option<String> {
+        self.index
+            .checked_sub(1)
+            .and_then(|i| self.entries.get(i))
+            .map(|e| e.url())
+    }
+
+    pub fn forward_url(&self) -> Option<String> {
+        self.entries.get(self.index + 1).map(|e| e.url())
+    }
+
+    /// 表示する位置を`delta`だけ動かす。履歴の範囲を超える場合は何もしない
+    pub fn go(&mut self, delta: i64) {
+        let index = self.index as i64 + delta;
+        if 0 <= index && (index as usize) < self.entries.len() {
+            self.index = index as usize;
+        }
+    }
+
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        self.entries.clone()
+    }
+
+    /// エントリのURLを、古いものから順に返す
+    pub fn urls(&self) -> Vec<String> {
+        self.entries.iter().map(|e| e.url()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_history() {
+        let mut history = SessionHistory::new();
+        assert_eq!(None, history.back_url());
+        history.push("http://example.com/a");
+        history.push("http://example.com/b");
+        history.push("http://example.com/b");
+        history.push("http://example.com/c");
+        assert_eq!(3, history.len());
+        assert_eq!(Some("http://example.com/b".to_string()), history.back_url());
+        assert_eq!(None, history.forward_url());
+
+        history.go(-2);
+        assert_eq!(Some("http://example.com/a".to_string()), history.current());
+        assert_eq!(
+            Some("http://example.com/b".to_string()),
+            history.forward_url()
+        );
+        history.go(-1);
+        assert_eq!(Some("http://example.com/a".to_string()), history.current());
+
+        // 戻った位置から新しいページに移動すると、後ろのエントリは捨てる
+        history.go(1);
+        history.push("http://example.com/d");
+        assert_eq!(
+            alloc::vec![
+                "http://example.com/a".to_string(),
+                "http://example.com/b".to_string(),
+                "http://example.com/d".to_string(),
+            ],
+            history.urls()
+        );
+        assert_eq!(None, history.forward_url());
+    }
+
+    #[test]
+    fn test_session_history_replace() {
+        let mut history = SessionHistory::new();
+        history.replace("http://example.com/a");
+        history.push("http://example.com/b");
+        history.set_title("B");
+        history.replace("http://example.com/c");
+        assert_eq!(
+            alloc::vec![
+                "http://example.com/a".to_string(),
+                "http://example.com/c".to_string(),
+            ],
+            history.urls()
+        );
+        assert_eq!("", history.entries()[1].title());
+        assert_eq!(Some("http://example.com/a".to_string()), history.back_url());
+    }
+
+    #[test]
+    fn test_session_history_title() {
+        let mut history = SessionHistory::new();
+        history.push("http://example.com/a");
+        history.set_title("A");
+        history.push("http://example.com/b");
+        assert_eq!("", history.entries()[1].title());
+
+        // 戻った先のエントリのタイトルを、読み込み直した後のタイトルで更新する
+        history.go(-1);
+        history.set_title("A2");
+        assert_eq!(
+            alloc::vec!["A2".to_string(), "".to_string()],
+            history
+                .entries()
+                .iter()
+                .map(|e| e.title())
+                .collect::<Vec<String>>()
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use crate::download::Download;
+use crate::permission::Permission;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+
+/// ページの読み込みの状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadState {
+    /// ドキュメントを解析している
+    Loading,
+    /// ドキュメントの解析、スクリプトの実行、レイアウトが終わった
+    Complete,
+    /// ページを読み込めず、エラーページを表示した
+    Failed,
+}
+
+/// マウスカーソルの形
+/// https://drafts.csswg.org/css-ui/#cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorKind {
+    Default,
+    /// リンクの上にある
+    Pointer,
+}
+
+/// ページの状態が変わったことを、ブラウザを埋め込んでいる側（UIなど）に知らせる。
+/// Browser::add_observerで登録する。必要な通知のメソッドだけを実装すればよい
+pub trait PageObserver {
+    fn on_title_changed(&self, _title: &str) {}
+
+    fn on_load_state(&self, _state: LoadState) {}
+
+    /// ページの<link rel="icon">が指すアイコンを取得できた。`url`はページのURLを基準に解決したURL
+    fn on_favicon(&self, _url: &str) {}
+
+    /// スクリプトのconsole.log()で出力された文字列や、ページが使うリソースを読み込めなかったエラー
+    fn on_console_message(&self, _message: &str) {}
+
+    /// ページが使うリソースを読み込めなかった。`count`は表示中のページで読み込めなかったリソースの数
+    fn on_subresource_error(&self, _count: usize) {}
+
+    fn on_cursor_change(&self, _cursor: CursorKind) {}
+
+    /// ページとして表示せずに、ダウンロードしたファイル
+    fn on_download(&self, _download: &Download) {}
+
+    /// `origin`に`permission`を許可するかを、ユーザーに尋ねる必要がある。
+    /// 答えはBrowser::set_permissionで渡し、許可された場合はページを読み込み直す
+    fn on_permission_request(&self, _origin: &str, _permission: Permission) {}
+
+    /// 表示中のページが、`destination`に移動する前にユーザーの確認を求めた。
+    /// 答えはBrowser::confirm_navigationで渡す
+    fn on_before_unload(&self, _destination: &str) {}
+}
+
+impl Debug for dyn PageObserver {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(f, "PageObserver")
+    }
+}
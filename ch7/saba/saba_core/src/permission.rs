@@ -0,0 +1,260 @@
+//! オリジンごとの権限。Cookieの保存と送信、スクリプトの実行を、サイトごとに許可またはブロックする。
+//! まだ決めていないオリジンの権限は、既定の値に従う。既定の値がAskの場合は、UIがユーザーに尋ね、
+//! 答えが出るまではブロックする。決めた権限はSettingsStorageに保存し、次に起動したときに読み込む
+//! https://w3c.github.io/permissions/
+
+use crate::error::Error;
+use crate::url::Url;
+use alloc::collections::BTreeMap;
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+
+/// オリジンごとに決める権限の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    /// Set-Cookieで受け取ったCookieを保存し、リクエストに付けて送る
+    Cookies,
+    /// ページのスクリプトを実行する
+    JavaScript,
+}
+
+impl Permission {
+    /// 設定の保存と、CLIのコマンドで使う名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            Permission::Cookies => "cookies",
+            Permission::JavaScript => "javascript",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "cookies" => Some(Permission::Cookies),
+            "javascript" | "js" => Some(Permission::JavaScript),
+            _ => None,
+        }
+    }
+
+    /// ユーザーに尋ねるときの、"Allow {origin} to ..."に続く説明
+    pub fn description(&self) -> &'static str {
+        match self {
+            Permission::Cookies => "store and send cookies",
+            Permission::JavaScript => "run JavaScript",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionState {
+    /// ユーザーに尋ねる。答えが出るまではブロックする
+    Ask,
+    Allow,
+    Block,
+}
+
+impl PermissionState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PermissionState::Ask => "ask",
+            PermissionState::Allow => "allow",
+            PermissionState::Block => "block",
+        }
+    }
+}
+
+/// SettingsStorageに権限を保存するときのキー
+pub const PERMISSIONS_SETTINGS_KEY: &str = "permissions";
+
+/// オリジンごとに決めた権限と、まだ決めていないオリジンに使う既定の値
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionManager {
+    decisions: BTreeMap<(String, Permission), bool>,
+    default_cookies: PermissionState,
+    default_javascript: PermissionState,
+    /// 既にユーザーに尋ねた権限。答えが出るまで、同じことを何度も尋ねないようにする
+    requested: BTreeSet<(String, Permission)>,
+}
+
+impl Default for PermissionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PermissionManager {
+    /// 既定では、どのオリジンにも全ての権限を許可する
+    pub fn new() -> Self {
+        Self {
+            decisions: BTreeMap::new(),
+            default_cookies: PermissionState::Allow,
+            default_javascript: PermissionState::Allow,
+            requested: BTreeSet::new(),
+        }
+    }
+
+    pub fn default_state(&self, permission: Permission) -> PermissionState {
+        match permission {
+            Permission::Cookies => self.default_cookies,
+            Permission::JavaScript => self.default_javascript,
+        }
+    }
+
+    pub fn set_default_state(&mut self, permission: Permission, state: PermissionState) {
+        match permission {
+            Permission::Cookies => self.default_cookies = state,
+            Permission::JavaScript => self.default_javascript = state,
+        }
+    }
+
+    /// `origin`に`permission`を許可しているかどうか。決めていない場合は既定の値を返す
+    pub fn state(&self, origin: &str, permission: Permission) -> PermissionState {
+        match self.decisions.get(&(origin.to_string(), permission)) {
+            Some(true) => PermissionState::Allow,
+            Some(false) => PermissionState::Block,
+            None => self.default_state(permission),
+        }
+    }
+
+    /// ユーザーが決めた権限を記録する
+    pub fn set(&mut self, origin: &str, permission: Permission, allowed: bool) {
+        let key = (origin.to_string(), permission);
+        self.requested.remove(&key);
+        self.decisions.insert(key, allowed);
+    }
+
+    /// `origin`について決めた権限を全て忘れ、既定の値に戻す
+    pub fn reset(&mut self, origin: &str) {
+        self.decisions.retain(|(o, _), _| o != origin);
+        self.requested.retain(|(o, _)| o != origin);
+    }
+
+    /// ユーザーに尋ねる必要があるかを確かめ、尋ねたことを記録する。
+    /// 権限がAskで、まだ尋ねていない場合にだけtrueを返す
+    pub fn take_request(&mut self, origin: &str, permission: Permission) -> bool {
+        self.state(origin, permission) == PermissionState::Ask
+            && self.requested.insert((origin.to_string(), permission))
+    }
+
+    /// 決めた権限を、1行に1つずつ"origin permission allow|block"の形式で書き出す
+    pub fn serialize(&self) -> String {
+        let mut text = String::new();
+        for ((origin, permission), allowed) in &self.decisions {
+            let state = if *allowed {
+                PermissionState::Allow
+            } else {
+                PermissionState::Block
+            };
+            text.push_str(&format!(
+                "{} {} {}\n",
+                origin,
+                permission.name(),
+                state.name()
+            ));
+        }
+        text
+    }
+
+    /// serializeで書き出した権限を読み込み、今の記録に加える
+    pub fn load(&mut self, text: &str) -> Result<(), Error> {
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let mut fields = line.split_whitespace();
+            let (origin, permission, state) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(origin), Some(permission), Some(state)) => (origin, permission, state),
+                _ => {
+                    return Err(Error::UnexpectedInput(format!(
+                        "invalid permission setting: {}",
+                        line
+                    )))
+                }
+            };
+            let permission = Permission::from_name(permission).ok_or_else(|| {
+                Error::UnexpectedInput(format!("unknown permission: {}", permission))
+            })?;
+            let allowed = match state {
+                "allow" => true,
+                "block" => false,
+                _ => {
+                    return Err(Error::UnexpectedInput(format!(
+                        "invalid permission state: {}",
+                        state
+                    )))
+                }
+            };
+            self.set(origin, permission, allowed);
+        }
+        Ok(())
+    }
+}
+
+/// 権限を決める単位になる、URLのオリジン。about:のような内部ページと解析できないURLはNoneを返す
+pub fn origin_of(url: &str) -> Option<String> {
+    Url::new(url.to_string())
+        .parse()
+        .ok()
+        .map(|url| url.origin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_state() {
+        let mut permissions = PermissionManager::new();
+        assert_eq!(
+            PermissionState::Allow,
+            permissions.state("http://example.com", Permission::JavaScript)
+        );
+
+        permissions.set_default_state(Permission::JavaScript, PermissionState::Ask);
+        assert!(permissions.take_request("http://example.com", Permission::JavaScript));
+        // 答えが出るまでは、同じことを尋ねない
+        assert!(!permissions.take_request("http://example.com", Permission::JavaScript));
+        assert!(!permissions.take_request("http://example.com", Permission::Cookies));
+
+        permissions.set("http://example.com", Permission::JavaScript, false);
+        assert_eq!(
+            PermissionState::Block,
+            permissions.state("http://example.com", Permission::JavaScript)
+        );
+        assert_eq!(
+            PermissionState::Ask,
+            permissions.state("http://example.com:8080", Permission::JavaScript)
+        );
+
+        permissions.reset("http://example.com");
+        assert_eq!(
+            PermissionState::Ask,
+            permissions.state("http://example.com", Permission::JavaScript)
+        );
+    }
+
+    #[test]
+    fn test_serialize_permissions() {
+        let mut permissions = PermissionManager::new();
+        permissions.set("http://example.com", Permission::Cookies, false);
+        permissions.set("https://example.com", Permission::JavaScript, true);
+        let text = permissions.serialize();
+        assert_eq!(
+            "http://example.com cookies block\nhttps://example.com javascript allow\n",
+            text
+        );
+
+        let mut loaded = PermissionManager::new();
+        loaded.load(&text).expect("failed to load permissions");
+        assert_eq!(permissions, loaded);
+        assert!(loaded.load("http://example.com camera allow").is_err());
+        assert!(loaded.load("http://example.com cookies").is_err());
+    }
+
+    #[test]
+    fn test_origin_of() {
+        assert_eq!(
+            Some("http://example.com".to_string()),
+            origin_of("http://example.com/index.html#top")
+        );
+        assert_eq!(None, origin_of("about:blank"));
+    }
+}
@@ -0,0 +1,145 @@
+//! saba_coreが使う、OSに依存する機能。
+//! saba_coreはno_stdなので、時刻の取得やログの出力は、ブラウザを埋め込む側（wasabi OS、端末、
+//! WebAssemblyのホストなど）がトレイトを実装して渡す
+
+use alloc::string::String;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+
+/// 現在時刻を返す。HTTPキャッシュの鮮度の判断と、タスクの実行時間の計測に使う
+pub trait Clock {
+    /// 1970年1月1日からの秒数。時計を持たない環境ではNoneを返す
+    fn now(&self) -> Option<u64>;
+
+    /// ミリ秒単位の現在時刻。イベントループでタスクに使った時間を測る。
+    /// 細かい時刻を得られない環境ではNoneを返す
+    fn now_millis(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl Debug for dyn Clock {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(f, "Clock")
+    }
+}
+
+/// 時計を持たない環境のためのClock。wasabi OSには時計がないので、これを使う
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoClock;
+
+impl Clock for NoClock {
+    fn now(&self) -> Option<u64> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+impl LogLevel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogLevel::Info => "info",
+            LogLevel::Warning => "warning",
+            LogLevel::Error => "error",
+        }
+    }
+}
+
+/// ブラウザの内部で起きたことを記録する。ページのconsole.log()とは別に、開発者が読むためのもの
+pub trait Logger {
+    fn log(&self, level: LogLevel, message: &str);
+}
+
+impl Debug for dyn Logger {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(f, "Logger")
+    }
+}
+
+/// 何も出力しないLogger
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullLogger;
+
+impl Logger for NullLogger {
+    fn log(&self, _level: LogLevel, _message: &str) {}
+}
+
+/// 権限の設定のような、ブラウザを閉じた後も残しておく設定の保存先。
+/// 設定ごとのキーに、文字列にした値を保存する。SVGに書き出したページも、ファイル名をキーにして保存する
+pub trait SettingsStorage {
+    fn load(&self, key: &str) -> Option<String>;
+    fn save(&self, key: &str, value: &str);
+}
+
+impl Debug for dyn SettingsStorage {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(f, "SettingsStorage")
+    }
+}
+
+/// 何も保存しないSettingsStorage。wasabi OSにはファイルシステムがないので、これを使う
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoSettingsStorage;
+
+impl SettingsStorage for NoSettingsStorage {
+    fn load(&self, _key: &str) -> Option<String> {
+        None
+    }
+
+    fn save(&self, _key: &str, _value: &str) {}
+}
+
+/// 長く実行され続けているスクリプトを止めるかどうかを決める。
+/// ランタイムはSCRIPT_WATCHDOG_INTERVAL回の関数の呼び出しと文の実行ごとに尋ね、trueが返ると実行を中断する。
+/// UIは「ページが応答しません」のようなポップアップを出して、ユーザーの答えを待てばよい
+pub trait ScriptWatchdog {
+    /// `ticks`は、スクリプトの実行を始めてから数えた関数の呼び出しと文の実行の回数
+    fn should_interrupt(&self, ticks: u64) -> bool;
+}
+
+impl Debug for dyn ScriptWatchdog {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(f, "ScriptWatchdog")
+    }
+}
+
+/// スクリプトを止めないScriptWatchdog。ユーザーに尋ねる方法がない環境では、これを使う
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoScriptWatchdog;
+
+impl ScriptWatchdog for NoScriptWatchdog {
+    fn should_interrupt(&self, _ticks: u64) -> bool {
+        false
+    }
+}
+
+/// ページの読み込みの途中で起きたパニックを捕まえ、ブラウザ全体が止まらないようにする。
+/// 解釈できないCSSやスクリプトはパニックせずにエラーとして扱うが、それ以外の不具合で起きた
+/// パニックをページごとに区切るために使う
+pub trait PanicGuard {
+    /// `f`を実行する。パニックした場合は、その理由をErrで返す
+    fn run(&self, f: &mut dyn FnMut()) -> Result<(), String>;
+}
+
+impl Debug for dyn PanicGuard {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(f, "PanicGuard")
+    }
+}
+
+/// パニックを捕まえないPanicGuard。no_stdの環境ではパニックから戻る方法がないので、これを使う
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoPanicGuard;
+
+impl PanicGuard for NoPanicGuard {
+    fn run(&self, f: &mut dyn FnMut()) -> Result<(), String> {
+        f();
+        Ok(())
+    }
+}
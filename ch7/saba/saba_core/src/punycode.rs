@@ -0,0 +1,156 @@
+use crate::error::Error;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// https://datatracker.ietf.org/doc/html/rfc3492#section-5
+const BASE: u32 = 36;
+const TMIN: u32 = 1;
+const TMAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 128;
+
+/// https://datatracker.ietf.org/doc/html/rfc3492#section-6.1
+fn adapt(delta: u32, num_points: u32, first_time: bool) -> u32 {
+    let mut delta = if first_time { delta / DAMP } else { delta / 2 };
+    delta += delta / num_points;
+
+    let mut k = 0;
+    while delta > ((BASE - TMIN) * TMAX) / 2 {
+        delta /= BASE - TMIN;
+        k += BASE;
+    }
+
+    k + (((BASE - TMIN + 1) * delta) / (delta + SKEW))
+}
+
+fn encode_digit(d: u32) -> char {
+    // 0..25は'a'..'z'、26..35は'0'..'9'に対応する
+    if d < 26 {
+        (b'a' + d as u8) as char
+    } else {
+        (b'0' + (d - 26) as u8) as char
+    }
+}
+
+/// https://datatracker.ietf.org/doc/html/rfc3492#section-6.3
+pub fn encode(input: &str) -> Result<String, Error> {
+    let code_points: Vec<u32> = input.chars().map(|c| c as u32).collect();
+    let mut output: String = input.chars().filter(|c| c.is_ascii()).collect();
+
+    let basic_len = output.len() as u32;
+    let mut handled = basic_len;
+    if basic_len > 0 {
+        output.push('-');
+    }
+
+    let mut n = INITIAL_N;
+    let mut delta: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+    let overflow = || Error::UnexpectedInput(format!("punycode overflow: {}", input));
+
+    while (handled as usize) < code_points.len() {
+        // まだ処理していない文字のうち、最小のコードポイントを探す
+        let m = code_points
+            .iter()
+            .filter(|&&c| c >= n)
+            .min()
+            .copied()
+            .ok_or_else(overflow)?;
+
+        delta = (m - n)
+            .checked_mul(handled + 1)
+            .and_then(|d| d.checked_add(delta))
+            .ok_or_else(overflow)?;
+        n = m;
+
+        for &c in &code_points {
+            if c < n {
+                delta = delta.checked_add(1).ok_or_else(overflow)?;
+            }
+
+            if c == n {
+                let mut q = delta;
+                let mut k = BASE;
+                loop {
+                    let t = if k <= bias {
+                        TMIN
+                    } else if k >= bias + TMAX {
+                        TMAX
+                    } else {
+                        k - bias
+                    };
+                    if q < t {
+                        break;
+                    }
+                    output.push(encode_digit(t + (q - t) % (BASE - t)));
+                    q = (q - t) / (BASE - t);
+                    k += BASE;
+                }
+
+                output.push(encode_digit(q));
+                bias = adapt(delta, handled + 1, handled == basic_len);
+                delta = 0;
+                handled += 1;
+            }
+        }
+
+        delta += 1;
+        n += 1;
+    }
+
+    Ok(output)
+}
+
+/// ホスト名をラベルごとに分け、ASCII以外の文字を含むラベルをpunycodeに変換する。
+/// 本書のブラウザでは、UTS #46のマッピングは小文字化のみを行う
+/// https://url.spec.whatwg.org/#concept-domain-to-ascii
+pub fn domain_to_ascii(domain: &str) -> Result<String, Error> {
+    let mut labels = Vec::new();
+
+    for label in domain.split('.') {
+        let label = label.to_lowercase();
+        if label.is_ascii() {
+            labels.push(label);
+        } else {
+            labels.push(format!("xn--{}", encode(&label)?));
+        }
+    }
+
+    Ok(labels.join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_encode_ascii_only() {
+        assert_eq!(Ok("example-".to_string()), encode("example"));
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(Ok("bcher-kva".to_string()), encode("bücher"));
+        assert_eq!(Ok("wgv71a119e".to_string()), encode("日本語"));
+    }
+
+    #[test]
+    fn test_domain_to_ascii() {
+        assert_eq!(
+            Ok("xn--wgv71a119e.example".to_string()),
+            domain_to_ascii("日本語.example")
+        );
+        assert_eq!(
+            Ok("xn--mnchen-3ya.de".to_string()),
+            domain_to_ascii("MÜNCHEN.de")
+        );
+        assert_eq!(
+            Ok("example.com".to_string()),
+            domain_to_ascii("example.com")
+        );
+    }
+}
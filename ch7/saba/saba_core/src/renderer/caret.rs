@@ -0,0 +1,516 @@
+//! キャレットブラウジング。ページのテキストの上にキャレットを表示し、矢印キーで動かす。
+//! Shiftキーを押しながら動かすと、キャレットを置いた位置から選択範囲を広げ、選択した文字列をコピーできる。
+//!
+//! キャレットの位置は、描画したテキストのDisplayItem（テキストラン）の番号と、その中のバイト位置で表す。
+//! 文字の位置はレイアウトと同じ送り幅で計算するので、描画した文字の間にキャレットが来る
+//! https://html.spec.whatwg.org/multipage/interaction.html#the-selection
+
+use crate::constants::CARET_COLOR;
+use crate::constants::CHAR_HEIGHT;
+use crate::constants::SELECTION_COLOR;
+use crate::display_item::DisplayItem;
+use crate::renderer::layout::computed_style::Color;
+use crate::renderer::layout::computed_style::ComputedStyle;
+use crate::renderer::layout::computed_style::DisplayType;
+use crate::renderer::layout::computed_style::FontSize;
+use crate::renderer::layout::computed_style::FontStyle;
+use crate::renderer::layout::computed_style::FontWeight;
+use crate::renderer::layout::computed_style::TextDecoration;
+use crate::renderer::layout::font::font_scale;
+use crate::renderer::layout::font::glyph_positions;
+use crate::renderer::layout::font::text_width;
+use crate::renderer::layout::layout_object::LayoutPoint;
+use crate::renderer::layout::layout_object::LayoutSize;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// 1つのDisplayItem::Textとして描画された文字列
+#[derive(Debug, Clone, PartialEq)]
+struct TextRun {
+    text: String,
+    font_size: FontSize,
+    point: LayoutPoint,
+}
+
+impl TextRun {
+    /// 先頭から`offset`バイトまでの横幅
+    fn width_until(&self, offset: usize) -> i64 {
+        text_width(&self.text[..offset], self.font_size)
+    }
+
+    /// ページの座標で`x`に最も近い文字の境目のバイト位置
+    fn offset_at(&self, x: i64) -> usize {
+        let mut nearest = self.text.len();
+        let mut distance = (self.point.x() + self.width_until(self.text.len()) - x).abs();
+        for ((offset, _), (_, left)) in self
+            .text
+            .char_indices()
+            .zip(glyph_positions(&self.text, self.font_size))
+        {
+            let d = (self.point.x() + left - x).abs();
+            if d < distance {
+                nearest = offset;
+                distance = d;
+            }
+        }
+        nearest
+    }
+
+    fn height(&self) -> i64 {
+        CHAR_HEIGHT * font_scale(self.font_size)
+    }
+}
+
+/// テキストランの番号と、その中のバイト位置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CaretPosition {
+    run: usize,
+    offset: usize,
+}
+
+impl CaretPosition {
+    pub fn new(run: usize, offset: usize) -> Self {
+        Self { run, offset }
+    }
+
+    pub fn run(&self) -> usize {
+        self.run
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+/// キャレットを動かす向き
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaretMovement {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// ページのテキストの上のキャレットと選択範囲
+#[derive(Debug, Clone, PartialEq)]
+pub struct Caret {
+    runs: Vec<TextRun>,
+    position: CaretPosition,
+    /// 選択を始めた位置。選択していない場合はNone
+    anchor: Option<CaretPosition>,
+}
+
+impl Caret {
+    /// ページの座標で描画した`items`のテキストから、キャレットを置ける位置を作る。
+    /// キャレットは、上端が`top`より下にある最初のテキストの先頭に置く
+    pub fn new(items: &[DisplayItem], top: i64) -> Self {
+        let runs = text_runs(items);
+        let run = runs
+            .iter()
+            .position(|run| run.point.y() >= top)
+            .unwrap_or(0);
+        Self {
+            runs,
+            position: CaretPosition::new(run, 0),
+            anchor: None,
+        }
+    }
+
+    /// レイアウトをやり直した後に、テキストランを作り直す。
+    /// キャレットはできるだけ同じ位置に残し、選択は解除する
+    pub fn set_items(&mut self, items: &[DisplayItem]) {
+        self.runs = text_runs(items);
+        self.anchor = None;
+        self.position = match self.runs.get(self.position.run) {
+            Some(run) => {
+                let mut offset = self.position.offset.min(run.text.len());
+                while !run.text.is_char_boundary(offset) {
+                    offset -= 1;
+                }
+                CaretPosition::new(self.position.run, offset)
+            }
+            None => CaretPosition::new(self.runs.len().saturating_sub(1), 0),
+        };
+    }
+
+    pub fn position(&self) -> CaretPosition {
+        self.position
+    }
+
+    /// キャレットを動かす。`extend`がtrueの場合は選択範囲を広げ、falseの場合は選択を解除する。
+    /// キャレットが動いた場合はtrueを返す
+    pub fn move_caret(&mut self, movement: CaretMovement, extend: bool) -> bool {
+        let next = match movement {
+            CaretMovement::Left => self.previous_position(),
+            CaretMovement::Right => self.next_position(),
+            CaretMovement::Up => self.vertical_position(false),
+            CaretMovement::Down => self.vertical_position(true),
+        };
+
+        if extend {
+            if self.anchor.is_none() {
+                self.anchor = Some(self.position);
+            }
+        } else {
+            self.anchor = None;
+        }
+
+        match next {
+            Some(next) if next != self.position => {
+                self.position = next;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 選択範囲の始まりと終わりを、文書の順に返す。何も選択していない場合はNone
+    pub fn selection(&self) -> Option<(CaretPosition, CaretPosition)> {
+        let anchor = self.anchor?;
+        if anchor == self.position {
+            return None;
+        }
+        Some((anchor.min(self.position), anchor.max(self.position)))
+    }
+
+    /// 選択した文字列。行が変わるところには改行を入れる
+    pub fn selected_text(&self) -> String {
+        let (start, end) = match self.selection() {
+            Some(selection) => selection,
+            None => return String::new(),
+        };
+
+        let mut text = String::new();
+        for i in start.run..=end.run {
+            let run = &self.runs[i];
+            if i > start.run && run.point.y() != self.runs[i - 1].point.y() {
+                text.push('\n');
+            }
+            let (from, to) = self.selected_range(i, start, end);
+            // text/plainのページで空白の代わりに使うノーブレークスペースは、空白に戻す
+            text.extend(
+                run.text[from..to]
+                    .chars()
+                    .map(|c| if c == '\u{00A0}' { ' ' } else { c }),
+            );
+        }
+        text
+    }
+
+    /// キャレットの矩形。ページにテキストがない場合はNone
+    pub fn caret_rect(&self) -> Option<(LayoutPoint, LayoutSize)> {
+        let run = self.runs.get(self.position.run)?;
+        Some((
+            LayoutPoint::new(
+                run.point.x() + run.width_until(self.position.offset),
+                run.point.y(),
+            ),
+            LayoutSize::new(1, run.height()),
+        ))
+    }
+
+    /// `items`に、選択範囲の背景とキャレットを加える。選択範囲の背景は、文字が隠れないように
+    /// 選択した文字列より前に描く。`items`はCaretを作ったときと同じレイアウトから描画したもの
+    pub fn paint(&self, items: Vec<DisplayItem>) -> Vec<DisplayItem> {
+        let selection = self.selection();
+        let selection_style = caret_style(SELECTION_COLOR);
+        let mut painted = Vec::new();
+        let mut run_index = 0;
+
+        for item in items {
+            if let DisplayItem::Text { .. } = item {
+                if let Some((start, end)) = selection {
+                    if let Some((layout_point, layout_size)) =
+                        self.selection_rect(run_index, start, end)
+                    {
+                        painted.push(DisplayItem::Rect {
+                            style: selection_style.clone(),
+                            layout_point,
+                            layout_size,
+                        });
+                    }
+                }
+                run_index += 1;
+            }
+            painted.push(item);
+        }
+
+        if let Some((layout_point, layout_size)) = self.caret_rect() {
+            painted.push(DisplayItem::Rect {
+                style: caret_style(CARET_COLOR),
+                layout_point,
+                layout_size,
+            });
+        }
+        painted
+    }
+
+    /// `run`番目のテキストランのうち、選択されているバイトの範囲
+    fn selected_range(
+        &self,
+        run: usize,
+        start: CaretPosition,
+        end: CaretPosition,
+    ) -> (usize, usize) {
+        let from = if run == start.run { start.offset } else { 0 };
+        let to = if run == end.run {
+            end.offset
+        } else {
+            self.runs[run].text.len()
+        };
+        (from, to)
+    }
+
+    fn selection_rect(
+        &self,
+        run: usize,
+        start: CaretPosition,
+        end: CaretPosition,
+    ) -> Option<(LayoutPoint, LayoutSize)> {
+        if run < start.run || run > end.run {
+            return None;
+        }
+        let (from, to) = self.selected_range(run, start, end);
+        if from >= to {
+            return None;
+        }
+        let text_run = &self.runs[run];
+        let left = text_run.width_until(from);
+        Some((
+            LayoutPoint::new(text_run.point.x() + left, text_run.point.y()),
+            LayoutSize::new(text_run.width_until(to) - left, text_run.height()),
+        ))
+    }
+
+    /// 2つのテキストランが同じ行に並んでいるかどうか
+    fn same_line(&self, a: usize, b: usize) -> bool {
+        self.runs[a].point.y() == self.runs[b].point.y()
+    }
+
+    fn next_position(&self) -> Option<CaretPosition> {
+        let CaretPosition { run, offset } = self.position;
+        let text = &self.runs.get(run)?.text;
+        if let Some(c) = text[offset..].chars().next() {
+            return Some(CaretPosition::new(run, offset + c.len_utf8()));
+        }
+
+        // 同じ行で隣り合うテキストランの境目は1つの位置なので、次の文字の後ろまで進める
+        let next = run + 1;
+        let next_text = &self.runs.get(next)?.text;
+        let offset = match next_text.chars().next() {
+            Some(c) if self.same_line(run, next) => c.len_utf8(),
+            _ => 0,
+        };
+        Some(CaretPosition::new(next, offset))
+    }
+
+    fn previous_position(&self) -> Option<CaretPosition> {
+        let CaretPosition { run, offset } = self.position;
+        let text = &self.runs.get(run)?.text;
+        if let Some(c) = text[..offset].chars().next_back() {
+            return Some(CaretPosition::new(run, offset - c.len_utf8()));
+        }
+
+        let previous = run.checked_sub(1)?;
+        let previous_text = &self.runs[previous].text;
+        let offset = match previous_text.chars().next_back() {
+            Some(c) if self.same_line(run, previous) => previous_text.len() - c.len_utf8(),
+            _ => previous_text.len(),
+        };
+        Some(CaretPosition::new(previous, offset))
+    }
+
+    /// 1つ上か下の行で、キャレットと横の位置が最も近い位置
+    fn vertical_position(&self, down: bool) -> Option<CaretPosition> {
+        let (caret_point, _) = self.caret_rect()?;
+        let y = caret_point.y();
+
+        // 隣の行の上端
+        let line_y = self
+            .runs
+            .iter()
+            .map(|run| run.point.y())
+            .filter(|run_y| if down { *run_y > y } else { *run_y < y })
+            .reduce(|a, b| if down { a.min(b) } else { a.max(b) })?;
+
+        // その行のテキストランのうち、キャレットの横の位置に最も近いもの
+        let x = caret_point.x();
+        let (run, _) = self
+            .runs
+            .iter()
+            .enumerate()
+            .filter(|(_, run)| run.point.y() == line_y)
+            .min_by_key(|(_, run)| {
+                let left = run.point.x();
+                let right = left + run.width_until(run.text.len());
+                if x < left {
+                    left - x
+                } else if x > right {
+                    x - right
+                } else {
+                    0
+                }
+            })?;
+        Some(CaretPosition::new(run, self.runs[run].offset_at(x)))
+    }
+}
+
+fn text_runs(items: &[DisplayItem]) -> Vec<TextRun> {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            DisplayItem::Text {
+                text,
+                style,
+                layout_point,
+            } => Some(TextRun {
+                text: text.clone(),
+                font_size: style.font_size(),
+                point: *layout_point,
+            }),
+            DisplayItem::Rect { .. } | DisplayItem::Image { .. } => None,
+        })
+        .collect()
+}
+
+fn caret_style(color: &str) -> Rc<ComputedStyle> {
+    let color = Color::from_name(color).expect("caret colors should be supported");
+    let mut style = ComputedStyle::new();
+    style.set_background_color(color.clone());
+    style.set_color(color);
+    style.set_display(DisplayType::Block);
+    style.set_font_size(FontSize::Medium);
+    style.set_font_weight(FontWeight::Normal);
+    style.set_font_style(FontStyle::Normal);
+    style.set_text_decoration(TextDecoration::None);
+    Rc::new(style)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::CHAR_HEIGHT_WITH_PADDING;
+    use crate::constants::CHAR_WIDTH;
+    use alloc::string::ToString;
+
+    fn text(text: &str, x: i64, y: i64) -> DisplayItem {
+        let mut style = ComputedStyle::new();
+        style.set_font_size(FontSize::Medium);
+        DisplayItem::Text {
+            text: text.to_string(),
+            style: Rc::new(style),
+            layout_point: LayoutPoint::new(x, y),
+        }
+    }
+
+    fn items() -> Vec<DisplayItem> {
+        alloc::vec![
+            text("ab", 0, 0),
+            text("cd", CHAR_WIDTH * 2, 0),
+            text("日本", 0, CHAR_HEIGHT_WITH_PADDING),
+            text("xyz", 0, CHAR_HEIGHT_WITH_PADDING * 2),
+        ]
+    }
+
+    #[test]
+    fn test_move_caret() {
+        let mut caret = Caret::new(&items(), 0);
+        assert_eq!(CaretPosition::new(0, 0), caret.position());
+        assert!(!caret.move_caret(CaretMovement::Left, false));
+
+        // 同じ行で隣り合うテキストランの境目では、止まらずに次の文字へ進む
+        caret.move_caret(CaretMovement::Right, false);
+        caret.move_caret(CaretMovement::Right, false);
+        assert_eq!(CaretPosition::new(0, 2), caret.position());
+        caret.move_caret(CaretMovement::Right, false);
+        assert_eq!(CaretPosition::new(1, 1), caret.position());
+        caret.move_caret(CaretMovement::Left, false);
+        assert_eq!(CaretPosition::new(1, 0), caret.position());
+
+        // 下の行では、横の位置が最も近い文字の境目に移る。全角の文字の途中には置かない
+        caret.move_caret(CaretMovement::Down, false);
+        assert_eq!(CaretPosition::new(2, 3), caret.position());
+        caret.move_caret(CaretMovement::Down, false);
+        assert_eq!(CaretPosition::new(3, 2), caret.position());
+        assert!(!caret.move_caret(CaretMovement::Down, false));
+        caret.move_caret(CaretMovement::Up, false);
+        assert_eq!(CaretPosition::new(2, 3), caret.position());
+        assert_eq!(
+            Some((
+                LayoutPoint::new(CHAR_WIDTH * 2, CHAR_HEIGHT_WITH_PADDING),
+                LayoutSize::new(1, CHAR_HEIGHT)
+            )),
+            caret.caret_rect()
+        );
+
+        // 行の先頭から左へ動くと、前の行の末尾に移る
+        caret.move_caret(CaretMovement::Left, false);
+        caret.move_caret(CaretMovement::Left, false);
+        assert_eq!(CaretPosition::new(1, 2), caret.position());
+
+        // 表示領域より下から始める
+        let caret = Caret::new(&items(), CHAR_HEIGHT_WITH_PADDING);
+        assert_eq!(CaretPosition::new(2, 0), caret.position());
+    }
+
+    #[test]
+    fn test_selection() {
+        let mut caret = Caret::new(&items(), 0);
+        caret.move_caret(CaretMovement::Right, false);
+        assert_eq!(None, caret.selection());
+        caret.move_caret(CaretMovement::Down, true);
+        caret.move_caret(CaretMovement::Right, true);
+        caret.move_caret(CaretMovement::Right, true);
+        assert_eq!(
+            Some((CaretPosition::new(0, 1), CaretPosition::new(2, 6))),
+            caret.selection()
+        );
+        assert_eq!("bcd\n日本", caret.selected_text());
+
+        // 選択範囲の背景は、選択した文字列の前に描く
+        let painted = caret.paint(items());
+        let rects: Vec<(usize, LayoutPoint, LayoutSize)> = painted
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| match item {
+                DisplayItem::Rect {
+                    layout_point,
+                    layout_size,
+                    ..
+                } => Some((i, *layout_point, *layout_size)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            alloc::vec![
+                (
+                    0,
+                    LayoutPoint::new(CHAR_WIDTH, 0),
+                    LayoutSize::new(CHAR_WIDTH, CHAR_HEIGHT)
+                ),
+                (
+                    2,
+                    LayoutPoint::new(CHAR_WIDTH * 2, 0),
+                    LayoutSize::new(CHAR_WIDTH * 2, CHAR_HEIGHT)
+                ),
+                (
+                    4,
+                    LayoutPoint::new(0, CHAR_HEIGHT_WITH_PADDING),
+                    LayoutSize::new(CHAR_WIDTH * 4, CHAR_HEIGHT)
+                ),
+                // キャレットは最後に描く
+                (
+                    7,
+                    LayoutPoint::new(CHAR_WIDTH * 4, CHAR_HEIGHT_WITH_PADDING),
+                    LayoutSize::new(1, CHAR_HEIGHT)
+                ),
+            ],
+            rects
+        );
+
+        // Shiftキーを押さずに動かすと、選択を解除する
+        caret.move_caret(CaretMovement::Left, false);
+        assert_eq!("", caret.selected_text());
+    }
+}
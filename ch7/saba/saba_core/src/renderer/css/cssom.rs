@@ -1,8 +1,13 @@
 use crate::alloc::string::ToString;
 use crate::renderer::css::token::CssToken;
 use crate::renderer::css::token::CssTokenizer;
+use crate::renderer::dom::node::Element;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
+use core::cmp::Ordering;
 use core::iter::Peekable;
 
 #[derive(Debug, Clone)]
@@ -16,23 +21,16 @@ impl CssParser {
     }
 
     /// https://www.w3.org/TR/css-syntax-3/#consume-component-value
-    fn consume_component_value(&mut self) -> ComponentValue {
-        self.t
-            .next()
-            .expect("should have a token in consume_component_value")
+    /// 入力が終わっている場合はNoneを返す
+    fn consume_component_value(&mut self) -> Option<ComponentValue> {
+        self.t.next()
     }
 
-    fn consume_ident(&mut self) -> String {
-        let token = match self.t.next() {
-            Some(t) => t,
-            None => panic!("should have a token but got None"),
-        };
-
-        match token {
-            CssToken::Ident(ref ident) => ident.to_string(),
-            _ => {
-                panic!("Parse error: {:?} is an unexpected token.", token);
-            }
+    /// 次のトークンが識別子でない場合はパースエラーとして、トークンを進めずにNoneを返す
+    fn consume_ident(&mut self) -> Option<String> {
+        match self.t.next_if(|t| matches!(t, CssToken::Ident(_))) {
+            Some(CssToken::Ident(ident)) => Some(ident),
+            _ => None,
         }
     }
 
@@ -45,7 +43,7 @@ impl CssParser {
         // Declaration構造体を初期化する
         let mut declaration = Declaration::new();
         // Declaration構造体のプロパティに識別子を設定する
-        declaration.set_property(self.consume_ident());
+        declaration.set_property(self.consume_ident()?);
 
         // もし次のトークンがコロンでない場合、パースエラーなので、Noneを返す
         match self.t.next() {
@@ -57,7 +55,7 @@ impl CssParser {
         }
 
         // Declaration構造体の値にコンポーネント値を設定する
-        declaration.set_value(self.consume_component_value());
+        declaration.set_value(self.consume_component_value()?);
 
         Some(declaration)
     }
@@ -93,37 +91,33 @@ impl CssParser {
         }
     }
 
+    /// パースエラーになったセレクタは、どの要素にも適用されないUnknownSelectorにする
     fn consume_selector(&mut self) -> Selector {
         let token = match self.t.next() {
             Some(t) => t,
-            None => panic!("should have a token but got None"),
+            None => return Selector::UnknownSelector,
         };
 
         match token {
-            CssToken::HashToken(value) => Selector::IdSelector(value[1..].to_string()),
-            CssToken::Delim(delim) => {
-                if delim == '.' {
-                    return Selector::ClassSelector(self.consume_ident());
-                }
-                panic!("Parse error: {:?} is an unexpected token.", token);
+            CssToken::HashToken(value) => {
+                self.consume_pseudo_class(Selector::IdSelector(value[1..].to_string()))
             }
+            CssToken::Delim('.') => match self.consume_ident() {
+                Some(class_name) => self.consume_pseudo_class(Selector::ClassSelector(class_name)),
+                None => Selector::UnknownSelector,
+            },
+            CssToken::Delim(_) => Selector::UnknownSelector,
             CssToken::Ident(ident) => {
-                // a:hoverのようなセレクタはタグ名のセレクタとして扱うため、
-                // もしコロン（:）が出てきた場合は宣言ブロックの開始直前まで
-                // トークンを進める
-                if self.t.peek() == Some(&CssToken::Colon) {
-                    while self.t.peek() != Some(&CssToken::OpenCurly) {
-                        self.t.next();
-                    }
-                }
-                Selector::TypeSelector(ident.to_string())
+                self.consume_pseudo_class(Selector::TypeSelector(ident.to_string()))
+            }
+            CssToken::Colon => {
+                // :first-childのように、タグ名などを持たない擬似クラスはすべての要素に適用される
+                self.consume_pseudo(None)
             }
             CssToken::AtKeyword(_keyword) => {
                 // @から始まるルールを無視するために、宣言ブロックの開始直前まで
                 // トークンを進める
-                while self.t.peek() != Some(&CssToken::OpenCurly) {
-                    self.t.next();
-                }
+                self.skip_to_declarations();
                 Selector::UnknownSelector
             }
             _ => {
@@ -133,6 +127,106 @@ impl CssParser {
         }
     }
 
+    /// セレクタの後ろにコロン（:）が続く場合、擬似クラスまたは擬似要素を解釈する
+    fn consume_pseudo_class(&mut self, selector: Selector) -> Selector {
+        if self.t.peek() != Some(&CssToken::Colon) {
+            return selector;
+        }
+        assert_eq!(self.t.next(), Some(CssToken::Colon));
+
+        self.consume_pseudo(Some(Box::new(selector)))
+    }
+
+    /// コロン（:）の後ろにある擬似クラスまたは擬似要素を解釈し、baseのセレクタと組み合わせる
+    fn consume_pseudo(&mut self, base: Option<Box<Selector>>) -> Selector {
+        // ::beforeのように、コロンが2つ続く場合は擬似要素
+        if self.t.peek() == Some(&CssToken::Colon) {
+            assert_eq!(self.t.next(), Some(CssToken::Colon));
+            if let Some(pseudo_element) = self.consume_pseudo_element() {
+                return Selector::PseudoElementSelector(base, pseudo_element);
+            }
+            // サポートしていない擬似要素のルールは、元の要素に適用されないようにする
+            self.skip_to_declarations();
+            return Selector::UnknownSelector;
+        }
+
+        // 古い仕様との互換性のため、:beforeと:afterもコロン1つで擬似要素として扱う
+        if let Some(pseudo_element) = self.consume_pseudo_element() {
+            return Selector::PseudoElementSelector(base, pseudo_element);
+        }
+
+        if let Some(pseudo_class) = self.consume_structural_pseudo_class() {
+            return Selector::PseudoClassSelector(base, pseudo_class);
+        }
+
+        // a:hoverのような構造擬似クラス以外のセレクタはタグ名のセレクタとして扱うため、
+        // 宣言ブロックの開始直前までトークンを進める
+        self.skip_to_declarations();
+        match base {
+            Some(selector) if matches!(*selector, Selector::TypeSelector(_)) => *selector,
+            _ => Selector::UnknownSelector,
+        }
+    }
+
+    fn skip_to_declarations(&mut self) {
+        while self.t.peek().is_some() && self.t.peek() != Some(&CssToken::OpenCurly) {
+            self.t.next();
+        }
+    }
+
+    /// https://www.w3.org/TR/css-pseudo-4/#generated-content
+    fn consume_pseudo_element(&mut self) -> Option<PseudoElement> {
+        let pseudo_element = match self.t.peek() {
+            Some(CssToken::Ident(name)) if name == "before" => PseudoElement::Before,
+            Some(CssToken::Ident(name)) if name == "after" => PseudoElement::After,
+            _ => return None,
+        };
+        self.t.next();
+        Some(pseudo_element)
+    }
+
+    /// コロン（:）の後ろにある構造擬似クラスを解釈する。サポートしていない擬似クラスの場合はNoneを返す
+    /// https://www.w3.org/TR/selectors-4/#structural-pseudos
+    fn consume_structural_pseudo_class(&mut self) -> Option<PseudoClass> {
+        let name = match self.t.peek() {
+            Some(CssToken::Ident(name)) => name.clone(),
+            _ => return None,
+        };
+
+        match name.as_str() {
+            "first-child" => {
+                self.t.next();
+                Some(PseudoClass::FirstChild)
+            }
+            "last-child" => {
+                self.t.next();
+                Some(PseudoClass::LastChild)
+            }
+            "nth-child" => {
+                self.t.next();
+                if self.t.next() != Some(CssToken::OpenParenthesis) {
+                    return None;
+                }
+
+                // 引数のトークンを文字列に戻してから、An+Bの形式として解釈する
+                let mut argument = String::new();
+                loop {
+                    match self.t.next() {
+                        Some(CssToken::CloseParenthesis) | None => break,
+                        Some(CssToken::Number(n)) => argument.push_str(&format!("{}", n)),
+                        Some(CssToken::Ident(s)) => argument.push_str(&s),
+                        Some(CssToken::Delim(c)) => argument.push(c),
+                        Some(_) => return None,
+                    }
+                }
+
+                let (a, b) = parse_an_plus_b(&argument)?;
+                Some(PseudoClass::NthChild(a, b))
+            }
+            _ => None,
+        }
+    }
+
     /// https://www.w3.org/TR/css-syntax-3/#consume-qualified-rule
     /// https://www.w3.org/TR/css-syntax-3/#qualified-rule
     /// https://www.w3.org/TR/css-syntax-3/#style-rules
@@ -204,16 +298,136 @@ impl CssParser {
 pub struct StyleSheet {
     /// https://drafts.csswg.org/cssom/#dom-cssstylesheet-cssrules
     pub rules: Vec<QualifiedRule>,
+    /// セレクタのID、クラス名、タグ名ごとに、ルールのインデックスを分類したもの。
+    /// `set_rules`でルールが設定されたときに作成する
+    id_rules: BTreeMap<String, Vec<usize>>,
+    class_rules: BTreeMap<String, Vec<usize>>,
+    type_rules: BTreeMap<String, Vec<usize>>,
+    /// :first-childのように、どの要素にも適用される可能性のあるルール
+    universal_rules: Vec<usize>,
+    /// ::beforeと::afterのルール
+    pseudo_element_rules: Vec<usize>,
 }
 
 impl StyleSheet {
     pub fn new() -> Self {
-        Self { rules: Vec::new() }
+        Self {
+            rules: Vec::new(),
+            id_rules: BTreeMap::new(),
+            class_rules: BTreeMap::new(),
+            type_rules: BTreeMap::new(),
+            universal_rules: Vec::new(),
+            pseudo_element_rules: Vec::new(),
+        }
     }
 
+    /// ルールを、カスケードで優先される順に並べて設定する。
+    /// オリジンの順に並べ替え、同じオリジンのルールは渡された順を保つ
     pub fn set_rules(&mut self, rules: Vec<QualifiedRule>) {
+        let mut rules = rules;
+        rules.sort_by_key(|rule| rule.origin);
         self.rules = rules;
+        self.build_index();
     }
+
+    /// 全てのルールを、`origin`が書いたものにする
+    pub fn set_origin(&mut self, origin: CascadeOrigin) {
+        for rule in &mut self.rules {
+            rule.origin = origin;
+        }
+    }
+
+    fn build_index(&mut self) {
+        self.id_rules = BTreeMap::new();
+        self.class_rules = BTreeMap::new();
+        self.type_rules = BTreeMap::new();
+        self.universal_rules = Vec::new();
+        self.pseudo_element_rules = Vec::new();
+
+        for (i, rule) in self.rules.iter().enumerate() {
+            // 擬似クラスを持つセレクタは、元のセレクタで分類する
+            let selector = match &rule.selector {
+                Selector::PseudoClassSelector(Some(base), _) => base.as_ref(),
+                Selector::PseudoClassSelector(None, _) => {
+                    self.universal_rules.push(i);
+                    continue;
+                }
+                // 擬似要素のルールは要素そのものには適用しないので、別に分類する
+                Selector::PseudoElementSelector(_, _) => {
+                    self.pseudo_element_rules.push(i);
+                    continue;
+                }
+                selector => selector,
+            };
+            let (bucket, key) = match selector {
+                Selector::IdSelector(id) => (&mut self.id_rules, id),
+                Selector::ClassSelector(class) => (&mut self.class_rules, class),
+                Selector::TypeSelector(tag) => (&mut self.type_rules, tag),
+                Selector::PseudoClassSelector(_, _)
+                | Selector::PseudoElementSelector(_, _)
+                | Selector::UnknownSelector => continue,
+            };
+            bucket.entry(key.clone()).or_default().push(i);
+        }
+    }
+
+    /// 要素に適用される可能性のあるルールを、スタイルシートに書かれた順番で返す。
+    /// 全てのルールを調べる代わりに、要素のID、クラス名、タグ名に対応するルールだけを返す
+    pub fn matching_rules(&self, element: &Element) -> Vec<&QualifiedRule> {
+        let mut indices: Vec<usize> = Vec::new();
+
+        if let Some(id) = element.get_attribute("id") {
+            if let Some(v) = self.id_rules.get(&id) {
+                indices.extend(v);
+            }
+        }
+        if let Some(class) = element.get_attribute("class") {
+            if let Some(v) = self.class_rules.get(&class) {
+                indices.extend(v);
+            }
+        }
+        if let Some(v) = self.type_rules.get(&element.kind().to_string()) {
+            indices.extend(v);
+        }
+        indices.extend(&self.universal_rules);
+
+        // カスケードの順番を保つために、ルールの出現順に並べ替える
+        indices.sort();
+        indices.iter().map(|i| &self.rules[*i]).collect()
+    }
+
+    /// 擬似要素のルールのうち、pseudo_elementに対するものを、スタイルシートに書かれた順番で返す。
+    /// 元のセレクタ（p::beforeのp）が要素に一致するかは呼び出し側で調べる
+    pub fn pseudo_element_rules(&self, pseudo_element: PseudoElement) -> Vec<&QualifiedRule> {
+        self.pseudo_element_rules
+            .iter()
+            .map(|i| &self.rules[*i])
+            .filter(|rule| {
+                matches!(rule.selector, Selector::PseudoElementSelector(_, p) if p == pseudo_element)
+            })
+            .collect()
+    }
+
+    /// 兄弟要素の中での位置によって適用されるかが変わるルールを持つかどうか。
+    /// 持つ場合、兄弟要素同士でスタイルを共有できない
+    pub fn has_structural_pseudo_classes(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| matches!(rule.selector, Selector::PseudoClassSelector(_, _)))
+    }
+}
+
+/// ルールを書いたのが誰か。カスケードでは、ユーザーエージェント、ユーザー、作者の順に後のものが優先される。
+/// 内部ページはユーザーエージェントのスタイルだけで表示し、作者のスタイルを使わない
+/// https://drafts.csswg.org/css-cascade/#cascading-origins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CascadeOrigin {
+    /// ブラウザが内部ページのために持つスタイル
+    UserAgent,
+    /// ブラウザを使う人が設定したスタイル
+    User,
+    /// ページの<style>と<link rel="stylesheet">のスタイル
+    Author,
 }
 
 /// https://www.w3.org/TR/css-syntax-3/#qualified-rule
@@ -225,6 +439,7 @@ pub struct QualifiedRule {
     /// https://www.w3.org/TR/css-syntax-3/#parse-a-list-of-declarations
     /// The content of the qualified rule’s block is parsed as a list of declarations.
     pub declarations: Vec<Declaration>,
+    pub origin: CascadeOrigin,
 }
 
 impl QualifiedRule {
@@ -232,6 +447,7 @@ impl QualifiedRule {
         Self {
             selector: Selector::TypeSelector("".to_string()),
             declarations: Vec::new(),
+            origin: CascadeOrigin::Author,
         }
     }
 
@@ -242,6 +458,17 @@ impl QualifiedRule {
     pub fn set_declarations(&mut self, declarations: Vec<Declaration>) {
         self.declarations = declarations;
     }
+
+    /// "p { color: red; }"のように、セレクタと宣言を1行の文字列にする
+    /// https://drafts.csswg.org/cssom/#serialize-a-css-rule
+    pub fn css_text(&self) -> String {
+        let mut s = format!("{} {{", self.selector.selector_text());
+        for declaration in &self.declarations {
+            s.push_str(&format!(" {};", declaration.css_text()));
+        }
+        s.push_str(" }");
+        s
+    }
 }
 
 /// https://www.w3.org/TR/selectors-4/
@@ -253,10 +480,90 @@ pub enum Selector {
     ClassSelector(String),
     /// https://www.w3.org/TR/selectors-4/#id-selectors
     IdSelector(String),
+    /// li:first-childのように、セレクタに擬似クラスを付けたもの。
+    /// 元のセレクタがNoneの場合はすべての要素が対象になる
+    /// https://www.w3.org/TR/selectors-4/#pseudo-classes
+    PseudoClassSelector(Option<Box<Selector>>, PseudoClass),
+    /// p::beforeのように、セレクタに一致する要素の中に作られる擬似要素
+    /// https://www.w3.org/TR/selectors-4/#pseudo-elements
+    PseudoElementSelector(Option<Box<Selector>>, PseudoElement),
     /// パース中にエラーが起こったときに使用されるセレクタ
     UnknownSelector,
 }
 
+impl Selector {
+    /// セレクタを文字列にする。UnknownSelectorは空文字列になる
+    /// https://drafts.csswg.org/cssom/#serialize-a-selector
+    pub fn selector_text(&self) -> String {
+        let base = |selector: &Option<Box<Selector>>| match selector {
+            Some(s) => s.selector_text(),
+            None => String::new(),
+        };
+        match self {
+            Selector::TypeSelector(name) => name.clone(),
+            Selector::ClassSelector(name) => format!(".{}", name),
+            Selector::IdSelector(name) => format!("#{}", name),
+            Selector::PseudoClassSelector(selector, pseudo_class) => {
+                let pseudo_class = match pseudo_class {
+                    PseudoClass::FirstChild => "first-child".to_string(),
+                    PseudoClass::LastChild => "last-child".to_string(),
+                    PseudoClass::NthChild(a, b) => {
+                        format!("nth-child({})", serialize_an_plus_b(*a, *b))
+                    }
+                };
+                format!("{}:{}", base(selector), pseudo_class)
+            }
+            Selector::PseudoElementSelector(selector, pseudo_element) => {
+                let pseudo_element = match pseudo_element {
+                    PseudoElement::Before => "before",
+                    PseudoElement::After => "after",
+                };
+                format!("{}::{}", base(selector), pseudo_element)
+            }
+            Selector::UnknownSelector => String::new(),
+        }
+    }
+}
+
+/// https://www.w3.org/TR/css-pseudo-4/#generated-content
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PseudoElement {
+    /// 要素の最初の子として作られる
+    Before,
+    /// 要素の最後の子として作られる
+    After,
+}
+
+/// https://www.w3.org/TR/selectors-4/#structural-pseudos
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PseudoClass {
+    /// https://www.w3.org/TR/selectors-4/#the-first-child-pseudo
+    FirstChild,
+    /// https://www.w3.org/TR/selectors-4/#the-last-child-pseudo
+    LastChild,
+    /// An+B番目の要素。(A, B)の組を持つ
+    /// https://www.w3.org/TR/selectors-4/#the-nth-child-pseudo
+    NthChild(i64, i64),
+}
+
+impl PseudoClass {
+    /// 兄弟要素の中で1から数えてindex番目、全部でcount個ある要素に一致するかどうか
+    pub fn matches(&self, index: usize, count: usize) -> bool {
+        match self {
+            PseudoClass::FirstChild => index == 1,
+            PseudoClass::LastChild => index == count,
+            PseudoClass::NthChild(a, b) => {
+                // index = a * n + b を満たす0以上の整数nがあるかどうか
+                let diff = index as i64 - b;
+                if *a == 0 {
+                    return diff == 0;
+                }
+                diff % a == 0 && diff / a >= 0
+            }
+        }
+    }
+}
+
 /// https://www.w3.org/TR/css-syntax-3/#declaration
 #[derive(Debug, Clone, PartialEq)]
 pub struct Declaration {
@@ -279,13 +586,90 @@ impl Declaration {
     pub fn set_value(&mut self, value: ComponentValue) {
         self.value = value;
     }
+
+    /// "color: red"のように、プロパティと値を文字列にする
+    /// https://drafts.csswg.org/cssom/#serialize-a-css-declaration
+    pub fn css_text(&self) -> String {
+        format!(
+            "{}: {}",
+            self.property,
+            serialize_component_value(&self.value)
+        )
+    }
 }
 
 pub type ComponentValue = CssToken;
 
+/// https://drafts.csswg.org/cssom/#serialize-a-css-component-value
+fn serialize_component_value(value: &ComponentValue) -> String {
+    match value {
+        CssToken::HashToken(s) | CssToken::Ident(s) => s.clone(),
+        CssToken::Delim(c) => c.to_string(),
+        CssToken::Number(n) => format!("{}", n),
+        CssToken::Colon => ":".to_string(),
+        CssToken::SemiColon => ";".to_string(),
+        CssToken::OpenParenthesis => "(".to_string(),
+        CssToken::CloseParenthesis => ")".to_string(),
+        CssToken::OpenCurly => "{".to_string(),
+        CssToken::CloseCurly => "}".to_string(),
+        CssToken::StringToken(s) => format!("\"{}\"", s),
+        CssToken::AtKeyword(s) => format!("@{}", s),
+    }
+}
+
+/// (A, B)の組を、"2n+1"、"-n+3"、"3"のようなAn+Bの形式の最も短い文字列にする
+/// https://www.w3.org/TR/css-syntax-3/#serializing-anb
+fn serialize_an_plus_b(a: i64, b: i64) -> String {
+    if a == 0 {
+        return format!("{}", b);
+    }
+
+    let mut s = match a {
+        1 => "n".to_string(),
+        -1 => "-n".to_string(),
+        a => format!("{}n", a),
+    };
+    match b.cmp(&0) {
+        Ordering::Greater => s.push_str(&format!("+{}", b)),
+        Ordering::Less => s.push_str(&format!("{}", b)),
+        Ordering::Equal => {}
+    }
+    s
+}
+
+/// "2n+1"、"odd"、"3"のような、:nth-child()の引数を解釈し、(A, B)の組を返す
+/// https://www.w3.org/TR/css-syntax-3/#anb-microsyntax
+fn parse_an_plus_b(s: &str) -> Option<(i64, i64)> {
+    let s = s.trim();
+    match s {
+        "odd" => return Some((2, 1)),
+        "even" => return Some((2, 0)),
+        _ => {}
+    }
+
+    let (a, b) = match s.find('n') {
+        Some(i) => {
+            let a = match &s[..i] {
+                "" | "+" => 1,
+                "-" => -1,
+                a => a.parse::<i64>().ok()?,
+            };
+            let b = match s[i + 1..].trim() {
+                "" => 0,
+                b => b.strip_prefix('+').unwrap_or(b).parse::<i64>().ok()?,
+            };
+            (a, b)
+        }
+        None => (0, s.parse::<i64>().ok()?),
+    };
+
+    Some((a, b))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::renderer::html::attribute::Attribute;
     use alloc::vec;
 
     #[test]
@@ -398,4 +782,217 @@ mod tests {
             i += 1;
         }
     }
+
+    #[test]
+    fn test_matching_rules() {
+        let style =
+            "p { color: red; } #id { color: blue; } .class { color: green; } h1 { color: black; }"
+                .to_string();
+        let t = CssTokenizer::new(style);
+        let cssom = CssParser::new(t).parse_stylesheet();
+
+        let mut id = Attribute::new();
+        for c in "id".chars() {
+            id.add_char(c, true);
+        }
+        for c in "id".chars() {
+            id.add_char(c, false);
+        }
+        let element = Element::new("p", vec![id]);
+
+        let rules = cssom.matching_rules(&element);
+        assert_eq!(2, rules.len());
+        assert_eq!(Selector::TypeSelector("p".to_string()), rules[0].selector);
+        assert_eq!(Selector::IdSelector("id".to_string()), rules[1].selector);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        // パースエラーになったルールと宣言は無視し、パニックせずに残りを解釈する
+        let style = "% { color: blue; } . { color: blue; } p { color: ; margin }".to_string();
+        let cssom = CssParser::new(CssTokenizer::new(style)).parse_stylesheet();
+
+        assert_eq!(3, cssom.rules.len());
+        assert_eq!(Selector::UnknownSelector, cssom.rules[0].selector);
+        assert_eq!(Selector::UnknownSelector, cssom.rules[1].selector);
+        assert_eq!(
+            Selector::TypeSelector("p".to_string()),
+            cssom.rules[2].selector
+        );
+        assert_eq!(
+            ComponentValue::SemiColon,
+            cssom.rules[2].declarations[0].value
+        );
+
+        let style = "p { color:".to_string();
+        let cssom = CssParser::new(CssTokenizer::new(style)).parse_stylesheet();
+        assert_eq!(1, cssom.rules.len());
+        assert!(cssom.rules[0].declarations.is_empty());
+
+        // 宣言ブロックのない@から始まるルールで、入力が終わる
+        let style = "@media screen".to_string();
+        let cssom = CssParser::new(CssTokenizer::new(style)).parse_stylesheet();
+        assert!(cssom.rules.is_empty());
+    }
+
+    #[test]
+    fn test_cascade_origin() {
+        let parse = |style: &str, origin: CascadeOrigin| {
+            let mut sheet = CssParser::new(CssTokenizer::new(style.to_string())).parse_stylesheet();
+            sheet.set_origin(origin);
+            sheet
+        };
+        let author = parse("p { color: red; }", CascadeOrigin::Author);
+        let user = parse("p { color: green; }", CascadeOrigin::User);
+        let user_agent = parse(
+            "p { color: blue; } p { color: black; }",
+            CascadeOrigin::UserAgent,
+        );
+
+        let mut rules = Vec::new();
+        for sheet in [&author, &user, &user_agent] {
+            rules.extend(sheet.rules.iter().cloned());
+        }
+        let mut cssom = StyleSheet::new();
+        cssom.set_rules(rules);
+
+        // 渡した順によらず、作者のルールが最後に適用される。同じオリジンのルールは書かれた順のまま
+        let element = Element::new("p", Vec::new());
+        let origins: Vec<CascadeOrigin> = cssom
+            .matching_rules(&element)
+            .iter()
+            .map(|rule| rule.origin)
+            .collect();
+        assert_eq!(
+            vec![
+                CascadeOrigin::UserAgent,
+                CascadeOrigin::UserAgent,
+                CascadeOrigin::User,
+                CascadeOrigin::Author,
+            ],
+            origins
+        );
+        assert_eq!(user_agent.rules[1], cssom.rules[1]);
+    }
+
+    #[test]
+    fn test_structural_pseudo_class() {
+        let style = "p:first-child { color: red; } .row:nth-child(2n+1) { color: blue; } :last-child { color: green; } a:hover { color: red; }".to_string();
+        let t = CssTokenizer::new(style);
+        let cssom = CssParser::new(t).parse_stylesheet();
+
+        assert_eq!(
+            Selector::PseudoClassSelector(
+                Some(Box::new(Selector::TypeSelector("p".to_string()))),
+                PseudoClass::FirstChild
+            ),
+            cssom.rules[0].selector
+        );
+        assert_eq!(
+            Selector::PseudoClassSelector(
+                Some(Box::new(Selector::ClassSelector("row".to_string()))),
+                PseudoClass::NthChild(2, 1)
+            ),
+            cssom.rules[1].selector
+        );
+        assert_eq!(
+            Selector::PseudoClassSelector(None, PseudoClass::LastChild),
+            cssom.rules[2].selector
+        );
+        // 構造擬似クラス以外の擬似クラスは、これまでどおりタグ名のセレクタとして扱う
+        assert_eq!(
+            Selector::TypeSelector("a".to_string()),
+            cssom.rules[3].selector
+        );
+        assert!(cssom.has_structural_pseudo_classes());
+    }
+
+    #[test]
+    fn test_pseudo_element() {
+        let style = "p::before { content: \"[\"; } a:after { content: \"]\"; } ::before { color: red; } p::selection { color: red; } p { color: blue; }".to_string();
+        let t = CssTokenizer::new(style);
+        let cssom = CssParser::new(t).parse_stylesheet();
+
+        assert_eq!(
+            Selector::PseudoElementSelector(
+                Some(Box::new(Selector::TypeSelector("p".to_string()))),
+                PseudoElement::Before
+            ),
+            cssom.rules[0].selector
+        );
+        // コロン1つの古い書き方も擬似要素として扱う
+        assert_eq!(
+            Selector::PseudoElementSelector(
+                Some(Box::new(Selector::TypeSelector("a".to_string()))),
+                PseudoElement::After
+            ),
+            cssom.rules[1].selector
+        );
+        assert_eq!(
+            Selector::PseudoElementSelector(None, PseudoElement::Before),
+            cssom.rules[2].selector
+        );
+        // サポートしていない擬似要素は元の要素に適用しない
+        assert_eq!(Selector::UnknownSelector, cssom.rules[3].selector);
+
+        assert_eq!(2, cssom.pseudo_element_rules(PseudoElement::Before).len());
+        assert_eq!(1, cssom.pseudo_element_rules(PseudoElement::After).len());
+        // 擬似要素のルールは要素そのものに適用されるルールには含まれない
+        let p = Element::new("p", Vec::new());
+        assert_eq!(1, cssom.matching_rules(&p).len());
+    }
+
+    #[test]
+    fn test_an_plus_b() {
+        assert_eq!(Some((2, 1)), parse_an_plus_b("odd"));
+        assert_eq!(Some((2, 0)), parse_an_plus_b("even"));
+        assert_eq!(Some((0, 3)), parse_an_plus_b("3"));
+        assert_eq!(Some((1, 0)), parse_an_plus_b("n"));
+        assert_eq!(Some((3, -1)), parse_an_plus_b("3n-1"));
+        assert_eq!(Some((-1, 3)), parse_an_plus_b("-n+3"));
+        assert_eq!(None, parse_an_plus_b("foo"));
+    }
+
+    #[test]
+    fn test_serialize_an_plus_b() {
+        assert_eq!("2n+1", serialize_an_plus_b(2, 1));
+        assert_eq!("2n", serialize_an_plus_b(2, 0));
+        assert_eq!("3", serialize_an_plus_b(0, 3));
+        assert_eq!("n", serialize_an_plus_b(1, 0));
+        assert_eq!("3n-1", serialize_an_plus_b(3, -1));
+        assert_eq!("-n+3", serialize_an_plus_b(-1, 3));
+    }
+
+    #[test]
+    fn test_css_text() {
+        let style =
+            "p{color:red;font-size:40} li:nth-child(odd) { content: \"x\"; } #a { } p::after { }"
+                .to_string();
+        let t = CssTokenizer::new(style);
+        let cssom = CssParser::new(t).parse_stylesheet();
+
+        let css_text: Vec<String> = cssom.rules.iter().map(|r| r.css_text()).collect();
+        assert_eq!(
+            vec![
+                "p { color: red; font-size: 40; }",
+                "li:nth-child(2n+1) { content: \"x\"; }",
+                "#a { }",
+                "p::after { }",
+            ],
+            css_text
+        );
+    }
+
+    #[test]
+    fn test_pseudo_class_matches() {
+        assert!(PseudoClass::FirstChild.matches(1, 3));
+        assert!(!PseudoClass::FirstChild.matches(2, 3));
+        assert!(PseudoClass::LastChild.matches(3, 3));
+        assert!(PseudoClass::NthChild(2, 1).matches(3, 5));
+        assert!(!PseudoClass::NthChild(2, 1).matches(4, 5));
+        // -n+3は先頭の3つの要素に一致する
+        assert!(PseudoClass::NthChild(-1, 3).matches(3, 5));
+        assert!(!PseudoClass::NthChild(-1, 3).matches(4, 5));
+        assert!(PseudoClass::NthChild(0, 2).matches(2, 5));
+    }
 }
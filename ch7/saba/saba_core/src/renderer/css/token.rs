@@ -48,11 +48,12 @@ impl CssTokenizer {
         let mut s = String::new();
 
         loop {
+            self.pos += 1;
+            // 閉じる引用符がないまま入力が終わった場合、そこまでを文字列とする
             if self.pos >= self.input.len() {
                 return s;
             }
 
-            self.pos += 1;
             let c = self.input[self.pos];
             match c {
                 '"' | '\'' => break,
@@ -106,6 +107,10 @@ impl CssTokenizer {
 
         loop {
             self.pos += 1;
+            if self.pos >= self.input.len() {
+                break;
+            }
+
             let c = self.input[self.pos];
             match c {
                 'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => {
@@ -136,11 +141,13 @@ impl Iterator for CssTokenizer {
                 ')' => CssToken::CloseParenthesis,
                 ',' => CssToken::Delim(','),
                 '.' => CssToken::Delim('.'),
+                // 本書では、:nth-child(2n+1)のようなセレクタでのみ使うので、常に<delim-token>として扱う
+                '+' => CssToken::Delim('+'),
                 ':' => CssToken::Colon,
                 ';' => CssToken::SemiColon,
                 '{' => CssToken::OpenCurly,
                 '}' => CssToken::CloseCurly,
-                ' ' | '\n' => {
+                ' ' | '\n' | '\t' | '\r' | '\x0c' => {
                     self.pos += 1;
                     continue;
                 }
@@ -169,9 +176,10 @@ impl Iterator for CssTokenizer {
                     // 次の3文字が識別子として有効な文字の場合、<at-keyword-token>
                     // トークンを作成して返す。
                     // それ以外の場合、<delim-token>を返す。
-                    if self.input[self.pos + 1].is_ascii_alphabetic()
-                        && self.input[self.pos + 2].is_alphanumeric()
-                        && self.input[self.pos + 3].is_alphanumeric()
+                    let next = |i: usize| self.input.get(self.pos + i).copied().unwrap_or(' ');
+                    if next(1).is_ascii_alphabetic()
+                        && next(2).is_alphanumeric()
+                        && next(3).is_alphanumeric()
                     {
                         // skip '@'
                         self.pos += 1;
@@ -187,9 +195,8 @@ impl Iterator for CssTokenizer {
                     self.pos -= 1;
                     t
                 }
-                _ => {
-                    unimplemented!("char {} is not supported yet", c);
-                }
+                // サポートしていない文字は、仕様どおり<delim-token>として返し、パーサーに任せる
+                _ => CssToken::Delim(c),
             };
 
             self.pos += 1;
@@ -297,4 +304,46 @@ mod tests {
         }
         assert!(t.next().is_none());
     }
+
+    #[test]
+    fn test_eof() {
+        let style = "a:hover #id \"unterminated".to_string();
+        let mut t = CssTokenizer::new(style);
+        let expected = [
+            CssToken::Ident("a".to_string()),
+            CssToken::Colon,
+            CssToken::Ident("hover".to_string()),
+            CssToken::HashToken("#id".to_string()),
+            CssToken::StringToken("unterminated".to_string()),
+        ];
+        for e in expected {
+            assert_eq!(Some(e.clone()), t.next());
+        }
+        assert!(t.next().is_none());
+
+        let mut t = CssTokenizer::new("p".to_string());
+        assert_eq!(Some(CssToken::Ident("p".to_string())), t.next());
+        assert!(t.next().is_none());
+
+        let mut t = CssTokenizer::new("@m".to_string());
+        assert_eq!(Some(CssToken::Delim('@')), t.next());
+        assert_eq!(Some(CssToken::Ident("m".to_string())), t.next());
+        assert!(t.next().is_none());
+    }
+
+    #[test]
+    fn test_unsupported_char() {
+        let style = "p > %\t*".to_string();
+        let mut t = CssTokenizer::new(style);
+        let expected = [
+            CssToken::Ident("p".to_string()),
+            CssToken::Delim('>'),
+            CssToken::Delim('%'),
+            CssToken::Delim('*'),
+        ];
+        for e in expected {
+            assert_eq!(Some(e.clone()), t.next());
+        }
+        assert!(t.next().is_none());
+    }
 }
@@ -1,8 +1,10 @@
-use crate::renderer::dom::node::Element;
+use crate::constants::MAX_TREE_DEPTH;
 use crate::renderer::dom::node::ElementKind;
 use crate::renderer::dom::node::Node;
 use crate::renderer::dom::node::NodeKind;
+use alloc::collections::BTreeMap;
 use alloc::rc::Rc;
+use alloc::rc::Weak;
 use alloc::string::String;
 use alloc::string::ToString;
 use alloc::vec::Vec;
@@ -12,46 +14,205 @@ pub fn get_element_by_id(
     node: Option<Rc<RefCell<Node>>>,
     id_name: &String,
 ) -> Option<Rc<RefCell<Node>>> {
-    match node {
-        Some(n) => {
-            if let NodeKind::Element(e) = n.borrow().kind() {
-                for attr in &e.attributes() {
-                    if attr.name() == "id" && attr.value() == *id_name {
-                        return Some(n.clone());
-                    }
-                }
-            }
-            let result1 = get_element_by_id(n.borrow().first_child(), id_name);
-            let result2 = get_element_by_id(n.borrow().next_sibling(), id_name);
-            if result1.is_none() {
-                return result2;
+    DomTreeIterator::new(node).find(|n| {
+        if let NodeKind::Element(e) = n.borrow().kind() {
+            return e.get_attribute("id").as_ref() == Some(id_name);
+        }
+        false
+    })
+}
+
+/// childをparentの最後の子ノードとして追加する
+pub fn append_child(parent: &Rc<RefCell<Node>>, child: Rc<RefCell<Node>>) {
+    let last_child = parent.borrow().last_child().upgrade();
+    match last_child {
+        Some(last) => {
+            last.borrow_mut().set_next_sibling(Some(child.clone()));
+            child
+                .borrow_mut()
+                .set_previous_sibling(Rc::downgrade(&last));
+        }
+        None => parent.borrow_mut().set_first_child(Some(child.clone())),
+    }
+
+    parent.borrow_mut().set_last_child(Rc::downgrade(&child));
+    child.borrow_mut().set_parent(Rc::downgrade(parent));
+}
+
+/// childを、parentの子ノードのreferenceの直前に挿入する
+/// https://dom.spec.whatwg.org/#concept-node-insert
+pub fn insert_before(
+    parent: &Rc<RefCell<Node>>,
+    child: Rc<RefCell<Node>>,
+    reference: &Rc<RefCell<Node>>,
+) {
+    let previous = reference.borrow().previous_sibling().upgrade();
+    match previous {
+        Some(ref previous) => {
+            previous.borrow_mut().set_next_sibling(Some(child.clone()));
+            child
+                .borrow_mut()
+                .set_previous_sibling(Rc::downgrade(previous));
+        }
+        None => parent.borrow_mut().set_first_child(Some(child.clone())),
+    }
+
+    child.borrow_mut().set_next_sibling(Some(reference.clone()));
+    reference
+        .borrow_mut()
+        .set_previous_sibling(Rc::downgrade(&child));
+    child.borrow_mut().set_parent(Rc::downgrade(parent));
+}
+
+/// childをparentの子ノードから取り除く
+/// https://dom.spec.whatwg.org/#concept-node-remove
+pub fn remove_child(parent: &Rc<RefCell<Node>>, child: &Rc<RefCell<Node>>) {
+    let previous = child.borrow().previous_sibling().upgrade();
+    let next = child.borrow().next_sibling();
+    match &previous {
+        Some(previous) => previous.borrow_mut().set_next_sibling(next.clone()),
+        None => parent.borrow_mut().set_first_child(next.clone()),
+    }
+    let previous = previous.as_ref().map(Rc::downgrade).unwrap_or_default();
+    match &next {
+        Some(next) => next.borrow_mut().set_previous_sibling(previous),
+        None => parent.borrow_mut().set_last_child(previous),
+    }
+
+    let mut child = child.borrow_mut();
+    child.set_parent(Weak::new());
+    child.set_previous_sibling(Weak::new());
+    child.set_next_sibling(None);
+}
+
+/// nodeがrootか、rootの子孫かどうか。親をたどるだけなので、文書の大きさによらない
+/// https://dom.spec.whatwg.org/#dom-node-contains
+pub fn contains(root: &Rc<RefCell<Node>>, node: &Rc<RefCell<Node>>) -> bool {
+    let mut current = node.clone();
+    loop {
+        if Rc::ptr_eq(&current, root) {
+            return true;
+        }
+        let parent = current.borrow().parent().upgrade();
+        current = match parent {
+            Some(parent) => parent,
+            None => return false,
+        };
+    }
+}
+
+/// parentの子ノードを全て取り除き、取り除いたノードを返す
+/// https://dom.spec.whatwg.org/#concept-node-remove
+pub fn remove_all_children(parent: &Rc<RefCell<Node>>) -> Vec<Rc<RefCell<Node>>> {
+    let mut removed = Vec::new();
+    let mut child = parent.borrow().first_child();
+    while let Some(c) = child {
+        child = c.borrow().next_sibling();
+        {
+            let mut c_mut = c.borrow_mut();
+            c_mut.set_parent(Weak::new());
+            c_mut.set_previous_sibling(Weak::new());
+            c_mut.set_next_sibling(None);
+        }
+        removed.push(c);
+    }
+
+    parent.borrow_mut().set_first_child(None);
+    parent.borrow_mut().set_last_child(Weak::new());
+    removed
+}
+
+/// 兄弟要素の中で、要素が1から数えて何番目にあるかと、兄弟要素の数を返す。
+/// テキストノードは数えない
+/// https://www.w3.org/TR/selectors-4/#child-index
+pub fn element_sibling_position(node: &Rc<RefCell<Node>>) -> Option<(usize, usize)> {
+    let parent = node.borrow().parent().upgrade()?;
+
+    let mut index = None;
+    let mut count = 0;
+    let mut sibling = parent.borrow().first_child();
+    while let Some(s) = sibling {
+        if let NodeKind::Element(_) = s.borrow().kind() {
+            count += 1;
+            if Rc::ptr_eq(&s, node) {
+                index = Some(count);
             }
-            result1
         }
-        None => None,
+        sibling = s.borrow().next_sibling();
     }
+
+    index.map(|i| (i, count))
 }
 
 pub fn get_target_element_node(
     node: Option<Rc<RefCell<Node>>>,
     element_kind: ElementKind,
 ) -> Option<Rc<RefCell<Node>>> {
-    match node {
-        Some(n) => {
-            if n.borrow().kind()
-                == NodeKind::Element(Element::new(&element_kind.to_string(), Vec::new()))
-            {
-                return Some(n.clone());
-            }
-            let result1 = get_target_element_node(n.borrow().first_child(), element_kind);
-            let result2 = get_target_element_node(n.borrow().next_sibling(), element_kind);
-            if result1.is_none() {
-                return result2;
+    DomTreeIterator::new(node).find(|n| n.borrow().element_kind() == Some(element_kind))
+}
+
+/// ノードとその後ろの兄弟ノード、およびそれらの子孫を文書順（行きがけ順）に辿るイテレータ。
+/// 再帰を使わずに明示的なスタックで辿るので、深くネストした文書でもスタックオーバーフローしない。
+/// depth_limitより深いノードは辿らない
+#[derive(Debug, Clone)]
+pub struct DomTreeIterator {
+    stack: Vec<(Rc<RefCell<Node>>, usize)>,
+    depth_limit: usize,
+}
+
+impl DomTreeIterator {
+    pub fn new(node: Option<Rc<RefCell<Node>>>) -> Self {
+        Self::with_depth_limit(node, MAX_TREE_DEPTH)
+    }
+
+    pub fn with_depth_limit(node: Option<Rc<RefCell<Node>>>, depth_limit: usize) -> Self {
+        let mut stack = Vec::new();
+        if let Some(n) = node {
+            stack.push((n, 0));
+        }
+        Self { stack, depth_limit }
+    }
+}
+
+impl Iterator for DomTreeIterator {
+    type Item = Rc<RefCell<Node>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, depth) = self.stack.pop()?;
+
+        // 子ノードを兄弟ノードより先に辿るため、兄弟ノードを先にスタックに積む
+        if let Some(sibling) = node.borrow().next_sibling() {
+            self.stack.push((sibling, depth));
+        }
+        if depth < self.depth_limit {
+            if let Some(child) = node.borrow().first_child() {
+                self.stack.push((child, depth + 1));
             }
-            result1
         }
-        None => None,
+
+        Some(node)
+    }
+}
+
+/// URLのフラグメントが指す要素を探す。idが一致する要素がなければ、nameが一致する<a>要素を探す
+/// https://html.spec.whatwg.org/multipage/browsing-the-web.html#find-a-potential-indicated-element
+pub fn get_indicated_element(root: Rc<RefCell<Node>>, fragment: &str) -> Option<Rc<RefCell<Node>>> {
+    if fragment.is_empty() {
+        return None;
     }
+
+    let element_with_attribute = |name: &str, kind: Option<ElementKind>| {
+        DomTreeIterator::new(Some(root.clone())).find(|n| match n.borrow().get_element() {
+            Some(e) => {
+                kind.map_or(true, |k| e.kind() == k)
+                    && e.get_attribute(name).as_deref() == Some(fragment)
+            }
+            None => false,
+        })
+    };
+
+    element_with_attribute("id", None)
+        .or_else(|| element_with_attribute("name", Some(ElementKind::A)))
 }
 
 pub fn get_style_content(root: Rc<RefCell<Node>>) -> String {
@@ -70,6 +231,100 @@ pub fn get_style_content(root: Rc<RefCell<Node>>) -> String {
     content
 }
 
+/// 最初の<title>要素のテキスト。前後の空白を取り除き、続く空白を1つの空白にまとめる。
+/// <title>要素がない場合はNoneを返す
+/// https://html.spec.whatwg.org/multipage/dom.html#document.title
+pub fn get_title_content(root: Rc<RefCell<Node>>) -> Option<String> {
+    let title_node = get_target_element_node(Some(root), ElementKind::Title)?;
+    let mut text = String::new();
+    let mut child = title_node.borrow().first_child();
+    while let Some(node) = child {
+        if let NodeKind::Text(ref s) = node.borrow().kind() {
+            text.push_str(s);
+        }
+        child = node.borrow().next_sibling();
+    }
+    Some(
+        text.split_ascii_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" "),
+    )
+}
+
+/// `name`属性が一致する最初の<meta>要素のcontent属性。名前は大文字と小文字を区別しない
+pub fn get_meta_content(root: Rc<RefCell<Node>>, name: &str) -> Option<String> {
+    DomTreeIterator::new(Some(root)).find_map(|node| {
+        let node = node.borrow();
+        if node.element_kind() != Some(ElementKind::Meta) {
+            return None;
+        }
+        let element = node.get_element()?;
+        match element.get_attribute("name") {
+            Some(n) if n.eq_ignore_ascii_case(name) => element.get_attribute("content"),
+            _ => None,
+        }
+    })
+}
+
+/// 文書順に並べた<link>要素の、rel属性、href属性、as属性。href属性がない<link>要素は含めない
+/// https://html.spec.whatwg.org/multipage/links.html#linkTypes
+pub fn get_links(root: Rc<RefCell<Node>>) -> Vec<(String, String, Option<String>)> {
+    DomTreeIterator::new(Some(root))
+        .filter_map(|node| {
+            let node = node.borrow();
+            if node.element_kind() != Some(ElementKind::Link) {
+                return None;
+            }
+            let element = node.get_element()?;
+            Some((
+                element.get_attribute("rel").unwrap_or_default(),
+                element.get_attribute("href")?,
+                element.get_attribute("as"),
+            ))
+        })
+        .collect()
+}
+
+/// ページのスタイルシートの元になる要素
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StyleSource {
+    /// <link rel="stylesheet">のhref属性
+    Link(String),
+    /// <style>の中身
+    Style(String),
+}
+
+/// <link rel="stylesheet">と<style>を文書順に並べる。後のスタイルシートのルールが優先されるので、順番を保つ
+/// https://drafts.csswg.org/cssom/#documentorshadowroot-document-or-shadow-root-css-style-sheets
+pub fn get_style_sources(root: Rc<RefCell<Node>>) -> Vec<StyleSource> {
+    DomTreeIterator::new(Some(root))
+        .filter_map(|node| {
+            let node = node.borrow();
+            match node.element_kind()? {
+                ElementKind::Link => {
+                    let element = node.get_element()?;
+                    let rel = element.get_attribute("rel")?;
+                    if !rel
+                        .split_ascii_whitespace()
+                        .any(|r| r.eq_ignore_ascii_case("stylesheet"))
+                    {
+                        return None;
+                    }
+                    Some(StyleSource::Link(element.get_attribute("href")?))
+                }
+                ElementKind::Style => {
+                    let text = match node.first_child()?.borrow().kind() {
+                        NodeKind::Text(ref s) => s.clone(),
+                        _ => return None,
+                    };
+                    Some(StyleSource::Style(text))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
 pub fn get_js_content(root: Rc<RefCell<Node>>) -> String {
     let js_node = match get_target_element_node(Some(root), ElementKind::Script) {
         Some(node) => node,
@@ -85,3 +340,114 @@ pub fn get_js_content(root: Rc<RefCell<Node>>) -> String {
     };
     content
 }
+
+/// 文書内の要素をIDとクラス名から引くための索引。
+/// DOMツリーが変更された場合は作り直す必要がある
+#[derive(Debug, Clone)]
+pub struct ElementIndex {
+    ids: BTreeMap<String, Rc<RefCell<Node>>>,
+    classes: BTreeMap<String, Vec<Rc<RefCell<Node>>>>,
+}
+
+impl ElementIndex {
+    pub fn new(root: Rc<RefCell<Node>>) -> Self {
+        let mut index = Self {
+            ids: BTreeMap::new(),
+            classes: BTreeMap::new(),
+        };
+        for n in DomTreeIterator::new(Some(root)) {
+            if let NodeKind::Element(e) = n.borrow().kind() {
+                if let Some(id) = e.get_attribute("id") {
+                    // 同じIDを持つ要素が複数ある場合、文書内で最初に現れた要素を使う
+                    index.ids.entry(id).or_insert(n.clone());
+                }
+                if let Some(class) = e.get_attribute("class") {
+                    index.classes.entry(class).or_default().push(n.clone());
+                }
+            }
+        }
+        index
+    }
+
+    pub fn get_element_by_id(&self, id_name: &str) -> Option<Rc<RefCell<Node>>> {
+        self.ids.get(id_name).cloned()
+    }
+
+    pub fn get_elements_by_class_name(&self, class_name: &str) -> Vec<Rc<RefCell<Node>>> {
+        match self.classes.get(class_name) {
+            Some(nodes) => nodes.clone(),
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+    use alloc::vec;
+
+    fn create_document(html: &str) -> Rc<RefCell<Node>> {
+        let t = HtmlTokenizer::new(html.to_string());
+        let window = HtmlParser::new(t).construct_tree();
+        let document = window.borrow().document();
+        document
+    }
+
+    #[test]
+    fn test_dom_tree_iterator() {
+        let document = create_document("<html><head></head><body><p>a</p><h1>b</h1></body></html>");
+        let kinds: Vec<Option<ElementKind>> = DomTreeIterator::new(Some(document))
+            .map(|n| n.borrow().element_kind())
+            .collect();
+        assert_eq!(
+            vec![
+                None,
+                Some(ElementKind::Html),
+                Some(ElementKind::Head),
+                Some(ElementKind::Body),
+                Some(ElementKind::P),
+                None,
+                Some(ElementKind::H1),
+                None,
+            ],
+            kinds
+        );
+    }
+
+    #[test]
+    fn test_dom_tree_iterator_depth_limit() {
+        let document = create_document("<html><head></head><body><p>a</p><h1>b</h1></body></html>");
+        // Documentの深さを0として、深さ2の<head>と<body>までを辿る
+        let count = DomTreeIterator::with_depth_limit(Some(document), 2).count();
+        assert_eq!(4, count);
+    }
+
+    #[test]
+    fn test_get_indicated_element() {
+        let document = create_document(
+            "<html><head></head><body><a name=\"top\">a</a><p id=\"sec\">b</p><p name=\"other\">c</p></body></html>",
+        );
+        let kind = |fragment: &str| {
+            get_indicated_element(document.clone(), fragment)
+                .and_then(|n| n.borrow().element_kind())
+        };
+        assert_eq!(Some(ElementKind::P), kind("sec"));
+        assert_eq!(Some(ElementKind::A), kind("top"));
+        // name属性で探すのは<a>要素だけ
+        assert_eq!(None, kind("other"));
+        assert_eq!(None, kind(""));
+    }
+
+    #[test]
+    fn test_get_title_content() {
+        let document = create_document(
+            "<html><head><title>\n  a &amp;\tb  </title><title>second</title></head><body></body></html>",
+        );
+        assert_eq!(Some("a & b".to_string()), get_title_content(document));
+
+        let document = create_document("<html><head></head><body></body></html>");
+        assert_eq!(None, get_title_content(document));
+    }
+}
@@ -0,0 +1,285 @@
+//! DOMツリーの変更を通知する仕組み。MutationObserverを簡略化したもの
+//! https://dom.spec.whatwg.org/#mutation-observers
+
+use crate::renderer::dom::node::Node;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+/// https://dom.spec.whatwg.org/#dom-mutationrecord-type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutationType {
+    /// 子ノードが追加または削除された
+    ChildList,
+    /// 属性の値が変更された
+    Attributes,
+}
+
+impl MutationType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MutationType::ChildList => "childList",
+            MutationType::Attributes => "attributes",
+        }
+    }
+}
+
+/// https://dom.spec.whatwg.org/#interface-mutationrecord
+#[derive(Debug, Clone)]
+pub struct MutationRecord {
+    kind: MutationType,
+    target: Rc<RefCell<Node>>,
+    added_nodes: Vec<Rc<RefCell<Node>>>,
+    removed_nodes: Vec<Rc<RefCell<Node>>>,
+    attribute_name: Option<String>,
+    old_value: Option<String>,
+}
+
+impl MutationRecord {
+    pub fn child_list(
+        target: Rc<RefCell<Node>>,
+        added_nodes: Vec<Rc<RefCell<Node>>>,
+        removed_nodes: Vec<Rc<RefCell<Node>>>,
+    ) -> Self {
+        Self {
+            kind: MutationType::ChildList,
+            target,
+            added_nodes,
+            removed_nodes,
+            attribute_name: None,
+            old_value: None,
+        }
+    }
+
+    pub fn attributes(
+        target: Rc<RefCell<Node>>,
+        attribute_name: String,
+        old_value: Option<String>,
+    ) -> Self {
+        Self {
+            kind: MutationType::Attributes,
+            target,
+            added_nodes: Vec::new(),
+            removed_nodes: Vec::new(),
+            attribute_name: Some(attribute_name),
+            old_value,
+        }
+    }
+
+    pub fn kind(&self) -> MutationType {
+        self.kind
+    }
+
+    pub fn target(&self) -> Rc<RefCell<Node>> {
+        self.target.clone()
+    }
+
+    pub fn added_nodes(&self) -> Vec<Rc<RefCell<Node>>> {
+        self.added_nodes.clone()
+    }
+
+    pub fn removed_nodes(&self) -> Vec<Rc<RefCell<Node>>> {
+        self.removed_nodes.clone()
+    }
+
+    pub fn attribute_name(&self) -> Option<String> {
+        self.attribute_name.clone()
+    }
+
+    pub fn old_value(&self) -> Option<String> {
+        self.old_value.clone()
+    }
+}
+
+/// どの変更を通知するか
+/// https://dom.spec.whatwg.org/#dictdef-mutationobserverinit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MutationObserverInit {
+    pub child_list: bool,
+    pub attributes: bool,
+    /// trueなら、子孫ノードの変更も通知する
+    pub subtree: bool,
+}
+
+impl MutationObserverInit {
+    /// 全ての種類の変更を、子孫ノードも含めて通知する
+    pub fn all() -> Self {
+        Self {
+            child_list: true,
+            attributes: true,
+            subtree: true,
+        }
+    }
+
+    fn accepts(&self, kind: MutationType) -> bool {
+        match kind {
+            MutationType::ChildList => self.child_list,
+            MutationType::Attributes => self.attributes,
+        }
+    }
+}
+
+pub type ObserverId = usize;
+
+#[derive(Debug, Clone)]
+struct Registration {
+    target: Rc<RefCell<Node>>,
+    options: MutationObserverInit,
+    records: Vec<MutationRecord>,
+}
+
+/// 登録されたオブザーバーと、それぞれに届いたまだ取り出されていないレコードを管理する。
+/// DOMツリーを変更する側はqueue_recordを呼び、変更を知りたい側（devtools、レイアウト、テストなど）は
+/// observeで登録してtake_recordsでレコードを取り出す
+#[derive(Debug, Clone, Default)]
+pub struct MutationObservers {
+    /// disconnectされたオブザーバーはNoneになる。ObserverIdはこのベクタの添字
+    registrations: Vec<Option<Registration>>,
+}
+
+impl MutationObservers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-mutationobserver-observe
+    pub fn observe(
+        &mut self,
+        target: Rc<RefCell<Node>>,
+        options: MutationObserverInit,
+    ) -> ObserverId {
+        self.registrations.push(Some(Registration {
+            target,
+            options,
+            records: Vec::new(),
+        }));
+        self.registrations.len() - 1
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-mutationobserver-disconnect
+    pub fn disconnect(&mut self, id: ObserverId) {
+        if let Some(registration) = self.registrations.get_mut(id) {
+            *registration = None;
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-mutationobserver-takerecords
+    pub fn take_records(&mut self, id: ObserverId) -> Vec<MutationRecord> {
+        match self.registrations.get_mut(id) {
+            Some(Some(registration)) => core::mem::take(&mut registration.records),
+            _ => Vec::new(),
+        }
+    }
+
+    /// まだ取り出されていないレコードがあるかどうか
+    pub fn has_pending_records(&self) -> bool {
+        self.registrations
+            .iter()
+            .flatten()
+            .any(|r| !r.records.is_empty())
+    }
+
+    /// レコードの対象ノードか、その祖先を監視しているオブザーバーにレコードを届ける
+    /// https://dom.spec.whatwg.org/#queueing-a-mutation-record
+    pub fn queue_record(&mut self, record: MutationRecord) {
+        for registration in self.registrations.iter_mut().flatten() {
+            if !registration.options.accepts(record.kind) {
+                continue;
+            }
+
+            let mut node = Some(record.target.clone());
+            let mut is_target = true;
+            while let Some(n) = node {
+                if Rc::ptr_eq(&n, &registration.target) {
+                    if is_target || registration.options.subtree {
+                        registration.records.push(record.clone());
+                    }
+                    break;
+                }
+                node = n.borrow().parent().upgrade();
+                is_target = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::dom::api::append_child;
+    use crate::renderer::dom::node::Element;
+    use crate::renderer::dom::node::NodeKind;
+    use alloc::string::ToString;
+
+    fn element(name: &str) -> Rc<RefCell<Node>> {
+        Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
+            name,
+            Vec::new(),
+        )))))
+    }
+
+    #[test]
+    fn test_queue_record() {
+        let body = element("body");
+        let p = element("p");
+        append_child(&body, p.clone());
+
+        let mut observers = MutationObservers::new();
+        let all = observers.observe(body.clone(), MutationObserverInit::all());
+        let direct = observers.observe(
+            body.clone(),
+            MutationObserverInit {
+                child_list: true,
+                attributes: true,
+                subtree: false,
+            },
+        );
+        let attributes_only = observers.observe(
+            p.clone(),
+            MutationObserverInit {
+                child_list: false,
+                attributes: true,
+                subtree: false,
+            },
+        );
+
+        let text = Rc::new(RefCell::new(Node::new(NodeKind::Text("a".to_string()))));
+        observers.queue_record(MutationRecord::child_list(
+            p.clone(),
+            alloc::vec![text],
+            Vec::new(),
+        ));
+        observers.queue_record(MutationRecord::attributes(
+            p.clone(),
+            "class".to_string(),
+            None,
+        ));
+        assert!(observers.has_pending_records());
+
+        // subtreeを指定したオブザーバーには、子孫ノードの変更も届く
+        let kinds: Vec<MutationType> = observers
+            .take_records(all)
+            .iter()
+            .map(|r| r.kind())
+            .collect();
+        assert_eq!(
+            alloc::vec![MutationType::ChildList, MutationType::Attributes],
+            kinds
+        );
+        assert!(observers.take_records(all).is_empty());
+
+        assert!(observers.take_records(direct).is_empty());
+
+        let records = observers.take_records(attributes_only);
+        assert_eq!(1, records.len());
+        assert_eq!(Some("class".to_string()), records[0].attribute_name());
+        assert!(Rc::ptr_eq(&p, &records[0].target()));
+        assert!(!observers.has_pending_records());
+
+        // disconnectした後は、レコードが届かない
+        observers.disconnect(all);
+        observers.queue_record(MutationRecord::attributes(p, "id".to_string(), None));
+        assert!(observers.take_records(all).is_empty());
+    }
+}
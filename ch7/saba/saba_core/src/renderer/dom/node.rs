@@ -1,4 +1,6 @@
+use crate::renderer::dom::api::get_title_content;
 use crate::renderer::html::attribute::Attribute;
+use crate::renderer::html::token::SourcePosition;
 use alloc::format;
 use alloc::rc::Rc;
 use alloc::rc::Weak;
@@ -9,15 +11,30 @@ use core::fmt::Display;
 use core::fmt::Formatter;
 use core::str::FromStr;
 
+/// ドキュメントの互換モード。<!DOCTYPE>がない古いページは、当時のブラウザに合わせて表示する
+/// https://dom.spec.whatwg.org/#concept-document-quirks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuirksMode {
+    /// 標準モード。<!DOCTYPE html>を持つページ
+    NoQuirks,
+    /// 限定互換モード。XHTML 1.0 TransitionalなどのDOCTYPEを持つページ
+    LimitedQuirks,
+    /// 互換モード。DOCTYPEがないか、古いDOCTYPEを持つページ
+    Quirks,
+}
+
 #[derive(Debug, Clone)]
 pub struct Window {
     document: Rc<RefCell<Node>>,
+    /// HTMLの解釈の最初にDOCTYPEから決める。レイアウトが互換モードの扱いを決めるのに使う
+    quirks_mode: QuirksMode,
 }
 
 impl Window {
     pub fn new() -> Self {
         let window = Self {
             document: Rc::new(RefCell::new(Node::new(NodeKind::Document))),
+            quirks_mode: QuirksMode::NoQuirks,
         };
 
         window
@@ -31,6 +48,19 @@ impl Window {
     pub fn document(&self) -> Rc<RefCell<Node>> {
         self.document.clone()
     }
+
+    /// 文書の<title>要素から決めたタイトル。<title>要素がない場合は空文字列
+    pub fn title(&self) -> String {
+        get_title_content(self.document.clone()).unwrap_or_default()
+    }
+
+    pub fn quirks_mode(&self) -> QuirksMode {
+        self.quirks_mode
+    }
+
+    pub fn set_quirks_mode(&mut self, quirks_mode: QuirksMode) {
+        self.quirks_mode = quirks_mode;
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -42,6 +72,8 @@ pub struct Node {
     last_child: Weak<RefCell<Node>>,
     previous_sibling: Weak<RefCell<Node>>,
     next_sibling: Option<Rc<RefCell<Node>>>,
+    /// HTMLのソースの中で、このノードを作ったトークンの位置。スクリプトが作ったノードはNoneになる
+    source_position: Option<SourcePosition>,
 }
 
 impl PartialEq for Node {
@@ -60,6 +92,7 @@ impl Node {
             last_child: Weak::new(),
             previous_sibling: Weak::new(),
             next_sibling: None,
+            source_position: None,
         }
     }
 
@@ -107,20 +140,28 @@ impl Node {
         self.next_sibling.as_ref().cloned()
     }
 
+    pub fn set_source_position(&mut self, source_position: Option<SourcePosition>) {
+        self.source_position = source_position;
+    }
+
+    pub fn source_position(&self) -> Option<SourcePosition> {
+        self.source_position
+    }
+
     pub fn kind(&self) -> NodeKind {
         self.kind.clone()
     }
 
     pub fn get_element(&self) -> Option<Element> {
         match self.kind {
-            NodeKind::Document | NodeKind::Text(_) => None,
+            NodeKind::Document | NodeKind::Text(_) | NodeKind::Comment(_) => None,
             NodeKind::Element(ref e) => Some(e.clone()),
         }
     }
 
     pub fn element_kind(&self) -> Option<ElementKind> {
         match self.kind {
-            NodeKind::Document | NodeKind::Text(_) => None,
+            NodeKind::Document | NodeKind::Text(_) | NodeKind::Comment(_) => None,
             NodeKind::Element(ref e) => Some(e.kind()),
         }
     }
@@ -134,6 +175,8 @@ pub enum NodeKind {
     Element(Element),
     /// https://dom.spec.whatwg.org/#interface-text
     Text(String),
+    /// https://dom.spec.whatwg.org/#interface-comment
+    Comment(String),
 }
 
 impl PartialEq for NodeKind {
@@ -145,6 +188,7 @@ impl PartialEq for NodeKind {
                 _ => false,
             },
             NodeKind::Text(_) => matches!(other, NodeKind::Text(_)),
+            NodeKind::Comment(_) => matches!(other, NodeKind::Comment(_)),
         }
     }
 }
@@ -182,9 +226,60 @@ impl Element {
         None
     }
 
+    /// 属性の値を設定し、変更前の値を返す。属性がなければ最後に追加する
+    /// https://dom.spec.whatwg.org/#dom-element-setattribute
+    pub fn set_attribute(&mut self, name: &str, value: String) -> Option<String> {
+        if let Some(attr) = self.attributes.iter_mut().find(|a| a.name() == name) {
+            let old_value = attr.value();
+            attr.set_value(value);
+            return Some(old_value);
+        }
+
+        let mut attr = Attribute::new();
+        for c in name.chars() {
+            attr.add_char(c, true);
+        }
+        attr.set_value(value);
+        self.attributes.push(attr);
+        None
+    }
+
     pub fn is_block_element(&self) -> bool {
         match self.kind {
-            ElementKind::Body | ElementKind::H1 | ElementKind::H2 | ElementKind::P => true,
+            ElementKind::Body
+            | ElementKind::H1
+            | ElementKind::H2
+            | ElementKind::H3
+            | ElementKind::H4
+            | ElementKind::H5
+            | ElementKind::H6
+            | ElementKind::P
+            | ElementKind::Main
+            | ElementKind::Section
+            | ElementKind::Header
+            | ElementKind::Footer
+            | ElementKind::Nav
+            | ElementKind::Article
+            | ElementKind::Div
+            | ElementKind::Ul
+            | ElementKind::Ol
+            | ElementKind::Li
+            | ElementKind::Dl
+            | ElementKind::Dt
+            | ElementKind::Dd
+            // 表の要素は、表のレイアウトで並べ方を決めるブロック要素として扱う
+            | ElementKind::Table
+            | ElementKind::Tbody
+            | ElementKind::Tr
+            | ElementKind::Td
+            | ElementKind::Th
+            // <iframe>は置換要素だが、本書のブラウザではインライン要素に大きさを持たせられないので、
+            // ブロック要素として扱う。<img>も同じ
+            | ElementKind::Iframe
+            | ElementKind::Img
+            | ElementKind::Hr
+            // <br>は改行を強制するために、大きさを持たないブロック要素として扱う
+            | ElementKind::Br => true,
             _ => false,
         }
     }
@@ -197,6 +292,12 @@ pub enum ElementKind {
     Html,
     /// https://html.spec.whatwg.org/multipage/semantics.html#the-head-element
     Head,
+    /// https://html.spec.whatwg.org/multipage/semantics.html#the-title-element
+    Title,
+    /// https://html.spec.whatwg.org/multipage/semantics.html#the-meta-element
+    Meta,
+    /// https://html.spec.whatwg.org/multipage/semantics.html#the-link-element
+    Link,
     /// https://html.spec.whatwg.org/multipage/semantics.html#the-style-element
     Style,
     /// https://html.spec.whatwg.org/multipage/scripting.html#the-script-element
@@ -208,8 +309,87 @@ pub enum ElementKind {
     /// https://html.spec.whatwg.org/multipage/sections.html#the-h1,-h2,-h3,-h4,-h5,-and-h6-elements
     H1,
     H2,
+    H3,
+    H4,
+    H5,
+    H6,
     /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-a-element
     A,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-main-element
+    Main,
+    /// https://html.spec.whatwg.org/multipage/sections.html#the-section-element
+    Section,
+    /// https://html.spec.whatwg.org/multipage/sections.html#the-header-element
+    Header,
+    /// https://html.spec.whatwg.org/multipage/sections.html#the-footer-element
+    Footer,
+    /// https://html.spec.whatwg.org/multipage/sections.html#the-nav-element
+    Nav,
+    /// https://html.spec.whatwg.org/multipage/sections.html#the-article-element
+    Article,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-div-element
+    Div,
+    /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-span-element
+    Span,
+    /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-b-element
+    B,
+    /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-i-element
+    I,
+    /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-strong-element
+    Strong,
+    /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-em-element
+    Em,
+    /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-u-element
+    U,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-ul-element
+    Ul,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-ol-element
+    Ol,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-li-element
+    Li,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-dl-element
+    Dl,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-dt-element
+    Dt,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-dd-element
+    Dd,
+    /// https://html.spec.whatwg.org/multipage/tables.html#the-table-element
+    Table,
+    /// https://html.spec.whatwg.org/multipage/tables.html#the-tbody-element
+    Tbody,
+    /// https://html.spec.whatwg.org/multipage/tables.html#the-tr-element
+    Tr,
+    /// https://html.spec.whatwg.org/multipage/tables.html#the-td-element
+    Td,
+    /// https://html.spec.whatwg.org/multipage/tables.html#the-th-element
+    Th,
+    /// https://html.spec.whatwg.org/multipage/iframe-embed-object.html#the-iframe-element
+    Iframe,
+    /// https://html.spec.whatwg.org/multipage/embedded-content.html#the-img-element
+    Img,
+    /// https://html.spec.whatwg.org/multipage/form-elements.html#the-textarea-element
+    Textarea,
+    /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-br-element
+    Br,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-hr-element
+    Hr,
+    /// https://html.spec.whatwg.org/multipage/input.html#the-input-element
+    Input,
+}
+
+impl ElementKind {
+    /// 見出し要素の階層。h1が1で、h6が6になる。見出しでない要素はNoneを返す
+    pub fn heading_level(&self) -> Option<usize> {
+        match self {
+            ElementKind::H1 => Some(1),
+            ElementKind::H2 => Some(2),
+            ElementKind::H3 => Some(3),
+            ElementKind::H4 => Some(4),
+            ElementKind::H5 => Some(5),
+            ElementKind::H6 => Some(6),
+            _ => None,
+        }
+    }
 }
 
 impl Display for ElementKind {
@@ -217,13 +397,50 @@ impl Display for ElementKind {
         let s = match self {
             ElementKind::Html => "html",
             ElementKind::Head => "head",
+            ElementKind::Title => "title",
+            ElementKind::Meta => "meta",
+            ElementKind::Link => "link",
             ElementKind::Style => "style",
             ElementKind::Script => "script",
             ElementKind::Body => "body",
             ElementKind::H1 => "h1",
             ElementKind::H2 => "h2",
+            ElementKind::H3 => "h3",
+            ElementKind::H4 => "h4",
+            ElementKind::H5 => "h5",
+            ElementKind::H6 => "h6",
             ElementKind::P => "p",
             ElementKind::A => "a",
+            ElementKind::Main => "main",
+            ElementKind::Section => "section",
+            ElementKind::Header => "header",
+            ElementKind::Footer => "footer",
+            ElementKind::Nav => "nav",
+            ElementKind::Article => "article",
+            ElementKind::Div => "div",
+            ElementKind::Span => "span",
+            ElementKind::B => "b",
+            ElementKind::I => "i",
+            ElementKind::Strong => "strong",
+            ElementKind::Em => "em",
+            ElementKind::U => "u",
+            ElementKind::Ul => "ul",
+            ElementKind::Ol => "ol",
+            ElementKind::Li => "li",
+            ElementKind::Dl => "dl",
+            ElementKind::Dt => "dt",
+            ElementKind::Dd => "dd",
+            ElementKind::Table => "table",
+            ElementKind::Tbody => "tbody",
+            ElementKind::Tr => "tr",
+            ElementKind::Td => "td",
+            ElementKind::Th => "th",
+            ElementKind::Iframe => "iframe",
+            ElementKind::Img => "img",
+            ElementKind::Textarea => "textarea",
+            ElementKind::Br => "br",
+            ElementKind::Hr => "hr",
+            ElementKind::Input => "input",
         };
         write!(f, "{}", s)
     }
@@ -236,13 +453,50 @@ impl FromStr for ElementKind {
         match s {
             "html" => Ok(ElementKind::Html),
             "head" => Ok(ElementKind::Head),
+            "title" => Ok(ElementKind::Title),
+            "meta" => Ok(ElementKind::Meta),
+            "link" => Ok(ElementKind::Link),
             "style" => Ok(ElementKind::Style),
             "script" => Ok(ElementKind::Script),
             "body" => Ok(ElementKind::Body),
             "p" => Ok(ElementKind::P),
             "h1" => Ok(ElementKind::H1),
             "h2" => Ok(ElementKind::H2),
+            "h3" => Ok(ElementKind::H3),
+            "h4" => Ok(ElementKind::H4),
+            "h5" => Ok(ElementKind::H5),
+            "h6" => Ok(ElementKind::H6),
             "a" => Ok(ElementKind::A),
+            "main" => Ok(ElementKind::Main),
+            "section" => Ok(ElementKind::Section),
+            "header" => Ok(ElementKind::Header),
+            "footer" => Ok(ElementKind::Footer),
+            "nav" => Ok(ElementKind::Nav),
+            "article" => Ok(ElementKind::Article),
+            "div" => Ok(ElementKind::Div),
+            "span" => Ok(ElementKind::Span),
+            "b" => Ok(ElementKind::B),
+            "i" => Ok(ElementKind::I),
+            "strong" => Ok(ElementKind::Strong),
+            "em" => Ok(ElementKind::Em),
+            "u" => Ok(ElementKind::U),
+            "ul" => Ok(ElementKind::Ul),
+            "ol" => Ok(ElementKind::Ol),
+            "li" => Ok(ElementKind::Li),
+            "dl" => Ok(ElementKind::Dl),
+            "dt" => Ok(ElementKind::Dt),
+            "dd" => Ok(ElementKind::Dd),
+            "table" => Ok(ElementKind::Table),
+            "tbody" => Ok(ElementKind::Tbody),
+            "tr" => Ok(ElementKind::Tr),
+            "td" => Ok(ElementKind::Td),
+            "th" => Ok(ElementKind::Th),
+            "iframe" => Ok(ElementKind::Iframe),
+            "img" => Ok(ElementKind::Img),
+            "textarea" => Ok(ElementKind::Textarea),
+            "br" => Ok(ElementKind::Br),
+            "hr" => Ok(ElementKind::Hr),
+            "input" => Ok(ElementKind::Input),
             _ => Err(format!("unimplemented element name {:?}", s)),
         }
     }
@@ -1,29 +1,53 @@
+use alloc::rc::Rc;
 use alloc::string::String;
+use alloc::string::ToString;
 
+/// 属性の名前は、トークナイザが同じ名前の属性の間で共有する文字列にする
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Attribute {
-    name: String,
+    name: Rc<str>,
     value: String,
 }
 
 impl Attribute {
     pub fn new() -> Self {
         Self {
-            name: String::new(),
+            name: Rc::from(""),
             value: String::new(),
         }
     }
 
     pub fn add_char(&mut self, c: char, is_name: bool) {
         if is_name {
-            self.name.push(c);
+            let mut name = self.name.to_string();
+            name.push(c);
+            self.name = Rc::from(name);
         } else {
             self.value.push(c);
         }
     }
 
+    /// 続く文字列をまとめて加える
+    pub fn add_str(&mut self, s: &str, is_name: bool) {
+        if is_name {
+            let mut name = self.name.to_string();
+            name.push_str(s);
+            self.name = Rc::from(name);
+        } else {
+            self.value.push_str(s);
+        }
+    }
+
+    pub fn set_name(&mut self, name: Rc<str>) {
+        self.name = name;
+    }
+
+    pub fn set_value(&mut self, value: String) {
+        self.value = value;
+    }
+
     pub fn name(&self) -> String {
-        self.name.clone()
+        self.name.to_string()
     }
 
     pub fn value(&self) -> String {
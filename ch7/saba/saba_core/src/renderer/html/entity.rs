@@ -0,0 +1,187 @@
+//! 文字参照（"&amp;"や"&#x26;"）が表す文字。HtmlTokenizerが、テキストと属性値の中の文字参照を置き換えるのに使う。
+//! 名前付き文字参照は、よく使われるものだけを持つ
+//! https://html.spec.whatwg.org/multipage/named-characters.html
+
+/// 名前付き文字参照の名前と、それが表す文字
+const NAMED_CHARACTER_REFERENCES: &[(&str, char)] = &[
+    ("amp", '&'),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("quot", '"'),
+    ("apos", '\''),
+    ("AMP", '&'),
+    ("LT", '<'),
+    ("GT", '>'),
+    ("QUOT", '"'),
+    ("nbsp", '\u{A0}'),
+    ("iexcl", '¡'),
+    ("cent", '¢'),
+    ("pound", '£'),
+    ("curren", '¤'),
+    ("yen", '¥'),
+    ("brvbar", '¦'),
+    ("sect", '§'),
+    ("uml", '¨'),
+    ("copy", '©'),
+    ("COPY", '©'),
+    ("ordf", 'ª'),
+    ("laquo", '«'),
+    ("not", '¬'),
+    ("shy", '\u{AD}'),
+    ("reg", '®'),
+    ("REG", '®'),
+    ("macr", '¯'),
+    ("deg", '°'),
+    ("plusmn", '±'),
+    ("sup2", '²'),
+    ("sup3", '³'),
+    ("acute", '´'),
+    ("micro", 'µ'),
+    ("para", '¶'),
+    ("middot", '·'),
+    ("cedil", '¸'),
+    ("sup1", '¹'),
+    ("ordm", 'º'),
+    ("raquo", '»'),
+    ("frac14", '¼'),
+    ("frac12", '½'),
+    ("frac34", '¾'),
+    ("iquest", '¿'),
+    ("Agrave", 'À'),
+    ("Aacute", 'Á'),
+    ("Acirc", 'Â'),
+    ("Atilde", 'Ã'),
+    ("Auml", 'Ä'),
+    ("Aring", 'Å'),
+    ("AElig", 'Æ'),
+    ("Ccedil", 'Ç'),
+    ("Egrave", 'È'),
+    ("Eacute", 'É'),
+    ("Ecirc", 'Ê'),
+    ("Euml", 'Ë'),
+    ("Iacute", 'Í'),
+    ("Ntilde", 'Ñ'),
+    ("Oacute", 'Ó'),
+    ("Ocirc", 'Ô'),
+    ("Otilde", 'Õ'),
+    ("Ouml", 'Ö'),
+    ("times", '×'),
+    ("Oslash", 'Ø'),
+    ("Uacute", 'Ú'),
+    ("Uuml", 'Ü'),
+    ("szlig", 'ß'),
+    ("agrave", 'à'),
+    ("aacute", 'á'),
+    ("acirc", 'â'),
+    ("atilde", 'ã'),
+    ("auml", 'ä'),
+    ("aring", 'å'),
+    ("aelig", 'æ'),
+    ("ccedil", 'ç'),
+    ("egrave", 'è'),
+    ("eacute", 'é'),
+    ("ecirc", 'ê'),
+    ("euml", 'ë'),
+    ("igrave", 'ì'),
+    ("iacute", 'í'),
+    ("icirc", 'î'),
+    ("iuml", 'ï'),
+    ("ntilde", 'ñ'),
+    ("ograve", 'ò'),
+    ("oacute", 'ó'),
+    ("ocirc", 'ô'),
+    ("otilde", 'õ'),
+    ("ouml", 'ö'),
+    ("divide", '÷'),
+    ("oslash", 'ø'),
+    ("ugrave", 'ù'),
+    ("uacute", 'ú'),
+    ("ucirc", 'û'),
+    ("uuml", 'ü'),
+    ("yacute", 'ý'),
+    ("yuml", 'ÿ'),
+    ("alpha", 'α'),
+    ("beta", 'β'),
+    ("gamma", 'γ'),
+    ("delta", 'δ'),
+    ("lambda", 'λ'),
+    ("mu", 'μ'),
+    ("pi", 'π'),
+    ("sigma", 'σ'),
+    ("omega", 'ω'),
+    ("ensp", '\u{2002}'),
+    ("emsp", '\u{2003}'),
+    ("thinsp", '\u{2009}'),
+    ("zwnj", '\u{200C}'),
+    ("zwj", '\u{200D}'),
+    ("ndash", '–'),
+    ("mdash", '—'),
+    ("lsquo", '‘'),
+    ("rsquo", '’'),
+    ("ldquo", '“'),
+    ("rdquo", '”'),
+    ("bull", '•'),
+    ("hellip", '…'),
+    ("prime", '′'),
+    ("euro", '€'),
+    ("trade", '™'),
+    ("larr", '←'),
+    ("uarr", '↑'),
+    ("rarr", '→'),
+    ("darr", '↓'),
+    ("harr", '↔'),
+    ("hearts", '♥'),
+];
+
+/// 名前付き文字参照の名前（"&"と";"を除いた部分）が表す文字
+pub fn named_character_reference(name: &str) -> Option<char> {
+    NAMED_CHARACTER_REFERENCES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, c)| *c)
+}
+
+/// ";"を省略しても文字参照として扱う、古くからある名前かどうか。
+/// HTML 4より前から使われている、Latin-1の文字を表すものが該当する（"&apos;"は含まない）
+/// https://html.spec.whatwg.org/multipage/parsing.html#named-character-reference-state
+pub fn is_legacy_character_reference(name: &str) -> bool {
+    name != "apos" && matches!(named_character_reference(name), Some(c) if (c as u32) < 0x100)
+}
+
+/// 数値文字参照のコードポイントが表す文字。文字として使えない値はU+FFFDにし、
+/// 0x80から0x9FまではWindows-1252の文字として読み替える
+/// https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-end-state
+pub fn numeric_character_reference(code: u32) -> char {
+    const WINDOWS_1252: [char; 32] = [
+        '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž',
+        '\u{8F}', '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}',
+        'ž', 'Ÿ',
+    ];
+    match code {
+        0 => '\u{FFFD}',
+        0x80..=0x9F => WINDOWS_1252[(code - 0x80) as usize],
+        _ => char::from_u32(code).unwrap_or('\u{FFFD}'),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_character_references() {
+        assert_eq!(Some('&'), named_character_reference("amp"));
+        assert_eq!(Some('©'), named_character_reference("copy"));
+        assert_eq!(None, named_character_reference("Amp"));
+        assert!(is_legacy_character_reference("eacute"));
+        assert!(!is_legacy_character_reference("apos"));
+        assert!(!is_legacy_character_reference("hellip"));
+
+        assert_eq!('&', numeric_character_reference(0x26));
+        assert_eq!('€', numeric_character_reference(0x80));
+        assert_eq!('\u{FFFD}', numeric_character_reference(0));
+        // サロゲートと、Unicodeの範囲を超える値
+        assert_eq!('\u{FFFD}', numeric_character_reference(0xD800));
+        assert_eq!('\u{FFFD}', numeric_character_reference(0x110000));
+    }
+}
@@ -1,3 +1,6 @@
 pub mod attribute;
+pub mod entity;
+pub mod parse_error;
 pub mod parser;
+pub mod srcset;
 pub mod token;
@@ -0,0 +1,170 @@
+//! HTMLの解釈の失敗。不正なHTMLでも読むのはやめずに、仕様に従って回復しながら続ける。
+//! 失敗した場所は記録しておき、解釈の後でまとめて取り出せる
+//! https://html.spec.whatwg.org/multipage/parsing.html#parse-errors
+
+use crate::renderer::html::token::SourcePosition;
+use alloc::string::String;
+use core::fmt::Display;
+use core::fmt::Formatter;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// "<?xml ...>"のような処理命令。不正なコメントとして読む
+    UnexpectedQuestionMarkInsteadOfTagName,
+    /// "<"や"</"の後にタグの名前にならない文字が続いた
+    InvalidFirstCharacterOfTagName,
+    /// "</>"。何もせずに読み飛ばす
+    MissingEndTagName,
+    /// 属性の名前の最初に"="がある
+    UnexpectedEqualsSignBeforeAttributeName,
+    /// 属性の名前の中に"\""、"'"、"<"がある
+    UnexpectedCharacterInAttributeName,
+    /// "="の後に値がなく、タグが閉じられた
+    MissingAttributeValue,
+    /// 引用符で囲んでいない属性値の中に"\""、"'"、"<"、"="、"`"がある
+    UnexpectedCharacterInUnquotedAttributeValue,
+    /// 引用符で囲んだ属性値の直後に、空白を挟まずに次の属性が続いた
+    MissingWhitespaceBetweenAttributes,
+    /// タグの途中に、">"が続かない"/"がある
+    UnexpectedSolidusInTag,
+    /// 同じ名前の属性が2回以上ある。最初の属性だけを使う
+    DuplicateAttribute,
+    /// 属性を持つ終了タグ。属性は捨てる
+    EndTagWithAttributes,
+    /// "</a/>"のように"/>"で閉じた終了タグ。"/"は無視する
+    EndTagWithTrailingSolidus,
+    /// "<!--"と"<!DOCTYPE"以外の"<!"。不正なコメントとして読む
+    IncorrectlyOpenedComment,
+    /// HTMLの要素の中にある"<![CDATA["。不正なコメントとして読む
+    CdataInHtmlContent,
+    /// "<"や"</"の直後で入力が終わった。文字として扱う
+    EofBeforeTagName,
+    /// タグの途中で入力が終わった。そのタグは捨てる
+    EofInTag,
+    /// コメントの途中で入力が終わった。そこまでをコメントにする
+    EofInComment,
+    /// DOCTYPEの途中で入力が終わった。ページを互換モードで表示する
+    EofInDoctype,
+    /// CDATAセクションの途中で入力が終わった
+    EofInCdata,
+    /// "<!-->"や"<!--->"。空のコメントとして扱う
+    AbruptClosingOfEmptyComment,
+    /// "--!>"で閉じたコメント
+    IncorrectlyClosedComment,
+    /// "<!DOCTYPE>"のように、DOCTYPEに名前がない
+    MissingDoctypeName,
+    /// 名前付き文字参照の後ろに";"がない
+    MissingSemicolonAfterCharacterReference,
+    /// ";"で終わる、知らない名前の文字参照
+    UnknownNamedCharacterReference,
+    /// "&#"や"&#x"の後に数字がない
+    AbsenceOfDigitsInNumericCharacterReference,
+    /// "&#0;"
+    NullCharacterReference,
+    /// Unicodeの範囲を超える数値文字参照
+    CharacterReferenceOutsideUnicodeRange,
+    /// サロゲートを指す数値文字参照
+    SurrogateCharacterReference,
+    /// 最初の要素の前に<!DOCTYPE>がない。ページを互換モードで表示する
+    MissingDoctype,
+    /// 最初の要素より後ろにある<!DOCTYPE>。無視する
+    UnexpectedDoctype,
+    /// 対応する開始タグが開いていない終了タグ。無視する
+    UnexpectedEndTag(String),
+    /// "<h1><h2>"のように、その場所では開けない開始タグ。開いている要素を閉じてから開く
+    UnexpectedStartTag(String),
+    /// 表の中の、セルに入っていない文字。表の直前に移す
+    UnexpectedCharacterInTable,
+}
+
+impl ParseErrorKind {
+    /// 仕様書でのエラーの名前。ツリー構築のエラーは仕様書に名前がないので、同じ形式で名付ける
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseErrorKind::UnexpectedQuestionMarkInsteadOfTagName => {
+                "unexpected-question-mark-instead-of-tag-name"
+            }
+            ParseErrorKind::InvalidFirstCharacterOfTagName => "invalid-first-character-of-tag-name",
+            ParseErrorKind::MissingEndTagName => "missing-end-tag-name",
+            ParseErrorKind::UnexpectedEqualsSignBeforeAttributeName => {
+                "unexpected-equals-sign-before-attribute-name"
+            }
+            ParseErrorKind::UnexpectedCharacterInAttributeName => {
+                "unexpected-character-in-attribute-name"
+            }
+            ParseErrorKind::MissingAttributeValue => "missing-attribute-value",
+            ParseErrorKind::UnexpectedCharacterInUnquotedAttributeValue => {
+                "unexpected-character-in-unquoted-attribute-value"
+            }
+            ParseErrorKind::MissingWhitespaceBetweenAttributes => {
+                "missing-whitespace-between-attributes"
+            }
+            ParseErrorKind::UnexpectedSolidusInTag => "unexpected-solidus-in-tag",
+            ParseErrorKind::DuplicateAttribute => "duplicate-attribute",
+            ParseErrorKind::EndTagWithAttributes => "end-tag-with-attributes",
+            ParseErrorKind::EndTagWithTrailingSolidus => "end-tag-with-trailing-solidus",
+            ParseErrorKind::IncorrectlyOpenedComment => "incorrectly-opened-comment",
+            ParseErrorKind::CdataInHtmlContent => "cdata-in-html-content",
+            ParseErrorKind::EofBeforeTagName => "eof-before-tag-name",
+            ParseErrorKind::EofInTag => "eof-in-tag",
+            ParseErrorKind::EofInComment => "eof-in-comment",
+            ParseErrorKind::EofInDoctype => "eof-in-doctype",
+            ParseErrorKind::EofInCdata => "eof-in-cdata",
+            ParseErrorKind::AbruptClosingOfEmptyComment => "abrupt-closing-of-empty-comment",
+            ParseErrorKind::IncorrectlyClosedComment => "incorrectly-closed-comment",
+            ParseErrorKind::MissingDoctypeName => "missing-doctype-name",
+            ParseErrorKind::MissingSemicolonAfterCharacterReference => {
+                "missing-semicolon-after-character-reference"
+            }
+            ParseErrorKind::UnknownNamedCharacterReference => "unknown-named-character-reference",
+            ParseErrorKind::AbsenceOfDigitsInNumericCharacterReference => {
+                "absence-of-digits-in-numeric-character-reference"
+            }
+            ParseErrorKind::NullCharacterReference => "null-character-reference",
+            ParseErrorKind::CharacterReferenceOutsideUnicodeRange => {
+                "character-reference-outside-unicode-range"
+            }
+            ParseErrorKind::SurrogateCharacterReference => "surrogate-character-reference",
+            ParseErrorKind::MissingDoctype => "missing-doctype",
+            ParseErrorKind::UnexpectedDoctype => "unexpected-doctype",
+            ParseErrorKind::UnexpectedEndTag(_) => "unexpected-end-tag",
+            ParseErrorKind::UnexpectedStartTag(_) => "unexpected-start-tag",
+            ParseErrorKind::UnexpectedCharacterInTable => "unexpected-character-in-table",
+        }
+    }
+}
+
+/// 解釈の失敗の種類と、ソースの中でそれが見つかった位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    position: SourcePosition,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, position: SourcePosition) -> Self {
+        Self { kind, position }
+    }
+
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind.clone()
+    }
+
+    pub fn position(&self) -> SourcePosition {
+        self.position
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        match &self.kind {
+            ParseErrorKind::UnexpectedEndTag(tag) => {
+                write!(f, "{}: {} </{}>", self.position, self.kind.code(), tag)
+            }
+            ParseErrorKind::UnexpectedStartTag(tag) => {
+                write!(f, "{}: {} <{}>", self.position, self.kind.code(), tag)
+            }
+            kind => write!(f, "{}: {}", self.position, kind.code()),
+        }
+    }
+}
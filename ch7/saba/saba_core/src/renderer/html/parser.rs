@@ -1,13 +1,24 @@
+use crate::renderer::dom::api::append_child;
+use crate::renderer::dom::api::insert_before;
+use crate::renderer::dom::api::remove_all_children;
+use crate::renderer::dom::api::remove_child;
 use crate::renderer::dom::node::Element;
 use crate::renderer::dom::node::ElementKind;
 use crate::renderer::dom::node::Node;
 use crate::renderer::dom::node::NodeKind;
+use crate::renderer::dom::node::QuirksMode;
 use crate::renderer::dom::node::Window;
 use crate::renderer::html::attribute::Attribute;
+use crate::renderer::html::parse_error::ParseError;
+use crate::renderer::html::parse_error::ParseErrorKind;
+use crate::renderer::html::token::is_whitespace;
 use crate::renderer::html::token::HtmlToken;
 use crate::renderer::html::token::HtmlTokenizer;
+use crate::renderer::html::token::State;
+use alloc::format;
 use alloc::rc::Rc;
 use alloc::string::String;
+use alloc::string::ToString;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 use core::str::FromStr;
@@ -21,6 +32,10 @@ pub enum InsertionMode {
     InHead,
     AfterHead,
     InBody,
+    /// このブラウザは、<tbody>の中もInTableで扱う
+    InTable,
+    InRow,
+    InCell,
     Text,
     AfterBody,
     AfterAfterBody,
@@ -34,7 +49,12 @@ pub struct HtmlParser {
     original_insertion_mode: InsertionMode,
     /// https://html.spec.whatwg.org/multipage/parsing.html#the-stack-of-open-elements
     stack_of_open_elements: Vec<Rc<RefCell<Node>>>,
+    /// 開いた書式要素。Noneは、セルの中の書式要素をセルの外で開き直さないためのマーカー
+    /// https://html.spec.whatwg.org/multipage/parsing.html#list-of-active-formatting-elements
+    active_formatting_elements: Vec<Option<Rc<RefCell<Node>>>>,
     t: HtmlTokenizer,
+    /// ツリー構築で見つけた解釈の失敗。トークナイザが見つけたものは、トークナイザが持つ
+    errors: Vec<ParseError>,
 }
 
 impl HtmlParser {
@@ -44,10 +64,25 @@ impl HtmlParser {
             mode: InsertionMode::Initial,
             original_insertion_mode: InsertionMode::Initial,
             stack_of_open_elements: Vec::new(),
+            active_formatting_elements: Vec::new(),
             t,
+            errors: Vec::new(),
         }
     }
 
+    /// トークナイザとツリー構築で見つけた解釈の失敗を、ソースの中の位置の順に返す
+    pub fn errors(&self) -> Vec<ParseError> {
+        let mut errors = self.t.errors();
+        errors.extend(self.errors.iter().cloned());
+        errors.sort_by_key(|error| error.position());
+        errors
+    }
+
+    /// 処理しているトークンの位置で、解釈の失敗を記録する
+    fn error(&mut self, kind: ParseErrorKind) {
+        self.errors.push(ParseError::new(kind, self.t.position()));
+    }
+
     fn contain_in_stack(&mut self, element_kind: ElementKind) -> bool {
         for i in 0..self.stack_of_open_elements.len() {
             if self.stack_of_open_elements[i].borrow().element_kind() == Some(element_kind) {
@@ -77,6 +112,412 @@ impl HtmlParser {
         }
     }
 
+    /// スタックの上から`element_kind`の要素を探す。`boundaries`の要素より下は探さない
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-the-specific-scope
+    fn has_in_specific_scope(&self, element_kind: ElementKind, boundaries: &[ElementKind]) -> bool {
+        for node in self.stack_of_open_elements.iter().rev() {
+            match node.borrow().element_kind() {
+                Some(kind) if kind == element_kind => return true,
+                Some(kind) if boundaries.contains(&kind) => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// セルの中からは、表の外の要素を探さない
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-scope
+    fn has_in_scope(&self, element_kind: ElementKind) -> bool {
+        self.has_in_specific_scope(element_kind, &SCOPE_BOUNDARIES)
+    }
+
+    /// このブラウザは<button>を扱わないので、通常のスコープと同じ
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-button-scope
+    fn has_in_button_scope(&self, element_kind: ElementKind) -> bool {
+        self.has_in_scope(element_kind)
+    }
+
+    /// 入れ子になったリストの中からは、外のリストの<li>を探さない
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-list-item-scope
+    fn has_in_list_item_scope(&self, element_kind: ElementKind) -> bool {
+        let mut boundaries = SCOPE_BOUNDARIES.to_vec();
+        boundaries.extend([ElementKind::Ul, ElementKind::Ol]);
+        self.has_in_specific_scope(element_kind, &boundaries)
+    }
+
+    /// 表の構造を作る要素は、セルの中の要素を境界にせずに探す
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-table-scope
+    fn has_in_table_scope(&self, element_kind: ElementKind) -> bool {
+        self.has_in_specific_scope(element_kind, &[ElementKind::Html, ElementKind::Table])
+    }
+
+    /// 現在のノードが`kinds`の要素か<html>になるまで、スタックから取り出す
+    /// https://html.spec.whatwg.org/multipage/parsing.html#clear-the-stack-back-to-a-table-context
+    fn clear_stack_back_to(&mut self, kinds: &[ElementKind]) {
+        while let Some(current) = self.stack_of_open_elements.last() {
+            let reached = match current.borrow().element_kind() {
+                Some(kind) => kind == ElementKind::Html || kinds.contains(&kind),
+                None => false,
+            };
+            if reached {
+                return;
+            }
+            self.stack_of_open_elements.pop();
+        }
+    }
+
+    /// 表を閉じた後に、開いている要素から挿入モードを決め直す
+    /// https://html.spec.whatwg.org/multipage/parsing.html#reset-the-insertion-mode-appropriately
+    fn reset_insertion_mode(&mut self) {
+        self.mode = InsertionMode::InBody;
+        for node in self.stack_of_open_elements.iter().rev() {
+            self.mode = match node.borrow().element_kind() {
+                Some(ElementKind::Td) | Some(ElementKind::Th) => InsertionMode::InCell,
+                Some(ElementKind::Tr) => InsertionMode::InRow,
+                Some(ElementKind::Tbody) | Some(ElementKind::Table) => InsertionMode::InTable,
+                Some(ElementKind::Body) | Some(ElementKind::Html) => InsertionMode::InBody,
+                _ => continue,
+            };
+            return;
+        }
+    }
+
+    /// 開いている行を閉じて、InTableに戻る
+    fn close_row(&mut self) {
+        self.clear_stack_back_to(&[ElementKind::Tr]);
+        self.stack_of_open_elements.pop();
+        self.mode = InsertionMode::InTable;
+    }
+
+    /// 開いているセルを、その中で終了タグが省略された要素と一緒に閉じて、InRowに戻る
+    /// https://html.spec.whatwg.org/multipage/parsing.html#close-the-cell
+    fn close_cell(&mut self) {
+        self.generate_implied_end_tags(None);
+        while let Some(node) = self.stack_of_open_elements.pop() {
+            if matches!(
+                node.borrow().element_kind(),
+                Some(ElementKind::Td) | Some(ElementKind::Th)
+            ) {
+                break;
+            }
+        }
+        self.clear_active_formatting_elements_to_last_marker();
+        self.mode = InsertionMode::InRow;
+    }
+
+    /// 表の中では、表の構造に関わらないトークンをInBodyの規則で処理する。
+    /// セルの中身はそのままInBodyで処理するが、セルの外にあるタグは解釈の失敗として記録する
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intable
+    fn mode_for_token(&mut self, token: &Option<HtmlToken>) -> InsertionMode {
+        let in_cell = match self.mode {
+            InsertionMode::InTable | InsertionMode::InRow => false,
+            InsertionMode::InCell => true,
+            mode => return mode,
+        };
+        match token {
+            Some(HtmlToken::StartTag { tag, .. }) => match tag.as_ref() {
+                "tbody" | "tr" | "td" | "th" => self.mode,
+                // セルの中の<table>は、入れ子の表としてInBodyで開く
+                "table" if !in_cell => self.mode,
+                _ if !in_cell => {
+                    self.error(ParseErrorKind::UnexpectedStartTag(tag.to_string()));
+                    InsertionMode::InBody
+                }
+                _ => InsertionMode::InBody,
+            },
+            Some(HtmlToken::EndTag { tag }) => match tag.as_ref() {
+                "table" | "tbody" | "tr" | "td" | "th" | "body" | "html" => self.mode,
+                _ if !in_cell => {
+                    self.error(ParseErrorKind::UnexpectedEndTag(tag.to_string()));
+                    InsertionMode::InBody
+                }
+                _ => InsertionMode::InBody,
+            },
+            // セルの外の文字は、InTableの規則で表の前に移す
+            Some(HtmlToken::Text(_)) if !in_cell && self.is_in_table_structure() => {
+                InsertionMode::InTable
+            }
+            Some(HtmlToken::Text(_)) => InsertionMode::InBody,
+            _ => self.mode,
+        }
+    }
+
+    fn is_current_node(&self, element_kind: ElementKind) -> bool {
+        self.stack_of_open_elements
+            .last()
+            .is_some_and(|n| n.borrow().element_kind() == Some(element_kind))
+    }
+
+    /// 現在のノードが、セルではない表の要素かどうか
+    fn is_in_table_structure(&self) -> bool {
+        matches!(
+            self.stack_of_open_elements
+                .last()
+                .and_then(|n| n.borrow().element_kind()),
+            Some(ElementKind::Table) | Some(ElementKind::Tbody) | Some(ElementKind::Tr)
+        )
+    }
+
+    /// 表の中でセルに入っていない文字列を、表の直前のテキストノードに移す
+    /// https://html.spec.whatwg.org/multipage/parsing.html#foster-parent
+    fn foster_parent_text(&mut self, text: &str) {
+        let table = match self
+            .stack_of_open_elements
+            .iter()
+            .rev()
+            .find(|n| n.borrow().element_kind() == Some(ElementKind::Table))
+        {
+            Some(table) => table.clone(),
+            None => return,
+        };
+        let parent = match table.borrow().parent().upgrade() {
+            Some(parent) => parent,
+            None => return,
+        };
+
+        if let Some(previous) = table.borrow().previous_sibling().upgrade() {
+            if let NodeKind::Text(ref mut s) = previous.borrow_mut().kind {
+                s.push_str(text);
+                return;
+            }
+        }
+
+        // 続けて移す文字は同じテキストノードに追加するので、解釈の失敗は最初の文字で1回だけ記録する
+        self.error(ParseErrorKind::UnexpectedCharacterInTable);
+        let node = Rc::new(RefCell::new(self.create_text(text)));
+        insert_before(&parent, node, &table);
+    }
+
+    /// 終了タグを省略できる要素を、`except`の要素が現在のノードになるまでスタックから取り出す。
+    /// 開いている途中のテキストノードも閉じる
+    /// https://html.spec.whatwg.org/multipage/parsing.html#generate-implied-end-tags
+    fn generate_implied_end_tags(&mut self, except: Option<ElementKind>) {
+        while let Some(current) = self.stack_of_open_elements.last() {
+            let implied = match &current.borrow().kind {
+                NodeKind::Text(_) => true,
+                NodeKind::Element(e) => Some(e.kind()) != except && has_implied_end_tag(e.kind()),
+                _ => false,
+            };
+            if !implied {
+                return;
+            }
+            self.stack_of_open_elements.pop();
+        }
+    }
+
+    /// 開いている<p>要素を、その中で終了タグが省略された要素と一緒に閉じる
+    /// https://html.spec.whatwg.org/multipage/parsing.html#close-a-p-element
+    fn close_p_element(&mut self) {
+        self.generate_implied_end_tags(Some(ElementKind::P));
+        self.pop_until(ElementKind::P);
+    }
+
+    /// "<p>one<p>two"のように、<p>の中でブロック要素を開く場合は、前の<p>を閉じて兄弟にする
+    fn close_p_element_in_button_scope(&mut self) {
+        if self.has_in_button_scope(ElementKind::P) {
+            self.close_p_element();
+        }
+    }
+
+    fn is_in_stack(&self, node: &Rc<RefCell<Node>>) -> bool {
+        self.stack_of_open_elements
+            .iter()
+            .any(|n| Rc::ptr_eq(n, node))
+    }
+
+    /// 最後のマーカーまで、書式要素のリストから取り除く
+    /// https://html.spec.whatwg.org/multipage/parsing.html#clear-the-list-of-active-formatting-elements-up-to-the-last-marker
+    fn clear_active_formatting_elements_to_last_marker(&mut self) {
+        while let Some(entry) = self.active_formatting_elements.pop() {
+            if entry.is_none() {
+                return;
+            }
+        }
+    }
+
+    /// "<p><b>a</p>b"の"b"のように、書式要素が閉じられた後に続く中身のために、
+    /// 閉じられた書式要素を同じ名前と属性で開き直す
+    /// https://html.spec.whatwg.org/multipage/parsing.html#reconstruct-the-active-formatting-elements
+    fn reconstruct_active_formatting_elements(&mut self) {
+        // 最後のマーカーか、まだ開いている書式要素より後ろにあるものを開き直す
+        let start = self
+            .active_formatting_elements
+            .iter()
+            .rposition(|entry| match entry {
+                Some(node) => self.is_in_stack(node),
+                None => true,
+            })
+            .map_or(0, |i| i + 1);
+
+        for i in start..self.active_formatting_elements.len() {
+            let element = match &self.active_formatting_elements[i] {
+                Some(node) => node.borrow().get_element(),
+                None => None,
+            };
+            if let Some(element) = element {
+                self.insert_element(&element.kind().to_string(), element.attributes());
+                self.active_formatting_elements[i] = self.stack_of_open_elements.last().cloned();
+            }
+        }
+    }
+
+    /// 書式要素の開始タグ。閉じられた書式要素を開き直してから開き、書式要素のリストに追加する
+    fn insert_formatting_element(&mut self, tag: &str, attributes: Vec<Attribute>) {
+        self.reconstruct_active_formatting_elements();
+        self.insert_element(tag, attributes);
+        let node = self.stack_of_open_elements.last().cloned();
+        self.active_formatting_elements.push(node);
+    }
+
+    /// 書式要素の終了タグで、"<b><i>a</b>b</i>"のように入れ子を間違えた書式要素を、中身を失わずに閉じる。
+    /// 仕様のブックマークの移動と、内側のループで複製する書式要素の数の制限を省いた簡略版
+    /// https://html.spec.whatwg.org/multipage/parsing.html#adoption-agency-algorithm
+    fn run_adoption_agency(&mut self, tag: &str) {
+        let element_kind =
+            ElementKind::from_str(tag).expect("failed to convert string to ElementKind");
+
+        // 書式要素の中で開かれたブロック要素がなくなるまで繰り返す
+        for _ in 0..8 {
+            // 最後のマーカーより後ろで、同じ名前の書式要素を探す
+            let mut found = None;
+            for (i, entry) in self.active_formatting_elements.iter().enumerate().rev() {
+                match entry {
+                    Some(node) if node.borrow().element_kind() == Some(element_kind) => {
+                        found = Some((i, node.clone()));
+                        break;
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+            let (list_index, formatting_element) = match found {
+                Some(found) => found,
+                None => {
+                    self.error(ParseErrorKind::UnexpectedEndTag(tag.to_string()));
+                    return;
+                }
+            };
+
+            let stack_index = match self
+                .stack_of_open_elements
+                .iter()
+                .position(|n| Rc::ptr_eq(n, &formatting_element))
+            {
+                Some(i) => i,
+                None => {
+                    // 既に閉じられた書式要素は、リストからも取り除く
+                    self.error(ParseErrorKind::UnexpectedEndTag(tag.to_string()));
+                    self.active_formatting_elements.remove(list_index);
+                    return;
+                }
+            };
+            if !self.has_in_scope(element_kind) {
+                self.error(ParseErrorKind::UnexpectedEndTag(tag.to_string()));
+                return;
+            }
+
+            // 終了タグの直前まで続いていたテキストノードを閉じる
+            let in_text = matches!(
+                self.stack_of_open_elements.last(),
+                Some(current) if matches!(current.borrow().kind, NodeKind::Text(_))
+            );
+            if in_text {
+                self.stack_of_open_elements.pop();
+            }
+            if stack_index != self.stack_of_open_elements.len() - 1 {
+                self.error(ParseErrorKind::UnexpectedEndTag(tag.to_string()));
+            }
+
+            // 書式要素の中で開かれた、最初のブロック要素
+            let furthest_block_index = (stack_index + 1..self.stack_of_open_elements.len())
+                .find(|i| is_special(&self.stack_of_open_elements[*i]));
+            let mut furthest_block_index = match furthest_block_index {
+                Some(i) => i,
+                None => {
+                    // "<b><i>a</b>"のように間にブロック要素がなければ、書式要素までをまとめて閉じる。
+                    // 一緒に閉じた<i>はリストに残るので、続く中身を入れるときに開き直す
+                    self.stack_of_open_elements.truncate(stack_index);
+                    self.active_formatting_elements.remove(list_index);
+                    return;
+                }
+            };
+            let furthest_block = self.stack_of_open_elements[furthest_block_index].clone();
+            let common_ancestor = self.stack_of_open_elements[stack_index - 1].clone();
+
+            // 書式要素とブロック要素の間にある要素を、ブロック要素の外に出す。
+            // 書式要素は複製して、ブロック要素をその子にする
+            let mut last_node = furthest_block.clone();
+            let mut index = furthest_block_index - 1;
+            while index > stack_index {
+                let node = self.stack_of_open_elements[index].clone();
+                let entry = self
+                    .active_formatting_elements
+                    .iter()
+                    .position(|e| e.as_ref().is_some_and(|e| Rc::ptr_eq(e, &node)));
+                let entry = match entry {
+                    Some(entry) => entry,
+                    None => {
+                        self.stack_of_open_elements.remove(index);
+                        furthest_block_index -= 1;
+                        index -= 1;
+                        continue;
+                    }
+                };
+
+                let clone = Rc::new(RefCell::new(self.clone_element(&node)));
+                self.active_formatting_elements[entry] = Some(clone.clone());
+                self.stack_of_open_elements[index] = clone.clone();
+                detach(&last_node);
+                append_child(&clone, last_node);
+                last_node = clone;
+                index -= 1;
+            }
+            detach(&last_node);
+            append_child(&common_ancestor, last_node);
+
+            // ブロック要素の中身を、書式要素の複製に移す
+            let clone = Rc::new(RefCell::new(self.clone_element(&formatting_element)));
+            for child in remove_all_children(&furthest_block) {
+                append_child(&clone, child);
+            }
+            append_child(&furthest_block, clone.clone());
+
+            self.active_formatting_elements[list_index] = Some(clone.clone());
+            self.stack_of_open_elements.remove(stack_index);
+            self.stack_of_open_elements
+                .insert(furthest_block_index, clone);
+        }
+    }
+
+    /// 同じ名前と属性を持つ、子を持たない要素を作る
+    fn clone_element(&self, node: &Rc<RefCell<Node>>) -> Node {
+        match node.borrow().get_element() {
+            Some(element) => self.create_element(&element.kind().to_string(), element.attributes()),
+            None => unreachable!("only elements are cloned"),
+        }
+    }
+
+    /// "<li>one<li>two"のように、開いている項目の中で開いた項目は、前の項目を閉じて兄弟にする。
+    /// <div>と<p>以外のブロック要素の中にある項目は、入れ子のリストの項目なので閉じない
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
+    fn close_list_item(&mut self, kinds: &[ElementKind]) {
+        let open_item = self
+            .stack_of_open_elements
+            .iter()
+            .rev()
+            .filter_map(|n| n.borrow().element_kind())
+            .find(|kind| {
+                !matches!(
+                    kind,
+                    ElementKind::A | ElementKind::Span | ElementKind::Div | ElementKind::P
+                ) && !is_formatting_element(*kind)
+            });
+        if let Some(kind) = open_item.filter(|kind| kinds.contains(kind)) {
+            self.generate_implied_end_tags(Some(kind));
+            self.pop_until(kind);
+        }
+    }
+
     fn pop_current_node(&mut self, element_kind: ElementKind) -> bool {
         let current = match self.stack_of_open_elements.last() {
             Some(n) => n,
@@ -91,53 +532,76 @@ impl HtmlParser {
         false
     }
 
-    fn create_char(&self, c: char) -> Node {
-        let mut s = String::new();
-        s.push(c);
-        Node::new(NodeKind::Text(s))
+    fn create_text(&self, text: &str) -> Node {
+        let mut node = Node::new(NodeKind::Text(String::from(text)));
+        node.set_source_position(Some(self.t.position()));
+        node
     }
 
-    fn insert_char(&mut self, c: char) {
+    fn insert_text(&mut self, text: &str) {
         let current = match self.stack_of_open_elements.last() {
             Some(n) => n.clone(),
             None => return,
         };
 
-        // 現在参照しているノードがテキストノードの場合、そのノードに文字を追加する。
+        // 現在参照しているノードがテキストノードの場合、そのノードに文字列を追加する。
         if let NodeKind::Text(ref mut s) = current.borrow_mut().kind {
-            s.push(c);
+            s.push_str(text);
             return;
         }
 
-        // 改行文字や空白文字のときはテキストノードを追加しない。
-        if c == '\n' || c == ' ' {
+        // 改行文字や空白文字だけのときはテキストノードを追加しない。
+        if is_whitespace_text(text) {
             return;
         }
 
-        let node = Rc::new(RefCell::new(self.create_char(c)));
+        // コメントの後ろのように、既に子ノードがある場合も最後の子として追加する
+        let node = Rc::new(RefCell::new(self.create_text(text)));
+        append_child(&current, node.clone());
 
-        if current.borrow().first_child().is_some() {
-            current
-                .borrow()
-                .first_child()
-                .unwrap()
-                .borrow_mut()
-                .set_next_sibling(Some(node.clone()));
-        } else {
-            current.borrow_mut().set_first_child(Some(node.clone()));
-        }
+        self.stack_of_open_elements.push(node);
+    }
 
-        current.borrow_mut().set_last_child(Rc::downgrade(&node));
-        node.borrow_mut().set_parent(Rc::downgrade(&current));
+    /// コメントを現在のノードの最後の子として追加する。`to_document`がtrueの場合は、
+    /// <html>の外のコメントとしてDocumentに追加する
+    /// https://html.spec.whatwg.org/multipage/parsing.html#insert-a-comment
+    fn insert_comment(&mut self, data: &str, to_document: bool) {
+        // テキストノードの途中にあるコメントは、そこでテキストノードを終える。
+        // コメントの後ろの文字は、新しいテキストノードになる
+        let in_text = matches!(
+            self.stack_of_open_elements.last(),
+            Some(current) if matches!(current.borrow().kind, NodeKind::Text(_))
+        );
+        if in_text {
+            self.stack_of_open_elements.pop();
+        }
 
-        self.stack_of_open_elements.push(node);
+        let parent = match self.stack_of_open_elements.last() {
+            Some(n) if !to_document => n.clone(),
+            _ => self.window.borrow().document(),
+        };
+        let mut node = Node::new(NodeKind::Comment(String::from(data)));
+        node.set_source_position(Some(self.t.position()));
+        append_child(&parent, Rc::new(RefCell::new(node)));
     }
 
     fn create_element(&self, tag: &str, attributes: Vec<Attribute>) -> Node {
-        Node::new(NodeKind::Element(Element::new(tag, attributes)))
+        // 省略されたタグを補った要素は、補うきっかけになったトークンの位置を持つ
+        let mut node = Node::new(NodeKind::Element(Element::new(tag, attributes)));
+        node.set_source_position(Some(self.t.position()));
+        node
     }
 
     fn insert_element(&mut self, tag: &str, attributes: Vec<Attribute>) {
+        // "a<span>b</span>"のようにテキストの後ろに続く要素は、テキストノードの子ではなく兄弟にする
+        let in_text = matches!(
+            self.stack_of_open_elements.last(),
+            Some(current) if matches!(current.borrow().kind, NodeKind::Text(_))
+        );
+        if in_text {
+            self.stack_of_open_elements.pop();
+        }
+
         let window = self.window.borrow();
         let current = match self.stack_of_open_elements.last() {
             Some(n) => n.clone(),
@@ -176,30 +640,92 @@ impl HtmlParser {
         current.borrow_mut().set_last_child(Rc::downgrade(&node));
         node.borrow_mut().set_parent(Rc::downgrade(&current));
 
-        self.stack_of_open_elements.push(node);
+        // 空要素は中身を持たないので、スタックに積まない
+        if !VOID_ELEMENTS.contains(&tag) {
+            self.stack_of_open_elements.push(node);
+        }
+    }
+
+    /// RCDATAの要素を挿入し、終了タグまでの中身を文字参照を置き換えたテキストとして読む。
+    /// 中身はText挿入モードで受け取り、終了タグで元の挿入モードに戻る
+    /// https://html.spec.whatwg.org/multipage/parsing.html#generic-rcdata-element-parsing-algorithm
+    fn insert_rcdata_element(&mut self, tag: &str, attributes: Vec<Attribute>) {
+        self.insert_element(tag, attributes);
+        self.t.switch_to(State::Rcdata);
+        self.original_insertion_mode = self.mode;
+        self.mode = InsertionMode::Text;
     }
 
+    /// トークナイザが返すトークンがなくなるまで、DOMツリーを作る
     pub fn construct_tree(&mut self) -> Rc<RefCell<Window>> {
         let mut token = self.t.next();
 
         while token.is_some() {
-            match self.mode {
+            // コメントは、どの挿入モードでも現在の位置に追加する
+            if let Some(HtmlToken::Comment(ref data)) = token {
+                match self.mode {
+                    // <style>や<iframe>の中身はまだ生のテキストとして読んでいないので、
+                    // コメントに見える部分も元の文字列に戻してテキストにする
+                    InsertionMode::Text => {
+                        self.insert_text(&format!("<!--{}-->", data));
+                    }
+                    InsertionMode::Initial
+                    | InsertionMode::BeforeHtml
+                    | InsertionMode::AfterAfterBody => self.insert_comment(data, true),
+                    _ => self.insert_comment(data, false),
+                }
+                token = self.t.next();
+                continue;
+            }
+            // 最初の挿入モード以外のDOCTYPEは、不正なものとして無視する
+            if matches!(token, Some(HtmlToken::Doctype { .. }))
+                && self.mode != InsertionMode::Initial
+            {
+                self.error(ParseErrorKind::UnexpectedDoctype);
+                token = self.t.next();
+                continue;
+            }
+
+            match self.mode_for_token(&token) {
                 InsertionMode::Initial => {
-                    // 本書では、DOCTYPEトークンをサポートしていないため、
-                    // <!doctype html>のようなトークンは文字トークンとして表される。
-                    // 文字トークンは無視する
-                    if let Some(HtmlToken::Char(_)) = token {
-                        token = self.t.next();
-                        continue;
+                    // 空白文字は無視する
+                    if let Some(HtmlToken::Text(ref text)) = token {
+                        if is_whitespace_text(text) {
+                            token = self.t.next();
+                            continue;
+                        }
                     }
 
+                    // DOCTYPEから互換モードを決める。DOCTYPEがないページは互換モードで表示する
+                    // https://html.spec.whatwg.org/multipage/parsing.html#the-initial-insertion-mode
+                    let quirks_mode = match token {
+                        Some(HtmlToken::Doctype {
+                            ref name,
+                            ref public_id,
+                            ref system_id,
+                            force_quirks,
+                        }) => doctype_quirks_mode(
+                            name.as_deref(),
+                            public_id.as_deref(),
+                            system_id.as_deref(),
+                            force_quirks,
+                        ),
+                        _ => QuirksMode::Quirks,
+                    };
+                    // DOCTYPEはDOMツリーに追加しない。それ以外のトークンは、次の挿入モードで処理し直す
+                    if matches!(token, Some(HtmlToken::Doctype { .. })) {
+                        token = self.t.next();
+                    } else {
+                        self.error(ParseErrorKind::MissingDoctype);
+                    }
+                    self.window.borrow_mut().set_quirks_mode(quirks_mode);
                     self.mode = InsertionMode::BeforeHtml;
                     continue;
                 }
                 InsertionMode::BeforeHtml => {
                     match token {
-                        Some(HtmlToken::Char(c)) => {
-                            if c == ' ' || c == '\n' {
+                        Some(HtmlToken::Text(ref text)) => {
+                            if is_whitespace_text(text) {
                                 token = self.t.next();
                                 continue;
                             }
@@ -209,7 +735,7 @@ impl HtmlParser {
                             self_closing: _,
                             ref attributes,
                         }) => {
-                            if tag == "html" {
+                            if &**tag == "html" {
                                 self.insert_element(tag, attributes.to_vec());
                                 self.mode = InsertionMode::BeforeHead;
                                 token = self.t.next();
@@ -217,11 +743,18 @@ impl HtmlParser {
                             }
                         }
                         Some(HtmlToken::EndTag { ref tag }) => {
-                            if tag != "head" || tag != "body" || tag != "html" || tag != "br" {
+                            if &**tag != "head"
+                                || &**tag != "body"
+                                || &**tag != "html"
+                                || &**tag != "br"
+                            {
                                 token = self.t.next();
                                 continue;
                             }
                         }
+                        Some(HtmlToken::Comment(_)) | Some(HtmlToken::Doctype { .. }) => {
+                            unreachable!("comments and DOCTYPE tokens are handled before the insertion mode is checked")
+                        }
                         Some(HtmlToken::Eof) | None => {
                             return self.window.clone();
                         }
@@ -232,8 +765,8 @@ impl HtmlParser {
                 }
                 InsertionMode::BeforeHead => {
                     match token {
-                        Some(HtmlToken::Char(c)) => {
-                            if c == ' ' || c == '\n' {
+                        Some(HtmlToken::Text(ref text)) => {
+                            if is_whitespace_text(text) {
                                 token = self.t.next();
                                 continue;
                             }
@@ -243,7 +776,7 @@ impl HtmlParser {
                             self_closing: _,
                             ref attributes,
                         }) => {
-                            if tag == "head" {
+                            if &**tag == "head" {
                                 self.insert_element(tag, attributes.to_vec());
                                 self.mode = InsertionMode::InHead;
                                 token = self.t.next();
@@ -261,9 +794,9 @@ impl HtmlParser {
                 }
                 InsertionMode::InHead => {
                     match token {
-                        Some(HtmlToken::Char(c)) => {
-                            if c == ' ' || c == '\n' {
-                                self.insert_char(c);
+                        Some(HtmlToken::Text(ref text)) => {
+                            if is_whitespace_text(text) {
+                                self.insert_text(text);
                                 token = self.t.next();
                                 continue;
                             }
@@ -273,17 +806,31 @@ impl HtmlParser {
                             self_closing: _,
                             ref attributes,
                         }) => {
-                            if tag == "style" || tag == "script" {
+                            if &**tag == "style" || &**tag == "script" {
                                 self.insert_element(tag, attributes.to_vec());
                                 self.original_insertion_mode = self.mode;
                                 self.mode = InsertionMode::Text;
                                 token = self.t.next();
                                 continue;
                             }
+                            // <title>の中身は、文字参照を置き換えたテキストとして読む
+                            // https://html.spec.whatwg.org/multipage/parsing.html#generic-rcdata-element-parsing-algorithm
+                            if &**tag == "title" {
+                                self.insert_rcdata_element(tag, attributes.to_vec());
+                                token = self.t.next();
+                                continue;
+                            }
+                            // <meta>と<link>は空要素なので、スタックに積まれない
+                            // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inhead
+                            if &**tag == "meta" || &**tag == "link" {
+                                self.insert_element(tag, attributes.to_vec());
+                                token = self.t.next();
+                                continue;
+                            }
                             // 仕様書には定められていないが、このブラウザは仕様を全て実装している
                             // わけではないので、<head>が省略されているHTML文書を扱うために必要。
                             // これがないと<head>が省略されているHTML文書で無限ループが発生
-                            if tag == "body" {
+                            if &**tag == "body" {
                                 self.pop_until(ElementKind::Head);
                                 self.mode = InsertionMode::AfterHead;
                                 continue;
@@ -295,26 +842,29 @@ impl HtmlParser {
                             }
                         }
                         Some(HtmlToken::EndTag { ref tag }) => {
-                            if tag == "head" {
+                            if &**tag == "head" {
                                 self.mode = InsertionMode::AfterHead;
                                 token = self.t.next();
                                 self.pop_until(ElementKind::Head);
                                 continue;
                             }
                         }
+                        Some(HtmlToken::Comment(_)) | Some(HtmlToken::Doctype { .. }) => {
+                            unreachable!("comments and DOCTYPE tokens are handled before the insertion mode is checked")
+                        }
                         Some(HtmlToken::Eof) | None => {
                             return self.window.clone();
                         }
                     }
-                    // <meta>や<title>などのサポートしていないタグは無視する
+                    // <link>や<base>などのサポートしていないタグは無視する
                     token = self.t.next();
                     continue;
                 }
                 InsertionMode::AfterHead => {
                     match token {
-                        Some(HtmlToken::Char(c)) => {
-                            if c == ' ' || c == '\n' {
-                                self.insert_char(c);
+                        Some(HtmlToken::Text(ref text)) => {
+                            if is_whitespace_text(text) {
+                                self.insert_text(text);
                                 token = self.t.next();
                                 continue;
                             }
@@ -324,7 +874,7 @@ impl HtmlParser {
                             self_closing: _,
                             ref attributes,
                         }) => {
-                            if tag == "body" {
+                            if &**tag == "body" {
                                 self.insert_element(tag, attributes.to_vec());
                                 token = self.t.next();
                                 self.mode = InsertionMode::InBody;
@@ -346,18 +896,114 @@ impl HtmlParser {
                             ref tag,
                             self_closing: _,
                             ref attributes,
-                        }) => match tag.as_str() {
+                        }) => match tag.as_ref() {
                             "p" => {
+                                self.close_p_element_in_button_scope();
                                 self.insert_element(tag, attributes.to_vec());
                                 token = self.t.next();
                                 continue;
                             }
-                            "h1" | "h2" => {
+                            // 見出しの中で開いた見出しは、前の見出しを閉じて兄弟にする
+                            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                                self.close_p_element_in_button_scope();
+                                self.generate_implied_end_tags(None);
+                                let in_heading = self
+                                    .stack_of_open_elements
+                                    .last()
+                                    .and_then(|n| n.borrow().element_kind())
+                                    .is_some_and(|kind| kind.heading_level().is_some());
+                                if in_heading {
+                                    self.error(ParseErrorKind::UnexpectedStartTag(tag.to_string()));
+                                    self.stack_of_open_elements.pop();
+                                }
                                 self.insert_element(tag, attributes.to_vec());
                                 token = self.t.next();
                                 continue;
                             }
-                            "a" => {
+                            // <span>は、<a>と同じく中身をそのまま持つインライン要素
+                            "a" | "span" => {
+                                self.reconstruct_active_formatting_elements();
+                                self.insert_element(tag, attributes.to_vec());
+                                token = self.t.next();
+                                continue;
+                            }
+                            "b" | "i" | "strong" | "em" | "u" => {
+                                self.insert_formatting_element(tag, attributes.to_vec());
+                                token = self.t.next();
+                                continue;
+                            }
+                            "li" => {
+                                self.close_list_item(&[ElementKind::Li]);
+                                self.close_p_element_in_button_scope();
+                                self.insert_element(tag, attributes.to_vec());
+                                token = self.t.next();
+                                continue;
+                            }
+                            // <dt>と<dd>は、開いている<dt>と<dd>のどちらも閉じる
+                            "dt" | "dd" => {
+                                self.close_list_item(&[ElementKind::Dt, ElementKind::Dd]);
+                                self.close_p_element_in_button_scope();
+                                self.insert_element(tag, attributes.to_vec());
+                                token = self.t.next();
+                                continue;
+                            }
+                            // 互換モードでは、<p>の中に表を入れられる
+                            "table" => {
+                                if self.window.borrow().quirks_mode() != QuirksMode::Quirks {
+                                    self.close_p_element_in_button_scope();
+                                }
+                                self.insert_element(tag, attributes.to_vec());
+                                self.mode = InsertionMode::InTable;
+                                token = self.t.next();
+                                continue;
+                            }
+                            // 表の外にある表の部品は無視する
+                            "tbody" | "tr" | "td" | "th" => {
+                                self.error(ParseErrorKind::UnexpectedStartTag(tag.to_string()));
+                                token = self.t.next();
+                                continue;
+                            }
+                            // <iframe>の中身は代替コンテンツなので、<style>や<script>と同じく
+                            // テキストとして扱う
+                            // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
+                            "iframe" => {
+                                self.insert_element(tag, attributes.to_vec());
+                                self.original_insertion_mode = self.mode;
+                                self.mode = InsertionMode::Text;
+                                token = self.t.next();
+                                continue;
+                            }
+                            // 空要素は中身を持たないので、挿入しても現在のノードは変わらない
+                            "img" | "br" | "input" => {
+                                self.reconstruct_active_formatting_elements();
+                                self.insert_element(tag, attributes.to_vec());
+                                token = self.t.next();
+                                continue;
+                            }
+                            // <body>の中の<meta>と<link>も、<head>の中と同じく挿入する
+                            "meta" | "link" => {
+                                self.insert_element(tag, attributes.to_vec());
+                                token = self.t.next();
+                                continue;
+                            }
+                            "hr" => {
+                                self.close_p_element_in_button_scope();
+                                self.insert_element(tag, attributes.to_vec());
+                                token = self.t.next();
+                                continue;
+                            }
+                            // <textarea>の中身は、<title>と同じくRCDATAとして読む
+                            // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
+                            "textarea" => {
+                                self.insert_rcdata_element(tag, attributes.to_vec());
+                                token = self.t.next();
+                                continue;
+                            }
+                            // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
+                            // A start tag whose tag name is one of: "address", "article", "aside", ...
+                            "main" | "section" | "header" | "footer" | "nav" | "article"
+                            | "div" | "ul" | "ol" | "dl" => {
+                                self.close_p_element_in_button_scope();
                                 self.insert_element(tag, attributes.to_vec());
                                 token = self.t.next();
                                 continue;
@@ -367,45 +1013,103 @@ impl HtmlParser {
                             }
                         },
                         Some(HtmlToken::EndTag { ref tag }) => {
-                            match tag.as_str() {
+                            match tag.as_ref() {
                                 "body" => {
                                     self.mode = InsertionMode::AfterBody;
-                                    token = self.t.next();
                                     if !self.contain_in_stack(ElementKind::Body) {
                                         // パースの失敗。トークンを無視する
+                                        self.error(ParseErrorKind::UnexpectedEndTag(
+                                            tag.to_string(),
+                                        ));
+                                        token = self.t.next();
                                         continue;
                                     }
+                                    token = self.t.next();
                                     self.pop_until(ElementKind::Body);
                                     continue;
                                 }
                                 "html" => {
                                     if self.pop_current_node(ElementKind::Body) {
                                         self.mode = InsertionMode::AfterBody;
-                                        assert!(self.pop_current_node(ElementKind::Html));
+                                        self.pop_current_node(ElementKind::Html);
                                     } else {
                                         token = self.t.next();
                                     }
                                     continue;
                                 }
+                                // 対応する<p>がない"</p>"は、空の<p>要素として扱う
+                                // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
                                 "p" => {
-                                    let element_kind = ElementKind::from_str(tag)
-                                        .expect("failed to convert string to ElementKind");
+                                    if !self.has_in_button_scope(ElementKind::P) {
+                                        self.error(ParseErrorKind::UnexpectedEndTag(
+                                            tag.to_string(),
+                                        ));
+                                        self.insert_element(tag, Vec::new());
+                                    }
+                                    self.close_p_element();
                                     token = self.t.next();
-                                    self.pop_until(element_kind);
                                     continue;
                                 }
-                                "h1" | "h2" => {
+                                "li" => {
+                                    if self.has_in_list_item_scope(ElementKind::Li) {
+                                        self.generate_implied_end_tags(Some(ElementKind::Li));
+                                        self.pop_until(ElementKind::Li);
+                                    } else {
+                                        self.error(ParseErrorKind::UnexpectedEndTag(
+                                            tag.to_string(),
+                                        ));
+                                    }
+                                    token = self.t.next();
+                                    continue;
+                                }
+                                "dt" | "dd" => {
                                     let element_kind = ElementKind::from_str(tag)
                                         .expect("failed to convert string to ElementKind");
+                                    if self.has_in_scope(element_kind) {
+                                        self.generate_implied_end_tags(Some(element_kind));
+                                        self.pop_until(element_kind);
+                                    } else {
+                                        self.error(ParseErrorKind::UnexpectedEndTag(
+                                            tag.to_string(),
+                                        ));
+                                    }
+                                    token = self.t.next();
+                                    continue;
+                                }
+                                "table" | "tbody" | "tr" | "td" | "th" => {
+                                    self.error(ParseErrorKind::UnexpectedEndTag(tag.to_string()));
+                                    token = self.t.next();
+                                    continue;
+                                }
+                                // "</br>"は、属性のない<br>として扱う
+                                "br" => {
+                                    self.error(ParseErrorKind::UnexpectedEndTag(tag.to_string()));
+                                    self.reconstruct_active_formatting_elements();
+                                    self.insert_element(tag, Vec::new());
+                                    token = self.t.next();
+                                    continue;
+                                }
+                                "b" | "i" | "strong" | "em" | "u" => {
+                                    self.run_adoption_agency(tag);
                                     token = self.t.next();
-                                    self.pop_until(element_kind);
                                     continue;
                                 }
-                                "a" => {
+                                "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "a" | "main"
+                                | "section" | "header" | "footer" | "nav" | "article" | "div"
+                                | "span" | "ul" | "ol" | "dl" => {
                                     let element_kind = ElementKind::from_str(tag)
                                         .expect("failed to convert string to ElementKind");
+                                    // 対応する開始タグがない場合、パースの失敗。トークンを無視する。
+                                    // セルの中からは、表の外の要素を閉じない
+                                    if self.has_in_scope(element_kind) {
+                                        self.generate_implied_end_tags(Some(element_kind));
+                                        self.pop_until(element_kind);
+                                    } else {
+                                        self.error(ParseErrorKind::UnexpectedEndTag(
+                                            tag.to_string(),
+                                        ));
+                                    }
                                     token = self.t.next();
-                                    self.pop_until(element_kind);
                                     continue;
                                 }
                                 _ => {
@@ -413,15 +1117,173 @@ impl HtmlParser {
                                 }
                             }
                         }
+                        Some(HtmlToken::Comment(_)) | Some(HtmlToken::Doctype { .. }) => {
+                            unreachable!("comments and DOCTYPE tokens are handled before the insertion mode is checked")
+                        }
+                        Some(HtmlToken::Eof) | None => {
+                            return self.window.clone();
+                        }
+                        Some(HtmlToken::Text(ref text)) => {
+                            self.reconstruct_active_formatting_elements();
+                            self.insert_text(text);
+                            token = self.t.next();
+                            continue;
+                        }
+                    }
+                }
+                InsertionMode::InTable => match token {
+                    Some(HtmlToken::Text(ref text)) => {
+                        if !is_whitespace_text(text) {
+                            self.foster_parent_text(text);
+                        }
+                        token = self.t.next();
+                        continue;
+                    }
+                    Some(HtmlToken::StartTag {
+                        ref tag,
+                        self_closing: _,
+                        ref attributes,
+                    }) => match tag.as_ref() {
+                        "tbody" => {
+                            self.clear_stack_back_to(&[ElementKind::Table]);
+                            self.insert_element(tag, attributes.to_vec());
+                            token = self.t.next();
+                            continue;
+                        }
+                        "tr" | "td" | "th" => {
+                            self.clear_stack_back_to(&[ElementKind::Tbody, ElementKind::Table]);
+                            // <tbody>を省略した行は、暗黙の<tbody>に入れる
+                            if self.is_current_node(ElementKind::Table) {
+                                self.insert_element("tbody", Vec::new());
+                            }
+                            if &**tag == "tr" {
+                                self.insert_element(tag, attributes.to_vec());
+                                self.mode = InsertionMode::InRow;
+                                token = self.t.next();
+                                continue;
+                            }
+                            // <tr>を省略したセルは、暗黙の<tr>に入れてInRowで処理し直す
+                            self.insert_element("tr", Vec::new());
+                            self.mode = InsertionMode::InRow;
+                            continue;
+                        }
+                        // 閉じていない表の中で開いた表は、前の表を閉じてから処理し直す
+                        "table" => {
+                            self.error(ParseErrorKind::UnexpectedStartTag(tag.to_string()));
+                            if self.has_in_table_scope(ElementKind::Table) {
+                                self.pop_until(ElementKind::Table);
+                                self.reset_insertion_mode();
+                            } else {
+                                token = self.t.next();
+                            }
+                            continue;
+                        }
+                        _ => {
+                            token = self.t.next();
+                            continue;
+                        }
+                    },
+                    Some(HtmlToken::EndTag { ref tag }) => {
+                        match tag.as_ref() {
+                            "table" if self.has_in_table_scope(ElementKind::Table) => {
+                                self.pop_until(ElementKind::Table);
+                                self.reset_insertion_mode();
+                            }
+                            "tbody" if self.has_in_table_scope(ElementKind::Tbody) => {
+                                self.clear_stack_back_to(&[ElementKind::Tbody]);
+                                self.stack_of_open_elements.pop();
+                            }
+                            _ => self.error(ParseErrorKind::UnexpectedEndTag(tag.to_string())),
+                        }
+                        token = self.t.next();
+                        continue;
+                    }
+                    Some(HtmlToken::Eof) | None => {
+                        return self.window.clone();
+                    }
+                    _ => {
+                        token = self.t.next();
+                        continue;
+                    }
+                },
+                InsertionMode::InRow => {
+                    match token {
+                        Some(HtmlToken::StartTag {
+                            ref tag,
+                            self_closing: _,
+                            ref attributes,
+                        }) => {
+                            if &**tag == "td" || &**tag == "th" {
+                                self.clear_stack_back_to(&[ElementKind::Tr]);
+                                self.insert_element(tag, attributes.to_vec());
+                                self.active_formatting_elements.push(None);
+                                self.mode = InsertionMode::InCell;
+                                token = self.t.next();
+                                continue;
+                            }
+                        }
+                        Some(HtmlToken::EndTag { ref tag }) => {
+                            if &**tag == "tr" {
+                                if self.has_in_table_scope(ElementKind::Tr) {
+                                    self.close_row();
+                                } else {
+                                    self.error(ParseErrorKind::UnexpectedEndTag(tag.to_string()));
+                                }
+                                token = self.t.next();
+                                continue;
+                            }
+                            if &**tag != "table" && &**tag != "tbody" {
+                                self.error(ParseErrorKind::UnexpectedEndTag(tag.to_string()));
+                                token = self.t.next();
+                                continue;
+                            }
+                        }
                         Some(HtmlToken::Eof) | None => {
                             return self.window.clone();
                         }
-                        Some(HtmlToken::Char(c)) => {
-                            self.insert_char(c);
+                        _ => {
                             token = self.t.next();
                             continue;
                         }
                     }
+
+                    // 行の中で始まった行や、閉じられた表は、今の行を閉じてからInTableで処理し直す
+                    if self.has_in_table_scope(ElementKind::Tr) {
+                        self.close_row();
+                    } else {
+                        token = self.t.next();
+                    }
+                }
+                InsertionMode::InCell => {
+                    match token {
+                        Some(HtmlToken::EndTag { ref tag }) => {
+                            let element_kind = ElementKind::from_str(tag)
+                                .expect("failed to convert string to ElementKind");
+                            if !self.has_in_table_scope(element_kind)
+                                || element_kind == ElementKind::Body
+                                || element_kind == ElementKind::Html
+                            {
+                                self.error(ParseErrorKind::UnexpectedEndTag(tag.to_string()));
+                                token = self.t.next();
+                                continue;
+                            }
+                            if element_kind == ElementKind::Td || element_kind == ElementKind::Th {
+                                self.generate_implied_end_tags(None);
+                                self.pop_until(element_kind);
+                                self.clear_active_formatting_elements_to_last_marker();
+                                self.mode = InsertionMode::InRow;
+                                token = self.t.next();
+                                continue;
+                            }
+                        }
+                        Some(HtmlToken::Eof) | None => {
+                            return self.window.clone();
+                        }
+                        _ => {}
+                    }
+
+                    // セルの中で始まったセルや行、閉じられた行や表は、今のセルを閉じてからInRowで処理し直す
+                    self.close_cell();
                 }
                 InsertionMode::Text => {
                     match token {
@@ -429,21 +1291,39 @@ impl HtmlParser {
                             return self.window.clone();
                         }
                         Some(HtmlToken::EndTag { ref tag }) => {
-                            if tag == "style" {
+                            if &**tag == "style" {
                                 self.pop_until(ElementKind::Style);
                                 self.mode = self.original_insertion_mode;
                                 token = self.t.next();
                                 continue;
                             }
-                            if tag == "script" {
+                            if &**tag == "script" {
                                 self.pop_until(ElementKind::Script);
                                 self.mode = self.original_insertion_mode;
                                 token = self.t.next();
                                 continue;
                             }
+                            if &**tag == "iframe" {
+                                self.pop_until(ElementKind::Iframe);
+                                self.mode = self.original_insertion_mode;
+                                token = self.t.next();
+                                continue;
+                            }
+                            if &**tag == "title" {
+                                self.pop_until(ElementKind::Title);
+                                self.mode = self.original_insertion_mode;
+                                token = self.t.next();
+                                continue;
+                            }
+                            if &**tag == "textarea" {
+                                self.pop_until(ElementKind::Textarea);
+                                self.mode = self.original_insertion_mode;
+                                token = self.t.next();
+                                continue;
+                            }
                         }
-                        Some(HtmlToken::Char(c)) => {
-                            self.insert_char(c);
+                        Some(HtmlToken::Text(ref text)) => {
+                            self.insert_text(text);
                             token = self.t.next();
                             continue;
                         }
@@ -454,12 +1334,12 @@ impl HtmlParser {
                 }
                 InsertionMode::AfterBody => {
                     match token {
-                        Some(HtmlToken::Char(_c)) => {
+                        Some(HtmlToken::Text(_)) => {
                             token = self.t.next();
                             continue;
                         }
                         Some(HtmlToken::EndTag { ref tag }) => {
-                            if tag == "html" {
+                            if &**tag == "html" {
                                 self.mode = InsertionMode::AfterAfterBody;
                                 token = self.t.next();
                                 continue;
@@ -475,7 +1355,7 @@ impl HtmlParser {
                 }
                 InsertionMode::AfterAfterBody => {
                     match token {
-                        Some(HtmlToken::Char(_c)) => {
+                        Some(HtmlToken::Text(_)) => {
                             token = self.t.next();
                             continue;
                         }
@@ -495,10 +1375,178 @@ impl HtmlParser {
     }
 }
 
+/// 互換モードにする、公開識別子の前方一致のパターン。大文字と小文字は区別しない
+/// https://html.spec.whatwg.org/multipage/parsing.html#the-initial-insertion-mode
+const QUIRKY_PUBLIC_ID_PREFIXES: &[&str] = &[
+    "+//silmaril//dtd html pro v0r11 19970101//",
+    "-//as//dtd html 3.0 aswedit + extensions//",
+    "-//advasoft ltd//dtd html 3.0 aswedit + extensions//",
+    "-//ietf//dtd html 2.0 level 1//",
+    "-//ietf//dtd html 2.0 level 2//",
+    "-//ietf//dtd html 2.0 strict level 1//",
+    "-//ietf//dtd html 2.0 strict level 2//",
+    "-//ietf//dtd html 2.0 strict//",
+    "-//ietf//dtd html 2.0//",
+    "-//ietf//dtd html 2.1e//",
+    "-//ietf//dtd html 3.0//",
+    "-//ietf//dtd html 3.2 final//",
+    "-//ietf//dtd html 3.2//",
+    "-//ietf//dtd html 3//",
+    "-//ietf//dtd html level 0//",
+    "-//ietf//dtd html level 1//",
+    "-//ietf//dtd html level 2//",
+    "-//ietf//dtd html level 3//",
+    "-//ietf//dtd html strict level 0//",
+    "-//ietf//dtd html strict level 1//",
+    "-//ietf//dtd html strict level 2//",
+    "-//ietf//dtd html strict level 3//",
+    "-//ietf//dtd html strict//",
+    "-//ietf//dtd html//",
+    "-//metrius//dtd metrius presentational//",
+    "-//microsoft//dtd internet explorer 2.0 html strict//",
+    "-//microsoft//dtd internet explorer 2.0 html//",
+    "-//microsoft//dtd internet explorer 2.0 tables//",
+    "-//microsoft//dtd internet explorer 3.0 html strict//",
+    "-//microsoft//dtd internet explorer 3.0 html//",
+    "-//microsoft//dtd internet explorer 3.0 tables//",
+    "-//netscape comm. corp.//dtd html//",
+    "-//netscape comm. corp.//dtd strict html//",
+    "-//o'reilly and associates//dtd html 2.0//",
+    "-//o'reilly and associates//dtd html extended 1.0//",
+    "-//o'reilly and associates//dtd html extended relaxed 1.0//",
+    "-//sq//dtd html 2.0 hotmetal + extensions//",
+    "-//softquad software//dtd hotmetal pro 6.0::19990601::extensions to html 4.0//",
+    "-//softquad//dtd hotmetal pro 4.0::19971010::extensions to html 4.0//",
+    "-//spyglass//dtd html 2.0 extended//",
+    "-//sun microsystems corp.//dtd hotjava html//",
+    "-//sun microsystems corp.//dtd hotjava strict html//",
+    "-//w3c//dtd html 3 1995-03-24//",
+    "-//w3c//dtd html 3.2 draft//",
+    "-//w3c//dtd html 3.2 final//",
+    "-//w3c//dtd html 3.2//",
+    "-//w3c//dtd html 3.2s draft//",
+    "-//w3c//dtd html 4.0 frameset//",
+    "-//w3c//dtd html 4.0 transitional//",
+    "-//w3c//dtd html experimental 19960712//",
+    "-//w3c//dtd html experimental 970421//",
+    "-//w3c//dtd w3 html//",
+    "-//w3o//dtd w3 html 3.0//",
+    "-//webtechs//dtd mozilla html 2.0//",
+    "-//webtechs//dtd mozilla html//",
+];
+
+/// DOCTYPEトークンから、ドキュメントの互換モードを決める
+/// https://html.spec.whatwg.org/multipage/parsing.html#the-initial-insertion-mode
+fn doctype_quirks_mode(
+    name: Option<&str>,
+    public_id: Option<&str>,
+    system_id: Option<&str>,
+    force_quirks: bool,
+) -> QuirksMode {
+    let public_id = public_id.map(|id| id.to_ascii_lowercase());
+    let system_id = system_id.map(|id| id.to_ascii_lowercase());
+    let public_starts_with =
+        |prefix: &str| matches!(&public_id, Some(id) if id.starts_with(prefix));
+    // HTML 4.01のFramesetとTransitionalは、システム識別子がない場合にだけ互換モードになる
+    let html4_transitional = public_starts_with("-//w3c//dtd html 4.01 frameset//")
+        || public_starts_with("-//w3c//dtd html 4.01 transitional//");
+
+    if force_quirks
+        || name != Some("html")
+        || matches!(
+            public_id.as_deref(),
+            Some("-//w3o//dtd w3 html strict 3.0//en//")
+                | Some("-/w3c/dtd html 4.0 transitional/en")
+                | Some("html")
+        )
+        || system_id.as_deref()
+            == Some("http://www.ibm.com/data/dtd/v11/ibmxhtml1-transitional.dtd")
+        || QUIRKY_PUBLIC_ID_PREFIXES
+            .iter()
+            .any(|prefix| public_starts_with(prefix))
+        || (system_id.is_none() && html4_transitional)
+    {
+        return QuirksMode::Quirks;
+    }
+
+    if public_starts_with("-//w3c//dtd xhtml 1.0 frameset//")
+        || public_starts_with("-//w3c//dtd xhtml 1.0 transitional//")
+        || (system_id.is_some() && html4_transitional)
+    {
+        return QuirksMode::LimitedQuirks;
+    }
+
+    QuirksMode::NoQuirks
+}
+
+/// 通常のスコープの境界になる要素のうち、このブラウザが扱うもの
+/// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-scope
+const SCOPE_BOUNDARIES: [ElementKind; 4] = [
+    ElementKind::Html,
+    ElementKind::Table,
+    ElementKind::Td,
+    ElementKind::Th,
+];
+
+/// 中身を持たず、終了タグも持たない空要素。スタックに積まないので、閉じる必要がない
+/// https://html.spec.whatwg.org/multipage/syntax.html#void-elements
+const VOID_ELEMENTS: [&str; 13] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// 空白文字だけの文字列かどうか。トークナイザは空白文字だけの範囲を別のトークンにするので、
+/// 空白文字から始まるトークンは全て空白文字になる
+fn is_whitespace_text(text: &str) -> bool {
+    text.chars().all(is_whitespace)
+}
+
+/// 終了タグを省略でき、親の要素や次の要素によって閉じられる要素。
+/// 仕様ではoptionやrubyの要素なども含むが、このブラウザが扱う要素のうちでは<p>、<li>、<dt>、<dd>だけ
+/// https://html.spec.whatwg.org/multipage/parsing.html#generate-implied-end-tags
+fn has_implied_end_tag(element_kind: ElementKind) -> bool {
+    matches!(
+        element_kind,
+        ElementKind::P | ElementKind::Li | ElementKind::Dt | ElementKind::Dd
+    )
+}
+
+/// 書式要素のうち、このブラウザが扱うもの。仕様では<a>なども含む
+/// https://html.spec.whatwg.org/multipage/parsing.html#formatting
+fn is_formatting_element(element_kind: ElementKind) -> bool {
+    matches!(
+        element_kind,
+        ElementKind::B | ElementKind::I | ElementKind::Strong | ElementKind::Em | ElementKind::U
+    )
+}
+
+/// 書式要素の終了タグで閉じずに、書式要素の中から外に出す要素。
+/// このブラウザでは、ブロック要素と<html>を特別な要素として扱う
+/// https://html.spec.whatwg.org/multipage/parsing.html#special
+fn is_special(node: &Rc<RefCell<Node>>) -> bool {
+    match node.borrow().get_element() {
+        Some(element) => element.is_block_element() || element.kind() == ElementKind::Html,
+        None => false,
+    }
+}
+
+/// 親があれば、親の子ノードから取り除く
+fn detach(node: &Rc<RefCell<Node>>) {
+    let parent = node.borrow().parent().upgrade();
+    if let Some(parent) = parent {
+        remove_child(&parent, node);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::alloc::string::ToString;
+    use crate::renderer::dom::api::get_target_element_node;
+    use crate::renderer::dom::api::DomTreeIterator;
+    use crate::renderer::html::token::SourcePosition;
+    use crate::utils::convert_dom_to_html5lib_string;
+    use crate::utils::convert_dom_to_string;
     use alloc::vec;
 
     #[test]
@@ -672,4 +1720,507 @@ mod tests {
             text
         );
     }
+
+    #[test]
+    fn test_sectioning_elements() {
+        let html = "<html><head></head><body><header><nav>menu</nav></header><main><article><section><p>text</p></section></article></main><footer>foot</footer></body></html>".to_string();
+        let t = HtmlTokenizer::new(html);
+        let window = HtmlParser::new(t).construct_tree();
+        let document = window.borrow().document();
+
+        let body = document
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of document")
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of document")
+            .borrow()
+            .next_sibling()
+            .expect("failed to get a next sibling of head");
+
+        let header = body
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of body");
+        assert_eq!(Some(ElementKind::Header), header.borrow().element_kind());
+        let nav = header
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of header");
+        assert_eq!(Some(ElementKind::Nav), nav.borrow().element_kind());
+
+        let main = header
+            .borrow()
+            .next_sibling()
+            .expect("failed to get a next sibling of header");
+        assert_eq!(Some(ElementKind::Main), main.borrow().element_kind());
+        let article = main
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of main");
+        assert_eq!(Some(ElementKind::Article), article.borrow().element_kind());
+        let section = article
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of article");
+        assert_eq!(Some(ElementKind::Section), section.borrow().element_kind());
+        let text = section
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of section")
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of p");
+        assert_eq!(
+            Rc::new(RefCell::new(Node::new(NodeKind::Text("text".to_string())))),
+            text
+        );
+
+        let footer = main
+            .borrow()
+            .next_sibling()
+            .expect("failed to get a next sibling of main");
+        assert_eq!(Some(ElementKind::Footer), footer.borrow().element_kind());
+    }
+
+    #[test]
+    fn test_div_and_span() {
+        let html = "<!doctype html><html><head></head><body><div><span>a</span>b</div><span>c</span></body></html>"
+            .to_string();
+        let t = HtmlTokenizer::new(html);
+        let mut parser = HtmlParser::new(t);
+        let window = parser.construct_tree();
+        let body = get_target_element_node(Some(window.borrow().document()), ElementKind::Body)
+            .expect("failed to get body");
+
+        let div = body
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of body");
+        assert_eq!(Some(ElementKind::Div), div.borrow().element_kind());
+        let span = div
+            .borrow()
+            .first_child()
+            .expect("failed to get a first child of div");
+        assert_eq!(Some(ElementKind::Span), span.borrow().element_kind());
+        // </span>で<span>を閉じるので、後ろの文字は<div>の子になる
+        assert_eq!(
+            Some(NodeKind::Text("b".to_string())),
+            span.borrow().next_sibling().map(|n| n.borrow().kind())
+        );
+
+        // </div>で<div>を閉じるので、後ろの<span>は<body>の子になる
+        let span = div
+            .borrow()
+            .next_sibling()
+            .expect("failed to get a next sibling of div");
+        assert_eq!(Some(ElementKind::Span), span.borrow().element_kind());
+        assert!(parser.errors().is_empty());
+    }
+
+    fn html5lib_tree(html: &str) -> String {
+        let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+        let document = window.borrow().document();
+        convert_dom_to_html5lib_string(&document)
+    }
+
+    #[test]
+    fn test_implied_end_tags() {
+        // html5lib-testsのtree-constructionのケースにならった、<p>と見出しの省略された終了タグを補うもの
+        let cases = [
+            (
+                "<p>One<p>Two",
+                "| <html>\n|   <head>\n|   <body>\n|     <p>\n|       \"One\"\n|     <p>\n|       \"Two\"",
+            ),
+            (
+                "<p>One<div>Two</div>Three",
+                "| <html>\n|   <head>\n|   <body>\n|     <p>\n|       \"One\"\n|     <div>\n|       \"Two\"\n|     \"Three\"",
+            ),
+            (
+                "<div><p>a</div>b",
+                "| <html>\n|   <head>\n|   <body>\n|     <div>\n|       <p>\n|         \"a\"\n|     \"b\"",
+            ),
+            (
+                "<p>a</p></p>",
+                "| <html>\n|   <head>\n|   <body>\n|     <p>\n|       \"a\"\n|     <p>",
+            ),
+            (
+                "<p><h1>a<h2>b</h2>",
+                "| <html>\n|   <head>\n|   <body>\n|     <p>\n|     <h1>\n|       \"a\"\n|     <h2>\n|       \"b\"",
+            ),
+            (
+                "<p><span>a<p>b",
+                "| <html>\n|   <head>\n|   <body>\n|     <p>\n|       <span>\n|         \"a\"\n|     <p>\n|       \"b\"",
+            ),
+        ];
+        for (html, expected) in cases {
+            assert_eq!(expected, html5lib_tree(html), "{}", html);
+        }
+    }
+
+    #[test]
+    fn test_void_elements() {
+        let cases = [
+            // 空要素は子を持たず、後ろのノードは兄弟になる
+            (
+                "<p>a<br>b<img src=x>c<input>d",
+                "| <html>\n|   <head>\n|   <body>\n|     <p>\n|       \"a\"\n|       <br>\n|       \"b\"\n|       <img>\n|         src=\"x\"\n|       \"c\"\n|       <input>\n|       \"d\"",
+            ),
+            // <hr>は開いている<p>を閉じる
+            (
+                "<p>a<hr>b",
+                "| <html>\n|   <head>\n|   <body>\n|     <p>\n|       \"a\"\n|     <hr>\n|     \"b\"",
+            ),
+            (
+                "<br/><meta name=a><link rel=stylesheet href=b>c",
+                "| <html>\n|   <head>\n|   <body>\n|     <br>\n|     <meta>\n|       name=\"a\"\n|     <link>\n|       href=\"b\"\n|       rel=\"stylesheet\"\n|     \"c\"",
+            ),
+        ];
+        for (html, expected) in cases {
+            assert_eq!(expected, html5lib_tree(html), "{}", html);
+        }
+
+        // "</br>"は<br>として扱う
+        let html = "<!doctype html><body>a</br>b".to_string();
+        let mut parser = HtmlParser::new(HtmlTokenizer::new(html));
+        let window = parser.construct_tree();
+        assert_eq!(
+            "| <html>\n|   <head>\n|   <body>\n|     \"a\"\n|     <br>\n|     \"b\"",
+            convert_dom_to_html5lib_string(&window.borrow().document())
+        );
+        let errors: Vec<ParseErrorKind> = parser.errors().iter().map(|e| e.kind()).collect();
+        assert_eq!(
+            vec![ParseErrorKind::UnexpectedEndTag("br".to_string())],
+            errors
+        );
+    }
+
+    #[test]
+    fn test_formatting_elements() {
+        let cases = [
+            (
+                "<b>a<i>b</i></b>c",
+                "| <html>\n|   <head>\n|   <body>\n|     <b>\n|       \"a\"\n|       <i>\n|         \"b\"\n|     \"c\"",
+            ),
+            // 先に閉じた<b>の中の<i>は、続く文字のために開き直す
+            (
+                "<b><i>a</b>b</i>c",
+                "| <html>\n|   <head>\n|   <body>\n|     <b>\n|       <i>\n|         \"a\"\n|     <i>\n|       \"b\"\n|     \"c\"",
+            ),
+            // 閉じた<p>の中の<strong>は、次の<p>の中で開き直す
+            (
+                "<p><strong>a</p><p>b</strong>c",
+                "| <html>\n|   <head>\n|   <body>\n|     <p>\n|       <strong>\n|         \"a\"\n|     <p>\n|       <strong>\n|         \"b\"\n|       \"c\"",
+            ),
+            // <b>の中で開いた<p>は<b>の外に出し、<p>の中身は<b>の複製に入れる
+            (
+                "<b>1<p>2</b>3</p>",
+                "| <html>\n|   <head>\n|   <body>\n|     <b>\n|       \"1\"\n|     <p>\n|       <b>\n|         \"2\"\n|       \"3\"",
+            ),
+            // 間にある<u>は複製して<div>の親にし、</u>では<div>を<u>の外に出す
+            (
+                "<em><u>1<div>2</em>3</u>4</div>",
+                "| <html>\n|   <head>\n|   <body>\n|     <em>\n|       <u>\n|         \"1\"\n|     <u>\n|     <div>\n|       <u>\n|         <em>\n|           \"2\"\n|         \"3\"\n|       \"4\"",
+            ),
+            // セルの中の書式要素は、セルの外で開き直さない
+            (
+                "<table><tr><td><b>a</td></tr></table>b",
+                "| <html>\n|   <head>\n|   <body>\n|     <table>\n|       <tbody>\n|         <tr>\n|           <td>\n|             <b>\n|               \"a\"\n|     \"b\"",
+            ),
+        ];
+        for (html, expected) in cases {
+            assert_eq!(expected, html5lib_tree(html), "{}", html);
+        }
+
+        // 開いていない書式要素の終了タグは無視する
+        let html = "<!doctype html><body>a</i>b".to_string();
+        let mut parser = HtmlParser::new(HtmlTokenizer::new(html));
+        let window = parser.construct_tree();
+        assert_eq!(
+            "| <html>\n|   <head>\n|   <body>\n|     \"ab\"",
+            convert_dom_to_html5lib_string(&window.borrow().document())
+        );
+        let errors: Vec<ParseErrorKind> = parser.errors().iter().map(|e| e.kind()).collect();
+        assert_eq!(
+            vec![ParseErrorKind::UnexpectedEndTag("i".to_string())],
+            errors
+        );
+    }
+
+    #[test]
+    fn test_list() {
+        let cases = [
+            (
+                "<ul><li>a<li>b</ul>",
+                "| <html>\n|   <head>\n|   <body>\n|     <ul>\n|       <li>\n|         \"a\"\n|       <li>\n|         \"b\"",
+            ),
+            // 入れ子のリストの<li>は、外のリストの<li>を閉じない
+            (
+                "<ol><li>a<ul><li>b</ul><li>c</ol>",
+                "| <html>\n|   <head>\n|   <body>\n|     <ol>\n|       <li>\n|         \"a\"\n|         <ul>\n|           <li>\n|             \"b\"\n|       <li>\n|         \"c\"",
+            ),
+            (
+                "<li><p>a<li>b",
+                "| <html>\n|   <head>\n|   <body>\n|     <li>\n|       <p>\n|         \"a\"\n|     <li>\n|       \"b\"",
+            ),
+            (
+                "<p>a<ul><li>b</li></ul>",
+                "| <html>\n|   <head>\n|   <body>\n|     <p>\n|       \"a\"\n|     <ul>\n|       <li>\n|         \"b\"",
+            ),
+        ];
+        for (html, expected) in cases {
+            assert_eq!(expected, html5lib_tree(html), "{}", html);
+        }
+
+        // <dt>と<dd>は、開いている<dt>と<dd>を閉じる。<p>も一緒に閉じる
+        let html = "<dl><dt>a<dd><p>b<dt>c</dt><dd>d</dl>";
+        assert_eq!(
+            "| <html>\n|   <head>\n|   <body>\n|     <dl>\n|       <dt>\n|         \"a\"\n|       <dd>\n|         <p>\n|           \"b\"\n|       <dt>\n|         \"c\"\n|       <dd>\n|         \"d\"",
+            html5lib_tree(html)
+        );
+
+        // 外のリストの<li>は、入れ子のリストの中から閉じられない
+        let html = "<!doctype html><ul><li><ul></li></ul></ul>".to_string();
+        let mut parser = HtmlParser::new(HtmlTokenizer::new(html));
+        parser.construct_tree();
+        let errors: Vec<ParseErrorKind> = parser.errors().iter().map(|e| e.kind()).collect();
+        assert_eq!(
+            vec![ParseErrorKind::UnexpectedEndTag("li".to_string())],
+            errors
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        let cases = [
+            // 省略された<tbody>と<tr>、セルと行の終了タグを補う
+            (
+                "<table><td>a<td>b<tr><th>c</table>d",
+                "| <html>\n|   <head>\n|   <body>\n|     <table>\n|       <tbody>\n|         <tr>\n|           <td>\n|             \"a\"\n|           <td>\n|             \"b\"\n|         <tr>\n|           <th>\n|             \"c\"\n|     \"d\"",
+            ),
+            // セルの外の文字は、表の前に移す
+            (
+                "<table>x<tr>y<td>z</table>",
+                "| <html>\n|   <head>\n|   <body>\n|     \"xy\"\n|     <table>\n|       <tbody>\n|         <tr>\n|           <td>\n|             \"z\"",
+            ),
+            // セルの中の表は入れ子になり、内側の表を閉じるとセルの中に戻る
+            (
+                "<table><tr><td><table><td>in</table>out</table>",
+                "| <html>\n|   <head>\n|   <body>\n|     <table>\n|       <tbody>\n|         <tr>\n|           <td>\n|             <table>\n|               <tbody>\n|                 <tr>\n|                   <td>\n|                     \"in\"\n|             \"out\"",
+            ),
+            // セルを閉じると、セルの中の<p>も閉じる。表の外の<div>は閉じない
+            (
+                "<div><table><tr><td><p>a</div>b</td></table>",
+                "| <html>\n|   <head>\n|   <body>\n|     <div>\n|       <table>\n|         <tbody>\n|           <tr>\n|             <td>\n|               <p>\n|                 \"ab\"",
+            ),
+            // 表の外の表の部品は無視する
+            (
+                "<td>a</td>",
+                "| <html>\n|   <head>\n|   <body>\n|     \"a\"",
+            ),
+        ];
+        for (html, expected) in cases {
+            assert_eq!(expected, html5lib_tree(html), "{}", html);
+        }
+
+        let html = "<!doctype html><table>ab<tr><td>c</td></tr></table>".to_string();
+        let mut parser = HtmlParser::new(HtmlTokenizer::new(html));
+        parser.construct_tree();
+        let errors: Vec<ParseErrorKind> = parser.errors().iter().map(|e| e.kind()).collect();
+        assert_eq!(vec![ParseErrorKind::UnexpectedCharacterInTable], errors);
+    }
+
+    #[test]
+    fn test_comment() {
+        let html = "<!-- before --><html><head></head><body><p>a<!-- in text -->b</p></body></html><!-- after -->".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let document = window.borrow().document();
+        // テキストの途中のコメントは、前後のテキストを別のテキストノードに分ける
+        let expected = r#"
+Document
+  Comment(" before ")
+  Element(Element { kind: Html, attributes: [] })
+    Element(Element { kind: Head, attributes: [] })
+    Element(Element { kind: Body, attributes: [] })
+      Element(Element { kind: P, attributes: [] })
+        Text("a")
+        Comment(" in text ")
+        Text("b")
+  Comment(" after ")
+"#;
+        assert_eq!(expected, convert_dom_to_string(&Some(document)));
+    }
+
+    #[test]
+    fn test_quirks_mode() {
+        let cases = [
+            ("<!DOCTYPE html><html></html>", QuirksMode::NoQuirks),
+            ("\n<!doctype html>\n<html></html>", QuirksMode::NoQuirks),
+            ("<html></html>", QuirksMode::Quirks),
+            ("<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 3.2 Final//EN\"><html></html>", QuirksMode::Quirks),
+            (
+                "<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\"><html></html>",
+                QuirksMode::Quirks,
+            ),
+            (
+                "<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\" \"http://www.w3.org/TR/html4/loose.dtd\"><html></html>",
+                QuirksMode::LimitedQuirks,
+            ),
+            (
+                "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Strict//EN\" \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd\"><html></html>",
+                QuirksMode::NoQuirks,
+            ),
+            ("<!DOCTYPE svg><html></html>", QuirksMode::Quirks),
+        ];
+        for (html, expected) in cases {
+            let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+            assert_eq!(expected, window.borrow().quirks_mode(), "{}", html);
+        }
+
+        // DOCTYPEはDOMツリーに追加せず、2つ目以降のDOCTYPEは無視する
+        let html = "<!DOCTYPE html><html><head></head><body><!DOCTYPE html><p>a</p></body></html>";
+        let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+        let expected = r#"
+Document
+  Element(Element { kind: Html, attributes: [] })
+    Element(Element { kind: Head, attributes: [] })
+    Element(Element { kind: Body, attributes: [] })
+      Element(Element { kind: P, attributes: [] })
+        Text("a")
+"#;
+        assert_eq!(
+            expected,
+            convert_dom_to_string(&Some(window.borrow().document()))
+        );
+    }
+
+    #[test]
+    fn test_rcdata_elements() {
+        let html = "<html><head><title>a <b> &amp; b</title></head><body><textarea><p>x</p></textarea><p>y</p></body></html>";
+        let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+        // <title>と<textarea>の中のタグはテキストになり、終了タグの後は普通のマークアップとして読む
+        let expected = r#"
+Document
+  Element(Element { kind: Html, attributes: [] })
+    Element(Element { kind: Head, attributes: [] })
+      Element(Element { kind: Title, attributes: [] })
+        Text("a <b> & b")
+    Element(Element { kind: Body, attributes: [] })
+      Element(Element { kind: Textarea, attributes: [] })
+        Text("<p>x</p>")
+      Element(Element { kind: P, attributes: [] })
+        Text("y")
+"#;
+        assert_eq!(
+            expected,
+            convert_dom_to_string(&Some(window.borrow().document()))
+        );
+    }
+
+    #[test]
+    fn test_meta() {
+        let html = "<html><head><meta charset=utf-8><meta name=viewport content=\"width=device-width\"><title>t</title></head><body></body></html>";
+        let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+        // <meta>は子を持たず、後ろの<title>は<meta>の兄弟になる
+        let document = window.borrow().document();
+        let head = document
+            .borrow()
+            .first_child()
+            .and_then(|html| html.borrow().first_child())
+            .expect("head should exist");
+        let kinds: Vec<Option<ElementKind>> = DomTreeIterator::new(head.borrow().first_child())
+            .map(|n| n.borrow().element_kind())
+            .collect();
+        assert_eq!(
+            vec![
+                Some(ElementKind::Meta),
+                Some(ElementKind::Meta),
+                Some(ElementKind::Title),
+                None
+            ],
+            kinds
+        );
+    }
+
+    #[test]
+    fn test_unterminated_tag() {
+        // 最後のタグが閉じていない場合も、その前のテキストは残す
+        let html = "<html><body><p>hello</p>world<a href=".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let expected = r#"
+Document
+  Element(Element { kind: Html, attributes: [] })
+    Element(Element { kind: Head, attributes: [] })
+    Element(Element { kind: Body, attributes: [] })
+      Element(Element { kind: P, attributes: [] })
+        Text("hello")
+      Text("world")
+"#;
+        assert_eq!(
+            expected,
+            convert_dom_to_string(&Some(window.borrow().document()))
+        );
+    }
+
+    #[test]
+    fn test_source_position() {
+        let html = "<html>\n<body>\n  <p>hello</p>\n</body>\n</html>";
+        let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+        let document = window.borrow().document();
+        let html = document.borrow().first_child().expect("html should exist");
+        // <head>は省略されているので、<body>の開始タグの位置で補う
+        let head = html.borrow().first_child().expect("head should exist");
+        assert_eq!(
+            Some(SourcePosition::new(2, 1)),
+            head.borrow().source_position()
+        );
+        let body = head.borrow().next_sibling().expect("body should exist");
+        assert_eq!(
+            Some(SourcePosition::new(2, 1)),
+            body.borrow().source_position()
+        );
+        let p = body.borrow().first_child().expect("p should exist");
+        assert_eq!(
+            Some(SourcePosition::new(3, 3)),
+            p.borrow().source_position()
+        );
+        let text = p.borrow().first_child().expect("text should exist");
+        assert_eq!(
+            Some(SourcePosition::new(3, 6)),
+            text.borrow().source_position()
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let html = "<html><body></p><p>a</p></a></body></html><!DOCTYPE html>";
+        let mut parser = HtmlParser::new(HtmlTokenizer::new(html.to_string()));
+        let window = parser.construct_tree();
+        // 対応する開始タグがない終了タグは、無視して読み続ける。ただし"</p>"は空の<p>要素になる
+        let expected = r#"
+Document
+  Element(Element { kind: Html, attributes: [] })
+    Element(Element { kind: Head, attributes: [] })
+    Element(Element { kind: Body, attributes: [] })
+      Element(Element { kind: P, attributes: [] })
+      Element(Element { kind: P, attributes: [] })
+        Text("a")
+"#;
+        assert_eq!(
+            expected,
+            convert_dom_to_string(&Some(window.borrow().document()))
+        );
+
+        let errors: Vec<String> = parser.errors().iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            vec![
+                "line 1, column 1: missing-doctype",
+                "line 1, column 13: unexpected-end-tag </p>",
+                "line 1, column 25: unexpected-end-tag </a>",
+                "line 1, column 43: unexpected-doctype",
+            ],
+            errors
+        );
+    }
 }
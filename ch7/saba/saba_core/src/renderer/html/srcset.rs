@@ -0,0 +1,289 @@
+//! https://html.spec.whatwg.org/multipage/images.html#srcset-attributes
+
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Descriptor {
+    /// "480w"のような幅記述子
+    Width(i64),
+    /// "2x"のようなピクセル密度記述子
+    Density(f64),
+}
+
+/// srcset属性に書かれた画像の候補
+/// https://html.spec.whatwg.org/multipage/images.html#image-candidate-string
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageCandidate {
+    url: String,
+    descriptor: Descriptor,
+}
+
+impl ImageCandidate {
+    pub fn new(url: String, descriptor: Descriptor) -> Self {
+        Self { url, descriptor }
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    pub fn descriptor(&self) -> Descriptor {
+        self.descriptor.clone()
+    }
+
+    /// 画像を表示する幅（source size）に対して、この画像が何倍の密度を持つか
+    fn density(&self, source_size: i64) -> f64 {
+        match self.descriptor {
+            Descriptor::Width(w) => w as f64 / source_size.max(1) as f64,
+            Descriptor::Density(d) => d,
+        }
+    }
+}
+
+fn parse_descriptor(descriptor: &str) -> Option<Descriptor> {
+    let (value, unit) = descriptor.split_at(descriptor.len().checked_sub(1)?);
+    match unit {
+        "w" => match value.parse::<i64>() {
+            Ok(w) if w > 0 => Some(Descriptor::Width(w)),
+            _ => None,
+        },
+        "x" => match value.parse::<f64>() {
+            Ok(d) if d > 0.0 => Some(Descriptor::Density(d)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/images.html#parse-a-srcset-attribute
+pub fn parse_srcset(srcset: &str) -> Vec<ImageCandidate> {
+    let mut candidates = Vec::new();
+    let mut rest = srcset;
+
+    loop {
+        // 候補の前にある空白とカンマを読み飛ばす
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+        if rest.is_empty() {
+            return candidates;
+        }
+
+        // URLは空白が現れるまで続く
+        let url_end = rest
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        let (url, after_url) = rest.split_at(url_end);
+
+        // URLの末尾がカンマの場合、記述子はない
+        let (url, descriptors) = if url.ends_with(',') {
+            rest = after_url;
+            (url.trim_end_matches(','), "")
+        } else {
+            let descriptors_end = after_url.find(',').unwrap_or(after_url.len());
+            let (descriptors, after_descriptors) = after_url.split_at(descriptors_end);
+            rest = after_descriptors;
+            (url, descriptors)
+        };
+
+        let descriptors: Vec<&str> = descriptors.split_ascii_whitespace().collect();
+        let descriptor = match descriptors.as_slice() {
+            // 記述子がない場合は"1x"として扱う
+            [] => Some(Descriptor::Density(1.0)),
+            [d] => parse_descriptor(d),
+            // "100w 50h"のような高さの記述子は使わない
+            [d, h] if h.ends_with('h') => parse_descriptor(d),
+            _ => None,
+        };
+
+        // 記述子が正しくない候補は無視する
+        if let Some(descriptor) = descriptor {
+            candidates.push(ImageCandidate::new(url.to_string(), descriptor));
+        }
+    }
+}
+
+/// srcset属性とsrc属性から、表示に使う画像のURLを選ぶ。
+/// 表示する幅（viewport_width）とズーム率から必要な密度を求め、それを満たす中で最も小さい画像を選ぶ。
+/// 満たす画像がない場合は最も密度の高い画像を選ぶ
+/// https://html.spec.whatwg.org/multipage/images.html#select-an-image-source
+pub fn select_image_source(
+    srcset: Option<&str>,
+    src: Option<&str>,
+    viewport_width: i64,
+    zoom: f64,
+) -> Option<String> {
+    let mut candidates = match srcset {
+        Some(srcset) => parse_srcset(srcset),
+        None => Vec::new(),
+    };
+
+    // src属性は、srcsetに同じ密度の候補がない場合に"1x"の候補として加える
+    if let Some(src) = src.filter(|s| !s.is_empty()) {
+        let has_width = candidates
+            .iter()
+            .any(|c| matches!(c.descriptor, Descriptor::Width(_)));
+        let has_1x = candidates
+            .iter()
+            .any(|c| c.descriptor == Descriptor::Density(1.0));
+        if !has_width && !has_1x {
+            candidates.push(ImageCandidate::new(
+                src.to_string(),
+                Descriptor::Density(1.0),
+            ));
+        }
+    }
+
+    // sizes属性はサポートしていないので、画像は表示領域の幅いっぱいに表示されるものとする
+    let source_size = viewport_width;
+    let target = zoom;
+
+    let enough = candidates
+        .iter()
+        .filter(|c| c.density(source_size) >= target)
+        .min_by(|a, b| a.density(source_size).total_cmp(&b.density(source_size)));
+    let best = match enough {
+        Some(c) => Some(c),
+        None => candidates
+            .iter()
+            .max_by(|a, b| a.density(source_size).total_cmp(&b.density(source_size))),
+    };
+
+    best.map(|c| c.url())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::token::HtmlToken;
+    use crate::renderer::html::token::HtmlTokenizer;
+    use alloc::vec;
+
+    #[test]
+    fn test_parse_srcset() {
+        assert_eq!(
+            vec![
+                ImageCandidate::new("small.png".to_string(), Descriptor::Width(300)),
+                ImageCandidate::new("large.png".to_string(), Descriptor::Width(1200)),
+            ],
+            parse_srcset("small.png 300w, large.png 1200w")
+        );
+        assert_eq!(
+            vec![
+                ImageCandidate::new("a.png".to_string(), Descriptor::Density(1.0)),
+                ImageCandidate::new("b.png".to_string(), Descriptor::Density(2.0)),
+                ImageCandidate::new("c.png".to_string(), Descriptor::Density(1.0)),
+            ],
+            parse_srcset(" a.png, b.png 2x ,  c.png,")
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_descriptor() {
+        assert_eq!(
+            vec![ImageCandidate::new(
+                "ok.png".to_string(),
+                Descriptor::Density(1.5)
+            )],
+            parse_srcset("bad.png 10q, zero.png 0w, ok.png 1.5x")
+        );
+    }
+
+    #[test]
+    fn test_select_density() {
+        let srcset = Some("a.png 1x, b.png 2x, c.png 3x");
+        assert_eq!(
+            Some("a.png".to_string()),
+            select_image_source(srcset, None, 600, 1.0)
+        );
+        assert_eq!(
+            Some("b.png".to_string()),
+            select_image_source(srcset, None, 600, 1.5)
+        );
+        // 十分な密度の画像がない場合は、最も密度の高い画像を使う
+        assert_eq!(
+            Some("c.png".to_string()),
+            select_image_source(srcset, None, 600, 4.0)
+        );
+    }
+
+    #[test]
+    fn test_select_width() {
+        let srcset = Some("small.png 300w, medium.png 600w, large.png 1200w");
+        assert_eq!(
+            Some("small.png".to_string()),
+            select_image_source(srcset, Some("fallback.png"), 300, 1.0)
+        );
+        assert_eq!(
+            Some("medium.png".to_string()),
+            select_image_source(srcset, Some("fallback.png"), 590, 1.0)
+        );
+        assert_eq!(
+            Some("large.png".to_string()),
+            select_image_source(srcset, Some("fallback.png"), 590, 2.0)
+        );
+    }
+
+    #[test]
+    fn test_fallback_to_src() {
+        assert_eq!(
+            Some("fallback.png".to_string()),
+            select_image_source(None, Some("fallback.png"), 600, 1.0)
+        );
+        assert_eq!(
+            Some("fallback.png".to_string()),
+            select_image_source(Some("hidpi.png 2x"), Some("fallback.png"), 600, 1.0)
+        );
+        assert_eq!(None, select_image_source(Some(""), None, 600, 1.0));
+    }
+
+    #[test]
+    fn test_responsive_page() {
+        // test_srcset.htmlの<img>タグから、表示領域の幅とズーム率に応じて画像を選ぶ
+        let html = include_str!("../../../../test_srcset.html").to_string();
+        let t = HtmlTokenizer::new(html);
+        let mut selected = Vec::new();
+        for token in t {
+            if let HtmlToken::StartTag {
+                ref tag,
+                ref attributes,
+                ..
+            } = token
+            {
+                if &**tag != "img" {
+                    continue;
+                }
+                let get = |name: &str| {
+                    attributes
+                        .iter()
+                        .find(|a| a.name() == name)
+                        .map(|a| a.value())
+                };
+                let srcset = get("srcset");
+                let src = get("src");
+                selected.push((
+                    select_image_source(srcset.as_deref(), src.as_deref(), 590, 1.0),
+                    select_image_source(srcset.as_deref(), src.as_deref(), 590, 2.0),
+                ));
+            }
+        }
+
+        assert_eq!(
+            vec![
+                (
+                    Some("http://host.test:8000/img/medium.png".to_string()),
+                    Some("http://host.test:8000/img/large.png".to_string())
+                ),
+                (
+                    Some("http://host.test:8000/img/logo.png".to_string()),
+                    Some("http://host.test:8000/img/logo@2x.png".to_string())
+                ),
+                (
+                    Some("http://host.test:8000/img/plain.png".to_string()),
+                    Some("http://host.test:8000/img/plain.png".to_string())
+                ),
+            ],
+            selected
+        );
+    }
+}
@@ -1,21 +1,77 @@
 use crate::renderer::html::attribute::Attribute;
+use crate::renderer::html::entity::is_legacy_character_reference;
+use crate::renderer::html::entity::named_character_reference;
+use crate::renderer::html::entity::numeric_character_reference;
+use crate::renderer::html::parse_error::ParseError;
+use crate::renderer::html::parse_error::ParseErrorKind;
+use alloc::collections::BTreeSet;
+use alloc::collections::VecDeque;
+use alloc::rc::Rc;
 use alloc::string::String;
+use alloc::string::ToString;
 use alloc::vec::Vec;
+use core::fmt::Display;
+use core::fmt::Formatter;
+use core::ops::Range;
+
+/// HTMLのソースの中の位置。行と列は1から数え、列は文字単位で数える
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SourcePosition {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SourcePosition {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+
+    /// `c`を読んだ後の位置に進める
+    fn advance(&mut self, c: char) {
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+    }
+}
+
+impl Default for SourcePosition {
+    fn default() -> Self {
+        Self::new(1, 1)
+    }
+}
+
+impl Display for SourcePosition {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum HtmlToken {
-    // 開始タグ
+    // 開始タグ。タグの名前は、同じ名前のタグの間で共有する
     StartTag {
-        tag: String,
+        tag: Rc<str>,
         self_closing: bool,
         attributes: Vec<Attribute>,
     },
     // 終了タグ
     EndTag {
-        tag: String,
+        tag: Rc<str>,
+    },
+    // 続く文字。空白文字だけの範囲と、それ以外の文字から始まる範囲は別のトークンにする
+    Text(Rc<str>),
+    // コメント。"<!--"と"-->"の間の文字列を持つ
+    Comment(String),
+    // DOCTYPE。名前は小文字にしたもの。省略された名前と識別子はNoneになる
+    Doctype {
+        name: Option<String>,
+        public_id: Option<String>,
+        system_id: Option<String>,
+        force_quirks: bool,
     },
-    // 文字
-    Char(char),
     // ファイルの終了（End Of File）
     Eof,
 }
@@ -48,6 +104,14 @@ pub enum State {
     AfterAttributeValueQuoted,
     /// https://html.spec.whatwg.org/multipage/parsing.html#self-closing-start-tag-state
     SelfClosingStartTag,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rcdata-state
+    Rcdata,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rcdata-less-than-sign-state
+    RcdataLessThanSign,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rcdata-end-tag-open-state
+    RcdataEndTagOpen,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rcdata-end-tag-name-state
+    RcdataEndTagName,
     /// https://html.spec.whatwg.org/multipage/parsing.html#script-data-state
     ScriptData,
     /// https://html.spec.whatwg.org/multipage/parsing.html#script-data-less-than-sign-state
@@ -58,86 +122,469 @@ pub enum State {
     ScriptDataEndTagName,
     /// https://html.spec.whatwg.org/multipage/parsing.html#temporary-buffer
     TemporaryBuffer,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#character-reference-state
+    CharacterReference,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#named-character-reference-state
+    NamedCharacterReference,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-state
+    NumericCharacterReference,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#hexadecimal-character-reference-state
+    HexadecimalCharacterReference,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#decimal-character-reference-state
+    DecimalCharacterReference,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#markup-declaration-open-state
+    MarkupDeclarationOpen,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#comment-start-state
+    CommentStart,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#comment-start-dash-state
+    CommentStartDash,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#comment-state
+    Comment,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#comment-end-dash-state
+    CommentEndDash,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#comment-end-state
+    CommentEnd,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#comment-end-bang-state
+    CommentEndBang,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#bogus-comment-state
+    BogusComment,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#cdata-section-state
+    CdataSection,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#cdata-section-bracket-state
+    CdataSectionBracket,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#cdata-section-end-state
+    CdataSectionEnd,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#doctype-state
+    Doctype,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#before-doctype-name-state
+    BeforeDoctypeName,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#doctype-name-state
+    DoctypeName,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-name-state
+    AfterDoctypeName,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-public-keyword-state
+    AfterDoctypePublicKeyword,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#before-doctype-public-identifier-state
+    BeforeDoctypePublicIdentifier,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#doctype-public-identifier-(double-quoted)-state
+    DoctypePublicIdentifierDoubleQuoted,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#doctype-public-identifier-(single-quoted)-state
+    DoctypePublicIdentifierSingleQuoted,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-public-identifier-state
+    AfterDoctypePublicIdentifier,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#between-doctype-public-and-system-identifiers-state
+    BetweenDoctypePublicAndSystemIdentifiers,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-system-keyword-state
+    AfterDoctypeSystemKeyword,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#before-doctype-system-identifier-state
+    BeforeDoctypeSystemIdentifier,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#doctype-system-identifier-(double-quoted)-state
+    DoctypeSystemIdentifierDoubleQuoted,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#doctype-system-identifier-(single-quoted)-state
+    DoctypeSystemIdentifierSingleQuoted,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-system-identifier-state
+    AfterDoctypeSystemIdentifier,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#bogus-doctype-state
+    BogusDoctype,
 }
 
+/// 名前付き文字参照として読む名前の長さの上限。これより長い場合は、文字参照ではないとみなす
+const MAX_CHARACTER_REFERENCE_NAME_LEN: usize = 32;
+
+/// HTMLの文字列をトークンに分ける。入力は受け取った文字列のまま持ち、バイトの位置で読み進める。
+/// テキスト、タグの名前、属性、コメントの中の続く文字は、1文字ずつではなく範囲ごとにまとめてトークンにする
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct HtmlTokenizer {
     state: State,
+    /// 次に読む文字のバイトの位置
     pos: usize,
     reconsume: bool,
     latest_token: Option<HtmlToken>,
-    input: Vec<char>,
+    /// 読んでいる入力。push_inputで受け取った入力は、受け取った単位のまま`queued_input`に並べ、
+    /// 読み終えたら次の入力に入れ替える
+    input: String,
+    queued_input: VecDeque<String>,
+    /// 最後に読んだ文字。やり直すときに、入力を戻らずに使う。この文字は常に`input`の`pos`の直前にある
+    current_char: char,
     buf: String,
+    /// 文字参照を読み終えた後に戻る状態。テキストと属性値のどちらの中の文字参照かを区別する
+    /// https://html.spec.whatwg.org/multipage/parsing.html#return-state
+    return_state: State,
+    /// 文字参照を置き換えた文字などのうち、まだ返していない文字
+    pending_text: String,
+    /// 作っているタグの名前と、最後の属性の名前。タグを返すときに`names`の文字列に置き換える
+    tag_name: String,
+    attribute_name: String,
+    /// これまでに読んだタグと属性の名前。同じ名前は同じ文字列を共有する
+    names: BTreeSet<Rc<str>>,
+    /// 最後に返した開始タグの名前。RCDATAの中では、この名前の終了タグだけをタグとして扱う
+    /// https://html.spec.whatwg.org/multipage/parsing.html#appropriate-end-tag-token
+    last_start_tag: Option<Rc<str>>,
+    /// 次に読む文字の位置
+    next_position: SourcePosition,
+    /// 最後に読んだ文字の位置。やり直す文字の位置にもなる
+    current_position: SourcePosition,
+    /// 最後に返したトークンが始まる位置
+    token_position: SourcePosition,
+    /// これまでに見つけた解釈の失敗
+    errors: Vec<ParseError>,
+    /// "<![CDATA[...]]>"の中身を文字として読むかどうか。falseの場合は、仕様に従ってコメントとして読む
+    cdata_allowed: bool,
+    /// 入力の続きがもう届かないかどうか。trueの場合、入力を読み終えたら途中のトークンを返してEofで終える
+    input_closed: bool,
+    /// 最後のトークンとしてEofを返したかどうか
+    eof_emitted: bool,
+    /// 作っている終了タグに書かれた属性。終了タグは属性を持たないので、返すときに捨てる
+    end_tag_attributes: Vec<Attribute>,
 }
 
 impl HtmlTokenizer {
+    /// 文書の全体を受け取る。入力を読み終えたらEofを返して終える
     pub fn new(html: String) -> Self {
         Self {
             state: State::Data,
             pos: 0,
             reconsume: false,
             latest_token: None,
-            input: html.chars().collect(),
+            input: html,
+            queued_input: VecDeque::new(),
+            current_char: '\0',
             buf: String::new(),
+            return_state: State::Data,
+            pending_text: String::new(),
+            tag_name: String::new(),
+            attribute_name: String::new(),
+            names: BTreeSet::new(),
+            last_start_tag: None,
+            next_position: SourcePosition::default(),
+            current_position: SourcePosition::default(),
+            token_position: SourcePosition::default(),
+            errors: Vec::new(),
+            cdata_allowed: false,
+            input_closed: true,
+            eof_emitted: false,
+            end_tag_attributes: Vec::new(),
+        }
+    }
+
+    /// 空の入力で始め、受信した順にpush_inputで入力を受け取る。全てを受信したらclose_inputを呼ぶ
+    pub fn streaming() -> Self {
+        Self {
+            input_closed: false,
+            ..Self::new(String::new())
+        }
+    }
+
+    /// 最後にnextで返したトークンが、ソースのどこから始まるか。
+    /// 文字参照を置き換えた文字は"&"の位置、タグやコメントは"<"の位置になる
+    pub fn position(&self) -> SourcePosition {
+        self.token_position
+    }
+
+    /// これまでに見つけた解釈の失敗を、見つけた順に返す
+    pub fn errors(&self) -> Vec<ParseError> {
+        self.errors.clone()
+    }
+
+    /// 最後に読んだ文字の位置で、解釈の失敗を記録する
+    fn error(&mut self, kind: ParseErrorKind) {
+        self.errors
+            .push(ParseError::new(kind, self.current_position));
+    }
+
+    /// トークナイザの状態を切り替える。<title>や<textarea>の開始タグを受け取ったツリー構築が、
+    /// その中身をマークアップではなくテキストとして読ませるために使う
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-elements-that-contain-only-text
+    pub fn switch_to(&mut self, state: State) {
+        self.state = state;
+    }
+
+    /// CDATAセクションを文字として読むかを切り替える。仕様では、SVGやMathMLのようなHTML以外の
+    /// 名前空間の要素の中でだけ許すので、ツリー構築がその要素の中に入ったときに使う
+    /// https://html.spec.whatwg.org/multipage/parsing.html#markup-declaration-open-state
+    pub fn set_cdata_allowed(&mut self, allowed: bool) {
+        self.cdata_allowed = allowed;
+    }
+
+    /// 入力の後ろにHTMLの続きを追加する。レスポンスのボディを受信しながら、届いた部分から順にトークンにする。
+    /// 読んでいる入力はそのまま残し、続きは読み終えたときに入れ替える
+    pub fn push_input(&mut self, chunk: &str) {
+        if !chunk.is_empty() {
+            self.queued_input.push_back(chunk.to_string());
         }
     }
 
-    fn is_eof(&self) -> bool {
-        self.pos > self.input.len()
+    /// 入力の続きがもう届かないことを知らせる。この後のnextは、入力を読み終えると途中のトークンを返し、
+    /// 最後にEofを返す
+    pub fn close_input(&mut self) {
+        self.input_closed = true;
+    }
+
+    /// 次に読む文字がまだ届いていないかどうか。nextがNoneを返した後にtrueであれば、
+    /// トークンの途中で止まっている状態を残しているので、push_inputで続きを渡すと読み進められる。
+    /// やり直す文字がある場合は、入力を読まずに続けられる
+    pub fn needs_more_input(&self) -> bool {
+        !self.input_closed && !self.reconsume && self.pending_text.is_empty() && self.at_end()
+    }
+
+    /// 入力を全て読んだかどうか。やり直す文字がある場合は、まだ読み終えていない
+    fn at_end(&self) -> bool {
+        !self.reconsume && self.pos >= self.input.len() && self.queued_input.is_empty()
     }
 
     fn consume_next_input(&mut self) -> char {
-        let c = self.input[self.pos];
-        self.pos += 1;
+        // 読み終えた入力は捨てて、続きの入力から読む
+        if self.pos >= self.input.len() {
+            if let Some(next) = self.queued_input.pop_front() {
+                self.input = next;
+                self.pos = 0;
+            }
+        }
+
+        let c = match self.input[self.pos..].chars().next() {
+            Some(c) => c,
+            None => panic!("input should remain"),
+        };
+        self.pos += c.len_utf8();
+        self.current_char = c;
+        self.current_position = self.next_position;
+        self.next_position.advance(c);
         c
     }
 
     fn reconsume_input(&mut self) -> char {
         self.reconsume = false;
-        self.input[self.pos - 1]
+        self.current_char
+    }
+
+    /// `is_stop`が真になる文字の手前まで、まとめて読み進める。読んだ範囲をバイトの位置で返す。
+    /// 状態を変えずに同じ処理を繰り返す、名前や値の途中の文字に使う
+    fn consume_run(&mut self, is_stop: impl Fn(char) -> bool) -> Range<usize> {
+        let start = self.pos;
+        for c in self.input[start..].chars() {
+            if is_stop(c) {
+                break;
+            }
+            self.pos += c.len_utf8();
+            self.current_char = c;
+            self.current_position = self.next_position;
+            self.next_position.advance(c);
+        }
+        start..self.pos
+    }
+
+    /// テキストの中で読んだ`c`から、続く文字を1つの文字トークンにする。`c`が空白文字の場合は
+    /// 空白文字だけを続けるので、ツリー構築は最初の文字で空白文字だけのトークンかを判断できる
+    fn text_run(&mut self, c: char, is_stop: impl Fn(char) -> bool) -> HtmlToken {
+        let start = self.pos - c.len_utf8();
+        let run = if is_whitespace(c) {
+            self.consume_run(|c| !is_whitespace(c) || is_stop(c))
+        } else {
+            self.consume_run(is_stop)
+        };
+        HtmlToken::Text(Rc::from(&self.input[start..run.end]))
+    }
+
+    /// タグと属性の名前を、これまでに読んだ同じ名前と共有する文字列にする
+    fn intern(&mut self, name: &str) -> Rc<str> {
+        if let Some(name) = self.names.get(name) {
+            return name.clone();
+        }
+        let name: Rc<str> = Rc::from(name);
+        self.names.insert(name.clone());
+        name
     }
 
     fn create_tag(&mut self, start_tag_token: bool) {
+        self.tag_name.clear();
+        let tag = self.intern("");
         if start_tag_token {
             self.latest_token = Some(HtmlToken::StartTag {
-                tag: String::new(),
+                tag,
                 self_closing: false,
                 attributes: Vec::new(),
             });
         } else {
-            self.latest_token = Some(HtmlToken::EndTag { tag: String::new() });
+            self.latest_token = Some(HtmlToken::EndTag { tag });
         }
     }
 
-    fn append_tag_name(&mut self, c: char) {
+    fn create_comment(&mut self, data: &str) {
+        self.latest_token = Some(HtmlToken::Comment(String::from(data)));
+    }
+
+    fn append_comment(&mut self, s: &str) {
         assert!(self.latest_token.is_some());
 
-        if let Some(t) = self.latest_token.as_mut() {
-            match t {
-                HtmlToken::StartTag {
-                    ref mut tag,
-                    self_closing: _,
-                    attributes: _,
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::Comment(ref mut data)) => data.push_str(s),
+            _ => panic!("`latest_token` should be Comment"),
+        }
+    }
+
+    /// consume_runで読んだ範囲を、作っているコメントに加える
+    fn append_comment_run(&mut self, run: Range<usize>) {
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::Comment(ref mut data)) => data.push_str(&self.input[run]),
+            _ => panic!("`latest_token` should be Comment"),
+        }
+    }
+
+    fn create_doctype(&mut self) {
+        self.latest_token = Some(HtmlToken::Doctype {
+            name: None,
+            public_id: None,
+            system_id: None,
+            force_quirks: false,
+        });
+    }
+
+    /// DOCTYPEの名前に文字を追加する。名前がまだない場合は、新しく名前を持たせる
+    fn append_doctype_name(&mut self, c: char) {
+        assert!(self.latest_token.is_some());
+
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::Doctype { ref mut name, .. }) => {
+                name.get_or_insert_with(String::new)
+                    .push(c.to_ascii_lowercase());
+            }
+            _ => panic!("`latest_token` should be Doctype"),
+        }
+    }
+
+    /// 公開識別子（`public`がtrueの場合）またはシステム識別子を、空の文字列で始める
+    fn start_doctype_identifier(&mut self, public: bool) {
+        assert!(self.latest_token.is_some());
+
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::Doctype {
+                ref mut public_id,
+                ref mut system_id,
+                ..
+            }) => {
+                if public {
+                    *public_id = Some(String::new());
+                } else {
+                    *system_id = Some(String::new());
                 }
-                | HtmlToken::EndTag { ref mut tag } => tag.push(c),
-                _ => panic!("`latest_token` should be either StartTag or EndTag"),
             }
+            _ => panic!("`latest_token` should be Doctype"),
+        }
+    }
+
+    fn append_doctype_identifier(&mut self, public: bool, c: char) {
+        assert!(self.latest_token.is_some());
+
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::Doctype {
+                ref mut public_id,
+                ref mut system_id,
+                ..
+            }) => {
+                let id = if public { public_id } else { system_id };
+                id.get_or_insert_with(String::new).push(c);
+            }
+            _ => panic!("`latest_token` should be Doctype"),
+        }
+    }
+
+    fn set_force_quirks_flag(&mut self) {
+        assert!(self.latest_token.is_some());
+
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::Doctype {
+                ref mut force_quirks,
+                ..
+            }) => *force_quirks = true,
+            _ => panic!("`latest_token` should be Doctype"),
         }
     }
 
+    fn append_tag_name(&mut self, c: char) {
+        self.tag_name.push(c);
+    }
+
+    /// consume_runで読んだ範囲を、作っているタグの名前に加える
+    fn append_tag_name_run(&mut self, run: Range<usize>) {
+        self.tag_name.push_str(&self.input[run]);
+    }
+
     fn take_latest_token(&mut self) -> Option<HtmlToken> {
         assert!(self.latest_token.is_some());
 
-        let t = self.latest_token.as_ref().cloned();
-        self.latest_token = None;
-        assert!(self.latest_token.is_none());
+        self.finish_attribute_name();
+        let mut t = self.latest_token.take();
+        if let Some(HtmlToken::StartTag { ref mut tag, .. })
+        | Some(HtmlToken::EndTag { ref mut tag }) = t
+        {
+            let name = core::mem::take(&mut self.tag_name);
+            *tag = self.intern(&name);
+            self.tag_name = name;
+        }
+        if let Some(HtmlToken::StartTag {
+            ref tag,
+            ref mut attributes,
+            ..
+        }) = t
+        {
+            self.last_start_tag = Some(tag.clone());
+
+            // 同じ名前の属性は、最初のものだけを残す
+            // https://html.spec.whatwg.org/multipage/parsing.html#attribute-name-state
+            let len = attributes.len();
+            let mut names = Vec::new();
+            attributes.retain(|attribute| {
+                if names.contains(&attribute.name()) {
+                    return false;
+                }
+                names.push(attribute.name());
+                true
+            });
+            if attributes.len() < len {
+                self.errors.push(ParseError::new(
+                    ParseErrorKind::DuplicateAttribute,
+                    self.token_position,
+                ));
+            }
+        }
+        // 終了タグに書かれた属性は捨てる
+        // https://html.spec.whatwg.org/multipage/parsing.html#parse-error-end-tag-with-attributes
+        if let Some(HtmlToken::EndTag { .. }) = t {
+            if !self.end_tag_attributes.is_empty() {
+                self.end_tag_attributes.clear();
+                self.errors.push(ParseError::new(
+                    ParseErrorKind::EndTagWithAttributes,
+                    self.token_position,
+                ));
+            }
+        }
 
         t
     }
 
+    /// 作っている終了タグが、最後に返した開始タグと同じ名前かどうか
+    /// https://html.spec.whatwg.org/multipage/parsing.html#appropriate-end-tag-token
+    fn is_appropriate_end_tag(&self) -> bool {
+        match (&self.latest_token, &self.last_start_tag) {
+            (Some(HtmlToken::EndTag { .. }), Some(start_tag)) => *self.tag_name == **start_tag,
+            _ => false,
+        }
+    }
+
+    /// RCDATAの中で、終了タグにならなかった"</"と読んだ名前を文字トークンにして、RCDATAに戻る
+    fn flush_rcdata_end_tag(&mut self) {
+        self.latest_token = None;
+        self.pending_text.push_str("</");
+        let name = core::mem::take(&mut self.buf);
+        self.pending_text.push_str(&name);
+        self.reconsume = true;
+        self.state = State::Rcdata;
+    }
+
     fn start_new_attribute(&mut self) {
         assert!(self.latest_token.is_some());
 
+        self.finish_attribute_name();
         if let Some(t) = self.latest_token.as_mut() {
             match t {
                 HtmlToken::StartTag {
@@ -147,14 +594,41 @@ impl HtmlTokenizer {
                 } => {
                     attributes.push(Attribute::new());
                 }
-                _ => panic!("`latest_token` should be either StartTag"),
+                HtmlToken::EndTag { .. } => self.end_tag_attributes.push(Attribute::new()),
+                _ => panic!("`latest_token` should be either StartTag or EndTag"),
             }
         }
     }
 
+    /// 属性の名前を読み終えたら、最後の属性に`names`の文字列として設定する
+    fn finish_attribute_name(&mut self) {
+        if self.attribute_name.is_empty() {
+            return;
+        }
+        let scratch = core::mem::take(&mut self.attribute_name);
+        let name = self.intern(&scratch);
+        self.attribute_name = scratch;
+        self.attribute_name.clear();
+
+        let attribute = match self.latest_token.as_mut() {
+            Some(HtmlToken::StartTag {
+                ref mut attributes, ..
+            }) => attributes.last_mut(),
+            _ => self.end_tag_attributes.last_mut(),
+        };
+        if let Some(attribute) = attribute {
+            attribute.set_name(name);
+        }
+    }
+
     fn append_attribute(&mut self, c: char, is_name: bool) {
         assert!(self.latest_token.is_some());
 
+        if is_name {
+            self.attribute_name.push(c);
+            return;
+        }
+
         if let Some(t) = self.latest_token.as_mut() {
             match t {
                 HtmlToken::StartTag {
@@ -167,9 +641,259 @@ impl HtmlTokenizer {
 
                     attributes[len - 1].add_char(c, is_name);
                 }
-                _ => panic!("`latest_token` should be either StartTag"),
+                HtmlToken::EndTag { .. } => match self.end_tag_attributes.last_mut() {
+                    Some(attribute) => attribute.add_char(c, is_name),
+                    None => panic!("attribute should exist"),
+                },
+                _ => panic!("`latest_token` should be either StartTag or EndTag"),
+            }
+        }
+    }
+
+    /// consume_runで読んだ範囲を、作っている属性の名前か値に加える
+    fn append_attribute_run(&mut self, run: Range<usize>, is_name: bool) {
+        if is_name {
+            self.attribute_name.push_str(&self.input[run]);
+            return;
+        }
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::StartTag {
+                ref mut attributes, ..
+            }) => match attributes.last_mut() {
+                Some(attribute) => attribute.add_str(&self.input[run], is_name),
+                None => panic!("attribute should exist"),
+            },
+            Some(HtmlToken::EndTag { .. }) => match self.end_tag_attributes.last_mut() {
+                Some(attribute) => attribute.add_str(&self.input[run], is_name),
+                None => panic!("attribute should exist"),
+            },
+            _ => panic!("`latest_token` should be either StartTag or EndTag"),
+        }
+    }
+
+    /// 文字参照の読み始め。`buf`に"&"を入れ、読み終えたら`return_state`に戻る
+    fn start_character_reference(&mut self, return_state: State) {
+        self.return_state = return_state;
+        self.buf = String::from("&");
+        self.state = State::CharacterReference;
+    }
+
+    /// 属性値の中の文字参照かどうか
+    fn in_attribute_value(&self) -> bool {
+        matches!(
+            self.return_state,
+            State::AttributeValueDoubleQuoted
+                | State::AttributeValueSingleQuoted
+                | State::AttributeValueUnquoted
+        )
+    }
+
+    /// 文字参照を置き換えた文字列を、属性値の中であれば属性値に加え、テキストの中であれば文字トークンにする。
+    /// 読み終えた後は、`return_state`に戻る
+    /// https://html.spec.whatwg.org/multipage/parsing.html#flush-code-points-consumed-as-a-character-reference
+    fn flush_code_points(&mut self, text: &str) {
+        for c in text.chars() {
+            if self.in_attribute_value() {
+                self.append_attribute(c, /*is_name*/ false);
+            } else {
+                self.pending_text.push(c);
+            }
+        }
+        self.state = self.return_state.clone();
+    }
+
+    /// ";"で終わらなかった名前付き文字参照を、置き換えた文字列か元の文字列にして戻った状態に渡す。
+    /// `next`は名前の後ろの文字
+    fn finish_named_reference(&mut self, next: char) {
+        let text = self.unterminated_named_reference(next);
+        if text != self.buf {
+            self.error(ParseErrorKind::MissingSemicolonAfterCharacterReference);
+        }
+        self.flush_code_points(&text);
+    }
+
+    /// 数字を読み終えた数値文字参照を、置き換えた文字にして戻った状態に渡す。
+    /// `semicolon`は、数字の後ろが";"だったかどうか。";"を文字参照の一部として読んだ場合はtrueを返す
+    fn finish_numeric_reference(&mut self, semicolon: bool) -> bool {
+        let (prefix_len, radix) = match self.state {
+            State::HexadecimalCharacterReference => (3, 16),
+            _ => (2, 10),
+        };
+        // "&#"や"&#x"の後に数字が続かない場合は、文字参照ではない
+        let digits = &self.buf[prefix_len..];
+        if digits.is_empty() {
+            self.error(ParseErrorKind::AbsenceOfDigitsInNumericCharacterReference);
+            let text = core::mem::take(&mut self.buf);
+            self.flush_code_points(&text);
+            return false;
+        }
+
+        // Unicodeの範囲を超える大きな値は、U+FFFDにする
+        let code = digits.chars().fold(0u32, |code, d| {
+            code.saturating_mul(radix)
+                .saturating_add(d.to_digit(radix).unwrap_or(0))
+                .min(0x110000)
+        });
+        match code {
+            0 => self.error(ParseErrorKind::NullCharacterReference),
+            0xD800..=0xDFFF => self.error(ParseErrorKind::SurrogateCharacterReference),
+            0x110000.. => self.error(ParseErrorKind::CharacterReferenceOutsideUnicodeRange),
+            _ => {}
+        }
+        self.flush_code_points(&numeric_character_reference(code).to_string());
+        if !semicolon {
+            self.error(ParseErrorKind::MissingSemicolonAfterCharacterReference);
+        }
+        semicolon
+    }
+
+    /// 入力の終わりで、途中まで読んだものを仕様に従って片付け、Dataの状態に戻る。
+    /// 文字にするものはpending_textに入れ、コメントとDOCTYPEは返す。
+    /// 閉じていないタグは捨てる
+    /// https://html.spec.whatwg.org/multipage/parsing.html#tokenization
+    fn finish_at_eof(&mut self) -> Option<HtmlToken> {
+        let state = core::mem::replace(&mut self.state, State::Data);
+        match state {
+            State::Data | State::Rcdata | State::ScriptData => None,
+            State::CdataSection => {
+                self.error(ParseErrorKind::EofInCdata);
+                None
+            }
+            State::CdataSectionBracket => {
+                self.error(ParseErrorKind::EofInCdata);
+                self.pending_text.push(']');
+                None
+            }
+            State::CdataSectionEnd => {
+                self.error(ParseErrorKind::EofInCdata);
+                self.pending_text.push_str("]]");
+                None
+            }
+            State::TagOpen | State::RcdataLessThanSign | State::ScriptDataLessThanSign => {
+                if state == State::TagOpen {
+                    self.error(ParseErrorKind::EofBeforeTagName);
+                }
+                self.pending_text.push('<');
+                None
+            }
+            State::EndTagOpen | State::RcdataEndTagOpen | State::ScriptDataEndTagOpen => {
+                if state == State::EndTagOpen {
+                    self.error(ParseErrorKind::EofBeforeTagName);
+                }
+                self.pending_text.push_str("</");
+                None
+            }
+            State::RcdataEndTagName | State::ScriptDataEndTagName => {
+                self.latest_token = None;
+                self.pending_text.push_str("</");
+                let name = core::mem::take(&mut self.buf);
+                self.pending_text.push_str(&name);
+                None
+            }
+            State::TemporaryBuffer => {
+                let text = core::mem::take(&mut self.buf);
+                self.pending_text.push_str(&text);
+                None
+            }
+            State::TagName
+            | State::BeforeAttributeName
+            | State::AttributeName
+            | State::AfterAttributeName
+            | State::BeforeAttributeValue
+            | State::AttributeValueDoubleQuoted
+            | State::AttributeValueSingleQuoted
+            | State::AttributeValueUnquoted
+            | State::AfterAttributeValueQuoted
+            | State::SelfClosingStartTag => {
+                self.error(ParseErrorKind::EofInTag);
+                self.latest_token = None;
+                None
+            }
+            State::CharacterReference
+            | State::NamedCharacterReference
+            | State::NumericCharacterReference
+            | State::HexadecimalCharacterReference
+            | State::DecimalCharacterReference => {
+                // 読んだところまでで文字参照を終え、戻った状態の終わりとして続きを片付ける
+                self.state = state.clone();
+                match state {
+                    State::NamedCharacterReference => self.finish_named_reference('\0'),
+                    State::HexadecimalCharacterReference | State::DecimalCharacterReference => {
+                        self.finish_numeric_reference(false);
+                    }
+                    _ => {
+                        if state == State::NumericCharacterReference {
+                            self.error(ParseErrorKind::AbsenceOfDigitsInNumericCharacterReference);
+                        }
+                        let text = core::mem::take(&mut self.buf);
+                        self.flush_code_points(&text);
+                    }
+                }
+                self.finish_at_eof()
+            }
+            State::MarkupDeclarationOpen => {
+                // "<!-"や"<!DOC"のように途中までしか読めなかったものは、不正なコメントにする
+                self.error(ParseErrorKind::IncorrectlyOpenedComment);
+                let data = core::mem::take(&mut self.buf);
+                self.create_comment(&data);
+                self.take_latest_token()
+            }
+            State::BogusComment => self.take_latest_token(),
+            State::CommentStart
+            | State::CommentStartDash
+            | State::Comment
+            | State::CommentEndDash
+            | State::CommentEnd
+            | State::CommentEndBang => {
+                self.error(ParseErrorKind::EofInComment);
+                self.take_latest_token()
+            }
+            State::BogusDoctype => self.take_latest_token(),
+            State::Doctype
+            | State::BeforeDoctypeName
+            | State::DoctypeName
+            | State::AfterDoctypeName
+            | State::AfterDoctypePublicKeyword
+            | State::BeforeDoctypePublicIdentifier
+            | State::DoctypePublicIdentifierDoubleQuoted
+            | State::DoctypePublicIdentifierSingleQuoted
+            | State::AfterDoctypePublicIdentifier
+            | State::BetweenDoctypePublicAndSystemIdentifiers
+            | State::AfterDoctypeSystemKeyword
+            | State::BeforeDoctypeSystemIdentifier
+            | State::DoctypeSystemIdentifierDoubleQuoted
+            | State::DoctypeSystemIdentifierSingleQuoted
+            | State::AfterDoctypeSystemIdentifier => {
+                self.error(ParseErrorKind::EofInDoctype);
+                self.set_force_quirks_flag();
+                self.take_latest_token()
+            }
+        }
+    }
+
+    /// ";"で終わっていない名前付き文字参照を置き換えた文字列。"&copyright"の"&copy"のように、
+    /// 名前の先頭が古くからある名前に一致する場合は、その部分だけを置き換える。
+    /// 属性値の中では、続く文字が英数字か"="の場合は置き換えない
+    fn unterminated_named_reference(&self, next: char) -> String {
+        let name = &self.buf[1..];
+        for len in (1..=name.len()).rev() {
+            if !is_legacy_character_reference(&name[..len]) {
+                continue;
+            }
+            let rest = &name[len..];
+            let following = rest.chars().next().unwrap_or(next);
+            if self.in_attribute_value() && (following.is_ascii_alphanumeric() || following == '=')
+            {
+                break;
             }
+            let mut text = String::new();
+            if let Some(c) = named_character_reference(&name[..len]) {
+                text.push(c);
+            }
+            text.push_str(rest);
+            return text;
         }
+        self.buf.clone()
     }
 
     fn set_self_closing_flag(&mut self) {
@@ -182,26 +906,69 @@ impl HtmlTokenizer {
                     ref mut self_closing,
                     attributes: _,
                 } => *self_closing = true,
-                _ => panic!("`latest_token` should be either StartTag"),
+                // 終了タグの"/>"は無視する
+                // https://html.spec.whatwg.org/multipage/parsing.html#parse-error-end-tag-with-trailing-solidus
+                HtmlToken::EndTag { .. } => self.errors.push(ParseError::new(
+                    ParseErrorKind::EndTagWithTrailingSolidus,
+                    self.token_position,
+                )),
+                _ => panic!("`latest_token` should be either StartTag or EndTag"),
             }
         }
     }
 }
 
+/// HTMLの仕様での空白文字。DOCTYPEの中の区切りや、空白文字だけのテキストの判定に使う
+/// https://infra.spec.whatwg.org/#ascii-whitespace
+pub fn is_whitespace(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\x0C' | '\r' | ' ')
+}
+
 impl Iterator for HtmlTokenizer {
     type Item = HtmlToken;
 
+    /// 入力を読み終えた場合はNoneを返す。トークンの途中で入力が終わった場合も、状態を残してNoneを返すので、
+    /// push_inputで入力を追加すると続きから読める
     fn next(&mut self) -> Option<Self::Item> {
-        if self.pos >= self.input.len() {
-            return None;
-        }
-
         loop {
+            if !self.pending_text.is_empty() {
+                let text = core::mem::take(&mut self.pending_text);
+                return Some(HtmlToken::Text(Rc::from(text)));
+            }
+
+            if self.needs_more_input() {
+                return None;
+            }
+
+            // 入力を読み終えたら、途中まで読んだトークンを返してから、最後にEofを1つだけ返す
+            if self.at_end() {
+                if self.eof_emitted {
+                    return None;
+                }
+                if self.state != State::Data {
+                    match self.finish_at_eof() {
+                        Some(token) => return Some(token),
+                        None => continue,
+                    }
+                }
+                self.eof_emitted = true;
+                self.token_position = self.next_position;
+                return Some(HtmlToken::Eof);
+            }
+
             let c = match self.reconsume {
                 true => self.reconsume_input(),
                 false => self.consume_next_input(),
             };
 
+            // テキストの中で読んだ文字から、次のトークンが始まる
+            if matches!(
+                self.state,
+                State::Data | State::Rcdata | State::CdataSection
+            ) {
+                self.token_position = self.current_position;
+            }
+
             match self.state {
                 State::Data => {
                     if c == '<' {
@@ -209,11 +976,12 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
+                    if c == '&' {
+                        self.start_character_reference(State::Data);
+                        continue;
                     }
 
-                    return Some(HtmlToken::Char(c));
+                    return Some(self.text_run(c, |c| c == '<' || c == '&'));
                 }
                 State::TagOpen => {
                     if c == '/' {
@@ -221,6 +989,21 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
+                    if c == '!' {
+                        self.buf = String::new();
+                        self.state = State::MarkupDeclarationOpen;
+                        continue;
+                    }
+
+                    // "<?xml ...>"のような処理命令は、HTMLでは不正なコメントとして読む
+                    if c == '?' {
+                        self.error(ParseErrorKind::UnexpectedQuestionMarkInsteadOfTagName);
+                        self.reconsume = true;
+                        self.state = State::BogusComment;
+                        self.create_comment("");
+                        continue;
+                    }
+
                     if c.is_ascii_alphabetic() {
                         self.reconsume = true;
                         self.state = State::TagName;
@@ -228,27 +1011,35 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
-                    }
-
+                    // "a < b"のような"<"は、文字として扱う
+                    self.error(ParseErrorKind::InvalidFirstCharacterOfTagName);
                     self.reconsume = true;
                     self.state = State::Data;
+                    self.pending_text.push('<');
                 }
                 State::EndTagOpen => {
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
-                    }
-
                     if c.is_ascii_alphabetic() {
                         self.reconsume = true;
                         self.state = State::TagName;
                         self.create_tag(false);
                         continue;
                     }
+
+                    // "</>"は読み飛ばす
+                    if c == '>' {
+                        self.error(ParseErrorKind::MissingEndTagName);
+                        self.state = State::Data;
+                        continue;
+                    }
+
+                    // "</ p>"のような終了タグは、不正なコメントとして読む
+                    self.error(ParseErrorKind::InvalidFirstCharacterOfTagName);
+                    self.create_comment("");
+                    self.reconsume = true;
+                    self.state = State::BogusComment;
                 }
                 State::TagName => {
-                    if c == ' ' {
+                    if is_whitespace(c) {
                         self.state = State::BeforeAttributeName;
                         continue;
                     }
@@ -268,26 +1059,40 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
-                    }
-
                     self.append_tag_name(c);
+                    let run = self.consume_run(|c| {
+                        is_whitespace(c) || c == '/' || c == '>' || c.is_ascii_uppercase()
+                    });
+                    self.append_tag_name_run(run);
                 }
 
                 State::BeforeAttributeName => {
-                    if c == '/' || c == '>' || self.is_eof() {
+                    if is_whitespace(c) {
+                        // 空白文字は無視する
+                        continue;
+                    }
+
+                    if c == '/' || c == '>' {
                         self.reconsume = true;
                         self.state = State::AfterAttributeName;
                         continue;
                     }
 
+                    // "="で始まる属性の名前は、"="を名前の一部として読む
+                    if c == '=' {
+                        self.error(ParseErrorKind::UnexpectedEqualsSignBeforeAttributeName);
+                        self.start_new_attribute();
+                        self.append_attribute(c, /*is_name*/ true);
+                        self.state = State::AttributeName;
+                        continue;
+                    }
+
                     self.reconsume = true;
                     self.state = State::AttributeName;
                     self.start_new_attribute();
                 }
                 State::AttributeName => {
-                    if c == ' ' || c == '/' || c == '>' || self.is_eof() {
+                    if is_whitespace(c) || c == '/' || c == '>' {
                         self.reconsume = true;
                         self.state = State::AfterAttributeName;
                         continue;
@@ -303,10 +1108,20 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
+                    if c == '"' || c == '\'' || c == '<' {
+                        self.error(ParseErrorKind::UnexpectedCharacterInAttributeName);
+                    }
+
                     self.append_attribute(c, /*is_name*/ true);
+                    let run = self.consume_run(|c| {
+                        is_whitespace(c)
+                            || matches!(c, '/' | '>' | '=' | '"' | '\'' | '<')
+                            || c.is_ascii_uppercase()
+                    });
+                    self.append_attribute_run(run, /*is_name*/ true);
                 }
                 State::AfterAttributeName => {
-                    if c == ' ' {
+                    if is_whitespace(c) {
                         // 空白文字は無視する
                         continue;
                     }
@@ -326,20 +1141,23 @@ impl Iterator for HtmlTokenizer {
                         return self.take_latest_token();
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
-                    }
-
                     self.reconsume = true;
                     self.state = State::AttributeName;
                     self.start_new_attribute();
                 }
                 State::BeforeAttributeValue => {
-                    if c == ' ' {
+                    if is_whitespace(c) {
                         // 空白文字は無視する
                         continue;
                     }
 
+                    // "<p class=>"は、値が空の属性として扱う
+                    if c == '>' {
+                        self.error(ParseErrorKind::MissingAttributeValue);
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
                     if c == '"' {
                         self.state = State::AttributeValueDoubleQuoted;
                         continue;
@@ -359,11 +1177,14 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
+                    if c == '&' {
+                        self.start_character_reference(State::AttributeValueDoubleQuoted);
+                        continue;
                     }
 
                     self.append_attribute(c, /*is_name*/ false);
+                    let run = self.consume_run(|c| c == '"' || c == '&');
+                    self.append_attribute_run(run, /*is_name*/ false);
                 }
                 State::AttributeValueSingleQuoted => {
                     if c == '\'' {
@@ -371,14 +1192,17 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
+                    if c == '&' {
+                        self.start_character_reference(State::AttributeValueSingleQuoted);
+                        continue;
                     }
 
                     self.append_attribute(c, /*is_name*/ false);
+                    let run = self.consume_run(|c| c == '\'' || c == '&');
+                    self.append_attribute_run(run, /*is_name*/ false);
                 }
                 State::AttributeValueUnquoted => {
-                    if c == ' ' {
+                    if is_whitespace(c) {
                         self.state = State::BeforeAttributeName;
                         continue;
                     }
@@ -388,14 +1212,23 @@ impl Iterator for HtmlTokenizer {
                         return self.take_latest_token();
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
+                    if c == '&' {
+                        self.start_character_reference(State::AttributeValueUnquoted);
+                        continue;
+                    }
+
+                    if matches!(c, '"' | '\'' | '<' | '=' | '`') {
+                        self.error(ParseErrorKind::UnexpectedCharacterInUnquotedAttributeValue);
                     }
 
                     self.append_attribute(c, /*is_name*/ false);
+                    let run = self.consume_run(|c| {
+                        is_whitespace(c) || matches!(c, '>' | '&' | '"' | '\'' | '<' | '=' | '`')
+                    });
+                    self.append_attribute_run(run, /*is_name*/ false);
                 }
                 State::AfterAttributeValueQuoted => {
-                    if c == ' ' {
+                    if is_whitespace(c) {
                         self.state = State::BeforeAttributeName;
                         continue;
                     }
@@ -410,10 +1243,7 @@ impl Iterator for HtmlTokenizer {
                         return self.take_latest_token();
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
-                    }
-
+                    self.error(ParseErrorKind::MissingWhitespaceBetweenAttributes);
                     self.reconsume = true;
                     self.state = State::BeforeAttributeName;
                 }
@@ -424,52 +1254,60 @@ impl Iterator for HtmlTokenizer {
                         return self.take_latest_token();
                     }
 
-                    if self.is_eof() {
-                        // invalid parse error.
-                        return Some(HtmlToken::Eof);
-                    }
+                    // "<a / href=x>"の"/"は無視して、続きを属性として読む
+                    self.error(ParseErrorKind::UnexpectedSolidusInTag);
+                    self.reconsume = true;
+                    self.state = State::BeforeAttributeName;
                 }
-                State::ScriptData => {
-                    if c == '<' {
-                        self.state = State::ScriptDataLessThanSign;
+                State::Rcdata => {
+                    if c == '&' {
+                        self.start_character_reference(State::Rcdata);
                         continue;
                     }
 
-                    if self.is_eof() {
-                        return Some(HtmlToken::Eof);
+                    if c == '<' {
+                        self.state = State::RcdataLessThanSign;
+                        continue;
                     }
 
-                    return Some(HtmlToken::Char(c));
+                    return Some(self.text_run(c, |c| c == '<' || c == '&'));
                 }
-                State::ScriptDataLessThanSign => {
+                State::RcdataLessThanSign => {
                     if c == '/' {
-                        // 一時的なバッファを空文字でリセットする
                         self.buf = String::new();
-                        self.state = State::ScriptDataEndTagOpen;
+                        self.state = State::RcdataEndTagOpen;
                         continue;
                     }
 
                     self.reconsume = true;
-                    self.state = State::ScriptData;
-                    return Some(HtmlToken::Char('<'));
+                    self.state = State::Rcdata;
+                    self.pending_text.push('<');
                 }
-                State::ScriptDataEndTagOpen => {
+                State::RcdataEndTagOpen => {
                     if c.is_ascii_alphabetic() {
                         self.reconsume = true;
-                        self.state = State::ScriptDataEndTagName;
+                        self.state = State::RcdataEndTagName;
                         self.create_tag(false);
                         continue;
                     }
 
                     self.reconsume = true;
-                    self.state = State::ScriptData;
-                    // 仕様では、"<"と"/"の2つの文字トークンを返すとなっているが、
-                    // 私たちの実装ではnextメソッドからは一つのトークンしか返せない
-                    // ため、"<"のトークンのみを返す
-                    return Some(HtmlToken::Char('<'));
+                    self.state = State::Rcdata;
+                    self.pending_text.push_str("</");
                 }
-                State::ScriptDataEndTagName => {
-                    if c == '>' {
+                State::RcdataEndTagName => {
+                    // 開始タグと同じ名前の終了タグだけを、タグとして扱う
+                    if is_whitespace(c) && self.is_appropriate_end_tag() {
+                        self.state = State::BeforeAttributeName;
+                        continue;
+                    }
+
+                    if c == '/' && self.is_appropriate_end_tag() {
+                        self.state = State::SelfClosingStartTag;
+                        continue;
+                    }
+
+                    if c == '>' && self.is_appropriate_end_tag() {
                         self.state = State::Data;
                         return self.take_latest_token();
                     }
@@ -480,27 +1318,519 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
-                    self.state = State::TemporaryBuffer;
-                    self.buf = String::from("</") + &self.buf;
-                    self.buf.push(c);
-                    continue;
+                    self.flush_rcdata_end_tag();
                 }
-                State::TemporaryBuffer => {
+                State::ScriptData => {
+                    if c == '<' {
+                        self.state = State::ScriptDataLessThanSign;
+                        continue;
+                    }
+
+                    return Some(self.text_run(c, |c| c == '<'));
+                }
+                State::ScriptDataLessThanSign => {
+                    if c == '/' {
+                        // 一時的なバッファを空文字でリセットする
+                        self.buf = String::new();
+                        self.state = State::ScriptDataEndTagOpen;
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::ScriptData;
+                    self.pending_text.push('<');
+                }
+                State::ScriptDataEndTagOpen => {
+                    if c.is_ascii_alphabetic() {
+                        self.reconsume = true;
+                        self.state = State::ScriptDataEndTagName;
+                        self.create_tag(false);
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::ScriptData;
+                    self.pending_text.push_str("</");
+                }
+                State::ScriptDataEndTagName => {
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if c.is_ascii_alphabetic() {
+                        self.buf.push(c);
+                        self.append_tag_name(c.to_ascii_lowercase());
+                        continue;
+                    }
+
+                    self.state = State::TemporaryBuffer;
+                    self.buf = String::from("</") + &self.buf;
+                    self.buf.push(c);
+                    continue;
+                }
+                // 終了タグにならなかった"</"と読んだ名前を、まとめて文字にしてScriptDataに戻る
+                State::TemporaryBuffer => {
+                    self.reconsume = true;
+                    self.state = State::ScriptData;
+                    let text = core::mem::take(&mut self.buf);
+                    self.pending_text.push_str(&text);
+                }
+                State::MarkupDeclarationOpen => {
+                    // 続きが届くまで判断できないので、読んだ文字をbufに貯めて"--"と比べる
+                    self.buf.push(c);
+                    if self.buf == "--" {
+                        self.state = State::CommentStart;
+                        self.create_comment("");
+                        continue;
+                    }
+                    if self.buf == "-" {
+                        continue;
+                    }
+                    // "DOCTYPE"は大文字と小文字を区別しない
+                    let lowercase = self.buf.to_ascii_lowercase();
+                    if lowercase == "doctype" {
+                        self.buf = String::new();
+                        self.state = State::Doctype;
+                        self.create_doctype();
+                        continue;
+                    }
+                    if "doctype".starts_with(&lowercase) {
+                        continue;
+                    }
+                    // "[CDATA["は大文字と小文字を区別する
+                    if self.buf == "[CDATA[" {
+                        self.buf = String::new();
+                        if self.cdata_allowed {
+                            self.state = State::CdataSection;
+                        } else {
+                            // HTMLの要素の中では、"]]>"までではなく最初の">"までをコメントとして読む
+                            self.error(ParseErrorKind::CdataInHtmlContent);
+                            self.create_comment("[CDATA[");
+                            self.state = State::BogusComment;
+                        }
+                        continue;
+                    }
+                    if "[CDATA[".starts_with(&self.buf) {
+                        continue;
+                    }
+
+                    // "<!--"と"<!DOCTYPE"以外の"<!"は、不正なコメントとして読む。
+                    // 読んだ文字のうち最後の1文字は、不正なコメントの状態で読み直す
+                    self.error(ParseErrorKind::IncorrectlyOpenedComment);
+                    let mut data = core::mem::take(&mut self.buf);
+                    data.pop();
+                    self.create_comment(&data);
+                    self.reconsume = true;
+                    self.state = State::BogusComment;
+                }
+                State::CommentStart => {
+                    if c == '-' {
+                        self.state = State::CommentStartDash;
+                        continue;
+                    }
+
+                    // "<!-->"は、空のコメントとして扱う
+                    if c == '>' {
+                        self.error(ParseErrorKind::AbruptClosingOfEmptyComment);
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+                State::CommentStartDash => {
+                    if c == '-' {
+                        self.state = State::CommentEnd;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.error(ParseErrorKind::AbruptClosingOfEmptyComment);
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.append_comment("-");
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+                State::Comment => {
+                    if c == '-' {
+                        self.state = State::CommentEndDash;
+                        continue;
+                    }
+
+                    self.append_comment(c.encode_utf8(&mut [0; 4]));
+                    let run = self.consume_run(|c| c == '-');
+                    self.append_comment_run(run);
+                }
+                State::CommentEndDash => {
+                    if c == '-' {
+                        self.state = State::CommentEnd;
+                        continue;
+                    }
+
+                    self.append_comment("-");
                     self.reconsume = true;
+                    self.state = State::Comment;
+                }
+                State::CommentEnd => {
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
 
-                    if self.buf.chars().count() == 0 {
-                        self.state = State::ScriptData;
+                    if c == '!' {
+                        self.state = State::CommentEndBang;
                         continue;
                     }
 
-                    // remove the first char
-                    let c = self
-                        .buf
-                        .chars()
-                        .nth(0)
-                        .expect("self.buf should have at least 1 char");
-                    self.buf.remove(0);
-                    return Some(HtmlToken::Char(c));
+                    // "--->"のように"-"が続く場合は、最後の2つだけがコメントの終わりになる
+                    if c == '-' {
+                        self.append_comment("-");
+                        continue;
+                    }
+
+                    self.append_comment("--");
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+                State::CommentEndBang => {
+                    if c == '-' {
+                        self.append_comment("--!");
+                        self.state = State::CommentEndDash;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.error(ParseErrorKind::IncorrectlyClosedComment);
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.append_comment("--!");
+                    self.reconsume = true;
+                    self.state = State::Comment;
+                }
+                State::BogusComment => {
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.append_comment(&c.to_string());
+                }
+                State::CdataSection => {
+                    if c == ']' {
+                        self.state = State::CdataSectionBracket;
+                        continue;
+                    }
+
+                    return Some(self.text_run(c, |c| c == ']'));
+                }
+                State::CdataSectionBracket => {
+                    if c == ']' {
+                        self.state = State::CdataSectionEnd;
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::CdataSection;
+                    self.pending_text.push(']');
+                }
+                State::CdataSectionEnd => {
+                    // "]]]>"のように"]"が続く場合は、最後の2つだけがCDATAセクションの終わりになる
+                    if c == ']' {
+                        self.pending_text.push(']');
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        continue;
+                    }
+
+                    self.pending_text.push_str("]]");
+                    self.reconsume = true;
+                    self.state = State::CdataSection;
+                }
+                State::Doctype => {
+                    self.state = State::BeforeDoctypeName;
+                    if !is_whitespace(c) {
+                        self.reconsume = true;
+                    }
+                }
+                State::BeforeDoctypeName => {
+                    if is_whitespace(c) {
+                        continue;
+                    }
+
+                    // "<!DOCTYPE>"のように名前がない場合は、互換モードにする
+                    if c == '>' {
+                        self.error(ParseErrorKind::MissingDoctypeName);
+                        self.set_force_quirks_flag();
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.append_doctype_name(c);
+                    self.state = State::DoctypeName;
+                }
+                State::DoctypeName => {
+                    if is_whitespace(c) {
+                        self.buf = String::new();
+                        self.state = State::AfterDoctypeName;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.append_doctype_name(c);
+                }
+                State::AfterDoctypeName => {
+                    if self.buf.is_empty() {
+                        if is_whitespace(c) {
+                            continue;
+                        }
+
+                        if c == '>' {
+                            self.state = State::Data;
+                            return self.take_latest_token();
+                        }
+                    }
+
+                    // 続きが届くまで判断できないので、読んだ文字をbufに貯めて"PUBLIC"と"SYSTEM"と比べる。
+                    // 大文字と小文字は区別しない
+                    self.buf.push(c.to_ascii_lowercase());
+                    if self.buf == "public" {
+                        self.buf = String::new();
+                        self.state = State::AfterDoctypePublicKeyword;
+                        continue;
+                    }
+                    if self.buf == "system" {
+                        self.buf = String::new();
+                        self.state = State::AfterDoctypeSystemKeyword;
+                        continue;
+                    }
+                    if "public".starts_with(self.buf.as_str())
+                        || "system".starts_with(self.buf.as_str())
+                    {
+                        continue;
+                    }
+
+                    self.buf = String::new();
+                    self.set_force_quirks_flag();
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+                State::AfterDoctypePublicKeyword | State::BeforeDoctypePublicIdentifier => {
+                    if is_whitespace(c) {
+                        self.state = State::BeforeDoctypePublicIdentifier;
+                        continue;
+                    }
+
+                    if c == '"' || c == '\'' {
+                        self.start_doctype_identifier(true);
+                        self.state = if c == '"' {
+                            State::DoctypePublicIdentifierDoubleQuoted
+                        } else {
+                            State::DoctypePublicIdentifierSingleQuoted
+                        };
+                        continue;
+                    }
+
+                    self.set_force_quirks_flag();
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+                State::DoctypePublicIdentifierDoubleQuoted
+                | State::DoctypePublicIdentifierSingleQuoted => {
+                    let quote = if self.state == State::DoctypePublicIdentifierDoubleQuoted {
+                        '"'
+                    } else {
+                        '\''
+                    };
+                    if c == quote {
+                        self.state = State::AfterDoctypePublicIdentifier;
+                        continue;
+                    }
+
+                    // 閉じる引用符の前に">"が来た場合は、そこでDOCTYPEを終える
+                    if c == '>' {
+                        self.set_force_quirks_flag();
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.append_doctype_identifier(true, c);
+                }
+                State::AfterDoctypePublicIdentifier
+                | State::BetweenDoctypePublicAndSystemIdentifiers => {
+                    if is_whitespace(c) {
+                        self.state = State::BetweenDoctypePublicAndSystemIdentifiers;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if c == '"' || c == '\'' {
+                        self.start_doctype_identifier(false);
+                        self.state = if c == '"' {
+                            State::DoctypeSystemIdentifierDoubleQuoted
+                        } else {
+                            State::DoctypeSystemIdentifierSingleQuoted
+                        };
+                        continue;
+                    }
+
+                    self.set_force_quirks_flag();
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+                State::AfterDoctypeSystemKeyword | State::BeforeDoctypeSystemIdentifier => {
+                    if is_whitespace(c) {
+                        self.state = State::BeforeDoctypeSystemIdentifier;
+                        continue;
+                    }
+
+                    if c == '"' || c == '\'' {
+                        self.start_doctype_identifier(false);
+                        self.state = if c == '"' {
+                            State::DoctypeSystemIdentifierDoubleQuoted
+                        } else {
+                            State::DoctypeSystemIdentifierSingleQuoted
+                        };
+                        continue;
+                    }
+
+                    self.set_force_quirks_flag();
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+                State::DoctypeSystemIdentifierDoubleQuoted
+                | State::DoctypeSystemIdentifierSingleQuoted => {
+                    let quote = if self.state == State::DoctypeSystemIdentifierDoubleQuoted {
+                        '"'
+                    } else {
+                        '\''
+                    };
+                    if c == quote {
+                        self.state = State::AfterDoctypeSystemIdentifier;
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.set_force_quirks_flag();
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    self.append_doctype_identifier(false, c);
+                }
+                State::AfterDoctypeSystemIdentifier => {
+                    if is_whitespace(c) {
+                        continue;
+                    }
+
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    // システム識別子の後ろの余分な文字は無視する。互換モードにはしない
+                    self.reconsume = true;
+                    self.state = State::BogusDoctype;
+                }
+                State::BogusDoctype => {
+                    if c == '>' {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+                }
+                State::CharacterReference => {
+                    if c.is_ascii_alphanumeric() {
+                        self.reconsume = true;
+                        self.state = State::NamedCharacterReference;
+                        continue;
+                    }
+
+                    if c == '#' {
+                        self.buf.push(c);
+                        self.state = State::NumericCharacterReference;
+                        continue;
+                    }
+
+                    // "&"の後に名前も"#"も続かない場合は、"&"をそのまま文字として扱う
+                    let text = core::mem::take(&mut self.buf);
+                    self.flush_code_points(&text);
+                    self.reconsume = true;
+                }
+                State::NamedCharacterReference => {
+                    if c.is_ascii_alphanumeric()
+                        && self.buf.len() <= MAX_CHARACTER_REFERENCE_NAME_LEN
+                    {
+                        self.buf.push(c);
+                        continue;
+                    }
+
+                    if c == ';' {
+                        match named_character_reference(&self.buf[1..]) {
+                            Some(decoded) => {
+                                self.flush_code_points(&decoded.to_string());
+                            }
+                            None => {
+                                // 知らない名前は、"&"と";"を含めてそのまま文字として扱う
+                                self.error(ParseErrorKind::UnknownNamedCharacterReference);
+                                let mut text = core::mem::take(&mut self.buf);
+                                text.push(';');
+                                self.flush_code_points(&text);
+                            }
+                        }
+                        continue;
+                    }
+
+                    self.finish_named_reference(c);
+                    self.reconsume = true;
+                }
+                State::NumericCharacterReference => {
+                    if c == 'x' || c == 'X' {
+                        self.buf.push(c);
+                        self.state = State::HexadecimalCharacterReference;
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::DecimalCharacterReference;
+                }
+                State::HexadecimalCharacterReference | State::DecimalCharacterReference => {
+                    let radix = match self.state {
+                        State::HexadecimalCharacterReference => 16,
+                        _ => 10,
+                    };
+                    if c.is_digit(radix) {
+                        self.buf.push(c);
+                        continue;
+                    }
+
+                    // ";"を省略した場合と、数字が続かなかった場合は、続く文字を戻った状態で読み直す
+                    let terminated = self.finish_numeric_reference(c == ';');
+                    self.reconsume = !terminated;
                 }
             }
         }
@@ -517,6 +1847,7 @@ mod tests {
     fn test_empty() {
         let html = "".to_string();
         let mut tokenizer = HtmlTokenizer::new(html);
+        assert_eq!(Some(HtmlToken::Eof), tokenizer.next());
         assert!(tokenizer.next().is_none());
     }
 
@@ -526,17 +1857,38 @@ mod tests {
         let mut tokenizer = HtmlTokenizer::new(html);
         let expected = [
             HtmlToken::StartTag {
-                tag: "body".to_string(),
+                tag: "body".into(),
                 self_closing: false,
                 attributes: Vec::new(),
             },
-            HtmlToken::EndTag {
-                tag: "body".to_string(),
-            },
+            HtmlToken::EndTag { tag: "body".into() },
+        ];
+        for e in expected {
+            assert_eq!(Some(e), tokenizer.next());
+        }
+    }
+
+    #[test]
+    fn test_end_tag_with_attributes() {
+        // 終了タグの属性と"/"は捨てる
+        let html = "</p class='a'></br/>".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        let expected = [
+            HtmlToken::EndTag { tag: "p".into() },
+            HtmlToken::EndTag { tag: "br".into() },
+            HtmlToken::Eof,
         ];
         for e in expected {
             assert_eq!(Some(e), tokenizer.next());
         }
+        let errors: Vec<ParseErrorKind> = tokenizer.errors().iter().map(|e| e.kind()).collect();
+        assert_eq!(
+            vec![
+                ParseErrorKind::EndTagWithAttributes,
+                ParseErrorKind::EndTagWithTrailingSolidus
+            ],
+            errors
+        );
     }
 
     #[test]
@@ -566,13 +1918,11 @@ mod tests {
 
         let expected = [
             HtmlToken::StartTag {
-                tag: "p".to_string(),
+                tag: "p".into(),
                 self_closing: false,
                 attributes: vec![attr1, attr2, attr3],
             },
-            HtmlToken::EndTag {
-                tag: "p".to_string(),
-            },
+            HtmlToken::EndTag { tag: "p".into() },
         ];
         for e in expected {
             assert_eq!(Some(e), tokenizer.next());
@@ -584,7 +1934,7 @@ mod tests {
         let html = "<img />".to_string();
         let mut tokenizer = HtmlTokenizer::new(html);
         let expected = [HtmlToken::StartTag {
-            tag: "img".to_string(),
+            tag: "img".into(),
             self_closing: true,
             attributes: Vec::new(),
         }];
@@ -593,30 +1943,480 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_position() {
+        let html = "<p>\n  a&amp;<!-- c -->\n</p>".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        let mut positions = Vec::new();
+        while let Some(token) = tokenizer.next() {
+            positions.push((token, tokenizer.position()));
+        }
+
+        assert_eq!(
+            (
+                HtmlToken::StartTag {
+                    tag: "p".into(),
+                    self_closing: false,
+                    attributes: Vec::new(),
+                },
+                SourcePosition::new(1, 1)
+            ),
+            positions[0]
+        );
+        assert_eq!(
+            (HtmlToken::Text("\n  ".into()), SourcePosition::new(1, 4)),
+            positions[1]
+        );
+        assert_eq!(
+            (HtmlToken::Text("a".into()), SourcePosition::new(2, 3)),
+            positions[2]
+        );
+        assert_eq!(
+            (HtmlToken::Text("&".into()), SourcePosition::new(2, 4)),
+            positions[3]
+        );
+        assert_eq!(
+            (
+                HtmlToken::Comment(" c ".to_string()),
+                SourcePosition::new(2, 9)
+            ),
+            positions[4]
+        );
+        assert_eq!(
+            (
+                HtmlToken::EndTag { tag: "p".into() },
+                SourcePosition::new(3, 1)
+            ),
+            positions[6]
+        );
+        assert_eq!("line 3, column 1", positions[6].1.to_string());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let html = "<p a=1 a=2 b=>x < y</ p>&#0;&copy<!-->".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        let mut attr = Attribute::new();
+        attr.add_char('a', true);
+        attr.add_char('1', false);
+        let mut empty = Attribute::new();
+        empty.add_char('b', true);
+        // 同じ名前の属性は最初のものだけを残し、値のない属性は空の値にする
+        assert_eq!(
+            Some(HtmlToken::StartTag {
+                tag: "p".into(),
+                self_closing: false,
+                attributes: vec![attr, empty],
+            }),
+            tokenizer.next()
+        );
+        let tokens: Vec<HtmlToken> = tokenizer.by_ref().collect();
+        // "<"は文字になり、"</ p>"はコメントになる
+        assert_eq!(Some(&HtmlToken::Text("<".into())), tokens.get(1));
+        assert!(tokens.contains(&HtmlToken::Comment(" p".to_string())));
+
+        let errors: Vec<(ParseErrorKind, SourcePosition)> = tokenizer
+            .errors()
+            .iter()
+            .map(|e| (e.kind(), e.position()))
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    ParseErrorKind::MissingAttributeValue,
+                    SourcePosition::new(1, 14)
+                ),
+                (
+                    ParseErrorKind::DuplicateAttribute,
+                    SourcePosition::new(1, 1)
+                ),
+                (
+                    ParseErrorKind::InvalidFirstCharacterOfTagName,
+                    SourcePosition::new(1, 18)
+                ),
+                (
+                    ParseErrorKind::InvalidFirstCharacterOfTagName,
+                    SourcePosition::new(1, 22)
+                ),
+                (
+                    ParseErrorKind::NullCharacterReference,
+                    SourcePosition::new(1, 28)
+                ),
+                (
+                    ParseErrorKind::MissingSemicolonAfterCharacterReference,
+                    SourcePosition::new(1, 34)
+                ),
+                (
+                    ParseErrorKind::AbruptClosingOfEmptyComment,
+                    SourcePosition::new(1, 38)
+                ),
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn test_push_input() {
+        let mut tokenizer = HtmlTokenizer::streaming();
+        tokenizer.push_input("<p cl");
+        // タグの途中で入力が終わった場合は、続きが届くまでトークンを返さない
+        assert_eq!(None, tokenizer.next());
+        assert!(tokenizer.needs_more_input());
+
+        tokenizer.push_input("ass=\"a\">t");
+        assert!(!tokenizer.needs_more_input());
+        let mut attr = Attribute::new();
+        for c in "class".chars() {
+            attr.add_char(c, true);
+        }
+        attr.add_char('a', false);
+        assert_eq!(
+            Some(HtmlToken::StartTag {
+                tag: "p".into(),
+                self_closing: false,
+                attributes: vec![attr],
+            }),
+            tokenizer.next()
+        );
+        // 読み終えた入力は、次の入力に移るときに捨てる
+        assert_eq!("ass=\"a\">t", tokenizer.input);
+        assert_eq!(Some(HtmlToken::Text("t".into())), tokenizer.next());
+        assert_eq!(None, tokenizer.next());
+
+        tokenizer.push_input("</p>");
+        assert_eq!(
+            Some(HtmlToken::EndTag { tag: "p".into() }),
+            tokenizer.next()
+        );
+        assert_eq!(None, tokenizer.next());
+    }
+
+    #[test]
+    fn test_eof() {
+        let tokens = |html: &str| HtmlTokenizer::new(html.to_string()).collect::<Vec<_>>();
+        let text = |s: &str| HtmlToken::Text(s.into());
+        // 最後の文字も返し、最後にEofを1つだけ返す
+        assert_eq!(vec![text("ab"), HtmlToken::Eof], tokens("ab"));
+        // "<"や"</"で終わった場合は、文字として返す
+        assert_eq!(vec![text("a"), text("</"), HtmlToken::Eof], tokens("a</"));
+        assert_eq!(vec![text("a"), text("<"), HtmlToken::Eof], tokens("a<"));
+        // 閉じていないタグは捨てる
+        for html in [
+            "a<p",
+            "a<p class",
+            "a<p class=",
+            "a<p class=\"x",
+            "a<p class=x&amp",
+            "a<br/",
+        ] {
+            assert_eq!(vec![text("a"), HtmlToken::Eof], tokens(html), "{}", html);
+        }
+        // 閉じていないコメントとDOCTYPEは、そこまでを返す
+        assert_eq!(
+            vec![HtmlToken::Comment(" x ".to_string()), HtmlToken::Eof],
+            tokens("<!-- x -")
+        );
+        assert_eq!(
+            vec![HtmlToken::Comment("DOC".to_string()), HtmlToken::Eof],
+            tokens("<!DOC")
+        );
+        assert_eq!(
+            vec![
+                HtmlToken::Doctype {
+                    name: Some("html".to_string()),
+                    public_id: None,
+                    system_id: None,
+                    force_quirks: true,
+                },
+                HtmlToken::Eof
+            ],
+            tokens("<!DOCTYPE html")
+        );
+        // 文字参照は、読んだところまでで置き換える
+        assert_eq!(
+            vec![text("x"), text("&"), text("©"), text("&#"), HtmlToken::Eof],
+            tokens("x&&copy&#")
+        );
+        assert_eq!(vec![text("\u{41}"), HtmlToken::Eof], tokens("&#65"));
+
+        let mut tokenizer = HtmlTokenizer::new("<p a='1".to_string());
+        assert_eq!(Some(HtmlToken::Eof), tokenizer.next());
+        assert_eq!(None, tokenizer.next());
+        assert_eq!(
+            vec![ParseErrorKind::EofInTag],
+            tokenizer
+                .errors()
+                .iter()
+                .map(|e| e.kind())
+                .collect::<Vec<_>>()
+        );
+
+        // 受信しながら読む場合は、close_inputを呼ぶまで途中のトークンを残す
+        let mut tokenizer = HtmlTokenizer::streaming();
+        tokenizer.push_input("<p>a</");
+        assert_eq!(
+            Some(HtmlToken::StartTag {
+                tag: "p".into(),
+                self_closing: false,
+                attributes: Vec::new(),
+            }),
+            tokenizer.next()
+        );
+        assert_eq!(Some(HtmlToken::Text("a".into())), tokenizer.next());
+        assert_eq!(None, tokenizer.next());
+        tokenizer.close_input();
+        assert_eq!(Some(HtmlToken::Text("</".into())), tokenizer.next());
+        assert_eq!(Some(HtmlToken::Eof), tokenizer.next());
+        assert_eq!(None, tokenizer.next());
+    }
+
+    #[test]
+    fn test_multibyte_input() {
+        let html = "<P Title='caf&eacute; 日本'><!-- コメント -->語".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        let mut attr = Attribute::new();
+        attr.add_str("title", true);
+        attr.add_str("café 日本", false);
+        assert_eq!(
+            Some(HtmlToken::StartTag {
+                tag: "p".into(),
+                self_closing: false,
+                attributes: vec![attr],
+            }),
+            tokenizer.next()
+        );
+        assert_eq!(
+            Some(HtmlToken::Comment(" コメント ".to_string())),
+            tokenizer.next()
+        );
+        assert_eq!(Some(HtmlToken::Text("語".into())), tokenizer.next());
+        // 列は、バイトではなく文字で数える
+        assert_eq!(SourcePosition::new(1, 40), tokenizer.position());
+        assert_eq!(Some(HtmlToken::Eof), tokenizer.next());
+    }
+
+    #[test]
+    fn test_character_reference() {
+        let tokenizer =
+            HtmlTokenizer::new("a&amp;b &lt;&#x26;&#38;&copy &copyright &bogus; &#; &".to_string());
+        let text: String = tokenizer
+            .filter(|token| *token != HtmlToken::Eof)
+            .map(|token| match token {
+                HtmlToken::Text(text) => text.to_string(),
+                _ => panic!("unexpected token {:?}", token),
+            })
+            .collect();
+        // 入力の最後の"&"は、文字参照ではなく文字として扱う
+        assert_eq!("a&b <&&© ©right &bogus; &#; &", text);
+    }
+
+    #[test]
+    fn test_character_reference_in_attribute() {
+        let mut tokenizer = HtmlTokenizer::new(
+            "<a href=\"?a=1&amp;b=2&copy=3\" title='&lt;&#x3E;' alt=x&quot;>".to_string(),
+        );
+        let attributes = match tokenizer.next() {
+            Some(HtmlToken::StartTag { attributes, .. }) => attributes,
+            token => panic!("unexpected token {:?}", token),
+        };
+        let values: Vec<String> = attributes.iter().map(|a| a.value()).collect();
+        // 属性値の中では、"="が続く"&copy"を文字参照として扱わない
+        assert_eq!(vec!["?a=1&b=2&copy=3", "<>", "x\""], values);
+    }
+
+    #[test]
+    fn test_character_reference_across_chunks() {
+        let mut tokenizer = HtmlTokenizer::streaming();
+        tokenizer.push_input("x&am");
+        assert_eq!(Some(HtmlToken::Text("x".into())), tokenizer.next());
+        assert_eq!(None, tokenizer.next());
+
+        tokenizer.push_input("p;y");
+        assert_eq!(Some(HtmlToken::Text("&".into())), tokenizer.next());
+        assert_eq!(Some(HtmlToken::Text("y".into())), tokenizer.next());
+    }
+
+    #[test]
+    fn test_comment() {
+        let tokenizer = HtmlTokenizer::new(
+            "a<!-- x -- y -->b<!---->c<!-->d<!--- z --->e<!DOC html>".to_string(),
+        );
+        let expected = vec![
+            HtmlToken::Text("a".into()),
+            HtmlToken::Comment(" x -- y ".to_string()),
+            HtmlToken::Text("b".into()),
+            HtmlToken::Comment("".to_string()),
+            HtmlToken::Text("c".into()),
+            HtmlToken::Comment("".to_string()),
+            HtmlToken::Text("d".into()),
+            HtmlToken::Comment("- z -".to_string()),
+            HtmlToken::Text("e".into()),
+            // "<!DOCTYPE"の途中までしか一致しない場合は、不正なコメントとして読む
+            HtmlToken::Comment("DOC html".to_string()),
+            HtmlToken::Eof,
+        ];
+        assert_eq!(expected, tokenizer.collect::<Vec<_>>());
+    }
+
+    fn doctype(
+        name: Option<&str>,
+        public_id: Option<&str>,
+        system_id: Option<&str>,
+        force_quirks: bool,
+    ) -> HtmlToken {
+        HtmlToken::Doctype {
+            name: name.map(|s| s.to_string()),
+            public_id: public_id.map(|s| s.to_string()),
+            system_id: system_id.map(|s| s.to_string()),
+            force_quirks,
+        }
+    }
+
+    #[test]
+    fn test_cdata_section() {
+        let html = "<![CDATA[a > b]]]]><![cdata[x]]>".to_string();
+        // HTMLの要素の中では、最初の">"までが不正なコメントになる
+        let tokenizer = HtmlTokenizer::new(html.clone());
+        let expected = vec![
+            HtmlToken::Comment("[CDATA[a ".to_string()),
+            HtmlToken::Text(" ".into()),
+            HtmlToken::Text("b]]]]>".into()),
+            HtmlToken::Comment("[cdata[x]]".to_string()),
+            HtmlToken::Eof,
+        ];
+        assert_eq!(expected, tokenizer.collect::<Vec<_>>());
+
+        let mut tokenizer = HtmlTokenizer::new(html);
+        tokenizer.set_cdata_allowed(true);
+        let text: String = tokenizer
+            .by_ref()
+            .map_while(|t| match t {
+                HtmlToken::Text(text) => Some(text.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!("a > b]]", text);
+        assert_eq!(
+            vec![ParseErrorKind::IncorrectlyOpenedComment],
+            tokenizer
+                .errors()
+                .iter()
+                .map(|e| e.kind())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_doctype() {
+        let cases = [
+            ("<!DOCTYPE html>", doctype(Some("html"), None, None, false)),
+            ("<!doctype HTML >", doctype(Some("html"), None, None, false)),
+            ("<!DOCTYPE>", doctype(None, None, None, true)),
+            (
+                "<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01//EN\" 'http://www.w3.org/TR/html4/strict.dtd'>",
+                doctype(
+                    Some("html"),
+                    Some("-//W3C//DTD HTML 4.01//EN"),
+                    Some("http://www.w3.org/TR/html4/strict.dtd"),
+                    false,
+                ),
+            ),
+            (
+                "<!DOCTYPE html system \"about:legacy-compat\">",
+                doctype(Some("html"), None, Some("about:legacy-compat"), false),
+            ),
+            (
+                "<!DOCTYPE html PUBLIC \"unterminated>",
+                doctype(Some("html"), Some("unterminated"), None, true),
+            ),
+            ("<!DOCTYPE html bogus>", doctype(Some("html"), None, None, true)),
+        ];
+        for (html, expected) in cases {
+            let mut tokenizer = HtmlTokenizer::new(html.to_string());
+            assert_eq!(Some(expected), tokenizer.next(), "{}", html);
+            assert_eq!(Some(HtmlToken::Eof), tokenizer.next(), "{}", html);
+        }
+    }
+
+    #[test]
+    fn test_doctype_across_chunks() {
+        let mut tokenizer = HtmlTokenizer::streaming();
+        tokenizer.push_input("<!DOC");
+        assert_eq!(None, tokenizer.next());
+        tokenizer.push_input("TYPE html PUB");
+        assert_eq!(None, tokenizer.next());
+        tokenizer.push_input("LIC \"x\"><p>");
+        assert_eq!(
+            Some(doctype(Some("html"), Some("x"), None, false)),
+            tokenizer.next()
+        );
+    }
+
+    #[test]
+    fn test_comment_across_chunks() {
+        let mut tokenizer = HtmlTokenizer::streaming();
+        tokenizer.push_input("<!");
+        assert_eq!(None, tokenizer.next());
+        tokenizer.push_input("-");
+        assert_eq!(None, tokenizer.next());
+        tokenizer.push_input("- <p> -");
+        assert_eq!(None, tokenizer.next());
+        tokenizer.push_input("->");
+        assert_eq!(
+            Some(HtmlToken::Comment(" <p> ".to_string())),
+            tokenizer.next()
+        );
+    }
+
     #[test]
     fn test_script_tag() {
         let html = "<script>js code;</script>".to_string();
         let mut tokenizer = HtmlTokenizer::new(html);
         let expected = [
             HtmlToken::StartTag {
-                tag: "script".to_string(),
+                tag: "script".into(),
                 self_closing: false,
                 attributes: Vec::new(),
             },
-            HtmlToken::Char('j'),
-            HtmlToken::Char('s'),
-            HtmlToken::Char(' '),
-            HtmlToken::Char('c'),
-            HtmlToken::Char('o'),
-            HtmlToken::Char('d'),
-            HtmlToken::Char('e'),
-            HtmlToken::Char(';'),
+            HtmlToken::Text("js code;".into()),
             HtmlToken::EndTag {
-                tag: "script".to_string(),
+                tag: "script".into(),
             },
         ];
         for e in expected {
             assert_eq!(Some(e), tokenizer.next());
         }
     }
+
+    #[test]
+    fn test_rcdata() {
+        let html = "<title>a<b>&amp;</b></TITLE>c".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        assert_eq!(
+            Some(HtmlToken::StartTag {
+                tag: "title".into(),
+                self_closing: false,
+                attributes: Vec::new(),
+            }),
+            tokenizer.next()
+        );
+
+        // RCDATAの中では、開始タグと同じ名前の終了タグだけがタグになり、文字参照は置き換える
+        tokenizer.switch_to(State::Rcdata);
+        let mut text = String::new();
+        let end_tag = loop {
+            match tokenizer.next() {
+                Some(HtmlToken::Text(run)) => text.push_str(&run),
+                token => break token,
+            }
+        };
+        assert_eq!("a<b>&</b>", text);
+        assert_eq!(
+            Some(HtmlToken::EndTag {
+                tag: "title".into(),
+            }),
+            end_tag
+        );
+        assert_eq!(Some(HtmlToken::Text("c".into())), tokenizer.next());
+    }
 }
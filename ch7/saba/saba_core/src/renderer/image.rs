@@ -0,0 +1,418 @@
+//! 画像のデコード。大きな画像を一度にデコードするとイベントループが止まってしまうので、
+//! IMAGE_DECODE_ROWS_PER_TASK行ずつのタスクに分けてデコードし、デコードできた行から描画する。
+//! デコーダーは今のところバイナリ形式のPPMだけ
+
+use crate::constants::IMAGE_DECODE_ROWS_PER_TASK;
+use crate::error::Error;
+use crate::task::TaskPriority;
+use crate::task::TaskQueue;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+/// デコードした画像。上の行から順にデコードされる
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    /// 0xrrggbbの形式の色を、左上から行ごとに並べたもの
+    pixels: Vec<u32>,
+    /// デコードが終わった行の数。これより下の行は描画しない
+    decoded_rows: usize,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+            decoded_rows: 0,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn decoded_rows(&self) -> usize {
+        self.decoded_rows
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.decoded_rows >= self.height
+    }
+
+    /// デコードが終わっていない行の画素はNoneを返す
+    pub fn pixel(&self, x: usize, y: usize) -> Option<u32> {
+        if x >= self.width || y >= self.decoded_rows {
+            return None;
+        }
+        Some(self.pixels[y * self.width + x])
+    }
+
+    /// 最近傍法で、幅`width`、高さ`height`に拡大・縮小した画像を返す。
+    /// 元の画像でデコードが終わっていない行から作る行は、デコードが終わっていないものとする
+    pub fn scaled(&self, width: usize, height: usize) -> Image {
+        let mut image = Image::new(width, height);
+        if self.width == 0 || self.height == 0 {
+            return image;
+        }
+
+        for y in 0..height {
+            let source_y = y * self.height / height;
+            if source_y >= self.decoded_rows {
+                break;
+            }
+            for x in 0..width {
+                let source_x = x * self.width / width;
+                image.pixels[y * width + x] = self.pixels[source_y * self.width + source_x];
+            }
+            image.decoded_rows = y + 1;
+        }
+        image
+    }
+
+    /// (x, y)を左上とする、幅`width`、高さ`height`の部分を切り出す。画像からはみ出す部分は含めない
+    pub fn cropped(&self, x: usize, y: usize, width: usize, height: usize) -> Image {
+        let width = width.min(self.width.saturating_sub(x));
+        let height = height.min(self.height.saturating_sub(y));
+        let mut image = Image::new(width, height);
+
+        for row in 0..height {
+            let start = (y + row) * self.width + x;
+            image.pixels[row * width..(row + 1) * width]
+                .copy_from_slice(&self.pixels[start..start + width]);
+        }
+        image.decoded_rows = self.decoded_rows.saturating_sub(y).min(height);
+        image
+    }
+
+    /// y行目を、同じ色が続く範囲ごとに(始まりのx, 幅, 色)の組にする。
+    /// 1画素ずつ描くより少ない回数で描ける。デコードが終わっていない行は空になる
+    pub fn row_spans(&self, y: usize) -> Vec<(usize, usize, u32)> {
+        let mut spans: Vec<(usize, usize, u32)> = Vec::new();
+        if y >= self.decoded_rows {
+            return spans;
+        }
+
+        for x in 0..self.width {
+            let color = self.pixels[y * self.width + x];
+            match spans.last_mut() {
+                Some((_, width, c)) if *c == color => *width += 1,
+                _ => spans.push((x, 1, color)),
+            }
+        }
+        spans
+    }
+}
+
+/// 画像を数行ずつデコードする
+pub trait ImageDecoder {
+    /// デコード中の画像。大きさはデコードを始める前にわかっている
+    fn image(&self) -> &Image;
+
+    /// 最大で`rows`行をデコードする。最後の行までデコードしたらtrueを返す
+    fn decode_rows(&mut self, rows: usize) -> Result<bool, Error>;
+}
+
+/// バイナリ形式のPPM(P6)のデコーダー。最大値が255以下の、1画素3バイトの画像を扱う
+/// https://netpbm.sourceforge.net/doc/ppm.html
+#[derive(Debug, Clone)]
+pub struct PpmDecoder {
+    data: Vec<u8>,
+    /// 次にデコードする画素のデータの位置
+    pos: usize,
+    max_value: u32,
+    image: Image,
+}
+
+impl PpmDecoder {
+    /// ヘッダを読み、画像の大きさを決める。画素のデータはまだデコードしない
+    pub fn new(data: Vec<u8>) -> Result<Self, Error> {
+        if !data.starts_with(b"P6") {
+            return Err(Error::UnexpectedInput(
+                "image is not a binary PPM".to_string(),
+            ));
+        }
+
+        let mut pos = 2;
+        let mut header = [0usize; 3];
+        for value in header.iter_mut() {
+            *value = read_header_number(&data, &mut pos)?;
+        }
+        let [width, height, max_value] = header;
+        if max_value == 0 || max_value > 255 {
+            return Err(Error::UnexpectedInput(format!(
+                "unsupported PPM max value {}",
+                max_value
+            )));
+        }
+        // 画素を確保する前に、データに収まらない大きさを拒否する。1画素は3バイトなので、
+        // データの長さの3分の1より多い画素はデコードできない
+        match width.checked_mul(height) {
+            Some(pixels) if pixels <= data.len() / 3 => {}
+            _ => {
+                return Err(Error::UnexpectedInput(format!(
+                    "PPM size {}x{} exceeds the data",
+                    width, height
+                )))
+            }
+        }
+        // ヘッダの最後の数字の後には、空白が1文字だけある
+        pos += 1;
+
+        Ok(Self {
+            data,
+            pos,
+            max_value: max_value as u32,
+            image: Image::new(width, height),
+        })
+    }
+
+    fn scale(&self, value: u8) -> u32 {
+        value as u32 * 255 / self.max_value
+    }
+}
+
+impl ImageDecoder for PpmDecoder {
+    fn image(&self) -> &Image {
+        &self.image
+    }
+
+    fn decode_rows(&mut self, rows: usize) -> Result<bool, Error> {
+        let width = self.image.width;
+        let end = (self.image.decoded_rows + rows).min(self.image.height);
+
+        for y in self.image.decoded_rows..end {
+            let row_end = self.pos + width * 3;
+            if row_end > self.data.len() {
+                return Err(Error::UnexpectedInput(format!(
+                    "PPM data ends at row {}",
+                    y
+                )));
+            }
+            for x in 0..width {
+                let p = self.pos + x * 3;
+                let color = (self.scale(self.data[p]) << 16)
+                    | (self.scale(self.data[p + 1]) << 8)
+                    | self.scale(self.data[p + 2]);
+                self.image.pixels[y * width + x] = color;
+            }
+            self.pos = row_end;
+            self.image.decoded_rows = y + 1;
+        }
+
+        Ok(self.image.is_complete())
+    }
+}
+
+/// データの先頭のバイト列から画像の形式を判定し、その形式のデコーダーを作る。
+/// 今はPPMだけをデコードでき、それ以外の形式はエラーを返す
+/// https://mimesniff.spec.whatwg.org/#matching-an-image-type-pattern
+pub fn create_decoder(data: Vec<u8>) -> Result<Rc<RefCell<dyn ImageDecoder>>, Error> {
+    if data.starts_with(b"P6") {
+        return Ok(Rc::new(RefCell::new(PpmDecoder::new(data)?)));
+    }
+
+    let format = if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        "PNG"
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        "GIF"
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        "JPEG"
+    } else {
+        "unknown"
+    };
+    Err(Error::UnexpectedInput(format!(
+        "{} images are not supported",
+        format
+    )))
+}
+
+/// PPMのヘッダの数字を読む。数字の前の空白と、"#"から行末までのコメントは読み飛ばす
+fn read_header_number(data: &[u8], pos: &mut usize) -> Result<usize, Error> {
+    while *pos < data.len() {
+        match data[*pos] {
+            b'#' => {
+                while *pos < data.len() && data[*pos] != b'\n' {
+                    *pos += 1;
+                }
+            }
+            c if c.is_ascii_whitespace() => *pos += 1,
+            _ => break,
+        }
+    }
+
+    let start = *pos;
+    while *pos < data.len() && data[*pos].is_ascii_digit() {
+        *pos += 1;
+    }
+    let digits = String::from_utf8_lossy(&data[start..*pos]).to_string();
+    digits
+        .parse::<usize>()
+        .map_err(|_| Error::UnexpectedInput("invalid PPM header".to_string()))
+}
+
+/// `decoder`の画像を、`queue`の先読みのタスクとして少しずつデコードする。
+/// タスクを1つ実行するたびに、デコードできたところまでの画像を`on_progress`に渡すので、
+/// 呼び出し側はそれを描画する。デコードに失敗した場合は`on_error`を呼び、残りのタスクを追加しない
+pub fn decode_in_tasks(
+    queue: Rc<RefCell<TaskQueue>>,
+    decoder: Rc<RefCell<dyn ImageDecoder>>,
+    on_progress: Rc<dyn Fn(&Image)>,
+    on_error: Rc<dyn Fn(Error)>,
+) {
+    let next_queue = queue.clone();
+    queue.borrow_mut().push(
+        TaskPriority::Prefetch,
+        Box::new(move || {
+            let result = decoder.borrow_mut().decode_rows(IMAGE_DECODE_ROWS_PER_TASK);
+            match result {
+                Ok(done) => {
+                    on_progress(decoder.borrow().image());
+                    if !done {
+                        decode_in_tasks(next_queue, decoder, on_progress, on_error);
+                    }
+                }
+                Err(e) => on_error(e),
+            }
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ppm(width: usize, height: usize) -> Vec<u8> {
+        let mut data = format!("P6\n# test image\n{} {}\n255\n", width, height).into_bytes();
+        for y in 0..height {
+            for x in 0..width {
+                data.extend_from_slice(&[x as u8, y as u8, 0xff]);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_ppm_decoder() {
+        let mut decoder = PpmDecoder::new(ppm(3, 5)).expect("failed to read the header");
+        assert_eq!((3, 5), (decoder.image().width(), decoder.image().height()));
+        assert_eq!(None, decoder.image().pixel(0, 0));
+
+        assert_eq!(Ok(false), decoder.decode_rows(2));
+        assert_eq!(Some(0x0101ff), decoder.image().pixel(1, 1));
+        assert_eq!(None, decoder.image().pixel(1, 2));
+
+        assert_eq!(Ok(true), decoder.decode_rows(10));
+        assert_eq!(Some(0x0204ff), decoder.image().pixel(2, 4));
+
+        let mut truncated = ppm(3, 5);
+        truncated.truncate(truncated.len() - 1);
+        let mut decoder = PpmDecoder::new(truncated).expect("failed to read the header");
+        assert_eq!(
+            Err(Error::UnexpectedInput("PPM data ends at row 4".to_string())),
+            decoder.decode_rows(5)
+        );
+        assert!(PpmDecoder::new(b"P3 1 1 255 0 0 0".to_vec()).is_err());
+
+        // データより大きい画像と、画素数が桁あふれする画像は、画素を確保せずにエラーにする
+        assert_eq!(
+            Err(Error::UnexpectedInput(
+                "PPM size 100000x100000 exceeds the data".to_string()
+            )),
+            PpmDecoder::new(b"P6 100000 100000 255 \xff\xff\xff".to_vec()).map(|_| ())
+        );
+        let huge = format!("P6 {} 2 255 ", usize::MAX).into_bytes();
+        assert!(PpmDecoder::new(huge).is_err());
+    }
+
+    #[test]
+    fn test_scaled_and_cropped() {
+        let mut decoder = PpmDecoder::new(ppm(2, 4)).expect("failed to read the header");
+        assert_eq!(Ok(false), decoder.decode_rows(2));
+        let image = decoder.image();
+
+        // 2倍に拡大すると、デコードが終わった2行は4行になる
+        let scaled = image.scaled(4, 8);
+        assert_eq!(4, scaled.decoded_rows());
+        assert_eq!(Some(0x0000ff), scaled.pixel(1, 1));
+        assert_eq!(Some(0x0101ff), scaled.pixel(2, 3));
+        assert_eq!(None, scaled.pixel(0, 4));
+
+        let cropped = scaled.cropped(2, 3, 10, 10);
+        assert_eq!((2, 5), (cropped.width(), cropped.height()));
+        assert_eq!(1, cropped.decoded_rows());
+        assert_eq!(Some(0x0101ff), cropped.pixel(0, 0));
+
+        assert_eq!(
+            alloc::vec![(0, 2, 0x0000ff), (2, 2, 0x0100ff)],
+            scaled.row_spans(0)
+        );
+        assert!(scaled.row_spans(4).is_empty());
+    }
+
+    #[test]
+    fn test_create_decoder() {
+        let decoder = create_decoder(ppm(3, 2)).expect("PPM should be supported");
+        assert_eq!(3, decoder.borrow().image().width());
+        assert_eq!(
+            Some(Error::UnexpectedInput(
+                "PNG images are not supported".to_string()
+            )),
+            create_decoder(b"\x89PNG\r\n\x1a\n....".to_vec()).err()
+        );
+        assert!(create_decoder(b"hello".to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_decode_in_tasks() {
+        let height = IMAGE_DECODE_ROWS_PER_TASK * 2 + 1;
+        let queue = Rc::new(RefCell::new(TaskQueue::new()));
+        let decoder = Rc::new(RefCell::new(
+            PpmDecoder::new(ppm(4, height)).expect("failed to read the header"),
+        ));
+        let progress = Rc::new(RefCell::new(Vec::new()));
+        let recorded = progress.clone();
+        decode_in_tasks(
+            queue.clone(),
+            decoder.clone(),
+            Rc::new(move |image: &Image| recorded.borrow_mut().push(image.decoded_rows())),
+            Rc::new(|e| panic!("failed to decode: {:?}", e)),
+        );
+
+        // タスクを1つ実行するたびに、IMAGE_DECODE_ROWS_PER_TASK行ずつデコードが進む
+        let mut slices = 0;
+        loop {
+            // タスクの中でキューにタスクを追加するので、借用したまま実行しない
+            let next = queue.borrow_mut().pop();
+            let (priority, task) = match next {
+                Some(next) => next,
+                None => break,
+            };
+            assert_eq!(TaskPriority::Prefetch, priority);
+            task();
+            slices += 1;
+            assert_eq!(slices, progress.borrow().len());
+        }
+        assert_eq!(
+            alloc::vec![
+                IMAGE_DECODE_ROWS_PER_TASK,
+                IMAGE_DECODE_ROWS_PER_TASK * 2,
+                height
+            ],
+            *progress.borrow()
+        );
+        assert!(decoder.borrow().image().is_complete());
+    }
+}
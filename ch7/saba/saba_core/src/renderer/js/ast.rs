@@ -1,7 +1,9 @@
 use crate::renderer::js::token::JsLexer;
 use crate::renderer::js::token::Token;
+use alloc::format;
 use alloc::rc::Rc;
 use alloc::string::String;
+use alloc::string::ToString;
 use alloc::vec::Vec;
 use core::iter::Peekable;
 
@@ -135,11 +137,16 @@ impl Node {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Program {
     body: Vec<Rc<Node>>,
+    /// 解釈できなかった場合の、最初の構文エラー。エラーがあるプログラムは実行しない
+    syntax_error: Option<String>,
 }
 
 impl Program {
     pub fn new() -> Self {
-        Self { body: Vec::new() }
+        Self {
+            body: Vec::new(),
+            syntax_error: None,
+        }
     }
 
     pub fn set_body(&mut self, body: Vec<Rc<Node>>) {
@@ -149,19 +156,47 @@ impl Program {
     pub fn body(&self) -> &Vec<Rc<Node>> {
         &self.body
     }
+
+    pub fn set_syntax_error(&mut self, syntax_error: Option<String>) {
+        self.syntax_error = syntax_error;
+    }
+
+    pub fn syntax_error(&self) -> Option<&str> {
+        self.syntax_error.as_deref()
+    }
 }
 
 pub struct JsParser {
     t: Peekable<JsLexer>,
+    syntax_error: Option<String>,
 }
 
 impl JsParser {
     pub fn new(t: JsLexer) -> Self {
-        Self { t: t.peekable() }
+        Self {
+            t: t.peekable(),
+            syntax_error: None,
+        }
+    }
+
+    /// 構文エラーを記録する。最初のエラーだけを残す
+    fn set_syntax_error(&mut self, message: String) {
+        if self.syntax_error.is_none() {
+            self.syntax_error = Some(message);
+        }
+    }
+
+    /// 次のトークンを消費する。サポートしていない文字は構文エラーとして記録する
+    fn next_token(&mut self) -> Option<Token> {
+        let token = self.t.next();
+        if let Some(Token::Invalid(c)) = token {
+            self.set_syntax_error(format!("Invalid or unexpected token {:?}", c));
+        }
+        token
     }
 
     fn primary_expression(&mut self) -> Option<Rc<Node>> {
-        let t = match self.t.next() {
+        let t = match self.next_token() {
             Some(token) => token,
             None => return None,
         };
@@ -212,6 +247,9 @@ impl JsParser {
                         if c == &',' {
                             // ','を消費する
                             assert!(self.t.next().is_some());
+                        } else {
+                            // サポートしていない記号から始まる引数も、消費して先に進める
+                            arguments.push(self.assignment_expression());
                         }
                     }
                     _ => arguments.push(self.assignment_expression()),
@@ -284,7 +322,7 @@ impl JsParser {
     }
 
     fn initialiser(&mut self) -> Option<Rc<Node>> {
-        let t = match self.t.next() {
+        let t = match self.next_token() {
             Some(token) => token,
             None => return None,
         };
@@ -299,7 +337,7 @@ impl JsParser {
     }
 
     fn identifier(&mut self) -> Option<Rc<Node>> {
-        let t = match self.t.next() {
+        let t = match self.next_token() {
             Some(token) => token,
             None => return None,
         };
@@ -357,13 +395,13 @@ impl JsParser {
     }
 
     fn function_body(&mut self) -> Option<Rc<Node>> {
-        // '{'を消費する
-        match self.t.next() {
-            Some(t) => match t {
-                Token::Punctuator(c) => assert!(c == '{'),
-                _ => unimplemented!("function should have open curly blacket but got {:?}", t),
-            },
-            None => unimplemented!("function should have open curly blacket but got None"),
+        // '{'を消費する。もし次のトークンが'{'でない場合、構文エラーになる
+        match self.next_token() {
+            Some(Token::Punctuator('{')) => {}
+            t => {
+                self.set_syntax_error(format!("function should have `{{` but got {:?}", t));
+                return None;
+            }
         }
 
         let mut body = Vec::new();
@@ -380,7 +418,10 @@ impl JsParser {
                     }
                     _ => {}
                 },
-                None => {}
+                None => {
+                    self.set_syntax_error("function should have `}` but got None".to_string());
+                    return None;
+                }
             }
 
             body.push(self.source_element());
@@ -390,13 +431,13 @@ impl JsParser {
     fn parameter_list(&mut self) -> Vec<Option<Rc<Node>>> {
         let mut params = Vec::new();
 
-        // '('を消費する。もし次のトークンが'('でない場合、エラーになる
-        match self.t.next() {
-            Some(t) => match t {
-                Token::Punctuator(c) => assert!(c == '('),
-                _ => unimplemented!("function should have `(` but got {:?}", t),
-            },
-            None => unimplemented!("function should have `(` but got None"),
+        // '('を消費する。もし次のトークンが'('でない場合、構文エラーになる
+        match self.next_token() {
+            Some(Token::Punctuator('(')) => {}
+            t => {
+                self.set_syntax_error(format!("function should have `(` but got {:?}", t));
+                return params;
+            }
         }
 
         loop {
@@ -412,6 +453,10 @@ impl JsParser {
                         if c == &',' {
                             // ','を消費する
                             assert!(self.t.next().is_some());
+                        } else {
+                            let message = format!("unexpected token {:?}", c);
+                            self.set_syntax_error(message);
+                            self.t.next();
                         }
                     }
                     _ => {
@@ -457,6 +502,13 @@ impl JsParser {
         loop {
             let node = self.source_element();
 
+            // 構文エラーがある場合は、そこで解釈をやめる
+            if self.syntax_error.is_some() {
+                program.set_body(body);
+                program.set_syntax_error(self.syntax_error.take());
+                return program;
+            }
+
             match node {
                 Some(n) => body.push(n),
                 None => {
@@ -645,4 +697,27 @@ mod tests {
         expected.set_body(body);
         assert_eq!(expected, parser.parse_ast());
     }
+
+    #[test]
+    fn test_syntax_error() {
+        let parse = |input: &str| JsParser::new(JsLexer::new(input.to_string())).parse_ast();
+
+        let program = parse("var a = 1; var b = 1 % 2;");
+        assert_eq!(
+            Some("Invalid or unexpected token '%'"),
+            program.syntax_error()
+        );
+
+        // 閉じていない関数や、引数の括弧がない関数でも、入力の終わりで止まる
+        for input in [
+            "function foo(a) { return a;",
+            "function (",
+            "function foo(a + b) {}",
+        ] {
+            assert!(parse(input).syntax_error().is_some(), "{}", input);
+        }
+
+        // サポートしていない記号から始まる引数があっても、先に進める
+        assert_eq!(None, parse("foo(-1); bar();").syntax_error());
+    }
 }
@@ -0,0 +1,287 @@
+//! ASTを、子を添字で指す平らな命令の列に変換したもの。
+//! ランタイムは`Rc<Node>`の木をたどる代わりにこの列を実行し、変数名は番号で比べる。
+//! ASTは、パースした結果の確認やエラーの表示のためだけに残す
+
+use crate::renderer::js::ast::Node;
+use crate::renderer::js::ast::Program;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::ops::Range;
+
+/// `Code::instructions`の添字
+pub type InstructionId = usize;
+
+/// `Code::names`の添字
+pub type NameId = usize;
+
+/// 1つの式または文。子の命令がない場合はNoneで、評価するとNoneになる
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
+    Number(u64),
+    String(String),
+    /// 変数の参照。定義されていない変数は、その名前の文字列になる
+    Identifier(NameId),
+    Add(Option<InstructionId>, Option<InstructionId>),
+    Sub(Option<InstructionId>, Option<InstructionId>),
+    /// 変数への代入
+    AssignVariable {
+        name: NameId,
+        value: Option<InstructionId>,
+    },
+    /// document.titleやDOMノードのプロパティのような、変数以外への代入
+    Assign {
+        target: Option<InstructionId>,
+        value: Option<InstructionId>,
+    },
+    Member {
+        object: Option<InstructionId>,
+        property: Option<InstructionId>,
+    },
+    /// 引数は`Code::lists`の範囲
+    Call {
+        callee: Option<InstructionId>,
+        arguments: Range<usize>,
+    },
+    DeclareVariable {
+        name: NameId,
+        init: Option<InstructionId>,
+    },
+    /// `Code::functions`の添字
+    DeclareFunction(usize),
+    /// 文を順に評価し、最後の文の値を返す。文は`Code::lists`の範囲
+    Sequence(Range<usize>),
+    /// 解釈できなかった式。評価するとNoneになる
+    Empty,
+}
+
+/// 関数宣言。関数の名前と仮引数の名前は、宣言した時点で決まる
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionCode {
+    pub name: Option<NameId>,
+    pub params: Vec<Option<NameId>>,
+    pub body: Option<InstructionId>,
+}
+
+/// 1つのプログラムを変換した命令の列。関数の本体も同じ列に含める
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Code {
+    instructions: Vec<Instruction>,
+    /// 引数と文の並び
+    lists: Vec<Option<InstructionId>>,
+    names: Vec<String>,
+    functions: Vec<FunctionCode>,
+    /// プログラムの最上位の文
+    statements: Vec<Option<InstructionId>>,
+}
+
+impl Code {
+    pub fn lower(program: &Program) -> Self {
+        let mut code = Self::default();
+        let statements = program
+            .body()
+            .iter()
+            .map(|node| code.lower_node(&Some(node.clone())))
+            .collect();
+        code.statements = statements;
+        code
+    }
+
+    pub fn statements(&self) -> &[Option<InstructionId>] {
+        &self.statements
+    }
+
+    pub fn instruction(&self, id: InstructionId) -> &Instruction {
+        &self.instructions[id]
+    }
+
+    pub fn list(&self, range: Range<usize>) -> &[Option<InstructionId>] {
+        &self.lists[range]
+    }
+
+    pub fn name(&self, id: NameId) -> &str {
+        &self.names[id]
+    }
+
+    pub fn function(&self, index: usize) -> &FunctionCode {
+        &self.functions[index]
+    }
+
+    /// 同じ名前には同じ番号を返す
+    fn intern(&mut self, name: &str) -> NameId {
+        match self.names.iter().position(|n| n == name) {
+            Some(id) => id,
+            None => {
+                self.names.push(name.to_string());
+                self.names.len() - 1
+            }
+        }
+    }
+
+    fn push(&mut self, instruction: Instruction) -> Option<InstructionId> {
+        self.instructions.push(instruction);
+        Some(self.instructions.len() - 1)
+    }
+
+    /// 子の命令を先に変換してから、まとめて`lists`に並べる
+    fn lower_list(&mut self, nodes: &[Option<Rc<Node>>]) -> Range<usize> {
+        let ids: Vec<Option<InstructionId>> =
+            nodes.iter().map(|node| self.lower_node(node)).collect();
+        let start = self.lists.len();
+        self.lists.extend(ids);
+        start..self.lists.len()
+    }
+
+    fn identifier_name(&mut self, node: &Option<Rc<Node>>) -> Option<NameId> {
+        match node.as_deref() {
+            Some(Node::Identifier(name)) => Some(self.intern(name)),
+            _ => None,
+        }
+    }
+
+    fn lower_node(&mut self, node: &Option<Rc<Node>>) -> Option<InstructionId> {
+        let node = node.as_ref()?;
+
+        match node.borrow() {
+            // 式文とreturn文は、中の式の値をそのまま返す
+            Node::ExpressionStatement(expression) => self.lower_node(expression),
+            Node::ReturnStatement { argument } => self.lower_node(argument),
+            Node::AdditiveExpression {
+                operator,
+                left,
+                right,
+            } => {
+                let left = self.lower_node(left);
+                let right = self.lower_node(right);
+                match operator {
+                    '+' => self.push(Instruction::Add(left, right)),
+                    '-' => self.push(Instruction::Sub(left, right)),
+                    _ => self.push(Instruction::Empty),
+                }
+            }
+            Node::AssignmentExpression {
+                operator,
+                left,
+                right,
+            } => {
+                if operator != &'=' {
+                    return self.push(Instruction::Empty);
+                }
+                let value = self.lower_node(right);
+                match self.identifier_name(left) {
+                    Some(name) => self.push(Instruction::AssignVariable { name, value }),
+                    None => {
+                        let target = self.lower_node(left);
+                        self.push(Instruction::Assign { target, value })
+                    }
+                }
+            }
+            Node::MemberExpression { object, property } => {
+                let object = self.lower_node(object);
+                let property = self.lower_node(property);
+                self.push(Instruction::Member { object, property })
+            }
+            Node::NumericLiteral(value) => self.push(Instruction::Number(*value)),
+            Node::StringLiteral(value) => self.push(Instruction::String(value.clone())),
+            Node::Identifier(name) => {
+                let name = self.intern(name);
+                self.push(Instruction::Identifier(name))
+            }
+            Node::VariableDeclaration { declarations } => {
+                let declarations = self.lower_list(declarations);
+                self.push(Instruction::Sequence(declarations))
+            }
+            Node::VariableDeclarator { id, init } => match self.identifier_name(id) {
+                Some(name) => {
+                    let init = self.lower_node(init);
+                    self.push(Instruction::DeclareVariable { name, init })
+                }
+                None => self.push(Instruction::Empty),
+            },
+            Node::BlockStatement { body } => {
+                let body = self.lower_list(body);
+                self.push(Instruction::Sequence(body))
+            }
+            Node::FunctionDeclaration { id, params, body } => {
+                let name = self.identifier_name(id);
+                let params = params
+                    .iter()
+                    .map(|param| self.identifier_name(param))
+                    .collect();
+                let body = self.lower_node(body);
+                self.functions.push(FunctionCode { name, params, body });
+                self.push(Instruction::DeclareFunction(self.functions.len() - 1))
+            }
+            Node::CallExpression { callee, arguments } => {
+                let callee = self.lower_node(callee);
+                let arguments = self.lower_list(arguments);
+                self.push(Instruction::Call { callee, arguments })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::js::ast::JsParser;
+    use crate::renderer::js::token::JsLexer;
+    use alloc::vec;
+
+    fn lower(input: &str) -> Code {
+        Code::lower(&JsParser::new(JsLexer::new(input.to_string())).parse_ast())
+    }
+
+    #[test]
+    fn test_lower() {
+        let code = lower("var a = 1; a = a + 2; f(a, \"b\");");
+        assert_eq!(3, code.statements().len());
+        assert_eq!(vec!["a", "f"], code.names);
+
+        // 子の命令は、親の命令より前に並ぶ
+        assert_eq!(
+            vec![
+                Instruction::Number(1),
+                Instruction::DeclareVariable {
+                    name: 0,
+                    init: Some(0)
+                },
+                Instruction::Sequence(0..1),
+                Instruction::Identifier(0),
+                Instruction::Number(2),
+                Instruction::Add(Some(3), Some(4)),
+                Instruction::AssignVariable {
+                    name: 0,
+                    value: Some(5)
+                },
+                Instruction::Identifier(1),
+                Instruction::Identifier(0),
+                Instruction::String("b".to_string()),
+                Instruction::Call {
+                    callee: Some(7),
+                    arguments: 1..3
+                },
+            ],
+            code.instructions
+        );
+        assert_eq!(&[Some(2), Some(6), Some(10)], code.statements());
+    }
+
+    #[test]
+    fn test_lower_function() {
+        let code = lower("function add(a, b) { return a + b; } add(1, 2)");
+        assert_eq!(
+            &FunctionCode {
+                name: Some(0),
+                params: vec![Some(1), Some(2)],
+                body: Some(3),
+            },
+            code.function(0)
+        );
+        // return文は中の式にまとめられ、本体はa + bだけの並びになる
+        assert_eq!(&Instruction::Sequence(0..1), code.instruction(3));
+        assert_eq!(&[Some(2)], code.list(0..1));
+    }
+}
@@ -1,3 +1,4 @@
 pub mod ast;
+pub mod code;
 pub mod runtime;
 pub mod token;
@@ -1,14 +1,29 @@
-use crate::renderer::dom::api::get_element_by_id;
+use crate::constants::MAX_MUTATION_DELIVERY_ROUNDS;
+use crate::constants::SCRIPT_WATCHDOG_INTERVAL;
+use crate::platform::NoScriptWatchdog;
+use crate::platform::ScriptWatchdog;
+use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::dom::api::append_child;
+use crate::renderer::dom::api::contains;
+use crate::renderer::dom::api::remove_all_children;
+use crate::renderer::dom::api::ElementIndex;
+use crate::renderer::dom::mutation::MutationObserverInit;
+use crate::renderer::dom::mutation::MutationObservers;
+use crate::renderer::dom::mutation::MutationRecord;
+use crate::renderer::dom::mutation::ObserverId;
 use crate::renderer::dom::node::Node as DomNode;
 use crate::renderer::dom::node::NodeKind as DomNodeKind;
-use crate::renderer::js::ast::Node;
 use crate::renderer::js::ast::Program;
+use crate::renderer::js::code::Code;
+use crate::renderer::js::code::Instruction;
+use crate::renderer::js::code::InstructionId;
+use crate::renderer::layout::layout_view::LayoutView;
+use alloc::collections::BTreeMap;
 use alloc::format;
 use alloc::rc::Rc;
 use alloc::string::String;
 use alloc::string::ToString;
 use alloc::vec::Vec;
-use core::borrow::Borrow;
 use core::cell::RefCell;
 use core::fmt::Display;
 use core::fmt::Formatter;
@@ -28,6 +43,14 @@ pub enum RuntimeValue {
         object: Rc<RefCell<DomNode>>,
         property: Option<String>,
     },
+    /// getBoundingClientRect()が返す、要素の位置と大きさ
+    /// https://drafts.fxtf.org/geometry/#domrect
+    DomRect {
+        x: u64,
+        y: u64,
+        width: u64,
+        height: u64,
+    },
 }
 
 impl Add<RuntimeValue> for RuntimeValue {
@@ -35,7 +58,7 @@ impl Add<RuntimeValue> for RuntimeValue {
 
     fn add(self, rhs: RuntimeValue) -> RuntimeValue {
         if let (RuntimeValue::Number(left_num), RuntimeValue::Number(right_num)) = (&self, &rhs) {
-            return RuntimeValue::Number(left_num + right_num);
+            return RuntimeValue::Number(left_num.wrapping_add(*right_num));
         }
 
         RuntimeValue::StringLiteral(self.to_string() + &rhs.to_string())
@@ -47,7 +70,8 @@ impl Sub<RuntimeValue> for RuntimeValue {
 
     fn sub(self, rhs: RuntimeValue) -> RuntimeValue {
         if let (RuntimeValue::Number(left_num), RuntimeValue::Number(right_num)) = (&self, &rhs) {
-            return RuntimeValue::Number(left_num - right_num);
+            // 負の数は扱えないので、リリースビルドと同じく桁あふれさせる
+            return RuntimeValue::Number(left_num.wrapping_sub(*right_num));
         }
 
         // NaN: Not a Number
@@ -66,21 +90,72 @@ impl Display for RuntimeValue {
             } => {
                 format!("HtmlElement: {:#?}", object)
             }
+            RuntimeValue::DomRect {
+                x,
+                y,
+                width,
+                height,
+            } => format!("DOMRect: ({}, {}, {}, {})", x, y, width, height),
         };
         write!(f, "{}", s)
     }
 }
 
+impl RuntimeValue {
+    /// devtoolsのコンソールに表示するための文字列。要素はタグ名とプロパティ名だけを表示する
+    pub fn inspect(&self) -> String {
+        match self {
+            RuntimeValue::StringLiteral(value) => format!("{:?}", value),
+            RuntimeValue::HtmlElement { object, property } => {
+                let name = match RefCell::borrow(object).kind() {
+                    DomNodeKind::Element(e) => e.kind().to_string(),
+                    DomNodeKind::Text(_) => "#text".to_string(),
+                    DomNodeKind::Comment(_) => "#comment".to_string(),
+                    DomNodeKind::Document => "#document".to_string(),
+                };
+                match property {
+                    Some(property) => format!("HtmlElement {}.{}", name, property),
+                    None => format!("HtmlElement {}", name),
+                }
+            }
+            value => value.to_string(),
+        }
+    }
+}
+
+/// スクリプトを評価した結果と、評価している間に出力されたメッセージ
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    /// 最後に評価した文の値
+    pub value: Option<RuntimeValue>,
+    pub console_messages: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+/// スクリプトから要求されたスクロール。スクリプトの実行後に、ページのレイアウトを使って行う
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScrollRequest {
+    /// window.scrollTo(x, y)
+    To(i64, i64),
+    /// element.scrollIntoView()
+    IntoView(Rc<RefCell<DomNode>>),
+}
+
+/// 宣言された関数。仮引数と本体は、宣言を含むコードの`index`番目の関数にある
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Function {
     id: String,
-    params: Vec<Option<Rc<Node>>>,
-    body: Option<Rc<Node>>,
+    code: Rc<Code>,
+    index: usize,
 }
 
 impl Function {
-    fn new(id: String, params: Vec<Option<Rc<Node>>>, body: Option<Rc<Node>>) -> Self {
-        Self { id, params, body }
+    fn new(id: &str, code: Rc<Code>, index: usize) -> Self {
+        Self {
+            id: id.to_string(),
+            code,
+            index,
+        }
     }
 }
 
@@ -99,14 +174,14 @@ impl Environment {
         }
     }
 
-    pub fn get_variable(&self, name: String) -> Option<RuntimeValue> {
+    pub fn get_variable(&self, name: &str) -> Option<RuntimeValue> {
         for variable in &self.variables {
             if variable.0 == name {
                 return variable.1.clone(); // (d1)
             }
         }
         if let Some(env) = &self.outer {
-            env.borrow_mut().get_variable(name) // (d2)
+            env.borrow().get_variable(name) // (d2)
         } else {
             None
         }
@@ -116,14 +191,10 @@ impl Environment {
         self.variables.push((name, value));
     }
 
-    fn update_variable(&mut self, name: String, value: Option<RuntimeValue>) {
-        for i in 0..self.variables.len() {
-            // もし変数を見つけた場合、今までの名前と値のペアを削除し、新しい値とのペアを追加する
-            if self.variables[i].0 == name {
-                self.variables.remove(i);
-                self.variables.push((name, value));
-                return;
-            }
+    fn update_variable(&mut self, name: &str, value: Option<RuntimeValue>) {
+        // もし変数を見つけた場合、その場で新しい値に置き換える
+        if let Some(variable) = self.variables.iter_mut().find(|v| v.0 == name) {
+            variable.1 = value;
         }
     }
 }
@@ -131,6 +202,37 @@ impl Environment {
 #[derive(Debug, Clone)]
 pub struct JsRuntime {
     dom_root: Rc<RefCell<DomNode>>,
+    /// getElementByIdで使う索引。DOMツリーが変更されたときは作り直す
+    element_index: Option<ElementIndex>,
+    /// getElementByIdで見つけた要素。DOMツリーが変更されても、要素がまだ文書の中にあり、
+    /// idも変わっていなければ、索引を作り直さずに使う
+    resolved_elements: BTreeMap<String, Rc<RefCell<DomNode>>>,
+    /// offsetWidthなどで要素の大きさを求めるときに使うスタイルシート
+    style: StyleSheet,
+    /// 要素の位置と大きさを求めるためのレイアウトツリー。DOMツリーが変更されたときは作り直す
+    layout_view: Option<LayoutView>,
+    /// 最後に要求されたスクロール
+    scroll_request: Option<ScrollRequest>,
+    /// DOMツリーの変更を知りたいオブザーバー
+    mutation_observers: MutationObservers,
+    /// スクリプトから登録されたオブザーバーと、レコードを渡す関数の名前
+    mutation_callbacks: Vec<(ObserverId, String)>,
+    /// document.titleに代入されたタイトル
+    title: Option<String>,
+    /// location.hrefに代入されたか、location.assign()に渡されたURL
+    navigation_request: Option<String>,
+    /// window.onbeforeunloadに代入された関数の名前
+    beforeunload_handler: Option<String>,
+    /// console.log()で出力された文字列
+    console_messages: Vec<String>,
+    /// 実行を続けられなかったエラー。定義されていない関数の呼び出しなど
+    errors: Vec<String>,
+    /// 止まらないスクリプトを中断するかどうかを決める
+    watchdog: Rc<dyn ScriptWatchdog>,
+    /// 実行中のスクリプトが、関数を呼び出して文を実行した回数
+    ticks: u64,
+    /// ScriptWatchdogに中断を求められたかどうか。求められた後は、何も評価せずに戻る
+    interrupted: bool,
     env: Rc<RefCell<Environment>>,
     functions: Vec<Function>,
 }
@@ -139,26 +241,327 @@ impl JsRuntime {
     pub fn new(dom_root: Rc<RefCell<DomNode>>) -> Self {
         Self {
             dom_root,
+            element_index: None,
+            resolved_elements: BTreeMap::new(),
+            style: StyleSheet::new(),
+            layout_view: None,
+            scroll_request: None,
+            mutation_observers: MutationObservers::new(),
+            mutation_callbacks: Vec::new(),
+            title: None,
+            navigation_request: None,
+            beforeunload_handler: None,
+            console_messages: Vec::new(),
+            errors: Vec::new(),
+            watchdog: Rc::new(NoScriptWatchdog),
+            ticks: 0,
+            interrupted: false,
             functions: Vec::new(),
             env: Rc::new(RefCell::new(Environment::new(None))),
         }
     }
 
+    pub fn set_watchdog(&mut self, watchdog: Rc<dyn ScriptWatchdog>) {
+        self.watchdog = watchdog;
+    }
+
+    /// スクリプトの実行を始める前に呼び、数えた回数と中断を求められたことを忘れる
+    fn start_ticking(&mut self) {
+        self.ticks = 0;
+        self.interrupted = false;
+    }
+
+    /// 関数の呼び出しと文の実行の前に呼び、実行を続けてよいかを返す。
+    /// SCRIPT_WATCHDOG_INTERVAL回ごとにScriptWatchdogに尋ね、中断を求められたらエラーとして記録する
+    fn tick(&mut self) -> bool {
+        if self.interrupted {
+            return false;
+        }
+        self.ticks += 1;
+        if self.ticks % SCRIPT_WATCHDOG_INTERVAL == 0 && self.watchdog.should_interrupt(self.ticks)
+        {
+            self.interrupted = true;
+            self.errors.push(format!(
+                "InternalError: script was stopped after {} steps because the page was unresponsive",
+                self.ticks
+            ));
+        }
+        !self.interrupted
+    }
+
+    pub fn set_style(&mut self, style: StyleSheet) {
+        self.style = style;
+        self.layout_view = None;
+    }
+
+    /// 要素のレイアウト上の位置と大きさを(x, y, width, height)で返す。
+    /// DOMツリーが変更されてレイアウトが古くなっている場合は、ここでレイアウトを計算し直す。
+    /// 描画されない要素の場合は、すべて0になる
+    /// https://drafts.csswg.org/cssom-view/#dom-htmlelement-offsetwidth
+    fn element_geometry(&mut self, node: &Rc<RefCell<DomNode>>) -> (u64, u64, u64, u64) {
+        let layout_view = self
+            .layout_view
+            .get_or_insert_with(|| LayoutView::new(self.dom_root.clone(), &self.style));
+
+        match layout_view.layout_object_for_node(node) {
+            Some(obj) => {
+                let obj = RefCell::borrow(&obj);
+                (
+                    obj.point().x().max(0) as u64,
+                    obj.point().y().max(0) as u64,
+                    obj.size().width().max(0) as u64,
+                    obj.size().height().max(0) as u64,
+                )
+            }
+            None => (0, 0, 0, 0),
+        }
+    }
+
+    /// スクリプトが要求したスクロールを取り出す
+    pub fn take_scroll_request(&mut self) -> Option<ScrollRequest> {
+        self.scroll_request.take()
+    }
+
+    /// スクリプトがdocument.titleに代入したタイトルを取り出す
+    pub fn take_title(&mut self) -> Option<String> {
+        self.title.take()
+    }
+
+    /// スクリプトがlocationで要求した移動先のURLを取り出す。相対URLは書かれたままで返す
+    pub fn take_navigation_request(&mut self) -> Option<String> {
+        self.navigation_request.take()
+    }
+
+    /// window.onbeforeunloadに登録された関数を呼び、ページを離れる前にユーザーの確認を求めるかどうかを返す。
+    /// 関数が空でない文字列を返した場合に確認を求める。関数が登録されていない場合はfalseを返す
+    /// https://html.spec.whatwg.org/multipage/browsing-the-web.html#prompt-to-unload-a-document
+    pub fn dispatch_beforeunload(&mut self) -> bool {
+        let handler = match self.beforeunload_handler.clone() {
+            Some(handler) => handler,
+            None => return false,
+        };
+        self.start_ticking();
+        let result = self.call_function(&handler, Vec::new(), self.env.clone());
+        self.deliver_mutation_records();
+        matches!(result, Some(RuntimeValue::StringLiteral(s)) if !s.is_empty())
+    }
+
+    /// スクリプトがconsole.log()で出力した文字列を取り出す
+    pub fn take_console_messages(&mut self) -> Vec<String> {
+        core::mem::take(&mut self.console_messages)
+    }
+
+    /// スクリプトの実行中に起きたエラーを取り出す
+    pub fn take_errors(&mut self) -> Vec<String> {
+        core::mem::take(&mut self.errors)
+    }
+
+    /// DOMツリーの変更を監視するオブザーバー。devtoolsやテストは、ここに登録して変更を受け取る
+    pub fn mutation_observers(&mut self) -> &mut MutationObservers {
+        &mut self.mutation_observers
+    }
+
+    /// スクリプトから登録されたオブザーバーに届いたレコードを、1つずつコールバック関数に渡す。
+    /// コールバック関数は callback(type, target) の形で呼ばれる
+    /// https://dom.spec.whatwg.org/#notify-mutation-observers
+    fn deliver_mutation_records(&mut self) {
+        for _ in 0..MAX_MUTATION_DELIVERY_ROUNDS {
+            let mut delivered = false;
+            for (id, callback) in self.mutation_callbacks.clone() {
+                for record in self.mutation_observers.take_records(id) {
+                    delivered = true;
+                    let arguments = alloc::vec![
+                        Some(RuntimeValue::StringLiteral(
+                            record.kind().name().to_string()
+                        )),
+                        Some(RuntimeValue::HtmlElement {
+                            object: record.target(),
+                            property: None,
+                        }),
+                    ];
+                    self.call_function(&callback, arguments, self.env.clone());
+                }
+            }
+            if !delivered {
+                return;
+            }
+        }
+    }
+
+    /// DOMツリーを変更したときに呼び、DOMツリーから作ったキャッシュを捨てる
+    fn invalidate_dom_caches(&mut self) {
+        self.element_index = None;
+        self.layout_view = None;
+    }
+
+    /// idで要素を探す。毎回のようにDOMツリーを変更するスクリプトでも、
+    /// 前に見つけた要素が使える間は、文書全体をたどって索引を作り直さない
+    fn find_element_by_id(&mut self, id: &str) -> Option<Rc<RefCell<DomNode>>> {
+        if let Some(node) = self.resolved_elements.get(id) {
+            let has_id = match RefCell::borrow(node).kind() {
+                DomNodeKind::Element(e) => e.get_attribute("id").as_deref() == Some(id),
+                _ => false,
+            };
+            if has_id && contains(&self.dom_root, node) {
+                return Some(node.clone());
+            }
+        }
+
+        let index = self
+            .element_index
+            .get_or_insert_with(|| ElementIndex::new(self.dom_root.clone()));
+        let node = index.get_element_by_id(id);
+        match &node {
+            Some(node) => self.resolved_elements.insert(id.to_string(), node.clone()),
+            None => self.resolved_elements.remove(id),
+        };
+        node
+    }
+
     /// (bool, Option<RuntimeValue>) のタプルを返す
     ///   bool: ブラウザAPIが呼ばれたかどうか。trueなら何かしらのAPIが呼ばれたことを示す
     ///   Option<RuntimeValue>: ブラウザAPIの呼び出しによって得られた結果
     fn call_browser_api(
         &mut self,
         func: &RuntimeValue,
-        arguments: &[Option<Rc<Node>>],
+        code: &Rc<Code>,
+        arguments: &[Option<InstructionId>],
         env: Rc<RefCell<Environment>>,
     ) -> (bool, Option<RuntimeValue>) {
-        if func == &RuntimeValue::StringLiteral("document.getElementById".to_string()) {
-            let arg = match self.eval(&arguments[0], env.clone()) {
+        // target.getBoundingClientRect()
+        if let RuntimeValue::HtmlElement {
+            object,
+            property: Some(property),
+        } = func
+        {
+            // https://drafts.csswg.org/cssom-view/#dom-element-scrollintoview
+            if property == "scrollIntoView" {
+                self.scroll_request = Some(ScrollRequest::IntoView(object.clone()));
+                return (true, None);
+            }
+
+            // https://dom.spec.whatwg.org/#dom-element-setattribute
+            if property == "setAttribute" {
+                let name = match arguments
+                    .first()
+                    .and_then(|a| self.eval(code, *a, env.clone()))
+                {
+                    Some(name) => name.to_string(),
+                    None => return (true, None),
+                };
+                let value = match arguments
+                    .get(1)
+                    .and_then(|a| self.eval(code, *a, env.clone()))
+                {
+                    Some(value) => value.to_string(),
+                    None => String::new(),
+                };
+                let old_value = match &mut object.borrow_mut().kind {
+                    DomNodeKind::Element(e) => e.set_attribute(&name, value),
+                    _ => return (true, None),
+                };
+                // 別の要素が、見つけておいた要素より前で同じidを持つかもしれない
+                if name == "id" {
+                    self.resolved_elements.clear();
+                }
+                self.mutation_observers
+                    .queue_record(MutationRecord::attributes(object.clone(), name, old_value));
+                // idやclassが変わると索引とスタイルが変わるので、作り直す
+                self.invalidate_dom_caches();
+                return (true, None);
+            }
+
+            if property == "getBoundingClientRect" {
+                let (x, y, width, height) = self.element_geometry(object);
+                return (
+                    true,
+                    Some(RuntimeValue::DomRect {
+                        x,
+                        y,
+                        width,
+                        height,
+                    }),
+                );
+            }
+        }
+
+        // ブラウザAPIの名前は文字列を作らずに比べる
+        let name = match func {
+            RuntimeValue::StringLiteral(name) => name.as_str(),
+            _ => "",
+        };
+
+        // https://drafts.csswg.org/cssom-view/#dom-window-scrollto
+        if name == "window.scrollTo" {
+            let mut coordinates = [0; 2];
+            for (i, coordinate) in coordinates.iter_mut().enumerate() {
+                let argument = match arguments.get(i) {
+                    Some(argument) => argument,
+                    None => break,
+                };
+                if let Some(RuntimeValue::Number(n)) = self.eval(code, *argument, env.clone()) {
+                    *coordinate = n as i64;
+                }
+            }
+            self.scroll_request = Some(ScrollRequest::To(coordinates[0], coordinates[1]));
+            return (true, None);
+        }
+
+        // document.observeMutations(target, callback) で、targetとその子孫の変更を監視する。
+        // MutationObserverを簡略化したもので、変更があるとスクリプトの実行後に callback(type, target) が呼ばれる
+        if name == "document.observeMutations" {
+            let target = match arguments
+                .first()
+                .and_then(|a| self.eval(code, *a, env.clone()))
+            {
+                Some(RuntimeValue::HtmlElement { object, .. }) => object,
+                _ => return (true, None),
+            };
+            let callback = match arguments
+                .get(1)
+                .and_then(|a| self.eval(code, *a, env.clone()))
+            {
+                Some(RuntimeValue::StringLiteral(name)) => name,
+                _ => return (true, None),
+            };
+            let id = self
+                .mutation_observers
+                .observe(target, MutationObserverInit::all());
+            self.mutation_callbacks.push((id, callback));
+            return (true, None);
+        }
+
+        // https://html.spec.whatwg.org/multipage/nav-history-apis.html#dom-location-assign
+        if name == "location.assign" {
+            self.navigation_request = arguments
+                .first()
+                .and_then(|a| self.eval(code, *a, env.clone()))
+                .map(|v| v.to_string());
+            return (true, None);
+        }
+
+        // 引数を空白で区切って1行にする
+        // https://console.spec.whatwg.org/#log
+        if name == "console.log" {
+            let mut values = Vec::new();
+            for argument in arguments {
+                if let Some(value) = self.eval(code, *argument, env.clone()) {
+                    values.push(value.to_string());
+                }
+            }
+            self.console_messages.push(values.join(" "));
+            return (true, None);
+        }
+
+        if name == "document.getElementById" {
+            let arg = match arguments
+                .first()
+                .and_then(|a| self.eval(code, *a, env.clone()))
+            {
                 Some(a) => a,
                 None => return (true, None),
             };
-            let target = match get_element_by_id(Some(self.dom_root.clone()), &arg.to_string()) {
+            let target = match self.find_element_by_id(&arg.to_string()) {
                 Some(n) => n,
                 None => return (true, None),
             };
@@ -176,91 +579,138 @@ impl JsRuntime {
 
     fn eval(
         &mut self,
-        node: &Option<Rc<Node>>,
+        code: &Rc<Code>,
+        id: Option<InstructionId>,
         env: Rc<RefCell<Environment>>,
     ) -> Option<RuntimeValue> {
-        let node = match node {
-            Some(n) => n,
-            None => return None,
-        };
-
-        match node.borrow() {
-            Node::ExpressionStatement(expr) => return self.eval(&expr, env.clone()),
-            Node::AdditiveExpression {
-                operator,
-                left,
-                right,
-            } => {
-                let left_value = match self.eval(&left, env.clone()) {
-                    Some(value) => value,
-                    None => return None,
-                };
-                let right_value = match self.eval(&right, env.clone()) {
-                    Some(value) => value,
-                    None => return None,
-                };
+        let id = id?;
 
-                if operator == &'+' {
-                    Some(left_value + right_value)
-                } else if operator == &'-' {
-                    Some(left_value - right_value)
-                } else {
-                    None
+        match code.instruction(id) {
+            Instruction::Number(value) => Some(RuntimeValue::Number(*value)),
+            Instruction::String(value) => Some(RuntimeValue::StringLiteral(value.clone())),
+            Instruction::Identifier(name) => {
+                let name = code.name(*name);
+                match env.borrow().get_variable(name) {
+                    Some(v) => Some(v),
+                    // 変数名が初めて使用される場合は、まだ値は保存されていないので、文字列として扱う
+                    // たとえば、var a = 42; のようなコードの場合、aはStringLiteralとして扱われる
+                    None => Some(RuntimeValue::StringLiteral(name.to_string())),
                 }
             }
-            Node::AssignmentExpression {
-                operator,
-                left,
-                right,
-            } => {
-                if operator != &'=' {
+            Instruction::Add(left, right) => {
+                let left_value = self.eval(code, *left, env.clone())?;
+                let right_value = self.eval(code, *right, env.clone())?;
+                Some(left_value + right_value)
+            }
+            Instruction::Sub(left, right) => {
+                let left_value = self.eval(code, *left, env.clone())?;
+                let right_value = self.eval(code, *right, env.clone())?;
+                Some(left_value - right_value)
+            }
+            // 変数の再割り当て
+            Instruction::AssignVariable { name, value } => {
+                let new_value = self.eval(code, *value, env.clone());
+                env.borrow_mut()
+                    .update_variable(code.name(*name), new_value);
+                None
+            }
+            Instruction::Assign { target, value } => {
+                let left_value = self.eval(code, *target, env.clone());
+
+                // document.title = "foo"; でページのタイトルを変更する
+                // https://html.spec.whatwg.org/multipage/dom.html#document.title
+                if left_value == Some(RuntimeValue::StringLiteral("document.title".to_string())) {
+                    self.title = self.eval(code, *value, env.clone()).map(|v| v.to_string());
                     return None;
                 }
-                // 変数の再割り当て
-                if let Some(node) = left {
-                    if let Node::Identifier(id) = node.borrow() {
-                        let new_value = self.eval(right, env.clone());
-                        env.borrow_mut().update_variable(id.to_string(), new_value);
+
+                // location.href = "next.html"; で別のページに移動する。移動はスクリプトの実行後に行う
+                // https://html.spec.whatwg.org/multipage/nav-history-apis.html#dom-location-href
+                if let Some(RuntimeValue::StringLiteral(name)) = &left_value {
+                    if is_location_href(name) {
+                        self.navigation_request =
+                            self.eval(code, *value, env.clone()).map(|v| v.to_string());
+                        return None;
+                    }
+                    // window.onbeforeunload = confirmLeave; でページを離れる前に呼ぶ関数を登録する
+                    if name == "window.onbeforeunload" || name == "onbeforeunload" {
+                        self.beforeunload_handler =
+                            self.eval(code, *value, env.clone()).map(|v| v.to_string());
                         return None;
                     }
                 }
 
                 // もし左辺の値がDOMツリーのノードを表すHtmlElementならば、DOMツリーを更新する
-                if let Some(RuntimeValue::HtmlElement { object, property }) =
-                    self.eval(left, env.clone())
-                {
-                    let right_value = match self.eval(right, env.clone()) {
-                        Some(value) => value,
-                        None => return None,
-                    };
+                if let Some(RuntimeValue::HtmlElement { object, property }) = left_value {
+                    let right_value = self.eval(code, *value, env.clone())?;
 
                     if let Some(p) = property {
                         // target.textContent = "foobar"; のようにノードのテキストを変更する
+                        // https://dom.spec.whatwg.org/#string-replace-all
                         if p == "textContent" {
-                            object
-                                .borrow_mut()
-                                .set_first_child(Some(Rc::new(RefCell::new(DomNode::new(
-                                    DomNodeKind::Text(right_value.to_string()),
-                                )))));
+                            let removed = remove_all_children(&object);
+                            let text = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Text(
+                                right_value.to_string(),
+                            ))));
+                            append_child(&object, text.clone());
+                            self.mutation_observers
+                                .queue_record(MutationRecord::child_list(
+                                    object.clone(),
+                                    alloc::vec![text],
+                                    removed,
+                                ));
+                            // 子ノードが置き換わったので、索引とレイアウトを作り直す
+                            self.invalidate_dom_caches();
                         }
                     }
                 }
                 None
             }
-            Node::MemberExpression { object, property } => {
-                let object_value = match self.eval(object, env.clone()) {
-                    Some(value) => value,
-                    None => return None,
-                };
-                let property_value = match self.eval(property, env.clone()) {
+            Instruction::Member { object, property } => {
+                let object_value = self.eval(code, *object, env.clone())?;
+                let property_value = match self.eval(code, *property, env.clone()) {
                     Some(value) => value,
                     // プロパティが存在しないため、`object_value`をここで返す
                     None => return Some(object_value),
                 };
+                // rect.widthのように、DOMRectのプロパティを読む
+                if let RuntimeValue::DomRect {
+                    x,
+                    y,
+                    width,
+                    height,
+                } = object_value
+                {
+                    return match property_value.to_string().as_str() {
+                        "x" | "left" => Some(RuntimeValue::Number(x)),
+                        "y" | "top" => Some(RuntimeValue::Number(y)),
+                        "width" => Some(RuntimeValue::Number(width)),
+                        "height" => Some(RuntimeValue::Number(height)),
+                        "right" => Some(RuntimeValue::Number(x + width)),
+                        "bottom" => Some(RuntimeValue::Number(y + height)),
+                        _ => None,
+                    };
+                }
 
                 // もしオブジェクトがDOMノードの場合、HtmlElementの`property`を更新する
                 if let RuntimeValue::HtmlElement { object, property } = object_value {
-                    assert!(property.is_none());
+                    // el.style.colorのような、プロパティのプロパティは扱えないのでundefinedにする
+                    if property.is_some() {
+                        return None;
+                    }
+
+                    // offsetWidthのような要素の大きさを表すプロパティは、レイアウトから値を読む
+                    let geometry = match property_value.to_string().as_str() {
+                        "offsetLeft" => Some(self.element_geometry(&object).0),
+                        "offsetTop" => Some(self.element_geometry(&object).1),
+                        "offsetWidth" => Some(self.element_geometry(&object).2),
+                        "offsetHeight" => Some(self.element_geometry(&object).3),
+                        _ => None,
+                    };
+                    if let Some(value) = geometry {
+                        return Some(RuntimeValue::Number(value));
+                    }
+
                     // HtmlElementの`property`に`property_value`の文字列をセットする
                     return Some(RuntimeValue::HtmlElement {
                         object,
@@ -274,107 +724,148 @@ impl JsRuntime {
                     object_value + RuntimeValue::StringLiteral(".".to_string()) + property_value,
                 );
             }
-            Node::NumericLiteral(value) => Some(RuntimeValue::Number(*value)),
-            Node::VariableDeclaration { declarations } => {
-                for declaration in declarations {
-                    self.eval(&declaration, env.clone());
-                }
+            Instruction::DeclareVariable { name, init } => {
+                let init = self.eval(code, *init, env.clone());
+                env.borrow_mut()
+                    .add_variable(code.name(*name).to_string(), init);
                 None
             }
-            Node::VariableDeclarator { id, init } => {
-                if let Some(node) = id {
-                    if let Node::Identifier(id) = node.borrow() {
-                        let init = self.eval(&init, env.clone());
-                        env.borrow_mut().add_variable(id.to_string(), init);
-                    }
+            Instruction::DeclareFunction(index) => {
+                if let Some(name) = code.function(*index).name {
+                    self.functions
+                        .push(Function::new(code.name(name), code.clone(), *index));
                 }
                 None
             }
-            Node::Identifier(name) => {
-                match env.borrow_mut().get_variable(name.to_string()) {
-                    Some(v) => Some(v),
-                    // 変数名が初めて使用される場合は、まだ値は保存されていないので、文字列として扱う
-                    // たとえば、var a = 42; のようなコードの場合、aはStringLiteralとして扱われる
-                    None => Some(RuntimeValue::StringLiteral(name.to_string())),
-                }
-            }
-            Node::StringLiteral(value) => Some(RuntimeValue::StringLiteral(value.to_string())),
-            Node::BlockStatement { body } => {
+            Instruction::Sequence(statements) => {
                 let mut result: Option<RuntimeValue> = None;
-                for stmt in body {
-                    result = self.eval(&stmt, env.clone());
+                for statement in code.list(statements.clone()) {
+                    if !self.tick() {
+                        return None;
+                    }
+                    result = self.eval(code, *statement, env.clone());
                 }
                 result
             }
-            Node::ReturnStatement { argument } => {
-                return self.eval(&argument, env.clone());
-            }
-            Node::FunctionDeclaration { id, params, body } => {
-                if let Some(RuntimeValue::StringLiteral(id)) = self.eval(&id, env.clone()) {
-                    let cloned_body = match body {
-                        Some(b) => Some(b.clone()),
-                        None => None,
-                    };
-                    self.functions
-                        .push(Function::new(id, params.to_vec(), cloned_body));
-                };
-                None
-            }
-            Node::CallExpression { callee, arguments } => {
+            Instruction::Call { callee, arguments } => {
+                if !self.tick() {
+                    return None;
+                }
+
                 // 新しいスコープを作成する
                 let new_env = Rc::new(RefCell::new(Environment::new(Some(env))));
 
-                let callee_value = match self.eval(callee, new_env.clone()) {
-                    Some(value) => value,
-                    None => return None,
-                };
+                let callee_value = self.eval(code, *callee, new_env.clone())?;
 
                 // ブラウザAPIの呼び出しを試みる
-                let api_result = self.call_browser_api(&callee_value, arguments, new_env.clone());
+                let arguments = code.list(arguments.clone());
+                let api_result =
+                    self.call_browser_api(&callee_value, code, arguments, new_env.clone());
                 if api_result.0 {
                     // もしブラウザAPIを呼び出していたら、ユーザーが定義した関数は実行しない
                     return api_result.1;
                 }
 
-                // 既に定義されている関数を探す
-                let function = {
-                    let mut f: Option<Function> = None;
+                let arguments = arguments
+                    .iter()
+                    .map(|item| self.eval(code, *item, new_env.clone()))
+                    .collect();
+                self.call_function(&callee_value.to_string(), arguments, new_env)
+            }
+            Instruction::Empty => None,
+        }
+    }
 
-                    for func in &self.functions {
-                        if callee_value == RuntimeValue::StringLiteral(func.id.to_string()) {
-                            f = Some(func.clone());
-                        }
-                    }
+    /// ユーザーが定義した関数を、評価済みの引数で呼ぶ
+    fn call_function(
+        &mut self,
+        name: &str,
+        arguments: Vec<Option<RuntimeValue>>,
+        env: Rc<RefCell<Environment>>,
+    ) -> Option<RuntimeValue> {
+        // 既に定義されている関数を探す
+        let function = match self.functions.iter().rev().find(|f| f.id == name) {
+            Some(f) => f.clone(),
+            None => {
+                self.errors
+                    .push(format!("ReferenceError: {} is not defined", name));
+                return None;
+            }
+        };
+        let declaration = function.code.function(function.index);
 
-                    match f {
-                        Some(f) => f,
-                        None => panic!("function {:?} doesn't exist", callee),
-                    }
-                };
+        // 関数呼び出し時に渡される引数を新しく作成したスコープのローカル変数として割り当てる。
+        // 引数が足りない仮引数はundefinedになり、仮引数より多い引数は使わない
+        // https://tc39.es/ecma262/#sec-functiondeclarationinstantiation
+        let new_env = Rc::new(RefCell::new(Environment::new(Some(env))));
+        let mut arguments = arguments.into_iter();
+        for param in declaration.params.iter() {
+            let value = arguments.next().flatten();
+            if let Some(param) = param {
+                new_env
+                    .borrow_mut()
+                    .add_variable(function.code.name(*param).to_string(), value);
+            }
+        }
 
-                // 関数呼び出し時に渡される引数を新しく作成したスコープのローカル変数として割り当てる
-                assert!(arguments.len() == function.params.len());
-                for (i, item) in arguments.iter().enumerate() {
-                    if let Some(RuntimeValue::StringLiteral(name)) =
-                        self.eval(&function.params[i], new_env.clone())
-                    {
-                        new_env
-                            .borrow_mut()
-                            .add_variable(name, self.eval(item, new_env.clone()));
-                    }
-                }
+        // 関数を新しいスコープと共に呼ぶ
+        self.eval(&function.code, declaration.body, new_env)
+    }
 
-                // 関数を新しいスコープと共に呼ぶ
-                self.eval(&function.body.clone(), new_env.clone())
+    pub fn execute(&mut self, program: &Program) {
+        if self.report_syntax_error(program) {
+            return;
+        }
+        let code = Rc::new(Code::lower(program));
+        self.start_ticking();
+        for statement in code.statements() {
+            if !self.tick() {
+                break;
             }
+            self.eval(&code, *statement, self.env.clone());
         }
+        self.deliver_mutation_records();
     }
 
-    pub fn execute(&mut self, program: &Program) {
-        for node in program.body() {
-            self.eval(&Some(node.clone()), self.env.clone());
+    /// 構文エラーのあるプログラムは実行せず、SyntaxErrorとして記録する。記録した場合はtrueを返す
+    fn report_syntax_error(&mut self, program: &Program) -> bool {
+        match program.syntax_error() {
+            Some(error) => {
+                self.errors.push(format!("SyntaxError: {}", error));
+                true
+            }
+            None => false,
         }
     }
+
+    /// ページのスクリプトと同じ環境でプログラムを実行し、最後の文の値を返す。
+    /// devtoolsのコンソールに入力された式の評価に使う
+    pub fn evaluate(&mut self, program: &Program) -> Evaluation {
+        let mut value = None;
+        if !self.report_syntax_error(program) {
+            let code = Rc::new(Code::lower(program));
+            self.start_ticking();
+            for statement in code.statements() {
+                if !self.tick() {
+                    break;
+                }
+                value = self.eval(&code, *statement, self.env.clone());
+            }
+            self.deliver_mutation_records();
+        }
+
+        Evaluation {
+            value,
+            console_messages: self.take_console_messages(),
+            errors: self.take_errors(),
+        }
+    }
+}
+
+/// ページの移動になる、locationへの代入先かどうか。
+/// メンバーの参照は2段までしか解釈しないので、window.location.hrefは扱えない
+fn is_location_href(name: &str) -> bool {
+    matches!(name, "location" | "location.href" | "window.location")
 }
 
 #[cfg(test)]
@@ -394,8 +885,9 @@ mod tests {
         let expected = [Some(RuntimeValue::Number(42))];
         let mut i = 0;
 
-        for node in ast.body() {
-            let result = runtime.eval(&Some(node.clone()), runtime.env.clone());
+        let code = Rc::new(Code::lower(&ast));
+        for statement in code.statements() {
+            let result = runtime.eval(&code, *statement, runtime.env.clone());
             assert_eq!(expected[i], result);
             i += 1;
         }
@@ -412,8 +904,9 @@ mod tests {
         let expected = [Some(RuntimeValue::Number(3))];
         let mut i = 0;
 
-        for node in ast.body() {
-            let result = runtime.eval(&Some(node.clone()), runtime.env.clone());
+        let code = Rc::new(Code::lower(&ast));
+        for statement in code.statements() {
+            let result = runtime.eval(&code, *statement, runtime.env.clone());
             assert_eq!(expected[i], result);
             i += 1;
         }
@@ -430,8 +923,9 @@ mod tests {
         let expected = [Some(RuntimeValue::Number(1))];
         let mut i = 0;
 
-        for node in ast.body() {
-            let result = runtime.eval(&Some(node.clone()), runtime.env.clone());
+        let code = Rc::new(Code::lower(&ast));
+        for statement in code.statements() {
+            let result = runtime.eval(&code, *statement, runtime.env.clone());
             assert_eq!(expected[i], result);
             i += 1;
         }
@@ -448,8 +942,9 @@ mod tests {
         let expected = [None];
         let mut i = 0;
 
-        for node in ast.body() {
-            let result = runtime.eval(&Some(node.clone()), runtime.env.clone());
+        let code = Rc::new(Code::lower(&ast));
+        for statement in code.statements() {
+            let result = runtime.eval(&code, *statement, runtime.env.clone());
             assert_eq!(expected[i], result);
             i += 1;
         }
@@ -466,8 +961,9 @@ mod tests {
         let expected = [None, Some(RuntimeValue::Number(43))];
         let mut i = 0;
 
-        for node in ast.body() {
-            let result = runtime.eval(&Some(node.clone()), runtime.env.clone());
+        let code = Rc::new(Code::lower(&ast));
+        for statement in code.statements() {
+            let result = runtime.eval(&code, *statement, runtime.env.clone());
             assert_eq!(expected[i], result);
             i += 1;
         }
@@ -484,8 +980,9 @@ mod tests {
         let expected = [None, None, Some(RuntimeValue::Number(1))];
         let mut i = 0;
 
-        for node in ast.body() {
-            let result = runtime.eval(&Some(node.clone()), runtime.env.clone());
+        let code = Rc::new(Code::lower(&ast));
+        for statement in code.statements() {
+            let result = runtime.eval(&code, *statement, runtime.env.clone());
             assert_eq!(expected[i], result);
             i += 1;
         }
@@ -502,8 +999,9 @@ mod tests {
         let expected = [None, Some(RuntimeValue::Number(43))];
         let mut i = 0;
 
-        for node in ast.body() {
-            let result = runtime.eval(&Some(node.clone()), runtime.env.clone());
+        let code = Rc::new(Code::lower(&ast));
+        for statement in code.statements() {
+            let result = runtime.eval(&code, *statement, runtime.env.clone());
             assert_eq!(expected[i], result);
             i += 1;
         }
@@ -520,8 +1018,9 @@ mod tests {
         let expected = [None, Some(RuntimeValue::Number(6))];
         let mut i = 0;
 
-        for node in ast.body() {
-            let result = runtime.eval(&Some(node.clone()), runtime.env.clone());
+        let code = Rc::new(Code::lower(&ast));
+        for statement in code.statements() {
+            let result = runtime.eval(&code, *statement, runtime.env.clone());
             assert_eq!(expected[i], result);
             i += 1;
         }
@@ -538,10 +1037,382 @@ mod tests {
         let expected = [None, None, Some(RuntimeValue::Number(43))];
         let mut i = 0;
 
-        for node in ast.body() {
-            let result = runtime.eval(&Some(node.clone()), runtime.env.clone());
+        let code = Rc::new(Code::lower(&ast));
+        for statement in code.statements() {
+            let result = runtime.eval(&code, *statement, runtime.env.clone());
             assert_eq!(expected[i], result);
             i += 1;
         }
     }
+
+    #[test]
+    fn test_get_element_by_id() {
+        use crate::renderer::html::parser::HtmlParser;
+        use crate::renderer::html::token::HtmlTokenizer;
+
+        let html = "<html><head></head><body><p id=\"target\">text</p></body></html>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).document();
+        let input = "document.getElementById(\"target\")".to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let ast = parser.parse_ast();
+        let mut runtime = JsRuntime::new(dom);
+
+        let code = Rc::new(Code::lower(&ast));
+        for statement in code.statements() {
+            let result = runtime.eval(&code, *statement, runtime.env.clone());
+            match result {
+                Some(RuntimeValue::HtmlElement { object, property }) => {
+                    assert_eq!(
+                        Some("target".to_string()),
+                        RefCell::borrow(&object)
+                            .get_element()
+                            .expect("should be an element")
+                            .get_attribute("id")
+                    );
+                    assert!(property.is_none());
+                }
+                _ => panic!("expected an HtmlElement but got {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_resolved_elements() {
+        use crate::renderer::html::parser::HtmlParser;
+        use crate::renderer::html::token::HtmlTokenizer;
+
+        let html = "<html><head></head><body><div id=\"outer\"><p id=\"inner\">a</p></div><p id=\"target\">b</p></body></html>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).document();
+        let mut runtime = JsRuntime::new(dom);
+        fn run(runtime: &mut JsRuntime, input: &str) -> Option<RuntimeValue> {
+            let ast = JsParser::new(JsLexer::new(input.to_string())).parse_ast();
+            runtime.evaluate(&ast).value
+        }
+
+        // 子ノードを変更しても、見つけておいた要素は索引を作り直さずに返す
+        run(
+            &mut runtime,
+            r#"var inner = document.getElementById("inner");
+var target = document.getElementById("target");
+target.textContent = "c";"#,
+        );
+        assert!(runtime.element_index.is_none());
+        let target = run(&mut runtime, r#"document.getElementById("target")"#);
+        assert!(matches!(target, Some(RuntimeValue::HtmlElement { .. })));
+        assert!(runtime.element_index.is_none());
+
+        // 文書から取り除かれた要素は返さない
+        let inner = run(
+            &mut runtime,
+            r#"var outer = document.getElementById("outer");
+outer.textContent = "d";
+document.getElementById("inner")"#,
+        );
+        assert_eq!(None, inner);
+        assert!(!runtime.resolved_elements.contains_key("inner"));
+
+        // idを変えた要素は、新しいidで見つかる
+        let target = run(
+            &mut runtime,
+            r#"var target = document.getElementById("target");
+target.setAttribute("id", "renamed");
+document.getElementById("target")"#,
+        );
+        assert_eq!(None, target);
+        let renamed = run(&mut runtime, r#"document.getElementById("renamed")"#);
+        assert!(matches!(renamed, Some(RuntimeValue::HtmlElement { .. })));
+    }
+
+    #[test]
+    fn test_element_geometry() {
+        use crate::renderer::html::parser::HtmlParser;
+        use crate::renderer::html::token::HtmlTokenizer;
+
+        let html =
+            "<html><head></head><body><p>a</p><p id=\"target\">text</p></body></html>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).document();
+        let input = r#"var target = document.getElementById("target");
+var rect = target.getBoundingClientRect();
+target.offsetWidth;
+target.offsetHeight;
+rect.top;
+rect.bottom;
+target.textContent = "changed changed changed changed changed changed changed changed changed changed";
+target.offsetHeight;"#
+            .to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let ast = parser.parse_ast();
+        let mut runtime = JsRuntime::new(dom);
+
+        let code = Rc::new(Code::lower(&ast));
+        let results: Vec<Option<RuntimeValue>> = code
+            .statements()
+            .iter()
+            .map(|statement| runtime.eval(&code, *statement, runtime.env.clone()))
+            .collect();
+        assert_eq!(
+            alloc::vec![
+                None,
+                None,
+                // <p>はブロック要素なので、コンテンツ領域の幅いっぱいに広がる
+                Some(RuntimeValue::Number(590)),
+                Some(RuntimeValue::Number(20)),
+                Some(RuntimeValue::Number(20)),
+                Some(RuntimeValue::Number(40)),
+                None,
+                // DOMツリーが変更された後は、レイアウトを計算し直す。長いテキストは2行に折り返される
+                Some(RuntimeValue::Number(40)),
+            ],
+            results
+        );
+    }
+
+    #[test]
+    fn test_scroll_request() {
+        use crate::renderer::html::parser::HtmlParser;
+        use crate::renderer::html::token::HtmlTokenizer;
+
+        let html = "<html><head></head><body><p id=\"target\">text</p></body></html>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).document();
+        let mut runtime = JsRuntime::new(dom.clone());
+
+        let ast = JsParser::new(JsLexer::new("window.scrollTo(0, 100)".to_string())).parse_ast();
+        runtime.execute(&ast);
+        assert_eq!(
+            Some(ScrollRequest::To(0, 100)),
+            runtime.take_scroll_request()
+        );
+        assert_eq!(None, runtime.take_scroll_request());
+
+        let input = r#"var target = document.getElementById("target");
+target.scrollIntoView();"#
+            .to_string();
+        let ast = JsParser::new(JsLexer::new(input)).parse_ast();
+        runtime.execute(&ast);
+        match runtime.take_scroll_request() {
+            Some(ScrollRequest::IntoView(node)) => assert_eq!(
+                Some("target".to_string()),
+                RefCell::borrow(&node)
+                    .get_element()
+                    .expect("should be an element")
+                    .get_attribute("id")
+            ),
+            request => panic!("expected scrollIntoView but got {:?}", request),
+        }
+    }
+
+    #[test]
+    fn test_observe_mutations() {
+        use crate::renderer::dom::api::get_element_by_id;
+        use crate::renderer::dom::mutation::MutationType;
+        use crate::renderer::html::parser::HtmlParser;
+        use crate::renderer::html::token::HtmlTokenizer;
+
+        let html =
+            "<html><head></head><body><p id=\"target\">text</p><p id=\"log\"></p></body></html>"
+                .to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).document();
+        let mut runtime = JsRuntime::new(dom.clone());
+        // ブラウザ側からも、ドキュメント全体の変更を監視できる
+        let all = runtime
+            .mutation_observers()
+            .observe(dom.clone(), MutationObserverInit::all());
+
+        let input = r#"var target = document.getElementById("target");
+var log = document.getElementById("log");
+function onMutation(type, node) {
+    log.textContent = type;
+}
+document.observeMutations(target, onMutation);
+target.setAttribute("class", "changed");"#
+            .to_string();
+        let ast = JsParser::new(JsLexer::new(input)).parse_ast();
+        runtime.execute(&ast);
+
+        let target = get_element_by_id(Some(dom.clone()), &"target".to_string())
+            .expect("failed to find #target");
+        assert_eq!(
+            Some("changed".to_string()),
+            RefCell::borrow(&target)
+                .get_element()
+                .expect("should be an element")
+                .get_attribute("class")
+        );
+
+        // コールバックは、属性の変更を受け取ってログを書き換えた
+        let log =
+            get_element_by_id(Some(dom.clone()), &"log".to_string()).expect("failed to find #log");
+        let text = RefCell::borrow(&log)
+            .first_child()
+            .expect("log should have a text node");
+        assert_eq!(
+            DomNodeKind::Text("attributes".to_string()),
+            RefCell::borrow(&text).kind()
+        );
+
+        let records = runtime.mutation_observers().take_records(all);
+        assert_eq!(2, records.len());
+        assert_eq!(MutationType::Attributes, records[0].kind());
+        assert_eq!(None, records[0].old_value());
+        assert_eq!(MutationType::ChildList, records[1].kind());
+        assert!(Rc::ptr_eq(&log, &records[1].target()));
+        assert!(Rc::ptr_eq(&text, &records[1].added_nodes()[0]));
+    }
+
+    #[test]
+    fn test_observe_mutations_with_fewer_params() {
+        use crate::renderer::dom::api::get_element_by_id;
+        use crate::renderer::html::parser::HtmlParser;
+        use crate::renderer::html::token::HtmlTokenizer;
+
+        let html =
+            "<html><head></head><body><p id=\"target\">text</p><p id=\"log\"></p></body></html>"
+                .to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).document();
+        let mut runtime = JsRuntime::new(dom.clone());
+
+        // 仮引数が1つのコールバックには、変更の種類だけが渡される。
+        // 仮引数が多い関数では、渡されなかった仮引数はundefinedになる
+        let input = r#"var target = document.getElementById("target");
+var log = document.getElementById("log");
+function onMutation(type) {
+    log.textContent = type + missing(type);
+}
+function missing(a, b) {
+    return b;
+}
+document.observeMutations(target, onMutation);
+target.setAttribute("class", "changed");"#
+            .to_string();
+        let ast = JsParser::new(JsLexer::new(input)).parse_ast();
+        runtime.execute(&ast);
+
+        let log =
+            get_element_by_id(Some(dom.clone()), &"log".to_string()).expect("failed to find #log");
+        let text = RefCell::borrow(&log)
+            .first_child()
+            .expect("log should have a text node");
+        assert_eq!(
+            DomNodeKind::Text("attributesundefined".to_string()),
+            RefCell::borrow(&text).kind()
+        );
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
+        let parse = |input: &str| JsParser::new(JsLexer::new(input.to_string())).parse_ast();
+        let mut runtime = JsRuntime::new(dom);
+        runtime.execute(&parse("var a=1; function foo() { return a + 1; }"));
+
+        // ページのスクリプトで定義された変数と関数を使える
+        let evaluation = runtime.evaluate(&parse("console.log(\"hi\"); foo() + a"));
+        assert_eq!(Some(RuntimeValue::Number(3)), evaluation.value);
+        assert_eq!(alloc::vec!["hi".to_string()], evaluation.console_messages);
+        assert!(evaluation.errors.is_empty());
+
+        // 定義されていない関数を呼んでも止まらずに、エラーを返す
+        let evaluation = runtime.evaluate(&parse("bar()"));
+        assert_eq!(None, evaluation.value);
+        assert_eq!(
+            alloc::vec!["ReferenceError: bar is not defined".to_string()],
+            evaluation.errors
+        );
+
+        // 構文エラーのある入力は、途中まで実行せずにエラーを返す
+        let evaluation = runtime.evaluate(&parse("a = 5; a % 2"));
+        assert_eq!(None, evaluation.value);
+        assert_eq!(
+            alloc::vec!["SyntaxError: Invalid or unexpected token '%'".to_string()],
+            evaluation.errors
+        );
+        assert_eq!(
+            Some(RuntimeValue::Number(1)),
+            runtime.evaluate(&parse("a")).value
+        );
+
+        // 10 - 20のような負の数になる計算や、プロパティのプロパティでもパニックしない
+        assert!(runtime
+            .evaluate(&parse("10 - 20; document.getElementById(); a.b.c"))
+            .errors
+            .is_empty());
+    }
+
+    #[test]
+    fn test_navigation_request_and_beforeunload() {
+        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
+        let parse = |input: &str| JsParser::new(JsLexer::new(input.to_string())).parse_ast();
+        let mut runtime = JsRuntime::new(dom);
+        assert!(!runtime.dispatch_beforeunload());
+
+        runtime.execute(&parse(
+            "var message = \"\"; function leave() { return message; } window.onbeforeunload = leave; location.href = \"next.html\";",
+        ));
+        assert_eq!(
+            Some("next.html".to_string()),
+            runtime.take_navigation_request()
+        );
+        assert_eq!(None, runtime.take_navigation_request());
+        // ハンドラが空の文字列を返す間は、確認を求めない
+        assert!(!runtime.dispatch_beforeunload());
+
+        runtime.execute(&parse(
+            "message = \"unsaved\"; location.assign(\"/other\");",
+        ));
+        assert!(runtime.dispatch_beforeunload());
+        assert_eq!(
+            Some("/other".to_string()),
+            runtime.take_navigation_request()
+        );
+    }
+
+    /// 尋ねられた回数を数え、`stop_at`回目に中断を求めるScriptWatchdog
+    struct CountingWatchdog {
+        asked: core::cell::Cell<u64>,
+        stop_at: u64,
+    }
+
+    impl ScriptWatchdog for CountingWatchdog {
+        fn should_interrupt(&self, _ticks: u64) -> bool {
+            self.asked.set(self.asked.get() + 1);
+            self.asked.get() == self.stop_at
+        }
+    }
+
+    #[test]
+    fn test_watchdog() {
+        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
+        let mut runtime = JsRuntime::new(dom);
+        let watchdog = Rc::new(CountingWatchdog {
+            asked: core::cell::Cell::new(0),
+            stop_at: 2,
+        });
+        runtime.set_watchdog(watchdog.clone());
+
+        let mut input = "var count = 0; ".to_string();
+        for _ in 0..30_000 {
+            input.push_str("count = count + 1; ");
+        }
+        let ast = JsParser::new(JsLexer::new(input)).parse_ast();
+        let evaluation = runtime.evaluate(&ast);
+
+        // 1回目は続け、2回目に尋ねられたときに中断する
+        assert_eq!(2, watchdog.asked.get());
+        assert_eq!(1, evaluation.errors.len());
+        assert!(evaluation.errors[0].contains("page was unresponsive"));
+
+        // 中断した後も、次のスクリプトは最初から数えて実行する
+        let ast = JsParser::new(JsLexer::new("count".to_string())).parse_ast();
+        let evaluation = runtime.evaluate(&ast);
+        assert_eq!(Some(RuntimeValue::Number(19_997)), evaluation.value);
+        assert!(evaluation.errors.is_empty());
+    }
 }
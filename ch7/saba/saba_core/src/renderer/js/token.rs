@@ -16,6 +16,8 @@ pub enum Token {
     Keyword(String),
     /// https://262.ecma-international.org/#sec-literals-string-literals
     StringLiteral(String),
+    /// サポートしていない文字。パーサーはSyntaxErrorとして扱う
+    Invalid(char),
 }
 
 pub struct JsLexer {
@@ -33,12 +35,7 @@ impl JsLexer {
 
     fn contains(&self, keyword: &str) -> bool {
         for i in 0..keyword.len() {
-            if keyword
-                .chars()
-                .nth(i)
-                .expect("failed to access to i-th char")
-                != self.input[self.pos + i]
-            {
+            if keyword.chars().nth(i) != self.input.get(self.pos + i).copied() {
                 return false;
             }
         }
@@ -107,7 +104,10 @@ impl JsLexer {
 
             match c {
                 '0'..='9' => {
-                    num = num * 10 + (c.to_digit(10).unwrap() as u64);
+                    // u64に収まらない数は、最大値にする
+                    num = num
+                        .saturating_mul(10)
+                        .saturating_add(c.to_digit(10).unwrap() as u64);
                     self.pos += 1;
                 }
                 _ => break,
@@ -127,7 +127,7 @@ impl Iterator for JsLexer {
         }
 
         // ホワイトスペースまたは改行文字が続く限り、次の位置に進める
-        while self.input[self.pos] == ' ' || self.input[self.pos] == '\n' {
+        while matches!(self.input[self.pos], ' ' | '\n' | '\t' | '\r') {
             self.pos += 1;
 
             if self.pos >= self.input.len() {
@@ -153,7 +153,10 @@ impl Iterator for JsLexer {
             '0'..='9' => Token::Number(self.consume_number()),
             'a'..='z' | 'A'..='Z' | '_' | '$' => Token::Identifier(self.consume_identifier()),
             '"' => Token::StringLiteral(self.consume_string()),
-            _ => unimplemented!("char {:?} is not supported yet", c),
+            _ => {
+                self.pos += 1;
+                Token::Invalid(c)
+            }
         };
 
         Some(token)
@@ -281,4 +284,23 @@ mod tests {
         }
         assert!(lexer.peek().is_none());
     }
+
+    #[test]
+    fn test_invalid_char() {
+        let input = "a % 99999999999999999999\tva".to_string();
+        let mut lexer = JsLexer::new(input).peekable();
+        let expected = [
+            Token::Identifier("a".to_string()),
+            Token::Invalid('%'),
+            Token::Number(u64::MAX),
+            Token::Identifier("va".to_string()),
+        ]
+        .to_vec();
+        let mut i = 0;
+        while lexer.peek().is_some() {
+            assert_eq!(Some(expected[i].clone()), lexer.next());
+            i += 1;
+        }
+        assert!(lexer.peek().is_none());
+    }
 }
@@ -7,6 +7,7 @@ use alloc::rc::Rc;
 use alloc::string::String;
 use alloc::string::ToString;
 use core::cell::RefCell;
+use core::str::FromStr;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ComputedStyle {
@@ -14,9 +15,12 @@ pub struct ComputedStyle {
     color: Option<Color>,
     display: Option<DisplayType>,
     font_size: Option<FontSize>,
+    font_weight: Option<FontWeight>,
+    font_style: Option<FontStyle>,
     text_decoration: Option<TextDecoration>,
     height: Option<f64>,
     width: Option<f64>,
+    object_fit: Option<ObjectFit>,
 }
 
 impl ComputedStyle {
@@ -26,13 +30,20 @@ impl ComputedStyle {
             color: None,
             display: None,
             font_size: None,
+            font_weight: None,
+            font_style: None,
             text_decoration: None,
             height: None,
             width: None,
+            object_fit: None,
         }
     }
 
-    pub fn defaulting(&mut self, node: &Rc<RefCell<Node>>, parent_style: Option<ComputedStyle>) {
+    pub fn defaulting(
+        &mut self,
+        node: &Rc<RefCell<Node>>,
+        parent_style: Option<Rc<ComputedStyle>>,
+    ) {
         // もし親ノードが存在し、親のCSSの値が初期値とは異なる場合、値を継承する
         if let Some(parent_style) = parent_style {
             if self.background_color.is_none() && parent_style.background_color() != Color::white()
@@ -45,6 +56,12 @@ impl ComputedStyle {
             if self.font_size.is_none() && parent_style.font_size() != FontSize::Medium {
                 self.font_size = Some(parent_style.font_size());
             }
+            if self.font_weight.is_none() && parent_style.font_weight() != FontWeight::Normal {
+                self.font_weight = Some(parent_style.font_weight());
+            }
+            if self.font_style.is_none() && parent_style.font_style() != FontStyle::Normal {
+                self.font_style = Some(parent_style.font_style());
+            }
             if self.text_decoration.is_none()
                 && parent_style.text_decoration() != TextDecoration::None
             {
@@ -65,14 +82,18 @@ impl ComputedStyle {
         if self.font_size.is_none() {
             self.font_size = Some(FontSize::default(node));
         }
+        if self.font_weight.is_none() {
+            self.font_weight = Some(FontWeight::default(node));
+        }
+        if self.font_style.is_none() {
+            self.font_style = Some(FontStyle::default(node));
+        }
         if self.text_decoration.is_none() {
             self.text_decoration = Some(TextDecoration::default(node));
         }
-        if self.height.is_none() {
-            self.height = Some(0.0);
-        }
-        if self.width.is_none() {
-            self.width = Some(0.0);
+        // widthとheightの初期値はautoなので、Noneのままにする
+        if self.object_fit.is_none() {
+            self.object_fit = Some(ObjectFit::Fill);
         }
     }
 
@@ -105,11 +126,37 @@ impl ComputedStyle {
             .expect("failed to access CSS property: display")
     }
 
+    pub fn set_font_size(&mut self, font_size: FontSize) {
+        self.font_size = Some(font_size);
+    }
+
     pub fn font_size(&self) -> FontSize {
         self.font_size
             .expect("failed to access CSS property: font_size")
     }
 
+    pub fn set_font_weight(&mut self, font_weight: FontWeight) {
+        self.font_weight = Some(font_weight);
+    }
+
+    pub fn font_weight(&self) -> FontWeight {
+        self.font_weight
+            .expect("failed to access CSS property: font_weight")
+    }
+
+    pub fn set_font_style(&mut self, font_style: FontStyle) {
+        self.font_style = Some(font_style);
+    }
+
+    pub fn font_style(&self) -> FontStyle {
+        self.font_style
+            .expect("failed to access CSS property: font_style")
+    }
+
+    pub fn set_text_decoration(&mut self, text_decoration: TextDecoration) {
+        self.text_decoration = Some(text_decoration);
+    }
+
     pub fn text_decoration(&self) -> TextDecoration {
         self.text_decoration
             .expect("failed to access CSS property: text_decoration")
@@ -119,16 +166,27 @@ impl ComputedStyle {
         self.height = Some(height);
     }
 
-    pub fn height(&self) -> f64 {
-        self.height.expect("failed to access CSS property: height")
+    /// 指定されていない（auto）場合はNone
+    pub fn height(&self) -> Option<f64> {
+        self.height
     }
 
     pub fn set_width(&mut self, width: f64) {
         self.width = Some(width);
     }
 
-    pub fn width(&self) -> f64 {
-        self.width.expect("failed to access CSS property: width")
+    /// 指定されていない（auto）場合はNone
+    pub fn width(&self) -> Option<f64> {
+        self.width
+    }
+
+    pub fn set_object_fit(&mut self, object_fit: ObjectFit) {
+        self.object_fit = Some(object_fit);
+    }
+
+    pub fn object_fit(&self) -> ObjectFit {
+        self.object_fit
+            .expect("failed to access CSS property: object_fit")
     }
 }
 
@@ -234,7 +292,7 @@ impl Color {
 }
 
 /// https://www.w3.org/TR/css-fonts-4/#absolute-size-mapping
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum FontSize {
     Medium,
     XLarge,
@@ -254,6 +312,76 @@ impl FontSize {
     }
 }
 
+/// このブラウザは、太字とそれ以外の2段階だけを扱う
+/// https://drafts.csswg.org/css-fonts/#font-weight-prop
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FontWeight {
+    Normal,
+    Bold,
+}
+
+impl FontWeight {
+    fn default(node: &Rc<RefCell<Node>>) -> Self {
+        match &node.borrow().kind() {
+            NodeKind::Element(element) => match element.kind() {
+                ElementKind::B | ElementKind::Strong => FontWeight::Bold,
+                _ => FontWeight::Normal,
+            },
+            _ => FontWeight::Normal,
+        }
+    }
+}
+
+impl FromStr for FontWeight {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" | "lighter" => Ok(Self::Normal),
+            "bold" | "bolder" => Ok(Self::Bold),
+            _ => Err(Error::UnexpectedInput(format!(
+                "font-weight {:?} is not supported yet",
+                s
+            ))),
+        }
+    }
+}
+
+/// obliqueは、italicと同じく斜体として扱う
+/// https://drafts.csswg.org/css-fonts/#font-style-prop
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FontStyle {
+    Normal,
+    Italic,
+}
+
+impl FontStyle {
+    fn default(node: &Rc<RefCell<Node>>) -> Self {
+        match &node.borrow().kind() {
+            NodeKind::Element(element) => match element.kind() {
+                ElementKind::I | ElementKind::Em => FontStyle::Italic,
+                _ => FontStyle::Normal,
+            },
+            _ => FontStyle::Normal,
+        }
+    }
+}
+
+impl FromStr for FontStyle {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(Self::Normal),
+            "italic" | "oblique" => Ok(Self::Italic),
+            _ => Err(Error::UnexpectedInput(format!(
+                "font-style {:?} is not supported yet",
+                s
+            ))),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum DisplayType {
     /// https://www.w3.org/TR/css-display-3/#valdef-display-block
@@ -276,6 +404,8 @@ impl DisplayType {
                 }
             }
             NodeKind::Text(_) => DisplayType::Inline,
+            // コメントは描画しない
+            NodeKind::Comment(_) => DisplayType::DisplayNone,
         }
     }
 
@@ -292,6 +422,37 @@ impl DisplayType {
     }
 }
 
+/// 置換要素の中身を、要素の矩形にどのように合わせるか
+/// https://drafts.csswg.org/css-images/#the-object-fit
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ObjectFit {
+    /// 縦横比を無視して、矩形全体に引き伸ばす
+    Fill,
+    /// 縦横比を保ったまま、矩形に収まる最大の大きさにする
+    Contain,
+    /// 縦横比を保ったまま、矩形全体を覆う最小の大きさにする
+    Cover,
+    /// 中身の大きさを変えない
+    None,
+}
+
+impl FromStr for ObjectFit {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fill" => Ok(Self::Fill),
+            "contain" => Ok(Self::Contain),
+            "cover" => Ok(Self::Cover),
+            "none" => Ok(Self::None),
+            _ => Err(Error::UnexpectedInput(format!(
+                "object-fit {:?} is not supported yet",
+                s
+            ))),
+        }
+    }
+}
+
 /// https://w3c.github.io/csswg-drafts/css-text-decor/#text-decoration-property
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TextDecoration {
@@ -303,7 +464,7 @@ impl TextDecoration {
     fn default(node: &Rc<RefCell<Node>>) -> Self {
         match &node.borrow().kind() {
             NodeKind::Element(element) => match element.kind() {
-                ElementKind::A => TextDecoration::Underline,
+                ElementKind::A | ElementKind::U => TextDecoration::Underline,
                 _ => TextDecoration::None,
             },
             _ => TextDecoration::None,
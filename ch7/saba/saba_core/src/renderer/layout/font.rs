@@ -0,0 +1,173 @@
+//! 文字の送り幅。レイアウトで測る横幅と、描画する文字の位置は、どちらもここで計算する。
+//! wasabi OSのビットマップフォントに合わせ、半角の文字はCHAR_WIDTH、全角の文字はその2倍、
+//! 結合文字と幅のない空白は0とする。ビットマップの文字は左右に余白を持つので、単語の間の空白と
+//! 幅の狭い約物はそれより詰め、全角の約物が続く場合は前の約物を半角の幅にする。
+//!
+//! 送り幅は1ピクセルをFONT_SUBPIXELSに分けた単位で足し合わせ、文字の位置を決めるときに1回だけ丸める。
+//! 1文字ずつ丸めた幅を足すと、長い行ほど描画した文字がレイアウトの幅からずれていくため
+//! https://drafts.csswg.org/css-text/#spacing
+//! https://drafts.csswg.org/css-text-4/#text-spacing-trim-property
+
+use crate::constants::CHAR_WIDTH;
+use crate::constants::FONT_SUBPIXELS;
+use crate::renderer::layout::computed_style::FontSize;
+use alloc::vec::Vec;
+
+/// 8x16ピクセルの文字を何倍の大きさで描画するか
+pub fn font_scale(font_size: FontSize) -> i64 {
+    match font_size {
+        FontSize::Medium => 1,
+        FontSize::XLarge => 2,
+        FontSize::XXLarge => 3,
+    }
+}
+
+/// 文字の送り幅を、1ピクセルのFONT_SUBPIXELS分の1の単位で返す。`next`は続く文字で、
+/// 全角の約物が続く場合に前の約物を詰めるために使う
+pub fn glyph_advance(c: char, next: Option<char>, font_size: FontSize) -> i64 {
+    let subpixels = CHAR_WIDTH * FONT_SUBPIXELS;
+    let advance = match c {
+        // 結合文字、ゼロ幅スペース、ゼロ幅接合子、BOM
+        '\u{0300}'..='\u{036F}' | '\u{200B}'..='\u{200D}' | '\u{FEFF}' => 0,
+        // 単語の間の空白
+        // https://drafts.csswg.org/css-text/#word-separator
+        ' ' | '\u{00A0}' => subpixels * 11 / 16,
+        '.' | ',' | ':' | ';' | '!' | '\'' | '|' => subpixels * 5 / 8,
+        // 全角の句読点と閉じ括弧の後に全角の約物が続く場合は、半角の幅に詰める
+        c if is_closing_punctuation(c) && next.is_some_and(is_fullwidth_punctuation) => subpixels,
+        c if is_wide(c) => subpixels * 2,
+        _ => subpixels,
+    };
+    advance * font_scale(font_size)
+}
+
+/// 全角の句読点と閉じ括弧。字面が文字の枠の左側に寄っている
+fn is_closing_punctuation(c: char) -> bool {
+    matches!(
+        c,
+        '、' | '。' | '，' | '．' | '」' | '』' | '）' | '】' | '〕' | '〉' | '》'
+    )
+}
+
+/// 全角の約物。開き括弧は字面が文字の枠の右側に寄っている
+fn is_fullwidth_punctuation(c: char) -> bool {
+    is_closing_punctuation(c) || matches!(c, '「' | '『' | '（' | '【' | '〔' | '〈' | '《')
+}
+
+/// 文字列のそれぞれの文字のバイトの位置、文字、送り幅を返す
+fn advances(text: &str, font_size: FontSize) -> impl Iterator<Item = (usize, char, i64)> + '_ {
+    let mut chars = text.char_indices().peekable();
+    core::iter::from_fn(move || {
+        let (i, c) = chars.next()?;
+        let next = chars.peek().map(|(_, next)| *next);
+        Some((i, c, glyph_advance(c, next, font_size)))
+    })
+}
+
+/// 全角で描画する文字。日本語の句読点（"、"や"。"）もここに含まれる
+/// https://www.unicode.org/reports/tr11/
+fn is_wide(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{115F}'
+        | '\u{2E80}'..='\u{303E}'
+        | '\u{3041}'..='\u{33FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{A000}'..='\u{A4CF}'
+        | '\u{AC00}'..='\u{D7A3}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FE30}'..='\u{FE4F}'
+        | '\u{FF00}'..='\u{FF60}'
+        | '\u{FFE0}'..='\u{FFE6}'
+        | '\u{1F300}'..='\u{1F64F}'
+        | '\u{1F900}'..='\u{1F9FF}'
+        | '\u{20000}'..='\u{3FFFD}')
+}
+
+/// 文字列を1行で描画したときの横幅。端数のピクセルは切り上げる
+pub fn text_width(text: &str, font_size: FontSize) -> i64 {
+    let advance: i64 = advances(text, font_size).map(|(_, _, a)| a).sum();
+    (advance + FONT_SUBPIXELS - 1) / FONT_SUBPIXELS
+}
+
+/// それぞれの文字と、行の始まりからその文字の左端までのピクセル数を返す
+pub fn glyph_positions(text: &str, font_size: FontSize) -> Vec<(char, i64)> {
+    let mut positions = Vec::new();
+    let mut advance = 0;
+    for (_, c, a) in advances(text, font_size) {
+        positions.push((c, advance / FONT_SUBPIXELS));
+        advance += a;
+    }
+    positions
+}
+
+/// `max_width`ピクセルに収まる、先頭からの文字列のバイト数
+pub fn fitting_prefix_len(text: &str, font_size: FontSize, max_width: i64) -> usize {
+    let max_advance = max_width * FONT_SUBPIXELS;
+    let mut advance = 0;
+    for (i, _, a) in advances(text, font_size) {
+        advance += a;
+        if advance > max_advance {
+            return i;
+        }
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_text_width() {
+        assert_eq!(CHAR_WIDTH * 5, text_width("abcde", FontSize::Medium));
+        // 単語の間の空白と、幅の狭い約物は詰める。8 + 5 + 5.5 + 8 + 5ピクセルの端数を切り上げる
+        assert_eq!(32, text_width("a, b.", FontSize::Medium));
+        // 全角の文字と句読点は、半角の2倍の幅を持つ
+        assert_eq!(CHAR_WIDTH * 2 * 3, text_width("日本。", FontSize::Medium));
+        // 全角の約物が続く場合は、前の句読点と閉じ括弧を半角の幅にする
+        assert_eq!(
+            CHAR_WIDTH * (2 + 1 + 1 + 2),
+            text_width("本」。」", FontSize::Medium)
+        );
+        assert_eq!(
+            CHAR_WIDTH * 2,
+            text_width("e\u{0301}\u{200B}x", FontSize::Medium)
+        );
+        assert_eq!(CHAR_WIDTH * 3 * 2, text_width("ab", FontSize::XXLarge));
+    }
+
+    #[test]
+    fn test_glyph_positions() {
+        assert_eq!(
+            alloc::vec![('a', 0), ('あ', 8), (' ', 24), ('b', 29)],
+            glyph_positions("aあ b", FontSize::Medium)
+        );
+        // 空白の5.5ピクセルの送り幅を足し合わせてから丸めるので、1文字ずつ丸めた場合と違って
+        // 端数が積み重なっても位置がずれない
+        assert_eq!(
+            alloc::vec![('a', 0), (' ', 8), ('b', 13), (' ', 21), ('c', 27)],
+            glyph_positions("a b c", FontSize::Medium)
+        );
+        // 送り幅を足し合わせた結果と、最後の文字の右端が一致する
+        let text = "saba は ブラウザ";
+        let (last, x) = *glyph_positions(text, FontSize::XLarge)
+            .last()
+            .expect("text should not be empty");
+        assert_eq!(
+            text_width(text, FontSize::XLarge),
+            x + text_width(&last.to_string(), FontSize::XLarge)
+        );
+    }
+
+    #[test]
+    fn test_fitting_prefix_len() {
+        assert_eq!(3, fitting_prefix_len("abcdef", FontSize::Medium, 24));
+        // バイト数を返すので、全角の文字の途中では分けない
+        assert_eq!(1, fitting_prefix_len("aあい", FontSize::Medium, 23));
+        assert_eq!(4, fitting_prefix_len("aあい", FontSize::Medium, 39));
+        assert_eq!(0, fitting_prefix_len("abc", FontSize::Medium, 7));
+        assert_eq!(2, fitting_prefix_len("ab", FontSize::Medium, 100));
+    }
+}
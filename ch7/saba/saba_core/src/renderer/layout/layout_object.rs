@@ -1,77 +1,187 @@
 use crate::alloc::string::ToString;
 use crate::constants::CHAR_HEIGHT_WITH_PADDING;
-use crate::constants::CHAR_WIDTH;
 use crate::constants::CONTENT_AREA_WIDTH;
+use crate::constants::IMAGE_ALT_BORDER_COLOR;
+use crate::constants::IMAGE_ALT_PADDING;
+use crate::constants::LIST_INDENT;
+use crate::constants::LIST_MARKER_GAP;
+use crate::constants::REPLACED_DEFAULT_HEIGHT;
+use crate::constants::REPLACED_DEFAULT_WIDTH;
 use crate::constants::WINDOW_PADDING;
 use crate::constants::WINDOW_WIDTH;
 use crate::display_item::DisplayItem;
 use crate::renderer::css::cssom::ComponentValue;
 use crate::renderer::css::cssom::Declaration;
+use crate::renderer::css::cssom::PseudoElement;
 use crate::renderer::css::cssom::Selector;
 use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::dom::api::element_sibling_position;
+use crate::renderer::dom::node::ElementKind;
 use crate::renderer::dom::node::Node;
 use crate::renderer::dom::node::NodeKind;
+use crate::renderer::image::Image;
 use crate::renderer::layout::computed_style::Color;
 use crate::renderer::layout::computed_style::ComputedStyle;
 use crate::renderer::layout::computed_style::DisplayType;
 use crate::renderer::layout::computed_style::FontSize;
+use crate::renderer::layout::computed_style::FontStyle;
+use crate::renderer::layout::computed_style::FontWeight;
+use crate::renderer::layout::computed_style::ObjectFit;
+use crate::renderer::layout::font::fitting_prefix_len;
+use crate::renderer::layout::font::font_scale;
+use crate::renderer::layout::font::text_width;
+use crate::renderer::layout::layout_view::outline_rects;
+use crate::renderer::layout::layout_view::outline_style;
+use crate::renderer::layout::text_run::TextRunCache;
+use alloc::collections::BTreeMap;
+use alloc::format;
 use alloc::rc::Rc;
 use alloc::rc::Weak;
 use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::cell::RefCell;
+use core::str::FromStr;
 
 /// https://drafts.csswg.org/css-text/#word-break-property
-fn find_index_for_line_break(line: String, max_index: usize) -> usize {
-    for i in (0..max_index).rev() {
-        if line.chars().collect::<Vec<char>>()[i] == ' ' {
-            return i;
-        }
+fn find_index_for_line_break(line: &str, max_len: usize) -> usize {
+    match line[..max_len].rfind(' ') {
+        Some(i) if i > 0 => i,
+        _ => max_len,
     }
-    max_index
 }
 
 /// https://drafts.csswg.org/css-text/#word-break-property
-fn split_text(line: String, char_width: i64) -> Vec<String> {
+fn split_text(line: String, font_size: FontSize) -> Vec<String> {
     let mut result: Vec<String> = vec![];
-    if line.len() as i64 * char_width > (WINDOW_WIDTH + WINDOW_PADDING) {
-        let s = line.split_at(find_index_for_line_break(
-            line.clone(),
-            ((WINDOW_WIDTH + WINDOW_PADDING) / char_width) as usize,
-        ));
+    let max_width = WINDOW_WIDTH + WINDOW_PADDING;
+    if text_width(&line, font_size) > max_width {
+        // 1文字も収まらない場合も、最初の1文字は必ずこの行に置く
+        let max_len = match fitting_prefix_len(&line, font_size, max_width) {
+            0 => line.chars().next().map_or(0, |c| c.len_utf8()),
+            len => len,
+        };
+        let s = line.split_at(find_index_for_line_break(&line, max_len));
         result.push(s.0.to_string());
-        result.extend(split_text(s.1.trim().to_string(), char_width))
+        result.extend(split_text(s.1.trim().to_string(), font_size))
     } else {
         result.push(line);
     }
     result
 }
+
+/// 改行と連続する空白を1つの空白にまとめる
+/// https://drafts.csswg.org/css-text/#white-space-phase-1
+fn collapse_white_space(text: &str) -> String {
+    text.replace("\n", " ")
+        .split(' ')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// スタイルを共有できる要素を見分けるためのキー。
+/// 親のLayoutObjectのアドレス、タグ名、class属性、style属性の組
+type StyleSharingKey = (usize, String, Option<String>, Option<String>);
+
+/// 同じ親を持ち、タグ名、クラス名、インラインスタイルが同じ要素同士で
+/// ComputedStyleを共有するためのキャッシュ
+#[derive(Debug, Clone, Default)]
+pub struct StyleSharingCache {
+    styles: BTreeMap<StyleSharingKey, Rc<ComputedStyle>>,
+}
+
+impl StyleSharingCache {
+    pub fn new() -> Self {
+        Self {
+            styles: BTreeMap::new(),
+        }
+    }
+
+    fn key(
+        node: &Rc<RefCell<Node>>,
+        parent_obj: &Option<Rc<RefCell<LayoutObject>>>,
+    ) -> Option<StyleSharingKey> {
+        let element = node.borrow().get_element()?;
+
+        // IDセレクタは要素ごとに異なるスタイルを適用するので、IDを持つ要素は共有しない
+        if element.get_attribute("id").is_some() {
+            return None;
+        }
+
+        let parent = match parent_obj {
+            Some(p) => Rc::as_ptr(p) as usize,
+            None => 0,
+        };
+
+        Some((
+            parent,
+            element.kind().to_string(),
+            element.get_attribute("class"),
+            element.get_attribute("style"),
+        ))
+    }
+
+    pub fn len(&self) -> usize {
+        self.styles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.styles.is_empty()
+    }
+}
+
 pub fn create_layout_object(
     node: &Option<Rc<RefCell<Node>>>,
     parent_obj: &Option<Rc<RefCell<LayoutObject>>>,
     cssom: &StyleSheet,
+    style_sharing_cache: &mut StyleSharingCache,
 ) -> Option<Rc<RefCell<LayoutObject>>> {
     if let Some(n) = node {
         // LayoutObjectを作成する
         let layout_object = Rc::new(RefCell::new(LayoutObject::new(n.clone(), parent_obj)));
 
-        // CSSのルールをセレクタで選択されたノードに適用する
-        for rule in &cssom.rules {
-            if layout_object.borrow().is_node_selected(&rule.selector) {
-                layout_object
-                    .borrow_mut()
-                    .cascading_style(rule.declarations.clone());
-            }
-        }
-
-        // CSSでスタイルが指定されていない場合、デフォルトの値または親のノードから継承した値を使用する
-        let parent_style = if let Some(parent) = parent_obj {
-            Some(parent.borrow().style())
-        } else {
+        // 同じスタイルになる兄弟要素が既にある場合、カスケードを計算せずにそのスタイルを使う。
+        // ただし、:nth-child()などは兄弟要素ごとに一致するかが変わるので、共有しない
+        let key = if cssom.has_structural_pseudo_classes() {
             None
+        } else {
+            StyleSharingCache::key(n, parent_obj)
         };
-        layout_object.borrow_mut().defaulting_style(n, parent_style);
+        let shared_style = match &key {
+            Some(k) => style_sharing_cache.styles.get(k).cloned(),
+            None => None,
+        };
+
+        if let Some(style) = shared_style {
+            layout_object.borrow_mut().set_shared_style(style);
+        } else {
+            // CSSのルールをセレクタで選択されたノードに適用する。
+            // ID、クラス名、タグ名で絞り込んだルールだけを調べる
+            if let Some(element) = n.borrow().get_element() {
+                for rule in cssom.matching_rules(&element) {
+                    if layout_object.borrow().is_node_selected(&rule.selector) {
+                        layout_object
+                            .borrow_mut()
+                            .cascading_style(rule.declarations.clone());
+                    }
+                }
+            }
+
+            // CSSでスタイルが指定されていない場合、デフォルトの値または親のノードから継承した値を使用する
+            let parent_style = if let Some(parent) = parent_obj {
+                Some(parent.borrow().style())
+            } else {
+                None
+            };
+            layout_object.borrow_mut().defaulting_style(n, parent_style);
+
+            if let Some(k) = key {
+                style_sharing_cache
+                    .styles
+                    .insert(k, layout_object.borrow().style());
+            }
+        }
 
         // displayプロパティがnoneの場合、ノードを作成しない
         if layout_object.borrow().style().display() == DisplayType::DisplayNone {
@@ -85,6 +195,63 @@ pub fn create_layout_object(
     None
 }
 
+/// 要素に対する::beforeまたは::afterの擬似要素のLayoutObjectを作成する。
+/// contentプロパティが文字列でない場合や、displayがnoneの場合は作成しない
+/// https://www.w3.org/TR/css-pseudo-4/#generated-content
+pub fn create_pseudo_element_layout_object(
+    parent_obj: &Rc<RefCell<LayoutObject>>,
+    cssom: &StyleSheet,
+    pseudo_element: PseudoElement,
+) -> Option<Rc<RefCell<LayoutObject>>> {
+    // p::beforeのpのように、元のセレクタが要素に一致するルールの宣言を集める
+    let mut declarations = Vec::new();
+    for rule in cssom.pseudo_element_rules(pseudo_element) {
+        if let Selector::PseudoElementSelector(base, _) = &rule.selector {
+            let selected = match base {
+                Some(base) => parent_obj.borrow().is_node_selected(base),
+                None => true,
+            };
+            if selected {
+                declarations.extend(rule.declarations.iter().cloned());
+            }
+        }
+    }
+
+    let mut content = None;
+    for declaration in &declarations {
+        if declaration.property == "content" {
+            content = match &declaration.value {
+                ComponentValue::StringToken(s) => Some(s.clone()),
+                _ => None,
+            };
+        }
+    }
+    let content = content?;
+
+    // 生成されたテキストはDOMツリーには存在しないので、親を元の要素にしたテキストノードを作る。
+    // こうすることで、<a>要素の擬似要素をクリックしたときもリンクをたどれる
+    let node = Rc::new(RefCell::new(Node::new(NodeKind::Text(content))));
+    node.borrow_mut()
+        .set_parent(Rc::downgrade(&parent_obj.borrow().node));
+
+    let layout_object = Rc::new(RefCell::new(LayoutObject::new(
+        node.clone(),
+        &Some(parent_obj.clone()),
+    )));
+    layout_object.borrow_mut().cascading_style(declarations);
+    let parent_style = parent_obj.borrow().style();
+    layout_object
+        .borrow_mut()
+        .defaulting_style(&node, Some(parent_style));
+
+    if layout_object.borrow().style().display() == DisplayType::DisplayNone {
+        return None;
+    }
+
+    layout_object.borrow_mut().update_kind();
+    Some(layout_object)
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum LayoutObjectKind {
     Block,
@@ -99,9 +266,18 @@ pub struct LayoutObject {
     first_child: Option<Rc<RefCell<LayoutObject>>>,
     next_sibling: Option<Rc<RefCell<LayoutObject>>>,
     parent: Weak<RefCell<LayoutObject>>,
-    style: ComputedStyle,
+    style: Rc<ComputedStyle>,
     point: LayoutPoint,
     size: LayoutSize,
+    /// スタイル、位置、サイズが変わるたびに増える世代番号
+    generation: u64,
+    /// 最後に描画したときの世代番号と、そのときに作成したDisplayItemのキャッシュ。
+    /// キャッシュを使うときにDisplayItemを複製しないように、共有できる形で持つ
+    painted: Option<(u64, Rc<[DisplayItem]>)>,
+    /// 置換要素の中身が本来持つ大きさ。わからない場合はNone
+    intrinsic_size: Option<LayoutSize>,
+    /// <img>に表示する画像。読み込めていない場合はNone
+    image: Option<Rc<Image>>,
 }
 
 impl PartialEq for LayoutObject {
@@ -123,26 +299,66 @@ impl LayoutObject {
             first_child: None,
             next_sibling: None,
             parent,
-            style: ComputedStyle::new(),
+            style: Rc::new(ComputedStyle::new()),
             point: LayoutPoint::new(0, 0),
             size: LayoutSize::new(0, 0),
+            generation: 0,
+            painted: None,
+            intrinsic_size: None,
+            image: None,
         }
     }
 
-    pub fn paint(&mut self) -> Vec<DisplayItem> {
+    /// 描画結果に影響する変更があったときに呼び、キャッシュを無効にする
+    fn invalidate(&mut self) {
+        self.generation += 1;
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn paint(&mut self) -> Rc<[DisplayItem]> {
+        // 前回の描画から変更がない場合、キャッシュしたDisplayItemを再利用する
+        if let Some((generation, items)) = &self.painted {
+            if *generation == self.generation {
+                return items.clone();
+            }
+        }
+
+        let items: Rc<[DisplayItem]> = self.paint_internal().into();
+        self.painted = Some((self.generation, items.clone()));
+        items
+    }
+
+    fn paint_internal(&self) -> Vec<DisplayItem> {
         if self.style.display() == DisplayType::DisplayNone {
             return vec![];
         }
 
         match self.kind {
+            LayoutObjectKind::Block if self.is_image() => return self.paint_image(),
+            LayoutObjectKind::Block if self.is_line_break() => return vec![],
             LayoutObjectKind::Block => {
                 // (d1)
                 if let NodeKind::Element(_e) = self.node_kind() {
-                    return vec![DisplayItem::Rect {
+                    let mut items = vec![DisplayItem::Rect {
                         style: self.style(),
                         layout_point: self.point(),
                         layout_size: self.size(),
                     }];
+                    if let Some(marker) = self.list_marker() {
+                        // マーカーは項目の外側、リストが右にずらした余白の中に描く
+                        let x = self.point.x()
+                            - text_width(&marker, self.style.font_size())
+                            - LIST_MARKER_GAP;
+                        items.push(DisplayItem::Text {
+                            text: marker,
+                            style: self.style(),
+                            layout_point: LayoutPoint::new(x, self.point.y()),
+                        });
+                    }
+                    return items;
                 }
             }
             LayoutObjectKind::Inline => { // (d2)
@@ -154,18 +370,8 @@ impl LayoutObject {
                 if let NodeKind::Text(t) = self.node_kind() {
                     let mut v = vec![];
 
-                    let ratio = match self.style.font_size() {
-                        FontSize::Medium => 1,
-                        FontSize::XLarge => 2,
-                        FontSize::XXLarge => 3,
-                    };
-                    let plain_text = t
-                        .replace("\n", " ")
-                        .split(' ')
-                        .filter(|s| !s.is_empty())
-                        .collect::<Vec<_>>()
-                        .join(" ");
-                    let lines = split_text(plain_text, CHAR_WIDTH * ratio);
+                    let plain_text = collapse_white_space(&t);
+                    let lines = split_text(plain_text, self.style.font_size());
                     let mut i = 0;
                     for line in lines {
                         let item = DisplayItem::Text {
@@ -188,10 +394,74 @@ impl LayoutObject {
         vec![]
     }
 
-    pub fn compute_size(&mut self, parent_size: LayoutSize) {
+    /// 画像を描く。画像を表示できない場合は、要素の矩形に枠を描き、その中にalt属性の文字列を描く。
+    /// 文字列は枠に収まる分だけを描く
+    /// https://html.spec.whatwg.org/multipage/rendering.html#images-3
+    fn paint_image(&self) -> Vec<DisplayItem> {
+        if let Some(image) = &self.image {
+            return self.paint_decoded_image(image);
+        }
+
+        // 画像を読み込めていない場合は、代わりにalt属性の文字列を描く
+        let mut items = outline_rects(
+            &outline_style(IMAGE_ALT_BORDER_COLOR),
+            self.point,
+            self.size,
+        );
+
+        let alt = collapse_white_space(&self.alt_text());
+        let inset = 1 + IMAGE_ALT_PADDING;
+        let font_size = self.style.font_size();
+        let line_height = CHAR_HEIGHT_WITH_PADDING * font_scale(font_size);
+        if self.size.height() < line_height + inset * 2 {
+            return items;
+        }
+        let len = fitting_prefix_len(&alt, font_size, self.size.width() - inset * 2);
+        if len > 0 {
+            items.push(DisplayItem::Text {
+                text: alt[..len].to_string(),
+                style: self.style(),
+                layout_point: LayoutPoint::new(self.point.x() + inset, self.point.y() + inset),
+            });
+        }
+        items
+    }
+
+    /// object-fitに従って画像を拡大・縮小し、要素の矩形からはみ出す部分を切り取って描く
+    fn paint_decoded_image(&self, image: &Image) -> Vec<DisplayItem> {
+        let (point, size) = self.object_rect();
+        let x1 = point.x().max(self.point.x());
+        let y1 = point.y().max(self.point.y());
+        let x2 = (point.x() + size.width()).min(self.point.x() + self.size.width());
+        let y2 = (point.y() + size.height()).min(self.point.y() + self.size.height());
+        if x2 <= x1 || y2 <= y1 {
+            return vec![];
+        }
+
+        let image = image
+            .scaled(size.width() as usize, size.height() as usize)
+            .cropped(
+                (x1 - point.x()) as usize,
+                (y1 - point.y()) as usize,
+                (x2 - x1) as usize,
+                (y2 - y1) as usize,
+            );
+        vec![DisplayItem::Image {
+            image: Rc::new(image),
+            layout_point: LayoutPoint::new(x1, y1),
+        }]
+    }
+
+    pub fn compute_size(&mut self, parent_size: LayoutSize, text_run_cache: &mut TextRunCache) {
         let mut size = LayoutSize::new(0, 0);
 
         match self.kind() {
+            LayoutObjectKind::Block if self.is_replaced() => {
+                // 置換要素の大きさは子ノードに依存しない
+                size = self.replaced_size();
+            }
+            // <br>は大きさを持たないブロック要素として、前後のインライン要素とテキストを別の行に分ける
+            LayoutObjectKind::Block if self.is_line_break() => {}
             LayoutObjectKind::Block => {
                 size.set_width(parent_size.width());
 
@@ -206,7 +476,10 @@ impl LayoutObject {
                         None => panic!("first child should exist"),
                     };
 
-                    if previous_child_kind == LayoutObjectKind::Block
+                    if self.is_table_row() {
+                        // 表の行の高さは、横に並ぶセルのうち最も高いものに合わせる
+                        height = height.max(c.borrow().size.height());
+                    } else if previous_child_kind == LayoutObjectKind::Block
                         || c.borrow().kind() == LayoutObjectKind::Block
                     {
                         height += c.borrow().size.height();
@@ -239,31 +512,283 @@ impl LayoutObject {
             }
             LayoutObjectKind::Text => {
                 if let NodeKind::Text(t) = self.node_kind() {
-                    let ratio = match self.style.font_size() {
-                        FontSize::Medium => 1,
-                        FontSize::XLarge => 2,
-                        FontSize::XXLarge => 3,
-                    };
-                    let width = CHAR_WIDTH * ratio * t.len() as i64;
+                    // 描画するときと同じように空白をまとめてから測り、描画する行の数だけ高さを取る
+                    let font_size = self.style.font_size();
+                    let plain_text = collapse_white_space(&t);
+                    let bold = self.style.font_weight() == FontWeight::Bold;
+                    let width = text_run_cache.measure(&plain_text, font_size, bold);
+                    let line_height = CHAR_HEIGHT_WITH_PADDING * font_scale(font_size);
                     if width > CONTENT_AREA_WIDTH {
                         // テキストが複数行のとき
                         size.set_width(CONTENT_AREA_WIDTH);
-                        let line_num = if width.wrapping_rem(CONTENT_AREA_WIDTH) == 0 {
-                            width.wrapping_div(CONTENT_AREA_WIDTH)
-                        } else {
-                            width.wrapping_div(CONTENT_AREA_WIDTH) + 1
-                        };
-                        size.set_height(CHAR_HEIGHT_WITH_PADDING * ratio * line_num);
+                        let line_num = split_text(plain_text, font_size).len() as i64;
+                        size.set_height(line_height * line_num);
                     } else {
                         // テキストが1行に収まるとき
                         size.set_width(width);
-                        size.set_height(CHAR_HEIGHT_WITH_PADDING * ratio);
+                        size.set_height(line_height);
+                    }
+                }
+            }
+        }
+
+        if self.size != size {
+            self.size = size;
+            self.invalidate();
+        }
+    }
+
+    /// 子ノードを右にずらして並べるリストかどうか
+    pub fn is_list(&self) -> bool {
+        matches!(
+            self.node.borrow().element_kind(),
+            Some(ElementKind::Ul) | Some(ElementKind::Ol)
+        )
+    }
+
+    /// <li>の左に描くマーカー。<ol>の中では"1."から数え、それ以外では"*"にする。
+    /// 仕様の"disc"の黒丸はビットマップフォントにないので、ASCIIの文字で代える
+    /// https://html.spec.whatwg.org/multipage/rendering.html#lists
+    fn list_marker(&self) -> Option<String> {
+        let node = self.node.borrow();
+        if node.element_kind() != Some(ElementKind::Li) {
+            return None;
+        }
+        let parent = node.parent().upgrade();
+        let ordered = parent.as_ref().map_or(false, |p| {
+            p.borrow().element_kind() == Some(ElementKind::Ol)
+        });
+        if !ordered {
+            return Some("*".to_string());
+        }
+
+        // start属性がある場合は、その番号から数える
+        let start = parent
+            .and_then(|p| p.borrow().get_element())
+            .and_then(|e| e.get_attribute("start"))
+            .and_then(|s| s.trim().parse::<i64>().ok())
+            .unwrap_or(1);
+        let mut index = 0;
+        let mut sibling = node.previous_sibling().upgrade();
+        while let Some(s) = sibling {
+            if s.borrow().element_kind() == Some(ElementKind::Li) {
+                index += 1;
+            }
+            sibling = s.borrow().previous_sibling().upgrade();
+        }
+        Some(format!("{}.", start + index))
+    }
+
+    pub fn is_table_row(&self) -> bool {
+        self.node.borrow().element_kind() == Some(ElementKind::Tr)
+    }
+
+    pub fn is_table_cell(&self) -> bool {
+        matches!(
+            self.node.borrow().element_kind(),
+            Some(ElementKind::Td) | Some(ElementKind::Th)
+        )
+    }
+
+    /// 行が属する表の列の数。最も多くセルがある行のセルの数にする。
+    /// 表の固定レイアウトと同じく、列の幅はセルの中身によらずに表の幅を等分する
+    /// https://drafts.csswg.org/css-tables/#fixed-table-layout
+    fn table_column_count(&self) -> i64 {
+        let parent = match self.parent.upgrade() {
+            Some(parent) => parent,
+            None => return 1,
+        };
+        let table = if parent.borrow().node.borrow().element_kind() == Some(ElementKind::Tbody) {
+            match parent.borrow().parent.upgrade() {
+                Some(table) => table,
+                None => return 1,
+            }
+        } else {
+            parent
+        };
+
+        // 行は<table>の直下か、<tbody>の中にある
+        let mut rows = Vec::new();
+        let mut child = table.borrow().first_child();
+        while let Some(c) = child {
+            if c.borrow().is_table_row() {
+                rows.push(c.clone());
+            } else {
+                let mut row = c.borrow().first_child();
+                while let Some(r) = row {
+                    if r.borrow().is_table_row() {
+                        rows.push(r.clone());
                     }
+                    row = r.borrow().next_sibling();
+                }
+            }
+            child = c.borrow().next_sibling();
+        }
+
+        let mut columns = 1;
+        for row in rows {
+            let mut cells = 0;
+            let mut cell = row.borrow().first_child();
+            while let Some(c) = cell {
+                if c.borrow().is_table_cell() {
+                    cells += 1;
                 }
+                cell = c.borrow().next_sibling();
             }
+            columns = columns.max(cells);
         }
+        columns
+    }
 
-        self.size = size;
+    pub fn is_iframe(&self) -> bool {
+        self.node.borrow().element_kind() == Some(ElementKind::Iframe)
+    }
+
+    pub fn is_image(&self) -> bool {
+        self.node.borrow().element_kind() == Some(ElementKind::Img)
+    }
+
+    pub fn is_line_break(&self) -> bool {
+        self.node.borrow().element_kind() == Some(ElementKind::Br)
+    }
+
+    /// 中身が文書の外から来る置換要素かどうか
+    /// https://html.spec.whatwg.org/multipage/rendering.html#replaced-elements
+    pub fn is_replaced(&self) -> bool {
+        self.is_iframe() || self.is_image()
+    }
+
+    /// 画像の代わりに表示するalt属性の文字列。属性がない場合は空の文字列を返す
+    fn alt_text(&self) -> String {
+        self.node
+            .borrow()
+            .get_element()
+            .and_then(|e| e.get_attribute("alt"))
+            .unwrap_or_default()
+    }
+
+    /// 大きさが指定されていない置換要素の大きさ。画像を表示できない場合は、alt属性の文字列が
+    /// 1行で収まる枠の大きさにする。alt属性が空の場合は何も表示しないので、大きさを持たない
+    fn default_replaced_size(&self) -> LayoutSize {
+        if !self.is_image() {
+            return LayoutSize::new(REPLACED_DEFAULT_WIDTH, REPLACED_DEFAULT_HEIGHT);
+        }
+        let alt = collapse_white_space(&self.alt_text());
+        if alt.is_empty() {
+            return LayoutSize::new(0, 0);
+        }
+        let font_size = self.style.font_size();
+        let inset = 1 + IMAGE_ALT_PADDING;
+        LayoutSize::new(
+            text_width(&alt, font_size) + inset * 2,
+            CHAR_HEIGHT_WITH_PADDING * font_scale(font_size) + inset * 2,
+        )
+    }
+
+    /// 画像の幅と高さのような、置換要素の中身が本来持つ大きさを設定する
+    pub fn set_intrinsic_size(&mut self, intrinsic_size: Option<LayoutSize>) {
+        if self.intrinsic_size != intrinsic_size {
+            self.intrinsic_size = intrinsic_size;
+            self.invalidate();
+        }
+    }
+
+    pub fn intrinsic_size(&self) -> Option<LayoutSize> {
+        self.intrinsic_size
+    }
+
+    /// <img>に表示する画像を設定する。画像の大きさを、要素の中身が本来持つ大きさにする。
+    /// Noneの場合はalt属性の文字列を表示する
+    pub fn set_image(&mut self, image: Option<Rc<Image>>) {
+        if self.image == image {
+            return;
+        }
+        let intrinsic_size = image
+            .as_ref()
+            .map(|i| LayoutSize::new(i.width() as i64, i.height() as i64));
+        self.set_intrinsic_size(intrinsic_size);
+        self.image = image;
+        self.invalidate();
+    }
+
+    /// width="300"のような、長さを表す属性の値を返す
+    fn attribute_length(&self, name: &str) -> Option<i64> {
+        self.node
+            .borrow()
+            .get_element()
+            .and_then(|e| e.get_attribute(name))
+            .and_then(|value| value.parse::<i64>().ok())
+    }
+
+    /// 置換要素の大きさを決める。CSSのwidthとheight、次にwidth属性とheight属性を使い、
+    /// 片方だけが指定されている場合は中身の縦横比を保つようにもう片方を決める
+    /// https://www.w3.org/TR/CSS22/visudet.html#inline-replaced-width
+    /// https://www.w3.org/TR/CSS22/visudet.html#inline-replaced-height
+    fn replaced_size(&self) -> LayoutSize {
+        let width = match self.style.width() {
+            Some(width) => Some(width as i64),
+            None => self.attribute_length("width"),
+        };
+        let height = match self.style.height() {
+            Some(height) => Some(height as i64),
+            None => self.attribute_length("height"),
+        };
+        let intrinsic = self
+            .intrinsic_size
+            .filter(|s| s.width() > 0 && s.height() > 0);
+
+        match (width, height, intrinsic) {
+            (Some(w), Some(h), _) => LayoutSize::new(w, h),
+            (Some(w), None, Some(i)) => LayoutSize::new(w, w * i.height() / i.width()),
+            (None, Some(h), Some(i)) => LayoutSize::new(h * i.width() / i.height(), h),
+            (None, None, Some(i)) => i,
+            (w, h, None) => {
+                let default = self.default_replaced_size();
+                LayoutSize::new(w.unwrap_or(default.width()), h.unwrap_or(default.height()))
+            }
+        }
+    }
+
+    /// object-fitに従って、置換要素の中身を描画する位置と大きさを返す。
+    /// 中身は要素の矩形の中央に置く
+    /// https://drafts.csswg.org/css-images/#the-object-fit
+    pub fn object_rect(&self) -> (LayoutPoint, LayoutSize) {
+        let intrinsic = match self.intrinsic_size {
+            Some(s) if s.width() > 0 && s.height() > 0 => s,
+            _ => return (self.point, self.size),
+        };
+
+        let box_size = self.size;
+        let size = match self.style.object_fit() {
+            ObjectFit::Fill => box_size,
+            ObjectFit::None => intrinsic,
+            ObjectFit::Contain | ObjectFit::Cover => {
+                // 横幅に合わせた場合に高さがはみ出すかどうかで、どちらの辺に合わせるかを決める
+                let fit_width =
+                    box_size.width() * intrinsic.height() <= box_size.height() * intrinsic.width();
+                let use_width = match self.style.object_fit() {
+                    ObjectFit::Contain => fit_width,
+                    _ => !fit_width,
+                };
+                if use_width {
+                    LayoutSize::new(
+                        box_size.width(),
+                        box_size.width() * intrinsic.height() / intrinsic.width(),
+                    )
+                } else {
+                    LayoutSize::new(
+                        box_size.height() * intrinsic.width() / intrinsic.height(),
+                        box_size.height(),
+                    )
+                }
+            }
+        };
+
+        let point = LayoutPoint::new(
+            self.point.x() + (box_size.width() - size.width()) / 2,
+            self.point.y() + (box_size.height() - size.height()) / 2,
+        );
+        (point, size)
     }
 
     pub fn compute_position(
@@ -276,6 +801,16 @@ impl LayoutObject {
         let mut point = LayoutPoint::new(0, 0);
 
         match (self.kind(), previous_sibling_kind) {
+            // 表のセルは、前のセルの右に並べる
+            (LayoutObjectKind::Block, _) if self.is_table_cell() => {
+                if let (Some(size), Some(pos)) = (previous_sibling_size, previous_sibling_point) {
+                    point.set_x(pos.x() + size.width());
+                    point.set_y(pos.y());
+                } else {
+                    point.set_x(parent_point.x());
+                    point.set_y(parent_point.y());
+                }
+            }
             // もしブロック要素が兄弟ノードの場合、Y軸方向に進む
             (LayoutObjectKind::Block, _) | (_, LayoutObjectKind::Block) => {
                 if let (Some(size), Some(pos)) = (previous_sibling_size, previous_sibling_point) {
@@ -301,7 +836,10 @@ impl LayoutObject {
             }
         }
 
-        self.point = point;
+        if self.point != point {
+            self.point = point;
+            self.invalidate();
+        }
     }
 
     pub fn is_node_selected(&self, selector: &Selector) -> bool {
@@ -329,13 +867,27 @@ impl LayoutObject {
                     }
                     false
                 }
-                Selector::UnknownSelector => false,
+                Selector::PseudoClassSelector(base, pseudo_class) => {
+                    if let Some(base) = base {
+                        if !self.is_node_selected(base) {
+                            return false;
+                        }
+                    }
+                    match element_sibling_position(&self.node) {
+                        Some((index, count)) => pseudo_class.matches(index, count),
+                        None => false,
+                    }
+                }
+                // 擬似要素のルールは要素そのものには適用しない
+                Selector::PseudoElementSelector(_, _) | Selector::UnknownSelector => false,
             },
             _ => false,
         }
     }
 
     pub fn cascading_style(&mut self, declarations: Vec<Declaration>) {
+        self.invalidate();
+
         for declaration in declarations {
             match declaration.property.as_str() {
                 "background-color" => {
@@ -344,7 +896,7 @@ impl LayoutObject {
                             Ok(color) => color,
                             Err(_) => Color::white(),
                         };
-                        self.style.set_background_color(color);
+                        Rc::make_mut(&mut self.style).set_background_color(color);
                         continue;
                     }
 
@@ -353,7 +905,7 @@ impl LayoutObject {
                             Ok(color) => color,
                             Err(_) => Color::white(),
                         };
-                        self.style.set_background_color(color);
+                        Rc::make_mut(&mut self.style).set_background_color(color);
                         continue;
                     }
                 }
@@ -363,7 +915,7 @@ impl LayoutObject {
                             Ok(color) => color,
                             Err(_) => Color::black(),
                         };
-                        self.style.set_color(color);
+                        Rc::make_mut(&mut self.style).set_color(color);
                     }
 
                     if let ComponentValue::HashToken(color_code) = &declaration.value {
@@ -371,7 +923,47 @@ impl LayoutObject {
                             Ok(color) => color,
                             Err(_) => Color::black(),
                         };
-                        self.style.set_color(color);
+                        Rc::make_mut(&mut self.style).set_color(color);
+                    }
+                }
+                "width" => {
+                    // 単位はpxとして扱う
+                    if let ComponentValue::Number(value) = declaration.value {
+                        Rc::make_mut(&mut self.style).set_width(value);
+                    }
+                }
+                "height" => {
+                    if let ComponentValue::Number(value) = declaration.value {
+                        Rc::make_mut(&mut self.style).set_height(value);
+                    }
+                }
+                "font-weight" => {
+                    let font_weight = match &declaration.value {
+                        ComponentValue::Ident(value) => FontWeight::from_str(value).ok(),
+                        // 600以上の数値は太字として扱う
+                        ComponentValue::Number(value) => Some(if *value >= 600.0 {
+                            FontWeight::Bold
+                        } else {
+                            FontWeight::Normal
+                        }),
+                        _ => None,
+                    };
+                    if let Some(font_weight) = font_weight {
+                        Rc::make_mut(&mut self.style).set_font_weight(font_weight);
+                    }
+                }
+                "font-style" => {
+                    if let ComponentValue::Ident(value) = &declaration.value {
+                        if let Ok(font_style) = FontStyle::from_str(value) {
+                            Rc::make_mut(&mut self.style).set_font_style(font_style);
+                        }
+                    }
+                }
+                "object-fit" => {
+                    if let ComponentValue::Ident(value) = &declaration.value {
+                        if let Ok(object_fit) = ObjectFit::from_str(value) {
+                            Rc::make_mut(&mut self.style).set_object_fit(object_fit);
+                        }
                     }
                 }
                 "display" => {
@@ -380,7 +972,7 @@ impl LayoutObject {
                             Ok(display_type) => display_type,
                             Err(_) => DisplayType::DisplayNone,
                         };
-                        self.style.set_display(display_type)
+                        Rc::make_mut(&mut self.style).set_display(display_type)
                     }
                 }
                 _ => {}
@@ -391,12 +983,15 @@ impl LayoutObject {
     pub fn defaulting_style(
         &mut self,
         node: &Rc<RefCell<Node>>,
-        parent_style: Option<ComputedStyle>,
+        parent_style: Option<Rc<ComputedStyle>>,
     ) {
-        self.style.defaulting(node, parent_style);
+        Rc::make_mut(&mut self.style).defaulting(node, parent_style);
+        self.invalidate();
     }
 
     pub fn update_kind(&mut self) {
+        let previous_kind = self.kind;
+
         match self.node_kind() {
             NodeKind::Document => panic!("should not create a layout object for a Document node"),
             NodeKind::Element(_) => {
@@ -410,6 +1005,11 @@ impl LayoutObject {
                 }
             }
             NodeKind::Text(_) => self.kind = LayoutObjectKind::Text,
+            NodeKind::Comment(_) => panic!("should not create a layout object for a Comment node"),
+        }
+
+        if self.kind != previous_kind {
+            self.invalidate();
         }
     }
 
@@ -421,6 +1021,11 @@ impl LayoutObject {
         self.node.borrow().kind().clone()
     }
 
+    /// このLayoutObjectを作成したDOMノード
+    pub fn node(&self) -> Rc<RefCell<Node>> {
+        self.node.clone()
+    }
+
     pub fn set_first_child(&mut self, first_child: Option<Rc<RefCell<LayoutObject>>>) {
         self.first_child = first_child;
     }
@@ -441,10 +1046,18 @@ impl LayoutObject {
         self.parent.clone()
     }
 
-    pub fn style(&self) -> ComputedStyle {
+    /// ComputedStyleは共有されているので、参照カウントを増やすだけで中身は複製しない。
+    /// 変更するときはRc::make_mutで必要な場合だけコピーする
+    pub fn style(&self) -> Rc<ComputedStyle> {
         self.style.clone()
     }
 
+    /// 兄弟要素で計算済みのスタイルを、計算し直さずにそのまま使う
+    pub fn set_shared_style(&mut self, style: Rc<ComputedStyle>) {
+        self.style = style;
+        self.invalidate();
+    }
+
     pub fn point(&self) -> LayoutPoint {
         self.point
     }
@@ -452,6 +1065,29 @@ impl LayoutObject {
     pub fn size(&self) -> LayoutSize {
         self.size
     }
+
+    /// 子ノードを並べる領域の左上の位置。リストの子ノードはLIST_INDENTだけ右にずらす
+    pub fn content_point(&self) -> LayoutPoint {
+        if self.is_list() {
+            return LayoutPoint::new(self.point.x() + LIST_INDENT, self.point.y());
+        }
+        self.point
+    }
+
+    /// 子ノードを並べる領域の大きさ。リストはずらした分だけ幅を狭める。
+    /// 表の行は、セルに1列分の幅を渡す
+    pub fn content_size(&self) -> LayoutSize {
+        if self.is_table_row() {
+            return LayoutSize::new(
+                self.size.width() / self.table_column_count(),
+                self.size.height(),
+            );
+        }
+        if self.is_list() {
+            return LayoutSize::new((self.size.width() - LIST_INDENT).max(0), self.size.height());
+        }
+        self.size
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Copy)]
@@ -1,118 +1,218 @@
 use crate::constants::CONTENT_AREA_WIDTH;
+use crate::constants::DEBUG_OVERLAY_BLOCK_COLOR;
+use crate::constants::DEBUG_OVERLAY_INLINE_COLOR;
+use crate::constants::DEBUG_OVERLAY_TEXT_COLOR;
+use crate::constants::MAX_TREE_DEPTH;
 use crate::display_item::DisplayItem;
+use crate::renderer::css::cssom::PseudoElement;
 use crate::renderer::css::cssom::StyleSheet;
 use crate::renderer::dom::api::get_target_element_node;
 use crate::renderer::dom::node::ElementKind;
 use crate::renderer::dom::node::Node;
+use crate::renderer::layout::computed_style::Color;
+use crate::renderer::layout::computed_style::ComputedStyle;
+use crate::renderer::layout::computed_style::DisplayType;
+use crate::renderer::layout::computed_style::FontSize;
+use crate::renderer::layout::computed_style::FontStyle;
+use crate::renderer::layout::computed_style::FontWeight;
+use crate::renderer::layout::computed_style::TextDecoration;
 use crate::renderer::layout::layout_object::create_layout_object;
+use crate::renderer::layout::layout_object::create_pseudo_element_layout_object;
 use crate::renderer::layout::layout_object::LayoutObject;
 use crate::renderer::layout::layout_object::LayoutObjectKind;
 use crate::renderer::layout::layout_object::LayoutPoint;
 use crate::renderer::layout::layout_object::LayoutSize;
+use crate::renderer::layout::layout_object::StyleSharingCache;
+use crate::renderer::layout::text_run::TextRunCache;
+use alloc::collections::BTreeMap;
+use alloc::format;
 use alloc::rc::Rc;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 
+/// DOMノードからLayoutObjectを引くためのキー。LayoutObjectがDOMノードへの参照を持つので、
+/// レイアウトツリーが存在する間はアドレスが再利用されることはない
+type NodeKey = usize;
+
+fn node_key(node: &Rc<RefCell<Node>>) -> NodeKey {
+    Rc::as_ptr(node) as NodeKey
+}
+
+/// 作成したLayoutObjectをレイアウトツリーのどこにつなぐか
+enum Attachment {
+    Root,
+    FirstChildOf(Rc<RefCell<LayoutObject>>),
+    NextSiblingOf(Rc<RefCell<LayoutObject>>),
+}
+
 fn build_layout_tree(
     node: &Option<Rc<RefCell<Node>>>,
-    parent_obj: &Option<Rc<RefCell<LayoutObject>>>,
     cssom: &StyleSheet,
+    cache: &mut StyleSharingCache,
+    node_map: &mut BTreeMap<NodeKey, Rc<RefCell<LayoutObject>>>,
+    depth_limit: usize,
 ) -> Option<Rc<RefCell<LayoutObject>>> {
-    // `create_layout_object`関数によって、ノードとなるLayoutObjectの作成を試みる。
-    // CSSによって"display:none"が指定されていた場合、ノードは作成されない
-    let mut target_node = node.clone();
-    let mut layout_object = create_layout_object(node, parent_obj, cssom);
-    // もしノードが作成されなかった場合、DOMノードの兄弟ノードを使用してLayoutObjectの
-    // 作成を試みる。LayoutObjectが作成されるまで、兄弟ノードを辿り続ける
-    while layout_object.is_none() {
-        if let Some(n) = target_node {
-            target_node = n.borrow().next_sibling().clone();
-            layout_object = create_layout_object(&target_node, parent_obj, cssom);
-        } else {
-            // もし兄弟ノードがない���合、処理するべきDOMツリーは終了したので、今まで
-            // 作成したレイアウトツリーを返す
-            return layout_object;
-        }
-    }
-
-    if let Some(n) = target_node {
-        let original_first_child = n.borrow().first_child();
-        let original_next_sibling = n.borrow().next_sibling();
-        let mut first_child = build_layout_tree(&original_first_child, &layout_object, cssom);
-        let mut next_sibling = build_layout_tree(&original_next_sibling, &None, cssom);
+    let mut root = None;
 
-        // もし子ノードに"display:node"が指定されていた場合、LayoutObjectは作成され
-        // ないため、子ノードの兄弟ノードを使用してLayoutObjectの作成を試みる。
-        // LayoutObjectが作成されるか、辿るべき兄弟ノードがなくなるまで処理を繰り返す
-        if first_child.is_none() && original_first_child.is_some() {
-            let mut original_dom_node = original_first_child
-                .expect("first child should exist")
-                .borrow()
-                .next_sibling();
+    // 再帰を使わずに、これから処理するDOMノードを明示的なスタックで管理する。
+    // (DOMノード, 親のLayoutObject, つなぐ場所, 深さ)
+    let mut stack = vec![(node.clone(), None, Attachment::Root, 0)];
+    // ::after擬似要素は子ノードをすべてつないだ後に、最後の子として追加する
+    let mut after_objects = Vec::new();
 
-            loop {
-                first_child = build_layout_tree(&original_dom_node, &layout_object, cssom);
+    while let Some((dom_node, parent_obj, attachment, depth)) = stack.pop() {
+        // `create_layout_object`関数によって、ノードとなるLayoutObjectの作成を試みる。
+        // CSSによって"display:none"が指定されていた場合、ノードは作成されないので、
+        // LayoutObjectが作成されるまで、兄弟ノードを辿り続ける
+        let mut target_node = dom_node;
+        let mut layout_object = create_layout_object(&target_node, &parent_obj, cssom, cache);
+        while layout_object.is_none() {
+            target_node = match target_node {
+                Some(n) => n.borrow().next_sibling(),
+                None => break,
+            };
+            layout_object = create_layout_object(&target_node, &parent_obj, cssom, cache);
+        }
 
-                if first_child.is_none() && original_dom_node.is_some() {
-                    original_dom_node = original_dom_node
-                        .expect("next sibling should exist")
-                        .borrow()
-                        .next_sibling();
-                    continue;
-                }
+        let (n, obj) = match (target_node, layout_object) {
+            (Some(n), Some(obj)) => (n, obj),
+            // もし兄弟ノードがない場合、このノードの位置につなぐLayoutObjectはない
+            _ => continue,
+        };
+        node_map.insert(node_key(&n), obj.clone());
 
-                break;
+        match attachment {
+            Attachment::Root => root = Some(obj.clone()),
+            Attachment::FirstChildOf(parent) => {
+                parent.borrow_mut().set_first_child(Some(obj.clone()))
+            }
+            Attachment::NextSiblingOf(previous) => {
+                previous.borrow_mut().set_next_sibling(Some(obj.clone()))
             }
         }
 
-        // もし兄弟ノードに"display:node"が指定されていた場合、LayoutObjectは作成され
-        // ないため、兄弟ノードの兄弟ノードを使用してLayoutObjectの作成を試みる。
-        // LayoutObjectが作成されるか、辿るべき兄弟ノードがなくなるまで処理を繰り返す
-        if next_sibling.is_none() && n.borrow().next_sibling().is_some() {
-            let mut original_dom_node = original_next_sibling
-                .expect("first child should exist")
-                .borrow()
-                .next_sibling();
-
-            loop {
-                next_sibling = build_layout_tree(&original_dom_node, &None, cssom);
-
-                if next_sibling.is_none() && original_dom_node.is_some() {
-                    original_dom_node = original_dom_node
-                        .expect("next sibling should exist")
-                        .borrow()
-                        .next_sibling();
-                    continue;
+        // 子ノードを兄弟ノードより先に処理するため、兄弟ノードを先にスタックに積む。
+        // 兄弟ノードも、同じ親からスタイルを継承する
+        stack.push((
+            n.borrow().next_sibling(),
+            parent_obj,
+            Attachment::NextSiblingOf(obj.clone()),
+            depth,
+        ));
+        // <iframe>の子ノードは代替コンテンツなので、レイアウトツリーに含めない。
+        // 中身は子フレームとして別に描画する
+        if depth < depth_limit && !obj.borrow().is_iframe() {
+            if n.borrow().get_element().is_some() {
+                if let Some(after) =
+                    create_pseudo_element_layout_object(&obj, cssom, PseudoElement::After)
+                {
+                    after_objects.push((obj.clone(), after));
                 }
-
-                break;
             }
+
+            // ::before擬似要素は最初の子になり、DOMの子ノードはその後ろにつなぐ
+            let before = if n.borrow().get_element().is_some() {
+                create_pseudo_element_layout_object(&obj, cssom, PseudoElement::Before)
+            } else {
+                None
+            };
+            let attachment = match before {
+                Some(before) => {
+                    obj.borrow_mut().set_first_child(Some(before.clone()));
+                    Attachment::NextSiblingOf(before)
+                }
+                None => Attachment::FirstChildOf(obj.clone()),
+            };
+            stack.push((n.borrow().first_child(), Some(obj), attachment, depth + 1));
         }
+    }
 
-        let obj = match layout_object {
-            Some(ref obj) => obj,
-            None => panic!("render object should exist here"),
+    for (obj, after) in after_objects {
+        let first_child = obj.borrow().first_child();
+        let mut last_child = match first_child {
+            Some(child) => child,
+            None => {
+                obj.borrow_mut().set_first_child(Some(after));
+                continue;
+            }
         };
-        obj.borrow_mut().set_first_child(first_child);
-        obj.borrow_mut().set_next_sibling(next_sibling);
+        loop {
+            let next = last_child.borrow().next_sibling();
+            match next {
+                Some(next) => last_child = next,
+                None => break,
+            }
+        }
+        last_child.borrow_mut().set_next_sibling(Some(after));
     }
 
-    layout_object
+    root
+}
+
+/// ツリーを明示的なスタックで辿るとき、子孫を処理する前と後のどちらの処理かを表す
+enum Visit {
+    Enter(Rc<RefCell<LayoutObject>>),
+    Leave(Rc<RefCell<LayoutObject>>),
 }
 
 #[derive(Debug, Clone)]
 pub struct LayoutView {
     root: Option<Rc<RefCell<LayoutObject>>>,
+    text_run_cache: TextRunCache,
+    /// DOMノードから、そのノードのために作られたLayoutObjectへの対応
+    node_map: BTreeMap<NodeKey, Rc<RefCell<LayoutObject>>>,
+    /// ブロック要素を並べる幅。ビューポートがページに決めたレイアウトの幅
+    viewport_width: i64,
 }
 
 impl LayoutView {
     pub fn new(root: Rc<RefCell<Node>>, cssom: &StyleSheet) -> Self {
+        Self::with_depth_limit(root, cssom, MAX_TREE_DEPTH)
+    }
+
+    /// depth_limitより深い要素はレイアウトツリーに含めない
+    pub fn with_depth_limit(
+        root: Rc<RefCell<Node>>,
+        cssom: &StyleSheet,
+        depth_limit: usize,
+    ) -> Self {
+        Self::build(root, cssom, depth_limit, CONTENT_AREA_WIDTH)
+    }
+
+    /// ブロック要素を、コンテンツエリアの幅ではなくviewport_widthの幅で並べる
+    pub fn with_viewport_width(
+        root: Rc<RefCell<Node>>,
+        cssom: &StyleSheet,
+        viewport_width: i64,
+    ) -> Self {
+        Self::build(root, cssom, MAX_TREE_DEPTH, viewport_width)
+    }
+
+    fn build(
+        root: Rc<RefCell<Node>>,
+        cssom: &StyleSheet,
+        depth_limit: usize,
+        viewport_width: i64,
+    ) -> Self {
         // レイアウトツリーは描画される要素だけを持つツリーなので、<body>タグを取得し、その子要素以下を
         // レイアウトツリーのノードに変換する。
         let body_root = get_target_element_node(Some(root), ElementKind::Body);
 
+        let mut node_map = BTreeMap::new();
+        let root = build_layout_tree(
+            &body_root,
+            cssom,
+            &mut StyleSharingCache::new(),
+            &mut node_map,
+            depth_limit,
+        );
+
         let mut tree = Self {
-            root: build_layout_tree(&body_root, &None, cssom),
+            root,
+            text_run_cache: TextRunCache::new(),
+            node_map,
+            viewport_width,
         };
 
         tree.update_layout();
@@ -121,57 +221,72 @@ impl LayoutView {
     }
 
     pub fn find_node_by_position(&self, position: (i64, i64)) -> Option<Rc<RefCell<LayoutObject>>> {
-        Self::find_node_by_position_internal(&self.root(), position)
-    }
-
-    fn find_node_by_position_internal(
-        node: &Option<Rc<RefCell<LayoutObject>>>,
-        position: (i64, i64),
-    ) -> Option<Rc<RefCell<LayoutObject>>> {
-        match node {
-            Some(n) => {
-                let first_child = n.borrow().first_child();
-                let result1 = Self::find_node_by_position_internal(&first_child, position);
-                if result1.is_some() {
-                    return result1;
-                }
+        // 子孫ノード、兄弟ノード、自分自身の順に調べ、最初に見つかったノードを返す
+        let mut stack = Vec::new();
+        if let Some(root) = self.root() {
+            stack.push(Visit::Enter(root));
+        }
 
-                let next_sibling = n.borrow().next_sibling();
-                let result2 = Self::find_node_by_position_internal(&next_sibling, position);
-                if result2.is_some() {
-                    return result2;
+        while let Some(visit) = stack.pop() {
+            match visit {
+                Visit::Enter(n) => {
+                    stack.push(Visit::Leave(n.clone()));
+                    if let Some(next_sibling) = n.borrow().next_sibling() {
+                        stack.push(Visit::Enter(next_sibling));
+                    }
+                    if let Some(first_child) = n.borrow().first_child() {
+                        stack.push(Visit::Enter(first_child));
+                    }
                 }
-
-                if n.borrow().point().x() <= position.0
-                    && position.0 <= (n.borrow().point().x() + n.borrow().size().width())
-                    && n.borrow().point().y() <= position.1
-                    && position.1 <= (n.borrow().point().y() + n.borrow().size().height())
-                {
-                    return Some(n.clone());
+                Visit::Leave(n) => {
+                    if n.borrow().point().x() <= position.0
+                        && position.0 <= (n.borrow().point().x() + n.borrow().size().width())
+                        && n.borrow().point().y() <= position.1
+                        && position.1 <= (n.borrow().point().y() + n.borrow().size().height())
+                    {
+                        return Some(n);
+                    }
                 }
-                None
             }
-            None => None,
         }
+
+        None
     }
 
-    fn calculate_node_size(node: &Option<Rc<RefCell<LayoutObject>>>, parent_size: LayoutSize) {
+    fn calculate_node_size(
+        node: &Option<Rc<RefCell<LayoutObject>>>,
+        parent_size: LayoutSize,
+        text_run_cache: &mut TextRunCache,
+    ) {
+        let mut stack = Vec::new();
         if let Some(n) = node {
-            // ノードがブロック要素の場合、子ノードのレイアウトを計算する前に横幅を決める
-            if n.borrow().kind() == LayoutObjectKind::Block {
-                n.borrow_mut().compute_size(parent_size);
-            }
-
-            let first_child = n.borrow().first_child();
-            Self::calculate_node_size(&first_child, n.borrow().size());
+            stack.push((Visit::Enter(n.clone()), parent_size));
+        }
 
-            let next_sibling = n.borrow().next_sibling();
-            Self::calculate_node_size(&next_sibling, parent_size);
+        while let Some((visit, parent_size)) = stack.pop() {
+            match visit {
+                Visit::Enter(n) => {
+                    // ノードがブロック要素の場合、子ノードのレイアウトを計算する前に横幅を決める
+                    if n.borrow().kind() == LayoutObjectKind::Block {
+                        n.borrow_mut().compute_size(parent_size, text_run_cache);
+                    }
 
-            // 子ノードのサイズが決まった後にサイズを計算する。
-            // ブロック要素のとき、高さは子ノードの高さに依存する
-            // インライン要素のとき、高さも横幅も子ノードに依存する
-            n.borrow_mut().compute_size(parent_size);
+                    // 子ノード、兄弟ノードの順に計算した後、このノードのサイズを計算し直す
+                    stack.push((Visit::Leave(n.clone()), parent_size));
+                    if let Some(next_sibling) = n.borrow().next_sibling() {
+                        stack.push((Visit::Enter(next_sibling), parent_size));
+                    }
+                    if let Some(first_child) = n.borrow().first_child() {
+                        stack.push((Visit::Enter(first_child), n.borrow().content_size()));
+                    }
+                }
+                Visit::Leave(n) => {
+                    // 子ノードのサイズが決まった後にサイズを計算する。
+                    // ブロック要素のとき、高さは子ノードの高さに依存する
+                    // インライン要素のとき、高さも横幅も子ノードに依存する
+                    n.borrow_mut().compute_size(parent_size, text_run_cache);
+                }
+            }
         }
     }
 
@@ -182,38 +297,57 @@ impl LayoutView {
         previous_sibling_point: Option<LayoutPoint>,
         previous_sibling_size: Option<LayoutSize>,
     ) {
+        let mut stack = Vec::new();
         if let Some(n) = node {
-            n.borrow_mut().compute_position(
+            stack.push((
+                n.clone(),
                 parent_point,
                 previous_sibling_kind,
                 previous_sibling_point,
                 previous_sibling_size,
-            );
-
-            // ノード（node）の子ノードの位置を計算をする
-            let first_child = n.borrow().first_child();
-            Self::calculate_node_position(
-                &first_child,
-                n.borrow().point(),
-                LayoutObjectKind::Block,
-                None,
-                None,
-            );
+            ));
+        }
 
-            // ノード（node）の兄弟ノードの位置を計算する
-            let next_sibling = n.borrow().next_sibling();
-            Self::calculate_node_position(
-                &next_sibling,
+        while let Some((n, parent_point, previous_sibling_kind, previous_point, previous_size)) =
+            stack.pop()
+        {
+            n.borrow_mut().compute_position(
                 parent_point,
-                n.borrow().kind(),
-                Some(n.borrow().point()),
-                Some(n.borrow().size()),
+                previous_sibling_kind,
+                previous_point,
+                previous_size,
             );
+
+            // ノード（node）の兄弟ノードの位置を、子ノードの後に計算する
+            if let Some(next_sibling) = n.borrow().next_sibling() {
+                stack.push((
+                    next_sibling,
+                    parent_point,
+                    n.borrow().kind(),
+                    Some(n.borrow().point()),
+                    Some(n.borrow().size()),
+                ));
+            }
+
+            // ノード（node）の子ノードの位置を計算をする
+            if let Some(first_child) = n.borrow().first_child() {
+                stack.push((
+                    first_child,
+                    n.borrow().content_point(),
+                    LayoutObjectKind::Block,
+                    None,
+                    None,
+                ));
+            }
         }
     }
 
     fn update_layout(&mut self) {
-        Self::calculate_node_size(&self.root, LayoutSize::new(CONTENT_AREA_WIDTH, 0));
+        Self::calculate_node_size(
+            &self.root,
+            LayoutSize::new(self.viewport_width, 0),
+            &mut self.text_run_cache,
+        );
 
         Self::calculate_node_position(
             &self.root,
@@ -224,46 +358,186 @@ impl LayoutView {
         );
     }
 
-    fn paint_node(node: &Option<Rc<RefCell<LayoutObject>>>, display_items: &mut Vec<DisplayItem>) {
-        match node {
-            Some(n) => {
-                display_items.extend(n.borrow_mut().paint());
+    pub fn paint(&self) -> Vec<DisplayItem> {
+        let mut display_items = Vec::new();
 
-                let first_child = n.borrow().first_child();
-                Self::paint_node(&first_child, display_items);
+        // 自分自身、子ノード、兄弟ノードの順に描画する
+        let mut stack: Vec<Rc<RefCell<LayoutObject>>> = self.root.iter().cloned().collect();
+        while let Some(n) = stack.pop() {
+            display_items.extend(n.borrow_mut().paint().iter().cloned());
 
-                let next_sibling = n.borrow().next_sibling();
-                Self::paint_node(&next_sibling, display_items);
+            if let Some(next_sibling) = n.borrow().next_sibling() {
+                stack.push(next_sibling);
+            }
+            if let Some(first_child) = n.borrow().first_child() {
+                stack.push(first_child);
             }
-            None => (),
         }
+
+        display_items
     }
 
-    pub fn paint(&self) -> Vec<DisplayItem> {
-        let mut display_items = Vec::new();
+    /// devtoolsのボックスの強調表示のように、全てのLayoutObjectの枠と座標を描くDisplayItemを作る。
+    /// 色を重ねて半透明にすることはできないので、幅1pxの四角で枠だけを描き、ページの内容を隠さない。
+    /// 座標は"x,y 幅x高さ"の形式で、枠の左上に書く
+    pub fn paint_debug_overlay(&self) -> Vec<DisplayItem> {
+        let block_style = outline_style(DEBUG_OVERLAY_BLOCK_COLOR);
+        let inline_style = outline_style(DEBUG_OVERLAY_INLINE_COLOR);
+        let text_style = outline_style(DEBUG_OVERLAY_TEXT_COLOR);
 
-        Self::paint_node(&self.root, &mut display_items);
+        let mut outlines = Vec::new();
+        let mut labels = Vec::new();
+        let mut stack: Vec<Rc<RefCell<LayoutObject>>> = self.root.iter().cloned().collect();
+        while let Some(n) = stack.pop() {
+            let object = n.borrow();
+            if object.style().display() != DisplayType::DisplayNone {
+                let style = match object.kind() {
+                    LayoutObjectKind::Block => block_style.clone(),
+                    LayoutObjectKind::Inline => inline_style.clone(),
+                    LayoutObjectKind::Text => text_style.clone(),
+                };
+                let (point, size) = (object.point(), object.size());
+                outlines.extend(outline_rects(&style, point, size));
+                labels.push(DisplayItem::Text {
+                    text: format!(
+                        "{},{} {}x{}",
+                        point.x(),
+                        point.y(),
+                        size.width(),
+                        size.height()
+                    ),
+                    style,
+                    layout_point: point,
+                });
+            }
 
-        display_items
+            if let Some(next_sibling) = object.next_sibling() {
+                stack.push(next_sibling);
+            }
+            if let Some(first_child) = object.first_child() {
+                stack.push(first_child);
+            }
+        }
+
+        // 座標が枠に隠れないように、全ての枠の後に描く
+        outlines.extend(labels);
+        outlines
+    }
+
+    /// 画像の大きさのような、置換要素の中身が本来持つ大きさが変わった後に、全体の大きさと位置を計算し直す
+    pub fn relayout(&mut self) {
+        self.update_layout();
+    }
+
+    /// レイアウトツリーの中にある<iframe>のLayoutObjectを文書順に返す
+    pub fn iframes(&self) -> Vec<Rc<RefCell<LayoutObject>>> {
+        self.find_all(|o| o.is_iframe())
+    }
+
+    /// レイアウトツリーの中にある<img>のLayoutObjectを文書順に返す
+    pub fn images(&self) -> Vec<Rc<RefCell<LayoutObject>>> {
+        self.find_all(|o| o.is_image())
+    }
+
+    fn find_all(
+        &self,
+        predicate: impl Fn(&LayoutObject) -> bool,
+    ) -> Vec<Rc<RefCell<LayoutObject>>> {
+        let mut found = Vec::new();
+
+        let mut stack: Vec<Rc<RefCell<LayoutObject>>> = self.root.iter().cloned().collect();
+        while let Some(n) = stack.pop() {
+            if predicate(&n.borrow()) {
+                found.push(n.clone());
+            }
+
+            if let Some(next_sibling) = n.borrow().next_sibling() {
+                stack.push(next_sibling);
+            }
+            if let Some(first_child) = n.borrow().first_child() {
+                stack.push(first_child);
+            }
+        }
+
+        found
     }
 
     pub fn root(&self) -> Option<Rc<RefCell<LayoutObject>>> {
         self.root.clone()
     }
+
+    /// DOMノードに対応するLayoutObjectを返す。display:noneなどで描画されないノードの場合はNone。
+    /// 逆向きの対応はLayoutObject::node()で得られる
+    pub fn layout_object_for_node(
+        &self,
+        node: &Rc<RefCell<Node>>,
+    ) -> Option<Rc<RefCell<LayoutObject>>> {
+        self.node_map.get(&node_key(node)).cloned()
+    }
+}
+
+/// 枠を描くための、背景と文字を`color`の色にしたスタイル
+pub fn outline_style(color: &str) -> Rc<ComputedStyle> {
+    let color = Color::from_name(color).expect("outline colors should be supported");
+    let mut style = ComputedStyle::new();
+    style.set_background_color(color.clone());
+    style.set_color(color);
+    style.set_display(DisplayType::Block);
+    style.set_font_size(FontSize::Medium);
+    style.set_font_weight(FontWeight::Normal);
+    style.set_font_style(FontStyle::Normal);
+    style.set_text_decoration(TextDecoration::None);
+    Rc::new(style)
+}
+
+/// `point`と`size`の矩形の上下左右の辺を、幅1pxの四角で表す。大きさが0の辺は描かない
+pub fn outline_rects(
+    style: &Rc<ComputedStyle>,
+    point: LayoutPoint,
+    size: LayoutSize,
+) -> Vec<DisplayItem> {
+    let (x, y, width, height) = (point.x(), point.y(), size.width(), size.height());
+    let edges = [
+        (x, y, width, 1),
+        (x, y + height - 1, width, 1),
+        (x, y, 1, height),
+        (x + width - 1, y, 1, height),
+    ];
+
+    edges
+        .iter()
+        .filter(|(_, _, w, h)| *w > 0 && *h > 0)
+        .map(|(x, y, w, h)| DisplayItem::Rect {
+            style: style.clone(),
+            layout_point: LayoutPoint::new(*x, *y),
+            layout_size: LayoutSize::new(*w, *h),
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::alloc::string::ToString;
+    use crate::constants::BOLD_STROKE_OFFSET;
+    use crate::constants::CHAR_HEIGHT_WITH_PADDING;
+    use crate::constants::IMAGE_ALT_PADDING;
+    use crate::constants::LIST_INDENT;
+    use crate::constants::LIST_MARKER_GAP;
     use crate::renderer::css::cssom::CssParser;
     use crate::renderer::css::token::CssTokenizer;
+    use crate::renderer::dom::api::append_child;
+    use crate::renderer::dom::api::get_element_by_id;
     use crate::renderer::dom::api::get_style_content;
     use crate::renderer::dom::node::Element;
     use crate::renderer::dom::node::NodeKind;
     use crate::renderer::html::parser::HtmlParser;
     use crate::renderer::html::token::HtmlTokenizer;
+    use crate::renderer::layout::computed_style::Color;
+    use crate::renderer::layout::font::text_width;
+    use alloc::format;
     use alloc::string::String;
+    use alloc::vec;
     use alloc::vec::Vec;
 
     fn create_layout_view(html: String) -> LayoutView {
@@ -339,6 +613,230 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_div_and_span() {
+        let html = "<html><head></head><body><div>a<span>b</span></div><div>c</div></body></html>"
+            .to_string();
+        let layout_view = create_layout_view(html);
+        let body = layout_view.root().expect("root should exist");
+
+        // <div>はブロック要素、<span>はインライン要素として扱う
+        let div = body.borrow().first_child().expect("div should exist");
+        assert_eq!(LayoutObjectKind::Block, div.borrow().kind());
+        let span = div
+            .borrow()
+            .first_child()
+            .and_then(|text| text.borrow().next_sibling())
+            .expect("span should exist");
+        assert_eq!(LayoutObjectKind::Inline, span.borrow().kind());
+
+        // 次の<div>は、前の<div>の下に並ぶ
+        let next_div = div.borrow().next_sibling().expect("div should exist");
+        assert_eq!(LayoutObjectKind::Block, next_div.borrow().kind());
+        assert_eq!(
+            div.borrow().point().y() + div.borrow().size().height(),
+            next_div.borrow().point().y()
+        );
+    }
+
+    #[test]
+    fn test_line_break() {
+        let html = "<html><head></head><body><p>a<br>b</p><p>c</p></body></html>".to_string();
+        let layout_view = create_layout_view(html);
+        let texts: Vec<(String, LayoutPoint)> = layout_view
+            .paint()
+            .into_iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text {
+                    text, layout_point, ..
+                } => Some((text, layout_point)),
+                _ => None,
+            })
+            .collect();
+
+        // <br>の後ろのテキストは、次の行の先頭から並べる。<br>自身は何も描かない
+        assert_eq!(3, texts.len());
+        assert_eq!(texts[0].1.x(), texts[1].1.x());
+        assert!(texts[0].1.y() < texts[1].1.y());
+        assert!(texts[1].1.y() < texts[2].1.y());
+        let p = layout_view
+            .root()
+            .and_then(|body| body.borrow().first_child())
+            .expect("p should exist");
+        assert_eq!(texts[2].1.y() - texts[0].1.y(), p.borrow().size().height());
+    }
+
+    #[test]
+    fn test_formatting_elements() {
+        let html = "<html><head><style>.plain { font-weight: normal; font-style: normal; } .heavy { font-weight: 700; }</style></head><body><p><b>a<i>b</i></b><em>c</em><u>d</u><strong class=plain>e</strong><span class=heavy>f</span></p></body></html>".to_string();
+        let layout_view = create_layout_view(html);
+        let texts: Vec<(String, FontWeight, FontStyle, TextDecoration)> = layout_view
+            .paint()
+            .into_iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text { text, style, .. } => Some((
+                    text,
+                    style.font_weight(),
+                    style.font_style(),
+                    style.text_decoration(),
+                )),
+                _ => None,
+            })
+            .collect();
+
+        use FontStyle::Italic;
+        use FontWeight::Bold;
+        assert_eq!(
+            vec![
+                (
+                    "a".to_string(),
+                    Bold,
+                    FontStyle::Normal,
+                    TextDecoration::None
+                ),
+                ("b".to_string(), Bold, Italic, TextDecoration::None),
+                (
+                    "c".to_string(),
+                    FontWeight::Normal,
+                    Italic,
+                    TextDecoration::None
+                ),
+                (
+                    "d".to_string(),
+                    FontWeight::Normal,
+                    FontStyle::Normal,
+                    TextDecoration::Underline
+                ),
+                (
+                    "e".to_string(),
+                    FontWeight::Normal,
+                    FontStyle::Normal,
+                    TextDecoration::None
+                ),
+                (
+                    "f".to_string(),
+                    Bold,
+                    FontStyle::Normal,
+                    TextDecoration::None
+                ),
+            ],
+            texts
+        );
+    }
+
+    #[test]
+    fn test_text_width() {
+        let html = "<html><head></head><body><p>日本</p><p><b>ab</b></p></body></html>".to_string();
+        let layout_view = create_layout_view(html);
+        let first = layout_view
+            .root()
+            .and_then(|body| body.borrow().first_child())
+            .expect("p should exist");
+        let second = first.borrow().next_sibling().expect("p should exist");
+        let width = |p: &Rc<RefCell<LayoutObject>>| {
+            p.borrow()
+                .first_child()
+                .expect("child should exist")
+                .borrow()
+                .size()
+                .width()
+        };
+
+        // 日本語のテキストは、UTF-8のバイト数ではなく描画する文字の幅で並べる
+        assert_eq!(text_width("日本", FontSize::Medium), width(&first));
+        // 太字のテキストは、重ねて描く分だけ広く取る
+        assert_eq!(
+            text_width("ab", FontSize::Medium) + BOLD_STROKE_OFFSET,
+            width(&second)
+        );
+    }
+
+    #[test]
+    fn test_list() {
+        let html =
+            "<html><head></head><body><ol start=9><li>a<li>b</ol><ul><li>c</ul></body></html>"
+                .to_string();
+        let layout_view = create_layout_view(html);
+        let body = layout_view.root().expect("root should exist");
+
+        // リストの項目は、リストの左端からLIST_INDENTだけ右に並べる
+        let ol = body.borrow().first_child().expect("ol should exist");
+        let li = ol.borrow().first_child().expect("li should exist");
+        assert_eq!(LayoutObjectKind::Block, li.borrow().kind());
+        assert_eq!(
+            ol.borrow().point().x() + LIST_INDENT,
+            li.borrow().point().x()
+        );
+        assert_eq!(
+            ol.borrow().size().width() - LIST_INDENT,
+            li.borrow().size().width()
+        );
+
+        // マーカーは項目の左に描く。<ol>の中ではstart属性の番号から数える
+        let markers: Vec<(String, i64)> = layout_view
+            .paint()
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text {
+                    text, layout_point, ..
+                } if text.ends_with('.') || text == "*" => Some((text.clone(), layout_point.x())),
+                _ => None,
+            })
+            .collect();
+        let x = |marker: &str| {
+            li.borrow().point().x() - text_width(marker, FontSize::Medium) - LIST_MARKER_GAP
+        };
+        assert_eq!(
+            vec![
+                ("9.".to_string(), x("9.")),
+                ("10.".to_string(), x("10.")),
+                ("*".to_string(), x("*")),
+            ],
+            markers
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        let html = "<html><head></head><body><table><tr><td>a<td>b<td>c<tr><td>d<p>e</table></body></html>"
+            .to_string();
+        let layout_view = create_layout_view(html);
+        let body = layout_view.root().expect("root should exist");
+        let table = body.borrow().first_child().expect("table should exist");
+        let tbody = table.borrow().first_child().expect("tbody should exist");
+        let first_row = tbody.borrow().first_child().expect("tr should exist");
+        let second_row = first_row.borrow().next_sibling().expect("tr should exist");
+
+        // 列の幅は、最も多くセルがある行に合わせて表の幅を等分する
+        let column_width = table.borrow().size().width() / 3;
+        let mut x = table.borrow().point().x();
+        let mut cell = first_row.borrow().first_child();
+        while let Some(c) = cell {
+            assert_eq!(
+                LayoutPoint::new(x, first_row.borrow().point().y()),
+                c.borrow().point()
+            );
+            assert_eq!(column_width, c.borrow().size().width());
+            x += column_width;
+            cell = c.borrow().next_sibling();
+        }
+
+        // 行の高さは最も高いセルに合わせ、次の行はその下に並べる
+        let tall_cell = second_row.borrow().first_child().expect("td should exist");
+        assert_eq!(
+            tall_cell.borrow().size().height(),
+            second_row.borrow().size().height()
+        );
+        assert_eq!(
+            first_row.borrow().point().y() + first_row.borrow().size().height(),
+            second_row.borrow().point().y()
+        );
+        assert_eq!(
+            first_row.borrow().size().height() + second_row.borrow().size().height(),
+            table.borrow().size().height()
+        );
+    }
+
     #[test]
     fn test_display_none() {
         let html = "<html><head><style>body{display:none;}</style></head><body>text</body></html>"
@@ -404,4 +902,446 @@ mod tests {
             .next_sibling()
             .is_none());
     }
+
+    #[test]
+    fn test_paint_cache() {
+        let html = "<html><head></head><body><p>text</p></body></html>".to_string();
+        let mut layout_view = create_layout_view(html);
+
+        let p = layout_view
+            .root()
+            .expect("root should exist")
+            .borrow()
+            .first_child()
+            .expect("p node should exist");
+        let generation = p.borrow().generation();
+        let items = layout_view.paint();
+        let painted = p.borrow_mut().paint();
+
+        // レイアウトに変化がなければ、世代番号は変わらず同じDisplayItemが返る。
+        // キャッシュしたDisplayItemは複製せずに共有する
+        layout_view.update_layout();
+        assert_eq!(generation, p.borrow().generation());
+        assert_eq!(items, layout_view.paint());
+        assert!(Rc::ptr_eq(&painted, &p.borrow_mut().paint()));
+    }
+
+    #[test]
+    fn test_paint_debug_overlay() {
+        let html = "<html><head></head><body><p>text</p></body></html>".to_string();
+        let layout_view = create_layout_view(html);
+
+        let items = layout_view.paint_debug_overlay();
+        let labels: Vec<String> = items
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text { text, .. } => Some(text.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                "0,0 590x20".to_string(),
+                "0,0 590x20".to_string(),
+                "0,0 32x20".to_string()
+            ],
+            labels
+        );
+        // <body>と<p>とテキストに、4辺ずつの枠がある
+        assert_eq!(12 + 3, items.len());
+        match &items[1] {
+            DisplayItem::Rect {
+                style,
+                layout_point,
+                layout_size,
+            } => {
+                assert_eq!(LayoutPoint::new(0, 19), *layout_point);
+                assert_eq!(LayoutSize::new(590, 1), *layout_size);
+                assert_eq!(0xff00ff, style.background_color().code_u32());
+            }
+            _ => panic!("overlay should start with outlines"),
+        }
+    }
+
+    #[test]
+    fn test_display_item_shares_style() {
+        let html = "<html><head></head><body><p>text</p></body></html>".to_string();
+        let layout_view = create_layout_view(html);
+
+        let body = layout_view.root().expect("root should exist");
+        let items = layout_view.paint();
+
+        // DisplayItemはComputedStyleを複製せず、LayoutObjectと同じものを参照する
+        match items.first() {
+            Some(DisplayItem::Rect { style, .. }) => {
+                assert!(Rc::ptr_eq(style, &body.borrow().style()))
+            }
+            _ => panic!("first display item should be a rect for body"),
+        }
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let t = HtmlTokenizer::new("<html><head></head><body></body></html>".to_string());
+        let window = HtmlParser::new(t).construct_tree();
+        let dom = window.borrow().document();
+        let body = get_target_element_node(Some(dom.clone()), ElementKind::Body)
+            .expect("body should exist");
+
+        // <body><p><p><p>...</p></p></p></body>のように深くネストしたツリーを作る
+        let mut parent = body;
+        for _ in 0..10 {
+            let p = Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
+                "p",
+                Vec::new(),
+            )))));
+            append_child(&parent, p.clone());
+            parent = p;
+        }
+
+        let cssom = CssParser::new(CssTokenizer::new(String::new())).parse_stylesheet();
+        let layout_view = LayoutView::with_depth_limit(dom, &cssom, 3);
+
+        let mut depth = 0;
+        let mut node = layout_view.root();
+        while let Some(n) = node {
+            node = n.borrow().first_child();
+            depth += 1;
+        }
+        // body自身と、深さ3までの子孫だけがレイアウトツリーに含まれる
+        assert_eq!(4, depth);
+        assert_eq!(4, layout_view.paint().len());
+    }
+
+    #[test]
+    fn test_viewport_width() {
+        let t = HtmlTokenizer::new("<html><body><p>a</p></body></html>".to_string());
+        let dom = HtmlParser::new(t).construct_tree().borrow().document();
+        let cssom = CssParser::new(CssTokenizer::new(String::new())).parse_stylesheet();
+        let layout_view = LayoutView::with_viewport_width(dom, &cssom, 320);
+
+        let body = layout_view.root().expect("root should exist");
+        assert_eq!(320, body.borrow().size().width());
+        let p = body.borrow().first_child().expect("p should exist");
+        assert_eq!(320, p.borrow().size().width());
+    }
+
+    #[test]
+    fn test_replaced_element_size() {
+        let html = r#"<html>
+<head>
+<style>
+  iframe {
+    width: 200px;
+  }
+</style>
+</head>
+<body><iframe width="400" height="300"></iframe></body>
+</html>"#
+            .to_string();
+        let layout_view = create_layout_view(html);
+
+        let iframe = layout_view
+            .root()
+            .expect("root should exist")
+            .borrow()
+            .first_child()
+            .expect("iframe node should exist");
+        // CSSのwidthが属性より優先され、heightは属性の値が使われる
+        assert_eq!(LayoutSize::new(200, 300), iframe.borrow().size());
+
+        // 中身の大きさがわかる場合、heightを指定しなければ縦横比を保つ
+        let html = r#"<html>
+<head>
+<style>
+  iframe {
+    width: 200px;
+  }
+</style>
+</head>
+<body><iframe></iframe></body>
+</html>"#
+            .to_string();
+        let layout_view = create_layout_view(html);
+        let iframe = layout_view
+            .root()
+            .expect("root should exist")
+            .borrow()
+            .first_child()
+            .expect("iframe node should exist");
+        iframe
+            .borrow_mut()
+            .set_intrinsic_size(Some(LayoutSize::new(100, 50)));
+        iframe.borrow_mut().compute_size(
+            LayoutSize::new(CONTENT_AREA_WIDTH, 0),
+            &mut TextRunCache::new(),
+        );
+        assert_eq!(LayoutSize::new(200, 100), iframe.borrow().size());
+    }
+
+    #[test]
+    fn test_image_alt_text() {
+        let html = r#"<html><body><img alt="broken"><img alt="long   alternative text" width="40" height="30"><img src="a.png"></body></html>"#
+            .to_string();
+        let layout_view = create_layout_view(html);
+        let images: Vec<Rc<RefCell<LayoutObject>>> = core::iter::successors(
+            layout_view
+                .root()
+                .expect("root should exist")
+                .borrow()
+                .first_child(),
+            |n| n.borrow().next_sibling(),
+        )
+        .collect();
+        assert_eq!(3, images.len());
+
+        // 大きさの指定がない場合は、alt属性の文字列が1行で収まる大きさにする
+        let inset = 1 + IMAGE_ALT_PADDING;
+        let broken = images[0].borrow().size();
+        assert_eq!(
+            LayoutSize::new(
+                text_width("broken", FontSize::Medium) + inset * 2,
+                CHAR_HEIGHT_WITH_PADDING + inset * 2
+            ),
+            broken
+        );
+        let items = images[0].borrow_mut().paint();
+        assert_eq!(5, items.len());
+        match &items[4] {
+            DisplayItem::Text {
+                text, layout_point, ..
+            } => {
+                assert_eq!("broken", text);
+                assert_eq!(
+                    LayoutPoint::new(
+                        images[0].borrow().point().x() + inset,
+                        images[0].borrow().point().y() + inset
+                    ),
+                    *layout_point
+                );
+            }
+            item => panic!("unexpected display item {:?}", item),
+        }
+
+        // 大きさが指定されている場合は、枠に収まる分だけを描く
+        assert_eq!(LayoutSize::new(40, 30), images[1].borrow().size());
+        let text = images[1]
+            .borrow_mut()
+            .paint()
+            .iter()
+            .find_map(|item| match item {
+                DisplayItem::Text { text, .. } => Some(text.clone()),
+                _ => None,
+            })
+            .expect("alt text should be painted");
+        assert!("long alternative text".starts_with(&text));
+        assert!(text_width(&text, FontSize::Medium) <= 40 - inset * 2);
+
+        // alt属性がない画像は、表示できない場合は何も描かない
+        assert_eq!(LayoutSize::new(0, 0), images[2].borrow().size());
+        assert!(images[2].borrow_mut().paint().is_empty());
+    }
+
+    #[test]
+    fn test_object_fit() {
+        let create_iframe = |object_fit: &str| {
+            let html = format!(
+                r#"<html>
+<head>
+<style>
+  iframe {{
+    object-fit: {};
+  }}
+</style>
+</head>
+<body><iframe width="200" height="100"></iframe></body>
+</html>"#,
+                object_fit
+            );
+            let layout_view = create_layout_view(html);
+            let iframe = layout_view
+                .root()
+                .expect("root should exist")
+                .borrow()
+                .first_child()
+                .expect("iframe node should exist");
+            iframe
+                .borrow_mut()
+                .set_intrinsic_size(Some(LayoutSize::new(50, 50)));
+            iframe
+        };
+
+        assert_eq!(
+            (LayoutPoint::new(0, 0), LayoutSize::new(200, 100)),
+            create_iframe("fill").borrow().object_rect()
+        );
+        assert_eq!(
+            (LayoutPoint::new(50, 0), LayoutSize::new(100, 100)),
+            create_iframe("contain").borrow().object_rect()
+        );
+        assert_eq!(
+            (LayoutPoint::new(0, -50), LayoutSize::new(200, 200)),
+            create_iframe("cover").borrow().object_rect()
+        );
+        assert_eq!(
+            (LayoutPoint::new(75, 25), LayoutSize::new(50, 50)),
+            create_iframe("none").borrow().object_rect()
+        );
+    }
+
+    #[test]
+    fn test_nth_child() {
+        let html = r#"<html>
+<head>
+<style>
+  p:nth-child(odd) {
+    color: red;
+  }
+  p:last-child {
+    color: blue;
+  }
+</style>
+</head>
+<body><p>a</p><p>b</p><p>c</p><p>d</p></body>
+</html>"#
+            .to_string();
+        let layout_view = create_layout_view(html);
+
+        let mut colors = Vec::new();
+        let mut node = layout_view
+            .root()
+            .expect("root should exist")
+            .borrow()
+            .first_child();
+        while let Some(n) = node {
+            colors.push(n.borrow().style().color());
+            node = n.borrow().next_sibling();
+        }
+        assert_eq!(
+            vec![
+                Color::from_name("red").expect("red should be a valid color"),
+                Color::black(),
+                Color::from_name("red").expect("red should be a valid color"),
+                Color::from_name("blue").expect("blue should be a valid color"),
+            ],
+            colors
+        );
+    }
+
+    #[test]
+    fn test_pseudo_element() {
+        let html = r#"<html>
+<head>
+<style>
+  p::before {
+    content: "[";
+    color: red;
+  }
+  p::after {
+    content: "]";
+  }
+  .empty::before {
+    content: "!";
+  }
+</style>
+</head>
+<body><p>a</p><p class="empty"></p></body>
+</html>"#
+            .to_string();
+        let layout_view = create_layout_view(html);
+
+        let texts: Vec<(String, Color)> = layout_view
+            .paint()
+            .into_iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text { text, style, .. } => Some((text, style.color())),
+                _ => None,
+            })
+            .collect();
+        let red = Color::from_name("red").expect("red should be a valid color");
+        assert_eq!(
+            vec![
+                ("[".to_string(), red.clone()),
+                ("a".to_string(), Color::black()),
+                ("]".to_string(), Color::black()),
+                // 後に書かれたルールのcontentで上書きされ、colorはp::beforeのものが使われる
+                ("!".to_string(), red),
+                ("]".to_string(), Color::black()),
+            ],
+            texts
+        );
+    }
+
+    #[test]
+    fn test_layout_object_for_node() {
+        let html = r#"<html>
+<head>
+<style>
+  .hidden {
+    display: none;
+  }
+</style>
+</head>
+<body><p id="target">text</p><p class="hidden" id="hidden">hidden</p></body>
+</html>"#
+            .to_string();
+        let t = HtmlTokenizer::new(html);
+        let window = HtmlParser::new(t).construct_tree();
+        let dom = window.borrow().document();
+        let style = get_style_content(dom.clone());
+        let cssom = CssParser::new(CssTokenizer::new(style)).parse_stylesheet();
+        let layout_view = LayoutView::new(dom.clone(), &cssom);
+
+        let target = get_element_by_id(Some(dom.clone()), &"target".to_string())
+            .expect("target should exist");
+        let obj = layout_view
+            .layout_object_for_node(&target)
+            .expect("layout object for #target should exist");
+        let first_child = layout_view
+            .root()
+            .expect("root should exist")
+            .borrow()
+            .first_child()
+            .expect("first child should exist");
+        assert!(Rc::ptr_eq(&first_child, &obj));
+        // LayoutObjectからDOMノードに戻れる
+        assert!(Rc::ptr_eq(&target, &obj.borrow().node()));
+
+        // 描画されないノードにはLayoutObjectがない
+        let hidden =
+            get_element_by_id(Some(dom), &"hidden".to_string()).expect("hidden should exist");
+        assert!(layout_view.layout_object_for_node(&hidden).is_none());
+    }
+
+    #[test]
+    fn test_style_sharing() {
+        let html = r#"<html>
+<head>
+<style>
+  .red {
+    color: red;
+  }
+</style>
+</head>
+<body><p>a</p><p class="red">b</p><p class="red">c</p><p id="d" class="red">d</p></body>
+</html>"#
+            .to_string();
+        let layout_view = create_layout_view(html);
+
+        let p1 = layout_view
+            .root()
+            .expect("root should exist")
+            .borrow()
+            .first_child()
+            .expect("p node should exist");
+        let p2 = p1.borrow().next_sibling().expect("p node should exist");
+        let p3 = p2.borrow().next_sibling().expect("p node should exist");
+        let p4 = p3.borrow().next_sibling().expect("p node should exist");
+
+        // 同じタグ名とクラス名を持つ兄弟要素は、同じComputedStyleを共有する
+        assert!(Rc::ptr_eq(&p2.borrow().style(), &p3.borrow().style()));
+        // IDを持つ要素は共有しない
+        assert!(!Rc::ptr_eq(&p3.borrow().style(), &p4.borrow().style()));
+        assert_eq!(p3.borrow().style(), p4.borrow().style());
+    }
 }
@@ -1,3 +1,5 @@
 pub mod computed_style;
+pub mod font;
 pub mod layout_object;
 pub mod layout_view;
+pub mod text_run;
@@ -0,0 +1,96 @@
+use crate::constants::BOLD_STROKE_OFFSET;
+use crate::renderer::layout::computed_style::FontSize;
+use crate::renderer::layout::font::text_width;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+
+/// 文字の大きさと、太字かどうかの組
+type TextRunStyle = (FontSize, bool);
+
+/// 同じ文字列を何度も計測しないように、計測した横幅を保存しておくキャッシュ。
+/// ソフトウェアのラスタライザが実装された場合は、描画済みの文字列もここに保存する
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TextRunCache {
+    /// 文字の大きさと太字かどうかごとに、文字列から横幅を引く。
+    /// 文字列を&strのまま探せるように、文字列だけを内側のキーにする
+    widths: BTreeMap<TextRunStyle, BTreeMap<String, i64>>,
+}
+
+impl TextRunCache {
+    pub fn new() -> Self {
+        Self {
+            widths: BTreeMap::new(),
+        }
+    }
+
+    /// 文字列を1行で描画したときの横幅を返す。太字は重ねて描く分だけ広く測る
+    pub fn measure(&mut self, text: &str, font_size: FontSize, bold: bool) -> i64 {
+        let widths = self.widths.entry((font_size, bold)).or_default();
+        if let Some(width) = widths.get(text) {
+            return *width;
+        }
+
+        let mut width = text_width(text, font_size);
+        if bold && width > 0 {
+            width += BOLD_STROKE_OFFSET;
+        }
+        widths.insert(text.to_string(), width);
+        width
+    }
+
+    pub fn len(&self) -> usize {
+        self.widths.values().map(|widths| widths.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.widths.values().all(|widths| widths.is_empty())
+    }
+
+    pub fn clear(&mut self) {
+        self.widths.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::CHAR_WIDTH;
+
+    #[test]
+    fn test_measure() {
+        let mut cache = TextRunCache::new();
+        assert_eq!(
+            CHAR_WIDTH * 4,
+            cache.measure("text", FontSize::Medium, false)
+        );
+        assert_eq!(
+            CHAR_WIDTH * 3 * 4,
+            cache.measure("text", FontSize::XXLarge, false)
+        );
+        // 横幅はバイト数ではなく文字ごとの送り幅で測る
+        assert_eq!(
+            CHAR_WIDTH * 2 * 2,
+            cache.measure("日本", FontSize::Medium, false)
+        );
+        assert_eq!(
+            CHAR_WIDTH * 4 + BOLD_STROKE_OFFSET,
+            cache.measure("text", FontSize::Medium, true)
+        );
+        assert_eq!(0, cache.measure("", FontSize::Medium, true));
+    }
+
+    #[test]
+    fn test_reuse_measured_width() {
+        let mut cache = TextRunCache::new();
+        cache.measure("text", FontSize::Medium, false);
+        cache.measure("text", FontSize::Medium, false);
+        assert_eq!(1, cache.len());
+
+        cache.measure("text", FontSize::Medium, true);
+        assert_eq!(2, cache.len());
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
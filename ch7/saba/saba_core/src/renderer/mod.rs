@@ -1,6 +1,10 @@
+pub mod caret;
 pub mod css;
 pub mod dom;
 pub mod html;
+pub mod image;
 pub mod js;
 pub mod layout;
 pub mod page;
+pub mod toc;
+pub mod viewport;
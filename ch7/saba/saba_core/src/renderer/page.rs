@@ -1,33 +1,329 @@
 use crate::browser::Browser;
+use crate::config::BrowserConfig;
+use crate::console::ConsoleEntry;
+use crate::console::ConsoleEntryKind;
+use crate::constants::CHAR_HEIGHT;
+use crate::constants::CONTENT_AREA_HEIGHT;
+use crate::constants::CONTENT_AREA_WIDTH;
+use crate::constants::MAX_FRAME_DEPTH;
+use crate::constants::TAB_WIDTH;
 use crate::display_item::DisplayItem;
+use crate::encoding::Encoding;
+use crate::error::Error;
 use crate::http::HttpResponse;
+use crate::internal_page::console_page_html;
+use crate::internal_page::css_page_html;
+use crate::internal_page::dom_page_html;
+use crate::internal_page::error_page_html;
+use crate::internal_page::headers_page_html;
+use crate::internal_page::internal_page_html;
+use crate::internal_page::internal_page_style;
+use crate::internal_page::status_page_html;
+use crate::internal_page::unsupported_content_page_html;
+use crate::internal_page::ABOUT_CONSOLE;
+use crate::internal_page::ABOUT_CSS;
+use crate::internal_page::ABOUT_DOM;
+use crate::internal_page::ABOUT_HEADERS;
+use crate::mime::charset;
+use crate::mime::content_kind;
+use crate::mime::ContentKind;
+use crate::navigation::SessionHistory;
+use crate::page_observer::CursorKind;
+use crate::page_observer::LoadState;
+use crate::page_observer::PageObserver;
+use crate::permission::Permission;
+use crate::renderer::caret::Caret;
+use crate::renderer::caret::CaretMovement;
 use crate::renderer::css::cssom::CssParser;
 use crate::renderer::css::cssom::StyleSheet;
 use crate::renderer::css::token::CssTokenizer;
+use crate::renderer::dom::api::append_child;
+use crate::renderer::dom::api::get_indicated_element;
 use crate::renderer::dom::api::get_js_content;
-use crate::renderer::dom::api::get_style_content;
+use crate::renderer::dom::api::get_links;
+use crate::renderer::dom::api::get_meta_content;
+use crate::renderer::dom::api::get_style_sources;
+use crate::renderer::dom::api::get_target_element_node;
+use crate::renderer::dom::api::StyleSource;
+use crate::renderer::dom::node::Element;
 use crate::renderer::dom::node::ElementKind;
+use crate::renderer::dom::node::Node;
 use crate::renderer::dom::node::NodeKind;
+use crate::renderer::dom::node::QuirksMode;
 use crate::renderer::dom::node::Window;
+use crate::renderer::html::parse_error::ParseError;
 use crate::renderer::html::parser::HtmlParser;
+use crate::renderer::html::srcset::select_image_source;
 use crate::renderer::html::token::HtmlTokenizer;
+use crate::renderer::image::Image;
 use crate::renderer::js::ast::JsParser;
+use crate::renderer::js::ast::Program;
+use crate::renderer::js::runtime::Evaluation;
 use crate::renderer::js::runtime::JsRuntime;
+use crate::renderer::js::runtime::ScrollRequest;
 use crate::renderer::js::token::JsLexer;
+use crate::renderer::layout::font::fitting_prefix_len;
+use crate::renderer::layout::font::font_scale;
+use crate::renderer::layout::layout_object::LayoutObject;
+use crate::renderer::layout::layout_object::LayoutPoint;
+use crate::renderer::layout::layout_object::LayoutSize;
 use crate::renderer::layout::layout_view::LayoutView;
+use crate::renderer::toc::heading_nodes;
+use crate::renderer::toc::heading_text;
+use crate::renderer::toc::TocEntry;
+use crate::renderer::viewport::Viewport;
+use crate::renderer::viewport::ViewportMeta;
+use crate::resource_loader::ResourceHint;
+use crate::security::SecurityState;
+use crate::svg_export::display_items_to_svg;
+use crate::text::contains_normalized;
+use crate::url::Url;
+use alloc::collections::BTreeMap;
+use alloc::format;
 use alloc::rc::Rc;
 use alloc::rc::Weak;
 use alloc::string::String;
+use alloc::string::ToString;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 
+/// <iframe>によって作られる、入れ子になった閲覧コンテキスト
+/// https://html.spec.whatwg.org/multipage/document-sequences.html#child-navigable
+#[derive(Debug, Clone)]
+pub struct ChildFrame {
+    src: String,
+    /// 親のページの中での<iframe>の位置と大きさ。子フレームはこの範囲に切り取って描画する
+    point: LayoutPoint,
+    size: LayoutSize,
+    /// 読み込みが終わるまではNone
+    page: Option<Page>,
+    scroll_y: i64,
+}
+
+impl ChildFrame {
+    fn contains(&self, position: (i64, i64)) -> bool {
+        self.point.x() <= position.0
+            && position.0 < self.point.x() + self.size.width()
+            && self.point.y() <= position.1
+            && position.1 < self.point.y() + self.size.height()
+    }
+
+    fn max_scroll_y(&self) -> i64 {
+        match &self.page {
+            Some(page) => (page.content_height() - self.size.height()).max(0),
+            None => 0,
+        }
+    }
+
+    /// 子フレームのDisplayItemを親のページの座標に移し、<iframe>の範囲からはみ出す部分を切り取る
+    fn clip(&self, item: DisplayItem) -> Option<DisplayItem> {
+        clip_display_item(item, self.point, self.size, (0, self.scroll_y))
+    }
+}
+
+/// レスポンスのContent-Typeのcharsetで指定された文字コード。指定がないか、知らない文字コードの場合はUTF-8にする
+fn response_encoding(response: &HttpResponse) -> Encoding {
+    response
+        .header_value("Content-Type")
+        .ok()
+        .and_then(|content_type| charset(&content_type))
+        .and_then(|label| Encoding::from_label(&label))
+        .unwrap_or(Encoding::Utf8)
+}
+
+/// テキストの1行を、空白がまとめられないように書き換える。
+/// 空白はノーブレークスペースに、タブは次のTAB_WIDTHの倍数の桁までのノーブレークスペースにする。
+/// 空行も1行の高さを持つように、ノーブレークスペースを1つ入れる
+fn preformat_line(line: &str) -> String {
+    let mut result = String::new();
+    let mut column = 0;
+    for c in line.chars() {
+        match c {
+            ' ' => {
+                result.push('\u{00A0}');
+                column += 1;
+            }
+            '\t' => {
+                let spaces = TAB_WIDTH - column % TAB_WIDTH;
+                for _ in 0..spaces {
+                    result.push('\u{00A0}');
+                }
+                column += spaces;
+            }
+            c => {
+                result.push(c);
+                column += 1;
+            }
+        }
+    }
+    if result.is_empty() {
+        result.push('\u{00A0}');
+    }
+    result
+}
+
+/// スクロール位置が`scroll`のときに、DisplayItemを`point`と`size`で表される表示領域の座標に移し、
+/// 表示領域からはみ出す部分を切り取る。表示領域に入らない場合はNoneを返す
+fn clip_display_item(
+    item: DisplayItem,
+    point: LayoutPoint,
+    size: LayoutSize,
+    scroll: (i64, i64),
+) -> Option<DisplayItem> {
+    let (left, top) = scroll;
+    let right = left + size.width();
+    let bottom = top + size.height();
+
+    match item {
+        DisplayItem::Rect {
+            style,
+            layout_point,
+            layout_size,
+        } => {
+            let y1 = layout_point.y().max(top);
+            let y2 = (layout_point.y() + layout_size.height()).min(bottom);
+            let x1 = layout_point.x().max(left);
+            let x2 = (layout_point.x() + layout_size.width()).min(right);
+            if y2 <= y1 || x2 <= x1 {
+                return None;
+            }
+
+            Some(DisplayItem::Rect {
+                style,
+                layout_point: LayoutPoint::new(point.x() + x1 - left, point.y() + y1 - top),
+                layout_size: LayoutSize::new(x2 - x1, y2 - y1),
+            })
+        }
+        DisplayItem::Text {
+            text,
+            style,
+            layout_point,
+        } => {
+            // 一部だけが見える行は描画しない
+            if layout_point.y() < top
+                || layout_point.y() + CHAR_HEIGHT * font_scale(style.font_size()) > bottom
+                || layout_point.x() < left
+            {
+                return None;
+            }
+
+            // 右端からはみ出す文字を取り除く
+            let len = fitting_prefix_len(&text, style.font_size(), right - layout_point.x());
+            if len == 0 {
+                return None;
+            }
+            let text = text[..len].to_string();
+
+            Some(DisplayItem::Text {
+                text,
+                style,
+                layout_point: LayoutPoint::new(
+                    point.x() + layout_point.x() - left,
+                    point.y() + layout_point.y() - top,
+                ),
+            })
+        }
+        DisplayItem::Image {
+            image,
+            layout_point,
+        } => {
+            // 画像はスクロールの途中でも見えるように、表示領域に入る部分だけを切り出す
+            let x1 = layout_point.x().max(left);
+            let y1 = layout_point.y().max(top);
+            let x2 = (layout_point.x() + image.width() as i64).min(right);
+            let y2 = (layout_point.y() + image.height() as i64).min(bottom);
+            if y2 <= y1 || x2 <= x1 {
+                return None;
+            }
+
+            let image = image.cropped(
+                (x1 - layout_point.x()) as usize,
+                (y1 - layout_point.y()) as usize,
+                (x2 - x1) as usize,
+                (y2 - y1) as usize,
+            );
+            Some(DisplayItem::Image {
+                image: Rc::new(image),
+                layout_point: LayoutPoint::new(point.x() + x1 - left, point.y() + y1 - top),
+            })
+        }
+    }
+}
+
+/// <img>のsrcset属性とsrc属性から表示する画像を選び、ページのURLを基準に解決したURLを返す
+fn image_source(
+    object: &LayoutObject,
+    url: &Option<String>,
+    viewport: &Viewport,
+) -> Option<String> {
+    let element = object.node().borrow().get_element()?;
+    let src = select_image_source(
+        element.get_attribute("srcset").as_deref(),
+        element.get_attribute("src").as_deref(),
+        viewport.layout_width(),
+        viewport.device_scale_factor(),
+    )?;
+    match url
+        .as_ref()
+        .and_then(|url| Url::new(url.clone()).parse().ok())
+    {
+        Some(base) => Some(base.join(&src)),
+        None => Some(src),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Page {
     browser: Weak<RefCell<Browser>>,
     frame: Option<Rc<RefCell<Window>>>,
+    /// ユーザーのスタイルシート、<link rel="stylesheet">のスタイルシート、<style>のスタイルシートを
+    /// 合わせたもの。ブラウザが作ったページでは、作者のスタイルの代わりに内部ページのスタイルを使う
     style: Option<StyleSheet>,
+    /// 内部ページ、エラーページ、ソースの表示のような、ブラウザが作ったページを表示しているかどうか
+    internal: bool,
+    /// <link rel="stylesheet">と<style>のスタイルシートを文書の順に並べたもの。
+    /// <link>はURLを持ち、取得中と読み込めなかったスタイルシートはNoneにする
+    author_styles: Vec<(Option<String>, Option<StyleSheet>)>,
     layout_view: Option<LayoutView>,
     display_items: Vec<DisplayItem>,
+    child_frames: Vec<ChildFrame>,
+    focused_child_frame: Option<usize>,
+    /// <img>が指す画像のURLごとの、デコードできたところまでの画像。取得中と読み込めなかった画像はNone。
+    /// ページを移動すると空にして、前のページの画像が届いても無視する
+    images: BTreeMap<String, Option<Rc<Image>>>,
+    /// ページのスクロール位置
+    scroll_x: i64,
+    scroll_y: i64,
+    title: String,
+    /// 表示しているページのURL。エラーページでは読み込めなかったURLを持ち、about:retryで読み込み直す
+    url: Option<String>,
+    /// 表示しているページの接続の安全性。ツールバーのアドレスバーの横に表示する
+    security_state: SecurityState,
+    /// マウスカーソルがある位置に合わせたカーソルの形
+    cursor: CursorKind,
+    /// ページのスクリプトを実行したJsRuntime。devtoolsのコンソールから、同じ環境で式を評価する
+    runtime: Option<JsRuntime>,
+    /// trueの場合、レイアウトの枠と座標をページの上に重ねて描画する
+    debug_overlay: bool,
+    /// キャレットブラウジングのキャレット。キャレットブラウジングをしていない場合はNone
+    caret: Option<Caret>,
+    /// ページのHTMLを解釈したときに見つけた失敗
+    parse_errors: Vec<ParseError>,
+    /// ページが使うリソースのうち、読み込めなかったものの数。UIはツールバーに表示する
+    subresource_errors: usize,
+    /// このページで移動してきたURLの履歴
+    history: SessionHistory,
+    /// スクリプトがlocationで要求した移動先。ページのURLを基準に解決したもの
+    script_navigation: Option<String>,
+    /// ページをレイアウトする幅と描く倍率。デバイスの倍率とユーザーの拡大率は、ページを移動しても残す
+    viewport: Viewport,
+    /// 表示しているページのレスポンス。文字コードを選び直したときに、ボディのバイト列から読み直す。
+    /// 内部ページとエラーページではNone
+    response: Option<HttpResponse>,
+    /// ボディを読むのに使った文字コード
+    encoding: Encoding,
+    /// ユーザーが選んだ文字コード。Noneの場合はContent-Typeのcharsetに従う。ページを移動すると戻す
+    encoding_override: Option<Encoding>,
 }
 
 impl Page {
@@ -36,28 +332,226 @@ impl Page {
             browser: Weak::new(),
             frame: None,
             style: None,
+            internal: false,
+            author_styles: Vec::new(),
             layout_view: None,
             display_items: Vec::new(),
+            child_frames: Vec::new(),
+            focused_child_frame: None,
+            images: BTreeMap::new(),
+            scroll_x: 0,
+            scroll_y: 0,
+            title: String::new(),
+            url: None,
+            security_state: SecurityState::Internal,
+            cursor: CursorKind::Default,
+            runtime: None,
+            debug_overlay: false,
+            caret: None,
+            parse_errors: Vec::new(),
+            subresource_errors: 0,
+            history: SessionHistory::new(),
+            script_navigation: None,
+            viewport: Viewport::new(CONTENT_AREA_WIDTH, CONTENT_AREA_HEIGHT),
+            response: None,
+            encoding: Encoding::Utf8,
+            encoding_override: None,
         }
     }
 
-    pub fn clicked(&self, position: (i64, i64)) -> Option<String> {
-        let view = match &self.layout_view {
-            Some(v) => v,
-            None => return None,
+    /// Browserに登録されているオブザーバーに通知する。Browserに属さないページでは、通知しない
+    fn notify_observers(&self, notify: impl Fn(&dyn PageObserver)) {
+        // オブザーバーがBrowserを使えるように、Browserの借用を終えてから呼ぶ
+        let observers = match self.browser.upgrade() {
+            Some(browser) => browser.borrow().observers(),
+            None => return,
         };
+        for observer in observers {
+            notify(observer.as_ref());
+        }
+    }
 
-        if let Some(n) = view.find_node_by_position(position) {
-            if let Some(parent) = n.borrow().parent().upgrade() {
-                if let NodeKind::Element(e) = parent.borrow().node_kind() {
-                    if e.kind() == ElementKind::A {
-                        return e.get_attribute("href");
-                    }
-                }
+    /// 表示しているURLのオリジンに、スクリプトの実行を許可しているかどうか。
+    /// 子フレームのページは親のページのBrowserで、子フレームのURLのオリジンについて確かめる。
+    /// Browserに属さないページでは、許可されているものとして扱う
+    fn is_js_permitted(&self) -> bool {
+        match (self.browser.upgrade(), &self.url) {
+            (Some(browser), Some(url)) => {
+                browser.borrow().is_permitted(url, Permission::JavaScript)
             }
+            _ => true,
         }
+    }
 
-        None
+    /// Browserのコンソールに記録する。Browserに属さないページでは、記録しない
+    fn record_console(&self, entry: ConsoleEntry) {
+        if let Some(browser) = self.browser.upgrade() {
+            browser.borrow().record_console(entry);
+        }
+    }
+
+    /// 子フレームや先読みのような、ページが使うリソースを読み込めなかったことを記録する。
+    /// シリアルのログを見なくても気付けるように、devtoolsのコンソールにエラーとして残す
+    pub fn report_subresource_error(&mut self, url: &str, reason: &str) {
+        self.subresource_errors += 1;
+        let message = format!("failed to load {}: {}", url, reason);
+        self.record_console(ConsoleEntry::new(ConsoleEntryKind::Error, &message));
+        let count = self.subresource_errors;
+        self.notify_observers(|o| {
+            o.on_console_message(&message);
+            o.on_subresource_error(count);
+        });
+    }
+
+    pub fn subresource_errors(&self) -> usize {
+        self.subresource_errors
+    }
+
+    pub fn title(&self) -> String {
+        self.title.clone()
+    }
+
+    pub fn url(&self) -> Option<String> {
+        self.url.clone()
+    }
+
+    /// 読み込めたページのURLを設定する。接続の安全性もURLから決める
+    pub fn set_url(&mut self, url: &str) {
+        self.url = Some(url.to_string());
+        self.security_state = SecurityState::from_url(url);
+    }
+
+    pub fn parse_errors(&self) -> Vec<ParseError> {
+        self.parse_errors.clone()
+    }
+
+    pub fn session_history(&self) -> SessionHistory {
+        self.history.clone()
+    }
+
+    pub fn session_history_mut(&mut self) -> &mut SessionHistory {
+        &mut self.history
+    }
+
+    /// ページを離れる前に、ユーザーの確認を求めるかどうか。スクリプトのbeforeunloadのハンドラに尋ねる。
+    /// 入力欄の編集はまだ扱っていないので、編集中の内容があるかどうかは見ない
+    pub fn before_unload(&mut self) -> bool {
+        match self.runtime.as_mut() {
+            Some(runtime) => runtime.dispatch_beforeunload(),
+            None => false,
+        }
+    }
+
+    /// スクリプトがlocationで要求した移動先を取り出す
+    pub fn take_script_navigation(&mut self) -> Option<String> {
+        self.script_navigation.take()
+    }
+
+    /// スクリプトが要求した移動先を、ページのURLを基準に解決して記録する
+    fn set_script_navigation(&mut self, destination: Option<String>) {
+        let destination = match destination {
+            Some(destination) => destination,
+            None => return,
+        };
+        let resolved = match self.url.as_ref().map(|url| Url::new(url.clone()).parse()) {
+            Some(Ok(url)) => url.join(&destination),
+            _ => destination,
+        };
+        self.script_navigation = Some(resolved);
+    }
+
+    pub fn security_state(&self) -> SecurityState {
+        self.security_state.clone()
+    }
+
+    /// ページのドキュメントノード。まだ何も読み込んでいない場合はNone
+    pub fn document(&self) -> Option<Rc<RefCell<Node>>> {
+        self.frame.as_ref().map(|frame| frame.borrow().document())
+    }
+
+    /// ページのドキュメントの互換モード。まだ何も読み込んでいない場合はNone
+    pub fn quirks_mode(&self) -> Option<QuirksMode> {
+        self.frame
+            .as_ref()
+            .map(|frame| frame.borrow().quirks_mode())
+    }
+
+    pub fn layout_root(&self) -> Option<Rc<RefCell<LayoutObject>>> {
+        self.layout_view.as_ref().and_then(|view| view.root())
+    }
+
+    /// マウスカーソルが表示領域の`position`に動いたときに呼ぶ。リンクの上ではカーソルの形を変える
+    pub fn hovered(&mut self, position: (i64, i64)) {
+        let position = (position.0 + self.scroll_x, position.1 + self.scroll_y);
+        let cursor = match self.link_at(position) {
+            Some(_) => CursorKind::Pointer,
+            None => CursorKind::Default,
+        };
+
+        if cursor != self.cursor {
+            self.cursor = cursor;
+            self.notify_observers(|o| o.on_cursor_change(cursor));
+        }
+    }
+
+    /// ページの座標`position`にあるリンクのURLを返す。<iframe>の中のリンクも探す
+    fn link_at(&self, position: (i64, i64)) -> Option<String> {
+        if let Some(frame) = self.child_frames.iter().find(|f| f.contains(position)) {
+            let position_in_frame = (
+                position.0 - frame.point.x(),
+                position.1 - frame.point.y() + frame.scroll_y,
+            );
+            return frame
+                .page
+                .as_ref()
+                .and_then(|page| page.link_at(position_in_frame));
+        }
+
+        let view = self.layout_view.as_ref()?;
+        let n = view.find_node_by_position(position)?;
+        let parent = n.borrow().parent().upgrade()?;
+        let href = match parent.borrow().node_kind() {
+            NodeKind::Element(e) if e.kind() == ElementKind::A => e.get_attribute("href"),
+            _ => None,
+        };
+        href
+    }
+
+    pub fn clicked(&mut self, position: (i64, i64)) -> Option<String> {
+        // 表示領域の座標を、スクロールを考慮したページの座標に変換する
+        let position = (position.0 + self.scroll_x, position.1 + self.scroll_y);
+
+        // <iframe>の中がクリックされた場合、その子フレームにフォーカスを移し、
+        // 子フレームの座標に変換してクリックを伝える
+        if let Some(index) = self.child_frames.iter().position(|f| f.contains(position)) {
+            self.focused_child_frame = Some(index);
+            let frame = &mut self.child_frames[index];
+            let position_in_frame = (
+                position.0 - frame.point.x(),
+                position.1 - frame.point.y() + frame.scroll_y,
+            );
+            return match &mut frame.page {
+                Some(page) => page.clicked(position_in_frame),
+                None => None,
+            };
+        }
+        self.focused_child_frame = None;
+
+        self.link_at(position)
+    }
+
+    /// 表示領域の座標`position`にあるリンクの絶対URLを返す。clickedと違い、<iframe>へのフォーカスは変えない。
+    /// リンクを新しいウィンドウで開くときに、開く先のページにはURLの基準がないので、このページのURLで解決しておく
+    pub fn link_url_at(&self, position: (i64, i64)) -> Option<String> {
+        let href = self.link_at((position.0 + self.scroll_x, position.1 + self.scroll_y))?;
+        match self
+            .url
+            .as_ref()
+            .and_then(|url| Url::new(url.clone()).parse().ok())
+        {
+            Some(base) => Some(base.join(&href)),
+            None => Some(href),
+        }
     }
 
     pub fn set_browser(&mut self, browser: Weak<RefCell<Browser>>) {
@@ -65,42 +559,677 @@ impl Page {
     }
 
     pub fn receive_response(&mut self, response: HttpResponse) {
-        self.create_frame(response.body());
+        self.encoding_override = None;
+        self.load_response(&response);
+        self.response = Some(response);
+    }
+
+    fn load_response(&mut self, response: &HttpResponse) {
+        self.encoding = self
+            .encoding_override
+            .unwrap_or_else(|| response_encoding(response));
+        let body = self.encoding.decode(&response.body_bytes());
+        // エラーのレスポンスもボディがあればそのまま表示する。ボディが空の場合は、
+        // 白紙のページの代わりにステータスコードと理由を表示する
+        if response.status_code() >= 400 && body.trim().is_empty() {
+            self.load_html(
+                status_page_html(response.status_code(), &response.reason()),
+                true,
+            );
+        } else {
+            // Content-Typeに合わせて表示する。HTML以外の文字はそのまま表示し、
+            // 画像のような表示できない種類の場合は、その旨を表示する
+            match content_kind(response) {
+                ContentKind::Html => self.load_html(body, false),
+                ContentKind::PlainText => self.load_text(body, true),
+                ContentKind::Unsupported(mime_type) => {
+                    self.load_html(unsupported_content_page_html(&mime_type), true)
+                }
+            }
+        }
+        self.notify_observers(|o| o.on_load_state(LoadState::Complete));
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    pub fn encoding_override(&self) -> Option<Encoding> {
+        self.encoding_override
+    }
+
+    /// ページを読む文字コードを選び、保存しておいたボディのバイト列から解釈し直す。
+    /// Noneの場合はContent-Typeのcharsetに戻す。読み直すレスポンスがない場合はfalseを返す
+    pub fn set_encoding_override(&mut self, encoding: Option<Encoding>) -> bool {
+        let response = match self.response.take() {
+            Some(response) => response,
+            None => return false,
+        };
+        self.encoding_override = encoding;
+        self.load_response(&response);
+        self.response = Some(response);
+        true
+    }
+
+    /// view-source:で開いたページ。HTMLを解釈せず、そのままテキストとして表示する
+    pub fn receive_source(&mut self, response: HttpResponse) {
+        self.response = None;
+        self.encoding = response_encoding(&response);
+        self.load_text(self.encoding.decode(&response.body_bytes()), false);
+        self.notify_observers(|o| o.on_load_state(LoadState::Complete));
+    }
+
+    /// about:blankのような内部ページを、ネットワークを使わずに読み込む
+    pub fn load_internal_page(&mut self, url: &str) -> Result<(), Error> {
+        // about:domとabout:cssは、読み込む前に表示していたページの内容を表示する
+        let html = if url == ABOUT_DOM {
+            dom_page_html(&self.document())
+        } else if url == ABOUT_CSS {
+            css_page_html(&self.style)
+        } else if url == ABOUT_CONSOLE {
+            let entries = match self.browser.upgrade() {
+                Some(browser) => browser.borrow().console_entries(),
+                None => Vec::new(),
+            };
+            console_page_html(&entries)
+        } else if url == ABOUT_HEADERS {
+            let config = match self.browser.upgrade() {
+                Some(browser) => browser.borrow().config(),
+                None => BrowserConfig::new(),
+            };
+            headers_page_html(&config)
+        } else {
+            internal_page_html(url)?
+        };
+        self.response = None;
+        self.encoding = Encoding::Utf8;
+        self.load_html(html, true);
+        self.url = Some(url.to_string());
+        self.security_state = SecurityState::Internal;
+        self.notify_observers(|o| o.on_load_state(LoadState::Complete));
+        Ok(())
+    }
+
+    /// ページを読み込めなかった理由を表示する
+    pub fn load_error_page(&mut self, url: &str, error: &Error) {
+        self.response = None;
+        self.encoding = Encoding::Utf8;
+        self.load_html(error_page_html(url, error), true);
+        self.url = Some(url.to_string());
+        self.security_state = SecurityState::from_error(url, error);
+        self.notify_observers(|o| o.on_load_state(LoadState::Failed));
+    }
+
+    /// `internal`は、ブラウザが作ったHTMLかどうか。その場合は、作者のスタイルを使わない
+    fn load_html(&mut self, html: String, internal: bool) {
+        self.notify_observers(|o| o.on_load_state(LoadState::Loading));
+        self.internal = internal;
+        let previous_title = core::mem::take(&mut self.title);
 
-        self.execute_js();
+        self.create_frame(html);
+        // <title>要素のテキストをタイトルにする。スクリプトがdocument.titleに代入した場合は、その値で置き換える
+        if let Some(frame) = &self.frame {
+            self.title = frame.borrow().title();
+        }
+
+        let scroll_request = self.execute_js();
 
         self.set_layout_view();
 
+        self.create_child_frames();
+
+        self.scroll_x = 0;
+        self.scroll_y = 0;
+        match scroll_request {
+            Some(ScrollRequest::To(x, y)) => self.set_scroll_position(x, y),
+            Some(ScrollRequest::IntoView(node)) => {
+                self.scroll_to_node(&node);
+            }
+            None => {}
+        }
+
         self.paint_tree();
+
+        if self.title != previous_title {
+            let title = self.title.clone();
+            self.notify_observers(|o| o.on_title_changed(&title));
+        }
     }
 
-    fn execute_js(&mut self) {
-        let dom = match &self.frame {
+    pub fn scroll_position(&self) -> (i64, i64) {
+        (self.scroll_x, self.scroll_y)
+    }
+
+    /// スクロール位置を、ページの内容からはみ出さない範囲に制限して設定する
+    fn set_scroll_position(&mut self, x: i64, y: i64) {
+        let max_x = (self.content_width() - CONTENT_AREA_WIDTH).max(0);
+        let max_y = (self.content_height() - CONTENT_AREA_HEIGHT).max(0);
+        self.scroll_x = x.clamp(0, max_x);
+        self.scroll_y = y.clamp(0, max_y);
+    }
+
+    /// ページをスクロールし、描画し直す。スクロール位置が変わった場合はtrueを返すので、
+    /// 呼び出し側はその場合に画面を更新する
+    /// https://drafts.csswg.org/cssom-view/#perform-a-scroll
+    pub fn scroll_to(&mut self, x: i64, y: i64) -> bool {
+        let previous = self.scroll_position();
+        self.set_scroll_position(x, y);
+        if self.scroll_position() == previous {
+            return false;
+        }
+
+        self.paint_tree();
+        true
+    }
+
+    pub fn scroll_by(&mut self, delta: i64) -> bool {
+        self.scroll_to(self.scroll_x, self.scroll_y + delta)
+    }
+
+    /// 要素の上端が表示領域の上端に来るようにスクロールする。
+    /// フラグメントへの移動とscrollIntoView()の両方で使う
+    /// https://drafts.csswg.org/cssom-view/#scroll-an-element-into-view
+    pub fn scroll_to_node(&mut self, node: &Rc<RefCell<Node>>) -> bool {
+        let y = match self
+            .layout_view
+            .as_ref()
+            .and_then(|v| v.layout_object_for_node(node))
+        {
+            Some(obj) => obj.borrow().point().y(),
+            // 描画されていない要素にはスクロールしない
+            None => return false,
+        };
+
+        self.scroll_to(self.scroll_x, y)
+    }
+
+    /// ページの見出しの目次。描画されていない見出しは含めない
+    pub fn table_of_contents(&self) -> Vec<TocEntry> {
+        let (document, layout_view) = match (self.document(), &self.layout_view) {
+            (Some(document), Some(layout_view)) => (document, layout_view),
+            _ => return Vec::new(),
+        };
+
+        heading_nodes(&document)
+            .into_iter()
+            .filter_map(|(level, node)| {
+                let y = layout_view
+                    .layout_object_for_node(&node)?
+                    .borrow()
+                    .point()
+                    .y();
+                let id = match node.borrow().kind() {
+                    NodeKind::Element(e) => e.get_attribute("id"),
+                    _ => None,
+                };
+                Some(TocEntry::new(level, heading_text(&node), id, y))
+            })
+            .collect()
+    }
+
+    /// 目次の`index`番目の見出しが、表示領域の上端に来るようにスクロールする
+    pub fn scroll_to_toc_entry(&mut self, index: usize) -> bool {
+        match self.table_of_contents().get(index) {
+            Some(entry) => self.scroll_to(self.scroll_x, entry.y()),
+            None => false,
+        }
+    }
+
+    /// ページの中で`query`を含むテキストの上端の位置を、ページの上から順に返す。
+    /// 大文字と小文字、空白の違い、文字参照で書いたかどうかは区別しない。行をまたぐ文字列は見つけない
+    pub fn find_text(&self, query: &str) -> Vec<i64> {
+        let mut found: Vec<i64> = self
+            .paint_document()
+            .into_iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text {
+                    text, layout_point, ..
+                } if contains_normalized(&text, query) => Some(layout_point.y()),
+                _ => None,
+            })
+            .collect();
+        found.sort();
+        found.dedup();
+        found
+    }
+
+    /// `query`を含む最初のテキストまでスクロールする。見つからなかった場合はfalseを返す
+    pub fn scroll_to_text(&mut self, query: &str) -> bool {
+        match self.find_text(query).first() {
+            Some(y) => {
+                self.scroll_to(self.scroll_x, *y);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// URLのフラグメント（#の後ろ）が指す要素までスクロールする。
+    /// 空のフラグメントと"top"はページの先頭を指す
+    /// https://html.spec.whatwg.org/multipage/browsing-the-web.html#scroll-to-the-fragment-identifier
+    pub fn scroll_to_fragment(&mut self, fragment: &str) -> bool {
+        let document = match &self.frame {
             Some(frame) => frame.borrow().document(),
+            None => return false,
+        };
+
+        match get_indicated_element(document, fragment) {
+            Some(node) => self.scroll_to_node(&node),
+            None if fragment.is_empty() || fragment.eq_ignore_ascii_case("top") => {
+                self.scroll_to(0, 0)
+            }
+            None => false,
+        }
+    }
+
+    /// レイアウトツリーの中の<iframe>ごとに、まだ読み込まれていない子フレームを作る。
+    /// src属性のURLは、ページのURLを基準に解決する
+    fn create_child_frames(&mut self) {
+        self.child_frames = Vec::new();
+        self.focused_child_frame = None;
+
+        let view = match &self.layout_view {
+            Some(v) => v,
             None => return,
         };
+        let base = self
+            .url
+            .as_ref()
+            .and_then(|url| Url::new(url.clone()).parse().ok());
+
+        for iframe in view.iframes() {
+            let src = match iframe.borrow().node_kind() {
+                NodeKind::Element(e) => e.get_attribute("src"),
+                _ => None,
+            };
+            if let Some(src) = src {
+                let src = match &base {
+                    Some(base) => base.join(&src),
+                    None => src,
+                };
+                self.child_frames.push(ChildFrame {
+                    src,
+                    point: iframe.borrow().point(),
+                    size: iframe.borrow().size(),
+                    page: None,
+                    scroll_y: 0,
+                });
+            }
+        }
+    }
+
+    /// まだ読み込まれていない子フレームと、そのsrc属性のURLを返す。子フレームは、このページから
+    /// 子フレームの番号をたどる経路で表す。読み込んだ子フレームの中の<iframe>も、MAX_FRAME_DEPTHの深さまで含める
+    pub fn pending_child_frames(&self) -> Vec<(Vec<usize>, String)> {
+        let mut pending = Vec::new();
+        self.collect_pending_child_frames(&mut Vec::new(), &mut pending);
+        pending
+    }
+
+    fn collect_pending_child_frames(
+        &self,
+        path: &mut Vec<usize>,
+        pending: &mut Vec<(Vec<usize>, String)>,
+    ) {
+        if path.len() >= MAX_FRAME_DEPTH {
+            return;
+        }
+        for (index, frame) in self.child_frames.iter().enumerate() {
+            path.push(index);
+            match &frame.page {
+                Some(page) => page.collect_pending_child_frames(path, pending),
+                None => pending.push((path.clone(), frame.src.clone())),
+            }
+            path.pop();
+        }
+    }
+
+    /// 子フレームの番号をたどる経路が指す子フレーム
+    fn child_frame_mut(&mut self, path: &[usize]) -> Option<&mut ChildFrame> {
+        let (last, parents) = path.split_last()?;
+        let mut page = self;
+        for index in parents {
+            page = page.child_frames.get_mut(*index)?.page.as_mut()?;
+        }
+        page.child_frames.get_mut(*last)
+    }
+
+    /// ページの<link rel="preload">と<link rel="prefetch">が指すリソース。URLはページのURLを基準に解決する
+    pub fn resource_hints(&self) -> Vec<ResourceHint> {
+        let dom = match self.document() {
+            Some(dom) => dom,
+            None => return Vec::new(),
+        };
+        let base = self
+            .url
+            .as_ref()
+            .and_then(|url| Url::new(url.clone()).parse().ok());
+        get_links(dom)
+            .into_iter()
+            .filter_map(|(rel, href, destination)| {
+                let kind = ResourceHint::kind_of_link(&rel, destination.as_deref())?;
+                let url = match &base {
+                    Some(base) => base.join(&href),
+                    None => href,
+                };
+                Some(ResourceHint::new(&url, kind))
+            })
+            .collect()
+    }
+
+    /// ページの<link rel="icon">が指すアイコンのURL。複数ある場合は最初のものを使う。
+    /// URLはページのURLを基準に解決する
+    pub fn icon_url(&self) -> Option<String> {
+        let (_, href, _) = get_links(self.document()?)
+            .into_iter()
+            .find(|(rel, _, _)| {
+                rel.split_ascii_whitespace()
+                    .any(|r| r.eq_ignore_ascii_case("icon"))
+            })?;
+        match self
+            .url
+            .as_ref()
+            .and_then(|url| Url::new(url.clone()).parse().ok())
+        {
+            Some(base) => Some(base.join(&href)),
+            None => Some(href),
+        }
+    }
+
+    /// `path`が指す子フレームに、読み込んだドキュメントを表示する。経路はpending_child_framesが返したもの
+    pub fn receive_child_frame_response(&mut self, path: &[usize], response: HttpResponse) {
+        let browser = self.browser.clone();
+        if let Some(frame) = self.child_frame_mut(path) {
+            let mut page = Page::new();
+            page.set_browser(browser);
+            // 子フレームの中の相対URLは、子フレームのドキュメントのURLを基準にする
+            page.set_url(&frame.src);
+            page.receive_response(response);
+            frame.page = Some(page);
+            frame.scroll_y = 0;
+        }
+
+        self.paint_tree();
+    }
+
+    /// フォーカスされている子フレームを、親のページとは独立してスクロールする。
+    /// スクロールした場合はtrueを返す
+    pub fn scroll_focused_child_frame(&mut self, delta: i64) -> bool {
+        let frame = match self.focused_child_frame {
+            Some(index) => &mut self.child_frames[index],
+            None => return false,
+        };
+
+        let scroll_y = (frame.scroll_y + delta).clamp(0, frame.max_scroll_y());
+        if scroll_y == frame.scroll_y {
+            return false;
+        }
+        frame.scroll_y = scroll_y;
+
+        self.paint_tree();
+        true
+    }
+
+    /// ページ全体の幅
+    pub fn content_width(&self) -> i64 {
+        match self.layout_view.as_ref().and_then(|v| v.root()) {
+            Some(root) => {
+                let width = root.borrow().size().width();
+                width
+            }
+            None => 0,
+        }
+    }
+
+    /// ページ全体の高さ
+    pub fn content_height(&self) -> i64 {
+        match self.layout_view.as_ref().and_then(|v| v.root()) {
+            Some(root) => {
+                let height = root.borrow().size().height();
+                height
+            }
+            None => 0,
+        }
+    }
+
+    /// HTMLを解釈せずに、テキストを1行ずつ表示する。`preformatted`がtrueの場合は、
+    /// 空行と行頭の空白も書かれたとおりに表示する
+    fn load_text(&mut self, source: String, preformatted: bool) {
+        self.notify_observers(|o| o.on_load_state(LoadState::Loading));
+        // プレーンテキストはサーバーが返したページとして、ソースの表示はブラウザが作ったページとして扱う
+        self.internal = !preformatted;
+        if !self.title.is_empty() {
+            self.title.clear();
+            self.notify_observers(|o| o.on_title_changed(""));
+        }
+        self.create_frame(String::from("<html><head></head><body></body></html>"));
+
+        if let Some(frame) = &self.frame {
+            let document = frame.borrow().document();
+            if let Some(body) = get_target_element_node(Some(document), ElementKind::Body) {
+                // ソースの1行を1つの<p>要素の中のテキストノードにする。
+                // トークナイザを通さないので、タグもそのまま文字として表示される
+                for line in source.lines() {
+                    let line = if preformatted {
+                        preformat_line(line)
+                    } else if line.trim().is_empty() {
+                        continue;
+                    } else {
+                        line.to_string()
+                    };
+                    let p = Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
+                        "p",
+                        Vec::new(),
+                    )))));
+                    let text = Rc::new(RefCell::new(Node::new(NodeKind::Text(line))));
+                    append_child(&p, text);
+                    append_child(&body, p);
+                }
+            }
+        }
+
+        self.set_layout_view();
+
+        // テキストには<iframe>がないので、前のページの子フレームを取り除くだけになる
+        self.create_child_frames();
+
+        self.paint_tree();
+    }
+
+    /// スクリプトを実行し、スクリプトが要求したスクロールを返す
+    fn execute_js(&mut self) -> Option<ScrollRequest> {
+        let dom = match &self.frame {
+            Some(frame) => frame.borrow().document(),
+            None => return None,
+        };
 
         let js = get_js_content(dom.clone());
+        // スクリプトの実行をブロックしているオリジンでは、スクリプトを実行しない。
+        // スクリプトがないページでは、権限を尋ねない
+        if !js.trim().is_empty() && !self.is_js_permitted() {
+            // 前のページの環境で、beforeunloadのハンドラを呼ばないようにする
+            self.runtime = None;
+            return None;
+        }
         let lexer = JsLexer::new(js);
 
         let mut parser = JsParser::new(lexer);
         let ast = parser.parse_ast();
 
         let mut runtime = JsRuntime::new(dom);
+        // offsetWidthなどでレイアウトを計算するときに、ページのスタイルを使う
+        if let Some(style) = &self.style {
+            runtime.set_style(style.clone());
+        }
+        // 止まらないスクリプトは、埋め込み側に尋ねて中断する
+        if let Some(browser) = self.browser.upgrade() {
+            runtime.set_watchdog(browser.borrow().script_watchdog());
+        }
         runtime.execute(&ast);
+
+        for message in runtime.take_console_messages() {
+            self.record_console(ConsoleEntry::new(ConsoleEntryKind::Log, &message));
+            self.notify_observers(|o| o.on_console_message(&message));
+        }
+        for error in runtime.take_errors() {
+            self.record_console(ConsoleEntry::new(ConsoleEntryKind::Error, &error));
+            let message = format!("Uncaught {}", error);
+            self.notify_observers(|o| o.on_console_message(&message));
+        }
+        if let Some(title) = runtime.take_title() {
+            self.title = title;
+        }
+        self.set_script_navigation(runtime.take_navigation_request());
+
+        let scroll_request = runtime.take_scroll_request();
+        self.runtime = Some(runtime);
+        scroll_request
+    }
+
+    /// ページのJsRuntimeを取り出す。about:consoleに移る前に、直前のページの環境を残しておくために使う
+    pub fn take_runtime(&mut self) -> Option<JsRuntime> {
+        self.runtime.take()
+    }
+
+    /// ページのスクリプトと同じ環境でプログラムを評価し、DOMツリーが変わった場合に備えて描画し直す。
+    /// スクリプトを実行したことがないページではNoneを返す
+    pub fn evaluate_script(&mut self, program: &Program) -> Option<Evaluation> {
+        let runtime = self.runtime.as_mut()?;
+        let evaluation = runtime.evaluate(program);
+        let title = runtime.take_title();
+        let scroll_request = runtime.take_scroll_request();
+        let navigation = runtime.take_navigation_request();
+        self.set_script_navigation(navigation);
+
+        self.set_layout_view();
+        match scroll_request {
+            Some(ScrollRequest::To(x, y)) => self.set_scroll_position(x, y),
+            Some(ScrollRequest::IntoView(node)) => {
+                self.scroll_to_node(&node);
+            }
+            None => {}
+        }
+        self.paint_tree();
+
+        if let Some(title) = title {
+            if title != self.title {
+                self.title = title;
+                let title = self.title.clone();
+                self.notify_observers(|o| o.on_title_changed(&title));
+            }
+        }
+
+        Some(evaluation)
     }
 
     fn create_frame(&mut self, html: String) {
         let html_tokenizer = HtmlTokenizer::new(html);
-        let frame = HtmlParser::new(html_tokenizer).construct_tree();
+        let mut parser = HtmlParser::new(html_tokenizer);
+        let frame = parser.construct_tree();
+        self.parse_errors = parser.errors();
+        self.subresource_errors = 0;
+        self.images = BTreeMap::new();
         let dom = frame.borrow().document();
 
-        let style = get_style_content(dom);
-        let css_tokenizer = CssTokenizer::new(style);
-        let cssom = CssParser::new(css_tokenizer).parse_stylesheet();
+        // <meta name="viewport">がないページは、前のページの指定を引き継がない
+        let meta = get_meta_content(dom.clone(), "viewport")
+            .map(|content| ViewportMeta::parse(&content))
+            .unwrap_or_default();
+        self.viewport.set_meta(meta);
+
+        let base = self
+            .url
+            .as_ref()
+            .and_then(|url| Url::new(url.clone()).parse().ok());
+        self.author_styles = get_style_sources(dom)
+            .into_iter()
+            .map(|source| match source {
+                StyleSource::Link(href) => match &base {
+                    Some(base) => (Some(base.join(&href)), None),
+                    None => (Some(href), None),
+                },
+                StyleSource::Style(text) => (
+                    None,
+                    Some(CssParser::new(CssTokenizer::new(text)).parse_stylesheet()),
+                ),
+            })
+            .collect();
 
         self.frame = Some(frame);
-        self.style = Some(cssom);
+        self.update_style();
+    }
+
+    /// 取得できた<link rel="stylesheet">のスタイルシートと<style>のスタイルシートを、ページのスタイルにする。
+    /// 後のルールが優先されるので、文書の順に並べる。
+    /// ブラウザが作ったページでは、作者のスタイルの代わりに内部ページのスタイルを使う。
+    /// どちらの場合も、ユーザーのスタイルを合わせる。ルールはStyleSheet::set_rulesがオリジンの順に並べる
+    fn update_style(&mut self) {
+        let mut rules = Vec::new();
+        if self.internal {
+            rules.extend(internal_page_style().rules);
+        } else {
+            for sheet in self
+                .author_styles
+                .iter()
+                .filter_map(|(_, sheet)| sheet.as_ref())
+            {
+                rules.extend(sheet.rules.iter().cloned());
+            }
+        }
+        if let Some(browser) = self.browser.upgrade() {
+            rules.extend(browser.borrow().user_stylesheet().rules);
+        }
+
+        let mut style = StyleSheet::new();
+        style.set_rules(rules);
+        self.style = Some(style);
+    }
+
+    /// まだ取得していない<link rel="stylesheet">のURLを返す。同じURLを指す<link>が複数あっても1回だけ返す
+    pub fn pending_stylesheets(&self) -> Vec<String> {
+        let mut pending: Vec<String> = Vec::new();
+        for (href, sheet) in &self.author_styles {
+            if let (Some(href), None) = (href, sheet) {
+                if !pending.contains(href) {
+                    pending.push(href.clone());
+                }
+            }
+        }
+        pending
+    }
+
+    /// `href`のスタイルシートをページのスタイルに加えて、レイアウトし直す。
+    /// このページの<link>が指すスタイルシートでない場合は無視する
+    pub fn receive_stylesheet(&mut self, href: &str, sheet: StyleSheet) {
+        let mut found = false;
+        for (linked, linked_sheet) in &mut self.author_styles {
+            if linked.as_deref() == Some(href) {
+                *linked_sheet = Some(sheet.clone());
+                found = true;
+            }
+        }
+        if !found {
+            return;
+        }
+
+        self.update_style();
+        if let (Some(runtime), Some(style)) = (&mut self.runtime, &self.style) {
+            runtime.set_style(style.clone());
+        }
+        self.set_layout_view();
+        self.create_child_frames();
+        self.paint_tree();
+    }
+
+    /// `href`のスタイルシートを取得できなかったことを記録する。ページは<style>のスタイルだけで表示する
+    pub fn receive_stylesheet_error(&mut self, href: &str, reason: &str) {
+        if self
+            .author_styles
+            .iter()
+            .any(|(linked, _)| linked.as_deref() == Some(href))
+        {
+            self.report_subresource_error(href, reason);
+        }
     }
 
     fn set_layout_view(&mut self) {
@@ -114,22 +1243,775 @@ impl Page {
             None => return,
         };
 
-        let layout_view = LayoutView::new(dom, &style);
-
+        let layout_view =
+            LayoutView::with_viewport_width(dom, &style, self.viewport.layout_width());
         self.layout_view = Some(layout_view);
+        self.apply_images();
     }
 
-    fn paint_tree(&mut self) {
-        if let Some(layout_view) = &self.layout_view {
-            self.display_items = layout_view.paint();
+    /// 取得した画像を、その画像を指す<img>のLayoutObjectに設定する。
+    /// 画像の大きさが変わった場合は、レイアウトし直す
+    fn apply_images(&mut self) {
+        let layout_view = match &mut self.layout_view {
+            Some(layout_view) => layout_view,
+            None => return,
+        };
+
+        let mut resized = false;
+        for object in layout_view.images() {
+            let image = image_source(&object.borrow(), &self.url, &self.viewport)
+                .and_then(|src| self.images.get(&src).cloned().flatten());
+            let intrinsic_size = object.borrow().intrinsic_size();
+            object.borrow_mut().set_image(image);
+            resized |= object.borrow().intrinsic_size() != intrinsic_size;
+        }
+        if resized {
+            layout_view.relayout();
+        }
+
+        if let Some(caret) = &mut self.caret {
+            caret.set_items(&layout_view.paint());
+        }
+    }
+
+    /// まだ取得を始めていない画像のURLを返し、取得中として記録する。同じURLを指す<img>が複数あっても1回だけ返す
+    pub fn take_pending_images(&mut self) -> Vec<String> {
+        let objects = match &self.layout_view {
+            Some(layout_view) => layout_view.images(),
+            None => return Vec::new(),
+        };
+
+        let mut pending = Vec::new();
+        for object in objects {
+            if let Some(src) = image_source(&object.borrow(), &self.url, &self.viewport) {
+                if !self.images.contains_key(&src) {
+                    self.images.insert(src.clone(), None);
+                    pending.push(src);
+                }
+            }
+        }
+        pending
+    }
+
+    /// `src`の画像を、デコードできたところまで表示する。このページが取得を始めた画像でない場合は無視する
+    pub fn receive_image(&mut self, src: &str, image: &Image) {
+        match self.images.get_mut(src) {
+            Some(entry) => *entry = Some(Rc::new(image.clone())),
+            None => return,
+        }
+        self.apply_images();
+        self.paint_tree();
+    }
+
+    /// `src`の画像を取得またはデコードできなかったことを記録する。
+    /// 画像の代わりに、alt属性の文字列かデコードできたところまでを表示したままにする
+    pub fn receive_image_error(&mut self, src: &str, reason: &str) {
+        if self.images.contains_key(src) {
+            self.report_subresource_error(src, reason);
+        }
+    }
+
+    /// スクロールを考慮せずに、ページ全体をページの座標で描画する
+    fn paint_document(&self) -> Vec<DisplayItem> {
+        let mut display_items = match (&self.layout_view, &self.caret) {
+            (Some(layout_view), Some(caret)) => caret.paint(layout_view.paint()),
+            (Some(layout_view), None) => layout_view.paint(),
+            _ => Vec::new(),
+        };
+
+        // 子フレームは<iframe>の矩形の上に重ねて描画する
+        for frame in &self.child_frames {
+            if let Some(page) = &frame.page {
+                for item in page.paint_document() {
+                    if let Some(item) = frame.clip(item) {
+                        display_items.push(item);
+                    }
+                }
+            }
         }
+
+        if self.debug_overlay {
+            if let Some(layout_view) = &self.layout_view {
+                display_items.extend(layout_view.paint_debug_overlay());
+            }
+        }
+
+        display_items
+    }
+
+    pub fn debug_overlay(&self) -> bool {
+        self.debug_overlay
+    }
+
+    pub fn viewport(&self) -> Viewport {
+        self.viewport.clone()
+    }
+
+    /// 1CSSピクセルを何ピクセルで描くかを変え、新しいレイアウトの幅でレイアウトし直す
+    pub fn set_device_scale_factor(&mut self, device_scale_factor: f64) {
+        self.viewport.set_device_scale_factor(device_scale_factor);
+        self.set_layout_view();
+        self.paint_tree();
+    }
+
+    /// paint-debugのオーバーレイを切り替え、描画し直す。ページを移動しても設定は残る
+    pub fn set_debug_overlay(&mut self, enabled: bool) {
+        self.debug_overlay = enabled;
+        self.paint_tree();
+    }
+
+    pub fn caret_browsing(&self) -> bool {
+        self.caret.is_some()
+    }
+
+    /// キャレットブラウジングを切り替え、描画し直す。キャレットは表示領域の最初のテキストの先頭に置く。
+    /// debug_overlayと同じように、ページを移動しても設定は残る
+    pub fn set_caret_browsing(&mut self, enabled: bool) {
+        self.caret = match (enabled, &self.layout_view) {
+            (false, _) => None,
+            (true, Some(layout_view)) => Some(Caret::new(&layout_view.paint(), self.scroll_y)),
+            (true, None) => Some(Caret::new(&[], 0)),
+        };
+        self.paint_tree();
+    }
+
+    /// キャレットを動かし、キャレットが表示領域に入るようにスクロールして描画し直す。
+    /// `extend`がtrueの場合は選択範囲を広げる。キャレットが動いた場合はtrueを返す
+    pub fn move_caret(&mut self, movement: CaretMovement, extend: bool) -> bool {
+        let caret = match &mut self.caret {
+            Some(caret) => caret,
+            None => return false,
+        };
+        let had_selection = caret.selection().is_some();
+        let moved = caret.move_caret(movement, extend);
+        let rect = caret.caret_rect();
+        // 動かなくても、選択を解除した場合は描画し直す
+        let changed = moved || had_selection != caret.selection().is_some();
+
+        if let Some((point, size)) = rect {
+            if point.y() < self.scroll_y {
+                self.set_scroll_position(self.scroll_x, point.y());
+            } else if point.y() + size.height() > self.scroll_y + CONTENT_AREA_HEIGHT {
+                self.set_scroll_position(
+                    self.scroll_x,
+                    point.y() + size.height() - CONTENT_AREA_HEIGHT,
+                );
+            }
+        }
+        if changed {
+            self.paint_tree();
+        }
+        changed
+    }
+
+    /// キャレットブラウジングで選択した文字列。何も選択していない場合は空の文字列
+    pub fn selected_text(&self) -> String {
+        match &self.caret {
+            Some(caret) => caret.selected_text(),
+            None => String::new(),
+        }
+    }
+
+    fn paint_tree(&mut self) {
+        // スクロール位置に合わせて、表示領域に入る部分だけを描画する
+        let viewport_size = LayoutSize::new(CONTENT_AREA_WIDTH, CONTENT_AREA_HEIGHT);
+        self.display_items = self
+            .paint_document()
+            .into_iter()
+            .filter_map(|item| {
+                clip_display_item(
+                    item,
+                    LayoutPoint::new(0, 0),
+                    viewport_size,
+                    (self.scroll_x, self.scroll_y),
+                )
+            })
+            .collect();
     }
 
     pub fn display_items(&self) -> Vec<DisplayItem> {
         self.display_items.clone()
     }
 
+    /// スクロール位置によらず、ページ全体を描画したSVGの文書を返す。
+    /// 幅はコンテンツエリアの幅で、高さはページの内容がコンテンツエリアより高い場合はそれに合わせる
+    pub fn to_svg(&self) -> String {
+        let items = self.paint_document();
+        let height = items
+            .iter()
+            .map(|item| match item {
+                DisplayItem::Rect {
+                    layout_point,
+                    layout_size,
+                    ..
+                } => layout_point.y() + layout_size.height(),
+                DisplayItem::Text {
+                    style,
+                    layout_point,
+                    ..
+                } => layout_point.y() + CHAR_HEIGHT * font_scale(style.font_size()),
+                DisplayItem::Image {
+                    image,
+                    layout_point,
+                } => layout_point.y() + image.height() as i64,
+            })
+            .fold(CONTENT_AREA_HEIGHT, i64::max);
+        display_items_to_svg(&items, CONTENT_AREA_WIDTH, height)
+    }
+
     pub fn clear_display_items(&mut self) {
         self.display_items = Vec::new();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::CHAR_HEIGHT_WITH_PADDING;
+    use alloc::vec;
+
+    #[test]
+    fn test_receive_source() {
+        let raw = "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html>\n<body><p>hello</p></body>\n</html>"
+            .to_string();
+        let response = HttpResponse::new(raw).expect("failed to parse http response");
+        let mut page = Page::new();
+        page.receive_source(response);
+
+        // タグは解釈されず、そのまま文字列として描画される
+        let texts: Vec<String> = page
+            .display_items()
+            .into_iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text { text, .. } => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            vec!["<html>", "<body><p>hello</p></body>", "</html>"],
+            texts
+        );
+    }
+
+    fn create_response(body: &str) -> HttpResponse {
+        let raw = "HTTP/1.1 200 OK\nContent-Type: text/html\n\n".to_string() + body;
+        HttpResponse::new(raw).expect("failed to parse http response")
+    }
+
+    fn texts(page: &Page) -> Vec<(String, i64)> {
+        page.display_items()
+            .into_iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text {
+                    text, layout_point, ..
+                } => Some((text, layout_point.y())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_content_type() {
+        let mut page = Page::new();
+        page.receive_response(
+            HttpResponse::new(
+                "HTTP/1.1 200 OK\nContent-Type: text/plain\n\n<b>bold</b>\n\n\tindent  x\n"
+                    .to_string(),
+            )
+            .expect("failed to parse http response"),
+        );
+        // タグはそのまま表示され、空行とタブと連続した空白も残る
+        let nbsp = '\u{00A0}';
+        assert_eq!(
+            vec![
+                ("<b>bold</b>".to_string(), 0),
+                (nbsp.to_string(), CHAR_HEIGHT_WITH_PADDING),
+                (
+                    format!("{}indent{}{}x", nbsp.to_string().repeat(8), nbsp, nbsp),
+                    CHAR_HEIGHT_WITH_PADDING * 2
+                ),
+            ],
+            texts(&page)
+        );
+
+        page.receive_response(
+            HttpResponse::new(
+                "HTTP/1.1 200 OK\nContent-Type: image/png\n\n\u{FFFD}PNG".to_string(),
+            )
+            .expect("failed to parse http response"),
+        );
+        assert!(texts(&page)
+            .iter()
+            .any(|(text, _)| text.contains("image/png is not supported")));
+    }
+
+    #[test]
+    fn test_iframe() {
+        let mut page = Page::new();
+        page.receive_response(create_response(
+            "<html><head></head><body><iframe src=\"http://example.com/child.html\" width=\"40\" height=\"40\">fallback</iframe></body></html>",
+        ));
+
+        assert_eq!(
+            vec![(vec![0], "http://example.com/child.html".to_string())],
+            page.pending_child_frames()
+        );
+        // 代替コンテンツは描画されない
+        assert!(texts(&page).is_empty());
+
+        page.receive_child_frame_response(
+            &[0],
+            create_response(
+                "<html><head></head><body><p>aaa</p><p>bbbbbbbbbb</p><p>ccc</p></body></html>",
+            ),
+        );
+        assert!(page.pending_child_frames().is_empty());
+
+        // <iframe>の高さに収まる2行だけが描画され、右端からはみ出す文字は切り取られる
+        assert_eq!(
+            vec![("aaa".to_string(), 0), ("bbbbb".to_string(), 20)],
+            texts(&page)
+        );
+    }
+
+    #[test]
+    fn test_iframe_relative_src() {
+        let mut page = Page::new();
+        page.set_url("http://example.com/dir/index.html");
+        page.receive_response(create_response(
+            "<html><head></head><body><iframe src=\"child.html\"></iframe><iframe src=\"/top.html\"></iframe></body></html>",
+        ));
+
+        assert_eq!(
+            vec![
+                (vec![0], "http://example.com/dir/child.html".to_string()),
+                (vec![1], "http://example.com/top.html".to_string())
+            ],
+            page.pending_child_frames()
+        );
+    }
+
+    #[test]
+    fn test_nested_iframe() {
+        let mut page = Page::new();
+        page.set_url("http://example.com/");
+        page.receive_response(create_response(
+            "<html><head></head><body><iframe src=\"outer.html\"></iframe></body></html>",
+        ));
+        page.receive_child_frame_response(
+            &[0],
+            create_response(
+                "<html><head></head><body><iframe src=\"inner.html\"></iframe></body></html>",
+            ),
+        );
+
+        // 子フレームの中の<iframe>は、子フレームの番号をたどる経路で表す
+        assert_eq!(
+            vec![(vec![0, 0], "http://example.com/inner.html".to_string())],
+            page.pending_child_frames()
+        );
+        page.receive_child_frame_response(
+            &[0, 0],
+            create_response("<html><head></head><body><p>inner</p></body></html>"),
+        );
+        assert!(page.pending_child_frames().is_empty());
+        assert_eq!(vec![("inner".to_string(), 0)], texts(&page));
+    }
+
+    #[test]
+    fn test_iframe_scroll() {
+        let mut page = Page::new();
+        page.receive_response(create_response(
+            "<html><head></head><body><iframe src=\"http://example.com/child.html\" width=\"100\" height=\"40\"></iframe></body></html>",
+        ));
+        page.receive_child_frame_response(
+            &[0],
+            create_response(
+                "<html><head></head><body><p>aaa</p><p>bbb</p><p>ccc</p></body></html>",
+            ),
+        );
+
+        // フォーカスされていない子フレームはスクロールしない
+        assert!(!page.scroll_focused_child_frame(20));
+
+        page.clicked((10, 10));
+        assert!(page.scroll_focused_child_frame(100));
+        assert_eq!(
+            vec![("bbb".to_string(), 0), ("ccc".to_string(), 20)],
+            texts(&page)
+        );
+
+        // 一番下までスクロールしている場合、それ以上はスクロールしない
+        assert!(!page.scroll_focused_child_frame(20));
+    }
+
+    #[derive(Debug, Default)]
+    struct EventRecorder {
+        events: RefCell<Vec<String>>,
+    }
+
+    impl PageObserver for EventRecorder {
+        fn on_title_changed(&self, title: &str) {
+            self.events
+                .borrow_mut()
+                .push(alloc::format!("title: {}", title));
+        }
+
+        fn on_load_state(&self, state: LoadState) {
+            self.events
+                .borrow_mut()
+                .push(alloc::format!("load: {:?}", state));
+        }
+
+        fn on_console_message(&self, message: &str) {
+            self.events
+                .borrow_mut()
+                .push(alloc::format!("console: {}", message));
+        }
+
+        fn on_cursor_change(&self, cursor: CursorKind) {
+            self.events
+                .borrow_mut()
+                .push(alloc::format!("cursor: {:?}", cursor));
+        }
+    }
+
+    #[test]
+    fn test_link_url_at() {
+        let mut page = Page::new();
+        page.set_url("http://example.com/dir/index.html");
+        page.receive_response(create_response(
+            "<html><body><a href=\"next.html\">next</a><p>text</p></body></html>",
+        ));
+
+        // 相対参照は、表示しているページのURLで解決する
+        assert_eq!(
+            Some("http://example.com/dir/next.html".to_string()),
+            page.link_url_at((5, 5))
+        );
+        assert_eq!(None, page.link_url_at((5, 25)));
+    }
+
+    #[test]
+    fn test_page_observer() {
+        let browser = Browser::new();
+        let recorder = Rc::new(EventRecorder::default());
+        browser.borrow_mut().add_observer(recorder.clone());
+        let page = browser.borrow().current_page();
+
+        page.borrow_mut().receive_response(create_response(
+            "<html><head><script>document.title = \"hello\"; console.log(\"loaded\", 1);</script></head><body><a href=\"http://example.com/next\">next</a><p>text</p></body></html>",
+        ));
+        assert_eq!("hello".to_string(), page.borrow().title());
+
+        // カーソルの形は、変わったときだけ通知される
+        page.borrow_mut().hovered((5, 5));
+        page.borrow_mut().hovered((10, 5));
+        page.borrow_mut().hovered((5, 25));
+
+        page.borrow_mut()
+            .load_error_page("http://example.com/", &Error::Cancelled);
+
+        assert_eq!(
+            vec![
+                "load: Loading",
+                "console: loaded 1",
+                "title: hello",
+                "load: Complete",
+                "cursor: Pointer",
+                "cursor: Default",
+                "load: Loading",
+                "title: ",
+                "load: Failed",
+            ],
+            *recorder.events.borrow()
+        );
+    }
+
+    #[test]
+    fn test_error_response() {
+        let mut page = Page::new();
+        // ボディのあるエラーのレスポンスは、そのページを表示する
+        page.receive_response(
+            HttpResponse::new(
+                "HTTP/1.1 500 Internal Server Error\nContent-Type: text/html\n\n<html><head></head><body><p>custom</p></body></html>"
+                    .to_string(),
+            )
+            .expect("failed to parse http response"),
+        );
+        assert_eq!(
+            vec!["custom".to_string()],
+            texts(&page)
+                .into_iter()
+                .map(|(text, _)| text)
+                .collect::<Vec<_>>()
+        );
+
+        // ボディが空の場合は、ステータスコードと理由と、読み込み直すためのリンクを表示する
+        page.receive_response(
+            HttpResponse::new("HTTP/1.1 404 Not Found\nContent-Length: 0\n\n".to_string())
+                .expect("failed to parse http response"),
+        );
+        assert_eq!(
+            vec![
+                "404 Not Found".to_string(),
+                "The server returned an error without a page to display.".to_string(),
+                "HTTP ERROR 404".to_string(),
+                "Retry".to_string(),
+            ],
+            texts(&page)
+                .into_iter()
+                .map(|(text, _)| text)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_debug_overlay() {
+        let mut page = Page::new();
+        page.receive_response(create_response(
+            "<html><head></head><body><p>hello</p></body></html>",
+        ));
+        let items = page.display_items();
+
+        // ページの描画の後に、枠と座標が重ねて描かれる
+        page.set_debug_overlay(true);
+        let overlaid = page.display_items();
+        assert_eq!(items[..], overlaid[..items.len()]);
+        // 最後に描くのは、<p>の中のテキストの座標
+        match overlaid.last() {
+            Some(DisplayItem::Text { text, .. }) => assert_eq!("0,0 40x20", text),
+            _ => panic!("overlay should end with labels"),
+        }
+
+        // ページを読み込み直しても、オーバーレイは表示されたまま
+        page.receive_response(create_response(
+            "<html><head></head><body><p>hi</p></body></html>",
+        ));
+        assert!(page.display_items().len() > 3);
+
+        page.set_debug_overlay(false);
+        assert_eq!(3, page.display_items().len());
+    }
+
+    fn long_page(script: &str) -> HttpResponse {
+        let mut body = String::new();
+        for i in 0..30 {
+            body.push_str(&alloc::format!("<p id=\"p{}\">line{}</p>", i, i));
+        }
+        create_response(&alloc::format!(
+            "<html><head><script>{}</script></head><body>{}</body></html>",
+            script,
+            body
+        ))
+    }
+
+    #[test]
+    fn test_scroll() {
+        let mut page = Page::new();
+        page.receive_response(long_page(""));
+        assert_eq!((0, 0), page.scroll_position());
+        // 表示領域に入る17行だけが描画される
+        assert_eq!(17, texts(&page).len());
+
+        assert!(page.scroll_by(40));
+        assert_eq!(("line2".to_string(), 0), texts(&page)[0]);
+
+        // ページの内容より下にはスクロールしない
+        assert!(page.scroll_to(0, 10000));
+        assert_eq!((0, 30 * 20 - CONTENT_AREA_HEIGHT), page.scroll_position());
+        assert!(!page.scroll_by(20));
+
+        // "#top"はページの先頭を指す
+        assert!(page.scroll_to_fragment("top"));
+        assert_eq!((0, 0), page.scroll_position());
+    }
+
+    #[test]
+    fn test_caret_browsing() {
+        let mut page = Page::new();
+        page.receive_response(long_page(""));
+        assert!(!page.move_caret(CaretMovement::Down, false));
+
+        page.set_caret_browsing(true);
+        assert!(page.caret_browsing());
+        // 表示領域の下端を越えると、キャレットが見えるようにスクロールする
+        for _ in 0..16 {
+            assert!(page.move_caret(CaretMovement::Down, false));
+        }
+        assert_eq!((0, 0), page.scroll_position());
+        assert!(page.move_caret(CaretMovement::Down, false));
+        assert_eq!(
+            (0, 17 * 20 + CHAR_HEIGHT - CONTENT_AREA_HEIGHT),
+            page.scroll_position()
+        );
+
+        for _ in 0..4 {
+            page.move_caret(CaretMovement::Right, true);
+        }
+        assert_eq!("line", page.selected_text());
+        page.move_caret(CaretMovement::Up, true);
+        assert_eq!("16\n", page.selected_text());
+
+        // ページを読み込み直しても、キャレットブラウジングは続く
+        page.receive_response(long_page(""));
+        assert!(page.caret_browsing());
+        assert_eq!("", page.selected_text());
+        page.set_caret_browsing(false);
+        assert!(!page.move_caret(CaretMovement::Down, false));
+    }
+
+    #[test]
+    fn test_scroll_to_fragment() {
+        let mut page = Page::new();
+        page.receive_response(long_page(""));
+
+        assert!(page.scroll_to_fragment("p5"));
+        assert_eq!((0, 100), page.scroll_position());
+        assert_eq!(("line5".to_string(), 0), texts(&page)[0]);
+        assert!(!page.scroll_to_fragment("unknown"));
+        assert_eq!((0, 100), page.scroll_position());
+    }
+
+    #[test]
+    fn test_table_of_contents() {
+        let mut body = String::new();
+        for i in 0..20 {
+            body.push_str(&alloc::format!("<p>line{}</p>", i));
+        }
+        let mut page = Page::new();
+        page.receive_response(create_response(&alloc::format!(
+            "<html><head><style>h4 {{ display: none; }}</style></head><body><h1>Title</h1><p>intro</p><h2 id=\"usage\">How  to <a href=\"/use\">use</a></h2>{}<h3>Details</h3><h4>hidden</h4></body></html>",
+            body
+        )));
+
+        // 描画されていない見出しは目次に含めない
+        let toc = page.table_of_contents();
+        assert_eq!(
+            alloc::vec![
+                TocEntry::new(1, "Title".to_string(), None, 0),
+                TocEntry::new(2, "How to use".to_string(), Some("usage".to_string()), 80),
+                TocEntry::new(3, "Details".to_string(), None, 520),
+            ],
+            toc
+        );
+
+        assert!(page.scroll_to_toc_entry(1));
+        assert_eq!((0, 80), page.scroll_position());
+        // ページの下端より先にはスクロールしない
+        assert!(page.scroll_to_toc_entry(2));
+        assert_eq!((0, 200), page.scroll_position());
+        assert!(!page.scroll_to_toc_entry(3));
+    }
+
+    #[test]
+    fn test_find_text() {
+        let mut page = Page::new();
+        page.receive_response(long_page(""));
+        // "line1"と"line10"から"line19"が見つかる
+        let found = page.find_text("LINE1");
+        assert_eq!(11, found.len());
+        assert_eq!(Some(&20), found.first());
+        assert!(page.scroll_to_text(" line12 "));
+        assert_eq!((0, 240), page.scroll_position());
+        assert!(!page.scroll_to_text("line30"));
+
+        // 文字参照で書いたテキストも、置き換えた文字で見つかる
+        let mut page = Page::new();
+        page.receive_response(create_response(
+            "<html><body><p>menu</p><p>Caf&eacute; au lait</p></body></html>",
+        ));
+        assert_eq!(alloc::vec![20], page.find_text("café"));
+        assert_eq!(alloc::vec![20], page.find_text("CAF&Eacute; AU"));
+    }
+
+    #[test]
+    fn test_stylesheet_order() {
+        let mut page = Page::new();
+        page.set_url("http://example.com/");
+        page.receive_response(create_response(
+            "<html><head><style>.a { color: red; } .b { color: red; }</style><link rel=stylesheet href=site.css><style>.b { color: green; }</style></head><body><p class=a>a</p><p class=b>b</p></body></html>",
+        ));
+        assert_eq!(
+            vec!["http://example.com/site.css".to_string()],
+            page.pending_stylesheets()
+        );
+        page.receive_stylesheet(
+            "http://example.com/site.css",
+            CssParser::new(CssTokenizer::new(
+                ".a { color: blue; } .b { color: blue; }".to_string(),
+            ))
+            .parse_stylesheet(),
+        );
+
+        // <link>のルールは前の<style>より優先し、後の<style>は<link>より優先する
+        let colors: Vec<u32> = page
+            .display_items()
+            .into_iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text { style, .. } => Some(style.color().code_u32()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec![0x0000ff, 0x008000], colors);
+    }
+
+    #[test]
+    fn test_viewport_meta() {
+        let mut page = Page::new();
+        page.receive_response(create_response(
+            "<html><head><meta name=\"Viewport\" content=\"width=320\"></head><body><p>a</p></body></html>",
+        ));
+        assert_eq!(320, page.viewport().layout_width());
+        let body = page.layout_root().expect("layout root should exist");
+        assert_eq!(320, body.borrow().size().width());
+
+        // 指定のないページは、デバイスの倍率で割ったコンテンツエリアの幅でレイアウトする
+        page.set_device_scale_factor(2.0);
+        page.receive_response(create_response("<html><body><p>b</p></body></html>"));
+        assert_eq!(CONTENT_AREA_WIDTH / 2, page.viewport().layout_width());
+        assert_eq!(2.0, page.viewport().device_scale_factor());
+    }
+
+    #[test]
+    fn test_encoding_override() {
+        let mut page = Page::new();
+        // "日本"をShift_JISで書いたボディを、UTF-8と偽って送ってきたレスポンス
+        let mut raw =
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\r\n<p>".to_vec();
+        raw.extend_from_slice(&[0x93, 0xFA, 0x96, 0x7B]);
+        raw.extend_from_slice(b"</p>");
+        page.receive_response(HttpResponse::from_bytes(&raw).expect("failed to parse response"));
+        assert_eq!(Encoding::Utf8, page.encoding());
+        assert_eq!(None, page.encoding_override());
+        assert_ne!("日本".to_string(), texts(&page)[0].0);
+
+        assert!(page.set_encoding_override(Some(Encoding::ShiftJis)));
+        assert_eq!(Encoding::ShiftJis, page.encoding());
+        assert_eq!(vec![("日本".to_string(), 0)], texts(&page));
+
+        // 選んだ文字コードは、別のページに移動すると戻す
+        page.receive_response(create_response("<p>a</p>"));
+        assert_eq!(None, page.encoding_override());
+        assert_eq!(Encoding::Utf8, page.encoding());
+
+        page.load_error_page(
+            "http://example.com/",
+            &Error::Network("refused".to_string()),
+        );
+        assert!(!page.set_encoding_override(Some(Encoding::Latin1)));
+    }
+
+    #[test]
+    fn test_scroll_from_script() {
+        let mut page = Page::new();
+        page.receive_response(long_page("window.scrollTo(0, 60)"));
+        assert_eq!((0, 60), page.scroll_position());
+
+        let mut page = Page::new();
+        page.receive_response(long_page(
+            "var target = document.getElementById(\"p3\");\ntarget.scrollIntoView();",
+        ));
+        assert_eq!((0, 60), page.scroll_position());
+    }
+}
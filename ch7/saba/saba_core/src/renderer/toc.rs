@@ -0,0 +1,66 @@
+//! ページの目次。h1からh6までの見出しを文書の順に集め、UIのサイドバーに一覧として表示する。
+//! 見出しをクリックすると、レイアウトで決まった見出しの位置までスクロールする
+
+use crate::renderer::dom::api::DomTreeIterator;
+use crate::renderer::dom::node::Node;
+use crate::renderer::dom::node::NodeKind;
+use crate::text::collapse_whitespace;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+/// 目次の1項目
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TocEntry {
+    level: usize,
+    text: String,
+    id: Option<String>,
+    y: i64,
+}
+
+impl TocEntry {
+    pub fn new(level: usize, text: String, id: Option<String>, y: i64) -> Self {
+        Self { level, text, id, y }
+    }
+
+    /// 見出しの階層。h1が1で、h6が6になる
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn text(&self) -> String {
+        self.text.clone()
+    }
+
+    /// 見出しのid属性。"#id"のフラグメントで同じ見出しを指せる
+    pub fn id(&self) -> Option<String> {
+        self.id.clone()
+    }
+
+    /// ページの座標での見出しの上端
+    pub fn y(&self) -> i64 {
+        self.y
+    }
+}
+
+/// `root`以下の見出し要素と、その階層を文書の順に返す
+pub fn heading_nodes(root: &Rc<RefCell<Node>>) -> Vec<(usize, Rc<RefCell<Node>>)> {
+    DomTreeIterator::new(root.borrow().first_child())
+        .filter_map(|node| {
+            let level = node.borrow().element_kind()?.heading_level()?;
+            Some((level, node))
+        })
+        .collect()
+}
+
+/// 見出しの中のテキストをつなげる。連続する空白は1つにまとめ、前後の空白は取り除く
+pub fn heading_text(heading: &Rc<RefCell<Node>>) -> String {
+    let mut text = String::new();
+    for node in DomTreeIterator::new(heading.borrow().first_child()) {
+        if let NodeKind::Text(t) = node.borrow().kind() {
+            text.push_str(&t);
+        }
+    }
+    collapse_whitespace(&text)
+}
@@ -0,0 +1,210 @@
+//! ビューポート。ページをレイアウトする幅を決める。
+//! ページは<meta name="viewport">で、レイアウトする幅と最初の拡大率を指定できる。
+//! 指定がない場合は、コンテンツエリアの幅をCSSピクセルに直した幅でレイアウトする。
+//! 文字をビットマップフォントの決まった大きさで描くため、ページを縮小・拡大して描くことはできない。
+//! そのため、コンテンツエリアより広い幅を指定したページも、コンテンツエリアの幅でレイアウトする
+//! https://drafts.csswg.org/css-viewport/#viewport-meta
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// initial-scaleとして受け付ける値の範囲
+/// https://drafts.csswg.org/css-viewport/#parsing-algorithm
+const MIN_SCALE: f64 = 0.1;
+const MAX_SCALE: f64 = 10.0;
+/// レイアウトする幅として受け付ける値の範囲
+const MIN_WIDTH: i64 = 1;
+const MAX_WIDTH: i64 = 10000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ViewportWidth {
+    /// "width=device-width"。コンテンツエリアの幅をCSSピクセルに直した幅
+    DeviceWidth,
+    /// CSSピクセルで指定した幅
+    Length(i64),
+}
+
+/// <meta name="viewport">のcontent属性に書かれた指定。指定されなかった値はNoneにする
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ViewportMeta {
+    width: Option<ViewportWidth>,
+    initial_scale: Option<f64>,
+}
+
+impl ViewportMeta {
+    /// "width=device-width, initial-scale=1"のような、カンマ、セミコロン、空白で区切った
+    /// "名前=値"の並びを読む。知らない名前と読めない値は無視する
+    /// https://drafts.csswg.org/css-viewport/#parsing-algorithm
+    pub fn parse(content: &str) -> Self {
+        let mut meta = Self::default();
+        let separators = |c: char| c == ',' || c == ';' || c.is_ascii_whitespace();
+        // "width = 320"のように"="の前後に空白がある場合も、1つの指定として読む
+        let normalized = content
+            .split('=')
+            .map(|part| part.trim())
+            .collect::<Vec<_>>()
+            .join("=");
+        for property in normalized.split(separators).filter(|p| !p.is_empty()) {
+            let (name, value) = match property.split_once('=') {
+                Some((name, value)) => (name.to_ascii_lowercase(), value.to_ascii_lowercase()),
+                None => continue,
+            };
+            match name.as_str() {
+                "width" => {
+                    meta.width = match value.as_str() {
+                        "device-width" => Some(ViewportWidth::DeviceWidth),
+                        value => parse_number(value)
+                            .map(|w| ViewportWidth::Length((w as i64).clamp(MIN_WIDTH, MAX_WIDTH))),
+                    }
+                }
+                "initial-scale" => meta.initial_scale = parse_scale(&value),
+                _ => {}
+            }
+        }
+        meta
+    }
+
+    pub fn width(&self) -> Option<ViewportWidth> {
+        self.width
+    }
+
+    pub fn initial_scale(&self) -> Option<f64> {
+        self.initial_scale
+    }
+}
+
+/// 数値の前の部分だけを読む。"2.0x"は2.0として扱う
+fn parse_number(value: &str) -> Option<f64> {
+    let end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .unwrap_or(value.len());
+    value[..end].parse::<f64>().ok()
+}
+
+fn parse_scale(value: &str) -> Option<f64> {
+    parse_number(value).map(|scale| scale.clamp(MIN_SCALE, MAX_SCALE))
+}
+
+/// ページを表示する領域。`width`と`height`は画面のピクセルで、device_scale_factorは
+/// 1CSSピクセルを何ピクセルで描くかを表す。device_scale_factorはレイアウトする幅と、
+/// srcsetから選ぶ画像の解像度に使う
+#[derive(Debug, Clone, PartialEq)]
+pub struct Viewport {
+    width: i64,
+    height: i64,
+    device_scale_factor: f64,
+    meta: ViewportMeta,
+}
+
+impl Viewport {
+    pub fn new(width: i64, height: i64) -> Self {
+        Self {
+            width,
+            height,
+            device_scale_factor: 1.0,
+            meta: ViewportMeta::default(),
+        }
+    }
+
+    pub fn width(&self) -> i64 {
+        self.width
+    }
+
+    pub fn height(&self) -> i64 {
+        self.height
+    }
+
+    pub fn device_scale_factor(&self) -> f64 {
+        self.device_scale_factor
+    }
+
+    /// 0以下の値は無視する
+    pub fn set_device_scale_factor(&mut self, device_scale_factor: f64) {
+        if device_scale_factor > 0.0 {
+            self.device_scale_factor = device_scale_factor;
+        }
+    }
+
+    pub fn meta(&self) -> ViewportMeta {
+        self.meta
+    }
+
+    /// ページの<meta name="viewport">の指定を使う。ページを移動した場合は、指定のないmetaで置き換える
+    pub fn set_meta(&mut self, meta: ViewportMeta) {
+        self.meta = meta;
+    }
+
+    /// コンテンツエリアの幅をCSSピクセルに直した幅。"width=device-width"はこの幅になる
+    pub fn device_width(&self) -> i64 {
+        ((self.width as f64 / self.device_scale_factor) as i64).max(MIN_WIDTH)
+    }
+
+    /// ページをレイアウトする幅。縮小して描けないため、device_width()より広くはしない
+    /// https://drafts.csswg.org/css-viewport/#initial-viewport
+    pub fn layout_width(&self) -> i64 {
+        let width = match self.meta.width {
+            Some(ViewportWidth::Length(width)) => width,
+            Some(ViewportWidth::DeviceWidth) => self.device_width(),
+            // 幅を指定せずにinitial-scaleだけを指定した場合は、その拡大率で画面に収まる幅にする
+            None => match self.meta.initial_scale {
+                Some(scale) => ((self.device_width() as f64 / scale) as i64).max(MIN_WIDTH),
+                None => self.device_width(),
+            },
+        };
+        width.min(self.device_width())
+    }
+
+    /// UIに表示する、ビューポートの状態の説明
+    pub fn summary(&self) -> String {
+        format!(
+            "layout width {}px, device scale factor {}",
+            self.layout_width(),
+            self.device_scale_factor
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_viewport_meta() {
+        let meta = ViewportMeta::parse("width=device-width, initial-scale=1");
+        assert_eq!(Some(ViewportWidth::DeviceWidth), meta.width());
+        assert_eq!(Some(1.0), meta.initial_scale());
+
+        let meta = ViewportMeta::parse("WIDTH = 320; initial-scale=20 unknown=1");
+        assert_eq!(Some(ViewportWidth::Length(320)), meta.width());
+        assert_eq!(Some(MAX_SCALE), meta.initial_scale());
+
+        assert_eq!(
+            ViewportMeta::default(),
+            ViewportMeta::parse("width=abc, initial-scale")
+        );
+    }
+
+    #[test]
+    fn test_viewport() {
+        let mut viewport = Viewport::new(590, 400);
+        assert_eq!(590, viewport.layout_width());
+
+        // 1CSSピクセルを2ピクセルで描く画面では、半分の幅でレイアウトする
+        viewport.set_device_scale_factor(2.0);
+        viewport.set_meta(ViewportMeta::parse("width=device-width"));
+        assert_eq!(295, viewport.layout_width());
+
+        // 画面より狭い幅はそのまま使い、広い幅は縮小して描けないため画面の幅に収める
+        viewport.set_device_scale_factor(1.0);
+        viewport.set_meta(ViewportMeta::parse("width=320"));
+        assert_eq!(320, viewport.layout_width());
+        viewport.set_meta(ViewportMeta::parse("width=980"));
+        assert_eq!(590, viewport.layout_width());
+
+        viewport.set_meta(ViewportMeta::parse("initial-scale=2"));
+        assert_eq!(295, viewport.layout_width());
+        viewport.set_meta(ViewportMeta::parse("initial-scale=0.5"));
+        assert_eq!(590, viewport.layout_width());
+    }
+}
@@ -0,0 +1,148 @@
+//! リソースの先読み。ページの<link rel="preload">と<link rel="prefetch">が指すリソースを、
+//! イベントループのタスクが全て終わった後で少しずつ取得し、HTTPキャッシュに入れておく。
+//! ページの表示や入力への反応を遅らせないように、先読みはどのタスクよりも後に回す
+//! https://html.spec.whatwg.org/multipage/links.html#link-type-preload
+//! https://html.spec.whatwg.org/multipage/links.html#link-type-prefetch
+
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// 先読みの種類。宣言した順に先に取得する
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ResourceHintKind {
+    /// 表示中のページがすぐに使うリソース。ページを離れたら取得しない
+    Preload,
+    /// 次に移動するページが使うかもしれないリソース。ページを離れても取得する
+    Prefetch,
+}
+
+/// <link>要素で指定された、先読みするリソース
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceHint {
+    url: String,
+    kind: ResourceHintKind,
+}
+
+impl ResourceHint {
+    pub fn new(url: &str, kind: ResourceHintKind) -> Self {
+        Self {
+            url: url.to_string(),
+            kind,
+        }
+    }
+
+    /// <link>要素のrel属性とas属性から、先読みの種類を決める。relは空白で区切った値を、
+    /// 大文字と小文字を区別せずに比べる。preloadは、このブラウザが使うスタイルシートと画像だけを取得する
+    pub fn kind_of_link(rel: &str, destination: Option<&str>) -> Option<ResourceHintKind> {
+        let has = |value: &str| {
+            rel.split_ascii_whitespace()
+                .any(|r| r.eq_ignore_ascii_case(value))
+        };
+        if has("preload") {
+            return match destination
+                .map(|d| d.trim().to_ascii_lowercase())
+                .as_deref()
+            {
+                Some("style") | Some("image") => Some(ResourceHintKind::Preload),
+                _ => None,
+            };
+        }
+        if has("prefetch") {
+            return Some(ResourceHintKind::Prefetch);
+        }
+        None
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    pub fn kind(&self) -> ResourceHintKind {
+        self.kind
+    }
+}
+
+/// 先読みするリソースを、種類ごとに追加された順に取り出すキュー
+#[derive(Debug, Clone, Default)]
+pub struct ResourceLoader {
+    queue: Vec<ResourceHint>,
+}
+
+impl ResourceLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// キューにすでにあるURLは追加しない
+    pub fn push(&mut self, hint: ResourceHint) {
+        if self.queue.iter().any(|h| h.url == hint.url) {
+            return;
+        }
+        self.queue.push(hint);
+    }
+
+    /// 次に取得するリソース。preloadをprefetchより先に取り出す
+    pub fn pop(&mut self) -> Option<ResourceHint> {
+        let index = self
+            .queue
+            .iter()
+            .enumerate()
+            .min_by_key(|(i, h)| (h.kind, *i))
+            .map(|(i, _)| i)?;
+        Some(self.queue.remove(index))
+    }
+
+    /// ページを離れるときに、そのページのためのpreloadを取り消す
+    pub fn cancel_preloads(&mut self) {
+        self.queue.retain(|h| h.kind != ResourceHintKind::Preload);
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind_of_link() {
+        assert_eq!(
+            Some(ResourceHintKind::Preload),
+            ResourceHint::kind_of_link("PreLoad", Some("style"))
+        );
+        assert_eq!(
+            Some(ResourceHintKind::Prefetch),
+            ResourceHint::kind_of_link("dns-prefetch prefetch", None)
+        );
+        // このブラウザが使わない種類のリソースは、preloadしない
+        assert_eq!(None, ResourceHint::kind_of_link("preload", Some("font")));
+        assert_eq!(None, ResourceHint::kind_of_link("preload", None));
+        assert_eq!(None, ResourceHint::kind_of_link("stylesheet", None));
+    }
+
+    #[test]
+    fn test_resource_loader() {
+        let mut loader = ResourceLoader::new();
+        loader.push(ResourceHint::new("/next.html", ResourceHintKind::Prefetch));
+        loader.push(ResourceHint::new("/a.css", ResourceHintKind::Preload));
+        loader.push(ResourceHint::new("/next.html", ResourceHintKind::Prefetch));
+        loader.push(ResourceHint::new("/b.png", ResourceHintKind::Preload));
+        assert_eq!(3, loader.len());
+
+        assert_eq!(Some("/a.css".to_string()), loader.pop().map(|h| h.url()));
+        loader.cancel_preloads();
+        assert_eq!(
+            Some("/next.html".to_string()),
+            loader.pop().map(|h| h.url())
+        );
+        assert!(loader.is_empty());
+        assert_eq!(None, loader.pop());
+    }
+}
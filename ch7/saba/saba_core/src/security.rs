@@ -0,0 +1,132 @@
+//! 表示しているページの接続の安全性。ツールバーのアドレスバーの横に警告の印として表示し、
+//! 印をクリックすると、接続とサーバー証明書の検証の結果を説明するポップアップを開く。
+//! TLSの実装がまだないので、https://のページも接続の暗号化と証明書を検証済みとは表示しない
+//! https://w3c.github.io/webappsec-secure-contexts/
+
+use crate::error::Error;
+use crate::internal_page::is_internal_url;
+use crate::internal_page::view_source_target;
+use crate::url::Url;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// 接続の安全性
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecurityState {
+    /// about:blankのような、ネットワークを使わないブラウザの内部ページ
+    Internal,
+    /// 暗号化していないhttp://の接続
+    Insecure,
+    /// https://のURLだが、ブラウザが接続の暗号化とサーバー証明書を検証していない
+    Unverified,
+    /// サーバー証明書を検証できなかった。理由を持つ
+    CertificateError(String),
+}
+
+impl SecurityState {
+    /// 読み込めたページのURLから、接続の安全性を決める。
+    /// 接続の暗号化と証明書の検証の結果はURLからはわからないので、https://のページはUnverifiedにする
+    pub fn from_url(url: &str) -> Self {
+        let url = view_source_target(url).unwrap_or(url);
+        if is_internal_url(url) {
+            return SecurityState::Internal;
+        }
+        if Url::new(url.to_string()).is_secure() {
+            SecurityState::Unverified
+        } else {
+            SecurityState::Insecure
+        }
+    }
+
+    /// `url`を読み込めなかったときの接続の安全性。証明書のエラーで読み込めなかった場合は、その理由を持つ
+    pub fn from_error(url: &str, error: &Error) -> Self {
+        match error {
+            Error::Certificate(reason) => SecurityState::CertificateError(reason.clone()),
+            _ => Self::from_url(url),
+        }
+    }
+
+    /// アドレスバーの横に表示する印。
+    /// wasabi OSのビットマップフォントはASCIIの文字しか持たないので、記号ではなく文字で表す
+    pub fn indicator(&self) -> &'static str {
+        match self {
+            SecurityState::Internal => "i",
+            SecurityState::Insecure => "!",
+            SecurityState::Unverified => "?",
+            SecurityState::CertificateError(_) => "X",
+        }
+    }
+
+    /// 印をクリックしたときに開くポップアップの見出し
+    pub fn summary(&self) -> String {
+        match self {
+            SecurityState::Internal => "This is a saba internal page",
+            SecurityState::Insecure => "Connection is not secure",
+            SecurityState::Unverified => "Connection is not verified",
+            SecurityState::CertificateError(_) => "Certificate is not valid",
+        }
+        .to_string()
+    }
+
+    /// ポップアップの見出しの下に並べる説明。1行ずつ返す
+    pub fn details(&self) -> Vec<String> {
+        match self {
+            SecurityState::Internal => {
+                vec!["The page was created by the browser without using the network.".to_string()]
+            }
+            SecurityState::Insecure => vec![
+                "The page was sent over HTTP without encryption.".to_string(),
+                "Do not enter passwords or other sensitive information.".to_string(),
+            ],
+            SecurityState::Unverified => vec![
+                "The page was requested over HTTPS, but the browser did not verify".to_string(),
+                "the encryption or the server certificate of the connection.".to_string(),
+                "Do not enter passwords or other sensitive information.".to_string(),
+            ],
+            SecurityState::CertificateError(reason) => vec![
+                "The server certificate could not be verified:".to_string(),
+                format!("  {}", reason),
+                "Someone may be impersonating the server.".to_string(),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_security_state() {
+        assert_eq!(
+            SecurityState::Internal,
+            SecurityState::from_url("about:blank")
+        );
+        assert_eq!(
+            SecurityState::Insecure,
+            SecurityState::from_url("http://example.com/")
+        );
+        assert_eq!(
+            SecurityState::Unverified,
+            SecurityState::from_url("https://example.com/")
+        );
+        assert_eq!(
+            SecurityState::Unverified,
+            SecurityState::from_url("view-source:https://example.com/")
+        );
+        assert_eq!(
+            SecurityState::CertificateError("expired".to_string()),
+            SecurityState::from_error(
+                "https://example.com/",
+                &Error::Certificate("expired".to_string())
+            )
+        );
+        assert_eq!(
+            SecurityState::Unverified,
+            SecurityState::from_error("https://example.com/", &Error::Network("".to_string()))
+        );
+    }
+}
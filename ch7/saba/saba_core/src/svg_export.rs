@@ -0,0 +1,154 @@
+//! DisplayItemをSVGの文書に変換する。ページを描画した結果を、解像度によらない画像として保存し、
+//! ドキュメントに載せたり、描画の変化を差分で比べたりするのに使う。
+//! 文字はwasabi OSのビットマップフォントと同じ幅になるように、textLengthで横幅を揃える
+//! https://www.w3.org/TR/SVG2/
+
+use crate::constants::CHAR_HEIGHT;
+use crate::display_item::DisplayItem;
+use crate::renderer::layout::computed_style::FontStyle;
+use crate::renderer::layout::computed_style::FontWeight;
+use crate::renderer::layout::computed_style::TextDecoration;
+use crate::renderer::layout::font::font_scale;
+use crate::renderer::layout::font::text_width;
+use alloc::format;
+use alloc::string::String;
+
+/// `items`を、幅`width`、高さ`height`のSVGの文書に変換する。背景は白で塗る
+pub fn display_items_to_svg(items: &[DisplayItem], width: i64, height: i64) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+        width, height
+    );
+    svg.push_str(&format!(
+        "<rect x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" fill=\"#ffffff\"/>\n",
+        width, height
+    ));
+
+    for item in items {
+        match item {
+            DisplayItem::Rect {
+                style,
+                layout_point,
+                layout_size,
+            } => {
+                svg.push_str(&format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+                    layout_point.x(),
+                    layout_point.y(),
+                    layout_size.width(),
+                    layout_size.height(),
+                    color_code(style.background_color().code_u32())
+                ));
+            }
+            DisplayItem::Text {
+                text,
+                style,
+                layout_point,
+            } => {
+                if text.is_empty() {
+                    continue;
+                }
+                let font_size = style.font_size();
+                let mut font_attributes = String::new();
+                if style.font_weight() == FontWeight::Bold {
+                    font_attributes.push_str(" font-weight=\"bold\"");
+                }
+                if style.font_style() == FontStyle::Italic {
+                    font_attributes.push_str(" font-style=\"italic\"");
+                }
+                if style.text_decoration() == TextDecoration::Underline {
+                    font_attributes.push_str(" text-decoration=\"underline\"");
+                }
+                // yは文字の上端なので、dominant-baselineで上端に合わせる
+                svg.push_str(&format!(
+                    "<text x=\"{}\" y=\"{}\" font-family=\"monospace\" font-size=\"{}\" textLength=\"{}\" lengthAdjust=\"spacingAndGlyphs\" dominant-baseline=\"text-before-edge\" fill=\"{}\"{}>{}</text>\n",
+                    layout_point.x(),
+                    layout_point.y(),
+                    CHAR_HEIGHT * font_scale(font_size),
+                    text_width(text, font_size),
+                    color_code(style.color().code_u32()),
+                    font_attributes,
+                    escape_xml(text)
+                ));
+            }
+            DisplayItem::Image {
+                image,
+                layout_point,
+            } => {
+                // 同じ色が続く範囲ごとに、高さ1の四角として描く
+                for y in 0..image.height() {
+                    for (x, width, color) in image.row_spans(y) {
+                        svg.push_str(&format!(
+                            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"1\" fill=\"{}\"/>\n",
+                            layout_point.x() + x as i64,
+                            layout_point.y() + y as i64,
+                            width,
+                            color_code(color)
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn color_code(color: u32) -> String {
+    format!("#{:06x}", color)
+}
+
+/// テキストと属性値に書けない文字を、実体参照にする
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::layout::computed_style::Color;
+    use crate::renderer::layout::computed_style::ComputedStyle;
+    use crate::renderer::layout::computed_style::FontSize;
+    use crate::renderer::layout::layout_object::LayoutPoint;
+    use crate::renderer::layout::layout_object::LayoutSize;
+    use alloc::rc::Rc;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    #[test]
+    fn test_display_items_to_svg() {
+        let mut style = ComputedStyle::new();
+        style.set_font_size(FontSize::Medium);
+        style.set_font_weight(FontWeight::Normal);
+        style.set_font_style(FontStyle::Normal);
+        style.set_text_decoration(TextDecoration::None);
+        style.set_color(Color::black());
+        style.set_background_color(Color::white());
+        let items = vec![
+            DisplayItem::Rect {
+                style: Rc::new(style.clone()),
+                layout_point: LayoutPoint::new(0, 0),
+                layout_size: LayoutSize::new(10, 20),
+            },
+            DisplayItem::Text {
+                text: "a < b & c".to_string(),
+                style: Rc::new(style),
+                layout_point: LayoutPoint::new(5, 30),
+            },
+        ];
+        let svg = display_items_to_svg(&items, 100, 50);
+
+        assert!(svg
+            .starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"100\" height=\"50\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains("<rect x=\"0\" y=\"0\" width=\"10\" height=\"20\""));
+        assert!(svg.contains(
+            "<text x=\"5\" y=\"30\" font-family=\"monospace\" font-size=\"16\" textLength=\"62\""
+        ));
+        assert!(svg.contains(">a &lt; b &amp; c</text>"));
+    }
+}
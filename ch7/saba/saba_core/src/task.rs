@@ -0,0 +1,228 @@
+//! ブラウザのイベントループで実行するタスク。
+//! UIはキーやマウスの入力を処理するたびにrun_tasksを呼び、溜まっているタスクを優先度の順に実行する。
+//! 先読みや画像のデコードのような重い処理は、入力への反応を遅らせないように、
+//! 1回の繰り返しで使える時間の中で少しずつ実行する
+
+use crate::constants::MAX_BACKGROUND_TASKS_PER_ITERATION;
+use crate::constants::TASK_TIME_BUDGET_MS;
+use crate::platform::Clock;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use core::cell::RefCell;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+
+pub type Task = Box<dyn FnOnce()>;
+
+/// タスクの優先度。宣言した順に優先する
+/// https://html.spec.whatwg.org/multipage/webappapis.html#task-source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TaskPriority {
+    /// キーやマウスの入力に応えるタスク。時間の上限を超えていても必ず実行する
+    Input,
+    /// タイマーで実行するタスク
+    Timer,
+    /// リソースの先読みや画像のデコードのような、急がないタスク
+    Prefetch,
+}
+
+/// 優先度ごとに、追加された順にタスクを持つキュー
+#[derive(Default)]
+pub struct TaskQueue {
+    input: VecDeque<Task>,
+    timer: VecDeque<Task>,
+    prefetch: VecDeque<Task>,
+}
+
+impl Debug for TaskQueue {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        f.debug_struct("TaskQueue")
+            .field("input", &self.input.len())
+            .field("timer", &self.timer.len())
+            .field("prefetch", &self.prefetch.len())
+            .finish()
+    }
+}
+
+impl TaskQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, priority: TaskPriority, task: Task) {
+        self.queue_mut(priority).push_back(task);
+    }
+
+    /// 次に実行するタスクの優先度
+    pub fn next_priority(&self) -> Option<TaskPriority> {
+        [
+            TaskPriority::Input,
+            TaskPriority::Timer,
+            TaskPriority::Prefetch,
+        ]
+        .into_iter()
+        .find(|p| !self.queue(*p).is_empty())
+    }
+
+    /// 最も優先度が高いタスクを取り出す
+    pub fn pop(&mut self) -> Option<(TaskPriority, Task)> {
+        let priority = self.next_priority()?;
+        let task = self.queue_mut(priority).pop_front()?;
+        Some((priority, task))
+    }
+
+    pub fn len(&self) -> usize {
+        self.input.len() + self.timer.len() + self.prefetch.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn queue(&self, priority: TaskPriority) -> &VecDeque<Task> {
+        match priority {
+            TaskPriority::Input => &self.input,
+            TaskPriority::Timer => &self.timer,
+            TaskPriority::Prefetch => &self.prefetch,
+        }
+    }
+
+    fn queue_mut(&mut self, priority: TaskPriority) -> &mut VecDeque<Task> {
+        match priority {
+            TaskPriority::Input => &mut self.input,
+            TaskPriority::Timer => &mut self.timer,
+            TaskPriority::Prefetch => &mut self.prefetch,
+        }
+    }
+}
+
+/// イベントループの1回の繰り返しで、キューのタスクを優先度の順に実行し、実行した数を返す。
+/// 入力のタスクは全て実行する。タイマーと先読みのタスクは、`clock`で測った時間が
+/// TASK_TIME_BUDGET_MSを超えるまで実行し、残りは次の繰り返しに回す。
+/// ミリ秒を測れない環境では、時間の代わりにMAX_BACKGROUND_TASKS_PER_ITERATION個まで実行する。
+///
+/// 実行中のタスクを止めることはできないので、重い処理は小さなタスクに分けて追加する。
+/// キューはタスクを実行する間は借用しないので、タスクの中から新しいタスクを追加できる
+pub fn run_tasks(queue: &RefCell<TaskQueue>, clock: &dyn Clock) -> usize {
+    let started = clock.now_millis();
+    let mut background = 0;
+    let mut ran = 0;
+
+    loop {
+        let priority = match queue.borrow().next_priority() {
+            Some(priority) => priority,
+            None => break,
+        };
+        if priority != TaskPriority::Input {
+            let exhausted = match (started, clock.now_millis()) {
+                (Some(started), Some(now)) => now.saturating_sub(started) >= TASK_TIME_BUDGET_MS,
+                _ => background >= MAX_BACKGROUND_TASKS_PER_ITERATION,
+            };
+            if exhausted {
+                break;
+            }
+            background += 1;
+        }
+
+        let task = match queue.borrow_mut().pop() {
+            Some((_, task)) => task,
+            None => break,
+        };
+        task();
+        ran += 1;
+    }
+
+    ran
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::NoClock;
+    use alloc::rc::Rc;
+    use alloc::vec::Vec;
+    use core::cell::Cell;
+
+    /// 時刻を読むたびに`step`ミリ秒進む時計
+    struct SteppingClock {
+        now: Cell<u64>,
+        step: u64,
+    }
+
+    impl Clock for SteppingClock {
+        fn now(&self) -> Option<u64> {
+            None
+        }
+
+        fn now_millis(&self) -> Option<u64> {
+            let now = self.now.get();
+            self.now.set(now + self.step);
+            Some(now)
+        }
+    }
+
+    fn push_logged(
+        queue: &RefCell<TaskQueue>,
+        log: &Rc<RefCell<Vec<&'static str>>>,
+        priority: TaskPriority,
+        name: &'static str,
+    ) {
+        let log = log.clone();
+        queue
+            .borrow_mut()
+            .push(priority, Box::new(move || log.borrow_mut().push(name)));
+    }
+
+    #[test]
+    fn test_priority() {
+        let queue = RefCell::new(TaskQueue::new());
+        let log = Rc::new(RefCell::new(Vec::new()));
+        push_logged(&queue, &log, TaskPriority::Prefetch, "prefetch");
+        push_logged(&queue, &log, TaskPriority::Timer, "timer1");
+        push_logged(&queue, &log, TaskPriority::Input, "key");
+        push_logged(&queue, &log, TaskPriority::Timer, "timer2");
+        assert_eq!(4, queue.borrow().len());
+
+        assert_eq!(4, run_tasks(&queue, &NoClock));
+        assert_eq!(
+            alloc::vec!["key", "timer1", "timer2", "prefetch"],
+            *log.borrow()
+        );
+        assert!(queue.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_time_budget() {
+        let queue = Rc::new(RefCell::new(TaskQueue::new()));
+        let log = Rc::new(RefCell::new(Vec::new()));
+        for _ in 0..10 {
+            push_logged(&queue, &log, TaskPriority::Prefetch, "prefetch");
+        }
+        // 先読みのタスクの中で起きた入力は、時間の上限を超えていても同じ繰り返しで実行する
+        let inner_queue = queue.clone();
+        let inner_log = log.clone();
+        queue.borrow_mut().push(
+            TaskPriority::Prefetch,
+            Box::new(move || push_logged(&inner_queue, &inner_log, TaskPriority::Input, "key")),
+        );
+
+        // 時刻を読むたびに5ミリ秒進むので、開始から15ミリ秒までの3つだけ実行する
+        let clock = SteppingClock {
+            now: Cell::new(0),
+            step: 5,
+        };
+        assert_eq!(3, run_tasks(&queue, &clock));
+        assert_eq!(8, queue.borrow().len());
+
+        // ミリ秒を測れない場合は、決まった数だけ実行する
+        assert_eq!(
+            MAX_BACKGROUND_TASKS_PER_ITERATION,
+            run_tasks(&queue, &NoClock)
+        );
+
+        // 残りの先読みと、その中で追加された入力のタスクを実行する
+        while run_tasks(&queue, &NoClock) > 0 {}
+        assert_eq!(11, log.borrow().len());
+        assert_eq!(Some(&"key"), log.borrow().last());
+    }
+}
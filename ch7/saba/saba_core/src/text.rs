@@ -0,0 +1,144 @@
+//! テキストの正規化。ページ内検索のように、利用者が入力した文字列とページのテキストを比べるときに使う。
+//! 大文字と小文字をそろえ、連続する空白を1つにまとめ、文字参照を文字に置き換えてから比べるので、
+//! "café"で"caf&eacute;"と書かれたテキストも見つけられる
+//! https://www.w3.org/TR/charmod-norm/
+
+use crate::renderer::html::entity::is_legacy_character_reference;
+use crate::renderer::html::entity::named_character_reference;
+use crate::renderer::html::entity::numeric_character_reference;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// 名前付き文字参照として読む名前の長さの上限。HtmlTokenizerと同じ値にする
+const MAX_CHARACTER_REFERENCE_NAME_LEN: usize = 32;
+
+/// テキストの中の文字参照（"&eacute;"や"&#xE9;"）を、それが表す文字に置き換える。
+/// 文字参照として読めない"&"は、そのまま残す
+/// https://html.spec.whatwg.org/multipage/parsing.html#character-reference-state
+pub fn decode_character_references(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut decoded = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != '&' {
+            decoded.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        match decode_reference(&chars[i + 1..]) {
+            Some((c, consumed)) => {
+                decoded.push(c);
+                i += 1 + consumed;
+            }
+            None => {
+                decoded.push('&');
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+/// "&"の後ろに続く文字参照を読み、表す文字と読んだ文字数を返す
+fn decode_reference(rest: &[char]) -> Option<(char, usize)> {
+    if rest.first() == Some(&'#') {
+        let hex = matches!(rest.get(1), Some('x') | Some('X'));
+        let start = if hex { 2 } else { 1 };
+        let radix = if hex { 16 } else { 10 };
+        let digits = rest[start..]
+            .iter()
+            .take_while(|c| c.is_digit(radix))
+            .count();
+        if digits == 0 {
+            return None;
+        }
+        let code = rest[start..start + digits].iter().fold(0u32, |code, c| {
+            code.saturating_mul(radix)
+                .saturating_add(c.to_digit(radix).unwrap_or(0))
+        });
+        let end = start + digits;
+        let consumed = if rest.get(end) == Some(&';') {
+            end + 1
+        } else {
+            end
+        };
+        return Some((numeric_character_reference(code), consumed));
+    }
+
+    let len = rest
+        .iter()
+        .take(MAX_CHARACTER_REFERENCE_NAME_LEN)
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .count();
+    let name: String = rest[..len].iter().collect();
+    if rest.get(len) == Some(&';') {
+        if let Some(c) = named_character_reference(&name) {
+            return Some((c, len + 1));
+        }
+    }
+    // ";"を省略できる古い名前は、名前の先頭が一致する部分だけを置き換える
+    (1..=len)
+        .rev()
+        .find(|l| is_legacy_character_reference(&name[..*l]))
+        .and_then(|l| Some((named_character_reference(&name[..l])?, l)))
+}
+
+/// 連続する空白を1つの空白にまとめ、前後の空白を取り除く
+pub fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::new();
+    for word in text.split_whitespace() {
+        if !collapsed.is_empty() {
+            collapsed.push(' ');
+        }
+        collapsed.push_str(word);
+    }
+    collapsed
+}
+
+/// 大文字と小文字を区別しないように、小文字にそろえる
+/// https://www.w3.org/TR/charmod-norm/#definitionCaseFolding
+pub fn fold_case(text: &str) -> String {
+    text.chars().flat_map(|c| c.to_lowercase()).collect()
+}
+
+/// 比べるための形にする。文字参照を置き換え、小文字にそろえ、空白をまとめる
+pub fn normalize(text: &str) -> String {
+    collapse_whitespace(&fold_case(&decode_character_references(text)))
+}
+
+/// 正規化した`text`が、正規化した`query`を含むかどうか。空の`query`はどのテキストにも含まれない
+pub fn contains_normalized(text: &str, query: &str) -> bool {
+    let query = normalize(query);
+    !query.is_empty() && normalize(text).contains(&query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_decode_character_references() {
+        assert_eq!("café", decode_character_references("caf&eacute;"));
+        assert_eq!("a & b", decode_character_references("a &amp; b"));
+        assert_eq!("é é", decode_character_references("&#xE9; &#233"));
+        // ";"を省略できる古い名前と、文字参照として読めない"&"
+        assert_eq!("©right", decode_character_references("&copyright"));
+        assert_eq!(
+            "&hellip &unknown; &#;",
+            decode_character_references("&hellip &unknown; &#;")
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!("hello world".to_string(), normalize("  Hello\n\tWORLD "));
+        assert!(contains_normalized("Caf&Eacute; au lait", "café AU"));
+        assert!(contains_normalized(
+            "<p>Café   au lait</p>",
+            "caf&eacute; au"
+        ));
+        assert!(!contains_normalized("cafe", "café"));
+        assert!(!contains_normalized("anything", "  "));
+    }
+}
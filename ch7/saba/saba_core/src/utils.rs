@@ -1,7 +1,11 @@
 use crate::renderer::dom::node::Node;
+use crate::renderer::dom::node::NodeKind;
+use crate::renderer::layout::layout_object::LayoutObject;
 use alloc::format;
 use alloc::rc::Rc;
 use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
 use core::cell::RefCell;
 
 pub fn convert_dom_to_string(root: &Option<Rc<RefCell<Node>>>) -> String {
@@ -26,3 +30,138 @@ fn convert_dom_to_string_internal(
         None => (),
     }
 }
+
+/// html5lib-testsのtree-constructionと同じ形式で、ドキュメントの子孫を1行に1つずつ書く。
+/// 属性は名前の順に、要素の1段下に並べる
+/// https://github.com/html5lib/html5lib-tests/tree/master/tree-construction
+pub fn convert_dom_to_html5lib_string(document: &Rc<RefCell<Node>>) -> String {
+    let mut lines = Vec::new();
+    let mut stack = vec![(document.borrow().first_child(), 0)];
+    while let Some((node, depth)) = stack.pop() {
+        let node = match node {
+            Some(node) => node,
+            None => continue,
+        };
+        let indent = "  ".repeat(depth);
+        match node.borrow().kind() {
+            NodeKind::Element(e) => {
+                lines.push(format!("| {}<{}>", indent, e.kind()));
+                let mut attributes = e.attributes();
+                attributes.sort_by_key(|a| a.name());
+                for a in attributes {
+                    lines.push(format!("| {}  {}=\"{}\"", indent, a.name(), a.value()));
+                }
+            }
+            NodeKind::Text(s) => lines.push(format!("| {}\"{}\"", indent, s)),
+            NodeKind::Comment(s) => lines.push(format!("| {}<!-- {} -->", indent, s)),
+            NodeKind::Document => continue,
+        }
+        stack.push((node.borrow().next_sibling(), depth));
+        stack.push((node.borrow().first_child(), depth + 1));
+    }
+    lines.join("\n")
+}
+
+/// レイアウトツリーを、1行に1つのレイアウトオブジェクトの種類、ノード、位置と大きさを書いた文字列にする
+pub fn convert_layout_to_string(root: &Option<Rc<RefCell<LayoutObject>>>) -> String {
+    let mut result = String::from("\n");
+    convert_layout_to_string_internal(root, 0, &mut result);
+    result
+}
+
+fn convert_layout_to_string_internal(
+    object: &Option<Rc<RefCell<LayoutObject>>>,
+    depth: usize,
+    result: &mut String,
+) {
+    match object {
+        Some(o) => {
+            let o = o.borrow();
+            result.push_str(&"  ".repeat(depth));
+            result.push_str(&format!(
+                "{:?} {:?} ({}, {}) {}x{}",
+                o.kind(),
+                o.node_kind(),
+                o.point().x(),
+                o.point().y(),
+                o.size().width(),
+                o.size().height()
+            ));
+            result.push('\n');
+            convert_layout_to_string_internal(&o.first_child(), depth + 1, result);
+            convert_layout_to_string_internal(&o.next_sibling(), depth, result);
+        }
+        None => (),
+    }
+}
+
+/// https://datatracker.ietf.org/doc/html/rfc4648#section-4
+pub fn base64_encode(input: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut result = Vec::new();
+    for chunk in input.chunks(3) {
+        let b0 = chunk[0] as u32;
+        let b1 = chunk.get(1).copied().unwrap_or(0) as u32;
+        let b2 = chunk.get(2).copied().unwrap_or(0) as u32;
+        let triple = (b0 << 16) | (b1 << 8) | b2;
+
+        result.push(TABLE[(triple >> 18) as usize & 0x3f]);
+        result.push(TABLE[(triple >> 12) as usize & 0x3f]);
+        if chunk.len() > 1 {
+            result.push(TABLE[(triple >> 6) as usize & 0x3f]);
+        } else {
+            result.push(b'=');
+        }
+        if chunk.len() > 2 {
+            result.push(TABLE[triple as usize & 0x3f]);
+        } else {
+            result.push(b'=');
+        }
+    }
+
+    String::from_utf8(result).expect("base64 output should be ASCII")
+}
+
+/// 検索クエリのように、URLのクエリに入れる文字列をエンコードする。空白は"+"にする
+/// https://url.spec.whatwg.org/#application-x-www-form-urlencoded-percent-encode-set
+pub fn form_urlencode(input: &str) -> String {
+    let mut result = String::new();
+    for b in input.bytes() {
+        match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'*' | b'-' | b'.' | b'_' => {
+                result.push(b as char)
+            }
+            b' ' => result.push('+'),
+            _ => result.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    result
+}
+
+/// form_urlencodeでエンコードした文字列を元に戻す。"+"は空白にし、"%XX"はそのバイトにする。
+/// "%"の後に16進数が続かない場合は、そのまま残す
+/// https://url.spec.whatwg.org/#concept-urlencoded-parser
+pub fn form_urldecode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => result.push(b' '),
+            b'%' => match input
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(b) => {
+                    result.push(b);
+                    i += 2;
+                }
+                None => result.push(b'%'),
+            },
+            b => result.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
@@ -0,0 +1,221 @@
+//! web-platform-testsとcss-parsing-testsにならって作ったCSSのケースを、
+//! `CssTokenizer`、`CssParser`とセレクタの一致の判定で実行し、通ったケースの割合を表示する。
+//! `cargo test --test css -- --nocapture`で、ファイルごとの結果を表示する。
+//! CSSの機能が増えたら、ファイルごとの最低限通るケースの数を上げる
+//! https://github.com/web-platform-tests/wpt/tree/master/css
+//! https://github.com/web-platform-tests/wpt/tree/master/css/css-syntax
+
+#[path = "html5lib/json.rs"]
+mod json;
+
+use json::Json;
+use saba_core::renderer::css::cssom::CssParser;
+use saba_core::renderer::css::token::CssToken;
+use saba_core::renderer::css::token::CssTokenizer;
+use saba_core::renderer::dom::node::ElementKind;
+use saba_core::renderer::dom::node::Node;
+use saba_core::renderer::html::parser::HtmlParser;
+use saba_core::renderer::html::token::HtmlTokenizer;
+use saba_core::renderer::layout::layout_object::LayoutObject;
+use std::cell::RefCell;
+use std::panic::catch_unwind;
+use std::rc::Rc;
+
+/// (ファイル名, 中身, 最低限通るケースの数)
+const TOKENIZER_TESTS: (&str, &str, usize) =
+    ("tokenizer.json", include_str!("css/tokenizer.json"), 18);
+
+const STYLESHEET_TESTS: (&str, &str, usize) =
+    ("stylesheet.json", include_str!("css/stylesheet.json"), 19);
+
+const SELECTOR_TESTS: (&str, &str, usize) =
+    ("selectors.json", include_str!("css/selectors.json"), 18);
+
+/// ファイルごとの結果
+#[derive(Debug, Default)]
+struct Summary {
+    passed: usize,
+    failed: Vec<String>,
+}
+
+impl Summary {
+    fn record(&mut self, passed: bool, name: &str) {
+        if passed {
+            self.passed += 1;
+        } else {
+            self.failed.push(name.escape_debug().to_string());
+        }
+    }
+
+    fn report(&self, name: &str, minimum: usize) {
+        let total = self.passed + self.failed.len();
+        println!(
+            "{}: {}/{} passed ({:.1}%)",
+            name,
+            self.passed,
+            total,
+            self.passed as f64 * 100.0 / total.max(1) as f64
+        );
+        for failed in &self.failed {
+            println!("  failed: {}", failed);
+        }
+        assert!(
+            self.passed >= minimum,
+            "{}: only {} cases passed, expected at least {}",
+            name,
+            self.passed,
+            minimum
+        );
+    }
+}
+
+/// 入力と期待する結果を交互に並べた配列を、組にする
+fn pairs(content: &str) -> Vec<(String, Json)> {
+    let root = Json::parse(content).expect("fixture should be valid JSON");
+    let items = root.as_array().expect("fixture should be an array");
+    assert!(items.len() % 2 == 0, "fixture should have pairs");
+    items
+        .chunks(2)
+        .map(|pair| {
+            let input = pair[0].as_str().expect("input should be a string");
+            (input.to_string(), pair[1].clone())
+        })
+        .collect()
+}
+
+/// トークンをcss-parsing-testsと同じ形式にする。
+/// 記号のトークンは文字列に、値を持つトークンは["ident", "p"]のような配列にする
+fn token_to_json(token: CssToken) -> Json {
+    let str = |s: &str| Json::String(s.to_string());
+    let pair = |kind: &str, value: Json| Json::Array(vec![str(kind), value]);
+    match token {
+        // HashTokenは先頭の"#"を含むので、取り除く
+        CssToken::HashToken(s) => pair("hash", str(s.trim_start_matches('#'))),
+        CssToken::Delim(c) => str(&c.to_string()),
+        CssToken::Number(n) => pair("number", Json::Number(n)),
+        CssToken::Colon => str(":"),
+        CssToken::SemiColon => str(";"),
+        CssToken::OpenParenthesis => str("("),
+        CssToken::CloseParenthesis => str(")"),
+        CssToken::OpenCurly => str("{"),
+        CssToken::CloseCurly => str("}"),
+        CssToken::Ident(s) => pair("ident", str(&s)),
+        CssToken::StringToken(s) => pair("string", str(&s)),
+        CssToken::AtKeyword(s) => pair("at-keyword", str(&s)),
+    }
+}
+
+/// トークンの列が一致したケースを通ったものとする。空白のトークンは作らないので、期待する結果にも含めない
+fn run_tokenizer_tests(content: &str) -> Summary {
+    let mut summary = Summary::default();
+    for (input, expected) in pairs(content) {
+        let actual = catch_unwind(|| {
+            let tokens = CssTokenizer::new(input.clone()).map(token_to_json);
+            Json::Array(tokens.collect())
+        });
+        summary.record(actual.ok() == Some(expected), &input);
+    }
+    summary
+}
+
+/// スタイルシートを解釈し、ルールごとのcssTextの列が一致したケースを通ったものとする
+fn run_stylesheet_tests(content: &str) -> Summary {
+    let mut summary = Summary::default();
+    for (input, expected) in pairs(content) {
+        let actual = catch_unwind(|| {
+            let sheet = CssParser::new(CssTokenizer::new(input.clone())).parse_stylesheet();
+            let rules = sheet.rules.iter().map(|r| Json::String(r.css_text()));
+            Json::Array(rules.collect())
+        });
+        summary.record(actual.ok() == Some(expected), &input);
+    }
+    summary
+}
+
+/// nodeの子孫のノードを、文書の順に並べる。node自身は含めない
+fn descendants(node: &Rc<RefCell<Node>>) -> Vec<Rc<RefCell<Node>>> {
+    let mut nodes = Vec::new();
+    let mut child = node.borrow().first_child();
+    while let Some(c) = child {
+        nodes.push(c.clone());
+        nodes.extend(descendants(&c));
+        child = c.borrow().next_sibling();
+    }
+    nodes
+}
+
+/// querySelectorAll()のように、セレクタに一致する要素のidを文書の順に返す。
+/// <html>と<head>を対象にしないように、<body>の子孫の要素だけを調べる。
+/// ブラウザがスタイルを適用するときと同じく、`matching_rules`で絞り込んでから`is_node_selected`で判定する
+fn select(html: &str, selector: &str) -> Vec<Json> {
+    let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+    let document = window.borrow().document();
+    let body = descendants(&document)
+        .into_iter()
+        .find(|n| n.borrow().element_kind() == Some(ElementKind::Body))
+        .expect("document should have a body");
+
+    let style = format!("{} {{ }}", selector);
+    let sheet = CssParser::new(CssTokenizer::new(style)).parse_stylesheet();
+    let rule = match sheet.rules.first() {
+        Some(rule) => rule,
+        None => return Vec::new(),
+    };
+
+    let mut ids = Vec::new();
+    for node in descendants(&body) {
+        let element = match node.borrow().get_element() {
+            Some(element) => element,
+            None => continue,
+        };
+        let layout_object = LayoutObject::new(node.clone(), &None);
+        if sheet.matching_rules(&element).contains(&rule)
+            && layout_object.is_node_selected(&rule.selector)
+        {
+            let id = element.get_attribute("id").unwrap_or_default();
+            ids.push(Json::String(id));
+        }
+    }
+    ids
+}
+
+/// 文書とセレクタの組ごとに、一致した要素のidの列が一致したケースを通ったものとする
+fn run_selector_tests(content: &str) -> Summary {
+    let mut summary = Summary::default();
+    let root = Json::parse(content).expect("fixture should be valid JSON");
+    for document in root.as_array().expect("fixture should be an array") {
+        let html = document
+            .get("html")
+            .and_then(|h| h.as_str())
+            .expect("document should have html");
+        let tests = document
+            .get("tests")
+            .and_then(|t| t.as_array())
+            .expect("document should have tests");
+        for test in tests {
+            let test = test.as_array().expect("test should be a pair");
+            let selector = test[0].as_str().expect("selector should be a string");
+            let actual = catch_unwind(|| Json::Array(select(html, selector)));
+            summary.record(actual.ok().as_ref() == Some(&test[1]), selector);
+        }
+    }
+    summary
+}
+
+#[test]
+fn css_tokenizer() {
+    let (name, content, minimum) = TOKENIZER_TESTS;
+    run_tokenizer_tests(content).report(name, minimum);
+}
+
+#[test]
+fn css_stylesheet() {
+    let (name, content, minimum) = STYLESHEET_TESTS;
+    run_stylesheet_tests(content).report(name, minimum);
+}
+
+#[test]
+fn css_selectors() {
+    let (name, content, minimum) = SELECTOR_TESTS;
+    run_selector_tests(content).report(name, minimum);
+}
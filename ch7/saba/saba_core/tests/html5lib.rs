@@ -0,0 +1,287 @@
+//! html5lib-testsのケースを`HtmlTokenizer`と`HtmlParser`で実行し、通ったケースの割合を表示する。
+//! `cargo test --test html5lib -- --nocapture`で、ファイルごとの結果を表示する。
+//! パーサーの機能が増えたら、ファイルごとの最低限通るケースの数を上げる
+//! https://github.com/html5lib/html5lib-tests
+
+#[path = "html5lib/json.rs"]
+mod json;
+
+use json::Json;
+use saba_core::renderer::html::parser::HtmlParser;
+use saba_core::renderer::html::token::HtmlToken;
+use saba_core::renderer::html::token::HtmlTokenizer;
+use saba_core::utils::convert_dom_to_html5lib_string;
+use std::collections::BTreeMap;
+use std::panic::catch_unwind;
+use std::panic::AssertUnwindSafe;
+
+/// (ファイル名, 中身, 最低限通るケースの数)
+const TOKENIZER_TESTS: &[(&str, &str, usize)] = &[(
+    "test1.test",
+    include_str!("html5lib/tokenizer/test1.test"),
+    58,
+)];
+
+const TREE_CONSTRUCTION_TESTS: &[(&str, &str, usize)] = &[
+    (
+        "tests1.dat",
+        include_str!("html5lib/tree-construction/tests1.dat"),
+        14,
+    ),
+    (
+        "tables01.dat",
+        include_str!("html5lib/tree-construction/tables01.dat"),
+        7,
+    ),
+];
+
+/// ファイルごとの結果
+#[derive(Debug, Default)]
+struct Summary {
+    passed: usize,
+    failed: Vec<String>,
+    skipped: usize,
+}
+
+impl Summary {
+    fn report(&self, name: &str) {
+        let total = self.passed + self.failed.len();
+        println!(
+            "{}: {}/{} passed ({:.1}%), {} skipped",
+            name,
+            self.passed,
+            total,
+            self.passed as f64 * 100.0 / total.max(1) as f64,
+            self.skipped
+        );
+        for failed in &self.failed {
+            println!("  failed: {}", failed);
+        }
+    }
+}
+
+/// トークンの列を、html5lib-testsのoutputと同じ形式にする。続く文字は1つの"Character"にまとめる
+fn tokenize(input: &str) -> (Json, Vec<String>) {
+    let mut tokenizer = HtmlTokenizer::new(input.to_string());
+    let mut output = Vec::new();
+    let mut text = String::new();
+    let str = |s: &str| Json::String(s.to_string());
+    for token in tokenizer.by_ref() {
+        if let HtmlToken::Text(ref run) = token {
+            text.push_str(run);
+            continue;
+        }
+        if !text.is_empty() {
+            output.push(Json::Array(vec![str("Character"), str(&text)]));
+            text.clear();
+        }
+        match token {
+            HtmlToken::StartTag {
+                tag,
+                self_closing,
+                attributes,
+            } => {
+                let mut map = BTreeMap::new();
+                for a in attributes {
+                    map.entry(a.name()).or_insert(Json::String(a.value()));
+                }
+                let mut start_tag = vec![str("StartTag"), str(&tag), Json::Object(map)];
+                if self_closing {
+                    start_tag.push(Json::Bool(true));
+                }
+                output.push(Json::Array(start_tag));
+            }
+            HtmlToken::EndTag { tag } => output.push(Json::Array(vec![str("EndTag"), str(&tag)])),
+            HtmlToken::Comment(data) => output.push(Json::Array(vec![str("Comment"), str(&data)])),
+            HtmlToken::Doctype {
+                name,
+                public_id,
+                system_id,
+                force_quirks,
+            } => {
+                let optional = |s: Option<String>| s.map_or(Json::Null, Json::String);
+                output.push(Json::Array(vec![
+                    str("DOCTYPE"),
+                    optional(name),
+                    optional(public_id),
+                    optional(system_id),
+                    Json::Bool(!force_quirks),
+                ]));
+            }
+            HtmlToken::Eof => break,
+            HtmlToken::Text(_) => unreachable!(),
+        }
+    }
+    if !text.is_empty() {
+        output.push(Json::Array(vec![str("Character"), str(&text)]));
+    }
+    let errors = tokenizer
+        .errors()
+        .iter()
+        .map(|e| e.kind().code().to_string())
+        .collect();
+    (Json::Array(output), errors)
+}
+
+/// トークンの列と、解釈の失敗の名前の列が両方とも一致したケースを通ったものとする。
+/// 失敗の位置は比べない
+fn run_tokenizer_tests(content: &str) -> Summary {
+    let mut summary = Summary::default();
+    let root = Json::parse(content).expect("fixture should be valid JSON");
+    let tests = root
+        .get("tests")
+        .and_then(|t| t.as_array())
+        .expect("fixture should have tests");
+    for test in tests {
+        let description = test.get("description").and_then(|d| d.as_str());
+        let description = description.unwrap_or("(no description)").to_string();
+        // Data状態以外から始めるケースと、文字をエスケープし直したケースはまだ扱わない
+        let data_state_only = match test.get("initialStates").and_then(|s| s.as_array()) {
+            Some(states) => states == &vec![Json::String("Data state".to_string())],
+            None => true,
+        };
+        if !data_state_only
+            || test.get("doubleEscaped").is_some()
+            || test.get("lastStartTag").is_some()
+        {
+            summary.skipped += 1;
+            continue;
+        }
+
+        let input = test.get("input").and_then(|i| i.as_str()).unwrap_or("");
+        let expected_errors: Vec<String> = test
+            .get("errors")
+            .and_then(|e| e.as_array())
+            .map(|errors| {
+                errors
+                    .iter()
+                    .filter_map(|e| e.get("code").and_then(|c| c.as_str()))
+                    .map(|c| c.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        match catch_unwind(|| tokenize(input)) {
+            Ok((output, errors))
+                if Some(&output) == test.get("output") && errors == expected_errors =>
+            {
+                summary.passed += 1
+            }
+            _ => summary.failed.push(description),
+        }
+    }
+    summary
+}
+
+/// tree-constructionのファイルの1つのケース
+#[derive(Debug, Default)]
+struct TreeConstructionTest {
+    data: Vec<String>,
+    document: Vec<String>,
+    /// 要素の中身として解釈するケース。HtmlParserは文書の全体しか解釈しない
+    fragment: bool,
+    /// スクリプトを有効にして解釈するケース。<noscript>の扱いが変わる
+    script_on: bool,
+}
+
+/// "#data"から始まる節の並びを、ケースごとに分ける。
+/// テキストノードは複数行になることがあるので、"#"で始まる既知の見出しだけを節の区切りにする
+fn parse_dat(content: &str) -> Vec<TreeConstructionTest> {
+    let mut tests: Vec<TreeConstructionTest> = Vec::new();
+    let mut section = "";
+    for line in content.lines() {
+        match line {
+            "#data" => {
+                tests.push(TreeConstructionTest::default());
+                section = "#data";
+                continue;
+            }
+            "#errors" | "#new-errors" | "#document" => {
+                section = line;
+                continue;
+            }
+            "#document-fragment" => {
+                section = line;
+                if let Some(test) = tests.last_mut() {
+                    test.fragment = true;
+                }
+                continue;
+            }
+            "#script-on" | "#script-off" => {
+                if let Some(test) = tests.last_mut() {
+                    test.script_on = line == "#script-on";
+                }
+                continue;
+            }
+            _ => {}
+        }
+        let test = match tests.last_mut() {
+            Some(test) => test,
+            None => continue,
+        };
+        match section {
+            "#data" => test.data.push(line.to_string()),
+            "#document" => test.document.push(line.to_string()),
+            _ => {}
+        }
+    }
+
+    // 次のケースとの間の空行は、ツリーに含めない
+    for test in &mut tests {
+        while test.document.last().is_some_and(|l| l.is_empty()) {
+            test.document.pop();
+        }
+    }
+    tests
+}
+
+fn run_tree_construction_tests(content: &str) -> Summary {
+    let mut summary = Summary::default();
+    for test in parse_dat(content) {
+        if test.fragment || test.script_on {
+            summary.skipped += 1;
+            continue;
+        }
+        let data = test.data.join("\n");
+        let expected = test.document.join("\n");
+        let actual = catch_unwind(AssertUnwindSafe(|| {
+            let window = HtmlParser::new(HtmlTokenizer::new(data.clone())).construct_tree();
+            let document = window.borrow().document();
+            convert_dom_to_html5lib_string(&document)
+        }));
+        match actual {
+            Ok(actual) if actual == expected => summary.passed += 1,
+            _ => summary.failed.push(data.escape_debug().to_string()),
+        }
+    }
+    summary
+}
+
+#[test]
+fn html5lib_tokenizer() {
+    for (name, content, minimum) in TOKENIZER_TESTS {
+        let summary = run_tokenizer_tests(content);
+        summary.report(name);
+        assert!(
+            summary.passed >= *minimum,
+            "{}: only {} cases passed, expected at least {}",
+            name,
+            summary.passed,
+            minimum
+        );
+    }
+}
+
+#[test]
+fn html5lib_tree_construction() {
+    for (name, content, minimum) in TREE_CONSTRUCTION_TESTS {
+        let summary = run_tree_construction_tests(content);
+        summary.report(name);
+        assert!(
+            summary.passed >= *minimum,
+            "{}: only {} cases passed, expected at least {}",
+            name,
+            summary.passed,
+            minimum
+        );
+    }
+}
@@ -3,86 +3,82 @@
 
 extern crate alloc;
 
-use crate::alloc::string::ToString;
-use alloc::format;
+use alloc::boxed::Box;
 use alloc::rc::Rc;
 use alloc::string::String;
+use alloc::vec::Vec;
 use core::cell::RefCell;
 use net_wasabi::http::HttpClient;
+use noli::prelude::SystemApi;
+use noli::sys::wasabi::Api;
 use noli::*;
 use saba_core::browser::Browser;
 use saba_core::error::Error;
+use saba_core::http::Header;
 use saba_core::http::HttpResponse;
-use saba_core::url::Url;
-use ui_wasabi::app::WasabiUI;
+use saba_core::permission::Permission;
+use saba_core::permission::PermissionState;
+use saba_core::platform::LogLevel;
+use saba_core::platform::Logger;
+use saba_core::platform::NoPanicGuard;
+use ui_wasabi::window_manager::WindowManager;
 
-fn handle_url(url: String) -> Result<HttpResponse, Error> {
-    // URLを解釈する
-    let parsed_url = match Url::new(url.to_string()).parse() {
-        Ok(url) => url,
-        Err(e) => {
-            return Err(Error::UnexpectedInput(format!(
-                "input html is not supported: {:?}",
-                e
-            )));
-        }
-    };
+/// `headers`はキャッシュの再検証のために追加するヘッダ
+fn handle_url(
+    client: &HttpClient,
+    browser: &Rc<RefCell<Browser>>,
+    url: String,
+    headers: Vec<Header>,
+) -> Result<HttpResponse, Error> {
+    // Browserに設定されているプロキシとヘッダを使う
+    client.set_proxy(browser.borrow().proxy());
+    client.set_config(browser.borrow().config());
 
-    // HTTPリクエストを送信する
-    let client = HttpClient::new();
-    let response = match client.get(
-        parsed_url.host(),
-        parsed_url.port().parse::<u16>().expect(&format!(
-            "port number should be u16 but got {}",
-            parsed_url.port()
-        )),
-        parsed_url.path(),
-    ) {
-        Ok(res) => {
-            // HTTPレスポンスのステータスコードが302のとき、転送する（リダイレクト）
-            if res.status_code() == 302 {
-                let location = match res.header_value("Location") {
-                    Ok(value) => value,
-                    Err(_) => return Ok(res),
-                };
-                let redirect_parsed_url = Url::new(location);
+    // HTTPリクエストを送信する。リダイレクトはHttpClientがたどる。
+    // 404や500のようなエラーのレスポンスもエラーにせずに返し、ページとして表示する
+    client.fetch_with_headers(url, headers)
+}
 
-                let redirect_res = match client.get(
-                    redirect_parsed_url.host(),
-                    redirect_parsed_url.port().parse::<u16>().expect(&format!(
-                        "port number should be u16 but got {}",
-                        parsed_url.port()
-                    )),
-                    redirect_parsed_url.path(),
-                ) {
-                    Ok(res) => res,
-                    Err(e) => return Err(Error::Network(format!("{:?}", e))),
-                };
+/// ブラウザのログをwasabi OSのコンソールに出力する
+struct ConsoleLogger;
 
-                redirect_res
-            } else {
-                res
-            }
-        }
-        Err(e) => {
-            return Err(Error::Network(format!(
-                "failed to get http response: {:?}",
-                e
-            )))
-        }
-    };
-    Ok(response)
+impl Logger for ConsoleLogger {
+    fn log(&self, level: LogLevel, message: &str) {
+        println!("[{}] {}", level.name(), message);
+    }
 }
 
 fn main() -> u64 {
     // Browser構造体を初期化
     let browser = Browser::new();
+    // wasabi OSには時計がないので、Clockは設定しない
+    browser.borrow_mut().set_logger(Rc::new(ConsoleLogger));
+    // CLIのCatchUnwindGuardはstd::panic::catch_unwindでパニックから戻るが、wasabi OSのアプリは
+    // no_stdで、パニックするとnoliのpanic_handlerがアプリを終了させる。巻き戻しの仕組みがないので
+    // パニックを捕まえることはできず、NoPanicGuardを使う。ページの内容が原因のパニックは、
+    // パーサーの深さの上限やスクリプトのウォッチドッグのように、エラーとして扱うことで防ぐ
+    browser.borrow_mut().set_panic_guard(Rc::new(NoPanicGuard));
+    // Cookieとスクリプトは、オリジンごとに初めて使うときにポップアップで尋ねる。
+    // wasabi OSにはファイルシステムがないので、決めた権限はブラウザを閉じるまでしか残らない
+    for permission in [Permission::Cookies, Permission::JavaScript] {
+        browser
+            .borrow()
+            .set_default_permission(permission, PermissionState::Ask);
+    }
+
+    // 最初のウィンドウを開く。リンクを中クリックすると、同じBrowserを共有する新しいウィンドウを開く
+    let ui = Rc::new(RefCell::new(WindowManager::new(browser.clone())));
 
-    // WasabiUI構造体を初期化
-    let ui = Rc::new(RefCell::new(WasabiUI::new(browser)));
+    // 接続を再利用できるように、HttpClientはアプリ全体で1つだけ作る
+    let mut client = HttpClient::new();
+    // 応答しないサーバーを待っている間にEscキーが押されたら、読み込みを取り消す
+    client.set_cancel_check(Box::new(|| Api::read_key() == Some(0x1B as char)));
 
     // アプリの実行を開始
-    match ui.borrow_mut().start(handle_url) {
+    match ui
+        .borrow_mut()
+        .start(&|url, headers| handle_url(&client, &browser, url, headers))
+    {
         Ok(_) => {}
         Err(e) => {
             println!("browser fails to start {:?}", e);
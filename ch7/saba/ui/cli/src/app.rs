@@ -0,0 +1,751 @@
+use crate::render::render_text;
+use saba_core::browser::Browser;
+use saba_core::constants::CONTENT_AREA_HEIGHT;
+use saba_core::download::Download;
+use saba_core::encoding::Encoding;
+use saba_core::error::Error;
+use saba_core::http::Header;
+use saba_core::http::HttpResponse;
+use saba_core::internal_page::ABOUT_BLANK;
+use saba_core::navigation::NavigationOutcome;
+use saba_core::navigation::NavigationRequest;
+use saba_core::navigation::NavigationSource;
+use saba_core::page_observer::LoadState;
+use saba_core::page_observer::PageObserver;
+use saba_core::permission::Permission;
+use saba_core::renderer::caret::CaretMovement;
+use std::cell::RefCell;
+use std::io::BufRead;
+use std::io::Write;
+use std::rc::Rc;
+
+/// 端末に出力するページの外のメッセージ。ページを描画した後にまとめて出力する
+#[derive(Debug, Default)]
+struct CliObserver {
+    messages: RefCell<Vec<String>>,
+}
+
+impl PageObserver for CliObserver {
+    fn on_title_changed(&self, title: &str) {
+        self.messages
+            .borrow_mut()
+            .push(format!("[title] {}", title));
+    }
+
+    fn on_load_state(&self, state: LoadState) {
+        if state == LoadState::Failed {
+            self.messages
+                .borrow_mut()
+                .push("[error] failed to load the page".to_string());
+        }
+    }
+
+    fn on_console_message(&self, message: &str) {
+        self.messages
+            .borrow_mut()
+            .push(format!("[console] {}", message));
+    }
+
+    fn on_download(&self, download: &Download) {
+        self.messages.borrow_mut().push(format!(
+            "[download] {} ({} bytes)",
+            download.name(),
+            download.size()
+        ));
+    }
+
+    fn on_permission_request(&self, origin: &str, permission: Permission) {
+        self.messages.borrow_mut().push(format!(
+            "[permission] Allow {} to {}? Type \"allow {}\" or \"block {}\"",
+            origin,
+            permission.description(),
+            permission.name(),
+            permission.name()
+        ));
+    }
+
+    fn on_before_unload(&self, destination: &str) {
+        self.messages.borrow_mut().push(format!(
+            "[navigation] Leave this page for {}? Type \"leave\" or \"stay\"",
+            destination
+        ));
+    }
+}
+
+/// 標準入力から1行ずつ命令を読み、ページをテキストとして標準出力に描画するUI。
+/// WasabiのGUIがない環境でも、saba_coreを動かして確かめられる
+///
+/// 命令:
+///   URLまたは検索語  ページを開く
+///   #fragment        ページの中の要素までスクロールする
+///   j / k            1画面分スクロールする
+///   back / forward   表示中のページの履歴を戻る、または進む
+///   leave / stay     ページが離れる前に確認を求めた移動を、続けるか取り消す
+///   d                レイアウトの枠の座標の表示を切り替える
+///   F7               キャレットブラウジングを切り替える
+///   left / right / up / down
+///                    キャレットを動かす。"shift+left"のように書くと選択範囲を広げる
+///   copy             選択した文字列を出力する
+///   security         接続の安全性と、サーバー証明書の検証の結果を出力する
+///   errors           ページのHTMLを解釈したときに見つけた失敗を、位置とともに出力する
+///   viewport         ページをレイアウトした幅と、デバイスの倍率を出力する
+///   encoding         ページを読んだ文字コードを出力する
+///   encoding 名前    utf-8、shift_jis、latin1のどれかの文字コードでページを読み直す。
+///                    autoの場合はContent-Typeのcharsetに戻す
+///   toc              ページの見出しの目次を出力する
+///   toc 番号         目次の番号の見出しまでスクロールする
+///   find 文字列      文字列を含むテキストを数え、最初に見つかった位置までスクロールする。
+///                    大文字と小文字、空白の違い、文字参照で書いたかどうかは区別しない
+///   svg [ファイル名] ページ全体をSVGに書き出し、--settingsで指定したディレクトリに保存する。
+///                    ファイル名を省略した場合はpage.svgにする
+///   permissions      表示中のページのオリジンに許可している権限を出力する
+///   allow / block 権限
+///                    表示中のページのオリジンに、cookiesまたはjavascriptを許可またはブロックし、読み込み直す
+///   >式              ページのスクリプトと同じ環境で式を評価する(devtoolsのコンソール)
+///   q                終了する
+#[derive(Debug)]
+pub struct CliUI {
+    browser: Rc<RefCell<Browser>>,
+    observer: Rc<CliObserver>,
+    /// 文字の色と下線をANSIエスケープシーケンスで出力するかどうか
+    ansi: bool,
+}
+
+impl CliUI {
+    pub fn new(browser: Rc<RefCell<Browser>>, ansi: bool) -> Self {
+        let observer = Rc::new(CliObserver::default());
+        browser.borrow_mut().add_observer(observer.clone());
+
+        Self {
+            browser,
+            observer,
+            ansi,
+        }
+    }
+
+    pub fn start<R: BufRead, W: Write>(
+        &mut self,
+        input: R,
+        output: &mut W,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+    ) -> Result<(), Error> {
+        // 起動直後はabout:blankを表示する
+        self.start_navigation(
+            handle_url,
+            NavigationRequest::new(ABOUT_BLANK, NavigationSource::AddressBar),
+        );
+        self.update_ui(output)?;
+
+        for line in input.lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Err(Error::InvalidUI(format!("failed to read input: {}", e))),
+            };
+
+            let page = self.browser.borrow().current_page();
+            match line.trim() {
+                "" => continue,
+                "q" => break,
+                "j" => {
+                    page.borrow_mut().scroll_by(CONTENT_AREA_HEIGHT);
+                }
+                "k" => {
+                    page.borrow_mut().scroll_by(-CONTENT_AREA_HEIGHT);
+                }
+                "back" => {
+                    let url = page.borrow().session_history().back_url();
+                    self.go_in_history(handle_url, url, NavigationSource::Back);
+                }
+                "forward" => {
+                    let url = page.borrow().session_history().forward_url();
+                    self.go_in_history(handle_url, url, NavigationSource::Forward);
+                }
+                "leave" | "stay" => {
+                    self.confirm_navigation(handle_url, line.trim() == "leave");
+                }
+                "d" => {
+                    let enabled = page.borrow().debug_overlay();
+                    page.borrow_mut().set_debug_overlay(!enabled);
+                }
+                "F7" => {
+                    let enabled = page.borrow().caret_browsing();
+                    page.borrow_mut().set_caret_browsing(!enabled);
+                }
+                "copy" => {
+                    let text = page.borrow().selected_text();
+                    self.observer
+                        .messages
+                        .borrow_mut()
+                        .push(format!("[copy] {}", text));
+                }
+                "security" => {
+                    let state = page.borrow().security_state();
+                    let mut messages = self.observer.messages.borrow_mut();
+                    messages.push(format!(
+                        "[security] {} {}",
+                        state.indicator(),
+                        state.summary()
+                    ));
+                    messages.extend(state.details().into_iter().map(|d| format!("  {}", d)));
+                }
+                "errors" => {
+                    let errors = page.borrow().parse_errors();
+                    let mut messages = self.observer.messages.borrow_mut();
+                    if errors.is_empty() {
+                        messages.push("[parse error] none".to_string());
+                    }
+                    messages.extend(errors.iter().map(|e| format!("[parse error] {}", e)));
+                }
+                "viewport" => {
+                    let viewport = page.borrow().viewport();
+                    self.observer
+                        .messages
+                        .borrow_mut()
+                        .push(format!("[viewport] {}", viewport.summary()));
+                }
+                "encoding" => {
+                    let page = page.borrow();
+                    let selected = match page.encoding_override() {
+                        Some(_) => "selected",
+                        None => "auto",
+                    };
+                    self.observer.messages.borrow_mut().push(format!(
+                        "[encoding] {} ({})",
+                        page.encoding().name(),
+                        selected
+                    ));
+                }
+                input if input.starts_with("encoding ") => {
+                    let label = input[9..].trim();
+                    let encoding = match label {
+                        "auto" => Some(None),
+                        label => Encoding::from_label(label).map(Some),
+                    };
+                    let message = match encoding {
+                        Some(encoding) if page.borrow_mut().set_encoding_override(encoding) => {
+                            format!("[encoding] {}", page.borrow().encoding().name())
+                        }
+                        Some(_) => "[encoding] this page cannot be decoded again".to_string(),
+                        None => format!("[error] unknown encoding: {}", label),
+                    };
+                    self.observer.messages.borrow_mut().push(message);
+                }
+                "toc" => {
+                    let toc = page.borrow().table_of_contents();
+                    let mut messages = self.observer.messages.borrow_mut();
+                    if toc.is_empty() {
+                        messages.push("[toc] no headings".to_string());
+                    }
+                    for (i, entry) in toc.iter().enumerate() {
+                        messages.push(format!(
+                            "[toc] {}{}. {}",
+                            "  ".repeat(entry.level() - 1),
+                            i + 1,
+                            entry.text()
+                        ));
+                    }
+                }
+                input if input.starts_with("toc ") => {
+                    let entries = page.borrow().table_of_contents().len();
+                    let index = match input[4..].trim().parse::<usize>() {
+                        Ok(n) if (1..=entries).contains(&n) => Some(n - 1),
+                        _ => None,
+                    };
+                    if let Some(index) = index {
+                        page.borrow_mut().scroll_to_toc_entry(index);
+                    } else {
+                        self.observer
+                            .messages
+                            .borrow_mut()
+                            .push(format!("[error] no such heading: {}", &input[4..]));
+                    }
+                }
+                input if input.starts_with("find ") => {
+                    let query = input[5..].trim();
+                    let found = page.borrow().find_text(query).len();
+                    page.borrow_mut().scroll_to_text(query);
+                    self.observer
+                        .messages
+                        .borrow_mut()
+                        .push(format!("[find] {} matches for {:?}", found, query));
+                }
+                input if input == "svg" || input.starts_with("svg ") => {
+                    let message = match svg_file_name(&input[3..]) {
+                        Some(name) => {
+                            let svg = self.browser.borrow().export_svg(&name);
+                            format!("[svg] exported {} ({} bytes)", name, svg.len())
+                        }
+                        None => format!("[error] invalid file name: {}", input[3..].trim()),
+                    };
+                    self.observer.messages.borrow_mut().push(message);
+                }
+                "permissions" => {
+                    let url = page.borrow().url().unwrap_or_default();
+                    let browser = self.browser.borrow();
+                    let mut messages = self.observer.messages.borrow_mut();
+                    for permission in [Permission::Cookies, Permission::JavaScript] {
+                        messages.push(format!(
+                            "[permission] {} {}",
+                            permission.name(),
+                            browser.permission_state(&url, permission).name()
+                        ));
+                    }
+                }
+                input if permission_command(input).is_some() => {
+                    if let Some((permission, allowed)) = permission_command(input) {
+                        self.set_permission(handle_url, permission, allowed);
+                    }
+                }
+                input if input.starts_with('>') => {
+                    let entries = self.browser.borrow().evaluate_console_input(&input[1..]);
+                    let mut messages = self.observer.messages.borrow_mut();
+                    messages.extend(entries.iter().map(|e| e.line()));
+                }
+                input => match caret_movement(input) {
+                    Some((movement, extend)) => {
+                        page.borrow_mut().move_caret(movement, extend);
+                    }
+                    None => {
+                        let destination = self.browser.borrow().resolve_input(input);
+                        self.start_navigation(
+                            handle_url,
+                            NavigationRequest::new(&destination, NavigationSource::AddressBar),
+                        );
+                    }
+                },
+            }
+            // 命令を処理した後、溜まっているタスクを進め、空いた時間でリソースを先読みしてから描画する
+            self.browser.borrow().run_tasks();
+            self.browser.borrow().load_resource_hints(handle_url);
+            self.update_ui(output)?;
+        }
+
+        Ok(())
+    }
+
+    /// ページを読み込む。読み込めなかった場合は、エラーページを表示する
+    fn start_navigation(
+        &mut self,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+        request: NavigationRequest,
+    ) {
+        let destination = request.destination();
+        let result = self
+            .browser
+            .borrow()
+            .request_navigation(request, handle_url);
+        self.finish_navigation(&destination, result);
+    }
+
+    /// 履歴の前後のページに移動する。移動先がない場合は、そのことを出力する
+    fn go_in_history(
+        &mut self,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+        url: Option<String>,
+        source: NavigationSource,
+    ) {
+        match url {
+            Some(url) => self.start_navigation(handle_url, NavigationRequest::new(&url, source)),
+            None => self
+                .observer
+                .messages
+                .borrow_mut()
+                .push("[navigation] no page in the history".to_string()),
+        }
+    }
+
+    /// ページが離れる前に確認を求めた移動を、ユーザーの答えに従って続けるか取り消す
+    fn confirm_navigation(
+        &mut self,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+        proceed: bool,
+    ) {
+        let destination = match self.browser.borrow().pending_navigation() {
+            Some(request) => request.destination(),
+            None => {
+                self.observer
+                    .messages
+                    .borrow_mut()
+                    .push("[navigation] no navigation is waiting".to_string());
+                return;
+            }
+        };
+        let result = self
+            .browser
+            .borrow()
+            .confirm_navigation(proceed, handle_url);
+        self.finish_navigation(&destination, result);
+    }
+
+    /// 移動の結果を出力する。読み込めなかった場合は、エラーページを表示する
+    fn finish_navigation(&mut self, destination: &str, result: Result<NavigationOutcome, Error>) {
+        match result {
+            Ok(NavigationOutcome::Cancelled) => self
+                .observer
+                .messages
+                .borrow_mut()
+                .push("[navigation] cancelled".to_string()),
+            Ok(_) => {}
+            Err(e) => {
+                let page = self.browser.borrow().current_page();
+                page.borrow_mut().load_error_page(destination, &e);
+            }
+        }
+    }
+
+    /// 表示中のページのオリジンに権限を設定し、新しい権限でページを読み込み直す
+    fn set_permission(
+        &mut self,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+        permission: Permission,
+        allowed: bool,
+    ) {
+        let url = self
+            .browser
+            .borrow()
+            .current_page()
+            .borrow()
+            .url()
+            .unwrap_or_default();
+        if let Err(e) = self
+            .browser
+            .borrow()
+            .set_permission(&url, permission, allowed)
+        {
+            self.observer
+                .messages
+                .borrow_mut()
+                .push(format!("[error] {:?}", e));
+            return;
+        }
+        self.start_navigation(
+            handle_url,
+            NavigationRequest::new(&url, NavigationSource::Reload),
+        );
+    }
+
+    fn update_ui<W: Write>(&mut self, output: &mut W) -> Result<(), Error> {
+        let display_items = self
+            .browser
+            .borrow()
+            .current_page()
+            .borrow()
+            .display_items();
+
+        let mut text = String::new();
+        for message in self.observer.messages.borrow_mut().drain(..) {
+            text.push_str(&message);
+            text.push('\n');
+        }
+        text.push_str(&render_text(&display_items, self.ansi));
+        text.push_str("> ");
+
+        output
+            .write_all(text.as_bytes())
+            .and_then(|_| output.flush())
+            .map_err(|e| Error::InvalidUI(format!("failed to write output: {}", e)))
+    }
+}
+
+/// "allow javascript"や"block cookies"のような命令を、権限と許可するかどうかに変換する
+fn permission_command(input: &str) -> Option<(Permission, bool)> {
+    let (command, name) = input.split_once(' ')?;
+    let allowed = match command {
+        "allow" => true,
+        "block" => false,
+        _ => return None,
+    };
+    Some((Permission::from_name(name.trim())?, allowed))
+}
+
+/// svgの命令で保存するファイル名。".svg"で終わらない場合は付け足す。
+/// 保存先のディレクトリの外に書かないように、パスの区切りを含む名前はNoneを返す
+fn svg_file_name(input: &str) -> Option<String> {
+    let name = match input.trim() {
+        "" => "page.svg",
+        name => name,
+    };
+    if name.contains(['/', '\\']) || name.starts_with('.') {
+        return None;
+    }
+    if name.ends_with(".svg") {
+        Some(name.to_string())
+    } else {
+        Some(format!("{}.svg", name))
+    }
+}
+
+/// "left"や"shift+down"のような命令を、キャレットを動かす向きと選択範囲を広げるかどうかに変換する
+fn caret_movement(input: &str) -> Option<(CaretMovement, bool)> {
+    let (key, extend) = match input.strip_prefix("shift+") {
+        Some(key) => (key, true),
+        None => (input, false),
+    };
+    let movement = match key {
+        "left" => CaretMovement::Left,
+        "right" => CaretMovement::Right,
+        "up" => CaretMovement::Up,
+        "down" => CaretMovement::Down,
+        _ => return None,
+    };
+    Some((movement, extend))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use saba_core::permission::PermissionState;
+
+    fn handle_url(url: String, _headers: Vec<Header>) -> Result<HttpResponse, Error> {
+        if url != "http://example.com/index.html" {
+            return Err(Error::Network(format!("failed to connect to {}", url)));
+        }
+        HttpResponse::new(
+            "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><head><script>console.log(\"hi\");</script></head><body><p>hello</p></body></html>"
+                .to_string(),
+        )
+    }
+
+    #[test]
+    fn test_start() {
+        let mut ui = CliUI::new(Browser::new(), false);
+        let input =
+            "http://example.com/index.html\n>1 + 2\nhttp://example.com/missing.html\nq\nabout:blank\n";
+        let mut output = Vec::new();
+        ui.start(input.as_bytes(), &mut output, &handle_url)
+            .expect("failed to run the cli ui");
+
+        let output = String::from_utf8(output).expect("output should be utf-8");
+        let screens: Vec<&str> = output.split("> ").collect();
+        // about:blank、2つのページとコンソールの評価、qで終了した後の空の入力待ち
+        assert_eq!(5, screens.len());
+        assert_eq!("\n", screens[0]);
+        assert_eq!("[console] hi\nhello\n", screens[1]);
+        assert_eq!("= 3\nhello\n", screens[2]);
+        // 読み込めなかったページの代わりに、エラーページを表示する
+        assert!(screens[3].starts_with("[error] failed to load the page\n"));
+        assert!(screens[3].contains("Could not connect to the server"));
+        assert_eq!("", screens[4]);
+    }
+
+    #[test]
+    fn test_caret_browsing() {
+        let mut ui = CliUI::new(Browser::new(), false);
+        let input = "http://example.com/index.html\nF7\nshift+right\nshift+right\nright\nshift+right\ncopy\n";
+        let mut output = Vec::new();
+        ui.start(input.as_bytes(), &mut output, &handle_url)
+            .expect("failed to run the cli ui");
+
+        let output = String::from_utf8(output).expect("output should be utf-8");
+        let screens: Vec<&str> = output.split("> ").collect();
+        // "he"を選択した後、Shiftなしで動かすと選択が解除され、次の1文字だけが選択される
+        assert_eq!("[copy] l\nhello\n", screens[7]);
+    }
+
+    #[test]
+    fn test_security() {
+        let mut ui = CliUI::new(Browser::new(), false);
+        let input = "security\nhttp://example.com/index.html\nsecurity\n";
+        let mut output = Vec::new();
+        ui.start(input.as_bytes(), &mut output, &handle_url)
+            .expect("failed to run the cli ui");
+
+        let output = String::from_utf8(output).expect("output should be utf-8");
+        let screens: Vec<&str> = output.split("> ").collect();
+        assert!(screens[1].starts_with("[security] i This is a saba internal page\n"));
+        assert!(screens[3].starts_with(
+            "[security] ! Connection is not secure\n  The page was sent over HTTP without encryption.\n"
+        ));
+    }
+
+    #[test]
+    fn test_permissions() {
+        let browser = Browser::new();
+        browser
+            .borrow()
+            .set_default_permission(Permission::JavaScript, PermissionState::Ask);
+        let mut ui = CliUI::new(browser, false);
+        let input = "http://example.com/index.html\nallow js\npermissions\nblock camera\n";
+        let mut output = Vec::new();
+        ui.start(input.as_bytes(), &mut output, &handle_url)
+            .expect("failed to run the cli ui");
+
+        let output = String::from_utf8(output).expect("output should be utf-8");
+        let screens: Vec<&str> = output.split("> ").collect();
+        // 許可するまではスクリプトを実行せず、許可すると読み込み直して実行する
+        assert_eq!(
+            "[permission] Allow http://example.com to run JavaScript? Type \"allow javascript\" or \"block javascript\"\nhello\n",
+            screens[1]
+        );
+        assert_eq!("[console] hi\nhello\n", screens[2]);
+        assert_eq!(
+            "[permission] cookies allow\n[permission] javascript allow\nhello\n",
+            screens[3]
+        );
+        // 知らない権限は、検索語として扱う
+        assert!(screens[4].starts_with("[error] failed to load the page\n"));
+    }
+
+    #[test]
+    fn test_toc() {
+        let handle_url = |_url: String, _headers: Vec<Header>| {
+            let mut body = String::new();
+            for i in 0..20 {
+                body.push_str(&format!("<p>line{}</p>", i));
+            }
+            HttpResponse::new(format!(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><body><h1>Title</h1>{}<h2>Usage</h2><p>end</p></body></html>",
+                body
+            ))
+        };
+        let mut ui = CliUI::new(Browser::new(), false);
+        let input = "toc\nhttp://example.com/\ntoc\ntoc 2\ntoc 3\n";
+        let mut output = Vec::new();
+        ui.start(input.as_bytes(), &mut output, &handle_url)
+            .expect("failed to run the cli ui");
+
+        let output = String::from_utf8(output).expect("output should be utf-8");
+        let screens: Vec<&str> = output.split("> ").collect();
+        assert!(screens[1].starts_with("[toc] no headings\n"));
+        assert!(screens[3].starts_with("[toc] 1. Title\n[toc]   2. Usage\nTitle\n"));
+        // 2つ目の見出しまでスクロールする。ページの下端より先にはスクロールしない
+        assert!(screens[4].starts_with("line6\n"));
+        assert!(screens[4].ends_with("Usage\n\nend\n"));
+        assert!(screens[5].starts_with("[error] no such heading: 3\n"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let handle_url = |_url: String, _headers: Vec<Header>| {
+            HttpResponse::new(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<!DOCTYPE html>\n<html><body>\n<p>a</h1></body></html>"
+                    .to_string(),
+            )
+        };
+        let mut ui = CliUI::new(Browser::new(), false);
+        let input = "http://example.com/\nerrors\n";
+        let mut output = Vec::new();
+        ui.start(input.as_bytes(), &mut output, &handle_url)
+            .expect("failed to run the cli ui");
+
+        let output = String::from_utf8(output).expect("output should be utf-8");
+        let screens: Vec<&str> = output.split("> ").collect();
+        assert!(
+            screens[2].starts_with("[parse error] line 3, column 5: unexpected-end-tag </h1>\n")
+        );
+    }
+
+    #[test]
+    fn test_viewport() {
+        let handle_url = |_url: String, _headers: Vec<Header>| {
+            HttpResponse::new(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><head><meta name=\"viewport\" content=\"width=320\"></head><body><p>narrow</p></body></html>"
+                    .to_string(),
+            )
+        };
+        let mut ui = CliUI::new(Browser::new(), false);
+        let input = "viewport\nhttp://example.com/\nviewport\n";
+        let mut output = Vec::new();
+        ui.start(input.as_bytes(), &mut output, &handle_url)
+            .expect("failed to run the cli ui");
+
+        let output = String::from_utf8(output).expect("output should be utf-8");
+        let screens: Vec<&str> = output.split("> ").collect();
+        assert!(screens[1].starts_with("[viewport] layout width 590px, device scale factor 1\n"));
+        assert!(screens[3].starts_with("[viewport] layout width 320px, device scale factor 1\n"));
+    }
+
+    #[test]
+    fn test_encoding() {
+        let handle_url = |_url: String, _headers: Vec<Header>| {
+            let mut raw = b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n<p>caf".to_vec();
+            raw.extend_from_slice(&[0xE9]);
+            raw.extend_from_slice(b"</p>");
+            HttpResponse::from_bytes(&raw)
+        };
+        let mut ui = CliUI::new(Browser::new(), false);
+        let input = "encoding latin1
+http://example.com/
+encoding
+encoding latin1
+encoding
+encoding euc
+";
+        let mut output = Vec::new();
+        ui.start(input.as_bytes(), &mut output, &handle_url)
+            .expect("failed to run the cli ui");
+
+        let output = String::from_utf8(output).expect("output should be utf-8");
+        let screens: Vec<&str> = output.split("> ").collect();
+        assert!(screens[1].starts_with("[encoding] this page cannot be decoded again\n"));
+        assert!(screens[3].starts_with("[encoding] UTF-8 (auto)\n"));
+        assert!(screens[4].starts_with("[encoding] windows-1252\n"));
+        assert!(screens[4].contains("café"));
+        assert!(screens[5].starts_with("[encoding] windows-1252 (selected)\n"));
+        assert!(screens[6].starts_with("[error] unknown encoding: euc\n"));
+    }
+
+    #[test]
+    fn test_svg() {
+        let mut ui = CliUI::new(Browser::new(), false);
+        let input = "http://example.com/index.html\nsvg\nsvg hello\nsvg ../x\n";
+        let mut output = Vec::new();
+        ui.start(input.as_bytes(), &mut output, &handle_url)
+            .expect("failed to run the cli ui");
+
+        let output = String::from_utf8(output).expect("output should be utf-8");
+        let screens: Vec<&str> = output.split("> ").collect();
+        assert!(screens[2].starts_with("[svg] exported page.svg ("));
+        assert!(screens[3].starts_with("[svg] exported hello.svg ("));
+        assert!(screens[4].starts_with("[error] invalid file name: ../x\n"));
+    }
+
+    #[test]
+    fn test_find() {
+        let mut ui = CliUI::new(Browser::new(), false);
+        let input = "http://example.com/index.html\nfind HELLO\nfind h&eacute;llo\n";
+        let mut output = Vec::new();
+        ui.start(input.as_bytes(), &mut output, &handle_url)
+            .expect("failed to run the cli ui");
+
+        let output = String::from_utf8(output).expect("output should be utf-8");
+        let screens: Vec<&str> = output.split("> ").collect();
+        assert!(screens[2].starts_with("[find] 1 matches for \"HELLO\"\n"));
+        assert!(screens[3].starts_with("[find] 0 matches for \"h&eacute;llo\"\n"));
+    }
+
+    #[test]
+    fn test_navigation() {
+        let handle_url = |url: String, _headers: Vec<Header>| {
+            let body = match url.as_str() {
+                "http://example.com/form.html" => "<html><head><script>function leave() { return \"unsaved\"; } window.onbeforeunload = leave;</script></head><body><p>form</p></body></html>",
+                _ => "<html><body><p>next</p></body></html>",
+            };
+            HttpResponse::new(format!(
+                "HTTP/1.1 200 OK\nContent-Type: text/html\n\n{}",
+                body
+            ))
+        };
+        let mut ui = CliUI::new(Browser::new(), false);
+        let input = "http://example.com/form.html\nhttp://example.com/next.html\nstay\nhttp://example.com/next.html\nleave\nback\nforward\nleave\nforward\nstay\n";
+        let mut output = Vec::new();
+        ui.start(input.as_bytes(), &mut output, &handle_url)
+            .expect("failed to run the cli ui");
+
+        let output = String::from_utf8(output).expect("output should be utf-8");
+        let screens: Vec<&str> = output.split("> ").collect();
+        // ページが確認を求めた場合は、答えるまで表示中のページに留まる
+        assert_eq!(
+            "[navigation] Leave this page for http://example.com/next.html? Type \"leave\" or \"stay\"\nform\n",
+            screens[2]
+        );
+        assert_eq!("[navigation] cancelled\nform\n", screens[3]);
+        assert_eq!("next\n", screens[5]);
+        assert_eq!("form\n", screens[6]);
+        // 戻ったページも確認を求めるので、進むには答える必要がある
+        assert!(screens[7].starts_with("[navigation] Leave this page"));
+        assert_eq!("next\n", screens[8]);
+        assert_eq!("[navigation] no page in the history\nnext\n", screens[9]);
+        assert_eq!("[navigation] no navigation is waiting\nnext\n", screens[10]);
+    }
+}
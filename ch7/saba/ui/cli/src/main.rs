@@ -0,0 +1,322 @@
+use saba_core::browser::Browser;
+use saba_core::config::BrowserConfig;
+use saba_core::constants::MAX_REDIRECTS;
+use saba_core::crawler::crawl;
+use saba_core::error::Error;
+use saba_core::http::host_header_value;
+use saba_core::http::is_redirect;
+use saba_core::http::redirect_authorization;
+use saba_core::http::Header;
+use saba_core::http::HttpRequest;
+use saba_core::http::HttpResponse;
+use saba_core::http::Proxy;
+use saba_core::permission::Permission;
+use saba_core::permission::PermissionState;
+use saba_core::platform::Clock;
+use saba_core::platform::LogLevel;
+use saba_core::platform::Logger;
+use saba_core::platform::PanicGuard;
+use saba_core::platform::SettingsStorage;
+use saba_core::url::Url;
+use std::io::Read;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::net::TcpStream;
+use std::net::ToSocketAddrs;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::SystemTime;
+use ui_cli::app::CliUI;
+
+/// 標準ライブラリのTcpStreamでリクエストを送る。接続は1回のリクエストごとに閉じる。
+/// `proxy`が指定された場合は、プロキシに接続してabsolute-formのリクエストを送る。
+/// `authorization`が指定された場合は、Authorizationヘッダを付ける
+fn send(
+    url: &Url,
+    authorization: Option<&str>,
+    headers: &[Header],
+    proxy: Option<&Proxy>,
+    config: &BrowserConfig,
+) -> Result<HttpResponse, Error> {
+    // 標準ライブラリにはTLSの実装がないので、https://のURLは取得できない
+    if url.is_secure() {
+        return Err(Error::Network(format!(
+            "HTTPS is not supported by the CLI: {}",
+            url
+        )));
+    }
+
+    let mut request = HttpRequest::get(&url.path());
+    if let Some(authorization) = authorization {
+        request = request.header("Authorization", authorization);
+    }
+    for header in headers {
+        request = request.header(&header.name(), &header.value());
+    }
+    let mut defaults = vec![
+        Header::new(
+            "Host".to_string(),
+            host_header_value(&url.host(), url.port()),
+        ),
+        Header::new("Connection".to_string(), "close".to_string()),
+    ];
+    defaults.extend(config.default_headers());
+    let mut request = request.with_default_headers(defaults);
+
+    let (host, port) = match proxy {
+        Some(proxy) => {
+            request = request.absolute_form(&url.host(), url.port());
+            (proxy.host(), proxy.port())
+        }
+        None => (url.host(), url.port()),
+    };
+    let request = request.serialize();
+
+    // 名前解決の失敗と接続の失敗を、エラーページで区別できるように分けて行う
+    let addrs: Vec<SocketAddr> = (host.as_str(), port)
+        .to_socket_addrs()
+        .map_err(|e| Error::Dns(format!("{}: {}", host, e)))?
+        .collect();
+    let mut stream = TcpStream::connect(&addrs[..])
+        .map_err(|e| Error::Network(format!("failed to connect to {}: {}", host, e)))?;
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| Error::Network(format!("failed to send a request: {}", e)))?;
+
+    let mut received = Vec::new();
+    stream
+        .read_to_end(&mut received)
+        .map_err(|e| Error::Network(format!("failed to receive a response: {}", e)))?;
+
+    HttpResponse::from_bytes(&received)
+}
+
+/// `headers`はキャッシュの再検証のために追加するヘッダ
+fn handle_url(
+    proxy: Option<&Proxy>,
+    config: &BrowserConfig,
+    url: String,
+    headers: Vec<Header>,
+) -> Result<HttpResponse, Error> {
+    let mut url = Url::new(url).parse()?;
+    let mut headers = headers;
+    let mut authorization = url.authorization();
+
+    for _ in 0..=MAX_REDIRECTS {
+        let response = send(&url, authorization.as_deref(), &headers, proxy, config)?;
+        if !is_redirect(response.status_code()) {
+            return Ok(response);
+        }
+        let location = match response.header_value("Location") {
+            Ok(location) => location,
+            Err(_) => return Ok(response),
+        };
+        let next = Url::new(url.join(&location)).parse()?;
+        authorization = redirect_authorization(&url, &next, authorization);
+        url = next;
+        headers = Vec::new();
+    }
+
+    Err(Error::Network(format!(
+        "too many redirects (more than {})",
+        MAX_REDIRECTS
+    )))
+}
+
+/// OSの時計を使うClock
+struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Option<u64> {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .ok()
+            .map(|d| d.as_secs())
+    }
+
+    fn now_millis(&self) -> Option<u64> {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .ok()
+            .map(|d| d.as_millis() as u64)
+    }
+}
+
+/// std::panic::catch_unwindでパニックを捕まえるPanicGuard。
+/// パニックしたページはエラーページになり、次の命令を続けて受け付ける
+struct CatchUnwindGuard;
+
+impl PanicGuard for CatchUnwindGuard {
+    fn run(&self, f: &mut dyn FnMut()) -> Result<(), String> {
+        std::panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+            // panic!に渡されたメッセージは、&strかStringになっている
+            match payload.downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => match payload.downcast_ref::<String>() {
+                    Some(message) => message.clone(),
+                    None => "unknown panic".to_string(),
+                },
+            }
+        })
+    }
+}
+
+/// ブラウザのログを標準エラー出力に書く。`verbose`がfalseなら、警告とエラーだけを書く
+struct StderrLogger {
+    verbose: bool,
+}
+
+impl Logger for StderrLogger {
+    fn log(&self, level: LogLevel, message: &str) {
+        if self.verbose || level != LogLevel::Info {
+            eprintln!("[{}] {}", level.name(), message);
+        }
+    }
+}
+
+/// 設定をディレクトリに保存するSettingsStorage。キーごとに1つのファイルに書く
+struct FileSettingsStorage {
+    dir: PathBuf,
+}
+
+impl SettingsStorage for FileSettingsStorage {
+    fn load(&self, key: &str) -> Option<String> {
+        std::fs::read_to_string(self.dir.join(key)).ok()
+    }
+
+    fn save(&self, key: &str, value: &str) {
+        let result = std::fs::create_dir_all(&self.dir)
+            .and_then(|_| std::fs::write(self.dir.join(key), value));
+        if let Err(e) = result {
+            eprintln!("[warning] failed to save {}: {}", key, e);
+        }
+    }
+}
+
+/// `--name value`の形式で指定されたオプションの値
+fn option_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1).cloned())
+}
+
+/// `start`から同じオリジンのリンクをたどり、リンク切れを報告する。
+/// 問題が見つかった場合は、終了コード1で終了する
+fn run_crawler(start: &str, proxy: Option<Proxy>, config: BrowserConfig) {
+    let max_depth = match option_value("--depth").map(|d| d.parse::<usize>()) {
+        None => 2,
+        Some(Ok(depth)) => depth,
+        Some(Err(_)) => {
+            eprintln!("--depth needs a number");
+            std::process::exit(2);
+        }
+    };
+
+    let handle_url = |url, headers| handle_url(proxy.as_ref(), &config, url, headers);
+    match crawl(start, max_depth, &handle_url) {
+        Ok(report) => {
+            print!("{}", report.to_text());
+            if report.has_problems() {
+                std::process::exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("failed to crawl {}: {:?}", start, e);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn main() {
+    // --proxy host:portが指定された場合は、全てのリクエストをプロキシを通して送る
+    let proxy = match option_value("--proxy").map(|p| Proxy::parse(&p)) {
+        None => None,
+        Some(Ok(proxy)) => Some(proxy),
+        Some(Err(e)) => {
+            eprintln!("invalid --proxy: {:?}", e);
+            std::process::exit(2);
+        }
+    };
+
+    // --user-agentと--langで、サーバーに送るUser-AgentとAccept-Languageを変える
+    let mut config = BrowserConfig::new();
+    if let Some(user_agent) = option_value("--user-agent") {
+        config.set_user_agent(user_agent);
+    }
+    config.set_accept_language(option_value("--lang"));
+
+    // --crawl URLが指定された場合は、ページを表示せずにリンクを確かめる
+    if let Some(start) = option_value("--crawl") {
+        run_crawler(&start, proxy, config);
+        return;
+    }
+
+    // --plainが指定された場合は、エスケープシーケンスを使わずに出力する
+    let ansi = !std::env::args().any(|arg| arg == "--plain");
+
+    // --verboseが指定された場合は、リソースを取得するたびにログを書く
+    let verbose = std::env::args().any(|arg| arg == "--verbose");
+
+    let browser = Browser::new();
+    browser.borrow_mut().set_clock(Rc::new(SystemClock));
+    browser
+        .borrow_mut()
+        .set_panic_guard(Rc::new(CatchUnwindGuard));
+    browser
+        .borrow_mut()
+        .set_logger(Rc::new(StderrLogger { verbose }));
+    browser.borrow_mut().set_proxy(proxy);
+    // --device-scale-factor Nが指定された場合は、1CSSピクセルをNピクセルとしてページをレイアウトする
+    if let Some(factor) = option_value("--device-scale-factor") {
+        match factor.parse::<f64>() {
+            Ok(factor) if factor > 0.0 => browser.borrow_mut().set_device_scale_factor(factor),
+            _ => {
+                eprintln!("--device-scale-factor needs a positive number");
+                std::process::exit(2);
+            }
+        }
+    }
+    // --settings DIRが指定された場合は、決めた権限とユーザーのスタイルシートとSVGに書き出したページを
+    // そのディレクトリに保存する。権限とスタイルシートは次に起動したときも使う
+    if let Some(dir) = option_value("--settings") {
+        browser
+            .borrow_mut()
+            .set_settings_storage(Rc::new(FileSettingsStorage {
+                dir: PathBuf::from(dir),
+            }));
+    }
+    // --user-stylesheet FILEが指定された場合は、ファイルのCSSをユーザーのスタイルシートとして全てのページに適用する
+    if let Some(path) = option_value("--user-stylesheet") {
+        match std::fs::read_to_string(&path) {
+            Ok(css) => browser.borrow_mut().set_user_stylesheet(&css),
+            Err(e) => {
+                eprintln!("failed to read --user-stylesheet {}: {}", path, e);
+                std::process::exit(2);
+            }
+        }
+    }
+    // --ask-permissionsが指定された場合は、オリジンごとにCookieとスクリプトを許可するかを尋ねる
+    if std::env::args().any(|arg| arg == "--ask-permissions") {
+        for permission in [Permission::Cookies, Permission::JavaScript] {
+            browser
+                .borrow()
+                .set_default_permission(permission, PermissionState::Ask);
+        }
+    }
+    browser.borrow_mut().set_config(config);
+
+    let mut ui = CliUI::new(browser.clone(), ansi);
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    // Browserに設定されているプロキシとヘッダを使う
+    let handle_url = |url, headers| {
+        let browser = browser.borrow();
+        handle_url(browser.proxy().as_ref(), &browser.config(), url, headers)
+    };
+    if let Err(e) = ui.start(stdin.lock(), &mut stdout, &handle_url) {
+        eprintln!("browser fails to start {:?}", e);
+        std::process::exit(1);
+    }
+}
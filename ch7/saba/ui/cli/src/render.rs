@@ -0,0 +1,137 @@
+use saba_core::constants::CHAR_HEIGHT_WITH_PADDING;
+use saba_core::constants::CHAR_WIDTH;
+use saba_core::display_item::DisplayItem;
+use saba_core::renderer::layout::computed_style::FontStyle;
+use saba_core::renderer::layout::computed_style::FontWeight;
+use saba_core::renderer::layout::computed_style::TextDecoration;
+
+/// DisplayItemを端末に表示する文字列に変換する。
+/// テキストの位置を文字の大きさで割って行と列を決める。四角は描画しない。
+/// `ansi`がtrueなら、文字の色と太字、斜体、下線をANSIエスケープシーケンスで表す
+pub fn render_text(items: &[DisplayItem], ansi: bool) -> String {
+    // (行, 列, 文字列)
+    let mut cells: Vec<(i64, i64, String)> = Vec::new();
+    for item in items {
+        if let DisplayItem::Text {
+            text,
+            style,
+            layout_point,
+        } = item
+        {
+            let mut cell = String::new();
+            if ansi {
+                let color = style.color().code_u32();
+                cell.push_str(&format!(
+                    "\x1b[38;2;{};{};{}m",
+                    (color >> 16) & 0xff,
+                    (color >> 8) & 0xff,
+                    color & 0xff
+                ));
+                if style.font_weight() == FontWeight::Bold {
+                    cell.push_str("\x1b[1m");
+                }
+                if style.font_style() == FontStyle::Italic {
+                    cell.push_str("\x1b[3m");
+                }
+                if style.text_decoration() == TextDecoration::Underline {
+                    cell.push_str("\x1b[4m");
+                }
+            }
+            cell.push_str(text);
+            if ansi {
+                cell.push_str("\x1b[0m");
+            }
+
+            cells.push((
+                layout_point.y() / CHAR_HEIGHT_WITH_PADDING,
+                layout_point.x() / CHAR_WIDTH,
+                cell,
+            ));
+        }
+    }
+    cells.sort_by_key(|(row, column, _)| (*row, *column));
+
+    let mut output = String::new();
+    let mut current_row = 0;
+    // 現在の行に書いた文字数。エスケープシーケンスは数えない
+    let mut current_column = 0;
+    for (row, column, cell) in cells {
+        while current_row < row {
+            output.push('\n');
+            current_row += 1;
+            current_column = 0;
+        }
+
+        if current_column < column {
+            output.push_str(&" ".repeat((column - current_column) as usize));
+            current_column = column;
+        } else if current_column > 0 {
+            // 前の文字列と重なる場合は、空白を1つ挟んで続ける
+            output.push(' ');
+            current_column += 1;
+        }
+
+        current_column += visible_width(&cell);
+        output.push_str(&cell);
+    }
+    output.push('\n');
+
+    output
+}
+
+/// エスケープシーケンスを除いた文字数
+fn visible_width(cell: &str) -> i64 {
+    let mut width = 0;
+    let mut in_escape = false;
+    for c in cell.chars() {
+        match (in_escape, c) {
+            (false, '\x1b') => in_escape = true,
+            (true, 'm') => in_escape = false,
+            (true, _) => {}
+            (false, _) => width += 1,
+        }
+    }
+    width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use saba_core::http::HttpResponse;
+    use saba_core::renderer::page::Page;
+
+    fn display_items(html: &str) -> Vec<DisplayItem> {
+        let raw = "HTTP/1.1 200 OK\nContent-Type: text/html\n\n".to_string() + html;
+        let response = HttpResponse::new(raw).expect("failed to parse http response");
+        let mut page = Page::new();
+        page.receive_response(response);
+        page.display_items()
+    }
+
+    #[test]
+    fn test_render_text() {
+        let items = display_items(
+            "<html><head></head><body><p>hello <a href=\"http://example.com\">link</a></p><p>world</p></body></html>",
+        );
+        assert_eq!("hello link\nworld\n", render_text(&items, false));
+    }
+
+    #[test]
+    fn test_render_ansi() {
+        let items = display_items(
+            "<html><head></head><body><a href=\"http://example.com\">link</a></body></html>",
+        );
+        // リンクは下線付きの文字になる
+        assert_eq!(
+            "\x1b[38;2;0;0;0m\x1b[4mlink\x1b[0m\n",
+            render_text(&items, true)
+        );
+
+        // <b>の中の<i>は、太字と斜体を両方とも受け継ぐ
+        let items = display_items("<html><head></head><body><b><i>text</i></b></body></html>");
+        assert_eq!(
+            "\x1b[38;2;0;0;0m\x1b[1m\x1b[3mtext\x1b[0m\n",
+            render_text(&items, true)
+        );
+    }
+}
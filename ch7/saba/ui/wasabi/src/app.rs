@@ -1,8 +1,10 @@
 use crate::alloc::string::ToString;
-use crate::cursor::Cursor;
+use crate::modal::Modal;
+use crate::observer::UiObserver;
 use alloc::format;
 use alloc::rc::Rc;
 use alloc::string::String;
+use alloc::vec::Vec;
 use core::cell::RefCell;
 use noli::error::Result as OsResult;
 use noli::prelude::SystemApi;
@@ -15,15 +17,26 @@ use noli::window::Window;
 use saba_core::browser::Browser;
 use saba_core::constants::WHITE;
 use saba_core::constants::WINDOW_HEIGHT;
-use saba_core::constants::WINDOW_INIT_X_POS;
-use saba_core::constants::WINDOW_INIT_Y_POS;
 use saba_core::constants::WINDOW_WIDTH;
 use saba_core::constants::*;
 use saba_core::display_item::DisplayItem;
+use saba_core::encoding::Encoding;
 use saba_core::error::Error;
+use saba_core::http::Header;
 use saba_core::http::HttpResponse;
+use saba_core::navigation::NavigationRequest;
+use saba_core::navigation::NavigationSource;
+use saba_core::permission::Permission;
+use saba_core::renderer::caret::CaretMovement;
 use saba_core::renderer::layout::computed_style::FontSize;
+use saba_core::renderer::layout::computed_style::FontWeight;
 use saba_core::renderer::layout::computed_style::TextDecoration;
+use saba_core::renderer::layout::font::font_scale;
+use saba_core::renderer::layout::font::glyph_positions;
+use saba_core::renderer::layout::font::text_width;
+use saba_core::renderer::toc::TocEntry;
+use saba_core::security::SecurityState;
+use saba_core::url::split_fragment;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum InputMode {
@@ -31,139 +44,365 @@ enum InputMode {
     Editing,
 }
 
+/// ESCから始まるエスケープシーケンスとして届くキー
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SpecialKey {
+    /// 矢印キー。Shiftキーを押している場合はtrueを持つ
+    Arrow(CaretMovement, bool),
+    F7,
+}
+
+/// 1つのウィンドウのUI。ウィンドウごとにBrowserのページを1つ持ち、アドレスバーの入力やポップアップなどの
+/// 状態はウィンドウごとに持つ。Browserとオブザーバーは、WindowManagerが全てのウィンドウで共有する
 #[derive(Debug)]
 pub struct WasabiUI {
     browser: Rc<RefCell<Browser>>,
+    /// このウィンドウが表示する、Browserのページの番号
+    page_index: usize,
+    /// 画面の上での、ウィンドウの左上の位置
+    origin: (i64, i64),
     input_url: String,
     input_mode: InputMode,
     window: Window,
-    cursor: Cursor,
+    /// ページの状態の変化を受け取るオブザーバー
+    observer: Rc<UiObserver>,
+    /// 開いているポップアップ。開いている間は、入力をページに渡さない
+    modal: Option<Modal>,
+    /// 開いているポップアップが尋ねている、オリジンと権限
+    permission_prompt: Option<(String, Permission)>,
+    /// 開いているポップアップが、ページを離れてよいかを尋ねているかどうか
+    unload_prompt: bool,
+    /// 見出しの目次のサイドバーを表示しているかどうか。表示している間は、ページをサイドバーの右に表示する
+    show_toc: bool,
+    /// 中クリックされた、新しいウィンドウで開くリンクのURL
+    new_window_request: Option<String>,
 }
 
 impl WasabiUI {
-    pub fn new(browser: Rc<RefCell<Browser>>) -> Self {
-        Self {
+    /// `page_index`のページを表示するウィンドウを、画面の`origin`の位置に作る
+    pub(crate) fn new(
+        browser: Rc<RefCell<Browser>>,
+        observer: Rc<UiObserver>,
+        page_index: usize,
+        origin: (i64, i64),
+    ) -> Result<Self, Error> {
+        let window = match Window::new(
+            "saba".to_string(),
+            WHITE,
+            origin.0,
+            origin.1,
+            WINDOW_WIDTH,
+            WINDOW_HEIGHT,
+        ) {
+            Ok(window) => window,
+            Err(_) => return Err(Error::InvalidUI("failed to create a window".to_string())),
+        };
+
+        Ok(Self {
             browser,
+            page_index,
+            origin,
             input_url: String::new(),
             input_mode: InputMode::Normal,
-            window: Window::new(
-                "saba".to_string(),
-                WHITE,
-                WINDOW_INIT_X_POS,
-                WINDOW_INIT_Y_POS,
-                WINDOW_WIDTH,
-                WINDOW_HEIGHT,
-            )
-            .unwrap(),
-            cursor: Cursor::new(),
-        }
+            window,
+            observer,
+            modal: None,
+            permission_prompt: None,
+            unload_prompt: false,
+            show_toc: false,
+            new_window_request: None,
+        })
     }
 
-    pub fn start(
+    /// ツールバーを描画し、`destination`を読み込む
+    pub(crate) fn open(
         &mut self,
-        handle_url: fn(String) -> Result<HttpResponse, Error>,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+        destination: String,
     ) -> Result<(), Error> {
         self.setup()?;
+        self.start_navigation(
+            handle_url,
+            NavigationRequest::new(&destination, NavigationSource::AddressBar),
+        )
+    }
 
-        self.run_app(handle_url)?;
+    pub(crate) fn page_index(&self) -> usize {
+        self.page_index
+    }
 
-        Ok(())
+    /// 画面の座標(x, y)が、このウィンドウの上にあるかどうか
+    pub(crate) fn contains(&self, x: i64, y: i64) -> bool {
+        (self.origin.0..=self.origin.0 + WINDOW_WIDTH).contains(&x)
+            && (self.origin.1..=self.origin.1 + WINDOW_HEIGHT).contains(&y)
     }
 
-    fn run_app(
-        &mut self,
-        handle_url: fn(String) -> Result<HttpResponse, Error>,
-    ) -> Result<(), Error> {
-        loop {
-            self.handle_mouse_input(handle_url)?;
-            self.handle_key_input(handle_url)?;
-        }
+    /// 中クリックされた、新しいウィンドウで開くリンクのURLを取り出す
+    pub(crate) fn take_new_window_request(&mut self) -> Option<String> {
+        self.new_window_request.take()
+    }
+
+    /// マウスカーソルが通った部分のウィンドウを描画し直す
+    pub(crate) fn flush_area(&mut self, rect: Rect) {
+        self.window.flush_area(rect);
     }
 
-    fn handle_mouse_input(
+    /// マウスの入力を処理する。WindowManagerは、入力の前にこのウィンドウのページをBrowserで選んでおく
+    pub(crate) fn handle_mouse_input(
         &mut self,
-        handle_url: fn(String) -> Result<HttpResponse, Error>,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+        event: MouseEvent,
     ) -> Result<(), Error> {
-        if let Some(MouseEvent { button, position }) = Api::get_mouse_cursor_info() {
-            self.window.flush_area(self.cursor.rect());
-            self.cursor.set_position(position.x, position.y);
-            self.window.flush_area(self.cursor.rect());
-            self.cursor.flush();
-
-            if button.l() || button.c() || button.r() {
-                // 相対位置を計算する
-                let relative_pos = (
-                    position.x - WINDOW_INIT_X_POS,
-                    position.y - WINDOW_INIT_Y_POS,
-                );
-
-                // ウィンドウの外をクリックされたときは何もしない
-                if relative_pos.0 < 0
-                    || relative_pos.0 > WINDOW_WIDTH
-                    || relative_pos.1 < 0
-                    || relative_pos.1 > WINDOW_HEIGHT
-                {
-                    println!("button clicked OUTSIDE window: {button:?} {position:?}");
-
-                    return Ok(());
-                }
+        let MouseEvent { button, position } = event;
 
-                // ツールバーの範囲をクリックされたとき、InputModeをEditingに変更する
-                if relative_pos.1 < TOOLBAR_HEIGHT + TITLE_BAR_HEIGHT
-                    && relative_pos.1 >= TITLE_BAR_HEIGHT
-                {
-                    self.clear_address_bar()?;
-                    self.input_url = String::new();
-                    self.input_mode = InputMode::Editing;
-                    println!("button clicked in toolbar: {button:?} {position:?}");
-                    return Ok(());
-                }
+        // 相対位置を計算する
+        let relative_pos = (position.x - self.origin.0, position.y - self.origin.1);
 
-                self.input_mode = InputMode::Normal;
+        // コンテンツエリアの上にあるときは、ページにカーソルの位置を伝える。
+        // カーソルの形が変わった場合は、オブザーバーに通知が届く
+        if relative_pos.1 >= TOOLBAR_HEIGHT + TITLE_BAR_HEIGHT {
+            let page = self.browser.borrow().current_page();
+            page.borrow_mut().hovered((
+                relative_pos.0 - self.content_offset_x(),
+                relative_pos.1 - TITLE_BAR_HEIGHT - TOOLBAR_HEIGHT,
+            ));
+        }
 
-                let position_in_content_area = (
-                    relative_pos.0,
-                    relative_pos.1 - TITLE_BAR_HEIGHT - TOOLBAR_HEIGHT,
-                );
-                let page = self.browser.borrow().current_page();
-                let next_destination = page.borrow_mut().clicked(position_in_content_area);
+        if button.l() || button.c() || button.r() {
+            // ウィンドウの外をクリックされたときは何もしない
+            if relative_pos.0 < 0
+                || relative_pos.0 > WINDOW_WIDTH
+                || relative_pos.1 < 0
+                || relative_pos.1 > WINDOW_HEIGHT
+            {
+                println!("button clicked OUTSIDE window: {button:?} {position:?}");
 
-                if let Some(url) = next_destination {
-                    self.input_url = url.clone();
-                    self.update_address_bar()?;
-                    self.start_navigation(handle_url, url)?;
+                return Ok(());
+            }
+
+            // ポップアップを開いている間は、どこをクリックしてもポップアップを閉じるだけにする
+            if self.modal.is_some() {
+                return self.close_modal();
+            }
+
+            let in_toolbar = relative_pos.1 < TOOLBAR_HEIGHT + TITLE_BAR_HEIGHT
+                && relative_pos.1 >= TITLE_BAR_HEIGHT;
+
+            // アドレスバーの左端の印をクリックされたとき、接続の安全性の説明を開く
+            if in_toolbar && (72..72 + SECURITY_INDICATOR_WIDTH).contains(&relative_pos.0) {
+                let state = self
+                    .browser
+                    .borrow()
+                    .current_page()
+                    .borrow()
+                    .security_state();
+                return self.open_modal(Modal::new(state.summary(), state.details()));
+            }
+
+            // ツールバーの範囲をクリックされたとき、InputModeをEditingに変更する
+            if in_toolbar {
+                self.clear_address_bar()?;
+                self.input_url = String::new();
+                self.input_mode = InputMode::Editing;
+                println!("button clicked in toolbar: {button:?} {position:?}");
+                return Ok(());
+            }
+
+            self.input_mode = InputMode::Normal;
+
+            let position_in_content_area = (
+                relative_pos.0,
+                relative_pos.1 - TITLE_BAR_HEIGHT - TOOLBAR_HEIGHT,
+            );
+            let page = self.browser.borrow().current_page();
+
+            // 目次のサイドバーをクリックされたとき、その行の見出しまでスクロールする
+            if self.show_toc && position_in_content_area.0 < TOC_SIDEBAR_WIDTH {
+                let row = (position_in_content_area.1 - WINDOW_PADDING) / CHAR_HEIGHT_WITH_PADDING;
+                // 1行目は"Contents"の見出しなので、2行目からが目次の項目になる
+                if row >= 1 && page.borrow_mut().scroll_to_toc_entry(row as usize - 1) {
+                    self.clear_content_area()?;
+                    self.update_ui()?;
                 }
+                return Ok(());
+            }
+
+            let position_in_content_area = (
+                position_in_content_area.0 - self.content_offset_x(),
+                position_in_content_area.1,
+            );
+
+            // 中クリックされたリンクは、このウィンドウでは開かずに、新しいウィンドウで開く
+            if button.c() {
+                self.new_window_request = page.borrow().link_url_at(position_in_content_area);
+                return Ok(());
+            }
+
+            let next_destination = page.borrow_mut().clicked(position_in_content_area);
+
+            if let Some(url) = next_destination {
+                self.input_url = url.clone();
+                self.update_address_bar()?;
+                self.start_navigation(
+                    handle_url,
+                    NavigationRequest::new(&url, NavigationSource::Link),
+                )?;
             }
         }
 
         Ok(())
     }
 
-    fn handle_key_input(
+    /// キーの入力を処理する。キーはフォーカスのあるウィンドウにだけ届く
+    pub(crate) fn handle_key_input(
         &mut self,
-        handle_url: fn(String) -> Result<HttpResponse, Error>,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+        key: char,
     ) -> Result<(), Error> {
+        // ポップアップを開いている間は、いずれかのキーでポップアップを閉じるだけにする。
+        // 権限を尋ねている場合は、yキーで許可し、それ以外のキーでブロックする。
+        // ページを離れてよいかを尋ねている場合は、yキーで移動し、それ以外のキーでページに留まる
+        if self.modal.is_some() && self.unload_prompt {
+            self.modal = None;
+            self.unload_prompt = false;
+            return self.confirm_navigation(handle_url, key == 'y');
+        }
+        if self.modal.is_some() {
+            match self.permission_prompt.take() {
+                Some((origin, permission)) => {
+                    self.modal = None;
+                    let allowed = key == 'y';
+                    if let Err(e) = self
+                        .browser
+                        .borrow()
+                        .set_permission(&origin, permission, allowed)
+                    {
+                        println!("failed to set a permission for {}: {:?}", origin, e);
+                    }
+                    // 新しい権限で、表示中のページを読み込み直す
+                    let url = self.browser.borrow().current_page().borrow().url();
+                    match url {
+                        Some(url) => self.start_navigation(
+                            handle_url,
+                            NavigationRequest::new(&url, NavigationSource::Reload),
+                        )?,
+                        None => self.close_modal()?,
+                    }
+                }
+                None => self.close_modal()?,
+            }
+            return Ok(());
+        }
+
         match self.input_mode {
             InputMode::Normal => {
-                // InputModeがNormalのとき、キー入力を無視する
-                let _ = Api::read_key();
+                // InputModeがNormalのとき、jキーとkキーでフォーカスされている<iframe>をスクロールする。
+                // フォーカスされている<iframe>がない場合は、ページ全体をスクロールする。
+                // dキーでレイアウトの枠と座標の表示を、tキーで見出しの目次のサイドバーを切り替える。
+                // F7キーでキャレットブラウジングを切り替え、矢印キーでキャレットを動かし、yキーで選択した文字列をコピーする。
+                // bキーとfキーで、履歴を戻ったり進んだりする。eキーで、ページを読む文字コードを
+                // UTF-8、Shift_JIS、windows-1252の順に切り替えて読み直す。それ以外のキー入力は無視する
+                let page = self.browser.borrow().current_page();
+                let delta = match key {
+                    '\x1b' => match read_escape_sequence() {
+                        Some(SpecialKey::F7) => {
+                            let enabled = page.borrow().caret_browsing();
+                            page.borrow_mut().set_caret_browsing(!enabled);
+                            self.clear_content_area()?;
+                            return self.update_ui();
+                        }
+                        Some(SpecialKey::Arrow(movement, shift)) => {
+                            if page.borrow_mut().move_caret(movement, shift) {
+                                self.clear_content_area()?;
+                                self.update_ui()?;
+                            }
+                            return Ok(());
+                        }
+                        None => return Ok(()),
+                    },
+                    'y' if page.borrow().caret_browsing() => {
+                        // wasabi OSにはクリップボードがないので、シリアルコンソールに出力する
+                        println!("copied: {}", page.borrow().selected_text());
+                        return Ok(());
+                    }
+                    'j' => CHAR_HEIGHT_WITH_PADDING,
+                    'k' => -CHAR_HEIGHT_WITH_PADDING,
+                    'b' | 'f' => {
+                        let history = page.borrow().session_history();
+                        let (url, source) = match key {
+                            'b' => (history.back_url(), NavigationSource::Back),
+                            _ => (history.forward_url(), NavigationSource::Forward),
+                        };
+                        return match url {
+                            Some(url) => self
+                                .start_navigation(handle_url, NavigationRequest::new(&url, source)),
+                            None => Ok(()),
+                        };
+                    }
+                    'e' => {
+                        let current = page.borrow().encoding();
+                        let next = Encoding::ALL
+                            .iter()
+                            .cycle()
+                            .skip_while(|e| **e != current)
+                            .nth(1)
+                            .copied();
+                        if page.borrow_mut().set_encoding_override(next) {
+                            println!("encoding: {}", page.borrow().encoding().name());
+                            self.clear_content_area()?;
+                            self.update_ui()?;
+                        }
+                        return Ok(());
+                    }
+                    't' => {
+                        self.show_toc = !self.show_toc;
+                        self.clear_content_area()?;
+                        return self.update_ui();
+                    }
+                    'd' => {
+                        let enabled = page.borrow().debug_overlay();
+                        page.borrow_mut().set_debug_overlay(!enabled);
+                        self.clear_content_area()?;
+                        return self.update_ui();
+                    }
+                    _ => return Ok(()),
+                };
+                let scrolled = page.borrow_mut().scroll_focused_child_frame(delta)
+                    || page.borrow_mut().scroll_by(delta);
+                if scrolled {
+                    self.clear_content_area()?;
+                    self.update_ui()?;
+                }
             }
             InputMode::Editing => {
-                if let Some(c) = Api::read_key() {
-                    if c == 0x0A as char {
-                        // エンターキーが押されたので、ナビゲーションを開始する
-                        self.start_navigation(handle_url, self.input_url.clone())?;
-
-                        self.input_url = String::new();
-                        self.input_mode = InputMode::Normal;
-                    } else if c == 0x7F as char || c == 0x08 as char {
-                        // デリートキーまたはバックスペースキーが押されたので、最後の文字を削除する
-                        self.input_url.pop();
-                        self.update_address_bar()?;
+                if key == 0x0A as char {
+                    if let Some(input) = self.input_url.strip_prefix('>') {
+                        // ">"から始まる入力は、devtoolsのコンソールとしてページのスクリプトで評価する
+                        let entries = self.browser.borrow().evaluate_console_input(input);
+                        for entry in entries {
+                            println!("{}", entry.line());
+                        }
+                        self.clear_content_area()?;
+                        self.update_ui()?;
                     } else {
-                        self.input_url.push(c);
-                        self.update_address_bar()?;
+                        // エンターキーが押されたので、ナビゲーションを開始する。
+                        // URLとして解釈できない入力は、検索エンジンで検索する
+                        let destination = self.browser.borrow().resolve_input(&self.input_url);
+                        self.start_navigation(
+                            handle_url,
+                            NavigationRequest::new(&destination, NavigationSource::AddressBar),
+                        )?;
                     }
+
+                    self.input_url = String::new();
+                    self.input_mode = InputMode::Normal;
+                } else if key == 0x7F as char || key == 0x08 as char {
+                    // デリートキーまたはバックスペースキーが押されたので、最後の文字を削除する
+                    self.input_url.pop();
+                    self.update_address_bar()?;
+                } else {
+                    self.input_url.push(key);
+                    self.update_address_bar()?;
                 }
             }
         }
@@ -171,29 +410,121 @@ impl WasabiUI {
         Ok(())
     }
 
+    /// ページを読み込む。読み込めなかった場合は、ブラウザを終了せずにエラーページを表示する
     fn start_navigation(
         &mut self,
-        handle_url: fn(String) -> Result<HttpResponse, Error>,
-        destination: String,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+        request: NavigationRequest,
     ) -> Result<(), Error> {
+        let destination = request.destination();
+        let result = self.navigate(handle_url, request);
+        self.finish_navigation(&destination, result)
+    }
+
+    /// ページを離れてよいかを尋ねていた移動を、ユーザーの答えに従って続けるか取り消す
+    fn confirm_navigation(
+        &mut self,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+        proceed: bool,
+    ) -> Result<(), Error> {
+        let destination = match self.browser.borrow().pending_navigation() {
+            Some(request) => request.destination(),
+            None => return self.close_modal(),
+        };
         self.clear_content_area()?;
+        let result = self
+            .browser
+            .borrow()
+            .confirm_navigation(proceed, handle_url)
+            .and_then(|_| self.show_navigation_result());
+        self.finish_navigation(&destination, result)
+    }
 
-        match handle_url(destination) {
-            Ok(response) => {
-                let page = self.browser.borrow().current_page();
-                page.borrow_mut().receive_response(response);
+    /// 読み込みの結果に合わせて描画する
+    fn finish_navigation(
+        &mut self,
+        destination: &str,
+        result: Result<(), Error>,
+    ) -> Result<(), Error> {
+        match result {
+            Ok(()) => Ok(()),
+            // Escキーで読み込みが取り消された場合は、表示中のページをそのまま残す
+            Err(Error::Cancelled) => {
+                self.clear_content_area()?;
+                self.update_ui()
             }
+            // UIの描画に失敗した場合は、ブラウザを続けられない
+            Err(e @ Error::InvalidUI(_)) => Err(e),
             Err(e) => {
-                return Err(e);
+                println!("failed to load {}: {:?}", destination, e);
+                let page = self.browser.borrow().current_page();
+                page.borrow_mut().load_error_page(destination, &e);
+                self.clear_content_area()?;
+                self.update_ui()
             }
         }
+    }
+
+    fn navigate(
+        &mut self,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+        request: NavigationRequest,
+    ) -> Result<(), Error> {
+        // "#"から始まるURLは、ページを取得し直さずに現在のページの中をスクロールする。
+        // スクロールしなかった場合は、描画し直さない
+        if let ("", Some(fragment)) = split_fragment(&request.destination()) {
+            let page = self.browser.borrow().current_page();
+            if page.borrow_mut().scroll_to_fragment(fragment) {
+                self.clear_content_area()?;
+                self.update_ui()?;
+            }
+            return Ok(());
+        }
+
+        self.clear_content_area()?;
+
+        // キャッシュに新鮮なレスポンスがある場合は、ネットワークを使わない
+        self.browser
+            .borrow()
+            .request_navigation(request, handle_url)?;
+
+        self.show_navigation_result()
+    }
 
+    /// 読み込んだページを描画し、読み込みの途中で届いた知らせやユーザーへの問いを表示する
+    fn show_navigation_result(&mut self) -> Result<(), Error> {
         self.update_ui()?;
 
+        // ページとして表示せずにダウンロードした場合は、表示中のページの上に確認のメッセージを出す
+        if let Some(message) = self.observer.take_download_message() {
+            self.show_download_bar(&message)?;
+        }
+
+        // 読み込みの途中で権限が必要になった場合は、許可するかをポップアップで尋ねる
+        if let Some((origin, permission)) = self.observer.take_permission_request() {
+            self.open_modal(Modal::new(
+                format!("Allow {} to {}?", origin, permission.description()),
+                alloc::vec!["Press y to allow, or any other key to block.".to_string()],
+            ))?;
+            self.permission_prompt = Some((origin, permission));
+        }
+
+        // 表示中のページが離れる前に確認を求めた場合は、移動するかをポップアップで尋ねる
+        if let Some(destination) = self.observer.take_before_unload() {
+            self.open_modal(Modal::new(
+                "Leave this page? Changes you made may not be saved.".to_string(),
+                alloc::vec![
+                    format!("Destination: {}", destination),
+                    "Press y to leave, or any other key to stay.".to_string(),
+                ],
+            ))?;
+            self.unload_prompt = true;
+        }
+
         Ok(())
     }
 
-    fn update_ui(&mut self) -> Result<(), Error> {
+    pub(crate) fn update_ui(&mut self) -> Result<(), Error> {
         let display_items = self
             .browser
             .borrow()
@@ -201,6 +532,10 @@ impl WasabiUI {
             .borrow()
             .display_items();
 
+        // 目次のサイドバーを表示している間は、ページをサイドバーの右にずらし、
+        // コンテンツエリアの右端からはみ出す部分は描画しない
+        let offset_x = self.content_offset_x();
+        let right = WINDOW_PADDING + CONTENT_AREA_WIDTH;
         for item in display_items {
             match item {
                 DisplayItem::Text {
@@ -208,19 +543,56 @@ impl WasabiUI {
                     style,
                     layout_point,
                 } => {
-                    if self
-                        .window
-                        .draw_string(
-                            style.color().code_u32(),
-                            layout_point.x() + WINDOW_PADDING,
-                            layout_point.y() + WINDOW_PADDING + TOOLBAR_HEIGHT,
-                            &text,
-                            convert_font_size(style.font_size()),
-                            style.text_decoration() == TextDecoration::Underline,
-                        )
-                        .is_err()
+                    let x = layout_point.x() + WINDOW_PADDING + offset_x;
+                    let y = layout_point.y() + WINDOW_PADDING + TOOLBAR_HEIGHT;
+                    let font_size = style.font_size();
+                    // ビットマップフォントには太字がないので、BOLD_STROKE_OFFSETだけずらして重ねて描く。
+                    // 斜体は描き分けられないので、通常の文字として描く
+                    let strokes = if style.font_weight() == FontWeight::Bold {
+                        2
+                    } else {
+                        1
+                    };
+
+                    // 1文字ずつ、レイアウトで測ったのと同じ送り幅で並べる
+                    for (c, offset) in glyph_positions(&text, font_size) {
+                        if c == ' ' || x + offset + CHAR_WIDTH * font_scale(font_size) > right {
+                            continue;
+                        }
+                        for stroke in 0..strokes {
+                            if self
+                                .window
+                                .draw_string(
+                                    style.color().code_u32(),
+                                    x + offset + stroke * BOLD_STROKE_OFFSET,
+                                    y,
+                                    &c.to_string(),
+                                    convert_font_size(font_size),
+                                    false,
+                                )
+                                .is_err()
+                            {
+                                return Err(Error::InvalidUI(
+                                    "failed to draw a string".to_string(),
+                                ));
+                            }
+                        }
+                    }
+
+                    // 下線は、文字列の横幅と同じ長さで引く
+                    if style.text_decoration() == TextDecoration::Underline
+                        && self
+                            .window
+                            .fill_rect(
+                                style.color().code_u32(),
+                                x,
+                                y + CHAR_HEIGHT * font_scale(font_size) - 1,
+                                text_width(&text, font_size).min(right - x),
+                                1,
+                            )
+                            .is_err()
                     {
-                        return Err(Error::InvalidUI("failed to draw a string".to_string()));
+                        return Err(Error::InvalidUI("failed to draw an underline".to_string()));
                     }
                 }
                 DisplayItem::Rect {
@@ -228,13 +600,18 @@ impl WasabiUI {
                     layout_point,
                     layout_size,
                 } => {
+                    let x = layout_point.x() + WINDOW_PADDING + offset_x;
+                    let width = layout_size.width().min(right - x);
+                    if width <= 0 {
+                        continue;
+                    }
                     if self
                         .window
                         .fill_rect(
                             style.background_color().code_u32(),
-                            layout_point.x() + WINDOW_PADDING,
+                            x,
                             layout_point.y() + WINDOW_PADDING + TOOLBAR_HEIGHT,
-                            layout_size.width(),
+                            width,
                             layout_size.height(),
                         )
                         .is_err()
@@ -242,9 +619,44 @@ impl WasabiUI {
                         return Err(Error::InvalidUI("failed to draw a string".to_string()));
                     }
                 }
+                DisplayItem::Image {
+                    image,
+                    layout_point,
+                } => {
+                    // 同じ色が続く範囲ごとに、高さ1の四角として描く
+                    let x = layout_point.x() + WINDOW_PADDING + offset_x;
+                    let y = layout_point.y() + WINDOW_PADDING + TOOLBAR_HEIGHT;
+                    for row in 0..image.height() {
+                        for (start, width, color) in image.row_spans(row) {
+                            let span_x = x + start as i64;
+                            let width = (width as i64).min(right - span_x);
+                            if width <= 0 {
+                                break;
+                            }
+                            if self
+                                .window
+                                .fill_rect(color, span_x, y + row as i64, width, 1)
+                                .is_err()
+                            {
+                                return Err(Error::InvalidUI(
+                                    "failed to draw an image".to_string(),
+                                ));
+                            }
+                        }
+                    }
+                }
             }
         }
 
+        if self.show_toc {
+            self.draw_toc_sidebar()?;
+        }
+
+        // 移動した先のページに合わせて、アドレスバーの印とタイトルバーを描き直す
+        self.draw_security_indicator()?;
+        self.draw_subresource_badge()?;
+        self.draw_title_bar()?;
+
         self.window.flush();
 
         Ok(())
@@ -317,12 +729,14 @@ impl WasabiUI {
             ));
         }
 
-        // input_urlをアドレスバーに描画する
+        self.draw_security_indicator()?;
+
+        // input_urlを、接続の安全性の印の右に描画する
         if self
             .window
             .draw_string(
                 BLACK,
-                74,
+                74 + SECURITY_INDICATOR_WIDTH,
                 6,
                 &self.input_url,
                 StringSize::Medium,
@@ -334,12 +748,13 @@ impl WasabiUI {
                 "failed to update an address bar".to_string(),
             ));
         }
+        self.draw_subresource_badge()?;
 
         // アドレスバーの部分の画面を更新する
         self.window.flush_area(
             Rect::new(
-                WINDOW_INIT_X_POS,
-                WINDOW_INIT_Y_POS + TITLE_BAR_HEIGHT,
+                self.origin.0,
+                self.origin.1 + TITLE_BAR_HEIGHT,
                 WINDOW_WIDTH,
                 TOOLBAR_HEIGHT,
             )
@@ -360,12 +775,14 @@ impl WasabiUI {
                 "failed to clear an address bar".to_string(),
             ));
         }
+        self.draw_security_indicator()?;
+        self.draw_subresource_badge()?;
 
         // アドレスバーの部分の画面を更新する
         self.window.flush_area(
             Rect::new(
-                WINDOW_INIT_X_POS,
-                WINDOW_INIT_Y_POS + TITLE_BAR_HEIGHT,
+                self.origin.0,
+                self.origin.1 + TITLE_BAR_HEIGHT,
                 WINDOW_WIDTH,
                 TOOLBAR_HEIGHT,
             )
@@ -375,6 +792,231 @@ impl WasabiUI {
         Ok(())
     }
 
+    /// アドレスバーの左端に、表示しているページの接続の安全性を表す印を文字で描画する
+    fn draw_security_indicator(&mut self) -> Result<(), Error> {
+        let state = self
+            .browser
+            .borrow()
+            .current_page()
+            .borrow()
+            .security_state();
+        let (x, y) = (74, 6);
+        let color = security_indicator_color(&state);
+        let result = self
+            .window
+            .fill_rect(WHITE, x, y, SECURITY_INDICATOR_WIDTH - 4, CHAR_HEIGHT)
+            .and_then(|_| {
+                self.window.draw_string(
+                    color,
+                    x,
+                    y,
+                    state.indicator(),
+                    StringSize::Medium,
+                    /*underline=*/ false,
+                )
+            });
+        if result.is_err() {
+            return Err(Error::InvalidUI(
+                "failed to draw a security indicator".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// アドレスバーの右端に、表示中のページで読み込めなかったリソースの数を赤い印で描画する。
+    /// 読み込めなかった理由は、devtoolsのコンソールに記録されている
+    fn draw_subresource_badge(&mut self) -> Result<(), Error> {
+        let count = self
+            .browser
+            .borrow()
+            .current_page()
+            .borrow()
+            .subresource_errors();
+        let (x, y) = (WINDOW_WIDTH - 6 - SUBRESOURCE_BADGE_WIDTH, 6);
+        let result = if count == 0 {
+            self.window
+                .fill_rect(WHITE, x, y, SUBRESOURCE_BADGE_WIDTH, CHAR_HEIGHT)
+        } else {
+            self.window
+                .fill_rect(RED, x, y, SUBRESOURCE_BADGE_WIDTH, CHAR_HEIGHT)
+                .and_then(|_| {
+                    self.window.draw_string(
+                        WHITE,
+                        x + 2,
+                        y,
+                        &format!("!{}", count.min(99)),
+                        StringSize::Medium,
+                        /*underline=*/ false,
+                    )
+                })
+        };
+        if result.is_err() {
+            return Err(Error::InvalidUI(
+                "failed to draw a subresource error badge".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// タイトルバーに、表示中のページのタイトルを描画する。ウィンドウに描画する位置はタイトルバーの下から
+    /// 数えるので、タイトルバーは負のy座標になる。収まらない文字は省く
+    fn draw_title_bar(&mut self) -> Result<(), Error> {
+        let title = self.browser.borrow().current_page().borrow().title();
+        let text = if title.is_empty() {
+            "saba".to_string()
+        } else {
+            format!("{} - saba", title)
+        };
+        let max_chars = ((WINDOW_WIDTH - WINDOW_PADDING * 2) / CHAR_WIDTH) as usize;
+        let text: String = text.chars().take(max_chars).collect();
+
+        let y = -TITLE_BAR_HEIGHT;
+        if self
+            .window
+            .fill_rect(DARKGREY, 0, y, WINDOW_WIDTH, TITLE_BAR_HEIGHT)
+            .and_then(|_| {
+                self.window.draw_string(
+                    WHITE,
+                    WINDOW_PADDING,
+                    y + (TITLE_BAR_HEIGHT - CHAR_HEIGHT) / 2,
+                    &text,
+                    StringSize::Medium,
+                    /*underline=*/ false,
+                )
+            })
+            .is_err()
+        {
+            return Err(Error::InvalidUI("failed to draw a title bar".to_string()));
+        }
+        Ok(())
+    }
+
+    /// 目次のサイドバーを表示している間、ページを右にずらす幅
+    fn content_offset_x(&self) -> i64 {
+        if self.show_toc {
+            TOC_SIDEBAR_WIDTH
+        } else {
+            0
+        }
+    }
+
+    /// コンテンツエリアの左側に、ページの見出しの一覧を描画する。
+    /// 見出しの階層に合わせて字下げし、サイドバーに収まらない文字と行は省く
+    fn draw_toc_sidebar(&mut self) -> Result<(), Error> {
+        let toc = self
+            .browser
+            .borrow()
+            .current_page()
+            .borrow()
+            .table_of_contents();
+        if self.draw_toc_entries(&toc).is_err() {
+            return Err(Error::InvalidUI(
+                "failed to draw a table of contents".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn draw_toc_entries(&mut self, toc: &[TocEntry]) -> OsResult<()> {
+        let top = TOOLBAR_HEIGHT + 2;
+        let height = CONTENT_AREA_HEIGHT - 2;
+        self.window
+            .fill_rect(LIGHTGREY, 0, top, TOC_SIDEBAR_WIDTH - 1, height)?;
+        self.window.draw_line(
+            GREY,
+            TOC_SIDEBAR_WIDTH - 1,
+            top,
+            TOC_SIDEBAR_WIDTH - 1,
+            top + height - 1,
+        )?;
+
+        self.window.draw_string(
+            BLACK,
+            WINDOW_PADDING,
+            TOOLBAR_HEIGHT + WINDOW_PADDING,
+            "Contents",
+            StringSize::Medium,
+            /*underline=*/ true,
+        )?;
+
+        let max_rows = (height / CHAR_HEIGHT_WITH_PADDING) as usize;
+        for (i, entry) in toc.iter().take(max_rows.saturating_sub(1)).enumerate() {
+            let x = WINDOW_PADDING + (entry.level() as i64 - 1) * CHAR_WIDTH;
+            let max_chars = ((TOC_SIDEBAR_WIDTH - WINDOW_PADDING - x) / CHAR_WIDTH).max(0) as usize;
+            let text: String = entry.text().chars().take(max_chars).collect();
+            self.window.draw_string(
+                DARKGREY,
+                x,
+                TOOLBAR_HEIGHT + WINDOW_PADDING + (i as i64 + 1) * CHAR_HEIGHT_WITH_PADDING,
+                &text,
+                StringSize::Medium,
+                /*underline=*/ false,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// ポップアップを開き、ページの上に重ねて描画する
+    fn open_modal(&mut self, modal: Modal) -> Result<(), Error> {
+        if modal.draw(&mut self.window).is_err() {
+            return Err(Error::InvalidUI("failed to draw a popup".to_string()));
+        }
+        self.modal = Some(modal);
+        self.window.flush();
+        Ok(())
+    }
+
+    /// ポップアップを閉じ、隠れていたページを描画し直す
+    fn close_modal(&mut self) -> Result<(), Error> {
+        self.modal = None;
+        self.permission_prompt = None;
+        self.unload_prompt = false;
+        self.clear_content_area()?;
+        self.update_ui()
+    }
+
+    /// コンテンツエリアの下端に、ダウンロードしたことを知らせる帯を描画する。
+    /// 次にコンテンツエリアを描画し直すときに消える
+    fn show_download_bar(&mut self, message: &str) -> Result<(), Error> {
+        let y = TOOLBAR_HEIGHT + CONTENT_AREA_HEIGHT - CHAR_HEIGHT_WITH_PADDING;
+        if self
+            .window
+            .fill_rect(
+                LIGHTGREY,
+                0,
+                y,
+                CONTENT_AREA_WIDTH,
+                CHAR_HEIGHT_WITH_PADDING,
+            )
+            .is_err()
+        {
+            return Err(Error::InvalidUI(
+                "failed to draw a download bar".to_string(),
+            ));
+        }
+        if self
+            .window
+            .draw_string(
+                BLACK,
+                WINDOW_PADDING,
+                y + 2,
+                message,
+                StringSize::Medium,
+                /*underline=*/ false,
+            )
+            .is_err()
+        {
+            return Err(Error::InvalidUI(
+                "failed to draw a download message".to_string(),
+            ));
+        }
+
+        self.window.flush();
+
+        Ok(())
+    }
+
     fn clear_content_area(&mut self) -> Result<(), Error> {
         // コンテンツエリアを白く塗りつぶす
         if self
@@ -399,6 +1041,46 @@ impl WasabiUI {
     }
 }
 
+/// ESCの後に続くキーを読み、矢印キー（ESC [ A、Shiftを押している場合はESC [ 1 ; 2 A）と
+/// F7キー（ESC [ 1 8 ~）を判別する。それ以外のシーケンスはNoneを返す
+/// https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h2-PC-Style-Function-Keys
+fn read_escape_sequence() -> Option<SpecialKey> {
+    if Api::read_key()? != '[' {
+        return None;
+    }
+
+    let mut params = String::new();
+    loop {
+        match Api::read_key()? {
+            c @ ('0'..='9' | ';') if params.len() < 8 => params.push(c),
+            final_char => {
+                let shift = params == "1;2";
+                let movement = match final_char {
+                    'A' => CaretMovement::Up,
+                    'B' => CaretMovement::Down,
+                    'C' => CaretMovement::Right,
+                    'D' => CaretMovement::Left,
+                    '~' if params == "18" => return Some(SpecialKey::F7),
+                    _ => return None,
+                };
+                if params.is_empty() || shift {
+                    return Some(SpecialKey::Arrow(movement, shift));
+                }
+                return None;
+            }
+        }
+    }
+}
+
+/// 接続の安全性の印の色。証明書を検証できなかった場合は、赤で警告する
+fn security_indicator_color(state: &SecurityState) -> u32 {
+    match state {
+        SecurityState::CertificateError(_) => RED,
+        SecurityState::Insecure | SecurityState::Unverified => DARKGREY,
+        SecurityState::Internal => GREY,
+    }
+}
+
 fn convert_font_size(size: FontSize) -> StringSize {
     match size {
         FontSize::Medium => StringSize::Medium,
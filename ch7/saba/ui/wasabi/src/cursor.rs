@@ -1,6 +1,7 @@
 use noli::bitmap::bitmap_draw_rect;
 use noli::rect::Rect;
 use noli::sheet::Sheet;
+use saba_core::page_observer::CursorKind;
 
 #[derive(Debug, Eq, PartialEq)]
 pub struct Cursor {
@@ -16,6 +17,16 @@ impl Cursor {
         Self { sheet }
     }
 
+    /// リンクの上では、カーソルの色を変えて知らせる
+    pub fn set_kind(&mut self, kind: CursorKind) {
+        let color = match kind {
+            CursorKind::Default => 0xff0000,
+            CursorKind::Pointer => 0x0000ff,
+        };
+        let bitmap = self.sheet.bitmap();
+        bitmap_draw_rect(bitmap, color, 0, 0, 10, 10).expect("failed to draw a cursor");
+    }
+
     pub fn rect(&self) -> Rect {
         self.sheet.rect()
     }
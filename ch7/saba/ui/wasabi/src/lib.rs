@@ -4,3 +4,7 @@ extern crate alloc;
 
 pub mod app;
 mod cursor;
+mod modal;
+mod observer;
+mod watchdog;
+pub mod window_manager;
@@ -0,0 +1,66 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use noli::error::Result as OsResult;
+use noli::window::StringSize;
+use noli::window::Window;
+use saba_core::constants::*;
+
+/// ページの上に重ねて表示するポップアップ。開いている間はマウスとキーの入力をページに渡さず、
+/// どこかをクリックするか、いずれかのキーを押すと閉じる。
+/// 閉じた後は、ポップアップに隠れていたページを描画し直す
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Modal {
+    title: String,
+    lines: Vec<String>,
+}
+
+impl Modal {
+    pub fn new(title: String, lines: Vec<String>) -> Self {
+        Self { title, lines }
+    }
+
+    /// ポップアップの位置と大きさ。コンテンツエリアの左上に、いちばん長い行が収まる幅で表示する
+    fn rect(&self) -> (i64, i64, i64, i64) {
+        let longest = self
+            .lines
+            .iter()
+            .chain(core::iter::once(&self.title))
+            .map(|line| line.chars().count() as i64)
+            .max()
+            .unwrap_or(0);
+        let width = (longest * CHAR_WIDTH + WINDOW_PADDING * 4).min(CONTENT_AREA_WIDTH);
+        let height = (self.lines.len() as i64 + 1) * CHAR_HEIGHT_WITH_PADDING + WINDOW_PADDING * 2;
+        (WINDOW_PADDING, TOOLBAR_HEIGHT + 4, width, height)
+    }
+
+    /// 枠と背景を描画し、見出しと説明を1行ずつ並べる
+    pub fn draw(&self, window: &mut Window) -> OsResult<()> {
+        let (x, y, width, height) = self.rect();
+        window.fill_rect(DARKGREY, x, y, width, height)?;
+        window.fill_rect(WHITE, x + 1, y + 1, width - 2, height - 2)?;
+
+        let x = x + WINDOW_PADDING * 2;
+        let mut y = y + WINDOW_PADDING;
+        window.draw_string(
+            BLACK,
+            x,
+            y,
+            &self.title,
+            StringSize::Medium,
+            /*underline=*/ true,
+        )?;
+        for line in &self.lines {
+            y += CHAR_HEIGHT_WITH_PADDING;
+            window.draw_string(
+                DARKGREY,
+                x,
+                y,
+                line,
+                StringSize::Medium,
+                /*underline=*/ false,
+            )?;
+        }
+
+        Ok(())
+    }
+}
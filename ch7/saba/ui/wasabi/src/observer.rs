@@ -0,0 +1,83 @@
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use core::cell::Cell;
+use core::cell::RefCell;
+use noli::println;
+use saba_core::download::Download;
+use saba_core::page_observer::CursorKind;
+use saba_core::page_observer::LoadState;
+use saba_core::page_observer::PageObserver;
+use saba_core::permission::Permission;
+
+/// Browserから届くページの状態の変化を受け取る。
+/// 通知はページの処理の途中で届くので、画面の更新はWasabiUIが入力を処理した後にまとめて行う
+#[derive(Debug, Default)]
+pub struct UiObserver {
+    cursor: Cell<Option<CursorKind>>,
+    /// まだ画面に表示していない、ダウンロードの確認のメッセージ
+    download_message: RefCell<Option<String>>,
+    /// まだユーザーに尋ねていない、オリジンと権限の組
+    permission_requests: RefCell<VecDeque<(String, Permission)>>,
+    /// ページが離れる前に確認を求めた、保留している移動先
+    before_unload: RefCell<Option<String>>,
+}
+
+impl UiObserver {
+    /// 前回から変わったカーソルの形を取り出す
+    pub fn take_cursor_change(&self) -> Option<CursorKind> {
+        self.cursor.take()
+    }
+
+    /// 前回から新しくダウンロードしたファイルの確認のメッセージを取り出す
+    pub fn take_download_message(&self) -> Option<String> {
+        self.download_message.borrow_mut().take()
+    }
+
+    /// ユーザーに尋ねる権限を、届いた順に1つずつ取り出す
+    pub fn take_permission_request(&self) -> Option<(String, Permission)> {
+        self.permission_requests.borrow_mut().pop_front()
+    }
+
+    /// ページを離れてよいかをユーザーに尋ねる必要がある場合に、保留している移動先を取り出す
+    pub fn take_before_unload(&self) -> Option<String> {
+        self.before_unload.borrow_mut().take()
+    }
+}
+
+impl PageObserver for UiObserver {
+    fn on_title_changed(&self, title: &str) {
+        println!("title: {}", title);
+    }
+
+    fn on_load_state(&self, state: LoadState) {
+        if state == LoadState::Failed {
+            println!("failed to load the page");
+        }
+    }
+
+    fn on_console_message(&self, message: &str) {
+        println!("console: {}", message);
+    }
+
+    fn on_cursor_change(&self, cursor: CursorKind) {
+        self.cursor.set(Some(cursor));
+    }
+
+    fn on_download(&self, download: &Download) {
+        let message = format!("Downloaded {} ({} bytes)", download.name(), download.size());
+        println!("{}", message);
+        *self.download_message.borrow_mut() = Some(message);
+    }
+
+    fn on_permission_request(&self, origin: &str, permission: Permission) {
+        self.permission_requests
+            .borrow_mut()
+            .push_back((origin.to_string(), permission));
+    }
+
+    fn on_before_unload(&self, destination: &str) {
+        *self.before_unload.borrow_mut() = Some(destination.to_string());
+    }
+}
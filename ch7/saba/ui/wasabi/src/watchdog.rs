@@ -0,0 +1,71 @@
+//! 止まらないスクリプトを中断するかを、ユーザーに尋ねる。
+//! スクリプトはイベントループの中で実行されるので、尋ねている間はキーの入力を直接待つ。
+//! 答えを受け取ったらポップアップのウィンドウを閉じ、隠れていた部分をWindowManagerが描き直す
+
+use crate::modal::Modal;
+use alloc::format;
+use alloc::string::ToString;
+use core::cell::RefCell;
+use noli::prelude::SystemApi;
+use noli::println;
+use noli::rect::Rect;
+use noli::sys::wasabi::Api;
+use noli::window::Window;
+use saba_core::constants::*;
+use saba_core::platform::ScriptWatchdog;
+
+/// スクリプトを実行しているウィンドウはページの処理の途中で借用されているので、
+/// 「ページが応答しません」のポップアップは新しいウィンドウに表示する
+#[derive(Debug, Default)]
+pub struct UnresponsivePagePrompt {
+    /// 閉じたポップアップが表示されていた画面の範囲。WindowManagerが取り出して描き直す
+    closed_area: RefCell<Option<Rect>>,
+}
+
+impl UnresponsivePagePrompt {
+    /// 閉じたポップアップが表示されていた範囲を取り出す
+    pub fn take_closed_area(&self) -> Option<Rect> {
+        self.closed_area.borrow_mut().take()
+    }
+}
+
+impl ScriptWatchdog for UnresponsivePagePrompt {
+    fn should_interrupt(&self, ticks: u64) -> bool {
+        let modal = Modal::new(
+            "This page is not responding.".to_string(),
+            alloc::vec![
+                format!("The script has run {} steps.", ticks),
+                "Press s to stop the script, or any other key to wait.".to_string(),
+            ],
+        );
+        let (x, y) = (
+            WINDOW_INIT_X_POS + NEW_WINDOW_OFFSET,
+            WINDOW_INIT_Y_POS + NEW_WINDOW_OFFSET,
+        );
+        let height = TOOLBAR_HEIGHT + CHAR_HEIGHT_WITH_PADDING * 4 + WINDOW_PADDING * 4;
+        let mut window = match Window::new("saba".to_string(), WHITE, x, y, WINDOW_WIDTH, height) {
+            Ok(window) => window,
+            Err(_) => {
+                // 尋ねられない場合は、スクリプトを続ける
+                println!("failed to open the unresponsive page prompt");
+                return false;
+            }
+        };
+        let interrupt = match modal.draw(&mut window) {
+            Ok(()) => {
+                window.flush();
+                loop {
+                    if let Some(key) = Api::read_key() {
+                        break key == 's';
+                    }
+                }
+            }
+            Err(_) => false,
+        };
+
+        // 答えを受け取ったら、ポップアップのウィンドウを閉じる
+        drop(window);
+        *self.closed_area.borrow_mut() = Rect::new(x, y, WINDOW_WIDTH, height).ok();
+        interrupt
+    }
+}
@@ -0,0 +1,182 @@
+//! 複数のウィンドウ。ウィンドウごとにWasabiUIを作り、Browserとオブザーバーは全てのウィンドウで共有する。
+//! HTTPキャッシュ、Cookie、権限はBrowserが持つので、どのウィンドウで読み込んでも同じものを使う。
+//! マウスの入力はカーソルの下にあるウィンドウに、キーの入力はフォーカスのあるウィンドウに渡す
+
+use crate::app::WasabiUI;
+use crate::cursor::Cursor;
+use crate::observer::UiObserver;
+use crate::watchdog::UnresponsivePagePrompt;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use noli::prelude::SystemApi;
+use noli::sys::api::MouseEvent;
+use noli::sys::wasabi::Api;
+use saba_core::browser::Browser;
+use saba_core::constants::NEW_WINDOW_OFFSET;
+use saba_core::constants::WINDOW_INIT_X_POS;
+use saba_core::constants::WINDOW_INIT_Y_POS;
+use saba_core::error::Error;
+use saba_core::http::Header;
+use saba_core::http::HttpResponse;
+use saba_core::internal_page::ABOUT_BLANK;
+
+#[derive(Debug)]
+pub struct WindowManager {
+    browser: Rc<RefCell<Browser>>,
+    /// ページの状態の変化を受け取るオブザーバー。通知は入力を処理しているウィンドウが取り出す
+    observer: Rc<UiObserver>,
+    /// 開いているウィンドウ。後から開いたものほど手前に表示される
+    windows: Vec<WasabiUI>,
+    /// キーの入力を渡すウィンドウ。最後にクリックされたウィンドウにフォーカスがある
+    focused: usize,
+    /// マウスカーソルは、全てのウィンドウの上で1つだけ表示する
+    cursor: Cursor,
+    /// 止まらないスクリプトを中断するかを尋ねるポップアップ。閉じた後に隠れていた部分を描き直す
+    watchdog: Rc<UnresponsivePagePrompt>,
+}
+
+impl WindowManager {
+    pub fn new(browser: Rc<RefCell<Browser>>) -> Self {
+        let observer = Rc::new(UiObserver::default());
+        browser.borrow_mut().add_observer(observer.clone());
+        // 止まらないスクリプトは、ポップアップで尋ねてから中断する
+        let watchdog = Rc::new(UnresponsivePagePrompt::default());
+        browser.borrow_mut().set_script_watchdog(watchdog.clone());
+
+        let page_index = browser.borrow().active_page_index();
+        let window = WasabiUI::new(
+            browser.clone(),
+            observer.clone(),
+            page_index,
+            (WINDOW_INIT_X_POS, WINDOW_INIT_Y_POS),
+        )
+        .unwrap();
+
+        Self {
+            browser,
+            observer,
+            windows: alloc::vec![window],
+            focused: 0,
+            cursor: Cursor::new(),
+            watchdog,
+        }
+    }
+
+    pub fn start(
+        &mut self,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+    ) -> Result<(), Error> {
+        // 起動直後はabout:blankを表示する
+        self.windows[0].open(handle_url, ABOUT_BLANK.to_string())?;
+
+        self.run_app(handle_url)?;
+
+        Ok(())
+    }
+
+    fn run_app(
+        &mut self,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+    ) -> Result<(), Error> {
+        loop {
+            if let Some(event) = Api::get_mouse_cursor_info() {
+                self.handle_mouse_input(handle_url, event)?;
+            }
+            if let Some(key) = Api::read_key() {
+                self.activate(self.focused)?;
+                self.windows[self.focused].handle_key_input(handle_url, key)?;
+            }
+            // 入力を処理した後、残りの時間で先読みなどのタスクを進める
+            self.activate(self.focused)?;
+            if self.browser.borrow().run_tasks() > 0 {
+                self.windows[self.focused].update_ui()?;
+            }
+            // タスクが全て終わったら、ページの<link>が指すリソースを先読みする。
+            // 読み込めなかった場合はツールバーの印を更新する
+            if self.browser.borrow().load_resource_hints(handle_url) > 0 {
+                self.windows[self.focused].update_ui()?;
+            }
+            self.repaint_closed_prompt();
+        }
+    }
+
+    /// スクリプトを中断するかを尋ねたポップアップを閉じた場合は、その下にあったウィンドウを
+    /// 奥にあるものから順に描き直す
+    fn repaint_closed_prompt(&mut self) {
+        if let Some(area) = self.watchdog.take_closed_area() {
+            for window in self.windows.iter_mut() {
+                window.flush_area(area);
+            }
+            self.cursor.flush();
+        }
+    }
+
+    /// マウスカーソルを動かし、カーソルの下にあるウィンドウに入力を渡す。
+    /// どのウィンドウの上にもない場合は、フォーカスのあるウィンドウに渡す
+    fn handle_mouse_input(
+        &mut self,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+        event: MouseEvent,
+    ) -> Result<(), Error> {
+        let (x, y) = (event.position.x, event.position.y);
+        for window in self.windows.iter_mut() {
+            window.flush_area(self.cursor.rect());
+        }
+        self.cursor.set_position(x, y);
+
+        // 重なっている場合は、手前にあるウィンドウに渡す
+        let index = (0..self.windows.len())
+            .rev()
+            .find(|i| self.windows[*i].contains(x, y))
+            .unwrap_or(self.focused);
+        if event.button.l() || event.button.c() || event.button.r() {
+            self.focused = index;
+        }
+        self.activate(index)?;
+        self.windows[index].handle_mouse_input(handle_url, event)?;
+
+        // カーソルの形が変わった場合は、オブザーバーに通知が届く
+        if let Some(kind) = self.observer.take_cursor_change() {
+            self.cursor.set_kind(kind);
+        }
+        for window in self.windows.iter_mut() {
+            window.flush_area(self.cursor.rect());
+        }
+        self.cursor.flush();
+
+        if let Some(url) = self.windows[index].take_new_window_request() {
+            self.open_window(handle_url, url)?;
+        }
+
+        Ok(())
+    }
+
+    /// 新しいウィンドウを開き、`url`を読み込む。新しいウィンドウは、前に開いたウィンドウから少しずらして表示する
+    fn open_window(
+        &mut self,
+        handle_url: &dyn Fn(String, Vec<Header>) -> Result<HttpResponse, Error>,
+        url: String,
+    ) -> Result<(), Error> {
+        let page_index = self.browser.borrow_mut().open_page();
+        let offset = NEW_WINDOW_OFFSET * self.windows.len() as i64;
+        let window = WasabiUI::new(
+            self.browser.clone(),
+            self.observer.clone(),
+            page_index,
+            (WINDOW_INIT_X_POS + offset, WINDOW_INIT_Y_POS + offset),
+        )?;
+        self.windows.push(window);
+        self.focused = self.windows.len() - 1;
+
+        self.activate(self.focused)?;
+        self.windows[self.focused].open(handle_url, url)
+    }
+
+    /// `index`のウィンドウのページを、Browserが入力を処理するページにする
+    fn activate(&mut self, index: usize) -> Result<(), Error> {
+        let page_index = self.windows[index].page_index();
+        self.browser.borrow_mut().set_active_page(page_index)
+    }
+}
@@ -0,0 +1,54 @@
+use saba_core::platform::Clock;
+use saba_core::platform::LogLevel;
+use saba_core::platform::Logger;
+
+// wasmファイルを読み込むJavaScriptが、"env"モジュールとして渡す関数
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+    fn saba_host_log(level: u32, ptr: *const u8, len: usize);
+    fn saba_host_now() -> f64;
+}
+
+/// JavaScriptのDate.now()を使うClock。wasm32以外でビルドした場合は時刻を返さない
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HostClock;
+
+impl Clock for HostClock {
+    #[cfg(target_arch = "wasm32")]
+    fn now(&self) -> Option<u64> {
+        // Date.now()はミリ秒を返す
+        let millis = unsafe { saba_host_now() };
+        Some((millis / 1000.0) as u64)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn now(&self) -> Option<u64> {
+        None
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn now_millis(&self) -> Option<u64> {
+        Some(unsafe { saba_host_now() } as u64)
+    }
+}
+
+/// JavaScriptのconsoleにログを出力するLogger
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HostLogger;
+
+impl Logger for HostLogger {
+    #[cfg(target_arch = "wasm32")]
+    fn log(&self, level: LogLevel, message: &str) {
+        let level = match level {
+            LogLevel::Info => 0,
+            LogLevel::Warning => 1,
+            LogLevel::Error => 2,
+        };
+        unsafe { saba_host_log(level, message.as_ptr(), message.len()) };
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn log(&self, level: LogLevel, message: &str) {
+        eprintln!("[{}] {}", level.name(), message);
+    }
+}
@@ -0,0 +1,137 @@
+//! saba_coreをwasm32-unknown-unknown向けにビルドし、JavaScriptのcanvasに描画するためのUI。
+//! ネットワークはJavaScriptのfetch()に任せ、取得したHTMLを受け取ってページを描画する。
+//! 使い方はwww/index.htmlを参照
+//!
+//! JavaScriptとは、wasmのメモリの上のバイト列でやりとりする。
+//! 文字列を渡すときは、saba_allocで確保した領域に書き込み、そのポインタと長さを渡す
+
+pub mod host;
+pub mod paint;
+
+use crate::host::HostClock;
+use crate::host::HostLogger;
+use crate::paint::paint_commands;
+use saba_core::browser::Browser;
+use saba_core::error::Error;
+use saba_core::http::HttpResponse;
+use saba_core::url::split_fragment;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+thread_local! {
+    static BROWSER: Rc<RefCell<Browser>> = {
+        let browser = Browser::new();
+        browser.borrow_mut().set_clock(Rc::new(HostClock));
+        browser.borrow_mut().set_logger(Rc::new(HostLogger));
+        browser
+    };
+    /// 最後に作った描画の命令。JavaScriptはsaba_output_ptrとsaba_output_lenで読み出す
+    static OUTPUT: RefCell<Vec<u8>> = RefCell::new(Vec::new());
+}
+
+/// `url`のページとして`html`を読み込み、描画の命令を返す。
+/// <iframe>のような、ページの中から参照されるリソースは読み込まない
+pub fn load_html(browser: &Browser, url: &str, html: &str) -> Result<String, Error> {
+    // フラグメントはサーバーに送らないので、取り除いてから比べる
+    let page = browser.navigate(url, &|requested, _| {
+        if split_fragment(&requested).0 != split_fragment(url).0 {
+            return Err(Error::Network(format!(
+                "{} is not available in the wasm build",
+                requested
+            )));
+        }
+        HttpResponse::new(format!(
+            "HTTP/1.1 200 OK\nContent-Type: text/html\n\n{}",
+            html
+        ))
+    })?;
+
+    Ok(paint_commands(&page.display_items()))
+}
+
+/// JavaScriptが文字列を書き込むための領域を確保する
+#[no_mangle]
+pub extern "C" fn saba_alloc(len: usize) -> *mut u8 {
+    let mut buffer = Vec::<u8>::with_capacity(len);
+    let ptr = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+    ptr
+}
+
+/// # Safety
+/// `ptr`と`len`は、saba_allocで確保した領域でなければならない
+#[no_mangle]
+pub unsafe extern "C" fn saba_free(ptr: *mut u8, len: usize) {
+    drop(Vec::from_raw_parts(ptr, 0, len));
+}
+
+/// ページを読み込み、描画の命令を作る。成功した場合は1、失敗した場合は0を返す。
+/// 失敗した場合も、エラーページの描画の命令を作る
+///
+/// # Safety
+/// それぞれのポインタと長さは、UTF-8の文字列を書き込んだ領域を指していなければならない
+#[no_mangle]
+pub unsafe extern "C" fn saba_load(
+    url_ptr: *const u8,
+    url_len: usize,
+    html_ptr: *const u8,
+    html_len: usize,
+) -> u32 {
+    let url = String::from_utf8_lossy(std::slice::from_raw_parts(url_ptr, url_len));
+    let html = String::from_utf8_lossy(std::slice::from_raw_parts(html_ptr, html_len));
+
+    let (commands, ok) = BROWSER.with(|browser| {
+        let browser = browser.borrow();
+        match load_html(&browser, &url, &html) {
+            Ok(commands) => (commands, true),
+            Err(e) => {
+                let page = browser.current_page();
+                page.borrow_mut().load_error_page(&url, &e);
+                let commands = paint_commands(&page.borrow().display_items());
+                (commands, false)
+            }
+        }
+    });
+
+    OUTPUT.with(|output| *output.borrow_mut() = commands.into_bytes());
+    ok as u32
+}
+
+#[no_mangle]
+pub extern "C" fn saba_output_ptr() -> *const u8 {
+    OUTPUT.with(|output| output.borrow().as_ptr())
+}
+
+#[no_mangle]
+pub extern "C" fn saba_output_len() -> usize {
+    OUTPUT.with(|output| output.borrow().len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saba_load() {
+        let url = "http://example.com/";
+        let html = "<html><body><p>hello</p></body></html>";
+        let ok = unsafe { saba_load(url.as_ptr(), url.len(), html.as_ptr(), html.len()) };
+        assert_eq!(1, ok);
+
+        let output = unsafe { std::slice::from_raw_parts(saba_output_ptr(), saba_output_len()) };
+        let output = std::str::from_utf8(output).expect("output should be utf-8");
+        assert!(output.ends_with("text\t0\t0\t#000000\t1\t0\t0\t0\t40\thello\n"));
+    }
+
+    #[test]
+    fn test_load_html_with_fragment() {
+        let browser = Browser::new();
+        let output = load_html(
+            &browser.borrow(),
+            "http://example.com/#hello",
+            "<html><body><p id=\"hello\">hello</p></body></html>",
+        )
+        .expect("failed to load a page with a fragment");
+        assert!(output.contains("\thello\n"));
+    }
+}
@@ -0,0 +1,108 @@
+use saba_core::display_item::DisplayItem;
+use saba_core::renderer::layout::computed_style::FontStyle;
+use saba_core::renderer::layout::computed_style::FontWeight;
+use saba_core::renderer::layout::computed_style::TextDecoration;
+use saba_core::renderer::layout::font::font_scale;
+use saba_core::renderer::layout::font::text_width;
+
+/// DisplayItemを、JavaScriptのcanvasで描画するための命令の列に変換する。
+/// 1行に1つの命令を、タブ区切りで書く
+///
+///   rect  x  y  幅  高さ  背景色
+///   text  x  y  文字色  文字の倍率  下線(0か1)  太字(0か1)  斜体(0か1)  幅  文字列
+///
+/// 画像は、同じ色が続く範囲ごとに高さ1のrectとして書く。
+/// 色は"#rrggbb"の形式。文字の倍率は、8x16ピクセルの文字を何倍にするかを表す。
+/// 幅はレイアウトで測った文字列の横幅で、文字列と下線はこの幅に収めて描画する
+pub fn paint_commands(items: &[DisplayItem]) -> String {
+    let mut commands = String::new();
+    for item in items {
+        match item {
+            DisplayItem::Rect {
+                style,
+                layout_point,
+                layout_size,
+            } => {
+                commands.push_str(&format!(
+                    "rect\t{}\t{}\t{}\t{}\t#{:06x}\n",
+                    layout_point.x(),
+                    layout_point.y(),
+                    layout_size.width(),
+                    layout_size.height(),
+                    style.background_color().code_u32()
+                ));
+            }
+            DisplayItem::Text {
+                text,
+                style,
+                layout_point,
+            } => {
+                let underline = style.text_decoration() == TextDecoration::Underline;
+                let bold = style.font_weight() == FontWeight::Bold;
+                let italic = style.font_style() == FontStyle::Italic;
+                commands.push_str(&format!(
+                    "text\t{}\t{}\t#{:06x}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                    layout_point.x(),
+                    layout_point.y(),
+                    style.color().code_u32(),
+                    font_scale(style.font_size()),
+                    underline as u8,
+                    bold as u8,
+                    italic as u8,
+                    text_width(text, style.font_size()),
+                    // タブと改行は命令の区切りなので、空白に置き換える
+                    text.replace(['\t', '\n'], " ")
+                ));
+            }
+            DisplayItem::Image {
+                image,
+                layout_point,
+            } => {
+                for y in 0..image.height() {
+                    for (x, width, color) in image.row_spans(y) {
+                        commands.push_str(&format!(
+                            "rect\t{}\t{}\t{}\t1\t#{:06x}\n",
+                            layout_point.x() + x as i64,
+                            layout_point.y() + y as i64,
+                            width,
+                            color
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use saba_core::browser::Browser;
+    use saba_core::http::HttpResponse;
+
+    #[test]
+    fn test_paint_commands() {
+        let browser = Browser::new();
+        let page = browser
+            .borrow()
+            .navigate("http://example.com/", &|_, _| {
+                HttpResponse::new(
+                    "HTTP/1.1 200 OK\nContent-Type: text/html\n\n<html><body><h1>title</h1><a href=\"/next\">link</a></body></html>"
+                        .to_string(),
+                )
+            })
+            .expect("failed to navigate");
+
+        let commands = paint_commands(&page.display_items());
+        // <body>と<h1>の背景、<h1>の文字、下線を引いたリンクの文字
+        assert_eq!(
+            "rect\t0\t0\t590\t80\t#ffffff\n\
+             rect\t0\t0\t590\t60\t#ffffff\n\
+             text\t0\t0\t#000000\t3\t0\t0\t0\t120\ttitle\n\
+             text\t0\t60\t#000000\t1\t1\t0\t0\t32\tlink\n",
+            commands
+        );
+    }
+}
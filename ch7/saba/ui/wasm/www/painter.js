@@ -0,0 +1,90 @@
+// ui_wasm.wasmを読み込み、saba_coreが作った描画の命令をcanvasに描画する。
+// 命令の形式はui/wasm/src/paint.rsを参照
+
+const CHAR_HEIGHT = 16;
+const LOG_LEVELS = ["info", "warn", "error"];
+
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+let wasm = null;
+
+function readString(ptr, len) {
+  return decoder.decode(new Uint8Array(wasm.memory.buffer, ptr, len));
+}
+
+// 文字列をwasmのメモリに書き込み、ポインタと長さを返す
+function writeString(text) {
+  const bytes = encoder.encode(text);
+  const ptr = wasm.saba_alloc(bytes.length);
+  new Uint8Array(wasm.memory.buffer, ptr, bytes.length).set(bytes);
+  return [ptr, bytes.length];
+}
+
+// saba_coreのplatformモジュールのClockとLoggerを、JavaScriptの機能で実装する
+const imports = {
+  env: {
+    saba_host_log: (level, ptr, len) => console[LOG_LEVELS[level]](readString(ptr, len)),
+    saba_host_now: () => Date.now(),
+  },
+};
+
+function paint(commands) {
+  const canvas = document.getElementById("screen");
+  const context = canvas.getContext("2d");
+  context.fillStyle = "#ffffff";
+  context.fillRect(0, 0, canvas.width, canvas.height);
+  context.textBaseline = "top";
+
+  for (const line of commands.split("\n")) {
+    const [kind, ...args] = line.split("\t");
+    if (kind === "rect") {
+      const [x, y, width, height, color] = args;
+      context.fillStyle = color;
+      context.fillRect(Number(x), Number(y), Number(width), Number(height));
+    } else if (kind === "text") {
+      const [x, y, color, scale, underline, bold, italic, width, text] = args;
+      const size = CHAR_HEIGHT * Number(scale);
+      context.fillStyle = color;
+      const style = italic === "1" ? "italic " : "";
+      const weight = bold === "1" ? "bold " : "";
+      context.font = `${style}${weight}${size}px monospace`;
+      // レイアウトで測った幅に収まるように描画する
+      context.fillText(text, Number(x), Number(y), Number(width));
+      if (underline === "1") {
+        context.fillRect(Number(x), Number(y) + size - 1, Number(width), 1);
+      }
+    }
+  }
+}
+
+async function load(url) {
+  const resolved = new URL(url, location.href).href;
+  let html = "";
+  try {
+    html = await (await fetch(resolved)).text();
+  } catch (e) {
+    console.error(`failed to fetch ${resolved}: ${e}`);
+  }
+
+  const [urlPtr, urlLen] = writeString(resolved);
+  const [htmlPtr, htmlLen] = writeString(html);
+  wasm.saba_load(urlPtr, urlLen, htmlPtr, htmlLen);
+  wasm.saba_free(urlPtr, urlLen);
+  wasm.saba_free(htmlPtr, htmlLen);
+
+  paint(readString(wasm.saba_output_ptr(), wasm.saba_output_len()));
+}
+
+async function main() {
+  const { instance } = await WebAssembly.instantiateStreaming(fetch("ui_wasm.wasm"), imports);
+  wasm = instance.exports;
+
+  const form = document.getElementById("address");
+  form.addEventListener("submit", (event) => {
+    event.preventDefault();
+    load(document.getElementById("url").value);
+  });
+  load(document.getElementById("url").value);
+}
+
+main();